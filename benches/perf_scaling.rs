@@ -30,6 +30,17 @@ fn bench_rule_scaling(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_parse_scaling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse/program_scaling");
+    for fact_count in [500usize, 2000, 8000] {
+        let src = build_rule_chain_program(20, fact_count);
+        group.bench_with_input(BenchmarkId::from_parameter(fact_count), &src, |b, src| {
+            b.iter(|| parse_program(black_box(src)).expect("parse"))
+        });
+    }
+    group.finish();
+}
+
 fn bench_counterexample_minimization_scaling(c: &mut Criterion) {
     let mut group = c.benchmark_group("prove/minimize_counterexample");
     for premise_count in [4usize, 6, 8, 10] {
@@ -113,6 +124,6 @@ criterion_group! {
     config = Criterion::default()
         .sample_size(10)
         .measurement_time(Duration::from_millis(300));
-    targets = bench_fact_scaling, bench_rule_scaling, bench_counterexample_minimization_scaling
+    targets = bench_fact_scaling, bench_rule_scaling, bench_parse_scaling, bench_counterexample_minimization_scaling
 }
 criterion_main!(benches);
@@ -2,6 +2,9 @@
   const payload = {
   "keywordTokens": [
     "import",
+    "alias",
+    "defconst",
+    "defmacro",
     "sort",
     "data",
     "relation",
@@ -10,7 +13,16 @@
     "assert",
     "universe",
     "defn",
+    "when-profile",
+    "project",
+    "module",
+    "reference",
+    "contract",
+    "quality-gate",
     "インポート",
+    "同義語",
+    "定数",
+    "マクロ",
     "型",
     "データ",
     "関係",
@@ -19,9 +31,16 @@
     "検証",
     "宇宙",
     "関数",
+    "プロファイル限定",
+    "プロジェクト",
+    "モジュール",
+    "参照",
+    "契約",
+    "品質ゲート",
     "and",
     "not",
     "let",
+    "letfn",
     "if",
     "match"
   ],
@@ -36,26 +55,83 @@
     "true",
     "false"
   ],
+  "operators": [
+    "->",
+    "!="
+  ],
   "surfaceTags": [
+    ":別名",
+    ":alias",
+    ":正規",
+    ":canonical",
     ":コンストラクタ",
     ":constructors",
     ":ctors",
     ":引数",
     ":args",
+    ":キー",
+    ":key",
     ":項",
     ":terms",
     ":頭",
     ":head",
     ":本体",
     ":body",
+    ":スコーレム",
+    ":skolem",
     ":params",
     ":式",
     ":formula",
     ":値",
+    ":value",
     ":values",
     ":戻り",
-    ":ret"
-  ]
+    ":ret",
+    ":節",
+    ":clauses",
+    ":尺度",
+    ":measure",
+    ":減少",
+    ":decreases",
+    ":説明",
+    ":doc",
+    ":メッセージ",
+    ":message",
+    ":プロファイル",
+    ":profile",
+    ":名前",
+    ":name",
+    ":概要",
+    ":summary",
+    ":パス",
+    ":path",
+    ":カテゴリ",
+    ":category",
+    ":元",
+    ":from",
+    ":先",
+    ":to",
+    ":出典",
+    ":source",
+    ":コマンド",
+    ":command",
+    ":必須",
+    ":required"
+  ],
+  "patterns": {
+    "lineComment": ";",
+    "blockCommentBegin": "#\\|",
+    "blockCommentEnd": "\\|#",
+    "datumCommentList": "#;\\s*\\(",
+    "datumCommentAtom": "#;\\s*(?:\"(?:[^\"\\\\]|\\\\.)*\"|[^\\s()]+)?",
+    "quotedAtom": "\"(?:[^\"\\\\]|\\\\.)*\"",
+    "multiLineBegin": "\"\"\"",
+    "multiLineEnd": "\"\"\"(?!\")",
+    "rawBegin": "((?:#)+)\"",
+    "rawEnd": "\"((?:#)+)",
+    "quote": "\"",
+    "escape": "\\\\(?:\\\\|\"|n|t|r)"
+  }
 };
 
   const escapeRegex = (value) => value.replace(/[|\\{}()[\]^$+*?.]/g, "\\$&");
@@ -85,30 +161,72 @@
     const typePattern = tokenPattern(payload.typeKeywords);
     const literalPattern = tokenPattern(payload.booleanLiterals);
     const surfaceTagPattern = tokenPattern(payload.surfaceTags);
+    const dtlOperatorPattern = tokenPattern(payload.operators);
+    const patterns = payload.patterns;
+    const pattern = (source) => new RegExp(source, "u");
     const dtlNumberPattern = supportsLookbehind
       ? /(?<![^\s()])[-+]?\d+(?![^\s()])/u
       : /[-+]?\d+/u;
     const dtlVariablePattern = supportsLookbehind
       ? /(?<![^\s()])\?[^\s()";]+(?![^\s()])/u
       : /\?[^\s()";]+/u;
-    const dtlOperatorPattern = supportsLookbehind
-      ? /(?<![^\s()])->(?![^\s()])/u
-      : /->/u;
     const dtlWildcardPattern = supportsLookbehind
       ? /(?<![^\s()])_(?![^\s()])/u
       : /_/u;
 
+    const escape = { begin: pattern(patterns.escape), relevance: 0 };
+    const datumCommentBody = {
+      begin: /\(/u,
+      end: /\)/u,
+      contains: [{ begin: pattern(patterns.quotedAtom) }, "self"]
+    };
+
     return {
       name: "DTL",
       aliases: ["dtl"],
       unicodeRegex: true,
       contains: [
-        hljs.COMMENT(";", "$"),
+        {
+          className: "comment",
+          begin: pattern(patterns.blockCommentBegin),
+          end: pattern(patterns.blockCommentEnd),
+          contains: ["self"]
+        },
+        {
+          className: "comment",
+          begin: pattern(patterns.datumCommentList),
+          end: /\)/u,
+          contains: [{ begin: pattern(patterns.quotedAtom) }, datumCommentBody]
+        },
+        {
+          className: "comment",
+          begin: pattern(patterns.datumCommentAtom)
+        },
+        hljs.COMMENT(patterns.lineComment, "$"),
+        {
+          className: "string",
+          begin: pattern(patterns.multiLineBegin),
+          end: pattern(patterns.multiLineEnd),
+          contains: [escape]
+        },
+        {
+          className: "string",
+          begin: pattern(patterns.rawBegin),
+          end: pattern(patterns.rawEnd),
+          "on:begin": (match, response) => {
+            response.data.rawHashes = match[1];
+          },
+          "on:end": (match, response) => {
+            if (response.data.rawHashes !== match[1]) {
+              response.ignoreMatch();
+            }
+          }
+        },
         {
           className: "string",
-          begin: /"/u,
-          end: /"/u,
-          illegal: /\n/u
+          begin: pattern(patterns.quote),
+          end: pattern(patterns.quote),
+          contains: [escape]
         },
         {
           className: "number",
@@ -253,7 +253,7 @@
       "label": "tail position",
       "aliases": [],
       "short_tip": "式評価の最終位置で結果をそのまま返す場所。",
-      "definition": "追加計算を伴わず、呼び出し結果がそのまま関数結果になる式位置。",
+      "definition": "追加計算を伴わず、呼び出し結果がそのまま関数結果になる式位置。再帰呼び出しは tail position になくてもよく、停止性は引数の減少で判定する。",
       "category": "semantics",
       "match_mode": "exact",
       "enabled_pages": [
@@ -341,7 +341,7 @@
       "label": "E-TOTAL",
       "aliases": [],
       "short_tip": "停止性/全域性違反を示す診断コード。",
-      "definition": "非 tail 再帰、非減少再帰、相互再帰など全域性条件違反を示すエラーコード。",
+      "definition": "構造的に減少しない再帰呼び出し（相互再帰では SCC 共通の辞書式順序で減少しないもの）や、自身の再帰グループを呼ぶ停止性の尺度など、全域性条件違反を示すエラーコード。",
       "category": "diagnostics",
       "match_mode": "token",
       "enabled_pages": [
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Mirrors an editor session: whatever parsed is checked even when the rest
+// of the file is broken.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(src) = std::str::from_utf8(data) {
+        let (program, _) = dtl::parse_program_lossy(src);
+        let _ = dtl::check_program(&program);
+    }
+});
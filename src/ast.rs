@@ -1,3 +1,8 @@
+use std::collections::BTreeMap;
+use std::ops::{Index, IndexMut};
+
+use serde::{Deserialize, Serialize};
+
 use crate::diagnostics::Span;
 use crate::types::{Atom, Formula, LogicTerm, Type};
 
@@ -5,6 +10,7 @@ use crate::types::{Atom, Formula, LogicTerm, Type};
 pub struct Program {
     pub imports: Vec<ImportDecl>,
     pub aliases: Vec<AliasDecl>,
+    pub consts: Vec<ConstDecl>,
     pub sorts: Vec<SortDecl>,
     pub data_decls: Vec<DataDecl>,
     pub relations: Vec<RelationDecl>,
@@ -13,6 +19,8 @@ pub struct Program {
     pub asserts: Vec<AssertDecl>,
     pub universes: Vec<UniverseDecl>,
     pub defns: Vec<Defn>,
+    pub exprs: ExprArena,
+    pub name_uses: Vec<NameUse>,
 }
 
 impl Program {
@@ -20,6 +28,7 @@ impl Program {
         Self {
             imports: Vec::new(),
             aliases: Vec::new(),
+            consts: Vec::new(),
             sorts: Vec::new(),
             data_decls: Vec::new(),
             relations: Vec::new(),
@@ -28,8 +37,52 @@ impl Program {
             asserts: Vec::new(),
             universes: Vec::new(),
             defns: Vec::new(),
+            exprs: ExprArena::new(),
+            name_uses: Vec::new(),
         }
     }
+
+    pub fn append(&mut self, other: Program) {
+        let offset = self.exprs.append(other.exprs);
+        self.imports.extend(other.imports);
+        self.aliases.extend(other.aliases);
+        self.consts
+            .extend(other.consts.into_iter().map(|mut constant| {
+                constant.value = offset.expr(constant.value);
+                constant
+            }));
+        self.sorts.extend(other.sorts);
+        self.data_decls.extend(other.data_decls);
+        self.relations.extend(other.relations);
+        self.facts.extend(other.facts);
+        self.rules.extend(other.rules);
+        self.asserts.extend(other.asserts);
+        self.universes.extend(other.universes);
+        self.name_uses.extend(other.name_uses);
+        self.defns.extend(other.defns.into_iter().map(|mut defn| {
+            defn.body = offset.expr(defn.body);
+            defn.measure = defn.measure.map(|measure| offset.expr(measure));
+            for clause in &mut defn.clauses {
+                clause.body = offset.expr(clause.body);
+                for pattern in &mut clause.patterns {
+                    *pattern = offset.pattern(*pattern);
+                }
+            }
+            defn
+        }));
+    }
+
+    pub fn select_profiles(&mut self, active: &[String]) {
+        let selected = |profile: &Option<String>| {
+            profile
+                .as_ref()
+                .is_none_or(|profile| active.contains(profile))
+        };
+        self.facts.retain(|fact| selected(&fact.profile));
+        self.rules.retain(|rule| selected(&rule.profile));
+        self.asserts
+            .retain(|assertion| selected(&assertion.profile));
+    }
 }
 
 impl Default for Program {
@@ -38,6 +91,12 @@ impl Default for Program {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameUse {
+    pub name: String,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ImportDecl {
     pub path: String,
@@ -51,9 +110,17 @@ pub struct AliasDecl {
     pub span: Span,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstDecl {
+    pub name: String,
+    pub value: ExprId,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SortDecl {
     pub name: String,
+    pub attributes: BTreeMap<String, String>,
     pub span: Span,
 }
 
@@ -61,13 +128,24 @@ pub struct SortDecl {
 pub struct RelationDecl {
     pub name: String,
     pub arg_sorts: Vec<String>,
+    pub arg_refinements: Vec<Option<ArgRefinement>>,
+    pub key: Option<Vec<usize>>,
+    pub doc: Option<String>,
+    pub attributes: BTreeMap<String, String>,
     pub span: Span,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgRefinement {
+    pub var: String,
+    pub formula: Formula,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DataDecl {
     pub name: String,
     pub constructors: Vec<ConstructorDecl>,
+    pub attributes: BTreeMap<String, String>,
     pub span: Span,
 }
 
@@ -82,13 +160,18 @@ pub struct ConstructorDecl {
 pub struct Fact {
     pub name: String,
     pub terms: Vec<LogicTerm>,
+    pub profile: Option<String>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Rule {
     pub head: Atom,
     pub body: Formula,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skolem: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
     pub span: Span,
 }
 
@@ -97,6 +180,10 @@ pub struct AssertDecl {
     pub name: String,
     pub params: Vec<Param>,
     pub formula: Formula,
+    pub doc: Option<String>,
+    pub message: Option<String>,
+    pub attributes: BTreeMap<String, String>,
+    pub profile: Option<String>,
     pub span: Span,
 }
 
@@ -119,7 +206,18 @@ pub struct Defn {
     pub name: String,
     pub params: Vec<Param>,
     pub ret_type: Type,
-    pub body: Expr,
+    pub body: ExprId,
+    pub measure: Option<ExprId>,
+    pub clauses: Vec<Clause>,
+    pub doc: Option<String>,
+    pub attributes: BTreeMap<String, String>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    pub patterns: Vec<PatternId>,
+    pub body: ExprId,
     pub span: Span,
 }
 
@@ -141,24 +239,33 @@ pub enum Expr {
         value: bool,
         span: Span,
     },
+    Hole {
+        span: Span,
+    },
     Call {
         name: String,
-        args: Vec<Expr>,
+        name_span: Span,
+        args: Vec<ExprId>,
         span: Span,
     },
     Let {
-        bindings: Vec<(String, Expr, Span)>,
-        body: Box<Expr>,
+        bindings: Vec<(String, ExprId, Span)>,
+        body: ExprId,
+        span: Span,
+    },
+    LetFn {
+        fns: Vec<LocalFn>,
+        body: ExprId,
         span: Span,
     },
     If {
-        cond: Box<Expr>,
-        then_branch: Box<Expr>,
-        else_branch: Box<Expr>,
+        cond: ExprId,
+        then_branch: ExprId,
+        else_branch: ExprId,
         span: Span,
     },
     Match {
-        scrutinee: Box<Expr>,
+        scrutinee: ExprId,
         arms: Vec<MatchArm>,
         span: Span,
     },
@@ -171,18 +278,58 @@ impl Expr {
             | Expr::Symbol { span, .. }
             | Expr::Int { span, .. }
             | Expr::Bool { span, .. }
+            | Expr::Hole { span }
             | Expr::Call { span, .. }
             | Expr::Let { span, .. }
+            | Expr::LetFn { span, .. }
             | Expr::If { span, .. }
             | Expr::Match { span, .. } => span,
         }
     }
+
+    pub fn children(&self) -> Vec<ExprId> {
+        match self {
+            Expr::Var { .. }
+            | Expr::Symbol { .. }
+            | Expr::Int { .. }
+            | Expr::Bool { .. }
+            | Expr::Hole { .. } => Vec::new(),
+            Expr::Call { args, .. } => args.clone(),
+            Expr::Let { bindings, body, .. } => bindings
+                .iter()
+                .map(|(_, value, _)| *value)
+                .chain([*body])
+                .collect(),
+            Expr::LetFn { fns, body, .. } => fns.iter().map(|f| f.body).chain([*body]).collect(),
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+                ..
+            } => vec![*cond, *then_branch, *else_branch],
+            Expr::Match {
+                scrutinee, arms, ..
+            } => [*scrutinee]
+                .into_iter()
+                .chain(arms.iter().map(|arm| arm.body))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalFn {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub ret_type: Type,
+    pub body: ExprId,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MatchArm {
-    pub pattern: Pattern,
-    pub body: Expr,
+    pub pattern: PatternId,
+    pub body: ExprId,
     pub span: Span,
 }
 
@@ -209,7 +356,7 @@ pub enum Pattern {
     },
     Ctor {
         name: String,
-        args: Vec<Pattern>,
+        args: Vec<PatternId>,
         span: Span,
     },
 }
@@ -226,3 +373,164 @@ impl Pattern {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExprId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PatternId(u32);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExprArena {
+    exprs: Vec<Expr>,
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaOffset {
+    exprs: u32,
+    patterns: u32,
+}
+
+impl ArenaOffset {
+    pub fn expr(self, id: ExprId) -> ExprId {
+        ExprId(id.0 + self.exprs)
+    }
+
+    pub fn pattern(self, id: PatternId) -> PatternId {
+        PatternId(id.0 + self.patterns)
+    }
+}
+
+impl ExprArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn alloc(&mut self, expr: Expr) -> ExprId {
+        let id = ExprId(u32::try_from(self.exprs.len()).expect("expression arena overflow"));
+        self.exprs.push(expr);
+        id
+    }
+
+    pub fn alloc_pattern(&mut self, pattern: Pattern) -> PatternId {
+        let id = PatternId(u32::try_from(self.patterns.len()).expect("pattern arena overflow"));
+        self.patterns.push(pattern);
+        id
+    }
+
+    pub fn span(&self, id: ExprId) -> &Span {
+        self[id].span()
+    }
+
+    pub fn pattern_span(&self, id: PatternId) -> &Span {
+        self[id].span()
+    }
+
+    pub fn len(&self) -> usize {
+        self.exprs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exprs.is_empty()
+    }
+
+    pub fn exprs_mut(&mut self) -> impl Iterator<Item = &mut Expr> {
+        self.exprs.iter_mut()
+    }
+
+    pub fn patterns_mut(&mut self) -> impl Iterator<Item = &mut Pattern> {
+        self.patterns.iter_mut()
+    }
+
+    pub fn append(&mut self, other: ExprArena) -> ArenaOffset {
+        let offset = ArenaOffset {
+            exprs: u32::try_from(self.exprs.len()).expect("expression arena overflow"),
+            patterns: u32::try_from(self.patterns.len()).expect("pattern arena overflow"),
+        };
+        self.exprs.extend(other.exprs.into_iter().map(|mut expr| {
+            match &mut expr {
+                Expr::Var { .. }
+                | Expr::Symbol { .. }
+                | Expr::Int { .. }
+                | Expr::Bool { .. }
+                | Expr::Hole { .. } => {}
+                Expr::Call { args, .. } => {
+                    for arg in args {
+                        *arg = offset.expr(*arg);
+                    }
+                }
+                Expr::Let { bindings, body, .. } => {
+                    for (_, value, _) in bindings {
+                        *value = offset.expr(*value);
+                    }
+                    *body = offset.expr(*body);
+                }
+                Expr::LetFn { fns, body, .. } => {
+                    for f in fns {
+                        f.body = offset.expr(f.body);
+                    }
+                    *body = offset.expr(*body);
+                }
+                Expr::If {
+                    cond,
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    for branch in [cond, then_branch, else_branch] {
+                        *branch = offset.expr(*branch);
+                    }
+                }
+                Expr::Match {
+                    scrutinee, arms, ..
+                } => {
+                    *scrutinee = offset.expr(*scrutinee);
+                    for arm in arms {
+                        arm.pattern = offset.pattern(arm.pattern);
+                        arm.body = offset.expr(arm.body);
+                    }
+                }
+            }
+            expr
+        }));
+        self.patterns
+            .extend(other.patterns.into_iter().map(|mut pattern| {
+                if let Pattern::Ctor { args, .. } = &mut pattern {
+                    for arg in args {
+                        *arg = offset.pattern(*arg);
+                    }
+                }
+                pattern
+            }));
+        offset
+    }
+}
+
+impl Index<ExprId> for ExprArena {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        &self.exprs[id.0 as usize]
+    }
+}
+
+impl IndexMut<ExprId> for ExprArena {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.exprs[id.0 as usize]
+    }
+}
+
+impl Index<PatternId> for ExprArena {
+    type Output = Pattern;
+
+    fn index(&self, id: PatternId) -> &Pattern {
+        &self.patterns[id.0 as usize]
+    }
+}
+
+impl IndexMut<PatternId> for ExprArena {
+    fn index_mut(&mut self, id: PatternId) -> &mut Pattern {
+        &mut self.patterns[id.0 as usize]
+    }
+}
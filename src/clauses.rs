@@ -0,0 +1,488 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Clause, DataDecl, Expr, ExprArena, ExprId, MatchArm, Param, Pattern, PatternId};
+use crate::diagnostics::{Diagnostic, Span};
+use crate::types::Type;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClauseDesugar {
+    pub body: ExprId,
+    pub unreachable: Vec<usize>,
+    pub errors: Vec<Diagnostic>,
+}
+
+pub fn desugar_clauses(
+    exprs: &mut ExprArena,
+    params: &[Param],
+    clauses: &[Clause],
+    data_decls: &[DataDecl],
+) -> ClauseDesugar {
+    let span = clauses_span(clauses);
+    let names = clauses
+        .iter()
+        .map(|clause| clause_names(exprs, clause))
+        .collect();
+    let wildcard = exprs.alloc_pattern(Pattern::Wildcard { span: EMPTY_SPAN });
+    let mut compiler = Compiler {
+        exprs,
+        clauses,
+        data_decls,
+        names,
+        taken: params.iter().map(|p| p.name.clone()).collect(),
+        reached: vec![false; clauses.len()],
+        errors: Vec::new(),
+        wildcard,
+        span,
+    };
+    let columns = params
+        .iter()
+        .map(|p| Column {
+            var: p.name.clone(),
+            ty: Some(p.ty.as_base().clone()),
+        })
+        .collect::<Vec<_>>();
+    let rows = clauses
+        .iter()
+        .enumerate()
+        .map(|(idx, clause)| Row {
+            pats: clause.patterns.clone(),
+            renames: HashMap::new(),
+            clause: idx,
+        })
+        .collect::<Vec<_>>();
+    let body = if rows.is_empty() {
+        compiler.exprs.alloc(Expr::Bool {
+            value: true,
+            span: compiler.span.clone(),
+        })
+    } else {
+        compiler.compile(&columns, rows)
+    };
+    ClauseDesugar {
+        body,
+        unreachable: (0..clauses.len())
+            .filter(|idx| !compiler.reached[*idx])
+            .collect(),
+        errors: compiler.errors,
+    }
+}
+
+fn clauses_span(clauses: &[Clause]) -> Span {
+    match (clauses.first(), clauses.last()) {
+        (Some(first), Some(last)) => Span {
+            end: last.span.end,
+            ..first.span.clone()
+        },
+        _ => EMPTY_SPAN,
+    }
+}
+
+struct Column {
+    var: String,
+    ty: Option<Type>,
+}
+
+struct Row {
+    pats: Vec<PatternId>,
+    renames: HashMap<String, String>,
+    clause: usize,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Head {
+    Ctor(String),
+    Int(i64),
+    Bool(bool),
+    Symbol(String),
+}
+
+struct Compiler<'a> {
+    exprs: &'a mut ExprArena,
+    clauses: &'a [Clause],
+    data_decls: &'a [DataDecl],
+    names: Vec<HashSet<String>>,
+    taken: HashSet<String>,
+    reached: Vec<bool>,
+    errors: Vec<Diagnostic>,
+    wildcard: PatternId,
+    span: Span,
+}
+
+impl<'a> Compiler<'a> {
+    fn compile(&mut self, columns: &[Column], mut rows: Vec<Row>) -> ExprId {
+        let Some(col) = rows[0]
+            .pats
+            .iter()
+            .position(|pat| is_refutable(&self.exprs[*pat]))
+        else {
+            let mut row = rows.swap_remove(0);
+            for (pat, column) in row.pats.iter().zip(columns) {
+                if let Pattern::Var { name, .. } = &self.exprs[*pat]
+                    && name != &column.var
+                {
+                    row.renames.insert(name.clone(), column.var.clone());
+                }
+            }
+            self.reached[row.clause] = true;
+            return copy_renamed(self.exprs, self.clauses[row.clause].body, &row.renames);
+        };
+
+        let mut heads = Vec::new();
+        for row in &rows {
+            if let Some(head) = pattern_head(&self.exprs[row.pats[col]])
+                && !heads.contains(&head)
+            {
+                heads.push(head);
+            }
+        }
+
+        let mut arms = Vec::new();
+        for head in &heads {
+            let first = rows
+                .iter()
+                .find(|row| pattern_head(&self.exprs[row.pats[col]]).as_ref() == Some(head))
+                .expect("every head comes from a row");
+            let (first_clause, arm_pat) = (first.clause, first.pats[col]);
+            let arm_span = self.clauses[first_clause].span.clone();
+            let arm_args = match &self.exprs[arm_pat] {
+                Pattern::Ctor { args, .. } => args.clone(),
+                _ => Vec::new(),
+            };
+            let arity = arm_args.len();
+            let field_types = self.field_types(head, columns[col].ty.as_ref(), arity);
+            let fields = (0..arity)
+                .map(|idx| {
+                    let preferred = match &self.exprs[arm_args[idx]] {
+                        Pattern::Var { name, .. } => Some(name.clone()),
+                        _ => None,
+                    };
+                    let var =
+                        self.fresh(preferred.as_deref(), first_clause, &columns[col].var, idx);
+                    Column {
+                        var,
+                        ty: field_types[idx].clone(),
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let mut sub_rows = Vec::new();
+            for row in &rows {
+                let pat = &self.exprs[row.pats[col]];
+                let args = match (pat, head) {
+                    (Pattern::Ctor { name, args, .. }, Head::Ctor(head_name))
+                        if name == head_name =>
+                    {
+                        if args.len() != arity {
+                            self.errors.push(Diagnostic::new(
+                                "E-MATCH",
+                                format!(
+                                    "constructor pattern arity differs between clauses: {name}"
+                                ),
+                                Some(pat.span().clone()),
+                            ));
+                            continue;
+                        }
+                        args.clone()
+                    }
+                    (Pattern::Wildcard { .. } | Pattern::Var { .. }, _) => {
+                        vec![self.wildcard; arity]
+                    }
+                    _ if pattern_head(pat).as_ref() == Some(head) => Vec::new(),
+                    _ => continue,
+                };
+                sub_rows.push(self.specialize(row, col, &columns[col].var, args));
+            }
+
+            let sub_columns = splice(columns, col, fields);
+            let body = self.compile(&sub_columns, sub_rows);
+            let pat_span = self.exprs.pattern_span(arm_pat).clone();
+            let pattern = match head {
+                Head::Ctor(name) => {
+                    let args = sub_columns[col..col + arity]
+                        .iter()
+                        .map(|column| {
+                            self.exprs.alloc_pattern(Pattern::Var {
+                                name: column.var.clone(),
+                                span: arm_span.clone(),
+                            })
+                        })
+                        .collect();
+                    Pattern::Ctor {
+                        name: name.clone(),
+                        args,
+                        span: pat_span,
+                    }
+                }
+                Head::Int(value) => Pattern::Int {
+                    value: *value,
+                    span: pat_span,
+                },
+                Head::Bool(value) => Pattern::Bool {
+                    value: *value,
+                    span: pat_span,
+                },
+                Head::Symbol(value) => Pattern::Symbol {
+                    value: value.clone(),
+                    span: pat_span,
+                },
+            };
+            arms.push(MatchArm {
+                pattern: self.exprs.alloc_pattern(pattern),
+                body,
+                span: arm_span,
+            });
+        }
+
+        let defaults = rows
+            .iter()
+            .filter(|row| !is_refutable(&self.exprs[row.pats[col]]))
+            .map(|row| self.specialize(row, col, &columns[col].var, Vec::new()))
+            .collect::<Vec<_>>();
+        if !defaults.is_empty() && !self.is_complete(&heads, columns[col].ty.as_ref()) {
+            let span = self.clauses[defaults[0].clause].span.clone();
+            let body = self.compile(&splice(columns, col, Vec::new()), defaults);
+            arms.push(MatchArm {
+                pattern: self
+                    .exprs
+                    .alloc_pattern(Pattern::Wildcard { span: span.clone() }),
+                body,
+                span,
+            });
+        }
+
+        let scrutinee = self.exprs.alloc(Expr::Var {
+            name: columns[col].var.clone(),
+            span: self.span.clone(),
+        });
+        self.exprs.alloc(Expr::Match {
+            scrutinee,
+            arms,
+            span: self.span.clone(),
+        })
+    }
+
+    fn specialize(&self, row: &Row, col: usize, var: &str, args: Vec<PatternId>) -> Row {
+        let mut renames = row.renames.clone();
+        if let Pattern::Var { name, .. } = &self.exprs[row.pats[col]] {
+            renames.insert(name.clone(), var.to_string());
+        }
+        let mut pats = row.pats[..col].to_vec();
+        pats.extend(args);
+        pats.extend_from_slice(&row.pats[col + 1..]);
+        Row {
+            pats,
+            renames,
+            clause: row.clause,
+        }
+    }
+
+    fn fresh(&mut self, preferred: Option<&str>, owner: usize, var: &str, idx: usize) -> String {
+        if let Some(name) = preferred
+            && !self.taken.contains(name)
+            && self
+                .names
+                .iter()
+                .enumerate()
+                .all(|(clause, names)| clause == owner || !names.contains(name))
+        {
+            self.taken.insert(name.to_string());
+            return name.to_string();
+        }
+        let base = format!("{var}.{idx}");
+        let mut name = base.clone();
+        let mut suffix = 0;
+        while self.taken.contains(&name) || self.names.iter().any(|names| names.contains(&name)) {
+            suffix += 1;
+            name = format!("{base}.{suffix}");
+        }
+        self.taken.insert(name.clone());
+        name
+    }
+
+    fn data_decl(&self, ty: Option<&Type>) -> Option<&'a DataDecl> {
+        let name = match ty? {
+            Type::Domain(name) | Type::Adt(name) => name,
+            _ => return None,
+        };
+        self.data_decls.iter().find(|data| &data.name == name)
+    }
+
+    fn field_types(&self, head: &Head, ty: Option<&Type>, arity: usize) -> Vec<Option<Type>> {
+        let fields = match head {
+            Head::Ctor(name) => self
+                .data_decl(ty)
+                .and_then(|data| data.constructors.iter().find(|ctor| &ctor.name == name))
+                .filter(|ctor| ctor.fields.len() == arity)
+                .map(|ctor| ctor.fields.clone()),
+            _ => None,
+        };
+        match fields {
+            Some(fields) => fields.into_iter().map(Some).collect(),
+            None => vec![None; arity],
+        }
+    }
+
+    fn is_complete(&self, heads: &[Head], ty: Option<&Type>) -> bool {
+        if matches!(ty, Some(Type::Bool)) {
+            return heads.contains(&Head::Bool(true)) && heads.contains(&Head::Bool(false));
+        }
+        self.data_decl(ty).is_some_and(|data| {
+            data.constructors.iter().all(|ctor| {
+                heads
+                    .iter()
+                    .any(|head| matches!(head, Head::Ctor(name) if name == &ctor.name))
+            })
+        })
+    }
+}
+
+const EMPTY_SPAN: Span = Span {
+    start: 0,
+    end: 0,
+    line: 0,
+    column: 0,
+    file_id: None,
+};
+
+fn splice(columns: &[Column], col: usize, fields: Vec<Column>) -> Vec<Column> {
+    let copy = |column: &Column| Column {
+        var: column.var.clone(),
+        ty: column.ty.clone(),
+    };
+    let mut out = columns[..col].iter().map(copy).collect::<Vec<_>>();
+    out.extend(fields);
+    out.extend(columns[col + 1..].iter().map(copy));
+    out
+}
+
+fn is_refutable(pattern: &Pattern) -> bool {
+    !matches!(pattern, Pattern::Wildcard { .. } | Pattern::Var { .. })
+}
+
+fn pattern_head(pattern: &Pattern) -> Option<Head> {
+    match pattern {
+        Pattern::Ctor { name, .. } => Some(Head::Ctor(name.clone())),
+        Pattern::Int { value, .. } => Some(Head::Int(*value)),
+        Pattern::Bool { value, .. } => Some(Head::Bool(*value)),
+        Pattern::Symbol { value, .. } => Some(Head::Symbol(value.clone())),
+        Pattern::Wildcard { .. } | Pattern::Var { .. } => None,
+    }
+}
+
+fn clause_names(exprs: &ExprArena, clause: &Clause) -> HashSet<String> {
+    let mut out = HashSet::new();
+    for pattern in &clause.patterns {
+        pattern_names(exprs, *pattern, &mut out);
+    }
+    expr_names(exprs, clause.body, &mut out);
+    out
+}
+
+fn pattern_names(exprs: &ExprArena, pattern: PatternId, out: &mut HashSet<String>) {
+    match &exprs[pattern] {
+        Pattern::Var { name, .. } => {
+            out.insert(name.clone());
+        }
+        Pattern::Ctor { args, .. } => {
+            for arg in args {
+                pattern_names(exprs, *arg, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn expr_names(exprs: &ExprArena, expr: ExprId, out: &mut HashSet<String>) {
+    match &exprs[expr] {
+        Expr::Var { name, .. } => {
+            out.insert(name.clone());
+        }
+        Expr::Let { bindings, .. } => {
+            out.extend(bindings.iter().map(|(name, _, _)| name.clone()));
+        }
+        Expr::LetFn { fns, .. } => {
+            for f in fns {
+                out.insert(f.name.clone());
+                out.extend(f.params.iter().map(|p| p.name.clone()));
+            }
+        }
+        Expr::Match { arms, .. } => {
+            for arm in arms {
+                pattern_names(exprs, arm.pattern, out);
+            }
+        }
+        Expr::Symbol { .. }
+        | Expr::Int { .. }
+        | Expr::Bool { .. }
+        | Expr::Hole { .. }
+        | Expr::Call { .. }
+        | Expr::If { .. } => {}
+    }
+    for child in exprs[expr].children() {
+        expr_names(exprs, child, out);
+    }
+}
+
+fn copy_renamed(exprs: &mut ExprArena, expr: ExprId, renames: &HashMap<String, String>) -> ExprId {
+    if renames.is_empty() {
+        return expr;
+    }
+    let mut node = exprs[expr].clone();
+    match &mut node {
+        Expr::Var { name, .. } => match renames.get(name) {
+            Some(to) => *name = to.clone(),
+            None => return expr,
+        },
+        Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } | Expr::Hole { .. } => {
+            return expr;
+        }
+        Expr::Call { args, .. } => {
+            for arg in args {
+                *arg = copy_renamed(exprs, *arg, renames);
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            let mut inner = renames.clone();
+            for (name, value, _) in bindings {
+                *value = copy_renamed(exprs, *value, &inner);
+                inner.remove(name);
+            }
+            *body = copy_renamed(exprs, *body, &inner);
+        }
+        Expr::LetFn { fns, body, .. } => {
+            let mut inner = renames.clone();
+            for f in fns {
+                let mut fn_renames = inner.clone();
+                for param in &f.params {
+                    fn_renames.remove(&param.name);
+                }
+                f.body = copy_renamed(exprs, f.body, &fn_renames);
+                inner.remove(&f.name);
+            }
+            *body = copy_renamed(exprs, *body, &inner);
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            for branch in [cond, then_branch, else_branch] {
+                *branch = copy_renamed(exprs, *branch, renames);
+            }
+        }
+        Expr::Match {
+            scrutinee, arms, ..
+        } => {
+            *scrutinee = copy_renamed(exprs, *scrutinee, renames);
+            for arm in arms {
+                let mut bound = HashSet::new();
+                pattern_names(exprs, arm.pattern, &mut bound);
+                let mut inner = renames.clone();
+                inner.retain(|name, _| !bound.contains(name));
+                arm.body = copy_renamed(exprs, arm.body, &inner);
+            }
+        }
+    }
+    exprs.alloc(node)
+}
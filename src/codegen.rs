@@ -0,0 +1,2205 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+use serde_json::json;
+
+use crate::ast::{
+    ConstructorDecl, DataDecl, Defn, Expr, ExprArena, ExprId, Pattern, PatternId, Program,
+};
+use crate::diagnostics::Diagnostic;
+use crate::fmt::render_type;
+use crate::interval::{ArithOp, ComparisonOp};
+use crate::logic_engine::{DISEQUALITY_PRED, DerivedFacts, Value, value_to_string};
+use crate::symbol::Symbol;
+use crate::typecheck::checked_and_solved;
+use crate::types::{Atom, Formula, LogicTerm, Type};
+
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+const NON_RAW_KEYWORDS: &[&str] = &["crate", "self", "Self", "super"];
+
+const RESERVED_TYPE_NAMES: &[&str] = &[
+    "ArithmeticOverflow",
+    "Box",
+    "Err",
+    "None",
+    "Ok",
+    "Option",
+    "RefinementError",
+    "Result",
+    "Some",
+    "String",
+    "Vec",
+];
+
+const HEADER: &str = "\
+// @generated by `dtl codegen rust`. Do not edit.
+//
+// Data declarations become enums, and sorts newtypes over their symbols.
+// Refinement types of defn signatures that only constrain their own value
+// become newtypes whose `new` checks the refinement; the generated functions
+// construct them directly, since the checker proved the refinement. Relations
+// answer from the facts derived when this file was generated. Functions whose
+// `Int` arithmetic may overflow `i64` return `Result<_, ArithmeticOverflow>`,
+// as evaluating them in dtl fails there.
+#![allow(
+    clippy::all,
+    confusable_idents,
+    dead_code,
+    irrefutable_let_patterns,
+    mixed_script_confusables,
+    non_camel_case_types,
+    non_snake_case,
+    uncommon_codepoints,
+    unreachable_code,
+    unused_braces,
+    unused_parens,
+    unused_variables
+)]
+";
+
+const OVERFLOW_TYPE: &str = "
+/// An `Int` operation whose result does not fit in `i64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArithmeticOverflow {
+    pub op: &'static str,
+    pub lhs: i64,
+    pub rhs: i64,
+}
+
+impl ArithmeticOverflow {
+    pub fn checked_add(lhs: i64, rhs: i64) -> Result<i64, Self> {
+        lhs.checked_add(rhs).ok_or(Self { op: \"+\", lhs, rhs })
+    }
+
+    pub fn checked_sub(lhs: i64, rhs: i64) -> Result<i64, Self> {
+        lhs.checked_sub(rhs).ok_or(Self { op: \"-\", lhs, rhs })
+    }
+
+    pub fn checked_mul(lhs: i64, rhs: i64) -> Result<i64, Self> {
+        lhs.checked_mul(rhs).ok_or(Self { op: \"*\", lhs, rhs })
+    }
+}
+
+impl std::fmt::Display for ArithmeticOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, \"integer overflow in ({} {} {})\", self.op, self.lhs, self.rhs)
+    }
+}
+
+impl std::error::Error for ArithmeticOverflow {}
+";
+
+pub fn generate_rust(program: &Program) -> Result<String, Vec<Diagnostic>> {
+    let (program, derived) = checked_and_solved(program)?;
+    let mut generator = RustGen::new(&program, &derived)?;
+    Ok(generator.render())
+}
+
+pub fn generate_typescript(program: &Program) -> Result<String, Vec<Diagnostic>> {
+    let (program, derived) = checked_and_solved(program)?;
+    let mut generator = TsGen::new(&program, &derived)?;
+    Ok(generator.render())
+}
+
+pub fn generate_jsonschema(program: &Program) -> Result<String, Vec<Diagnostic>> {
+    let (program, derived) = checked_and_solved(program)?;
+    let generator = SchemaGen {
+        decls: Decls::new(&program, &derived),
+        universes: program
+            .universes
+            .iter()
+            .map(|universe| (universe.ty_name.as_str(), universe.values.as_slice()))
+            .collect(),
+    };
+    Ok(generator.render())
+}
+
+pub fn generate_sql(program: &Program) -> Result<String, Vec<Diagnostic>> {
+    let (program, derived) = checked_and_solved(program)?;
+    let generator = SqlGen {
+        decls: Decls::new(&program, &derived),
+        universes: program
+            .universes
+            .iter()
+            .map(|universe| (universe.ty_name.as_str(), universe.values.as_slice()))
+            .collect(),
+    };
+    Ok(generator.render())
+}
+
+fn codegen_error(message: String) -> Diagnostic {
+    Diagnostic::new("E-CODEGEN", message, None)
+}
+
+fn type_ident(name: &str) -> String {
+    escape_keyword(camel_case(name))
+}
+
+fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    for word in name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.extend(first.to_uppercase());
+            out.push_str(chars.as_str());
+        }
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, 'T');
+    }
+    out
+}
+
+fn value_ident(name: &str) -> String {
+    escape_keyword(snake_case(name))
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut after_lower = false;
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            if c.is_uppercase() && after_lower {
+                out.push('_');
+            }
+            after_lower = c.is_lowercase() || c.is_ascii_digit();
+            out.extend(c.to_lowercase());
+        } else if !out.is_empty() && !out.ends_with('_') {
+            out.push('_');
+            after_lower = false;
+        }
+    }
+    let mut out = out.trim_end_matches('_').to_string();
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert_str(0, "v_");
+    }
+    out
+}
+
+fn escape_keyword(ident: String) -> String {
+    if NON_RAW_KEYWORDS.contains(&ident.as_str()) {
+        format!("{ident}_")
+    } else if RUST_KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{ident}")
+    } else {
+        ident
+    }
+}
+
+fn int_literal(value: i64) -> String {
+    if value < 0 {
+        format!("({value})")
+    } else {
+        value.to_string()
+    }
+}
+
+fn tuple(items: &[String]) -> String {
+    match items {
+        [item] => format!("({item},)"),
+        items => format!("({})", items.join(", ")),
+    }
+}
+
+fn indent(text: &str, depth: usize) -> String {
+    let pad = "    ".repeat(depth);
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{pad}{line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+struct Decls<'a> {
+    program: &'a Program,
+    derived: &'a DerivedFacts,
+    data: HashMap<&'a str, &'a DataDecl>,
+    ctors: HashMap<&'a str, (&'a DataDecl, &'a ConstructorDecl)>,
+    relations: HashMap<&'a str, &'a [String]>,
+}
+
+impl<'a> Decls<'a> {
+    fn new(program: &'a Program, derived: &'a DerivedFacts) -> Self {
+        Decls {
+            program,
+            derived,
+            data: program
+                .data_decls
+                .iter()
+                .map(|data| (data.name.as_str(), data))
+                .collect(),
+            ctors: program
+                .data_decls
+                .iter()
+                .flat_map(|data| {
+                    data.constructors
+                        .iter()
+                        .map(move |ctor| (ctor.name.as_str(), (data, ctor)))
+                })
+                .collect(),
+            relations: program
+                .relations
+                .iter()
+                .map(|relation| (relation.name.as_str(), relation.arg_sorts.as_slice()))
+                .collect(),
+        }
+    }
+
+    fn data_name<'t>(&self, ty: &'t Type) -> Option<&'t str> {
+        match base_type(ty) {
+            Type::Adt(name) | Type::Domain(name) if self.data.contains_key(name.as_str()) => {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    fn relation_rows(&self, name: &str) -> impl Iterator<Item = &'a Vec<Value>> {
+        self.derived.facts.get(name).into_iter().flatten()
+    }
+
+    fn relation_values(&self, atom: &Atom, var: &str) -> Option<RelationValues<'a>> {
+        self.relations.get(atom.pred.as_str())?;
+        let constants = atom
+            .terms
+            .iter()
+            .map(|term| match term {
+                LogicTerm::Var(name) if name == var => Some(None),
+                term => term_value(term).map(Some),
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let mut values = Vec::new();
+        for row in self.relation_rows(&atom.pred) {
+            let mut own_value = None;
+            let matches = row
+                .iter()
+                .zip(&constants)
+                .all(|(value, constant)| match constant {
+                    Some(constant) => value == constant,
+                    None => own_value.replace(value).is_none_or(|seen| seen == value),
+                });
+            match (matches, own_value) {
+                (false, _) => {}
+                (true, None) => return Some(RelationValues::Ground(true)),
+                (true, Some(value)) if !values.contains(&value) => values.push(value),
+                (true, Some(_)) => {}
+            }
+        }
+        if constants.iter().all(Option::is_some) {
+            return Some(RelationValues::Ground(false));
+        }
+        Some(RelationValues::Values(values))
+    }
+}
+
+enum RelationValues<'a> {
+    Ground(bool),
+    Values(Vec<&'a Value>),
+}
+
+struct SignatureSlot<'a> {
+    ty: &'a Type,
+    name: String,
+    origin: String,
+}
+
+fn signature_slots(defn: &Defn) -> Vec<SignatureSlot<'_>> {
+    let owner = camel_case(&defn.name);
+    let mut slots = defn
+        .params
+        .iter()
+        .map(|param| SignatureSlot {
+            ty: &param.ty,
+            name: format!("{owner}{}", camel_case(&param.name)),
+            origin: format!("parameter {} of {}", param.name, defn.name),
+        })
+        .collect::<Vec<_>>();
+    slots.push(SignatureSlot {
+        ty: &defn.ret_type,
+        name: format!("{owner}Output"),
+        origin: format!("return type of {}", defn.name),
+    });
+    slots
+}
+
+struct Newtype {
+    ident: String,
+    ty: Type,
+    base: String,
+    check: String,
+    origin: String,
+}
+
+struct DefnSig {
+    params: Vec<Option<String>>,
+    ret: Option<String>,
+}
+
+struct RustGen<'a> {
+    decls: Decls<'a>,
+    boxed: HashSet<(&'a str, usize)>,
+    type_names: HashMap<&'a str, String>,
+    variant_names: HashMap<&'a str, String>,
+    fn_names: HashMap<&'a str, String>,
+    values: HashSet<String>,
+    types: HashSet<String>,
+    skipped: Vec<(&'a str, String)>,
+    sigs: HashMap<&'a str, DefnSig>,
+    fallible: HashSet<&'a str>,
+    in_fallible: bool,
+    newtypes: Vec<Newtype>,
+    used_relations: BTreeSet<&'a str>,
+    labels: usize,
+    temps: usize,
+}
+
+impl<'a> RustGen<'a> {
+    fn new(program: &'a Program, derived: &'a DerivedFacts) -> Result<Self, Vec<Diagnostic>> {
+        let mut generator = RustGen {
+            decls: Decls::new(program, derived),
+            boxed: HashSet::new(),
+            type_names: HashMap::new(),
+            variant_names: HashMap::new(),
+            fn_names: HashMap::new(),
+            values: HashSet::new(),
+            types: HashSet::new(),
+            skipped: Vec::new(),
+            sigs: HashMap::new(),
+            fallible: HashSet::new(),
+            in_fallible: false,
+            newtypes: Vec::new(),
+            used_relations: BTreeSet::new(),
+            labels: 0,
+            temps: 0,
+        };
+        generator.assign_names()?;
+        generator.find_boxed_fields();
+        generator.find_skipped_defns();
+        generator.find_fallible_defns();
+        generator.assign_newtypes();
+        Ok(generator)
+    }
+
+    fn assign_names(&mut self) -> Result<(), Vec<Diagnostic>> {
+        let program = self.decls.program;
+        let mut errors = Vec::new();
+        let mut types: HashMap<String, &str> = HashMap::new();
+        let mut values: HashMap<String, &str> = HashMap::new();
+        let mut claim = |space: &mut HashMap<String, &'a str>, ident: String, name: &'a str| {
+            if let Some(other) = space.get(&ident) {
+                errors.push(codegen_error(format!(
+                    "{other} and {name} both become the Rust name {ident}"
+                )));
+            } else {
+                space.insert(ident, name);
+            }
+        };
+        for sort in &program.sorts {
+            let ident = type_ident(&sort.name);
+            claim(&mut types, ident.clone(), &sort.name);
+            claim(&mut values, ident.clone(), &sort.name);
+            self.type_names.insert(&sort.name, ident);
+        }
+        for data in &program.data_decls {
+            let ident = type_ident(&data.name);
+            claim(&mut types, ident.clone(), &data.name);
+            self.type_names.insert(&data.name, ident);
+            let mut variants = HashMap::new();
+            for ctor in &data.constructors {
+                let variant = type_ident(&ctor.name);
+                claim(&mut variants, variant.clone(), &ctor.name);
+                self.variant_names.insert(&ctor.name, variant);
+            }
+        }
+        for relation in &program.relations {
+            let ident = value_ident(&relation.name);
+            claim(&mut values, ident.clone(), &relation.name);
+            self.fn_names.insert(&relation.name, ident);
+        }
+        for defn in &program.defns {
+            let ident = value_ident(&defn.name);
+            claim(&mut values, ident.clone(), &defn.name);
+            self.fn_names.insert(&defn.name, ident);
+        }
+        for (ident, name) in &types {
+            if RESERVED_TYPE_NAMES.contains(&ident.as_str()) {
+                errors.push(codegen_error(format!(
+                    "{name} becomes the Rust name {ident}, which the generated code uses"
+                )));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        self.types = types.into_keys().collect();
+        self.values = values.into_keys().collect();
+        Ok(())
+    }
+
+    fn find_boxed_fields(&mut self) {
+        let field_data = |data: &'a DataDecl| {
+            data.constructors
+                .iter()
+                .flat_map(|ctor| ctor.fields.iter())
+                .filter_map(|field| self.decls.data_name(field))
+                .collect::<Vec<_>>()
+        };
+        let edges = self
+            .decls
+            .program
+            .data_decls
+            .iter()
+            .map(|data| (data.name.as_str(), field_data(data)))
+            .collect::<HashMap<_, _>>();
+        let reaches = |from: &str, to: &str| {
+            let mut seen = HashSet::new();
+            let mut stack = vec![from];
+            while let Some(name) = stack.pop() {
+                if name == to {
+                    return true;
+                }
+                if seen.insert(name) {
+                    stack.extend(edges.get(name).into_iter().flatten().copied());
+                }
+            }
+            false
+        };
+        for data in &self.decls.program.data_decls {
+            for ctor in &data.constructors {
+                for (idx, field) in ctor.fields.iter().enumerate() {
+                    if self
+                        .decls
+                        .data_name(field)
+                        .is_some_and(|name| reaches(name, &data.name))
+                    {
+                        self.boxed.insert((&ctor.name, idx));
+                    }
+                }
+            }
+        }
+    }
+
+    fn find_skipped_defns(&mut self) {
+        let program = self.decls.program;
+        let mut skipped = HashMap::new();
+        for defn in &program.defns {
+            let higher_order = defn
+                .params
+                .iter()
+                .map(|param| &param.ty)
+                .chain([&defn.ret_type])
+                .any(|ty| matches!(base_type(ty), Type::Fun(..)))
+                || descendants(&program.exprs, defn.body)
+                    .into_iter()
+                    .any(|id| match &program.exprs[id] {
+                        Expr::LetFn { fns, .. } => fns.iter().any(|f| {
+                            f.params
+                                .iter()
+                                .map(|param| &param.ty)
+                                .chain([&f.ret_type])
+                                .any(|ty| matches!(base_type(ty), Type::Fun(..)))
+                        }),
+                        _ => false,
+                    });
+            if higher_order {
+                skipped.insert(
+                    defn.name.as_str(),
+                    "it takes or returns a function".to_string(),
+                );
+            }
+        }
+        loop {
+            let mut changed = false;
+            for defn in &program.defns {
+                if skipped.contains_key(defn.name.as_str()) {
+                    continue;
+                }
+                let callee = descendants(&program.exprs, defn.body)
+                    .into_iter()
+                    .find_map(|id| match &program.exprs[id] {
+                        Expr::Call { name, .. } if skipped.contains_key(name.as_str()) => {
+                            Some(name.clone())
+                        }
+                        _ => None,
+                    });
+                if let Some(callee) = callee {
+                    skipped.insert(
+                        defn.name.as_str(),
+                        format!("it calls {callee}, which is not generated"),
+                    );
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        self.skipped = program
+            .defns
+            .iter()
+            .filter_map(|defn| {
+                skipped
+                    .remove(defn.name.as_str())
+                    .map(|reason| (defn.name.as_str(), reason))
+            })
+            .collect();
+    }
+
+    fn find_fallible_defns(&mut self) {
+        let program = self.decls.program;
+        let calls = |defn: &Defn| {
+            descendants(&program.exprs, defn.body)
+                .into_iter()
+                .filter_map(|id| match &program.exprs[id] {
+                    Expr::Call { name, args, .. } => Some((name.as_str(), args.len())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        for defn in &program.defns {
+            if calls(defn)
+                .iter()
+                .any(|(name, arity)| ArithOp::from_name(name).is_some() && *arity == 2)
+            {
+                self.fallible.insert(&defn.name);
+            }
+        }
+        loop {
+            let mut changed = false;
+            for defn in &program.defns {
+                if !self.fallible.contains(defn.name.as_str())
+                    && calls(defn)
+                        .iter()
+                        .any(|(name, _)| self.fallible.contains(name))
+                {
+                    self.fallible.insert(&defn.name);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    fn is_skipped(&self, name: &str) -> bool {
+        self.skipped.iter().any(|(skipped, _)| *skipped == name)
+    }
+
+    fn assign_newtypes(&mut self) {
+        let program = self.decls.program;
+        for defn in &program.defns {
+            if self.is_skipped(&defn.name) {
+                continue;
+            }
+            let mut params = signature_slots(defn)
+                .into_iter()
+                .map(|slot| self.newtype_for(slot.ty, slot.name, slot.origin))
+                .collect::<Vec<_>>();
+            let ret = params.pop().flatten();
+            self.sigs.insert(&defn.name, DefnSig { params, ret });
+        }
+    }
+
+    fn newtype_for(&mut self, ty: &Type, name: String, origin: String) -> Option<String> {
+        let Type::Refine { var, base, formula } = ty else {
+            return None;
+        };
+        if let Some(existing) = self.newtypes.iter().find(|newtype| newtype.ty == *ty) {
+            return Some(existing.ident.clone());
+        }
+        let base_ty = self.rust_type(base)?;
+        let check = self.refinement_check(formula, var, base)?;
+        let mut ident = name.clone();
+        let mut suffix = 2;
+        while RESERVED_TYPE_NAMES.contains(&ident.as_str())
+            || self.types.contains(&ident)
+            || self.values.contains(&ident)
+        {
+            ident = format!("{name}{suffix}");
+            suffix += 1;
+        }
+        self.types.insert(ident.clone());
+        self.values.insert(ident.clone());
+        self.newtypes.push(Newtype {
+            ident: ident.clone(),
+            ty: ty.clone(),
+            base: base_ty,
+            check,
+            origin,
+        });
+        Some(ident)
+    }
+
+    fn refinement_check(&mut self, formula: &Formula, var: &str, base: &Type) -> Option<String> {
+        match formula {
+            Formula::True => Some("true".to_string()),
+            Formula::And(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.refinement_check(item, var, base))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(match items.as_slice() {
+                    [] => "true".to_string(),
+                    [item] => item.clone(),
+                    items => format!("({})", items.join(" && ")),
+                })
+            }
+            Formula::Not(inner) => Some(format!("!{}", self.refinement_check(inner, var, base)?)),
+            Formula::Atom(atom) => {
+                let own = |term: &LogicTerm| matches!(term, LogicTerm::Var(name) if name == var);
+                let own_ident = value_ident(var);
+                if let Some(op) = ComparisonOp::from_pred(&atom.pred) {
+                    let operand = |term: &LogicTerm| match term {
+                        LogicTerm::Int(value) => Some(int_literal(*value)),
+                        term if own(term) => Some(format!("*{own_ident}")),
+                        _ => None,
+                    };
+                    let [lhs, rhs] = atom.terms.as_slice() else {
+                        return None;
+                    };
+                    return Some(format!(
+                        "({} {} {})",
+                        operand(lhs)?,
+                        op.as_str(),
+                        operand(rhs)?
+                    ));
+                }
+                if atom.pred == DISEQUALITY_PRED {
+                    let operand = |term: &LogicTerm| {
+                        if own(term) {
+                            Some(format!("*{own_ident}"))
+                        } else {
+                            self.term(term, base)
+                        }
+                    };
+                    let [lhs, rhs] = atom.terms.as_slice() else {
+                        return None;
+                    };
+                    return Some(format!("({} != {})", operand(lhs)?, operand(rhs)?));
+                }
+                let sorts = *self.decls.relations.get(atom.pred.as_str())?;
+                let relation = self
+                    .decls
+                    .program
+                    .relations
+                    .iter()
+                    .find(|r| r.name == atom.pred)?;
+                let args = atom
+                    .terms
+                    .iter()
+                    .zip(sorts)
+                    .map(|(term, sort)| {
+                        if own(term) {
+                            Some(own_ident.clone())
+                        } else {
+                            Some(format!("&{}", self.term(term, &named_type(sort))?))
+                        }
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                self.used_relations.insert(&relation.name);
+                Some(format!(
+                    "{}({})",
+                    self.fn_names[atom.pred.as_str()],
+                    args.join(", ")
+                ))
+            }
+        }
+    }
+
+    fn rust_type(&self, ty: &Type) -> Option<String> {
+        match base_type(ty) {
+            Type::Bool => Some("bool".to_string()),
+            Type::Int => Some("i64".to_string()),
+            Type::Symbol => Some("String".to_string()),
+            Type::Domain(name) | Type::Adt(name) => self.type_names.get(name.as_str()).cloned(),
+            Type::Fun(..) | Type::Refine { .. } | Type::Error => None,
+        }
+    }
+
+    fn field_type(&self, ctor: &str, idx: usize, ty: &Type) -> String {
+        let ty = self
+            .rust_type(ty)
+            .expect("constructor fields have first-order types");
+        if self.boxed.contains(&(ctor, idx)) {
+            format!("Box<{ty}>")
+        } else {
+            ty
+        }
+    }
+
+    fn term(&self, term: &LogicTerm, ty: &Type) -> Option<String> {
+        Some(self.value(&term_value(term)?, ty))
+    }
+
+    fn value(&self, value: &Value, ty: &Type) -> String {
+        match value {
+            Value::Int(value) => int_literal(*value),
+            Value::Bool(value) => value.to_string(),
+            Value::Symbol(symbol) => match base_type(ty) {
+                Type::Domain(name) if !self.decls.data.contains_key(name.as_str()) => {
+                    format!(
+                        "{}::new({:?})",
+                        self.type_names[name.as_str()],
+                        symbol.as_str()
+                    )
+                }
+                _ => format!("String::from({:?})", symbol.as_str()),
+            },
+            Value::Adt { ctor, fields } => {
+                let (data, decl) = self.decls.ctors[ctor.as_str()];
+                let path = format!(
+                    "{}::{}",
+                    self.type_names[data.name.as_str()],
+                    self.variant_names[ctor.as_str()]
+                );
+                if fields.is_empty() {
+                    return path;
+                }
+                let fields = fields
+                    .iter()
+                    .zip(&decl.fields)
+                    .enumerate()
+                    .map(|(idx, (field, ty))| {
+                        let field = self.value(field, ty);
+                        if self.boxed.contains(&(ctor.as_str(), idx)) {
+                            format!("Box::new({field})")
+                        } else {
+                            field
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                format!("{path}({})", fields.join(", "))
+            }
+        }
+    }
+
+    fn render(&mut self) -> String {
+        let program = self.decls.program;
+        let mut out = String::from(HEADER);
+        for sort in &program.sorts {
+            let ident = &self.type_names[sort.name.as_str()];
+            let _ = write!(
+                out,
+                "\n/// Values of sort `{}`.\n\
+                 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]\n\
+                 pub struct {ident}(pub String);\n\n\
+                 impl {ident} {{\n    \
+                     pub fn new(name: &str) -> Self {{\n        \
+                         Self(name.to_string())\n    \
+                     }}\n\
+                 }}\n",
+                sort.name
+            );
+        }
+        for data in &program.data_decls {
+            let _ = write!(
+                out,
+                "\n/// `data {}`.\n\
+                 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]\n\
+                 pub enum {} {{\n",
+                data.name,
+                self.type_names[data.name.as_str()]
+            );
+            for ctor in &data.constructors {
+                let variant = &self.variant_names[ctor.name.as_str()];
+                if ctor.fields.is_empty() {
+                    let _ = writeln!(out, "    {variant},");
+                } else {
+                    let fields = ctor
+                        .fields
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, ty)| self.field_type(&ctor.name, idx, ty))
+                        .collect::<Vec<_>>();
+                    let _ = writeln!(out, "    {variant}({}),", fields.join(", "));
+                }
+            }
+            out.push_str("}\n");
+        }
+
+        let mut functions = String::new();
+        for defn in &program.defns {
+            if self.is_skipped(&defn.name) {
+                continue;
+            }
+            functions.push('\n');
+            functions.push_str(&self.render_defn(defn));
+        }
+
+        if !self.newtypes.is_empty() {
+            out.push_str(
+                "\n/// A value that does not satisfy the refinement of `type_name`.\n\
+                 #[derive(Debug, Clone, Copy, PartialEq, Eq)]\n\
+                 pub struct RefinementError {\n    \
+                     pub type_name: &'static str,\n\
+                 }\n\n\
+                 impl std::fmt::Display for RefinementError {\n    \
+                     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {\n        \
+                         write!(f, \"value does not satisfy the refinement of {}\", self.type_name)\n    \
+                     }\n\
+                 }\n\n\
+                 impl std::error::Error for RefinementError {}\n",
+            );
+        }
+        for newtype in &self.newtypes {
+            let Type::Refine { var, .. } = &newtype.ty else {
+                continue;
+            };
+            let Newtype {
+                ident,
+                base,
+                check,
+                origin,
+                ..
+            } = newtype;
+            let _ = write!(
+                out,
+                "\n/// `{}`: {origin}.\n\
+                 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]\n\
+                 pub struct {ident}({base});\n\n\
+                 impl {ident} {{\n    \
+                     pub fn new(value: {base}) -> Result<Self, RefinementError> {{\n        \
+                         let {} = &value;\n        \
+                         if {check} {{\n            \
+                             Ok(Self(value))\n        \
+                         }} else {{\n            \
+                             Err(RefinementError {{ type_name: {ident:?} }})\n        \
+                         }}\n    \
+                     }}\n\n    \
+                     pub fn get(&self) -> &{base} {{\n        \
+                         &self.0\n    \
+                     }}\n\n    \
+                     pub fn into_inner(self) -> {base} {{\n        \
+                         self.0\n    \
+                     }}\n\
+                 }}\n",
+                render_type(&newtype.ty),
+                value_ident(var),
+            );
+        }
+
+        if program
+            .defns
+            .iter()
+            .any(|defn| self.fallible.contains(defn.name.as_str()) && !self.is_skipped(&defn.name))
+        {
+            out.push_str(OVERFLOW_TYPE);
+        }
+
+        out.push_str(&functions);
+
+        for name in self.used_relations.clone() {
+            out.push('\n');
+            out.push_str(&self.render_relation(name));
+        }
+
+        if !self.skipped.is_empty() {
+            out.push_str("\n// Not generated:\n");
+            for (name, reason) in &self.skipped {
+                let _ = writeln!(out, "// - {name}: {reason}");
+            }
+        }
+        out
+    }
+
+    fn render_relation(&self, name: &str) -> String {
+        let sorts = self.decls.relations[name];
+        let types = sorts
+            .iter()
+            .map(|sort| named_type(sort))
+            .collect::<Vec<_>>();
+        let rust_types = types
+            .iter()
+            .map(|ty| {
+                self.rust_type(ty)
+                    .expect("relation columns have first-order types")
+            })
+            .collect::<Vec<_>>();
+        let params = rust_types
+            .iter()
+            .enumerate()
+            .map(|(idx, ty)| format!("a{idx}: &{ty}"))
+            .collect::<Vec<_>>();
+        let rows = self
+            .decls
+            .relation_rows(name)
+            .map(|row| {
+                let values = row
+                    .iter()
+                    .zip(&types)
+                    .map(|(value, ty)| self.value(value, ty))
+                    .collect::<Vec<_>>();
+                format!("        {},\n", tuple(&values))
+            })
+            .collect::<Vec<_>>();
+        let args = (0..sorts.len())
+            .map(|idx| format!("a{idx}.clone()"))
+            .collect::<Vec<_>>();
+        format!(
+            "/// Facts of `{name}` derived when this file was generated.\n\
+             pub fn {}({}) -> bool {{\n    \
+                 let rows: [{}; {}] = [\n{}    ];\n    \
+                 rows.contains(&{})\n\
+             }}\n",
+            self.fn_names[name],
+            params.join(", "),
+            tuple(&rust_types),
+            rows.len(),
+            rows.concat(),
+            tuple(&args),
+        )
+    }
+
+    fn render_defn(&mut self, defn: &'a Defn) -> String {
+        self.labels = 0;
+        self.temps = 0;
+        self.in_fallible = self.fallible.contains(defn.name.as_str());
+        let sig = &self.sigs[defn.name.as_str()];
+        let mut scope = Scope::default();
+        let mut params = Vec::new();
+        let mut unwrap = String::new();
+        for (param, newtype) in defn.params.iter().zip(&sig.params) {
+            let ident = self.local_ident(&param.name, &scope);
+            match newtype {
+                Some(newtype) => {
+                    params.push(format!("{ident}: {newtype}"));
+                    let _ = writeln!(unwrap, "    let {ident} = {ident}.into_inner();");
+                }
+                None => params.push(format!(
+                    "{ident}: {}",
+                    self.rust_type(&param.ty)
+                        .expect("generated defns have first-order types")
+                )),
+            }
+            scope.vars.insert(param.name.clone(), ident);
+        }
+        let ret = match &sig.ret {
+            Some(newtype) => newtype.clone(),
+            None => self
+                .rust_type(&defn.ret_type)
+                .expect("generated defns have first-order types"),
+        };
+        let wrap = sig.ret.clone();
+        let body = self.expr(defn.body, &scope);
+        let body = match wrap {
+            Some(newtype) => format!("{newtype}({body})"),
+            None => body,
+        };
+        let (ret, body) = self.fallible_result(ret, body);
+        let mut out = String::new();
+        match &defn.doc {
+            Some(doc) => {
+                for line in doc.lines() {
+                    let _ = writeln!(out, "/// {line}");
+                }
+            }
+            None => {
+                let _ = writeln!(out, "/// `defn {}`.", defn.name);
+            }
+        }
+        let _ = write!(
+            out,
+            "pub fn {}({}) -> {ret} {{\n{unwrap}{}\n}}\n",
+            self.fn_names[defn.name.as_str()],
+            params.join(", "),
+            indent(&body, 1),
+        );
+        out
+    }
+
+    fn fallible_result(&self, ret: String, body: String) -> (String, String) {
+        if self.in_fallible {
+            (
+                format!("Result<{ret}, ArithmeticOverflow>"),
+                format!("Ok({body})"),
+            )
+        } else {
+            (ret, body)
+        }
+    }
+
+    fn local_ident(&self, name: &str, scope: &Scope) -> String {
+        let mut ident = value_ident(name);
+        while self.values.contains(&ident) || scope.fns.values().any(|f| *f == ident) {
+            ident.push('_');
+        }
+        ident
+    }
+
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("__dtl_{}", self.temps)
+    }
+
+    fn expr(&mut self, id: ExprId, scope: &Scope) -> String {
+        let program = self.decls.program;
+        match &program.exprs[id] {
+            Expr::Var { name, .. } => format!("{}.clone()", scope.vars[name]),
+            Expr::Int { value, .. } => int_literal(*value),
+            Expr::Bool { value, .. } => value.to_string(),
+            Expr::Symbol { value, .. } => format!("String::from({value:?})"),
+            Expr::Hole { .. } => "unreachable!(\"typed hole\")".to_string(),
+            Expr::Call { name, args, .. } => self.call(name, args, scope),
+            Expr::Let { bindings, body, .. } => {
+                let mut inner = scope.clone();
+                let mut lines = Vec::new();
+                for (name, value, _) in bindings {
+                    let value = self.expr(*value, &inner);
+                    let ident = self.local_ident(name, &inner);
+                    lines.push(format!("let {ident} = {value};"));
+                    inner.vars.insert(name.clone(), ident);
+                }
+                lines.push(self.expr(*body, &inner));
+                format!("{{\n{}\n}}", indent(&lines.join("\n"), 1))
+            }
+            Expr::LetFn { fns, body, .. } => {
+                let mut inner = scope.clone();
+                let mut lines = Vec::new();
+                for f in fns {
+                    let mut fn_scope = inner.clone();
+                    let mut params = Vec::new();
+                    for param in &f.params {
+                        let ident = self.local_ident(&param.name, &fn_scope);
+                        params.push(format!(
+                            "{ident}: {}",
+                            self.rust_type(&param.ty)
+                                .expect("generated defns have first-order types")
+                        ));
+                        fn_scope.vars.insert(param.name.clone(), ident);
+                    }
+                    let ret = self
+                        .rust_type(&f.ret_type)
+                        .expect("generated defns have first-order types");
+                    let body = self.expr(f.body, &fn_scope);
+                    let (ret, body) = self.fallible_result(ret, body);
+                    let ident = self.local_ident(&f.name, &inner);
+                    lines.push(format!(
+                        "let {ident} = |{}| -> {ret} {{\n{}\n}};",
+                        params.join(", "),
+                        indent(&body, 1)
+                    ));
+                    inner.fns.insert(f.name.clone(), ident);
+                }
+                lines.push(self.expr(*body, &inner));
+                format!("{{\n{}\n}}", indent(&lines.join("\n"), 1))
+            }
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let cond = self.expr(*cond, scope);
+                let then_branch = self.expr(*then_branch, scope);
+                let else_branch = self.expr(*else_branch, scope);
+                format!(
+                    "if {cond} {{\n{}\n}} else {{\n{}\n}}",
+                    indent(&then_branch, 1),
+                    indent(&else_branch, 1)
+                )
+            }
+            Expr::Match {
+                scrutinee, arms, ..
+            } => {
+                self.labels += 1;
+                let label = format!("'dtl_match_{}", self.labels);
+                let value = self.temp();
+                let mut lines = vec![format!("let {value} = {};", self.expr(*scrutinee, scope))];
+                for arm in arms {
+                    let mut tests = Vec::new();
+                    let mut binds = Vec::new();
+                    self.pattern(
+                        arm.pattern,
+                        format!("&{value}"),
+                        &mut tests,
+                        &mut binds,
+                        &mut HashMap::new(),
+                    );
+                    let mut inner = scope.clone();
+                    let mut body_lines = Vec::new();
+                    for (name, access) in binds {
+                        let ident = self.local_ident(&name, &inner);
+                        body_lines.push(format!("let {ident} = ({access}).clone();"));
+                        inner.vars.insert(name, ident);
+                    }
+                    let body = self.expr(arm.body, &inner);
+                    let body = if body.starts_with(['{', '\'']) {
+                        format!("({body})")
+                    } else {
+                        body
+                    };
+                    body_lines.push(format!("break {label} {body};"));
+                    let mut block = body_lines.join("\n");
+                    for test in tests.iter().rev() {
+                        block = format!("{test} {{\n{}\n}}", indent(&block, 1));
+                    }
+                    lines.push(block);
+                }
+                lines.push("unreachable!(\"no match arm\")".to_string());
+                format!("{label}: {{\n{}\n}}", indent(&lines.join("\n"), 1))
+            }
+        }
+    }
+
+    fn pattern(
+        &mut self,
+        pattern: PatternId,
+        access: String,
+        tests: &mut Vec<String>,
+        binds: &mut Vec<(String, String)>,
+        seen: &mut HashMap<String, String>,
+    ) {
+        let program = self.decls.program;
+        match &program.exprs[pattern] {
+            Pattern::Wildcard { .. } => {}
+            Pattern::Var { name, .. } => match seen.get(name) {
+                Some(first) => tests.push(format!("if *{access} == *{first}")),
+                None => {
+                    seen.insert(name.clone(), access.clone());
+                    binds.push((name.clone(), access));
+                }
+            },
+            Pattern::Int { value, .. } => {
+                tests.push(format!("if *{access} == {}", int_literal(*value)))
+            }
+            Pattern::Bool { value, .. } => tests.push(format!("if *{access} == {value}")),
+            Pattern::Symbol { value, .. } => tests.push(format!("if *{access} == {value:?}")),
+            Pattern::Ctor { name, args, .. } => {
+                let (data, _) = self.decls.ctors[name.as_str()];
+                let path = format!(
+                    "{}::{}",
+                    self.type_names[data.name.as_str()],
+                    self.variant_names[name.as_str()]
+                );
+                if args.is_empty() {
+                    tests.push(format!("if let {path} = {access}"));
+                    return;
+                }
+                let mut fields = Vec::new();
+                let mut nested = Vec::new();
+                for (idx, arg) in args.iter().enumerate() {
+                    if matches!(program.exprs[*arg], Pattern::Wildcard { .. }) {
+                        fields.push("_".to_string());
+                        continue;
+                    }
+                    let temp = self.temp();
+                    let field_access = if self.boxed.contains(&(name.as_str(), idx)) {
+                        format!("&**{temp}")
+                    } else {
+                        temp.clone()
+                    };
+                    fields.push(temp);
+                    nested.push((*arg, field_access));
+                }
+                tests.push(format!("if let {path}({}) = {access}", fields.join(", ")));
+                for (arg, field_access) in nested {
+                    self.pattern(arg, field_access, tests, binds, seen);
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[ExprId], scope: &Scope) -> String {
+        let mut rendered = args
+            .iter()
+            .map(|arg| self.expr(*arg, scope))
+            .collect::<Vec<_>>();
+        if let Some(op) = ArithOp::from_name(name)
+            && let [lhs, rhs] = rendered.as_slice()
+        {
+            let method = match op {
+                ArithOp::Add => "checked_add",
+                ArithOp::Sub => "checked_sub",
+                ArithOp::Mul => "checked_mul",
+            };
+            return format!("ArithmeticOverflow::{method}({lhs}, {rhs})?");
+        }
+        if let Some(ident) = scope.fns.get(name) {
+            let call = format!("{ident}({})", rendered.join(", "));
+            return if self.in_fallible {
+                format!("{call}?")
+            } else {
+                call
+            };
+        }
+        if let Some((data, _)) = self.decls.ctors.get(name) {
+            let path = format!(
+                "{}::{}",
+                self.type_names[data.name.as_str()],
+                self.variant_names[name]
+            );
+            if rendered.is_empty() {
+                return path;
+            }
+            for (idx, arg) in rendered.iter_mut().enumerate() {
+                if self.boxed.contains(&(name, idx)) {
+                    *arg = format!("Box::new({arg})");
+                }
+            }
+            return format!("{path}({})", rendered.join(", "));
+        }
+        if let Some((relation, _)) = self.decls.relations.get_key_value(name) {
+            self.used_relations.insert(relation);
+            let args = rendered
+                .iter()
+                .map(|arg| format!("&{arg}"))
+                .collect::<Vec<_>>();
+            return format!("{}({})", self.fn_names[name], args.join(", "));
+        }
+        let sig = &self.sigs[name];
+        for (arg, newtype) in rendered.iter_mut().zip(&sig.params) {
+            if let Some(newtype) = newtype {
+                *arg = format!("{newtype}({arg})");
+            }
+        }
+        let mut call = format!("{}({})", self.fn_names[name], rendered.join(", "));
+        if self.fallible.contains(name) {
+            call.push('?');
+        }
+        match sig.ret {
+            Some(_) => format!("{call}.0"),
+            None => call,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    vars: HashMap<String, String>,
+    fns: HashMap<String, String>,
+}
+
+const TS_KEYWORDS: &[&str] = &[
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+const TS_RESERVED_NAMES: &[&str] = &["Record", "isInt", "sameValue"];
+
+const TS_HEADER: &str = "\
+// @generated by `dtl codegen typescript`. Do not edit.
+//
+// Data declarations become tagged unions `{ tag, fields }` (nullary
+// constructors have no `fields`), sorts strings and Int safe integers. Every
+// type has an `isX` validator. Refinement types of defn signatures that only
+// constrain their own value become branded types whose validator checks the
+// refinement. Relations answer from the facts derived when this file was
+// generated.
+";
+
+const TS_IS_INT: &str = "
+function isInt(value: unknown): value is number {
+  return typeof value === \"number\" && Number.isSafeInteger(value);
+}
+";
+
+const TS_SAME_VALUE: &str = "
+function sameValue(a: unknown, b: unknown): boolean {
+  if (Array.isArray(a) && Array.isArray(b)) {
+    return a.length === b.length && a.every((item, i) => sameValue(item, b[i]));
+  }
+  if (typeof a === \"object\" && a !== null && typeof b === \"object\" && b !== null) {
+    const x = a as Record<string, unknown>;
+    const y = b as Record<string, unknown>;
+    const keys = Object.keys(x);
+    return (
+      keys.length === Object.keys(y).length && keys.every((key) => sameValue(x[key], y[key]))
+    );
+  }
+  return a === b;
+}
+";
+
+fn ts_value_ident(name: &str) -> String {
+    let camel = camel_case(name);
+    let mut chars = camel.chars();
+    let mut out = chars
+        .next()
+        .into_iter()
+        .flat_map(char::to_lowercase)
+        .collect::<String>();
+    out.push_str(chars.as_str());
+    if TS_KEYWORDS.contains(&out.as_str()) {
+        out.push('_');
+    }
+    out
+}
+
+fn ts_string(value: &str) -> String {
+    serde_json::to_string(value).expect("strings serialize")
+}
+
+struct Branded {
+    ident: String,
+    validator: String,
+    ty: Type,
+    base: String,
+    check: String,
+    origin: String,
+}
+
+struct TsGen<'a> {
+    decls: Decls<'a>,
+    universes: HashMap<&'a str, &'a [LogicTerm]>,
+    type_names: HashMap<&'a str, String>,
+    validator_names: HashMap<&'a str, String>,
+    fn_names: HashMap<&'a str, String>,
+    types: HashSet<String>,
+    values: HashSet<String>,
+    branded: Vec<Branded>,
+    used_relations: BTreeSet<&'a str>,
+    uses_is_int: bool,
+    uses_same_value: bool,
+}
+
+impl<'a> TsGen<'a> {
+    fn new(program: &'a Program, derived: &'a DerivedFacts) -> Result<Self, Vec<Diagnostic>> {
+        let mut generator = TsGen {
+            decls: Decls::new(program, derived),
+            universes: program
+                .universes
+                .iter()
+                .map(|universe| (universe.ty_name.as_str(), universe.values.as_slice()))
+                .collect(),
+            type_names: HashMap::new(),
+            validator_names: HashMap::new(),
+            fn_names: HashMap::new(),
+            types: HashSet::new(),
+            values: HashSet::new(),
+            branded: Vec::new(),
+            used_relations: BTreeSet::new(),
+            uses_is_int: false,
+            uses_same_value: false,
+        };
+        generator.assign_names()?;
+        generator.assign_branded();
+        Ok(generator)
+    }
+
+    fn assign_names(&mut self) -> Result<(), Vec<Diagnostic>> {
+        let program = self.decls.program;
+        let mut errors = Vec::new();
+        let mut types: HashMap<String, &str> = HashMap::new();
+        let mut values: HashMap<String, &str> = HashMap::new();
+        let mut claim = |space: &mut HashMap<String, &'a str>, ident: String, name: &'a str| {
+            if TS_RESERVED_NAMES.contains(&ident.as_str()) {
+                errors.push(codegen_error(format!(
+                    "{name} becomes the TypeScript name {ident}, which the generated code uses"
+                )));
+            } else if let Some(other) = space.get(&ident) {
+                errors.push(codegen_error(format!(
+                    "{other} and {name} both become the TypeScript name {ident}"
+                )));
+            } else {
+                space.insert(ident, name);
+            }
+        };
+        let type_decls = program
+            .sorts
+            .iter()
+            .map(|sort| sort.name.as_str())
+            .chain(program.data_decls.iter().map(|data| data.name.as_str()));
+        for name in type_decls {
+            let ident = camel_case(name);
+            let validator = format!("is{ident}");
+            claim(&mut types, ident.clone(), name);
+            claim(&mut values, validator.clone(), name);
+            self.type_names.insert(name, ident);
+            self.validator_names.insert(name, validator);
+        }
+        for relation in &program.relations {
+            let ident = ts_value_ident(&relation.name);
+            claim(&mut values, ident.clone(), &relation.name);
+            self.fn_names.insert(&relation.name, ident);
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        self.types = types.into_keys().collect();
+        self.values = values.into_keys().collect();
+        Ok(())
+    }
+
+    fn assign_branded(&mut self) {
+        let program = self.decls.program;
+        for slot in program.defns.iter().flat_map(signature_slots) {
+            self.branded_for(slot.ty, slot.name, slot.origin);
+        }
+    }
+
+    fn branded_for(&mut self, ty: &Type, name: String, origin: String) {
+        let Type::Refine { var, base, formula } = ty else {
+            return;
+        };
+        if self.branded.iter().any(|branded| branded.ty == *ty) {
+            return;
+        }
+        let Some(base_ty) = self.ts_type(base) else {
+            return;
+        };
+        let Some(check) = self.refinement_check(formula, var) else {
+            return;
+        };
+        let mut ident = name.clone();
+        let mut suffix = 2;
+        while TS_RESERVED_NAMES.contains(&ident.as_str())
+            || self.types.contains(&ident)
+            || self.values.contains(&format!("is{ident}"))
+        {
+            ident = format!("{name}{suffix}");
+            suffix += 1;
+        }
+        let validator = format!("is{ident}");
+        self.types.insert(ident.clone());
+        self.values.insert(validator.clone());
+        let guard = self.guard(base, "value");
+        self.branded.push(Branded {
+            ident,
+            validator,
+            ty: ty.clone(),
+            base: base_ty,
+            check: format!("{guard} && {check}"),
+            origin,
+        });
+    }
+
+    fn refinement_check(&mut self, formula: &Formula, var: &str) -> Option<String> {
+        match formula {
+            Formula::True => Some("true".to_string()),
+            Formula::And(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.refinement_check(item, var))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(match items.as_slice() {
+                    [] => "true".to_string(),
+                    [item] => item.clone(),
+                    items => format!("({})", items.join(" && ")),
+                })
+            }
+            Formula::Not(inner) => Some(format!("!{}", self.refinement_check(inner, var)?)),
+            Formula::Atom(atom) => {
+                let operand = |generator: &Self, term: &LogicTerm| match term {
+                    LogicTerm::Var(name) if name == var => Some("value".to_string()),
+                    term => Some(generator.value(&term_value(term)?)),
+                };
+                if let Some(op) = ComparisonOp::from_pred(&atom.pred) {
+                    let [lhs, rhs] = atom.terms.as_slice() else {
+                        return None;
+                    };
+                    if !matches!(lhs, LogicTerm::Int(_) | LogicTerm::Var(_))
+                        || !matches!(rhs, LogicTerm::Int(_) | LogicTerm::Var(_))
+                    {
+                        return None;
+                    }
+                    return Some(format!(
+                        "({} {} {})",
+                        operand(self, lhs)?,
+                        op.as_str(),
+                        operand(self, rhs)?
+                    ));
+                }
+                let args = atom
+                    .terms
+                    .iter()
+                    .map(|term| operand(self, term))
+                    .collect::<Option<Vec<_>>>()?;
+                if atom.pred == DISEQUALITY_PRED {
+                    self.uses_same_value = true;
+                    return Some(format!("!sameValue({})", args.join(", ")));
+                }
+                let (relation, _) = self.decls.relations.get_key_value(atom.pred.as_str())?;
+                self.used_relations.insert(relation);
+                Some(format!("{}({})", self.fn_names[relation], args.join(", ")))
+            }
+        }
+    }
+
+    fn ts_type(&self, ty: &Type) -> Option<String> {
+        match base_type(ty) {
+            Type::Bool => Some("boolean".to_string()),
+            Type::Int => Some("number".to_string()),
+            Type::Symbol => Some("string".to_string()),
+            Type::Domain(name) | Type::Adt(name) => self.type_names.get(name.as_str()).cloned(),
+            Type::Fun(..) | Type::Refine { .. } | Type::Error => None,
+        }
+    }
+
+    fn guard(&mut self, ty: &Type, expr: &str) -> String {
+        match base_type(ty) {
+            Type::Bool => format!("typeof {expr} === \"boolean\""),
+            Type::Int => {
+                self.uses_is_int = true;
+                format!("isInt({expr})")
+            }
+            Type::Domain(name) | Type::Adt(name) => {
+                format!("{}({expr})", self.validator_names[name.as_str()])
+            }
+            Type::Symbol | Type::Fun(..) | Type::Refine { .. } | Type::Error => {
+                format!("typeof {expr} === \"string\"")
+            }
+        }
+    }
+
+    fn value(&self, value: &Value) -> String {
+        match value {
+            Value::Int(value) => value.to_string(),
+            Value::Bool(value) => value.to_string(),
+            Value::Symbol(symbol) => ts_string(symbol.as_str()),
+            Value::Adt { ctor, fields } if fields.is_empty() => {
+                format!("{{ tag: {} }}", ts_string(ctor))
+            }
+            Value::Adt { ctor, fields } => format!(
+                "{{ tag: {}, fields: [{}] }}",
+                ts_string(ctor),
+                fields
+                    .iter()
+                    .map(|field| self.value(field))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    fn render(&mut self) -> String {
+        let program = self.decls.program;
+        let mut body = String::new();
+        for sort in &program.sorts {
+            let ident = &self.type_names[sort.name.as_str()];
+            let validator = &self.validator_names[sort.name.as_str()];
+            let universe = self.universes.get(sort.name.as_str()).map(|values| {
+                values
+                    .iter()
+                    .filter_map(|term| match term {
+                        LogicTerm::Symbol(name) => Some(ts_string(name)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            });
+            let check = match universe {
+                Some(values) => format!(
+                    "typeof value === \"string\" && [{}].includes(value)",
+                    values.join(", ")
+                ),
+                None => "typeof value === \"string\"".to_string(),
+            };
+            let _ = write!(
+                body,
+                "\n/** Values of sort `{}`. */\n\
+                 export type {ident} = string;\n\n\
+                 export function {validator}(value: unknown): value is {ident} {{\n  \
+                     return {check};\n\
+                 }}\n",
+                sort.name
+            );
+        }
+        for data in &program.data_decls {
+            let ident = self.type_names[data.name.as_str()].clone();
+            let validator = self.validator_names[data.name.as_str()].clone();
+            let mut variants = Vec::new();
+            let mut cases = String::new();
+            for ctor in &data.constructors {
+                let tag = ts_string(&ctor.name);
+                if ctor.fields.is_empty() {
+                    variants.push(format!("  | {{ tag: {tag} }}"));
+                    let _ = write!(
+                        cases,
+                        "    case {tag}:\n      return fields === undefined;\n"
+                    );
+                    continue;
+                }
+                let field_types = ctor
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        self.ts_type(field)
+                            .expect("constructor fields have first-order types")
+                    })
+                    .collect::<Vec<_>>();
+                variants.push(format!(
+                    "  | {{ tag: {tag}; fields: [{}] }}",
+                    field_types.join(", ")
+                ));
+                let mut checks = vec![
+                    "Array.isArray(fields)".to_string(),
+                    format!("fields.length === {}", ctor.fields.len()),
+                ];
+                for (idx, field) in ctor.fields.iter().enumerate() {
+                    checks.push(self.guard(field, &format!("fields[{idx}]")));
+                }
+                let _ = write!(
+                    cases,
+                    "    case {tag}:\n      return {};\n",
+                    checks.join(" && ")
+                );
+            }
+            let _ = write!(
+                body,
+                "\n/** `data {}`. */\n\
+                 export type {ident} =\n{};\n\n\
+                 export function {validator}(value: unknown): value is {ident} {{\n  \
+                     if (typeof value !== \"object\" || value === null) {{\n    \
+                         return false;\n  \
+                     }}\n  \
+                     const {{ tag, fields }} = value as {{ tag?: unknown; fields?: unknown }};\n  \
+                     switch (tag) {{\n\
+                 {cases}    \
+                         default:\n      \
+                             return false;\n  \
+                     }}\n\
+                 }}\n",
+                data.name,
+                variants.join("\n"),
+            );
+        }
+        for branded in &self.branded {
+            let Branded {
+                ident,
+                validator,
+                ty,
+                base,
+                check,
+                origin,
+            } = branded;
+            let _ = write!(
+                body,
+                "\n/** `{}`: {origin}. */\n\
+                 export type {ident} = {base} & {{ readonly __refinement: {} }};\n\n\
+                 export function {validator}(value: unknown): value is {ident} {{\n  \
+                     return {check};\n\
+                 }}\n",
+                render_type(ty),
+                ts_string(ident),
+            );
+        }
+        for name in self.used_relations.clone() {
+            body.push('\n');
+            body.push_str(&self.render_relation(name));
+        }
+
+        let mut out = String::from(TS_HEADER);
+        if self.uses_is_int {
+            out.push_str(TS_IS_INT);
+        }
+        if self.uses_same_value {
+            out.push_str(TS_SAME_VALUE);
+        }
+        out.push_str(&body);
+        out
+    }
+
+    fn render_relation(&mut self, name: &str) -> String {
+        self.uses_same_value = true;
+        let types = self.decls.relations[name]
+            .iter()
+            .map(|sort| {
+                self.ts_type(&named_type(sort))
+                    .expect("relation columns have first-order types")
+            })
+            .collect::<Vec<_>>();
+        let params = types
+            .iter()
+            .enumerate()
+            .map(|(idx, ty)| format!("a{idx}: {ty}"))
+            .collect::<Vec<_>>();
+        let args = (0..types.len())
+            .map(|idx| format!("a{idx}"))
+            .collect::<Vec<_>>();
+        let rows = self
+            .decls
+            .relation_rows(name)
+            .map(|row| {
+                let values = row
+                    .iter()
+                    .map(|value| self.value(value))
+                    .collect::<Vec<_>>();
+                format!("    [{}],\n", values.join(", "))
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "/** Facts of `{name}` derived when this file was generated. */\n\
+             export function {}({}): boolean {{\n  \
+                 const rows: [{}][] = [\n{}  ];\n  \
+                 return rows.some((row) => sameValue(row, [{}]));\n\
+             }}\n",
+            self.fn_names[name],
+            params.join(", "),
+            types.join(", "),
+            rows.concat(),
+            args.join(", "),
+        )
+    }
+}
+
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+fn schema_ref(name: &str) -> serde_json::Value {
+    let pointer = name.replace('~', "~0").replace('/', "~1");
+    let mut fragment = String::from("#/$defs/");
+    for byte in pointer.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&byte) {
+            fragment.push(byte as char);
+        } else {
+            let _ = write!(fragment, "%{byte:02X}");
+        }
+    }
+    json!({ "$ref": fragment })
+}
+
+fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Int(value) => json!(value),
+        Value::Bool(value) => json!(value),
+        Value::Symbol(symbol) => json!(symbol.as_str()),
+        Value::Adt { ctor, fields } if fields.is_empty() => json!({ "tag": ctor }),
+        Value::Adt { ctor, fields } => json!({
+            "tag": ctor,
+            "fields": fields.iter().map(json_value).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+struct SchemaGen<'a> {
+    decls: Decls<'a>,
+    universes: HashMap<&'a str, &'a [LogicTerm]>,
+}
+
+impl<'a> SchemaGen<'a> {
+    fn render(&self) -> String {
+        let program = self.decls.program;
+        let mut defs = serde_json::Map::new();
+        for sort in &program.sorts {
+            let mut schema = json!({
+                "description": format!("Values of sort `{}`.", sort.name),
+                "type": "string",
+            });
+            if let Some(values) = self.universes.get(sort.name.as_str()) {
+                schema["enum"] = values
+                    .iter()
+                    .filter_map(|term| match term {
+                        LogicTerm::Symbol(name) => Some(json!(name)),
+                        _ => None,
+                    })
+                    .collect();
+            }
+            defs.insert(sort.name.clone(), schema);
+        }
+        for data in &program.data_decls {
+            let variants = data
+                .constructors
+                .iter()
+                .map(|ctor| {
+                    if ctor.fields.is_empty() {
+                        return json!({
+                            "type": "object",
+                            "properties": { "tag": { "const": ctor.name } },
+                            "required": ["tag"],
+                            "additionalProperties": false,
+                        });
+                    }
+                    json!({
+                        "type": "object",
+                        "properties": {
+                            "tag": { "const": ctor.name },
+                            "fields": {
+                                "type": "array",
+                                "prefixItems": ctor
+                                    .fields
+                                    .iter()
+                                    .map(|field| self.type_schema(field))
+                                    .collect::<Vec<_>>(),
+                                "minItems": ctor.fields.len(),
+                                "items": false,
+                            },
+                        },
+                        "required": ["tag", "fields"],
+                        "additionalProperties": false,
+                    })
+                })
+                .collect::<Vec<_>>();
+            defs.insert(
+                data.name.clone(),
+                json!({
+                    "description": format!("`data {}`.", data.name),
+                    "oneOf": variants,
+                }),
+            );
+        }
+
+        let mut refinements: Vec<&Type> = Vec::new();
+        for slot in program.defns.iter().flat_map(signature_slots) {
+            let Type::Refine { var, base, formula } = slot.ty else {
+                continue;
+            };
+            if refinements.contains(&slot.ty) {
+                continue;
+            }
+            let Some(constraints) = self.constraints(formula, var) else {
+                continue;
+            };
+            refinements.push(slot.ty);
+            let mut schema = self.type_schema(base);
+            schema["description"] = json!(format!("`{}`: {}.", render_type(slot.ty), slot.origin));
+            let mut all_of = Vec::new();
+            for constraint in constraints {
+                match constraint {
+                    serde_json::Value::Object(keywords)
+                        if keywords.keys().all(|key| schema.get(key).is_none()) =>
+                    {
+                        schema
+                            .as_object_mut()
+                            .expect("schemas are objects")
+                            .extend(keywords);
+                    }
+                    serde_json::Value::Bool(true) => {}
+                    constraint => all_of.push(constraint),
+                }
+            }
+            if !all_of.is_empty() {
+                schema["allOf"] = json!(all_of);
+            }
+            let mut name = slot.name.clone();
+            let mut suffix = 2;
+            while defs.contains_key(&name) {
+                name = format!("{}{suffix}", slot.name);
+                suffix += 1;
+            }
+            defs.insert(name, schema);
+        }
+
+        let document = json!({
+            "$schema": JSON_SCHEMA_DIALECT,
+            "$comment": "@generated by `dtl codegen jsonschema`. Do not edit.",
+            "$defs": defs,
+        });
+        let mut out = serde_json::to_string_pretty(&document).expect("schemas serialize");
+        out.push('\n');
+        out
+    }
+
+    fn type_schema(&self, ty: &Type) -> serde_json::Value {
+        match base_type(ty) {
+            Type::Bool => json!({ "type": "boolean" }),
+            Type::Int => json!({ "type": "integer" }),
+            Type::Domain(name) | Type::Adt(name) => schema_ref(name),
+            Type::Symbol | Type::Fun(..) | Type::Refine { .. } | Type::Error => {
+                json!({ "type": "string" })
+            }
+        }
+    }
+
+    fn constraints(&self, formula: &Formula, var: &str) -> Option<Vec<serde_json::Value>> {
+        match formula {
+            Formula::And(items) => Some(
+                items
+                    .iter()
+                    .map(|item| self.constraints(item, var))
+                    .collect::<Option<Vec<_>>>()?
+                    .concat(),
+            ),
+            formula => Some(vec![self.constraint(formula, var)?]),
+        }
+    }
+
+    fn constraint(&self, formula: &Formula, var: &str) -> Option<serde_json::Value> {
+        let own = |term: &LogicTerm| matches!(term, LogicTerm::Var(name) if name == var);
+        match formula {
+            Formula::True => Some(json!(true)),
+            Formula::And(_) => Some(json!({ "allOf": self.constraints(formula, var)? })),
+            Formula::Not(inner) => Some(json!({ "not": self.constraint(inner, var)? })),
+            Formula::Atom(atom) => {
+                if let Some(op) = ComparisonOp::from_pred(&atom.pred) {
+                    let (keyword, bound) = match atom.terms.as_slice() {
+                        [lhs, LogicTerm::Int(bound)] if own(lhs) => (op, *bound),
+                        [LogicTerm::Int(bound), rhs] if own(rhs) => (op.flip(), *bound),
+                        [LogicTerm::Int(lhs), LogicTerm::Int(rhs)] => {
+                            return Some(json!(op.holds(*lhs, *rhs)));
+                        }
+                        _ => return None,
+                    };
+                    let keyword = match keyword {
+                        ComparisonOp::Lt => "exclusiveMaximum",
+                        ComparisonOp::Le => "maximum",
+                        ComparisonOp::Gt => "exclusiveMinimum",
+                        ComparisonOp::Ge => "minimum",
+                    };
+                    return Some(json!({ keyword: bound }));
+                }
+                if atom.pred == DISEQUALITY_PRED {
+                    return match atom.terms.as_slice() {
+                        [lhs, rhs] if own(lhs) && own(rhs) => Some(json!(false)),
+                        [lhs, other] | [other, lhs] if own(lhs) => {
+                            Some(json!({ "not": { "const": json_value(&term_value(other)?) } }))
+                        }
+                        [lhs, rhs] => Some(json!(term_value(lhs)? != term_value(rhs)?)),
+                        _ => None,
+                    };
+                }
+                Some(match self.decls.relation_values(atom, var)? {
+                    RelationValues::Ground(holds) => json!(holds),
+                    RelationValues::Values(values) if values.is_empty() => json!(false),
+                    RelationValues::Values(values) => {
+                        json!({ "enum": values.into_iter().map(json_value).collect::<Vec<_>>() })
+                    }
+                })
+            }
+        }
+    }
+}
+
+const SQL_HEADER: &str = "\
+-- @generated by `dtl codegen sql`. Do not edit.
+-- Column arg_N holds argument N. A relation's :key is its table's primary
+-- key; relations without one are sets, keyed by all of their columns.
+";
+
+fn sql_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn sql_value(value: &Value) -> String {
+    match value {
+        Value::Int(value) => value.to_string(),
+        Value::Bool(value) => (if *value { "TRUE" } else { "FALSE" }).to_string(),
+        value => sql_string(&value_to_string(value)),
+    }
+}
+
+fn sql_in(column: &str, values: &[String]) -> String {
+    if values.is_empty() {
+        return "FALSE".to_string();
+    }
+    format!("{column} IN ({})", values.join(", "))
+}
+
+struct SqlGen<'a> {
+    decls: Decls<'a>,
+    universes: HashMap<&'a str, &'a [LogicTerm]>,
+}
+
+impl<'a> SqlGen<'a> {
+    fn render(&self) -> String {
+        let mut out = SQL_HEADER.to_string();
+        let mut skipped = Vec::new();
+        for relation in &self.decls.program.relations {
+            if relation.arg_sorts.is_empty() {
+                skipped.push(&relation.name);
+                continue;
+            }
+            out.push('\n');
+            if let Some(doc) = &relation.doc {
+                for line in doc.lines() {
+                    let _ = writeln!(out, "-- {line}");
+                }
+            }
+            let columns = (1..=relation.arg_sorts.len())
+                .map(|position| sql_ident(&format!("arg_{position}")))
+                .collect::<Vec<_>>();
+            let key = match &relation.key {
+                Some(key) => key
+                    .iter()
+                    .map(|&position| columns[position - 1].clone())
+                    .collect(),
+                None => columns.clone(),
+            };
+            let _ = writeln!(out, "CREATE TABLE {} (", sql_ident(&relation.name));
+            for ((column, sort), refinement) in columns
+                .iter()
+                .zip(&relation.arg_sorts)
+                .zip(&relation.arg_refinements)
+            {
+                let _ = write!(out, "    {column} {} NOT NULL", self.column_type(sort));
+                let mut checks = Vec::from_iter(self.sort_check(column, sort));
+                if let Some(refinement) = refinement {
+                    checks.extend(self.check(&refinement.formula, &refinement.var, column));
+                }
+                for check in checks {
+                    let _ = write!(out, " CHECK ({check})");
+                }
+                out.push_str(",\n");
+            }
+            let _ = writeln!(out, "    PRIMARY KEY ({})\n);", key.join(", "));
+        }
+        if !skipped.is_empty() {
+            out.push_str("\n-- Not generated (a table needs at least one column):\n");
+            for name in skipped {
+                let _ = writeln!(out, "-- - {name}");
+            }
+        }
+        out
+    }
+
+    fn column_type(&self, sort: &str) -> &'static str {
+        match named_type(sort) {
+            Type::Int => "BIGINT",
+            Type::Bool => "BOOLEAN",
+            _ => "TEXT",
+        }
+    }
+
+    fn sort_check(&self, column: &str, sort: &str) -> Option<String> {
+        if let Some(values) = self.universes.get(sort) {
+            let values = values
+                .iter()
+                .filter_map(|term| Some(sql_value(&term_value(term)?)))
+                .collect::<Vec<_>>();
+            return Some(sql_in(column, &values));
+        }
+        let data = self.decls.data.get(sort)?;
+        let values = data
+            .constructors
+            .iter()
+            .map(|ctor| {
+                ctor.fields
+                    .is_empty()
+                    .then(|| sql_string(&format!("({})", ctor.name)))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(sql_in(column, &values))
+    }
+
+    fn check(&self, formula: &Formula, var: &str, column: &str) -> Option<String> {
+        let own = |term: &LogicTerm| matches!(term, LogicTerm::Var(name) if name == var);
+        let operand = |term: &LogicTerm| {
+            if own(term) {
+                Some(column.to_string())
+            } else {
+                Some(sql_value(&term_value(term)?))
+            }
+        };
+        match formula {
+            Formula::True => Some("TRUE".to_string()),
+            Formula::And(items) if items.is_empty() => Some("TRUE".to_string()),
+            Formula::And(items) => Some(
+                items
+                    .iter()
+                    .map(|item| self.check(item, var, column))
+                    .collect::<Option<Vec<_>>>()?
+                    .join(" AND "),
+            ),
+            Formula::Not(inner) => Some(format!("NOT ({})", self.check(inner, var, column)?)),
+            Formula::Atom(atom) => {
+                let op = if atom.pred == DISEQUALITY_PRED {
+                    Some("<>")
+                } else {
+                    ComparisonOp::from_pred(&atom.pred).map(ComparisonOp::as_str)
+                };
+                if let (Some(op), [lhs, rhs]) = (op, atom.terms.as_slice()) {
+                    return Some(format!("{} {op} {}", operand(lhs)?, operand(rhs)?));
+                }
+                Some(match self.decls.relation_values(atom, var)? {
+                    RelationValues::Ground(holds) => {
+                        (if holds { "TRUE" } else { "FALSE" }).to_string()
+                    }
+                    RelationValues::Values(values) => sql_in(
+                        column,
+                        &values.into_iter().map(sql_value).collect::<Vec<_>>(),
+                    ),
+                })
+            }
+        }
+    }
+}
+
+fn base_type(ty: &Type) -> &Type {
+    match ty {
+        Type::Refine { base, .. } => base_type(base),
+        ty => ty,
+    }
+}
+
+fn named_type(name: &str) -> Type {
+    match name {
+        "Bool" => Type::Bool,
+        "Int" => Type::Int,
+        "Symbol" => Type::Symbol,
+        name => Type::Domain(name.to_string()),
+    }
+}
+
+fn term_value(term: &LogicTerm) -> Option<Value> {
+    match term {
+        LogicTerm::Var(_) => None,
+        LogicTerm::Symbol(name) => Some(Value::Symbol(Symbol::intern(name))),
+        LogicTerm::Int(value) => Some(Value::Int(*value)),
+        LogicTerm::Bool(value) => Some(Value::Bool(*value)),
+        LogicTerm::Ctor { name, args } => Some(Value::Adt {
+            ctor: Symbol::intern(name),
+            fields: args.iter().map(term_value).collect::<Option<_>>()?,
+        }),
+    }
+}
+
+fn descendants(exprs: &ExprArena, root: ExprId) -> Vec<ExprId> {
+    let mut out = Vec::new();
+    let mut stack = vec![root];
+    while let Some(id) = stack.pop() {
+        out.push(id);
+        stack.extend(exprs[id].children());
+    }
+    out
+}
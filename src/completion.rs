@@ -0,0 +1,331 @@
+use std::collections::BTreeSet;
+
+use crate::ast::{Expr, ExprArena, ExprId, Param, Pattern, PatternId, Program};
+use crate::diagnostics::Span;
+use crate::grammar::FORMS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CompletionKind {
+    Keyword,
+    Sort,
+    Relation,
+    Defn,
+    Constructor,
+    Constant,
+    Variable,
+}
+
+impl CompletionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CompletionKind::Keyword => "keyword",
+            CompletionKind::Sort => "sort",
+            CompletionKind::Relation => "relation",
+            CompletionKind::Defn => "defn",
+            CompletionKind::Constructor => "constructor",
+            CompletionKind::Constant => "constant",
+            CompletionKind::Variable => "variable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Completion {
+    pub kind: CompletionKind,
+    pub label: String,
+}
+
+const TYPE_KEYWORDS: &[&str] = &["Bool", "Int", "Symbol", "Refine", "->"];
+const FORMULA_KEYWORDS: &[&str] = &["and", "not"];
+const EXPR_KEYWORDS: &[&str] = &["let", "letfn", "if", "match", "true", "false"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    TopLevel,
+    Type,
+    Formula,
+    Fact,
+    Term,
+    Name,
+    Expr,
+    None,
+}
+
+pub fn completions_at(program: &Program, file: &str, offset: usize) -> Vec<Completion> {
+    let cursor = Cursor { file, offset };
+    let mut variables = Vec::new();
+    let context = context_at(program, &cursor, &mut variables);
+
+    let mut out = BTreeSet::new();
+    let mut add = |kind: CompletionKind, label: &str| {
+        out.insert(Completion {
+            kind,
+            label: label.to_string(),
+        });
+    };
+    let keywords: &[&str] = match context {
+        Context::Type => TYPE_KEYWORDS,
+        Context::Formula => FORMULA_KEYWORDS,
+        Context::Expr => EXPR_KEYWORDS,
+        Context::TopLevel | Context::Fact | Context::Term | Context::Name | Context::None => &[],
+    };
+    for keyword in keywords {
+        add(CompletionKind::Keyword, keyword);
+    }
+    if context == Context::TopLevel {
+        for form in FORMS {
+            add(CompletionKind::Keyword, form.head);
+            add(CompletionKind::Keyword, form.japanese);
+        }
+    }
+    if context == Context::Type {
+        for sort in &program.sorts {
+            add(CompletionKind::Sort, &sort.name);
+        }
+        for data in &program.data_decls {
+            add(CompletionKind::Sort, &data.name);
+        }
+    }
+    if matches!(
+        context,
+        Context::Formula | Context::Fact | Context::Name | Context::Expr
+    ) {
+        for relation in &program.relations {
+            add(CompletionKind::Relation, &relation.name);
+        }
+    }
+    if matches!(context, Context::Name | Context::Expr) {
+        for defn in &program.defns {
+            add(CompletionKind::Defn, &defn.name);
+        }
+    }
+    if matches!(
+        context,
+        Context::Formula | Context::Fact | Context::Term | Context::Name | Context::Expr
+    ) {
+        for data in &program.data_decls {
+            for ctor in &data.constructors {
+                add(CompletionKind::Constructor, &ctor.name);
+            }
+        }
+        for constant in &program.consts {
+            add(CompletionKind::Constant, &constant.name);
+        }
+        for name in &variables {
+            add(CompletionKind::Variable, name);
+        }
+    }
+    out.into_iter().collect()
+}
+
+struct Cursor<'a> {
+    file: &'a str,
+    offset: usize,
+}
+
+impl Cursor<'_> {
+    fn in_file(&self, span: &Span) -> bool {
+        span.file_id.as_deref().is_none_or(|id| id == self.file)
+    }
+
+    fn covers(&self, span: &Span) -> bool {
+        self.in_file(span) && span.start <= self.offset && self.offset < span.end
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Form {
+    Import,
+    Alias,
+    Const,
+    Sort,
+    Data,
+    Relation,
+    Fact,
+    Rule,
+    Assert(usize),
+    Universe,
+    Defn(usize),
+}
+
+fn context_at(program: &Program, cursor: &Cursor<'_>, variables: &mut Vec<String>) -> Context {
+    let heads = program
+        .imports
+        .iter()
+        .map(|item| (&item.span, Form::Import))
+        .chain(program.aliases.iter().map(|item| (&item.span, Form::Alias)))
+        .chain(program.consts.iter().map(|item| (&item.span, Form::Const)))
+        .chain(program.sorts.iter().map(|item| (&item.span, Form::Sort)))
+        .chain(
+            program
+                .data_decls
+                .iter()
+                .map(|item| (&item.span, Form::Data)),
+        )
+        .chain(
+            program
+                .relations
+                .iter()
+                .map(|item| (&item.span, Form::Relation)),
+        )
+        .chain(program.facts.iter().map(|item| (&item.span, Form::Fact)))
+        .chain(program.rules.iter().map(|item| (&item.span, Form::Rule)))
+        .chain(
+            program
+                .asserts
+                .iter()
+                .enumerate()
+                .map(|(idx, item)| (&item.span, Form::Assert(idx))),
+        )
+        .chain(
+            program
+                .universes
+                .iter()
+                .map(|item| (&item.span, Form::Universe)),
+        )
+        .chain(
+            program
+                .defns
+                .iter()
+                .enumerate()
+                .map(|(idx, item)| (&item.span, Form::Defn(idx))),
+        );
+    let Some((head, form)) = heads
+        .filter(|(span, _)| cursor.in_file(span) && span.start <= cursor.offset)
+        .max_by_key(|(span, _)| span.start)
+    else {
+        return Context::TopLevel;
+    };
+    if cursor.offset <= head.end {
+        return Context::TopLevel;
+    }
+
+    match form {
+        Form::Import => Context::None,
+        Form::Sort => Context::TopLevel,
+        Form::Data | Form::Relation => Context::Type,
+        Form::Alias => Context::Name,
+        Form::Universe | Form::Const => Context::Term,
+        Form::Fact => Context::Fact,
+        Form::Rule => Context::Formula,
+        Form::Assert(idx) => {
+            let assertion = &program.asserts[idx];
+            if assertion.params.iter().any(|p| cursor.covers(&p.span)) {
+                return Context::Type;
+            }
+            variables.extend(assertion.params.iter().map(|p| p.name.clone()));
+            Context::Formula
+        }
+        Form::Defn(idx) => {
+            let defn = &program.defns[idx];
+            let exprs = &program.exprs;
+            let body = exprs.span(defn.body);
+            if defn.params.iter().any(|p| cursor.covers(&p.span)) {
+                Context::Type
+            } else if let Some(clause) = defn
+                .clauses
+                .iter()
+                .find(|clause| cursor.covers(exprs.span(clause.body)))
+            {
+                variables.extend(defn.params.iter().map(|p| p.name.clone()));
+                for pattern in &clause.patterns {
+                    collect_pattern_vars(exprs, *pattern, variables);
+                }
+                collect_scope(exprs, clause.body, cursor, variables);
+                Context::Expr
+            } else if cursor.covers(body) {
+                variables.extend(defn.params.iter().map(|p| p.name.clone()));
+                collect_scope(exprs, defn.body, cursor, variables);
+                Context::Expr
+            } else if let Some(measure) = defn
+                .measure
+                .filter(|measure| cursor.covers(exprs.span(*measure)))
+            {
+                variables.extend(defn.params.iter().map(|p| p.name.clone()));
+                collect_scope(exprs, measure, cursor, variables);
+                Context::Expr
+            } else if cursor.offset >= body.end {
+                Context::TopLevel
+            } else if after_params(&defn.params, cursor) {
+                Context::Type
+            } else {
+                Context::None
+            }
+        }
+    }
+}
+
+fn after_params(params: &[Param], cursor: &Cursor<'_>) -> bool {
+    params
+        .last()
+        .is_none_or(|param| cursor.offset >= param.span.end)
+}
+
+fn collect_scope(exprs: &ExprArena, expr: ExprId, cursor: &Cursor<'_>, out: &mut Vec<String>) {
+    if !cursor.covers(exprs.span(expr)) {
+        return;
+    }
+    match &exprs[expr] {
+        Expr::Var { .. }
+        | Expr::Symbol { .. }
+        | Expr::Int { .. }
+        | Expr::Bool { .. }
+        | Expr::Hole { .. } => {}
+        Expr::Call { args, .. } => {
+            for arg in args {
+                collect_scope(exprs, *arg, cursor, out);
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            for (name, value, span) in bindings {
+                if cursor.covers(span) {
+                    collect_scope(exprs, *value, cursor, out);
+                    return;
+                }
+                if span.end <= cursor.offset {
+                    out.push(name.clone());
+                }
+            }
+            collect_scope(exprs, *body, cursor, out);
+        }
+        Expr::LetFn { fns, body, .. } => {
+            if let Some(f) = fns.iter().find(|f| cursor.covers(exprs.span(f.body))) {
+                out.extend(f.params.iter().map(|p| p.name.clone()));
+                collect_scope(exprs, f.body, cursor, out);
+                return;
+            }
+            collect_scope(exprs, *body, cursor, out);
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            for branch in [cond, then_branch, else_branch] {
+                collect_scope(exprs, *branch, cursor, out);
+            }
+        }
+        Expr::Match {
+            scrutinee, arms, ..
+        } => {
+            collect_scope(exprs, *scrutinee, cursor, out);
+            if let Some(arm) = arms.iter().find(|arm| cursor.covers(exprs.span(arm.body))) {
+                collect_pattern_vars(exprs, arm.pattern, out);
+                collect_scope(exprs, arm.body, cursor, out);
+            }
+        }
+    }
+}
+
+fn collect_pattern_vars(exprs: &ExprArena, pattern: PatternId, out: &mut Vec<String>) {
+    match &exprs[pattern] {
+        Pattern::Var { name, .. } => out.push(name.clone()),
+        Pattern::Ctor { args, .. } => {
+            for arg in args {
+                collect_pattern_vars(exprs, *arg, out);
+            }
+        }
+        _ => {}
+    }
+}
@@ -1,6 +1,9 @@
+use std::collections::HashSet;
 use std::fmt;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -17,6 +20,21 @@ pub struct Diagnostic {
     pub source: Option<String>,
     pub reason: Option<&'static str>,
     pub arg_indices: Option<Vec<usize>>,
+    pub suggestion: Option<String>,
+    pub related: Vec<RelatedSpan>,
+    pub fixes: Vec<TextEdit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelatedSpan {
+    pub span: Span,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub replacement: String,
 }
 
 impl Diagnostic {
@@ -28,11 +46,19 @@ impl Diagnostic {
             source: None,
             reason: None,
             arg_indices: None,
+            suggestion: None,
+            related: Vec::new(),
+            fixes: Vec::new(),
         }
     }
 
-    pub fn hint(&self) -> Option<&'static str> {
-        hint_for_code(self.code)
+    pub fn hint(&self) -> Option<String> {
+        let base = hint_for_code(self.code);
+        match (&self.suggestion, base) {
+            (Some(name), Some(base)) => Some(format!("`{name}` のことですか？ {base}")),
+            (Some(name), None) => Some(format!("`{name}` のことですか？")),
+            (None, base) => base.map(str::to_string),
+        }
     }
 
     pub fn with_source(mut self, source: impl Into<String>) -> Self {
@@ -61,6 +87,46 @@ impl Diagnostic {
     pub fn arg_indices(&self) -> Option<&[usize]> {
         self.arg_indices.as_deref()
     }
+
+    pub fn with_suggestion(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+
+    pub fn suggestion(&self) -> Option<&str> {
+        self.suggestion.as_deref()
+    }
+
+    pub fn with_related(mut self, span: Span, label: impl Into<String>) -> Self {
+        self.related.push(RelatedSpan {
+            span,
+            label: label.into(),
+        });
+        self
+    }
+
+    pub fn related(&self) -> &[RelatedSpan] {
+        &self.related
+    }
+
+    pub fn with_fix(mut self, span: Span, replacement: impl Into<String>) -> Self {
+        self.fixes.push(TextEdit {
+            span,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    pub fn with_suggestion_fix(self, span: &Span) -> Self {
+        match self.suggestion.clone() {
+            Some(name) => self.with_fix(span.clone(), name),
+            None => self,
+        }
+    }
+
+    pub fn fixes(&self) -> &[TextEdit] {
+        &self.fixes
+    }
 }
 
 impl fmt::Display for Diagnostic {
@@ -78,6 +144,15 @@ impl fmt::Display for Diagnostic {
         } else {
             write!(f, "{}: {}", self.code, self.message)?;
         }
+        for related in &self.related {
+            write!(f, " ({} at ", related.label)?;
+            if let Some(file_id) = &related.span.file_id
+                && self.source() != Some(file_id.as_str())
+            {
+                write!(f, "{file_id}:")?;
+            }
+            write!(f, "{}:{})", related.span.line, related.span.column)?;
+        }
         if let Some(hint) = hint {
             write!(f, " (hint: {hint})")?;
         }
@@ -89,7 +164,11 @@ pub fn hint_for_code(code: &str) -> Option<&'static str> {
     match code {
         "E-IO" => Some("入力ファイルのパスと読み取り権限を確認してください。"),
         "E-IMPORT" => Some("import パスと循環依存の有無を確認してください。"),
+        "E-CONFIG" => Some("`dtl.toml` の構文と `[aliases]` の記述を確認してください。"),
         "E-PARSE" => Some("S式の括弧対応とフォーム構造を確認してください。"),
+        "E-EDITION" => Some(
+            "`; edition:` pragma の値を確認し、使用している機能が導入された edition 以降を指定してください。",
+        ),
         "E-SYNTAX-AUTO" => Some(
             "Core/Surface が混在しています。`; syntax: core` または `; syntax: surface` を明示し、1ファイル内の構文を統一してください。",
         ),
@@ -99,15 +178,24 @@ pub fn hint_for_code(code: &str) -> Option<&'static str> {
         "E-ENTAIL" => {
             Some("Refinement の前提事実・規則を追加し、含意が導出可能か確認してください。")
         }
+        "E-KEY" => Some(
+            "relation の `:key` の位置が同じで残りの引数が異なる fact や rule の導出を取り除いてください。",
+        ),
         "E-TOTAL" => Some(
-            "再帰は tail position かつ ADT 引数の構造減少が必要です。相互再帰は SCC 内の全再帰エッジで同条件を満たす場合のみ許可されます。",
+            "再帰呼び出しには ADT 引数の構造減少が必要です。相互再帰は SCC 内の全再帰エッジが共通の辞書式順序で減少する場合のみ許可されます。",
         ),
         "E-DATA" => Some("data 宣言の重複・再帰・constructor の整合性を確認してください。"),
         "E-MATCH" => Some("match の網羅性・到達不能分岐・パターン型整合性を確認してください。"),
+        "E-HOLE" => {
+            Some("型付きホール `_?` を、期待型に合う式（候補を参照）で置き換えてください。")
+        }
         "E-PROVE" => Some("universe と証明義務を確認し、反例トレースを参照して修正してください。"),
         "E-FMT-SELFDOC-UNSUPPORTED" => {
             Some("selfdoc フォームは fmt 非対応です。selfdoc 生成物を直接整形しないでください。")
         }
+        "W-FMT-COMMENT" => Some(
+            "ブロックコメントや `#;` を含む箇所は書かれたまま残しました。Surface 構文ではコメントをフォームの外へ移すと整形されます。Core 構文のファイルは Surface 構文へ移行するか、これらのコメントを除くと整形されます。",
+        ),
         "E-SELFDOC-CONFIG" => Some("`.dtl-selfdoc.toml` の構文と必須項目を確認してください。"),
         "E-SELFDOC-SCAN" => {
             Some("scan.include/exclude と .gitignore の組み合わせを確認してください。")
@@ -119,14 +207,70 @@ pub fn hint_for_code(code: &str) -> Option<&'static str> {
         "E-SELFDOC-CONTRACT" => Some(
             "README.md または language-spec に selfdoc 契約テーブル（`<!-- selfdoc:cli-contracts:start -->`）を定義してください。",
         ),
-        "E-SELFDOC-GATE" => Some("workflow YAML の jobs/steps/run 記述を確認してください。"),
-        "E-SELFCHECK" => {
-            Some("selfcheck の claim_coverage が 100% になるよう契約テーブルを補完してください。")
-        }
+        "E-SELFDOC-GATE" => Some(
+            "workflow YAML の jobs/steps/run 記述、または package.json の構文を確認してください。",
+        ),
+        "E-SELFDOC-MANIFEST" => Some("Cargo.toml が TOML として正しいか確認してください。"),
+        "E-SELFDOC-BASELINE" => Some(
+            "--baseline には以前の selfdoc / selfcheck の --out ディレクトリ（selfdoc.generated.dtl を含む）を指定してください。",
+        ),
+        "E-SELFCHECK" => Some(
+            "契約テーブルを補完するか、--min-coverage / [selfcheck] allow_unproved で要求を調整してください。",
+        ),
         _ => None,
     }
 }
 
+pub fn closest_name<'a>(
+    target: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    let target_len = target.chars().count();
+    let candidates = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != target);
+    if target_len <= 2 {
+        let folded = target.to_lowercase();
+        return candidates
+            .filter(|candidate| candidate.to_lowercase() == folded)
+            .min()
+            .map(str::to_string);
+    }
+    let max_distance = target_len / 3;
+    candidates
+        .map(|candidate| (edit_distance(target, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Drops diagnostics that repeat an earlier one's code, span and message,
+/// keeping the first. A macro argument substituted several times is checked
+/// once per copy, and every copy keeps the argument's own span; span-less
+/// diagnostics are never merged.
+pub(crate) fn dedup_diagnostics(diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen = HashSet::new();
+    diagnostics.retain(|diag| {
+        diag.span.is_none() || seen.insert((diag.code, diag.span.clone(), diag.message.clone()))
+    });
+}
+
 pub fn line_col(src: &str, offset: usize) -> (usize, usize) {
     let mut line = 1usize;
     let mut col = 1usize;
@@ -158,3 +302,43 @@ pub fn make_span_with_file(src: &str, start: usize, end: usize, file_id: Option<
         file_id: file_id.map(str::to_string),
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(src: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(
+            src.bytes()
+                .enumerate()
+                .filter(|(_, byte)| *byte == b'\n')
+                .map(|(idx, _)| idx + 1),
+        );
+        Self { line_starts }
+    }
+
+    pub fn line_col(&self, src: &str, offset: usize) -> (usize, usize) {
+        let offset = offset.min(src.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let start = self.line_starts[line - 1];
+        let column = src[start..]
+            .char_indices()
+            .take_while(|(idx, _)| start + idx < offset)
+            .count();
+        (line, column + 1)
+    }
+
+    pub fn span(&self, src: &str, start: usize, end: usize) -> Span {
+        let (line, column) = self.line_col(src, start);
+        Span {
+            start,
+            end,
+            line,
+            column,
+            file_id: None,
+        }
+    }
+}
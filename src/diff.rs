@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ast::Program;
+use crate::diagnostics::Diagnostic;
+use crate::lint::{
+    AlphaState, normalize_assert, normalize_defn, normalize_fact, normalize_logic_term,
+    normalize_rule, normalize_type,
+};
+use crate::name_resolve::normalize_program_aliases;
+use crate::slice::{SliceTarget, slice_program};
+use crate::types::Type;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeclKind {
+    Sort,
+    Data,
+    Relation,
+    Fact,
+    Rule,
+    Universe,
+    Assert,
+    Defn,
+}
+
+impl DeclKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeclKind::Sort => "sort",
+            DeclKind::Data => "data",
+            DeclKind::Relation => "relation",
+            DeclKind::Fact => "fact",
+            DeclKind::Rule => "rule",
+            DeclKind::Universe => "universe",
+            DeclKind::Assert => "assert",
+            DeclKind::Defn => "defn",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeclChange {
+    pub kind: DeclKind,
+    pub name: String,
+    pub change: ChangeKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramDiff {
+    pub changes: Vec<DeclChange>,
+    pub affected_obligations: Vec<String>,
+}
+
+impl ProgramDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+type DeclKey = (DeclKind, String);
+
+pub fn diff_programs(old: &Program, new: &Program) -> Result<ProgramDiff, Vec<Diagnostic>> {
+    let old = normalize_program_aliases(old)?;
+    let new = normalize_program_aliases(new)?;
+    let before = declarations(&old);
+    let after = declarations(&new);
+
+    let keys = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+    let changes = keys
+        .into_iter()
+        .filter_map(|key| {
+            let change = match (before.get(key), after.get(key)) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(a), Some(b)) if a != b => ChangeKind::Changed,
+                _ => return None,
+            };
+            Some(DeclChange {
+                kind: key.0,
+                name: key.1.clone(),
+                change,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let changed = changes
+        .iter()
+        .map(|change| (change.kind, change.name.clone()))
+        .collect::<BTreeSet<_>>();
+    let mut affected_obligations = Vec::new();
+    for (id, target) in obligation_targets(&new) {
+        let mut touched = is_touched(&new, &target, &changed)?;
+        if !touched && has_target(&old, &target) {
+            touched = is_touched(&old, &target, &changed)?;
+        }
+        if touched {
+            affected_obligations.push(id);
+        }
+    }
+
+    Ok(ProgramDiff {
+        changes,
+        affected_obligations,
+    })
+}
+
+fn declarations(program: &Program) -> BTreeMap<DeclKey, Vec<String>> {
+    let mut out = BTreeMap::<DeclKey, Vec<String>>::new();
+    let mut push = |kind: DeclKind, name: &str, key: String| {
+        out.entry((kind, name.to_string())).or_default().push(key);
+    };
+    for sort in &program.sorts {
+        push(DeclKind::Sort, &sort.name, String::new());
+    }
+    for data in &program.data_decls {
+        let ctors = data
+            .constructors
+            .iter()
+            .map(|ctor| {
+                let fields = ctor
+                    .fields
+                    .iter()
+                    .map(normalize_type)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!("({} {fields})", ctor.name)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        push(DeclKind::Data, &data.name, ctors);
+    }
+    for relation in &program.relations {
+        let args = relation
+            .arg_sorts
+            .iter()
+            .zip(&relation.arg_refinements)
+            .map(|(sort, refinement)| match refinement {
+                Some(refinement) => normalize_type(&Type::Refine {
+                    var: refinement.var.clone(),
+                    base: Box::new(Type::Domain(sort.clone())),
+                    formula: refinement.formula.clone(),
+                }),
+                None => sort.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        push(DeclKind::Relation, &relation.name, args);
+    }
+    for fact in &program.facts {
+        push(DeclKind::Fact, &fact.name, normalize_fact(fact));
+    }
+    for rule in &program.rules {
+        push(DeclKind::Rule, &rule.head.pred, normalize_rule(rule));
+    }
+    for universe in &program.universes {
+        let mut values = universe
+            .values
+            .iter()
+            .map(|value| normalize_logic_term(value, &mut AlphaState::default()))
+            .collect::<Vec<_>>();
+        values.sort();
+        push(DeclKind::Universe, &universe.ty_name, values.join(" "));
+    }
+    for assertion in &program.asserts {
+        push(
+            DeclKind::Assert,
+            &assertion.name,
+            normalize_assert(assertion),
+        );
+    }
+    for defn in &program.defns {
+        push(
+            DeclKind::Defn,
+            &defn.name,
+            normalize_defn(&program.exprs, defn),
+        );
+    }
+    for keys in out.values_mut() {
+        keys.sort();
+    }
+    out
+}
+
+fn obligation_targets(program: &Program) -> Vec<(String, SliceTarget)> {
+    let defns = program
+        .defns
+        .iter()
+        .filter(|defn| matches!(defn.ret_type, Type::Refine { .. }))
+        .map(|defn| {
+            (
+                format!("defn::{}", defn.name),
+                SliceTarget::Defn(defn.name.clone()),
+            )
+        });
+    let asserts = program.asserts.iter().map(|assertion| {
+        (
+            format!("assert::{}", assertion.name),
+            SliceTarget::Assert(assertion.name.clone()),
+        )
+    });
+    defns.chain(asserts).collect()
+}
+
+fn has_target(program: &Program, target: &SliceTarget) -> bool {
+    match target {
+        SliceTarget::Assert(name) => program.asserts.iter().any(|a| &a.name == name),
+        SliceTarget::Defn(name) => program.defns.iter().any(|d| &d.name == name),
+    }
+}
+
+fn is_touched(
+    program: &Program,
+    target: &SliceTarget,
+    changed: &BTreeSet<DeclKey>,
+) -> Result<bool, Vec<Diagnostic>> {
+    let slice = slice_program(program, target)?;
+    let mut touched = declarations(&slice)
+        .into_keys()
+        .any(|key| changed.contains(&key));
+    for relation in &slice.relations {
+        for kind in [DeclKind::Fact, DeclKind::Rule] {
+            touched |= changed.contains(&(kind, relation.name.clone()));
+        }
+    }
+    let types = slice.sorts.iter().map(|sort| &sort.name);
+    for name in types.chain(slice.data_decls.iter().map(|data| &data.name)) {
+        touched |= changed.contains(&(DeclKind::Universe, name.clone()));
+    }
+    Ok(touched)
+}
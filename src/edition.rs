@@ -0,0 +1,180 @@
+use std::fmt;
+
+use crate::diagnostics::{Diagnostic, make_span};
+use crate::parser::shebang_len;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Edition {
+    E2025,
+    E2026,
+    #[default]
+    E2027,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EditionFeature {
+    ProjectSurfaceKeywords,
+    DeclarationTags,
+    SkolemRules,
+    TerminationMeasures,
+    TypedHoles,
+    LocalFunctions,
+    Constants,
+    ClauseDefns,
+    Disequality,
+    RelationRefinements,
+    RelationKeys,
+    RawStrings,
+    MultiLineStrings,
+    BlockComments,
+    DatumComments,
+    Profiles,
+    Macros,
+}
+
+impl Edition {
+    pub const LATEST: Edition = Edition::E2027;
+    pub const ALL: &'static [Edition] = &[Edition::E2025, Edition::E2026, Edition::E2027];
+
+    pub fn parse(text: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|edition| edition.as_str() == text)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Edition::E2025 => "2025",
+            Edition::E2026 => "2026",
+            Edition::E2027 => "2027",
+        }
+    }
+
+    pub fn supports(self, feature: EditionFeature) -> bool {
+        self >= feature.introduced_in()
+    }
+}
+
+impl fmt::Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl EditionFeature {
+    pub const ALL: &'static [EditionFeature] = &[
+        EditionFeature::ProjectSurfaceKeywords,
+        EditionFeature::DeclarationTags,
+        EditionFeature::SkolemRules,
+        EditionFeature::TerminationMeasures,
+        EditionFeature::TypedHoles,
+        EditionFeature::LocalFunctions,
+        EditionFeature::Constants,
+        EditionFeature::ClauseDefns,
+        EditionFeature::Disequality,
+        EditionFeature::RelationRefinements,
+        EditionFeature::RelationKeys,
+        EditionFeature::RawStrings,
+        EditionFeature::MultiLineStrings,
+        EditionFeature::BlockComments,
+        EditionFeature::DatumComments,
+        EditionFeature::Profiles,
+        EditionFeature::Macros,
+    ];
+
+    pub fn introduced_in(self) -> Edition {
+        match self {
+            EditionFeature::ProjectSurfaceKeywords => Edition::E2026,
+            _ => Edition::E2027,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            EditionFeature::ProjectSurfaceKeywords => "project surface keywords",
+            EditionFeature::DeclarationTags => "declaration tags (:doc, :message, attributes)",
+            EditionFeature::SkolemRules => ":skolem",
+            EditionFeature::TerminationMeasures => ":measure / :decreases",
+            EditionFeature::TypedHoles => "typed hole _?",
+            EditionFeature::LocalFunctions => "letfn",
+            EditionFeature::Constants => "defconst",
+            EditionFeature::ClauseDefns => ":clauses",
+            EditionFeature::Disequality => "!=",
+            EditionFeature::RelationRefinements => "Refine in relation signatures",
+            EditionFeature::RelationKeys => "relation :key",
+            EditionFeature::RawStrings => "raw string #\"...\"#",
+            EditionFeature::MultiLineStrings => "multi-line string \"\"\"...\"\"\"",
+            EditionFeature::BlockComments => "block comment #| ... |#",
+            EditionFeature::DatumComments => "datum comment #;",
+            EditionFeature::Profiles => "when-profile / :profile",
+            EditionFeature::Macros => "defmacro",
+        }
+    }
+}
+
+pub(crate) fn source_edition(src: &str) -> Edition {
+    edition_from_pragma(src)
+        .ok()
+        .flatten()
+        .unwrap_or(Edition::LATEST)
+}
+
+pub fn edition_from_pragma(src: &str) -> Result<Option<Edition>, Diagnostic> {
+    let mut offset = shebang_len(src);
+    for line in src[offset..].split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if !trimmed.starts_with(';') {
+            break;
+        }
+        let body = trimmed.trim_start_matches(';').trim();
+        let lower = body.to_ascii_lowercase();
+        let Some(rest) = lower.strip_prefix("edition:") else {
+            continue;
+        };
+        let value = rest.trim();
+        return match Edition::parse(value) {
+            Some(edition) => Ok(Some(edition)),
+            None => {
+                let start = line_start + (line.len() - line.trim_start().len());
+                let end = line_start + line.trim_end().len();
+                Err(Diagnostic::new(
+                    "E-EDITION",
+                    format!(
+                        "unsupported edition: {value} (supported: {})",
+                        Edition::ALL
+                            .iter()
+                            .map(|edition| edition.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    Some(make_span(src, start, end)),
+                ))
+            }
+        };
+    }
+    Ok(None)
+}
+
+pub fn feature_unavailable(
+    src: &str,
+    feature: EditionFeature,
+    edition: Edition,
+    start: usize,
+    end: usize,
+) -> Diagnostic {
+    Diagnostic::new(
+        "E-EDITION",
+        format!(
+            "{} is not available in edition {edition}; it requires edition {} or later",
+            feature.describe(),
+            feature.introduced_in()
+        ),
+        Some(make_span(src, start, end)),
+    )
+}
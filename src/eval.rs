@@ -0,0 +1,895 @@
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{
+    DataDecl, Defn, Expr, ExprArena, ExprId, LocalFn, Param, Pattern, PatternId, Program,
+};
+use crate::diagnostics::Diagnostic;
+use crate::fmt::{render_logic_term, render_pattern, render_type};
+use crate::interval::{ArithOp, ComparisonOp};
+use crate::logic_engine::{DISEQUALITY_PRED, DerivedFacts, Value, disequality_holds};
+use crate::name_resolve::fold_term_consts;
+use crate::parser::parse_term;
+use crate::symbol::Symbol;
+use crate::typecheck::checked_and_solved;
+use crate::types::{Formula, LogicTerm, Type};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FunctionValue {
+    pub table: Vec<(Vec<EvalValue>, EvalValue)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EvalValue {
+    Symbol(String),
+    Int(i64),
+    Bool(bool),
+    Adt {
+        ctor: String,
+        fields: Vec<EvalValue>,
+    },
+    Function(FunctionValue),
+}
+
+impl EvalValue {
+    pub fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Symbol(s) => EvalValue::Symbol(s.to_string()),
+            Value::Int(i) => EvalValue::Int(*i),
+            Value::Bool(b) => EvalValue::Bool(*b),
+            Value::Adt { ctor, fields } => EvalValue::Adt {
+                ctor: ctor.to_string(),
+                fields: fields.iter().map(EvalValue::from_value).collect(),
+            },
+        }
+    }
+
+    pub fn to_value(&self) -> Option<Value> {
+        match self {
+            EvalValue::Symbol(s) => Some(Value::Symbol(Symbol::intern(s))),
+            EvalValue::Int(i) => Some(Value::Int(*i)),
+            EvalValue::Bool(b) => Some(Value::Bool(*b)),
+            EvalValue::Adt { ctor, fields } => Some(Value::Adt {
+                ctor: Symbol::intern(ctor),
+                fields: fields
+                    .iter()
+                    .map(EvalValue::to_value)
+                    .collect::<Option<Vec<_>>>()?,
+            }),
+            EvalValue::Function(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for EvalValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalValue::Symbol(s) => write!(f, "{s}"),
+            EvalValue::Int(i) => write!(f, "{i}"),
+            EvalValue::Bool(b) => write!(f, "{b}"),
+            EvalValue::Adt { ctor, fields } => {
+                write!(f, "({ctor}")?;
+                for field in fields {
+                    write!(f, " {field}")?;
+                }
+                write!(f, ")")
+            }
+            EvalValue::Function(_) => write!(f, "<function>"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalLimits {
+    pub max_depth: usize,
+    pub max_steps: u64,
+}
+
+impl EvalLimits {
+    pub const MAX_DEPTH: usize = 65_536;
+}
+
+// Stack the evaluation thread reserves per level of nesting, about twice what
+// a debug build uses, and for the frames around it.
+const STACK_PER_LEVEL: usize = 8 * 1024;
+const STACK_BASE: usize = 256 * 1024;
+
+thread_local! {
+    static EVAL_STACK_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn with_eval_stack<R: Send>(max_depth: usize, f: impl FnOnce() -> R + Send) -> R {
+    let max_depth = max_depth.min(EvalLimits::MAX_DEPTH);
+    if EVAL_STACK_DEPTH.get() >= max_depth {
+        return f();
+    }
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .name("dtl-eval".to_string())
+            .stack_size(STACK_BASE + max_depth * STACK_PER_LEVEL)
+            .spawn_scoped(scope, || {
+                EVAL_STACK_DEPTH.set(max_depth);
+                f()
+            })
+            .expect("failed to spawn the evaluation thread")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+impl Default for EvalLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 1024,
+            max_steps: 1_000_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    DepthLimit(usize),
+    StepLimit(u64),
+    Overflow { op: ArithOp, lhs: i64, rhs: i64 },
+    Stuck(String),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::DepthLimit(limit) => {
+                write!(f, "evaluation exceeded the depth limit of {limit}")
+            }
+            EvalError::StepLimit(limit) => {
+                write!(f, "evaluation exceeded the step limit of {limit}")
+            }
+            EvalError::Overflow { op, lhs, rhs } => {
+                write!(f, "integer overflow in ({} {lhs} {rhs})", op.as_str())
+            }
+            EvalError::Stuck(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation {
+    pub value: EvalValue,
+    pub steps: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EvalTrace {
+    pub max_depth: Option<usize>,
+    pub steps: Vec<TraceStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    pub depth: usize,
+    pub event: TraceEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    Call {
+        name: String,
+        args: Vec<(String, EvalValue)>,
+    },
+    Return {
+        name: String,
+        value: EvalValue,
+    },
+    Arm {
+        target: EvalValue,
+        index: usize,
+        pattern: String,
+        bindings: Vec<(String, EvalValue)>,
+    },
+    Let {
+        name: String,
+        value: EvalValue,
+    },
+    If {
+        condition: bool,
+    },
+    Fact {
+        relation: String,
+        args: Vec<EvalValue>,
+        holds: bool,
+    },
+}
+
+impl TraceEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TraceEvent::Call { .. } => "call",
+            TraceEvent::Return { .. } => "return",
+            TraceEvent::Arm { .. } => "match",
+            TraceEvent::Let { .. } => "let",
+            TraceEvent::If { .. } => "if",
+            TraceEvent::Fact { .. } => "fact",
+        }
+    }
+}
+
+fn write_bindings(f: &mut fmt::Formatter<'_>, bindings: &[(String, EvalValue)]) -> fmt::Result {
+    for (name, value) in bindings {
+        write!(f, " {name}={value}")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceEvent::Call { name, args } => {
+                write!(f, "call {name}")?;
+                write_bindings(f, args)
+            }
+            TraceEvent::Return { name, value } => write!(f, "return {name} = {value}"),
+            TraceEvent::Arm {
+                target,
+                index,
+                pattern,
+                bindings,
+            } => {
+                write!(f, "match {target} -> arm {index} {pattern}")?;
+                write_bindings(f, bindings)
+            }
+            TraceEvent::Let { name, value } => write!(f, "let {name} = {value}"),
+            TraceEvent::If { condition } => {
+                let branch = if *condition { "then" } else { "else" };
+                write!(f, "if {condition} -> {branch}")
+            }
+            TraceEvent::Fact {
+                relation,
+                args,
+                holds,
+            } => {
+                write!(f, "fact ({relation}")?;
+                for arg in args {
+                    write!(f, " {arg}")?;
+                }
+                write!(f, ") = {holds}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:width$}{}", "", self.event, width = self.depth * 2)
+    }
+}
+
+pub fn evaluate(
+    program: &Program,
+    application: &str,
+    limits: EvalLimits,
+) -> Result<Evaluation, Vec<Diagnostic>> {
+    evaluate_with(program, application, limits, None)
+}
+
+pub fn evaluate_traced(
+    program: &Program,
+    application: &str,
+    limits: EvalLimits,
+    trace: &mut EvalTrace,
+) -> Result<Evaluation, Vec<Diagnostic>> {
+    evaluate_with(program, application, limits, Some(trace))
+}
+
+fn evaluate_with(
+    program: &Program,
+    application: &str,
+    limits: EvalLimits,
+    trace: Option<&mut EvalTrace>,
+) -> Result<Evaluation, Vec<Diagnostic>> {
+    let term = parse_term(application).map_err(|diags| {
+        diags
+            .into_iter()
+            .map(|diag| diag.with_source(APPLICATION_SOURCE))
+            .collect::<Vec<_>>()
+    })?;
+    let (name, mut args) = match term {
+        LogicTerm::Symbol(name) => (name, Vec::new()),
+        LogicTerm::Ctor { name, args } => (name, args),
+        term => {
+            return Err(vec![eval_error(format!(
+                "expected a defn application, got {}",
+                render_logic_term(&term)
+            ))]);
+        }
+    };
+    for arg in &mut args {
+        fold_term_consts(program, arg)?;
+    }
+    let (program, derived) = checked_and_solved(program)?;
+    let interpreter = Interpreter::new(&program, &derived);
+    let Some(defn) = interpreter.defns.get(name.as_str()) else {
+        return Err(vec![
+            Diagnostic::new("E-RESOLVE", format!("unknown defn: {name}"), None)
+                .with_source(APPLICATION_SOURCE),
+        ]);
+    };
+    if defn.params.len() != args.len() {
+        return Err(vec![eval_error(format!(
+            "{name} expects {} arguments, got {}",
+            defn.params.len(),
+            args.len()
+        ))]);
+    }
+    let args = defn
+        .params
+        .iter()
+        .zip(&args)
+        .map(|(param, arg)| {
+            interpreter.literal(arg, &param.ty).map_err(|message| {
+                eval_error(format!("argument {} of {name}: {message}", param.name))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|diag| vec![diag])?;
+    interpreter
+        .call_with(&name, &args, limits, trace)
+        .map_err(|err| vec![eval_error(err.to_string())])
+}
+
+pub fn run_entry_point(
+    program: &Program,
+    limits: EvalLimits,
+) -> Result<(String, Evaluation), Vec<Diagnostic>> {
+    let (program, derived) = checked_and_solved(program)?;
+    let entry = entry_point(&program).map_err(|diag| vec![diag])?;
+    let evaluation = Interpreter::new(&program, &derived)
+        .call(&entry.name, &[], limits)
+        .map_err(|err| vec![eval_error(err.to_string())])?;
+    Ok((entry.name.clone(), evaluation))
+}
+
+pub fn entry_point(program: &Program) -> Result<&Defn, Diagnostic> {
+    let marked = program
+        .defns
+        .iter()
+        .filter(|defn| {
+            defn.attributes
+                .get("entry")
+                .is_some_and(|value| value == "true")
+        })
+        .collect::<Vec<_>>();
+    let entry = match marked.as_slice() {
+        [] => program
+            .defns
+            .iter()
+            .find(|defn| defn.name == "main")
+            .ok_or_else(|| {
+                eval_error(
+                    "no entrypoint: define (defn main () ...) or mark a defn with :entry true"
+                        .to_string(),
+                )
+            })?,
+        [entry] => entry,
+        [first, second, ..] => {
+            return Err(Diagnostic::new(
+                "E-EVAL",
+                format!(
+                    "several defns are marked :entry true: {} and {}",
+                    first.name, second.name
+                ),
+                Some(second.span.clone()),
+            ));
+        }
+    };
+    if !entry.params.is_empty() {
+        return Err(Diagnostic::new(
+            "E-EVAL",
+            format!("entrypoint {} must take no parameters", entry.name),
+            Some(entry.span.clone()),
+        ));
+    }
+    Ok(entry)
+}
+
+const APPLICATION_SOURCE: &str = "<application>";
+
+fn eval_error(message: String) -> Diagnostic {
+    Diagnostic::new("E-EVAL", message, None)
+}
+
+pub struct Interpreter<'a> {
+    program: &'a Program,
+    derived: &'a DerivedFacts,
+    defns: HashMap<&'a str, &'a Defn>,
+    data: HashMap<&'a str, &'a DataDecl>,
+    constructors: HashSet<&'a str>,
+    relations: HashMap<&'a str, usize>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a Program, derived: &'a DerivedFacts) -> Self {
+        Self {
+            program,
+            derived,
+            defns: program
+                .defns
+                .iter()
+                .map(|defn| (defn.name.as_str(), defn))
+                .collect(),
+            data: program
+                .data_decls
+                .iter()
+                .map(|data| (data.name.as_str(), data))
+                .collect(),
+            constructors: program
+                .data_decls
+                .iter()
+                .flat_map(|data| data.constructors.iter().map(|ctor| ctor.name.as_str()))
+                .collect(),
+            relations: program
+                .relations
+                .iter()
+                .map(|relation| (relation.name.as_str(), relation.arg_sorts.len()))
+                .collect(),
+        }
+    }
+
+    pub fn call(
+        &self,
+        name: &str,
+        args: &[EvalValue],
+        limits: EvalLimits,
+    ) -> Result<Evaluation, EvalError> {
+        self.call_with(name, args, limits, None)
+    }
+
+    pub fn call_traced(
+        &self,
+        name: &str,
+        args: &[EvalValue],
+        limits: EvalLimits,
+        trace: &mut EvalTrace,
+    ) -> Result<Evaluation, EvalError> {
+        self.call_with(name, args, limits, Some(trace))
+    }
+
+    fn call_with(
+        &self,
+        name: &str,
+        args: &[EvalValue],
+        limits: EvalLimits,
+        trace: Option<&mut EvalTrace>,
+    ) -> Result<Evaluation, EvalError> {
+        let defn = self
+            .defns
+            .get(name)
+            .ok_or_else(|| stuck(format!("unknown defn: {name}")))?;
+        let limits = EvalLimits {
+            max_depth: limits.max_depth.min(EvalLimits::MAX_DEPTH),
+            ..limits
+        };
+        with_eval_stack(limits.max_depth, || {
+            let mut run = Run {
+                interpreter: self,
+                limits,
+                steps: 0,
+                trace,
+                calls: 0,
+            };
+            let value = run.apply(
+                name,
+                &defn.params,
+                defn.body,
+                args.to_vec(),
+                Scope::default(),
+                0,
+            )?;
+            Ok(Evaluation {
+                value,
+                steps: run.steps,
+            })
+        })
+    }
+
+    fn literal(&self, term: &LogicTerm, ty: &Type) -> Result<EvalValue, String> {
+        let mismatch = || {
+            format!(
+                "expected {}, got {}",
+                render_type(ty),
+                render_logic_term(term)
+            )
+        };
+        match (ty, term) {
+            (Type::Refine { var, base, formula }, term) => {
+                let value = self.literal(term, base)?;
+                let env = HashMap::from([(
+                    var.clone(),
+                    value.to_value().expect("literals are fact values"),
+                )]);
+                if !formula_holds(formula, self.derived, &env) {
+                    return Err(format!("{value} does not satisfy {}", render_type(ty)));
+                }
+                Ok(value)
+            }
+            (Type::Bool, LogicTerm::Bool(value)) => Ok(EvalValue::Bool(*value)),
+            (Type::Int, LogicTerm::Int(value)) => Ok(EvalValue::Int(*value)),
+            (Type::Symbol, LogicTerm::Symbol(value)) => Ok(EvalValue::Symbol(value.clone())),
+            (Type::Domain(name) | Type::Adt(name), term)
+                if self.data.contains_key(name.as_str()) =>
+            {
+                let (ctor, args) = match term {
+                    LogicTerm::Symbol(ctor) => (ctor, &[][..]),
+                    LogicTerm::Ctor { name, args } => (name, args.as_slice()),
+                    _ => return Err(mismatch()),
+                };
+                let Some(decl) = self.data[name.as_str()]
+                    .constructors
+                    .iter()
+                    .find(|decl| &decl.name == ctor)
+                else {
+                    return Err(format!("{ctor} is not a constructor of {name}"));
+                };
+                if decl.fields.len() != args.len() {
+                    return Err(format!(
+                        "{ctor} expects {} fields, got {}",
+                        decl.fields.len(),
+                        args.len()
+                    ));
+                }
+                Ok(EvalValue::Adt {
+                    ctor: ctor.clone(),
+                    fields: args
+                        .iter()
+                        .zip(&decl.fields)
+                        .map(|(arg, field)| self.literal(arg, field))
+                        .collect::<Result<_, _>>()?,
+                })
+            }
+            (Type::Domain(_), LogicTerm::Symbol(value)) => Ok(EvalValue::Symbol(value.clone())),
+            (Type::Fun(..), _) => Err("functions cannot be written as literals".to_string()),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+struct Closure<'a> {
+    decl: &'a LocalFn,
+    scope: Scope<'a>,
+}
+
+#[derive(Clone, Default)]
+struct Scope<'a> {
+    values: HashMap<String, EvalValue>,
+    fns: HashMap<String, Rc<Closure<'a>>>,
+}
+
+struct Run<'r, 'a> {
+    interpreter: &'r Interpreter<'a>,
+    limits: EvalLimits,
+    steps: u64,
+    trace: Option<&'r mut EvalTrace>,
+    calls: usize,
+}
+
+impl<'a> Run<'_, 'a> {
+    fn exprs(&self) -> &'a ExprArena {
+        &self.interpreter.program.exprs
+    }
+
+    fn record(&mut self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(trace) = self.trace.as_deref_mut()
+            && trace.max_depth.is_none_or(|max| self.calls <= max)
+        {
+            trace.steps.push(TraceStep {
+                depth: self.calls,
+                event: event(),
+            });
+        }
+    }
+
+    fn apply(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        body: ExprId,
+        args: Vec<EvalValue>,
+        mut scope: Scope<'a>,
+        depth: usize,
+    ) -> Result<EvalValue, EvalError> {
+        self.record(|| TraceEvent::Call {
+            name: name.to_string(),
+            args: params
+                .iter()
+                .map(|param| param.name.clone())
+                .zip(args.iter().cloned())
+                .collect(),
+        });
+        bind(&mut scope, params, args, name)?;
+        self.calls += 1;
+        let value = self.eval(body, &scope, depth)?;
+        self.calls -= 1;
+        self.record(|| TraceEvent::Return {
+            name: name.to_string(),
+            value: value.clone(),
+        });
+        Ok(value)
+    }
+
+    fn call(
+        &mut self,
+        name: &str,
+        args: Vec<EvalValue>,
+        scope: &Scope<'a>,
+        depth: usize,
+    ) -> Result<EvalValue, EvalError> {
+        if let Some(EvalValue::Function(fun)) = scope.values.get(name) {
+            return fun
+                .table
+                .iter()
+                .find(|(inputs, _)| *inputs == args)
+                .map(|(_, output)| output.clone())
+                .ok_or_else(|| stuck(format!("{name} has no value at the given arguments")));
+        }
+        if let Some(closure) = scope.fns.get(name) {
+            let decl = closure.decl;
+            let local = closure.scope.clone();
+            return self.apply(name, &decl.params, decl.body, args, local, depth + 1);
+        }
+        let interpreter = self.interpreter;
+        if let Some(defn) = interpreter.defns.get(name) {
+            let local = Scope::default();
+            return self.apply(name, &defn.params, defn.body, args, local, depth + 1);
+        }
+        if interpreter.constructors.contains(name) {
+            return Ok(EvalValue::Adt {
+                ctor: name.to_string(),
+                fields: args,
+            });
+        }
+        if let Some(&arity) = interpreter.relations.get(name) {
+            if arity != args.len() {
+                return Err(arity_error(name, arity, args.len()));
+            }
+            let tuple = args
+                .iter()
+                .map(EvalValue::to_value)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| stuck(format!("{name} is applied to a function")))?;
+            let holds = interpreter
+                .derived
+                .facts
+                .get(name)
+                .is_some_and(|set| set.contains(&tuple));
+            self.record(|| TraceEvent::Fact {
+                relation: name.to_string(),
+                args,
+                holds,
+            });
+            return Ok(EvalValue::Bool(holds));
+        }
+        match (ArithOp::from_name(name), args.as_slice()) {
+            (Some(op), [EvalValue::Int(lhs), EvalValue::Int(rhs)]) => op
+                .apply(*lhs, *rhs)
+                .map(EvalValue::Int)
+                .ok_or(EvalError::Overflow {
+                    op,
+                    lhs: *lhs,
+                    rhs: *rhs,
+                }),
+            _ => Err(stuck(format!("cannot apply {name}"))),
+        }
+    }
+
+    fn eval(
+        &mut self,
+        expr: ExprId,
+        scope: &Scope<'a>,
+        depth: usize,
+    ) -> Result<EvalValue, EvalError> {
+        if depth > self.limits.max_depth {
+            return Err(EvalError::DepthLimit(self.limits.max_depth));
+        }
+        self.steps += 1;
+        if self.steps > self.limits.max_steps {
+            return Err(EvalError::StepLimit(self.limits.max_steps));
+        }
+
+        match &self.exprs()[expr] {
+            Expr::Var { name, .. } => scope
+                .values
+                .get(name)
+                .cloned()
+                .ok_or_else(|| stuck(format!("{name} is not a value"))),
+            Expr::Symbol { value, .. } => Ok(EvalValue::Symbol(value.clone())),
+            Expr::Int { value, .. } => Ok(EvalValue::Int(*value)),
+            Expr::Bool { value, .. } => Ok(EvalValue::Bool(*value)),
+            Expr::Hole { .. } => Err(stuck("reached a typed hole".to_string())),
+            Expr::Call { name, args, .. } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(*arg, scope, depth + 1))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(name, args, scope, depth)
+            }
+            Expr::Let { bindings, body, .. } => {
+                let mut local = scope.clone();
+                for (name, value, _) in bindings {
+                    let value = self.eval(*value, &local, depth + 1)?;
+                    self.record(|| TraceEvent::Let {
+                        name: name.clone(),
+                        value: value.clone(),
+                    });
+                    local.values.insert(name.clone(), value);
+                }
+                self.eval(*body, &local, depth + 1)
+            }
+            Expr::LetFn { fns, body, .. } => {
+                let mut local = scope.clone();
+                for decl in fns {
+                    let closure = Closure {
+                        decl,
+                        scope: local.clone(),
+                    };
+                    local.fns.insert(decl.name.clone(), Rc::new(closure));
+                }
+                self.eval(*body, &local, depth + 1)
+            }
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+                ..
+            } => match self.eval(*cond, scope, depth + 1)? {
+                EvalValue::Bool(condition) => {
+                    self.record(|| TraceEvent::If { condition });
+                    let branch = if condition { then_branch } else { else_branch };
+                    self.eval(*branch, scope, depth + 1)
+                }
+                value => Err(stuck(format!("if condition {value} is not a Bool"))),
+            },
+            Expr::Match {
+                scrutinee, arms, ..
+            } => {
+                let target = self.eval(*scrutinee, scope, depth + 1)?;
+                for (index, arm) in arms.iter().enumerate() {
+                    let mut captures = HashMap::new();
+                    if !matches_pattern(self.exprs(), arm.pattern, &target, &mut captures) {
+                        continue;
+                    }
+                    let exprs = self.exprs();
+                    self.record(|| {
+                        let mut bindings = captures
+                            .iter()
+                            .map(|(name, value)| (name.clone(), value.clone()))
+                            .collect::<Vec<_>>();
+                        bindings.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+                        TraceEvent::Arm {
+                            target: target.clone(),
+                            index: index + 1,
+                            pattern: render_pattern(exprs, arm.pattern),
+                            bindings,
+                        }
+                    });
+                    let mut local = scope.clone();
+                    local.values.extend(captures);
+                    return self.eval(arm.body, &local, depth + 1);
+                }
+                Err(stuck(format!("no match arm covers {target}")))
+            }
+        }
+    }
+}
+
+fn bind(
+    scope: &mut Scope<'_>,
+    params: &[Param],
+    args: Vec<EvalValue>,
+    name: &str,
+) -> Result<(), EvalError> {
+    if params.len() != args.len() {
+        return Err(arity_error(name, params.len(), args.len()));
+    }
+    for (param, value) in params.iter().zip(args) {
+        scope.values.insert(param.name.clone(), value);
+    }
+    Ok(())
+}
+
+fn stuck(message: String) -> EvalError {
+    EvalError::Stuck(message)
+}
+
+fn arity_error(name: &str, expected: usize, got: usize) -> EvalError {
+    stuck(format!("{name} expects {expected} arguments, got {got}"))
+}
+
+fn matches_pattern(
+    exprs: &ExprArena,
+    pattern: PatternId,
+    target: &EvalValue,
+    binds: &mut HashMap<String, EvalValue>,
+) -> bool {
+    match &exprs[pattern] {
+        Pattern::Wildcard { .. } => true,
+        Pattern::Var { name, .. } => {
+            if let Some(prev) = binds.get(name) {
+                prev == target
+            } else {
+                binds.insert(name.clone(), target.clone());
+                true
+            }
+        }
+        Pattern::Symbol { value, .. } => matches!(target, EvalValue::Symbol(s) if s == value),
+        Pattern::Int { value, .. } => matches!(target, EvalValue::Int(i) if i == value),
+        Pattern::Bool { value, .. } => matches!(target, EvalValue::Bool(b) if b == value),
+        Pattern::Ctor { name, args, .. } => {
+            let EvalValue::Adt { ctor, fields } = target else {
+                return false;
+            };
+            name == ctor
+                && args.len() == fields.len()
+                && args
+                    .iter()
+                    .zip(fields)
+                    .all(|(pattern, value)| matches_pattern(exprs, *pattern, value, binds))
+        }
+    }
+}
+
+pub(crate) fn formula_holds(
+    formula: &Formula,
+    derived: &DerivedFacts,
+    env: &HashMap<String, Value>,
+) -> bool {
+    match formula {
+        Formula::True => true,
+        Formula::Atom(atom) => {
+            let Some(tuple) = instantiate_terms(&atom.terms, env) else {
+                return false;
+            };
+            if atom.pred == DISEQUALITY_PRED {
+                return disequality_holds(&tuple);
+            }
+            if let Some(op) = ComparisonOp::from_pred(&atom.pred) {
+                return matches!(tuple.as_slice(), [Value::Int(lhs), Value::Int(rhs)] if op.holds(*lhs, *rhs));
+            }
+            derived
+                .facts
+                .get(&atom.pred)
+                .map(|set| set.contains(&tuple))
+                .unwrap_or(false)
+        }
+        Formula::And(items) => items.iter().all(|item| formula_holds(item, derived, env)),
+        Formula::Not(inner) => !formula_holds(inner, derived, env),
+    }
+}
+
+pub(crate) fn instantiate_terms(
+    terms: &[LogicTerm],
+    env: &HashMap<String, Value>,
+) -> Option<Vec<Value>> {
+    terms
+        .iter()
+        .map(|term| instantiate_term(term, env))
+        .collect::<Option<Vec<_>>>()
+}
+
+fn instantiate_term(term: &LogicTerm, env: &HashMap<String, Value>) -> Option<Value> {
+    match term {
+        LogicTerm::Var(name) => env.get(name).cloned(),
+        LogicTerm::Symbol(s) => Some(Value::Symbol(Symbol::intern(s))),
+        LogicTerm::Int(i) => Some(Value::Int(*i)),
+        LogicTerm::Bool(b) => Some(Value::Bool(*b)),
+        LogicTerm::Ctor { name, args } => {
+            let fields = args
+                .iter()
+                .map(|arg| instantiate_term(arg, env))
+                .collect::<Option<Vec<_>>>()?;
+            Some(Value::Adt {
+                ctor: Symbol::intern(name),
+                fields,
+            })
+        }
+    }
+}
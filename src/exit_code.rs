@@ -0,0 +1,120 @@
+use crate::diagnostics::Diagnostic;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FailureClass {
+    // Declared in pipeline order: when diagnostics of several classes are
+    // reported together, the earliest stage decides the exit code.
+    Io,
+    Parse,
+    Type,
+    Proof,
+    Eval,
+    Lint,
+}
+
+impl FailureClass {
+    pub const ALL: &'static [FailureClass] = &[
+        FailureClass::Parse,
+        FailureClass::Type,
+        FailureClass::Proof,
+        FailureClass::Io,
+        FailureClass::Lint,
+        FailureClass::Eval,
+    ];
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureClass::Parse => 1,
+            FailureClass::Type => 2,
+            FailureClass::Proof => 3,
+            FailureClass::Io => 4,
+            FailureClass::Lint => 5,
+            FailureClass::Eval => 6,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureClass::Parse => "parse",
+            FailureClass::Type => "type",
+            FailureClass::Proof => "proof",
+            FailureClass::Io => "io",
+            FailureClass::Lint => "lint",
+            FailureClass::Eval => "eval",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            FailureClass::Parse => "構文エラー（S式・フォーム構造・edition・import 循環）",
+            FailureClass::Type => "名前解決・層化否定・型検査・全域性・match 網羅性のエラー",
+            FailureClass::Proof => "証明義務の失敗、証明器のエラー、claim coverage 不足",
+            FailureClass::Io => "ファイル入出力・設定ファイル・コマンドライン引数のエラー",
+            FailureClass::Lint => "lint --deny-warnings の警告、fmt --check の差分",
+            FailureClass::Eval => {
+                "eval / run の評価エラー（引数の不整合・エントリポイント・上限超過・整数オーバーフロー）"
+            }
+        }
+    }
+
+    pub fn diagnostic_codes(self) -> &'static [&'static str] {
+        match self {
+            FailureClass::Parse => &[
+                "E-PARSE",
+                "E-SYNTAX-AUTO",
+                "E-EDITION",
+                "E-IMPORT",
+                "E-FMT-SELFDOC-UNSUPPORTED",
+            ],
+            FailureClass::Type => &[
+                "E-RESOLVE",
+                "E-DATA",
+                "E-STRATIFY",
+                "E-TYPE",
+                "E-ENTAIL",
+                "E-KEY",
+                "E-TOTAL",
+                "E-MATCH",
+                "E-HOLE",
+                "E-CODEGEN",
+            ],
+            FailureClass::Proof => &["E-PROVE", "E-SELFCHECK"],
+            FailureClass::Io => &[
+                "E-IO",
+                "E-CONFIG",
+                "E-MINIMIZE",
+                "E-SELFDOC-CONFIG",
+                "E-SELFDOC-SCAN",
+                "E-SELFDOC-CLASSIFY",
+                "E-SELFDOC-REF",
+                "E-SELFDOC-CONTRACT",
+                "E-SELFDOC-GATE",
+                "E-SELFDOC-MANIFEST",
+                "E-SELFDOC-BASELINE",
+            ],
+            FailureClass::Eval => &["E-EVAL"],
+            FailureClass::Lint => &[],
+        }
+    }
+
+    pub fn of_code(code: &str) -> Self {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|class| class.diagnostic_codes().contains(&code))
+            .unwrap_or(FailureClass::Type)
+    }
+}
+
+pub fn failure_class(diags: &[Diagnostic]) -> Option<FailureClass> {
+    diags
+        .iter()
+        .map(|diag| FailureClass::of_code(diag.code))
+        .min()
+}
+
+pub fn failure_exit_code(diags: &[Diagnostic]) -> i32 {
+    failure_class(diags)
+        .unwrap_or(FailureClass::Type)
+        .exit_code()
+}
@@ -0,0 +1,303 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use crate::ast::Program;
+use crate::diagnostics::Diagnostic;
+use crate::fmt::render_rule;
+use crate::logic_engine::{
+    DerivationStep, KnowledgeBase, fact_key, solve_facts_with_derivations, value_to_string,
+};
+use crate::name_resolve::normalize_program_aliases;
+use crate::prover::{
+    ObligationTrace, ProofTrace, logic_term_to_const_value, obligation_source, prove_program,
+};
+use crate::types::{Atom, LogicTerm};
+
+const HELP: &str = "コマンド:\n  番号  その項目を開く\n  b     前の画面へ戻る\n  help  このヘルプを表示する\n  q     終了する";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum View {
+    Obligations,
+    Obligation(usize),
+    Fact(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Obligation(usize),
+    Fact(String),
+}
+
+pub struct Explorer {
+    trace: ProofTrace,
+    sources: Vec<Option<(String, String)>>,
+    rules: Vec<(Atom, String)>,
+    derivations: HashMap<String, DerivationStep>,
+    facts: BTreeSet<String>,
+    views: Vec<View>,
+    notice: Option<String>,
+}
+
+impl Explorer {
+    pub fn new(program: &Program) -> Result<Self, Vec<Diagnostic>> {
+        let trace = prove_program(program)?;
+        let normalized = normalize_program_aliases(program)?;
+        let kb = KnowledgeBase::from_program(&normalized)?;
+        let (derived, steps) = solve_facts_with_derivations(&kb)?;
+        let sources = trace
+            .obligations
+            .iter()
+            .map(|obligation| {
+                obligation_source(program, &obligation.id).map(|(formula, span)| {
+                    let location = match &span.file_id {
+                        Some(file) => format!("{file}:{}:{}", span.line, span.column),
+                        None => format!("{}:{}", span.line, span.column),
+                    };
+                    (location, formula)
+                })
+            })
+            .collect();
+        Ok(Self {
+            trace,
+            sources,
+            rules: kb
+                .rules
+                .iter()
+                .map(|rule| (rule.head.clone(), render_rule(rule)))
+                .collect(),
+            derivations: steps
+                .into_iter()
+                .map(|step| (step.fact.clone(), step))
+                .collect(),
+            facts: derived
+                .facts
+                .iter()
+                .flat_map(|(pred, tuples)| tuples.iter().map(|tuple| fact_key(pred, tuple)))
+                .collect(),
+            views: vec![View::Obligations],
+            notice: None,
+        })
+    }
+
+    pub fn command(&mut self, input: &str) -> bool {
+        self.notice = None;
+        match input.trim() {
+            "" => {}
+            "q" => return false,
+            "help" => self.notice = Some(HELP.to_string()),
+            "b" => {
+                if self.views.len() > 1 {
+                    self.views.pop();
+                }
+            }
+            input => match input.parse::<usize>() {
+                Ok(number) if (1..=self.targets().len()).contains(&number) => {
+                    let view = match self.targets().swap_remove(number - 1) {
+                        Target::Obligation(index) => View::Obligation(index),
+                        Target::Fact(fact) => View::Fact(fact),
+                    };
+                    self.views.push(view);
+                }
+                Ok(number) => self.notice = Some(format!("項目 {number} はありません")),
+                Err(_) => {
+                    self.notice = Some(format!("不明なコマンド: {input}（help でコマンド一覧）"));
+                }
+            },
+        }
+        true
+    }
+
+    pub fn screen(&self) -> String {
+        let mut out = String::new();
+        let mut number = 0;
+        let mut item = |out: &mut String, label: &str| {
+            number += 1;
+            let _ = writeln!(out, "  {number}. {label}");
+        };
+        match self.view() {
+            View::Obligations => {
+                let summary = &self.trace.summary;
+                let _ = writeln!(
+                    out,
+                    "証明義務 {} 件（成功 {} / 失敗 {}）\n",
+                    summary.total, summary.proved, summary.failed
+                );
+                for obligation in &self.trace.obligations {
+                    item(
+                        &mut out,
+                        &format!("[{}] {}", obligation.result, obligation.id),
+                    );
+                }
+            }
+            View::Obligation(index) => {
+                let obligation = &self.trace.obligations[*index];
+                let _ = writeln!(out, "{} [{}]", obligation.id, obligation.result);
+                if let Some((location, formula)) = &self.sources[*index] {
+                    let _ = writeln!(out, "位置: {location}\n式: {formula}");
+                }
+                if let Some(message) = &obligation.message {
+                    let _ = writeln!(out, "メッセージ: {message}");
+                }
+                let valuation = match &obligation.counterexample {
+                    Some(counterexample) => ("反例の代入", &counterexample.valuation),
+                    None => ("代入", &obligation.valuation),
+                };
+                if !valuation.1.is_empty() {
+                    let values = valuation
+                        .1
+                        .iter()
+                        .map(|nv| format!("{} = {}", nv.name, nv.value))
+                        .collect::<Vec<_>>();
+                    let _ = writeln!(out, "{}: {}", valuation.0, values.join(", "));
+                }
+                for (label, facts) in obligation_sections(obligation) {
+                    let _ = writeln!(out, "{label}:");
+                    for fact in facts {
+                        item(&mut out, fact);
+                    }
+                }
+                if let Some(counterexample) = &obligation.counterexample {
+                    self.write_rules(&mut out, "関連する rule", &counterexample.missing_goals);
+                }
+            }
+            View::Fact(fact) => {
+                let _ = writeln!(out, "{fact}");
+                match self.derivations.get(fact) {
+                    Some(step) => {
+                        let _ = writeln!(
+                            out,
+                            "状態: rule で導出（stratum {} / iteration {}）\nrule: {}",
+                            step.stratum, step.iteration, self.rules[step.rule].1
+                        );
+                        if !step.premises.is_empty() {
+                            out.push_str("前提:\n");
+                            for premise in &step.premises {
+                                item(&mut out, premise);
+                            }
+                        }
+                    }
+                    None if self.facts.contains(fact) => out.push_str("状態: fact として宣言\n"),
+                    None if fact.starts_with("not ") => {
+                        out.push_str("状態: 否定した式が成り立つため不成立\n");
+                    }
+                    None => {
+                        out.push_str("状態: 導出されていない\n");
+                        self.write_rules(
+                            &mut out,
+                            "head が一致する rule",
+                            std::slice::from_ref(fact),
+                        );
+                    }
+                }
+            }
+        }
+        if let Some(notice) = &self.notice {
+            let _ = writeln!(out, "\n{notice}");
+        }
+        out.push_str(if self.views.len() > 1 {
+            "\n番号: 開く  b: 戻る  help: ヘルプ  q: 終了\n"
+        } else {
+            "\n番号: 開く  help: ヘルプ  q: 終了\n"
+        });
+        out
+    }
+
+    fn view(&self) -> &View {
+        self.views
+            .last()
+            .expect("the obligation list is never popped")
+    }
+
+    fn targets(&self) -> Vec<Target> {
+        match self.view() {
+            View::Obligations => (0..self.trace.obligations.len())
+                .map(Target::Obligation)
+                .collect(),
+            View::Obligation(index) => obligation_sections(&self.trace.obligations[*index])
+                .into_iter()
+                .flat_map(|(_, facts)| facts)
+                .map(|fact| Target::Fact(fact.clone()))
+                .collect(),
+            View::Fact(fact) => self
+                .derivations
+                .get(fact)
+                .map(|step| step.premises.iter().cloned().map(Target::Fact).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn write_rules(&self, out: &mut String, label: &str, facts: &[String]) {
+        let rules = self
+            .rules
+            .iter()
+            .filter(|(head, _)| facts.iter().any(|fact| head_matches(head, fact)))
+            .collect::<Vec<_>>();
+        if rules.is_empty() {
+            return;
+        }
+        let _ = writeln!(out, "{label}:");
+        for (_, rule) in rules {
+            let _ = writeln!(out, "  - {rule}");
+        }
+    }
+}
+
+fn obligation_sections(obligation: &ObligationTrace) -> Vec<(&'static str, &[String])> {
+    let sections = match &obligation.counterexample {
+        Some(counterexample) => [
+            ("前提", counterexample.premises.as_slice()),
+            ("成立しない goal", counterexample.missing_goals.as_slice()),
+            ("導出された fact", obligation.derived.as_slice()),
+        ],
+        None => [
+            ("前提", obligation.premises.as_slice()),
+            ("成立しない goal", &[][..]),
+            ("導出された fact", obligation.derived.as_slice()),
+        ],
+    };
+    sections
+        .into_iter()
+        .filter(|(_, facts)| !facts.is_empty())
+        .collect()
+}
+
+fn head_matches(head: &Atom, fact: &str) -> bool {
+    let Some(args) = fact
+        .strip_prefix(head.pred.as_str())
+        .and_then(|rest| rest.strip_prefix('('))
+        .and_then(|rest| rest.strip_suffix(')'))
+    else {
+        return false;
+    };
+    let args = fact_args(args);
+    if args.len() != head.terms.len() {
+        return false;
+    }
+    let mut bound = HashMap::new();
+    head.terms.iter().zip(args).all(|(term, arg)| match term {
+        LogicTerm::Var(name) => *bound.entry(name).or_insert(arg) == arg,
+        term => logic_term_to_const_value(term).is_none_or(|value| value_to_string(&value) == arg),
+    })
+}
+
+fn fact_args(args: &str) -> Vec<&str> {
+    if args.is_empty() {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, ch) in args.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                out.push(&args[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&args[start..]);
+    out
+}
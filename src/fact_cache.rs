@@ -0,0 +1,101 @@
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use crate::ast::Program;
+use crate::diagnostics::Diagnostic;
+use crate::logic_engine::{DerivedFacts, KnowledgeBase, solve_facts};
+use crate::snapshot::{KnowledgeBaseSnapshot, kb_fingerprint, read_kb_snapshot, write_kb_snapshot};
+
+pub const FACT_CACHE_DIR: &str = ".dtl-cache";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactCache {
+    dir: PathBuf,
+}
+
+impl FactCache {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            dir: root.join(FACT_CACHE_DIR).join("facts"),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn entry_path(&self, kb: &KnowledgeBase) -> PathBuf {
+        self.dir.join(format!("{}.json.zst", kb_fingerprint(kb)))
+    }
+
+    pub fn solve(&self, kb: &KnowledgeBase) -> Result<DerivedFacts, Vec<Diagnostic>> {
+        let path = self.entry_path(kb);
+        if let Ok(snapshot) = read_kb_snapshot(&path)
+            && snapshot.matches(kb)
+        {
+            tracing::debug!(path = %path.display(), "fact cache hit");
+            return Ok(snapshot.derived_facts());
+        }
+        let derived = solve_facts(kb)?;
+        if let Err(err) = self.store(&path, &KnowledgeBaseSnapshot::new(kb, &derived)) {
+            tracing::debug!(path = %path.display(), error = %err.message, "fact cache not written");
+        }
+        Ok(derived)
+    }
+
+    // Written under a temporary name and renamed, so concurrent runs never
+    // read a partial entry.
+    fn store(&self, path: &Path, snapshot: &KnowledgeBaseSnapshot) -> Result<(), Diagnostic> {
+        fs::create_dir_all(&self.dir).map_err(|e| {
+            Diagnostic::new(
+                "E-IO",
+                format!("failed to create {}: {e}", self.dir.display()),
+                None,
+            )
+        })?;
+        let tmp = self
+            .dir
+            .join(format!("{}.tmp.json.zst", std::process::id()));
+        write_kb_snapshot(&tmp, snapshot)?;
+        fs::rename(&tmp, path).map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            Diagnostic::new(
+                "E-IO",
+                format!("failed to write {}: {e}", path.display()),
+                None,
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FixpointOptions<'a> {
+    pub cache: Option<&'a FactCache>,
+    pub workers: NonZeroUsize,
+}
+
+impl Default for FixpointOptions<'_> {
+    fn default() -> Self {
+        Self {
+            cache: None,
+            workers: NonZeroUsize::MIN,
+        }
+    }
+}
+
+impl FixpointOptions<'_> {
+    pub fn knowledge_base(&self, program: &Program) -> Result<KnowledgeBase, Vec<Diagnostic>> {
+        Ok(KnowledgeBase::from_program(program)?.with_workers(self.workers))
+    }
+}
+
+pub fn solve_facts_cached(
+    kb: &KnowledgeBase,
+    options: FixpointOptions<'_>,
+) -> Result<DerivedFacts, Vec<Diagnostic>> {
+    match options.cache {
+        Some(cache) => cache.solve(kb),
+        None => solve_facts(kb),
+    }
+}
@@ -1,14 +1,180 @@
 use crate::ast::{
-    AliasDecl, AssertDecl, DataDecl, Defn, Expr, Fact, ImportDecl, Pattern, Program, RelationDecl,
-    Rule, SortDecl, UniverseDecl,
+    AliasDecl, AssertDecl, ConstDecl, DataDecl, Defn, Expr, ExprArena, ExprId, Fact, ImportDecl,
+    Pattern, PatternId, Program, RelationDecl, Rule, SortDecl, UniverseDecl,
+};
+use crate::diagnostics::{Diagnostic, Span, TextEdit, make_span_with_file};
+use crate::edition::{Edition, edition_from_pragma, source_edition};
+use crate::parser::{
+    ParseOptions, SurfaceKeywordMap, SurfaceKeywords, block_comment_end, contains_code_comment,
+    datum_comment_starts, parse_program_with_options, quote_atom, shebang_len, string_literal_end,
+    string_literals, surface_keywords_with_map, top_level_layout,
 };
-use crate::diagnostics::Diagnostic;
-use crate::parser::parse_program;
 use crate::types::{Atom, Formula, LogicTerm, Type};
+use std::collections::{BTreeMap, HashMap};
 use std::iter::Peekable;
 use std::str::CharIndices;
 use std::sync::OnceLock;
 
+const MAX_INLINE_WIDTH: usize = 80;
+
+struct SurfaceVocabulary<'a> {
+    pragma: Option<&'a str>,
+    import: &'a str,
+    alias: &'a str,
+    defconst: &'a str,
+    sort: &'a str,
+    data: &'a str,
+    relation: &'a str,
+    fact: &'a str,
+    rule: &'a str,
+    assert: &'a str,
+    universe: &'a str,
+    defn: &'a str,
+    when_profile: &'a str,
+    alias_tag: &'a str,
+    canonical_tag: &'a str,
+    constructors_tag: &'a str,
+    args_tag: &'a str,
+    key_tag: &'a str,
+    terms_tag: &'a str,
+    head_tag: &'a str,
+    body_tag: &'a str,
+    clauses_tag: &'a str,
+    skolem_tag: &'a str,
+    measure_tag: &'a str,
+    params_tag: &'a str,
+    formula_tag: &'a str,
+    values_tag: &'a str,
+    value_tag: &'a str,
+    ret_tag: &'a str,
+    doc_tag: &'a str,
+    message_tag: &'a str,
+}
+
+const JAPANESE_VOCABULARY: SurfaceVocabulary<'static> = SurfaceVocabulary {
+    pragma: None,
+    import: "インポート",
+    alias: "同義語",
+    defconst: "定数",
+    sort: "型",
+    data: "データ",
+    relation: "関係",
+    fact: "事実",
+    rule: "規則",
+    assert: "検証",
+    universe: "宇宙",
+    defn: "関数",
+    when_profile: "プロファイル限定",
+    alias_tag: ":別名",
+    canonical_tag: ":正規",
+    constructors_tag: ":コンストラクタ",
+    args_tag: ":引数",
+    key_tag: ":キー",
+    terms_tag: ":項",
+    head_tag: ":頭",
+    body_tag: ":本体",
+    clauses_tag: ":節",
+    skolem_tag: ":スコーレム",
+    measure_tag: ":尺度",
+    params_tag: ":引数",
+    formula_tag: ":式",
+    values_tag: ":値",
+    value_tag: ":値",
+    ret_tag: ":戻り",
+    doc_tag: ":説明",
+    message_tag: ":メッセージ",
+};
+
+const ENGLISH_VOCABULARY: SurfaceVocabulary<'static> = SurfaceVocabulary {
+    pragma: Some("; keywords: en"),
+    import: "import",
+    alias: "alias",
+    defconst: "defconst",
+    sort: "sort",
+    data: "data",
+    relation: "relation",
+    fact: "fact",
+    rule: "rule",
+    assert: "assert",
+    universe: "universe",
+    defn: "defn",
+    when_profile: "when-profile",
+    alias_tag: ":alias",
+    canonical_tag: ":canonical",
+    constructors_tag: ":constructors",
+    args_tag: ":args",
+    key_tag: ":key",
+    terms_tag: ":terms",
+    head_tag: ":head",
+    body_tag: ":body",
+    clauses_tag: ":clauses",
+    skolem_tag: ":skolem",
+    measure_tag: ":measure",
+    params_tag: ":params",
+    formula_tag: ":formula",
+    values_tag: ":values",
+    value_tag: ":value",
+    ret_tag: ":ret",
+    doc_tag: ":doc",
+    message_tag: ":message",
+};
+
+impl<'a> SurfaceVocabulary<'a> {
+    fn new(keywords: SurfaceKeywords, custom: &'a SurfaceKeywordMap) -> Self {
+        let base = match keywords {
+            SurfaceKeywords::Japanese => &JAPANESE_VOCABULARY,
+            SurfaceKeywords::English => &ENGLISH_VOCABULARY,
+        };
+        Self {
+            pragma: base.pragma,
+            import: custom.word_for(base.import).unwrap_or(base.import),
+            alias: custom.word_for(base.alias).unwrap_or(base.alias),
+            defconst: custom.word_for(base.defconst).unwrap_or(base.defconst),
+            sort: custom.word_for(base.sort).unwrap_or(base.sort),
+            data: custom.word_for(base.data).unwrap_or(base.data),
+            relation: custom.word_for(base.relation).unwrap_or(base.relation),
+            fact: custom.word_for(base.fact).unwrap_or(base.fact),
+            rule: custom.word_for(base.rule).unwrap_or(base.rule),
+            assert: custom.word_for(base.assert).unwrap_or(base.assert),
+            universe: custom.word_for(base.universe).unwrap_or(base.universe),
+            defn: custom.word_for(base.defn).unwrap_or(base.defn),
+            when_profile: custom
+                .word_for(base.when_profile)
+                .unwrap_or(base.when_profile),
+            alias_tag: custom.word_for(base.alias_tag).unwrap_or(base.alias_tag),
+            canonical_tag: custom
+                .word_for(base.canonical_tag)
+                .unwrap_or(base.canonical_tag),
+            constructors_tag: custom
+                .word_for(base.constructors_tag)
+                .unwrap_or(base.constructors_tag),
+            args_tag: custom.word_for(base.args_tag).unwrap_or(base.args_tag),
+            key_tag: custom.word_for(base.key_tag).unwrap_or(base.key_tag),
+            terms_tag: custom.word_for(base.terms_tag).unwrap_or(base.terms_tag),
+            head_tag: custom.word_for(base.head_tag).unwrap_or(base.head_tag),
+            body_tag: custom.word_for(base.body_tag).unwrap_or(base.body_tag),
+            clauses_tag: custom
+                .word_for(base.clauses_tag)
+                .unwrap_or(base.clauses_tag),
+            skolem_tag: custom.word_for(base.skolem_tag).unwrap_or(base.skolem_tag),
+            measure_tag: custom
+                .word_for(base.measure_tag)
+                .unwrap_or(base.measure_tag),
+            params_tag: custom.word_for(base.params_tag).unwrap_or(base.params_tag),
+            formula_tag: custom
+                .word_for(base.formula_tag)
+                .unwrap_or(base.formula_tag),
+            values_tag: custom.word_for(base.values_tag).unwrap_or(base.values_tag),
+            value_tag: custom.word_for(base.value_tag).unwrap_or(base.value_tag),
+            ret_tag: custom.word_for(base.ret_tag).unwrap_or(base.ret_tag),
+            doc_tag: custom.word_for(base.doc_tag).unwrap_or(base.doc_tag),
+            message_tag: custom
+                .word_for(base.message_tag)
+                .unwrap_or(base.message_tag),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct FormatOptions {
     pub preserve_context: bool,
@@ -23,29 +189,282 @@ impl Default for FormatOptions {
 }
 
 pub fn format_source(src: &str, options: FormatOptions) -> Result<String, Vec<Diagnostic>> {
-    let program = parse_program(src)?;
-    if contains_selfdoc_form(src) {
+    format_source_with_keywords(src, options, &SurfaceKeywordMap::default())
+}
+
+pub fn format_source_with_keywords(
+    src: &str,
+    options: FormatOptions,
+    keywords: &SurfaceKeywordMap,
+) -> Result<String, Vec<Diagnostic>> {
+    let program = parse_program_with_options(
+        src,
+        &ParseOptions {
+            keywords: keywords.clone(),
+            ..ParseOptions::default()
+        },
+    )?;
+    if contains_selfdoc_form(src)
+        || contains_custom_selfdoc_head(src, keywords)
+        || contains_macro_definition(src)
+    {
         return Ok(src.trim_end().to_string() + "\n");
     }
+    if contains_code_comment(src, source_edition(src)) {
+        return format_forms_in_place(src, keywords);
+    }
 
+    let vocab = &SurfaceVocabulary::new(surface_keywords_with_map(src, keywords), keywords);
     let mut out = String::new();
+    let shebang = src[..shebang_len(src)].trim_end();
+    if !shebang.is_empty() {
+        out.push_str(shebang);
+        out.push('\n');
+    }
     out.push_str("; syntax: surface\n");
+    if let Ok(Some(edition)) = edition_from_pragma(src) {
+        out.push_str(&format!("; edition: {edition}\n"));
+    }
+    if let Some(pragma) = vocab.pragma {
+        out.push_str(pragma);
+        out.push('\n');
+    }
 
     if options.preserve_context {
-        render_with_context_blocks(program, src, &mut out);
+        render_with_context_blocks(program, src, keywords, vocab, &mut out);
     } else {
-        let mut forms = ContextForms::from_program(program);
+        let mut program = program;
+        let exprs = std::mem::take(&mut program.exprs);
+        let mut forms = ContextForms::from_program(program).with_profile_blocks(src, keywords);
         forms.sort_for_render();
-        render_forms(&forms, &mut out);
+        render_forms(&exprs, &forms, vocab, &mut out);
+    }
+
+    Ok(restore_literal_spellings(src, out.trim_end()) + "\n")
+}
+
+fn restore_literal_spellings(src: &str, formatted: &str) -> String {
+    let mut spellings = HashMap::new();
+    for (atom, range) in string_literals(src) {
+        spellings.entry(atom).or_insert(&src[range]);
     }
+    let mut out = String::new();
+    let mut last = 0;
+    for (atom, range) in string_literals(formatted) {
+        if let Some(spelling) = spellings.get(&atom) {
+            out.push_str(&formatted[last..range.start]);
+            out.push_str(spelling);
+            last = range.end;
+        }
+    }
+    out.push_str(&formatted[last..]);
+    out
+}
 
-    Ok(out.trim_end().to_string() + "\n")
+pub fn format_range(
+    src: &str,
+    selection: &Span,
+    options: FormatOptions,
+) -> Result<Option<TextEdit>, Vec<Diagnostic>> {
+    format_range_with_keywords(src, selection, options, &SurfaceKeywordMap::default())
+}
+
+pub fn format_range_with_keywords(
+    src: &str,
+    selection: &Span,
+    options: FormatOptions,
+    keywords: &SurfaceKeywordMap,
+) -> Result<Option<TextEdit>, Vec<Diagnostic>> {
+    let parse_options = ParseOptions {
+        keywords: keywords.clone(),
+        ..ParseOptions::default()
+    };
+    parse_program_with_options(src, &parse_options)?;
+    if contains_selfdoc_form(src)
+        || contains_custom_selfdoc_head(src, keywords)
+        || contains_macro_definition(src)
+    {
+        return Ok(None);
+    }
+    let edit = |start: usize, end: usize, replacement: String| {
+        (replacement != src[start..end]).then(|| TextEdit {
+            span: make_span_with_file(src, start, end, selection.file_id.as_deref()),
+            replacement,
+        })
+    };
+
+    let layout = top_level_layout(src, keywords)?;
+    if !layout.surface {
+        let formatted = format_source_with_keywords(src, options, keywords)?;
+        return Ok(edit(0, src.len(), formatted));
+    }
+    let selected_end = selection.end.max(selection.start + 1);
+    let forms = layout
+        .forms
+        .into_iter()
+        .filter(|&(start, end)| selection.start < end && start < selected_end)
+        .collect::<Vec<_>>();
+    let (Some(&(start, _)), Some(&(_, end))) = (forms.first(), forms.last()) else {
+        return Ok(None);
+    };
+
+    let out = render_forms_between(src, &forms, keywords)?;
+    Ok(edit(start, end, out))
+}
+
+pub fn format_warnings(src: &str, keywords: &SurfaceKeywordMap) -> Vec<Diagnostic> {
+    let edition = source_edition(src);
+    if !contains_code_comment(src, edition)
+        || contains_selfdoc_form(src)
+        || contains_custom_selfdoc_head(src, keywords)
+        || contains_macro_definition(src)
+    {
+        return Vec::new();
+    }
+    let Ok(layout) = top_level_layout(src, keywords) else {
+        return Vec::new();
+    };
+    if !layout.surface {
+        return vec![Diagnostic::new(
+            "W-FMT-COMMENT",
+            "core syntax file contains a block or datum comment and was left unformatted",
+            None,
+        )];
+    }
+    layout
+        .forms
+        .into_iter()
+        .filter(|&(start, end)| contains_code_comment(&src[start..end], edition))
+        .map(|(start, end)| {
+            Diagnostic::new(
+                "W-FMT-COMMENT",
+                "form contains a block or datum comment and was left as written",
+                Some(make_span_with_file(src, start, end, None)),
+            )
+        })
+        .collect()
+}
+
+pub fn leaves_core_comments_unformatted(src: &str, keywords: &SurfaceKeywordMap) -> bool {
+    format_warnings(src, keywords)
+        .iter()
+        .any(|warning| warning.span.is_none())
+}
+
+fn format_forms_in_place(
+    src: &str,
+    keywords: &SurfaceKeywordMap,
+) -> Result<String, Vec<Diagnostic>> {
+    let layout = top_level_layout(src, keywords)?;
+    let (Some(&(start, _)), Some(&(_, end))) = (layout.forms.first(), layout.forms.last()) else {
+        return Ok(src.trim_end().to_string() + "\n");
+    };
+    if !layout.surface {
+        return Ok(src.trim_end().to_string() + "\n");
+    }
+    let body = render_forms_between(src, &layout.forms, keywords)?;
+    Ok(format!("{}{body}{}", &src[..start], &src[end..])
+        .trim_end()
+        .to_string()
+        + "\n")
+}
+
+fn render_forms_between(
+    src: &str,
+    forms: &[(usize, usize)],
+    keywords: &SurfaceKeywordMap,
+) -> Result<String, Vec<Diagnostic>> {
+    let parse_options = ParseOptions {
+        keywords: keywords.clone(),
+        ..ParseOptions::default()
+    };
+    let vocab = &SurfaceVocabulary::new(surface_keywords_with_map(src, keywords), keywords);
+    let edition = source_edition(src);
+    let mut out = String::new();
+    let mut cursor = forms.first().map_or(0, |&(start, _)| start);
+    for &(form_start, form_end) in forms {
+        out.push_str(&src[cursor..form_start]);
+        cursor = form_end;
+        if contains_code_comment(&src[form_start..form_end], edition) {
+            out.push_str(&src[form_start..form_end]);
+            continue;
+        }
+        let form = format!(
+            "; syntax: surface\n; edition: {edition}\n{}",
+            &src[form_start..form_end]
+        );
+        let mut program = parse_program_with_options(&form, &parse_options)?;
+        let exprs = std::mem::take(&mut program.exprs);
+        let mut rendered = String::new();
+        render_forms(
+            &exprs,
+            &ContextForms::from_program(program).with_profile_blocks(&form, keywords),
+            vocab,
+            &mut rendered,
+        );
+        out.push_str(&restore_literal_spellings(src, rendered.trim_end()));
+    }
+    Ok(out)
+}
+
+pub(crate) struct FormExcerpt {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) text: String,
+}
+
+pub(crate) fn form_excerpts(src: &str) -> Vec<FormExcerpt> {
+    let keywords = SurfaceKeywordMap::default();
+    let Ok(layout) = top_level_layout(src, &keywords) else {
+        return Vec::new();
+    };
+    let verbatim = !layout.surface || contains_selfdoc_form(src) || contains_macro_definition(src);
+    let vocab = &SurfaceVocabulary::new(surface_keywords_with_map(src, &keywords), &keywords);
+    layout
+        .forms
+        .into_iter()
+        .map(|(start, end)| {
+            let raw = &src[start..end];
+            let form = format!("; syntax: surface\n{raw}");
+            let text = match parse_program_with_options(&form, &ParseOptions::default()) {
+                Ok(mut program) if !verbatim => {
+                    let exprs = std::mem::take(&mut program.exprs);
+                    let mut rendered = String::new();
+                    render_forms(
+                        &exprs,
+                        &ContextForms::from_program(program).with_profile_blocks(&form, &keywords),
+                        vocab,
+                        &mut rendered,
+                    );
+                    rendered.trim_end().to_string()
+                }
+                _ => raw.to_string(),
+            };
+            FormExcerpt { start, end, text }
+        })
+        .collect()
+}
+
+pub fn render_program(program: &Program, keywords: SurfaceKeywords) -> String {
+    let custom = SurfaceKeywordMap::default();
+    let vocab = &SurfaceVocabulary::new(keywords, &custom);
+    let mut out = String::from("; syntax: surface\n");
+    if let Some(pragma) = vocab.pragma {
+        out.push_str(pragma);
+        out.push('\n');
+    }
+    out.push('\n');
+    let mut forms = ContextForms::from_program(program.clone());
+    forms.sort_for_render();
+    render_forms(&program.exprs, &forms, vocab, &mut out);
+    out.trim_end().to_string() + "\n"
 }
 
 #[derive(Debug, Clone, Copy)]
 enum TopLevelKind {
     Import,
     Alias,
+    Const,
     Sort,
     Data,
     Relation,
@@ -61,6 +480,7 @@ struct ContextAssignments {
     block_names: Vec<String>,
     imports: Vec<Option<usize>>,
     aliases: Vec<Option<usize>>,
+    consts: Vec<Option<usize>>,
     sorts: Vec<Option<usize>>,
     data_decls: Vec<Option<usize>>,
     relations: Vec<Option<usize>>,
@@ -69,6 +489,7 @@ struct ContextAssignments {
     asserts: Vec<Option<usize>>,
     universes: Vec<Option<usize>>,
     defns: Vec<Option<usize>>,
+    profile_blocks: Vec<(usize, usize)>,
 }
 
 impl ContextAssignments {
@@ -76,6 +497,7 @@ impl ContextAssignments {
         match kind {
             TopLevelKind::Import => self.imports.push(block_idx),
             TopLevelKind::Alias => self.aliases.push(block_idx),
+            TopLevelKind::Const => self.consts.push(block_idx),
             TopLevelKind::Sort => self.sorts.push(block_idx),
             TopLevelKind::Data => self.data_decls.push(block_idx),
             TopLevelKind::Relation => self.relations.push(block_idx),
@@ -92,6 +514,7 @@ impl ContextAssignments {
 struct ContextForms {
     imports: Vec<ImportDecl>,
     aliases: Vec<AliasDecl>,
+    consts: Vec<ConstDecl>,
     sorts: Vec<SortDecl>,
     data_decls: Vec<DataDecl>,
     relations: Vec<RelationDecl>,
@@ -100,6 +523,7 @@ struct ContextForms {
     asserts: Vec<AssertDecl>,
     universes: Vec<UniverseDecl>,
     defns: Vec<Defn>,
+    profile_blocks: Vec<(usize, usize)>,
 }
 
 impl ContextForms {
@@ -107,6 +531,7 @@ impl ContextForms {
         Self {
             imports: program.imports,
             aliases: program.aliases,
+            consts: program.consts,
             sorts: program.sorts,
             data_decls: program.data_decls,
             relations: program.relations,
@@ -115,12 +540,25 @@ impl ContextForms {
             asserts: program.asserts,
             universes: program.universes,
             defns: program.defns,
+            profile_blocks: Vec::new(),
         }
     }
 
+    fn with_profile_blocks(mut self, src: &str, keywords: &SurfaceKeywordMap) -> Self {
+        self.profile_blocks = collect_context_assignments(src, keywords).profile_blocks;
+        self
+    }
+
+    fn profile_block(&self, span: &Span) -> Option<usize> {
+        self.profile_blocks
+            .iter()
+            .position(|&(start, end)| start <= span.start && span.start < end)
+    }
+
     fn is_empty(&self) -> bool {
         self.imports.is_empty()
             && self.aliases.is_empty()
+            && self.consts.is_empty()
             && self.sorts.is_empty()
             && self.data_decls.is_empty()
             && self.relations.is_empty()
@@ -135,6 +573,7 @@ impl ContextForms {
         self.imports.sort_by(|a, b| a.path.cmp(&b.path));
         self.aliases
             .sort_by(|a, b| a.alias.cmp(&b.alias).then(a.canonical.cmp(&b.canonical)));
+        self.consts.sort_by(|a, b| a.name.cmp(&b.name));
         self.sorts.sort_by(|a, b| a.name.cmp(&b.name));
         self.data_decls.sort_by(|a, b| a.name.cmp(&b.name));
         self.relations.sort_by(|a, b| a.name.cmp(&b.name));
@@ -142,10 +581,17 @@ impl ContextForms {
     }
 }
 
-fn render_with_context_blocks(program: Program, src: &str, out: &mut String) {
+fn render_with_context_blocks(
+    program: Program,
+    src: &str,
+    keywords: &SurfaceKeywordMap,
+    vocab: &SurfaceVocabulary<'_>,
+    out: &mut String,
+) {
     let Program {
         imports,
         aliases,
+        consts,
         sorts,
         data_decls,
         relations,
@@ -154,9 +600,11 @@ fn render_with_context_blocks(program: Program, src: &str, out: &mut String) {
         asserts,
         universes,
         defns,
+        exprs,
+        name_uses: _,
     } = program;
 
-    let assignments = collect_context_assignments(src);
+    let assignments = collect_context_assignments(src, keywords);
 
     let mut prelude = ContextForms::default();
     let mut blocks = assignments
@@ -167,6 +615,7 @@ fn render_with_context_blocks(program: Program, src: &str, out: &mut String) {
 
     assign_imports(imports, &assignments.imports, &mut prelude, &mut blocks);
     assign_aliases(aliases, &assignments.aliases, &mut prelude, &mut blocks);
+    assign_consts(consts, &assignments.consts, &mut prelude, &mut blocks);
     assign_sorts(sorts, &assignments.sorts, &mut prelude, &mut blocks);
     assign_data_decls(
         data_decls,
@@ -182,14 +631,16 @@ fn render_with_context_blocks(program: Program, src: &str, out: &mut String) {
     assign_defns(defns, &assignments.defns, &mut prelude, &mut blocks);
 
     prelude.sort_for_render();
+    prelude.profile_blocks = assignments.profile_blocks.clone();
     for (_, forms) in &mut blocks {
         forms.sort_for_render();
+        forms.profile_blocks = assignments.profile_blocks.clone();
     }
 
     let mut emitted = false;
     if blocks.is_empty() || !prelude.is_empty() {
         out.push_str("; @context: default\n\n");
-        render_forms(&prelude, out);
+        render_forms(&exprs, &prelude, vocab, out);
         emitted = true;
     }
 
@@ -198,7 +649,7 @@ fn render_with_context_blocks(program: Program, src: &str, out: &mut String) {
             out.push('\n');
         }
         out.push_str(&format!("; @context: {name}\n\n"));
-        render_forms(forms, out);
+        render_forms(&exprs, forms, vocab, out);
         emitted = true;
     }
 }
@@ -207,6 +658,7 @@ fn canonical_top_level_kind(head: &str) -> Option<TopLevelKind> {
     match head {
         "import" | "インポート" => Some(TopLevelKind::Import),
         "alias" | "同義語" => Some(TopLevelKind::Alias),
+        "defconst" | "定数" => Some(TopLevelKind::Const),
         "sort" | "型" => Some(TopLevelKind::Sort),
         "data" | "データ" => Some(TopLevelKind::Data),
         "relation" | "関係" => Some(TopLevelKind::Relation),
@@ -230,15 +682,52 @@ fn contains_selfdoc_form(src: &str) -> bool {
     pattern.is_match(src)
 }
 
-fn collect_context_assignments(src: &str) -> ContextAssignments {
+fn contains_macro_definition(src: &str) -> bool {
+    static MACRO_DEFINITION_RE: OnceLock<regex::Regex> = OnceLock::new();
+    let pattern = MACRO_DEFINITION_RE.get_or_init(|| {
+        regex::Regex::new(r"\(\s*(?:defmacro|マクロ)\s").expect("valid macro regex")
+    });
+    pattern.is_match(src)
+}
+
+fn contains_custom_selfdoc_head(src: &str, keywords: &SurfaceKeywordMap) -> bool {
+    keywords.iter().any(|(word, canonical)| {
+        matches!(
+            canonical,
+            "project"
+                | "module"
+                | "reference"
+                | "contract"
+                | "quality-gate"
+                | "プロジェクト"
+                | "モジュール"
+                | "参照"
+                | "契約"
+                | "品質ゲート"
+        ) && src.contains(&format!("({word}"))
+    })
+}
+
+fn collect_context_assignments(src: &str, keywords: &SurfaceKeywordMap) -> ContextAssignments {
+    let edition = source_edition(src);
     let mut out = ContextAssignments::default();
     let mut current_block = None;
     let mut it = src.char_indices().peekable();
+    skip_to(&mut it, shebang_len(src));
 
-    while let Some((_, ch)) = it.next() {
+    while let Some((idx, ch)) = it.next() {
         if ch.is_whitespace() {
             continue;
         }
+        if let Some(end) = block_comment_end(src, idx, edition) {
+            skip_to(&mut it, end);
+            continue;
+        }
+        if datum_comment_starts(src.as_bytes(), idx, edition) {
+            it.next();
+            skip_datum(src, edition, &mut it);
+            continue;
+        }
         if ch == ';' {
             let comment = consume_comment(&mut it);
             if let Some(ctx) = parse_context_marker(&comment) {
@@ -252,16 +741,58 @@ fn collect_context_assignments(src: &str) -> ContextAssignments {
         }
 
         let mut depth = 1usize;
-        let head = consume_head_atom(&mut it, &mut depth);
-        if let Some(kind) = canonical_top_level_kind(head.as_str()) {
+        let head = consume_head_atom(src, edition, &mut it, &mut depth);
+        let head = keywords.canonical(&head).unwrap_or(&head);
+        if let Some(kind) = canonical_top_level_kind(head) {
             out.push(kind, current_block);
+        } else if depth == 1 && matches!(head, "when-profile" | "プロファイル限定") {
+            collect_profile_forms(src, edition, &mut it, keywords, current_block, &mut out);
+            let end = it.peek().map_or(src.len(), |&(end, _)| end);
+            out.profile_blocks.push((idx, end));
+            continue;
         }
-        consume_to_form_end(&mut it, &mut depth);
+        consume_to_form_end(src, edition, &mut it, &mut depth);
     }
 
     out
 }
 
+fn collect_profile_forms(
+    src: &str,
+    edition: Edition,
+    it: &mut Peekable<CharIndices<'_>>,
+    keywords: &SurfaceKeywordMap,
+    block: Option<usize>,
+    out: &mut ContextAssignments,
+) {
+    while let Some((idx, ch)) = it.peek().copied() {
+        if ch.is_whitespace() {
+            it.next();
+        } else if ch == ';' {
+            consume_comment(it);
+        } else if let Some(end) = block_comment_end(src, idx, edition) {
+            skip_to(it, end);
+        } else if datum_comment_starts(src.as_bytes(), idx, edition) {
+            it.nth(1);
+            skip_datum(src, edition, it);
+        } else if ch == ')' {
+            it.next();
+            return;
+        } else if ch == '(' {
+            it.next();
+            let mut depth = 1usize;
+            let head = consume_head_atom(src, edition, it, &mut depth);
+            let head = keywords.canonical(&head).unwrap_or(&head);
+            if let Some(kind) = canonical_top_level_kind(head) {
+                out.push(kind, block);
+            }
+            consume_to_form_end(src, edition, it, &mut depth);
+        } else {
+            skip_datum(src, edition, it);
+        }
+    }
+}
+
 fn consume_comment(it: &mut Peekable<CharIndices<'_>>) -> String {
     let mut out = String::new();
     while let Some((_, ch)) = it.peek().copied() {
@@ -281,15 +812,29 @@ fn parse_context_marker(comment: &str) -> Option<&str> {
     (!name.is_empty()).then_some(name)
 }
 
-fn consume_head_atom(it: &mut Peekable<CharIndices<'_>>, depth: &mut usize) -> String {
+fn consume_head_atom(
+    src: &str,
+    edition: Edition,
+    it: &mut Peekable<CharIndices<'_>>,
+    depth: &mut usize,
+) -> String {
     loop {
-        let Some((_, ch)) = it.peek().copied() else {
+        let Some((idx, ch)) = it.peek().copied() else {
             return String::new();
         };
         if ch.is_whitespace() {
             it.next();
             continue;
         }
+        if let Some(end) = block_comment_end(src, idx, edition) {
+            skip_to(it, end);
+            continue;
+        }
+        if datum_comment_starts(src.as_bytes(), idx, edition) {
+            it.nth(1);
+            skip_datum(src, edition, it);
+            continue;
+        }
         if ch == ';' {
             it.next();
             consume_comment(it);
@@ -318,15 +863,27 @@ fn consume_head_atom(it: &mut Peekable<CharIndices<'_>>, depth: &mut usize) -> S
     }
 }
 
-fn consume_to_form_end(it: &mut Peekable<CharIndices<'_>>, depth: &mut usize) {
+fn consume_to_form_end(
+    src: &str,
+    edition: Edition,
+    it: &mut Peekable<CharIndices<'_>>,
+    depth: &mut usize,
+) {
     while *depth > 0 {
-        let Some((_, ch)) = it.next() else {
+        let Some((idx, ch)) = it.next() else {
             break;
         };
         match ch {
             ';' => {
                 consume_comment(it);
             }
+            '"' | '#' => {
+                if let Some(end) = block_comment_end(src, idx, edition)
+                    .or_else(|| string_literal_end(src, idx, edition))
+                {
+                    skip_to(it, end);
+                }
+            }
             '(' => *depth += 1,
             ')' => *depth = depth.saturating_sub(1),
             _ => {}
@@ -334,6 +891,42 @@ fn consume_to_form_end(it: &mut Peekable<CharIndices<'_>>, depth: &mut usize) {
     }
 }
 
+fn skip_datum(src: &str, edition: Edition, it: &mut Peekable<CharIndices<'_>>) {
+    while let Some((idx, ch)) = it.next() {
+        if ch.is_whitespace() {
+            continue;
+        }
+        if ch == ';' {
+            consume_comment(it);
+            continue;
+        }
+        if let Some(end) = block_comment_end(src, idx, edition) {
+            skip_to(it, end);
+            continue;
+        }
+        if datum_comment_starts(src.as_bytes(), idx, edition) {
+            it.next();
+            skip_datum(src, edition, it);
+            continue;
+        }
+        if let Some(end) = string_literal_end(src, idx, edition) {
+            skip_to(it, end);
+        } else if ch == '(' {
+            consume_to_form_end(src, edition, it, &mut 1);
+        } else if ch != ')' {
+            while it
+                .next_if(|(_, c)| !c.is_whitespace() && !matches!(c, '(' | ')' | ';'))
+                .is_some()
+            {}
+        }
+        return;
+    }
+}
+
+fn skip_to(it: &mut Peekable<CharIndices<'_>>, end: usize) {
+    while it.next_if(|(next, _)| *next < end).is_some() {}
+}
+
 fn assign_imports(
     items: Vec<ImportDecl>,
     contexts: &[Option<usize>],
@@ -368,6 +961,23 @@ fn assign_aliases(
     }
 }
 
+fn assign_consts(
+    items: Vec<ConstDecl>,
+    contexts: &[Option<usize>],
+    prelude: &mut ContextForms,
+    blocks: &mut [(String, ContextForms)],
+) {
+    for (idx, item) in items.into_iter().enumerate() {
+        if let Some(block_idx) = contexts.get(idx).copied().flatten()
+            && let Some((_, forms)) = blocks.get_mut(block_idx)
+        {
+            forms.consts.push(item);
+            continue;
+        }
+        prelude.consts.push(item);
+    }
+}
+
 fn assign_sorts(
     items: Vec<SortDecl>,
     contexts: &[Option<usize>],
@@ -504,26 +1114,58 @@ fn assign_defns(
     }
 }
 
-fn render_forms(forms: &ContextForms, out: &mut String) {
+fn render_forms(
+    exprs: &ExprArena,
+    forms: &ContextForms,
+    vocab: &SurfaceVocabulary<'_>,
+    out: &mut String,
+) {
     for import in &forms.imports {
-        out.push_str(&format!("(インポート \"{}\")\n", import.path));
+        out.push_str(&format!(
+            "({} {})\n",
+            vocab.import,
+            quote_atom(&import.path)
+        ));
     }
     if !forms.imports.is_empty() {
         out.push('\n');
     }
 
     for alias in &forms.aliases {
-        out.push_str(&format!(
-            "(同義語 :別名 {} :正規 {})\n",
-            alias.alias, alias.canonical
+        out.push_str(&render_tagged_form(
+            vocab.alias,
+            None,
+            &[
+                (vocab.alias_tag, alias.alias.clone()),
+                (vocab.canonical_tag, alias.canonical.clone()),
+            ],
         ));
+        out.push('\n');
     }
     if !forms.aliases.is_empty() {
         out.push('\n');
     }
 
+    for constant in &forms.consts {
+        out.push_str(&render_tagged_form(
+            vocab.defconst,
+            Some(&constant.name),
+            &[(vocab.value_tag, render_expr(exprs, constant.value))],
+        ));
+        out.push('\n');
+    }
+    if !forms.consts.is_empty() {
+        out.push('\n');
+    }
+
     for sort in &forms.sorts {
-        out.push_str(&format!("(型 {})\n", sort.name));
+        let extra = declaration_tags(vocab, None, &sort.attributes);
+        let tags = extra
+            .iter()
+            .map(|(tag, value)| (tag.as_str(), value.clone()))
+            .collect::<Vec<_>>();
+        out.push_str(&render_tagged_form(vocab.sort, Some(&sort.name), &tags));
+        out.push('\n');
     }
     if !forms.sorts.is_empty() {
         out.push('\n');
@@ -548,67 +1190,111 @@ fn render_forms(forms: &ContextForms, out: &mut String) {
             })
             .collect::<Vec<_>>()
             .join(" ");
-        out.push_str(&format!(
-            "(データ {} :コンストラクタ ({}))\n",
-            data.name, ctors
-        ));
+        let mut tags = vec![(vocab.constructors_tag, format!("({ctors})"))];
+        let extra = declaration_tags(vocab, None, &data.attributes);
+        tags.extend(
+            extra
+                .iter()
+                .map(|(tag, value)| (tag.as_str(), value.clone())),
+        );
+        out.push_str(&render_tagged_form(vocab.data, Some(&data.name), &tags));
+        out.push('\n');
     }
     if !forms.data_decls.is_empty() {
         out.push('\n');
     }
 
     for relation in &forms.relations {
-        out.push_str(&format!(
-            "(関係 {} :引数 ({}))\n",
-            relation.name,
-            relation.arg_sorts.join(" ")
+        let args = relation
+            .arg_sorts
+            .iter()
+            .zip(&relation.arg_refinements)
+            .map(|(sort, refinement)| match refinement {
+                Some(refinement) => format!(
+                    "(Refine {} {sort} {})",
+                    refinement.var,
+                    render_formula_refine(&refinement.formula)
+                ),
+                None => sort.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut tags = vec![(vocab.args_tag, format!("({args})"))];
+        if let Some(key) = &relation.key {
+            let positions = key.iter().map(usize::to_string).collect::<Vec<_>>();
+            tags.push((vocab.key_tag, format!("({})", positions.join(" "))));
+        }
+        let extra = declaration_tags(vocab, relation.doc.as_deref(), &relation.attributes);
+        tags.extend(
+            extra
+                .iter()
+                .map(|(tag, value)| (tag.as_str(), value.clone())),
+        );
+        out.push_str(&render_tagged_form(
+            vocab.relation,
+            Some(&relation.name),
+            &tags,
         ));
+        out.push('\n');
     }
     if !forms.relations.is_empty() {
         out.push('\n');
     }
 
-    for fact in &forms.facts {
+    let facts = forms.facts.iter().map(|fact| {
         let terms = fact
             .terms
             .iter()
             .map(render_logic_term)
             .collect::<Vec<_>>()
             .join(" ");
-        out.push_str(&format!("(事実 {} :項 ({}))\n", fact.name, terms));
-    }
-    if !forms.facts.is_empty() {
-        out.push('\n');
-    }
+        let form = render_tagged_form(
+            vocab.fact,
+            Some(&fact.name),
+            &[(vocab.terms_tag, format!("({terms})"))],
+        );
+        ProfiledForm::new(forms, fact.profile.as_deref(), &fact.span, form)
+    });
+    let facts = facts.collect::<Vec<_>>();
 
-    for rule in &forms.rules {
-        out.push_str(&format!(
-            "(規則 :頭 {} :本体 {})\n",
-            render_atom_rule(&rule.head),
-            render_formula_rule(&rule.body)
-        ));
-    }
-    if !forms.rules.is_empty() {
-        out.push('\n');
-    }
+    let rules = forms.rules.iter().map(|rule| {
+        let mut tags = vec![
+            (vocab.head_tag, render_atom_rule(&rule.head)),
+            (vocab.body_tag, render_formula_rule(&rule.body)),
+        ];
+        if let Some(depth) = rule.skolem {
+            tags.push((vocab.skolem_tag, depth.to_string()));
+        }
+        let form = render_tagged_form(vocab.rule, None, &tags);
+        ProfiledForm::new(forms, rule.profile.as_deref(), &rule.span, form)
+    });
+    let rules = rules.collect::<Vec<_>>();
 
-    for assertion in &forms.asserts {
+    let asserts = forms.asserts.iter().map(|assertion| {
         let params = assertion
             .params
             .iter()
             .map(|p| format!("({} {})", p.name, render_type(&p.ty)))
             .collect::<Vec<_>>()
             .join(" ");
-        out.push_str(&format!(
-            "(検証 {} :引数 ({}) :式 {})\n",
-            assertion.name,
-            params,
-            render_formula_refine(&assertion.formula)
-        ));
-    }
-    if !forms.asserts.is_empty() {
-        out.push('\n');
-    }
+        let mut tags = vec![
+            (vocab.params_tag, format!("({params})")),
+            (vocab.formula_tag, render_formula_refine(&assertion.formula)),
+        ];
+        if let Some(message) = &assertion.message {
+            tags.push((vocab.message_tag, quote_atom(message)));
+        }
+        let extra = declaration_tags(vocab, assertion.doc.as_deref(), &assertion.attributes);
+        tags.extend(
+            extra
+                .iter()
+                .map(|(tag, value)| (tag.as_str(), value.clone())),
+        );
+        let form = render_tagged_form(vocab.assert, Some(&assertion.name), &tags);
+        ProfiledForm::new(forms, assertion.profile.as_deref(), &assertion.span, form)
+    });
+    let asserts = asserts.collect::<Vec<_>>();
+    push_profiled_sections(vocab, [facts, rules, asserts], out);
 
     for universe in &forms.universes {
         let values = universe
@@ -617,7 +1303,12 @@ fn render_forms(forms: &ContextForms, out: &mut String) {
             .map(render_logic_term)
             .collect::<Vec<_>>()
             .join(" ");
-        out.push_str(&format!("(宇宙 {} :値 ({}))\n", universe.ty_name, values));
+        out.push_str(&render_tagged_form(
+            vocab.universe,
+            Some(&universe.ty_name),
+            &[(vocab.values_tag, format!("({values})"))],
+        ));
+        out.push('\n');
     }
     if !forms.universes.is_empty() {
         out.push('\n');
@@ -630,23 +1321,194 @@ fn render_forms(forms: &ContextForms, out: &mut String) {
             .map(|p| format!("({} {})", p.name, render_type(&p.ty)))
             .collect::<Vec<_>>()
             .join(" ");
-        out.push_str(&format!(
-            "(関数 {}\n  :引数 ({})\n  :戻り {}\n  :本体 {})\n",
-            defn.name,
-            params,
-            render_type(&defn.ret_type),
-            render_expr(&defn.body)
-        ));
+        let mut tags = vec![
+            (vocab.params_tag, format!("({params})")),
+            (vocab.ret_tag, render_type(&defn.ret_type)),
+        ];
+        if defn.clauses.is_empty() {
+            tags.push((vocab.body_tag, render_expr(exprs, defn.body)));
+        } else {
+            let clauses = defn
+                .clauses
+                .iter()
+                .map(|clause| {
+                    let patterns = clause
+                        .patterns
+                        .iter()
+                        .map(|pattern| render_pattern(exprs, *pattern))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    format!("(({patterns}) {})", render_expr(exprs, clause.body))
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            tags.push((vocab.clauses_tag, format!("({clauses})")));
+        }
+        if let Some(measure) = defn.measure {
+            tags.push((vocab.measure_tag, render_expr(exprs, measure)));
+        }
+        let extra = declaration_tags(vocab, defn.doc.as_deref(), &defn.attributes);
+        tags.extend(
+            extra
+                .iter()
+                .map(|(tag, value)| (tag.as_str(), value.clone())),
+        );
+        out.push_str(&render_tagged_block(vocab.defn, Some(&defn.name), &tags));
+        out.push('\n');
     }
 }
 
-fn render_type(ty: &Type) -> String {
+struct ProfiledForm<'a> {
+    profile: Option<&'a str>,
+    block: Option<usize>,
+    start: usize,
+    form: String,
+}
+
+impl<'a> ProfiledForm<'a> {
+    fn new(forms: &ContextForms, profile: Option<&'a str>, span: &Span, form: String) -> Self {
+        Self {
+            profile,
+            block: profile.and(forms.profile_block(span)),
+            start: span.start,
+            form,
+        }
+    }
+}
+
+fn push_profiled_sections(
+    vocab: &SurfaceVocabulary<'_>,
+    sections: [Vec<ProfiledForm<'_>>; 3],
+    out: &mut String,
+) {
+    let mut members = BTreeMap::<usize, Vec<&ProfiledForm<'_>>>::new();
+    for form in sections.iter().flatten() {
+        if let Some(block) = form.block {
+            members.entry(block).or_default().push(form);
+        }
+    }
+    for forms in members.values_mut() {
+        forms.sort_by_key(|form| form.start);
+    }
+
+    for section in &sections {
+        let mut chunks: Vec<(Option<&str>, Vec<&str>, bool)> = Vec::new();
+        for form in section {
+            match (form.profile, form.block) {
+                (None, _) => chunks.push((None, vec![&form.form], false)),
+                (Some(_), Some(block)) => {
+                    if let Some(forms) = members.remove(&block) {
+                        let forms = forms.iter().map(|form| form.form.as_str()).collect();
+                        chunks.push((form.profile, forms, false));
+                    }
+                }
+                (Some(_), None) => match chunks.last_mut() {
+                    Some((profile, forms, true)) if *profile == form.profile => {
+                        forms.push(&form.form);
+                    }
+                    _ => chunks.push((form.profile, vec![&form.form], true)),
+                },
+            }
+        }
+
+        let mut previous_gated = None;
+        for (profile, forms, _) in &chunks {
+            let gated = profile.is_some();
+            if previous_gated.is_some_and(|previous| previous || gated) {
+                out.push('\n');
+            }
+            previous_gated = Some(gated);
+            let Some(name) = profile else {
+                out.push_str(forms[0]);
+                out.push('\n');
+                continue;
+            };
+            out.push_str(&format!("({} {name}", vocab.when_profile));
+            for line in forms.iter().flat_map(|form| form.lines()) {
+                out.push_str("\n  ");
+                out.push_str(line);
+            }
+            out.push_str(")\n");
+        }
+        if !chunks.is_empty() {
+            out.push('\n');
+        }
+    }
+}
+
+fn render_tagged_form(head: &str, name: Option<&str>, tags: &[(&str, String)]) -> String {
+    let mut inline = format!("({head}");
+    if let Some(name) = name {
+        inline.push(' ');
+        inline.push_str(name);
+    }
+    for (tag, value) in tags {
+        inline.push_str(&format!(" {tag} {value}"));
+    }
+    inline.push(')');
+    if display_width(&inline) <= MAX_INLINE_WIDTH {
+        inline
+    } else {
+        render_tagged_block(head, name, tags)
+    }
+}
+
+fn render_tagged_block(head: &str, name: Option<&str>, tags: &[(&str, String)]) -> String {
+    let tag_width = tags
+        .iter()
+        .map(|(tag, _)| display_width(tag))
+        .max()
+        .unwrap_or(0);
+    let mut out = format!("({head}");
+    if let Some(name) = name {
+        out.push(' ');
+        out.push_str(name);
+    }
+    for (tag, value) in tags {
+        let padding = " ".repeat(tag_width - display_width(tag));
+        out.push_str(&format!("\n  {tag}{padding} {value}"));
+    }
+    out.push(')');
+    out
+}
+
+fn declaration_tags(
+    vocab: &SurfaceVocabulary<'_>,
+    doc: Option<&str>,
+    attributes: &BTreeMap<String, String>,
+) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    if let Some(doc) = doc {
+        out.push((vocab.doc_tag.to_string(), quote_atom(doc)));
+    }
+    for (key, value) in attributes {
+        out.push((format!(":{key}"), quote_atom(value)));
+    }
+    out
+}
+
+fn render_symbol(symbol: &str) -> String {
+    match symbol
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        Some(text) => quote_atom(text),
+        None => symbol.to_string(),
+    }
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+pub(crate) fn render_type(ty: &Type) -> String {
     match ty {
         Type::Bool => "Bool".to_string(),
         Type::Int => "Int".to_string(),
         Type::Symbol => "Symbol".to_string(),
         Type::Domain(name) => name.clone(),
         Type::Adt(name) => format!("(Adt {name})"),
+        Type::Error => "?".to_string(),
         Type::Fun(args, ret) => format!(
             "(-> ({}) {})",
             args.iter().map(render_type).collect::<Vec<_>>().join(" "),
@@ -661,6 +1523,14 @@ fn render_type(ty: &Type) -> String {
     }
 }
 
+pub(crate) fn render_rule(rule: &Rule) -> String {
+    format!(
+        "(rule {} {})",
+        render_atom_rule(&rule.head),
+        render_formula_rule(&rule.body)
+    )
+}
+
 fn render_formula_rule(formula: &Formula) -> String {
     match formula {
         Formula::True => "true".to_string(),
@@ -728,7 +1598,7 @@ fn render_atom_refine(atom: &Atom) -> String {
 fn render_logic_term_rule(term: &LogicTerm) -> String {
     match term {
         LogicTerm::Var(v) => format!("?{v}"),
-        LogicTerm::Symbol(s) => s.clone(),
+        LogicTerm::Symbol(s) => render_symbol(s),
         LogicTerm::Int(i) => i.to_string(),
         LogicTerm::Bool(b) => b.to_string(),
         LogicTerm::Ctor { name, args } => {
@@ -751,7 +1621,7 @@ fn render_logic_term_rule(term: &LogicTerm) -> String {
 fn render_logic_term_refine(term: &LogicTerm) -> String {
     match term {
         LogicTerm::Var(v) => v.clone(),
-        LogicTerm::Symbol(s) => s.clone(),
+        LogicTerm::Symbol(s) => render_symbol(s),
         LogicTerm::Int(i) => i.to_string(),
         LogicTerm::Bool(b) => b.to_string(),
         LogicTerm::Ctor { name, args } => {
@@ -771,10 +1641,10 @@ fn render_logic_term_refine(term: &LogicTerm) -> String {
     }
 }
 
-fn render_logic_term(term: &LogicTerm) -> String {
+pub(crate) fn render_logic_term(term: &LogicTerm) -> String {
     match term {
         LogicTerm::Var(v) => v.clone(),
-        LogicTerm::Symbol(s) => s.clone(),
+        LogicTerm::Symbol(s) => render_symbol(s),
         LogicTerm::Int(i) => i.to_string(),
         LogicTerm::Bool(b) => b.to_string(),
         LogicTerm::Ctor { name, args } => {
@@ -794,12 +1664,13 @@ fn render_logic_term(term: &LogicTerm) -> String {
     }
 }
 
-fn render_expr(expr: &Expr) -> String {
-    match expr {
+pub(crate) fn render_expr(exprs: &ExprArena, expr: ExprId) -> String {
+    match &exprs[expr] {
         Expr::Var { name, .. } => name.clone(),
-        Expr::Symbol { value, .. } => value.clone(),
+        Expr::Symbol { value, .. } => render_symbol(value),
         Expr::Int { value, .. } => value.to_string(),
         Expr::Bool { value, .. } => value.to_string(),
+        Expr::Hole { .. } => "_?".to_string(),
         Expr::Call { name, args, .. } => {
             if args.is_empty() {
                 format!("({name})")
@@ -807,7 +1678,10 @@ fn render_expr(expr: &Expr) -> String {
                 format!(
                     "({} {})",
                     name,
-                    args.iter().map(render_expr).collect::<Vec<_>>().join(" ")
+                    args.iter()
+                        .map(|arg| render_expr(exprs, *arg))
+                        .collect::<Vec<_>>()
+                        .join(" ")
                 )
             }
         }
@@ -815,10 +1689,28 @@ fn render_expr(expr: &Expr) -> String {
             "(let ({}) {})",
             bindings
                 .iter()
-                .map(|(name, expr, _)| format!("({} {})", name, render_expr(expr)))
+                .map(|(name, value, _)| format!("({} {})", name, render_expr(exprs, *value)))
                 .collect::<Vec<_>>()
                 .join(" "),
-            render_expr(body)
+            render_expr(exprs, *body)
+        ),
+        Expr::LetFn { fns, body, .. } => format!(
+            "(letfn ({}) {})",
+            fns.iter()
+                .map(|f| format!(
+                    "({} ({}) {} {})",
+                    f.name,
+                    f.params
+                        .iter()
+                        .map(|p| format!("({} {})", p.name, render_type(&p.ty)))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    render_type(&f.ret_type),
+                    render_expr(exprs, f.body)
+                ))
+                .collect::<Vec<_>>()
+                .join(" "),
+            render_expr(exprs, *body)
         ),
         Expr::If {
             cond,
@@ -827,20 +1719,20 @@ fn render_expr(expr: &Expr) -> String {
             ..
         } => format!(
             "(if {} {} {})",
-            render_expr(cond),
-            render_expr(then_branch),
-            render_expr(else_branch)
+            render_expr(exprs, *cond),
+            render_expr(exprs, *then_branch),
+            render_expr(exprs, *else_branch)
         ),
         Expr::Match {
             scrutinee, arms, ..
         } => format!(
             "(match {} {})",
-            render_expr(scrutinee),
+            render_expr(exprs, *scrutinee),
             arms.iter()
                 .map(|arm| format!(
                     "({} {})",
-                    render_pattern(&arm.pattern),
-                    render_expr(&arm.body)
+                    render_pattern(exprs, arm.pattern),
+                    render_expr(exprs, arm.body)
                 ))
                 .collect::<Vec<_>>()
                 .join(" ")
@@ -848,11 +1740,11 @@ fn render_expr(expr: &Expr) -> String {
     }
 }
 
-fn render_pattern(pattern: &Pattern) -> String {
-    match pattern {
+pub(crate) fn render_pattern(exprs: &ExprArena, pattern: PatternId) -> String {
+    match &exprs[pattern] {
         Pattern::Wildcard { .. } => "_".to_string(),
         Pattern::Var { name, .. } => name.clone(),
-        Pattern::Symbol { value, .. } => value.clone(),
+        Pattern::Symbol { value, .. } => render_symbol(value),
         Pattern::Int { value, .. } => value.to_string(),
         Pattern::Bool { value, .. } => value.to_string(),
         Pattern::Ctor { name, args, .. } => {
@@ -863,7 +1755,7 @@ fn render_pattern(pattern: &Pattern) -> String {
                     "({} {})",
                     name,
                     args.iter()
-                        .map(render_pattern)
+                        .map(|arg| render_pattern(exprs, *arg))
                         .collect::<Vec<_>>()
                         .join(" ")
                 )
@@ -992,7 +1884,7 @@ mod tests {
             (universe Subject (alice bob))
             (defn can ((u Subject)) Bool (if true (allowed u) false))
         "#;
-        let assignments = collect_context_assignments(src);
+        let assignments = collect_context_assignments(src, &SurfaceKeywordMap::default());
 
         assert_eq!(assignments.block_names, vec!["prelude", "app"]);
         assert_eq!(assignments.imports, vec![Some(0)]);
@@ -1011,28 +1903,75 @@ mod tests {
     fn consume_head_atom_and_form_end_cover_edge_cases() {
         let mut end_it = "".char_indices().peekable();
         let mut depth = 1usize;
-        assert_eq!(consume_head_atom(&mut end_it, &mut depth), "");
+        assert_eq!(
+            consume_head_atom("", Edition::LATEST, &mut end_it, &mut depth),
+            ""
+        );
         assert_eq!(depth, 1);
 
         let mut ws_comment_it = "  ; note\nrule ?x)".char_indices().peekable();
         depth = 1;
-        assert_eq!(consume_head_atom(&mut ws_comment_it, &mut depth), "rule");
+        assert_eq!(
+            consume_head_atom(
+                "  ; note\nrule ?x)",
+                Edition::LATEST,
+                &mut ws_comment_it,
+                &mut depth
+            ),
+            "rule"
+        );
+        assert_eq!(depth, 1);
+
+        let block = "#| (a #| b |# c) |# fact p)";
+        let mut block_it = block.char_indices().peekable();
+        depth = 1;
+        assert_eq!(
+            consume_head_atom(block, Edition::LATEST, &mut block_it, &mut depth),
+            "fact"
+        );
         assert_eq!(depth, 1);
 
         let mut open_it = "(nested".char_indices().peekable();
         depth = 1;
-        assert_eq!(consume_head_atom(&mut open_it, &mut depth), "");
+        assert_eq!(
+            consume_head_atom("(nested", Edition::LATEST, &mut open_it, &mut depth),
+            ""
+        );
         assert_eq!(depth, 2);
 
         let mut close_it = ")".char_indices().peekable();
         depth = 1;
-        assert_eq!(consume_head_atom(&mut close_it, &mut depth), "");
+        assert_eq!(
+            consume_head_atom(")", Edition::LATEST, &mut close_it, &mut depth),
+            ""
+        );
         assert_eq!(depth, 0);
 
         let mut consume_it = " foo ; cmt\n(bar)) trailing".char_indices().peekable();
         depth = 1;
-        consume_to_form_end(&mut consume_it, &mut depth);
+        consume_to_form_end(
+            " foo ; cmt\n(bar)) trailing",
+            Edition::LATEST,
+            &mut consume_it,
+            &mut depth,
+        );
         assert_eq!(depth, 0);
+
+        let datum = "#; (ignored (x)) #; \"a b\" #;#; y z ; note\n fact p)";
+        let mut datum_it = datum.char_indices().peekable();
+        depth = 1;
+        assert_eq!(
+            consume_head_atom(datum, Edition::LATEST, &mut datum_it, &mut depth),
+            "fact"
+        );
+        assert_eq!(depth, 1);
+
+        let block = " #| ) |# x) (next";
+        let mut block_it = block.char_indices().peekable();
+        depth = 1;
+        consume_to_form_end(block, Edition::LATEST, &mut block_it, &mut depth);
+        assert_eq!(depth, 0);
+        assert_eq!(block_it.next(), Some((11, ' ')));
     }
 
     #[test]
@@ -1118,85 +2057,82 @@ mod tests {
 
     #[test]
     fn render_expr_and_pattern_cover_all_variants() {
-        let call0 = Expr::Call {
+        let mut exprs = ExprArena::new();
+        let var = |exprs: &mut ExprArena, name: &str| {
+            exprs.alloc(Expr::Var {
+                name: name.to_string(),
+                span: span(),
+            })
+        };
+        let int = |exprs: &mut ExprArena, value: i64| {
+            exprs.alloc(Expr::Int {
+                value,
+                span: span(),
+            })
+        };
+
+        let call0 = exprs.alloc(Expr::Call {
             name: "f0".to_string(),
+            name_span: span(),
             args: vec![],
             span: span(),
-        };
-        let call1 = Expr::Call {
+        });
+        let x = var(&mut exprs, "x");
+        let call1 = exprs.alloc(Expr::Call {
             name: "f1".to_string(),
-            args: vec![Expr::Var {
-                name: "x".to_string(),
-                span: span(),
-            }],
+            name_span: span(),
+            args: vec![x],
             span: span(),
-        };
+        });
 
-        assert_eq!(
-            render_expr(&Expr::Var {
-                name: "v".to_string(),
-                span: span(),
-            }),
-            "v"
-        );
-        assert_eq!(
-            render_expr(&Expr::Symbol {
-                value: "sym".to_string(),
-                span: span(),
-            }),
-            "sym"
-        );
-        assert_eq!(
-            render_expr(&Expr::Int {
-                value: 7,
-                span: span(),
-            }),
-            "7"
-        );
-        assert_eq!(
-            render_expr(&Expr::Bool {
-                value: false,
-                span: span(),
-            }),
-            "false"
-        );
-        assert_eq!(render_expr(&call0), "(f0)");
-        assert_eq!(render_expr(&call1), "(f1 x)");
-
-        let let_expr = Expr::Let {
-            bindings: vec![(
-                "a".to_string(),
-                Expr::Int {
-                    value: 1,
-                    span: span(),
-                },
-                span(),
-            )],
-            body: Box::new(Expr::Var {
-                name: "a".to_string(),
-                span: span(),
-            }),
+        let v = var(&mut exprs, "v");
+        assert_eq!(render_expr(&exprs, v), "v");
+        let sym = exprs.alloc(Expr::Symbol {
+            value: "sym".to_string(),
             span: span(),
-        };
-        assert_eq!(render_expr(&let_expr), "(let ((a 1)) a)");
+        });
+        assert_eq!(render_expr(&exprs, sym), "sym");
+        let seven = int(&mut exprs, 7);
+        assert_eq!(render_expr(&exprs, seven), "7");
+        let fals = exprs.alloc(Expr::Bool {
+            value: false,
+            span: span(),
+        });
+        assert_eq!(render_expr(&exprs, fals), "false");
+        assert_eq!(render_expr(&exprs, call0), "(f0)");
+        assert_eq!(render_expr(&exprs, call1), "(f1 x)");
+
+        let one = int(&mut exprs, 1);
+        let a = var(&mut exprs, "a");
+        let let_expr = exprs.alloc(Expr::Let {
+            bindings: vec![("a".to_string(), one, span())],
+            body: a,
+            span: span(),
+        });
+        assert_eq!(render_expr(&exprs, let_expr), "(let ((a 1)) a)");
 
-        let if_expr = Expr::If {
-            cond: Box::new(Expr::Bool {
-                value: true,
-                span: span(),
-            }),
-            then_branch: Box::new(Expr::Int {
-                value: 1,
-                span: span(),
-            }),
-            else_branch: Box::new(Expr::Int {
-                value: 0,
-                span: span(),
-            }),
+        let tru = exprs.alloc(Expr::Bool {
+            value: true,
             span: span(),
-        };
-        assert_eq!(render_expr(&if_expr), "(if true 1 0)");
+        });
+        let zero = int(&mut exprs, 0);
+        let if_expr = exprs.alloc(Expr::If {
+            cond: tru,
+            then_branch: one,
+            else_branch: zero,
+            span: span(),
+        });
+        assert_eq!(render_expr(&exprs, if_expr), "(if true 1 0)");
 
+        let leaf = exprs.alloc_pattern(Pattern::Ctor {
+            name: "leaf".to_string(),
+            args: vec![],
+            span: span(),
+        });
+        let tail = exprs.alloc_pattern(Pattern::Var {
+            name: "tail".to_string(),
+            span: span(),
+        });
         let patterns = [
             Pattern::Wildcard { span: span() },
             Pattern::Var {
@@ -1217,71 +2153,62 @@ mod tests {
             },
             Pattern::Ctor {
                 name: "node".to_string(),
-                args: vec![
-                    Pattern::Ctor {
-                        name: "leaf".to_string(),
-                        args: vec![],
-                        span: span(),
-                    },
-                    Pattern::Var {
-                        name: "tail".to_string(),
-                        span: span(),
-                    },
-                ],
+                args: vec![leaf, tail],
                 span: span(),
             },
-        ];
-        assert_eq!(render_pattern(&patterns[0]), "_");
-        assert_eq!(render_pattern(&patterns[1]), "v");
-        assert_eq!(render_pattern(&patterns[2]), "alice");
-        assert_eq!(render_pattern(&patterns[3]), "3");
-        assert_eq!(render_pattern(&patterns[4]), "true");
-        assert_eq!(render_pattern(&patterns[5]), "(node (leaf) tail)");
-
-        let match_expr = Expr::Match {
-            scrutinee: Box::new(Expr::Var {
-                name: "xs".to_string(),
-                span: span(),
-            }),
+        ]
+        .map(|pattern| exprs.alloc_pattern(pattern));
+        assert_eq!(render_pattern(&exprs, patterns[0]), "_");
+        assert_eq!(render_pattern(&exprs, patterns[1]), "v");
+        assert_eq!(render_pattern(&exprs, patterns[2]), "alice");
+        assert_eq!(render_pattern(&exprs, patterns[3]), "3");
+        assert_eq!(render_pattern(&exprs, patterns[4]), "true");
+        assert_eq!(render_pattern(&exprs, patterns[5]), "(node (leaf) tail)");
+
+        let xs = var(&mut exprs, "xs");
+        let n_pattern = exprs.alloc_pattern(Pattern::Var {
+            name: "n".to_string(),
+            span: span(),
+        });
+        let node = exprs.alloc_pattern(Pattern::Ctor {
+            name: "node".to_string(),
+            args: vec![n_pattern],
+            span: span(),
+        });
+        let n = var(&mut exprs, "n");
+        let match_expr = exprs.alloc(Expr::Match {
+            scrutinee: xs,
             arms: vec![
                 MatchArm {
-                    pattern: Pattern::Ctor {
-                        name: "leaf".to_string(),
-                        args: vec![],
-                        span: span(),
-                    },
-                    body: Expr::Int {
-                        value: 0,
-                        span: span(),
-                    },
+                    pattern: leaf,
+                    body: zero,
                     span: span(),
                 },
                 MatchArm {
-                    pattern: Pattern::Ctor {
-                        name: "node".to_string(),
-                        args: vec![Pattern::Var {
-                            name: "n".to_string(),
-                            span: span(),
-                        }],
-                        span: span(),
-                    },
-                    body: Expr::Var {
-                        name: "n".to_string(),
-                        span: span(),
-                    },
+                    pattern: node,
+                    body: n,
                     span: span(),
                 },
             ],
             span: span(),
-        };
+        });
         assert_eq!(
-            render_expr(&match_expr),
+            render_expr(&exprs, match_expr),
             "(match xs ((leaf) 0) ((node n) n))"
         );
     }
 
     #[test]
     fn render_forms_and_context_blocks_work_for_mixed_content() {
+        let mut exprs = ExprArena::new();
+        let limit = exprs.alloc(Expr::Int {
+            value: 3,
+            span: span(),
+        });
+        let x = exprs.alloc(Expr::Var {
+            name: "x".to_string(),
+            span: span(),
+        });
         let mut forms = ContextForms {
             imports: vec![ImportDecl {
                 path: "zeta.dtl".to_string(),
@@ -1292,8 +2219,14 @@ mod tests {
                 canonical: "read".to_string(),
                 span: span(),
             }],
+            consts: vec![ConstDecl {
+                name: "limit".to_string(),
+                value: limit,
+                span: span(),
+            }],
             sorts: vec![SortDecl {
                 name: "Subject".to_string(),
+                attributes: BTreeMap::new(),
                 span: span(),
             }],
             data_decls: vec![DataDecl {
@@ -1310,16 +2243,22 @@ mod tests {
                         span: span(),
                     },
                 ],
+                attributes: BTreeMap::new(),
                 span: span(),
             }],
             relations: vec![RelationDecl {
                 name: "allowed".to_string(),
                 arg_sorts: vec!["Subject".to_string()],
+                arg_refinements: vec![None],
+                key: None,
+                doc: None,
+                attributes: BTreeMap::new(),
                 span: span(),
             }],
             facts: vec![Fact {
                 name: "allowed".to_string(),
                 terms: vec![LogicTerm::Symbol("alice".to_string())],
+                profile: None,
                 span: span(),
             }],
             rules: vec![Rule {
@@ -1331,6 +2270,8 @@ mod tests {
                     pred: "allowed".to_string(),
                     terms: vec![LogicTerm::Var("x".to_string())],
                 }),
+                skolem: None,
+                profile: None,
                 span: span(),
             }],
             asserts: vec![AssertDecl {
@@ -1344,6 +2285,10 @@ mod tests {
                     pred: "allowed".to_string(),
                     terms: vec![LogicTerm::Var("u".to_string())],
                 }),
+                doc: None,
+                message: None,
+                attributes: BTreeMap::new(),
+                profile: Some("production".to_string()),
                 span: span(),
             }],
             universes: vec![UniverseDecl {
@@ -1359,31 +2304,37 @@ mod tests {
                     span: span(),
                 }],
                 ret_type: Type::Int,
-                body: Expr::Var {
-                    name: "x".to_string(),
-                    span: span(),
-                },
+                body: x,
+                measure: None,
+                clauses: Vec::new(),
+                doc: None,
+                attributes: BTreeMap::new(),
                 span: span(),
             }],
+            profile_blocks: Vec::new(),
         };
         forms.sort_for_render();
 
         let mut rendered = String::new();
-        render_forms(&forms, &mut rendered);
+        render_forms(&exprs, &forms, &JAPANESE_VOCABULARY, &mut rendered);
         assert!(rendered.contains("(インポート \"zeta.dtl\")"));
         assert!(rendered.contains("(同義語 :別名 閲覧 :正規 read)"));
+        assert!(rendered.contains("(定数 limit :値 3)"));
         assert!(rendered.contains("(型 Subject)"));
         assert!(rendered.contains("(データ Node :コンストラクタ ((leaf) (cons Int)))"));
         assert!(rendered.contains("(関係 allowed :引数 (Subject))"));
         assert!(rendered.contains("(事実 allowed :項 (alice))"));
         assert!(rendered.contains("(規則 :頭 (allowed ?x) :本体 (allowed ?x))"));
-        assert!(rendered.contains("(検証 ok :引数 ((u Subject)) :式 (allowed u))"));
+        assert!(rendered.contains(
+            "(プロファイル限定 production\n  (検証 ok :引数 ((u Subject)) :式 (allowed u)))"
+        ));
         assert!(rendered.contains("(宇宙 Subject :値 (alice))"));
         assert!(rendered.contains("(関数 id"));
 
         let program = Program {
             imports: forms.imports.clone(),
             aliases: forms.aliases.clone(),
+            consts: forms.consts.clone(),
             sorts: forms.sorts.clone(),
             data_decls: forms.data_decls.clone(),
             relations: forms.relations.clone(),
@@ -1392,6 +2343,8 @@ mod tests {
             asserts: forms.asserts.clone(),
             universes: forms.universes.clone(),
             defns: forms.defns.clone(),
+            exprs,
+            name_uses: Vec::new(),
         };
         let src = r#"
             ; @context: pre
@@ -1407,7 +2360,13 @@ mod tests {
             (data Node (leaf) (cons Int))
         "#;
         let mut out = String::new();
-        render_with_context_blocks(program, src, &mut out);
+        render_with_context_blocks(
+            program,
+            src,
+            &SurfaceKeywordMap::default(),
+            &JAPANESE_VOCABULARY,
+            &mut out,
+        );
         assert!(out.contains("; @context: pre"));
         assert!(out.contains("; @context: app"));
     }
@@ -1434,4 +2393,138 @@ mod tests {
         let err = format_source("(", FormatOptions::default()).expect_err("parse error");
         assert!(!err.is_empty());
     }
+
+    #[test]
+    fn long_surface_forms_break_with_aligned_tags_idempotently() {
+        let src = r#"; syntax: surface
+(型 利用者)
+(関係 閲覧可能な文書を保持している利用者 :引数 (利用者))
+(関係 アクセス権限を付与された利用者 :引数 (利用者))
+(規則 :頭 (閲覧可能な文書を保持している利用者 ?u) :本体 (アクセス権限を付与された利用者 ?u))
+"#;
+        let once = format_source(src, FormatOptions::default()).expect("format");
+        assert!(once.contains(
+            "(規則\n  :頭   (閲覧可能な文書を保持している利用者 ?u)\n  :本体 (アクセス権限を付与された利用者 ?u))\n"
+        ));
+        assert!(once.contains("(関係 アクセス権限を付与された利用者 :引数 (利用者))\n"));
+
+        let twice = format_source(&once, FormatOptions::default()).expect("format twice");
+        assert_eq!(once, twice);
+
+        assert_eq!(
+            render_tagged_block(
+                "検証",
+                Some("ok"),
+                &[(":引数", "()".into()), (":式", "true".into())]
+            ),
+            "(検証 ok\n  :引数 ()\n  :式   true)"
+        );
+        assert_eq!(display_width(":本体"), 5);
+    }
+
+    #[test]
+    fn quoted_symbols_and_raw_strings_round_trip() {
+        let src = "(relation p (Symbol))\n(fact p #\"say \"hi\" (now)\"#)\n(fact p \"\"\"\nline\\\\1\nline2\"\"\")\n(fact p \"a\\n\\\"\\\"\\\"b\")\n";
+        let once = format_source(src, FormatOptions::default()).expect("format");
+        assert!(once.contains(r##":項 (#"say "hi" (now)"#))"##), "{once}");
+        assert!(
+            once.contains(":項 (\"\"\"\nline\\\\1\nline2\"\"\"))"),
+            "{once}"
+        );
+        assert!(once.contains(r#":項 ("a\n\"\"\"b"))"#), "{once}");
+        let twice = format_source(&once, FormatOptions::default()).expect("reformat");
+        assert_eq!(once, twice);
+        let terms = |text: &str| {
+            crate::parse_program(text)
+                .expect("parse")
+                .facts
+                .into_iter()
+                .map(|fact| fact.terms)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(terms(&once), terms(src));
+    }
+
+    #[test]
+    fn multi_line_docstrings_round_trip() {
+        let src = "; syntax: surface\n(型 利用者)\n(関係 閲覧 :引数 (利用者) :説明 \"\"\"\n一行目\n  \"二\" 行目\"\"\")\n";
+        let once = format_source(src, FormatOptions::default()).expect("format");
+        assert!(
+            once.contains(":説明 \"\"\"\n一行目\n  \"二\" 行目\"\"\")\n"),
+            "{once}"
+        );
+        let twice = format_source(&once, FormatOptions::default()).expect("reformat");
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn docstrings_survive_formatting() {
+        let src = r#"; syntax: surface
+(型 利用者)
+(関係 閲覧 :引数 (利用者) :説明 "閲覧できる利用者")
+(関数 可否 :引数 ((u 利用者)) :戻り Bool :本体 (閲覧 u) :説明 "改行\nと \"引用\" を含む")
+"#;
+        let once = format_source(src, FormatOptions::default()).expect("format");
+        assert!(once.contains("(関係 閲覧 :引数 (利用者) :説明 \"閲覧できる利用者\")\n"));
+        assert!(once.contains("  :説明 \"改行\\nと \\\"引用\\\" を含む\")\n"));
+
+        let twice = format_source(&once, FormatOptions::default()).expect("format twice");
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn metadata_attributes_survive_formatting() {
+        let src = r#"; syntax: surface
+(型 利用者 :owner "id-team")
+(データ 操作 :コンストラクタ ((閲覧)) :since "0.3")
+(関係 閲覧可 :引数 (利用者) :ticket "SEC-1" :owner "security" :説明 "閲覧権限")
+"#;
+        let once = format_source(src, FormatOptions::default()).expect("format");
+        assert!(once.contains("(型 利用者 :owner \"id-team\")\n"));
+        assert!(once.contains("(データ 操作 :コンストラクタ ((閲覧)) :since \"0.3\")\n"));
+        assert!(once.contains(
+            "(関係 閲覧可 :引数 (利用者) :説明 \"閲覧権限\" :owner \"security\" :ticket \"SEC-1\")\n"
+        ));
+
+        let twice = format_source(&once, FormatOptions::default()).expect("format twice");
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn assert_messages_survive_formatting() {
+        let src = "; syntax: surface\n(型 利用者)\n(関係 閲覧 :引数 (利用者))\n(検証 全員 :引数 ((u 利用者)) :式 (閲覧 u) :メッセージ \"全員が閲覧できること\")\n";
+        let once = format_source(src, FormatOptions::default()).expect("format");
+        assert!(once.contains(":メッセージ \"全員が閲覧できること\""));
+
+        let twice = format_source(&once, FormatOptions::default()).expect("format twice");
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn english_surface_files_keep_english_keywords() {
+        let src = r#"; syntax: surface
+(sort Subject)
+(relation allowed :args (Subject))
+(fact allowed :terms (alice))
+(defn ok :params ((u Subject)) :ret Bool :body (allowed u))
+"#;
+        let once = format_source(src, FormatOptions::default()).expect("format");
+        assert!(once.starts_with("; syntax: surface\n; keywords: en\n"));
+        assert!(once.contains("(relation allowed :args (Subject))\n"));
+        assert!(once.contains("(fact allowed :terms (alice))\n"));
+        assert!(once.contains(
+            "(defn ok\n  :params ((u Subject))\n  :ret    Bool\n  :body   (allowed u))\n"
+        ));
+        assert!(!once.contains("関係"));
+
+        let twice = format_source(&once, FormatOptions::default()).expect("format twice");
+        assert_eq!(once, twice);
+
+        let forced = format_source(
+            "; keywords: en\n(sort Subject)\n(relation allowed (Subject))\n",
+            FormatOptions::default(),
+        )
+        .expect("format core with english pragma");
+        assert!(forced.contains("(relation allowed :args (Subject))"));
+    }
 }
@@ -0,0 +1,571 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    Lexical,
+    Shared,
+    Core,
+    Surface,
+}
+
+impl Dialect {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Dialect::Lexical => "lexical",
+            Dialect::Shared => "shared",
+            Dialect::Core => "core",
+            Dialect::Surface => "surface",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Production {
+    pub name: String,
+    pub dialect: Dialect,
+    pub rhs: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TagField {
+    pub spellings: &'static [&'static str],
+    pub value: &'static str,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FormGrammar {
+    pub head: &'static str,
+    pub japanese: &'static str,
+    pub core: Option<&'static str>,
+    pub surface_positional: &'static str,
+    pub surface_tags: &'static [TagField],
+    pub doc: bool,
+    pub message: bool,
+    pub attributes: bool,
+}
+
+pub const DOC_TAG: &[&str] = &[":説明", ":doc"];
+pub const MESSAGE_TAG: &[&str] = &[":メッセージ", ":message"];
+pub const PROFILE_TAG: &[&str] = &[":プロファイル", ":profile"];
+
+const fn tag(spellings: &'static [&'static str], value: &'static str) -> TagField {
+    TagField {
+        spellings,
+        value,
+        required: true,
+    }
+}
+
+const fn optional_tag(spellings: &'static [&'static str], value: &'static str) -> TagField {
+    TagField {
+        spellings,
+        value,
+        required: false,
+    }
+}
+
+const fn define(
+    head: &'static str,
+    japanese: &'static str,
+    core: Option<&'static str>,
+    surface_positional: &'static str,
+    surface_tags: &'static [TagField],
+) -> FormGrammar {
+    FormGrammar {
+        head,
+        japanese,
+        core,
+        surface_positional,
+        surface_tags,
+        doc: false,
+        message: false,
+        attributes: false,
+    }
+}
+
+const fn declaration(mut grammar: FormGrammar, doc: bool, message: bool) -> FormGrammar {
+    grammar.doc = doc;
+    grammar.message = message;
+    grammar.attributes = true;
+    grammar
+}
+
+pub const FORMS: &[FormGrammar] = &[
+    define(
+        "import",
+        "インポート",
+        Some("QuotedAtom"),
+        "QuotedAtom",
+        &[],
+    ),
+    define(
+        "alias",
+        "同義語",
+        Some("Name Name"),
+        "",
+        &[
+            tag(&[":別名", ":alias"], "Name"),
+            tag(&[":正規", ":canonical"], "Name"),
+        ],
+    ),
+    define(
+        "defconst",
+        "定数",
+        Some("Name Expr"),
+        "Name",
+        &[tag(&[":値", ":value"], "Expr")],
+    ),
+    define(
+        "defmacro",
+        "マクロ",
+        Some("Name MacroParams Template [ \"...\" ] { Template [ \"...\" ] }"),
+        "Name MacroParams Template [ \"...\" ] { Template [ \"...\" ] }",
+        &[],
+    ),
+    declaration(
+        define("sort", "型", Some("Name"), "Name", &[]),
+        false,
+        false,
+    ),
+    declaration(
+        define(
+            "data",
+            "データ",
+            Some("Name Constructor { Constructor }"),
+            "Name",
+            &[tag(
+                &[":コンストラクタ", ":constructors", ":ctors"],
+                "Constructors",
+            )],
+        ),
+        false,
+        false,
+    ),
+    declaration(
+        define(
+            "relation",
+            "関係",
+            Some("Name SortList [ \":key\" KeyList ]"),
+            "Name",
+            &[
+                tag(&[":引数", ":args"], "SortList"),
+                optional_tag(&[":キー", ":key"], "KeyList"),
+            ],
+        ),
+        true,
+        false,
+    ),
+    define(
+        "fact",
+        "事実",
+        Some("Name { Term }"),
+        "Name",
+        &[tag(&[":項", ":terms"], "TermList")],
+    ),
+    define(
+        "rule",
+        "規則",
+        Some("RuleAtom RuleFormula [ \":skolem\" Int ]"),
+        "",
+        &[
+            tag(&[":頭", ":head"], "RuleAtom"),
+            tag(&[":本体", ":body"], "RuleFormula"),
+            optional_tag(&[":スコーレム", ":skolem"], "Int"),
+        ],
+    ),
+    declaration(
+        define(
+            "assert",
+            "検証",
+            Some("Name Params Formula"),
+            "Name",
+            &[
+                tag(&[":引数", ":params"], "Params"),
+                tag(&[":式", ":formula"], "Formula"),
+            ],
+        ),
+        true,
+        true,
+    ),
+    define(
+        "universe",
+        "宇宙",
+        Some("Name TermList"),
+        "Name",
+        &[tag(&[":値", ":values"], "TermList")],
+    ),
+    declaration(
+        define(
+            "defn",
+            "関数",
+            Some(
+                "Name Params Type ( Expr | \":clauses\" Clauses ) [ \":measure\" Expr | \":decreases\" Name ]",
+            ),
+            "Name",
+            &[
+                tag(&[":引数", ":params"], "Params"),
+                tag(&[":戻り", ":ret"], "Type"),
+                optional_tag(&[":本体", ":body"], "Expr"),
+                optional_tag(&[":節", ":clauses"], "Clauses"),
+                optional_tag(&[":尺度", ":measure"], "Expr"),
+                optional_tag(&[":減少", ":decreases"], "Name"),
+            ],
+        ),
+        true,
+        false,
+    ),
+    define(
+        "when-profile",
+        "プロファイル限定",
+        Some("Name { CoreFact | CoreRule | CoreAssert }"),
+        "Name { SurfaceFact | SurfaceRule | SurfaceAssert }",
+        &[],
+    ),
+    define(
+        "project",
+        "プロジェクト",
+        None,
+        "",
+        &[
+            tag(&[":名前", ":name"], "Text"),
+            tag(&[":概要", ":summary"], "Text"),
+        ],
+    ),
+    define(
+        "module",
+        "モジュール",
+        None,
+        "",
+        &[
+            tag(&[":名前", ":name"], "Text"),
+            tag(&[":パス", ":path"], "Text"),
+            tag(&[":カテゴリ", ":category"], "Text"),
+        ],
+    ),
+    define(
+        "reference",
+        "参照",
+        None,
+        "",
+        &[tag(&[":元", ":from"], "Text"), tag(&[":先", ":to"], "Text")],
+    ),
+    define(
+        "contract",
+        "契約",
+        None,
+        "",
+        &[
+            tag(&[":名前", ":name"], "Text"),
+            tag(&[":出典", ":source"], "Text"),
+            tag(&[":パス", ":path"], "Text"),
+        ],
+    ),
+    define(
+        "quality-gate",
+        "品質ゲート",
+        None,
+        "",
+        &[
+            tag(&[":名前", ":name"], "Text"),
+            tag(&[":コマンド", ":command"], "Text"),
+            tag(&[":出典", ":source"], "Text"),
+            optional_tag(&[":必須", ":required"], "YesNo"),
+        ],
+    ),
+];
+
+const LEXICAL_RULES: &[(&str, &str)] = &[
+    ("Comment", "LineComment | BlockComment | DatumComment"),
+    ("LineComment", "\";\" { ? any character except newline ? }"),
+    (
+        "BlockComment",
+        "\"#|\" { ? any character, up to the closing \"|#\" ? | BlockComment } \"|#\"",
+    ),
+    ("DatumComment", "\"#;\" { Comment } Datum"),
+    ("Datum", "Atom | \"(\" { Datum } \")\""),
+    (
+        "Atom",
+        "QuotedAtom | RawString | MultiLineString | BareAtom",
+    ),
+    (
+        "QuotedAtom",
+        "'\"' { ? any character except '\"' and '\\' ? | Escape } '\"'",
+    ),
+    ("Escape", "'\\' ( '\\' | '\"' | \"n\" | \"t\" | \"r\" )"),
+    (
+        "RawString",
+        "Hashes '\"' { ? any character ? } '\"' ? the opening Hashes again ?",
+    ),
+    ("Hashes", "\"#\" { \"#\" }"),
+    (
+        "MultiLineString",
+        "'\"\"\"' { ? any character except '\\' ? | Escape } '\"\"\"'",
+    ),
+    ("BareAtom", "BareStart { BareChar }"),
+    (
+        "BareStart",
+        "? any BareChar except '\"', not starting a raw string ?",
+    ),
+    (
+        "BareChar",
+        "? any character except whitespace, \"(\", \")\" and \";\" ?",
+    ),
+    ("Name", "BareAtom"),
+    ("Keyword", "\":\" { BareChar }"),
+    ("Var", "\"?\" BareChar { BareChar }"),
+    ("Int", "[ \"+\" | \"-\" ] Digit { Digit }"),
+    (
+        "Digit",
+        "\"0\" | \"1\" | \"2\" | \"3\" | \"4\" | \"5\" | \"6\" | \"7\" | \"8\" | \"9\"",
+    ),
+];
+
+const SHARED_RULES: &[(&str, &str)] = &[
+    ("Program", "{ CoreForm } | { SurfaceForm }"),
+    ("Text", "QuotedAtom | Name"),
+    ("Attribute", "Keyword Text"),
+    ("YesNo", "\"yes\" | \"no\" | \"true\" | \"false\""),
+    (
+        "Type",
+        "\"Bool\" | \"Int\" | \"Symbol\" | Name | \"(\" \"Refine\" Name Type Formula \")\" | \"(\" \"->\" \"(\" { Type } \")\" Type \")\" | \"(\" \"Adt\" Name \")\"",
+    ),
+    ("Param", "\"(\" Name Type \")\""),
+    ("Params", "\"(\" { Param } \")\""),
+    (
+        "SortList",
+        "\"(\" { Name | \"(\" \"Refine\" Name Name Formula \")\" } \")\"",
+    ),
+    ("KeyList", "\"(\" Int { Int } \")\""),
+    ("Constructor", "\"(\" Name { Type } \")\""),
+    ("Constructors", "\"(\" Constructor { Constructor } \")\""),
+    (
+        "Expr",
+        "\"true\" | \"false\" | Int | Name | \"(\" \"let\" \"(\" { \"(\" Name Expr \")\" } \")\" Expr \")\" | \"(\" \"letfn\" \"(\" { LocalFn } \")\" Expr \")\" | \"(\" \"if\" Expr Expr Expr \")\" | \"(\" \"match\" Expr MatchArm { MatchArm } \")\" | \"(\" Name { Expr } \")\"",
+    ),
+    ("LocalFn", "\"(\" Name Params Type Expr \")\""),
+    ("MatchArm", "\"(\" Pattern Expr \")\""),
+    ("Clauses", "\"(\" Clause { Clause } \")\""),
+    ("Clause", "\"(\" \"(\" { Pattern } \")\" Expr \")\""),
+    (
+        "Pattern",
+        "\"_\" | \"true\" | \"false\" | Int | Name | \"(\" Name { Pattern } \")\"",
+    ),
+    (
+        "Formula",
+        "\"true\" | \"(\" \"and\" Formula { Formula } \")\" | \"(\" \"not\" Formula \")\" | \"(\" Name { Term } \")\"",
+    ),
+    (
+        "RuleFormula",
+        "\"true\" | \"(\" \"and\" RuleFormula { RuleFormula } \")\" | \"(\" \"not\" RuleFormula \")\" | RuleAtom",
+    ),
+    ("RuleAtom", "\"(\" Name { RuleTerm } \")\""),
+    (
+        "Term",
+        "\"true\" | \"false\" | Int | Name | \"(\" Name { Term } \")\"",
+    ),
+    (
+        "RuleTerm",
+        "\"true\" | \"false\" | Int | Var | Name | \"(\" Name { RuleTerm } \")\"",
+    ),
+    ("TermList", "\"(\" { Term } \")\""),
+    ("MacroParams", "\"(\" { Name } [ Name \"...\" ] \")\""),
+    ("Template", "Atom | \"(\" { Template [ \"...\" ] } \")\""),
+];
+
+impl FormGrammar {
+    pub fn tag(&self, key: &str) -> &'static [&'static str] {
+        self.surface_tags
+            .iter()
+            .find(|field| field.spellings.contains(&key))
+            .map_or(&[], |field| field.spellings)
+    }
+
+    fn production_name(&self, prefix: &str) -> String {
+        let mut name = prefix.to_string();
+        for part in self.head.split('-') {
+            let mut chars = part.chars();
+            if let Some(first) = chars.next() {
+                name.push(first.to_ascii_uppercase());
+                name.push_str(chars.as_str());
+            }
+        }
+        name
+    }
+
+    fn declaration_tags(&self, doc: &[&str], message: &[&str]) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.doc {
+            out.push(format!("{} Text", alternatives(doc)));
+        }
+        if self.message {
+            out.push(format!("{} Text", alternatives(message)));
+        }
+        if self.attributes {
+            out.push("Attribute".to_string());
+        }
+        out
+    }
+
+    fn core_production(&self) -> Option<Production> {
+        let args = self.core?;
+        let mut rhs = format!("\"(\" \"{}\" {args}", self.head);
+        let tags = self.declaration_tags(&[":doc"], &[":message"]);
+        if !tags.is_empty() {
+            rhs.push_str(&format!(" {{ {} }}", tags.join(" | ")));
+        }
+        rhs.push_str(" \")\"");
+        Some(Production {
+            name: self.production_name("Core"),
+            dialect: Dialect::Core,
+            rhs,
+        })
+    }
+
+    fn surface_production(&self) -> Production {
+        let mut rhs = format!("\"(\" ( \"{}\" | \"{}\" )", self.head, self.japanese);
+        if !self.surface_positional.is_empty() {
+            rhs.push(' ');
+            rhs.push_str(self.surface_positional);
+        }
+        let mut tags = self
+            .surface_tags
+            .iter()
+            .map(|field| format!("{} {}", alternatives(field.spellings), field.value))
+            .collect::<Vec<_>>();
+        tags.extend(self.declaration_tags(DOC_TAG, MESSAGE_TAG));
+        if !tags.is_empty() {
+            rhs.push_str(&format!(" {{ {} }}", tags.join(" | ")));
+        }
+        rhs.push_str(" \")\"");
+        Production {
+            name: self.production_name("Surface"),
+            dialect: Dialect::Surface,
+            rhs,
+        }
+    }
+}
+
+fn alternatives(spellings: &[&str]) -> String {
+    let quoted = spellings
+        .iter()
+        .map(|spelling| format!("\"{spelling}\""))
+        .collect::<Vec<_>>();
+    if quoted.len() == 1 {
+        quoted[0].clone()
+    } else {
+        format!("( {} )", quoted.join(" | "))
+    }
+}
+
+pub fn form(head: &str) -> Option<&'static FormGrammar> {
+    FORMS.iter().find(|grammar| grammar.head == head)
+}
+
+pub fn core_form(head: &str) -> Option<&'static FormGrammar> {
+    form(head).filter(|grammar| grammar.core.is_some())
+}
+
+pub fn surface_form(head: &str) -> Option<&'static FormGrammar> {
+    FORMS
+        .iter()
+        .find(|grammar| grammar.head == head || grammar.japanese == head)
+}
+
+pub fn is_surface_tag(key: &str) -> bool {
+    DOC_TAG.contains(&key)
+        || MESSAGE_TAG.contains(&key)
+        || FORMS.iter().any(|grammar| {
+            grammar
+                .surface_tags
+                .iter()
+                .any(|field| field.spellings.contains(&key))
+        })
+}
+
+pub fn productions() -> Vec<Production> {
+    let rule = |dialect: Dialect, (name, rhs): &(&str, &str)| Production {
+        name: name.to_string(),
+        dialect,
+        rhs: rhs.to_string(),
+    };
+    let mut out = LEXICAL_RULES
+        .iter()
+        .map(|entry| rule(Dialect::Lexical, entry))
+        .chain(
+            SHARED_RULES
+                .iter()
+                .map(|entry| rule(Dialect::Shared, entry)),
+        )
+        .collect::<Vec<_>>();
+
+    let core = FORMS
+        .iter()
+        .filter_map(FormGrammar::core_production)
+        .collect::<Vec<_>>();
+    out.push(Production {
+        name: "CoreForm".to_string(),
+        dialect: Dialect::Core,
+        rhs: core
+            .iter()
+            .map(|production| production.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" | "),
+    });
+    out.extend(core);
+
+    let surface = FORMS
+        .iter()
+        .map(FormGrammar::surface_production)
+        .collect::<Vec<_>>();
+    out.push(Production {
+        name: "SurfaceForm".to_string(),
+        dialect: Dialect::Surface,
+        rhs: surface
+            .iter()
+            .map(|production| production.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" | "),
+    });
+    out.extend(surface);
+    out
+}
+
+fn required_tags(production: &Production) -> Option<String> {
+    if production.dialect != Dialect::Surface {
+        return None;
+    }
+    let grammar = FORMS
+        .iter()
+        .find(|grammar| grammar.production_name("Surface") == production.name)?;
+    let required = grammar
+        .surface_tags
+        .iter()
+        .filter(|field| field.required)
+        .map(|field| field.spellings[0])
+        .collect::<Vec<_>>();
+    (!required.is_empty()).then(|| required.join(", "))
+}
+
+pub fn render_ebnf() -> String {
+    let mut out = String::new();
+    let mut current = None;
+    for production in productions() {
+        if current != Some(production.dialect) {
+            if current.is_some() {
+                out.push('\n');
+            }
+            out.push_str(&format!("(* {} *)\n", production.dialect.as_str()));
+            current = Some(production.dialect);
+        }
+        out.push_str(&format!("{} = {} ;\n", production.name, production.rhs));
+        if let Some(required) = required_tags(&production) {
+            out.push_str(&format!("(* required: {required} *)\n"));
+        }
+    }
+    out.push_str(
+        "\n(* Whitespace and Comment separate tokens. Surface tags may appear in any order. *)\n",
+    );
+    out
+}
@@ -0,0 +1,354 @@
+use std::collections::BTreeMap;
+
+use crate::logic_engine::DISEQUALITY_PRED;
+use crate::types::{Atom, Formula, LogicTerm};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl ComparisonOp {
+    pub fn from_pred(pred: &str) -> Option<Self> {
+        match pred {
+            "<" => Some(ComparisonOp::Lt),
+            "<=" => Some(ComparisonOp::Le),
+            ">" => Some(ComparisonOp::Gt),
+            ">=" => Some(ComparisonOp::Ge),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ComparisonOp::Lt => "<",
+            ComparisonOp::Le => "<=",
+            ComparisonOp::Gt => ">",
+            ComparisonOp::Ge => ">=",
+        }
+    }
+
+    pub fn holds(self, lhs: i64, rhs: i64) -> bool {
+        match self {
+            ComparisonOp::Lt => lhs < rhs,
+            ComparisonOp::Le => lhs <= rhs,
+            ComparisonOp::Gt => lhs > rhs,
+            ComparisonOp::Ge => lhs >= rhs,
+        }
+    }
+
+    pub fn flip(self) -> Self {
+        match self {
+            ComparisonOp::Lt => ComparisonOp::Gt,
+            ComparisonOp::Le => ComparisonOp::Ge,
+            ComparisonOp::Gt => ComparisonOp::Lt,
+            ComparisonOp::Ge => ComparisonOp::Le,
+        }
+    }
+
+    fn negate(self) -> Self {
+        match self {
+            ComparisonOp::Lt => ComparisonOp::Ge,
+            ComparisonOp::Le => ComparisonOp::Gt,
+            ComparisonOp::Gt => ComparisonOp::Le,
+            ComparisonOp::Ge => ComparisonOp::Lt,
+        }
+    }
+
+    fn bound(self, bound: i64) -> Interval {
+        match self {
+            ComparisonOp::Lt => bound
+                .checked_sub(1)
+                .map_or(Interval::EMPTY, Interval::at_most),
+            ComparisonOp::Le => Interval::at_most(bound),
+            ComparisonOp::Gt => bound
+                .checked_add(1)
+                .map_or(Interval::EMPTY, Interval::at_least),
+            ComparisonOp::Ge => Interval::at_least(bound),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl ArithOp {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "+" => Some(ArithOp::Add),
+            "-" => Some(ArithOp::Sub),
+            "*" => Some(ArithOp::Mul),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ArithOp::Add => "+",
+            ArithOp::Sub => "-",
+            ArithOp::Mul => "*",
+        }
+    }
+
+    pub fn apply(self, lhs: i64, rhs: i64) -> Option<i64> {
+        match self {
+            ArithOp::Add => lhs.checked_add(rhs),
+            ArithOp::Sub => lhs.checked_sub(rhs),
+            ArithOp::Mul => lhs.checked_mul(rhs),
+        }
+    }
+
+    pub fn apply_interval(self, lhs: Interval, rhs: Interval) -> Interval {
+        if lhs.is_empty() || rhs.is_empty() {
+            return Interval::EMPTY;
+        }
+        let (a_lo, a_hi) = lhs.widened();
+        let (b_lo, b_hi) = rhs.widened();
+        let (lo, hi) = match self {
+            ArithOp::Add => (a_lo + b_lo, a_hi + b_hi),
+            ArithOp::Sub => (a_lo - b_hi, a_hi - b_lo),
+            ArithOp::Mul => {
+                let products = [a_lo * b_lo, a_lo * b_hi, a_hi * b_lo, a_hi * b_hi];
+                (
+                    products.into_iter().min().unwrap_or_default(),
+                    products.into_iter().max().unwrap_or_default(),
+                )
+            }
+        };
+        let (Ok(lo), Ok(hi)) = (i64::try_from(lo), i64::try_from(hi)) else {
+            return Interval::TOP;
+        };
+        Interval {
+            lo: (lo != i64::MIN).then_some(lo),
+            hi: (hi != i64::MAX).then_some(hi),
+        }
+    }
+}
+
+pub fn is_builtin_operator(name: &str) -> bool {
+    ComparisonOp::from_pred(name).is_some()
+        || name == DISEQUALITY_PRED
+        || ArithOp::from_name(name).is_some()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub lo: Option<i64>,
+    pub hi: Option<i64>,
+}
+
+impl Interval {
+    pub const TOP: Interval = Interval { lo: None, hi: None };
+    pub const EMPTY: Interval = Interval {
+        lo: Some(1),
+        hi: Some(0),
+    };
+
+    pub fn point(value: i64) -> Self {
+        Self {
+            lo: Some(value),
+            hi: Some(value),
+        }
+    }
+
+    pub fn at_least(lo: i64) -> Self {
+        Self {
+            lo: Some(lo),
+            hi: None,
+        }
+    }
+
+    pub fn at_most(hi: i64) -> Self {
+        Self {
+            lo: None,
+            hi: Some(hi),
+        }
+    }
+
+    fn widened(&self) -> (i128, i128) {
+        (
+            i128::from(self.lo.unwrap_or(i64::MIN)),
+            i128::from(self.hi.unwrap_or(i64::MAX)),
+        )
+    }
+
+    pub fn is_top(&self) -> bool {
+        self.lo.is_none() && self.hi.is_none()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!((self.lo, self.hi), (Some(lo), Some(hi)) if lo > hi)
+    }
+
+    pub fn contains(&self, other: &Interval) -> bool {
+        if other.is_empty() {
+            return true;
+        }
+        let lo_ok = match (self.lo, other.lo) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(a), Some(b)) => a <= b,
+        };
+        let hi_ok = match (self.hi, other.hi) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(a), Some(b)) => b <= a,
+        };
+        lo_ok && hi_ok
+    }
+
+    pub fn join(&self, other: &Interval) -> Interval {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        Interval {
+            lo: self.lo.zip(other.lo).map(|(a, b)| a.min(b)),
+            hi: self.hi.zip(other.hi).map(|(a, b)| a.max(b)),
+        }
+    }
+
+    pub fn meet(&self, other: &Interval) -> Interval {
+        let lo = match (self.lo, other.lo) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        let hi = match (self.hi, other.hi) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        Interval { lo, hi }
+    }
+
+    pub fn to_formula(&self, var: &str) -> Option<Formula> {
+        let bound = |op: ComparisonOp, value: i64| {
+            Formula::Atom(Atom {
+                pred: op.as_str().to_string(),
+                terms: vec![LogicTerm::Var(var.to_string()), LogicTerm::Int(value)],
+            })
+        };
+        let mut items = Vec::new();
+        if let Some(lo) = self.lo {
+            items.push(bound(ComparisonOp::Ge, lo));
+        }
+        if let Some(hi) = self.hi {
+            items.push(bound(ComparisonOp::Le, hi));
+        }
+        match items.len() {
+            0 => None,
+            1 => items.pop(),
+            _ => Some(Formula::And(items)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConjunctSplit {
+    pub bounds: BTreeMap<String, Interval>,
+    pub opaque: Vec<Formula>,
+    pub contradiction: bool,
+    pub rest: Vec<Formula>,
+}
+
+impl ConjunctSplit {
+    pub fn bound(&self, var: &str) -> Interval {
+        self.bounds.get(var).copied().unwrap_or(Interval::TOP)
+    }
+
+    pub fn is_unsatisfiable(&self) -> bool {
+        self.contradiction || self.bounds.values().any(Interval::is_empty)
+    }
+
+    pub fn implies_comparisons_of(&self, goal: &ConjunctSplit) -> bool {
+        !goal.contradiction
+            && goal
+                .bounds
+                .iter()
+                .all(|(var, need)| need.contains(&self.bound(var)))
+            && goal.opaque.iter().all(|item| self.opaque.contains(item))
+    }
+
+    pub fn rest_formula(&self) -> Formula {
+        match self.rest.as_slice() {
+            [] => Formula::True,
+            [item] => item.clone(),
+            items => Formula::And(items.to_vec()),
+        }
+    }
+}
+
+pub fn split_conjuncts(formula: &Formula) -> ConjunctSplit {
+    let mut split = ConjunctSplit::default();
+    collect_conjuncts(formula, &mut split);
+    split
+}
+
+pub fn var_interval(formula: &Formula, var: &str) -> Interval {
+    split_conjuncts(formula).bound(var)
+}
+
+pub fn mentions_comparison(formula: &Formula) -> bool {
+    match formula {
+        Formula::True => false,
+        Formula::Atom(atom) => ComparisonOp::from_pred(&atom.pred).is_some(),
+        Formula::And(items) => items.iter().any(mentions_comparison),
+        Formula::Not(inner) => mentions_comparison(inner),
+    }
+}
+
+fn collect_conjuncts(formula: &Formula, split: &mut ConjunctSplit) {
+    match formula {
+        Formula::True => {}
+        Formula::And(items) => {
+            for item in items {
+                collect_conjuncts(item, split);
+            }
+        }
+        Formula::Atom(atom) if ComparisonOp::from_pred(&atom.pred).is_some() => {
+            add_comparison(atom, false, formula, split);
+        }
+        Formula::Not(inner) => match inner.as_ref() {
+            Formula::Atom(atom) if ComparisonOp::from_pred(&atom.pred).is_some() => {
+                add_comparison(atom, true, formula, split);
+            }
+            _ if mentions_comparison(inner) => split.opaque.push(formula.clone()),
+            _ => split.rest.push(formula.clone()),
+        },
+        Formula::Atom(_) => split.rest.push(formula.clone()),
+    }
+}
+
+fn add_comparison(atom: &Atom, negated: bool, formula: &Formula, split: &mut ConjunctSplit) {
+    let Some(mut op) = ComparisonOp::from_pred(&atom.pred) else {
+        return;
+    };
+    if negated {
+        op = op.negate();
+    }
+    match atom.terms.as_slice() {
+        [LogicTerm::Int(lhs), LogicTerm::Int(rhs)] => {
+            if !op.holds(*lhs, *rhs) {
+                split.contradiction = true;
+            }
+        }
+        [LogicTerm::Var(var), LogicTerm::Int(value)] => add_bound(split, var, op.bound(*value)),
+        [LogicTerm::Int(value), LogicTerm::Var(var)] => {
+            add_bound(split, var, op.flip().bound(*value))
+        }
+        _ => split.opaque.push(formula.clone()),
+    }
+}
+
+fn add_bound(split: &mut ConjunctSplit, var: &str, bound: Interval) {
+    let entry = split.bounds.entry(var.to_string()).or_insert(Interval::TOP);
+    *entry = entry.meet(&bound);
+}
@@ -1,34 +1,107 @@
 #![allow(clippy::result_large_err)]
 
 pub mod ast;
+pub mod clauses;
+pub mod codegen;
+pub mod completion;
 pub mod diagnostics;
+pub mod diff;
+pub mod edition;
+pub mod eval;
+pub mod exit_code;
+pub mod explorer;
+pub mod fact_cache;
 pub mod fmt;
+pub mod grammar;
+pub mod interval;
 pub mod lint;
 pub mod logic_engine;
+pub mod macros;
+pub mod minimize;
 pub mod name_resolve;
 pub mod parser;
+pub mod partial_eval;
+pub mod plan;
+pub mod project;
 pub mod prover;
+pub mod reference;
 pub mod reference_prover;
+pub mod refs;
+pub mod slice;
+pub mod snapshot;
 pub mod stratify;
+pub mod symbol;
+pub mod tags;
+pub mod testing;
+pub mod timings;
 pub mod typecheck;
 pub mod types;
 
 pub use ast::Program;
-pub use diagnostics::{Diagnostic, Span};
-pub use fmt::{FormatOptions, format_source};
-pub use lint::{LintDiagnostic, LintOptions, LintSeverity, lint_program};
-pub use logic_engine::{DerivedFacts, GroundFact, KnowledgeBase, solve_facts};
-pub use parser::{parse_program, parse_program_with_source};
+pub use codegen::{generate_jsonschema, generate_rust, generate_sql, generate_typescript};
+pub use completion::{Completion, CompletionKind, completions_at};
+pub use diagnostics::{Diagnostic, RelatedSpan, Span, TextEdit};
+pub use diff::{ChangeKind, DeclChange, DeclKind, ProgramDiff, diff_programs};
+pub use edition::{Edition, EditionFeature};
+pub use eval::{
+    EvalError, EvalLimits, EvalTrace, EvalValue, Evaluation, FunctionValue, Interpreter,
+    TraceEvent, TraceStep, entry_point, evaluate, evaluate_traced, run_entry_point,
+    with_eval_stack,
+};
+pub use exit_code::{FailureClass, failure_class, failure_exit_code};
+pub use explorer::Explorer;
+pub use fact_cache::{FACT_CACHE_DIR, FactCache, FixpointOptions, solve_facts_cached};
+pub use fmt::{
+    FormatOptions, format_range, format_range_with_keywords, format_source,
+    format_source_with_keywords, format_warnings, leaves_core_comments_unformatted, render_program,
+};
+pub use lint::{
+    ComplexityLimits, DupLimits, DupSampling, LintDiagnostic, LintOptions, LintSeverity,
+    lint_program,
+};
+pub use logic_engine::{
+    DerivationStep, DerivedFacts, DerivedTuple, GroundFact, KnowledgeBase, SolveFactsIter,
+    solve_facts, solve_facts_incremental, solve_facts_iter, solve_facts_with_derivations,
+};
+pub use macros::{ExpansionStep, MacroExpansion, expand_macros};
+pub use minimize::{MinimizeReport, Symptom, minimize_program};
+pub use parser::{
+    ParseOptions, SurfaceKeywordMap, SurfaceKeywords, parse_program, parse_program_lossy,
+    parse_program_lossy_with_options, parse_program_with_options, parse_program_with_source,
+    parse_term, quote_atom, surface_keywords,
+};
+pub use partial_eval::partially_evaluate;
+pub use plan::{
+    Access, PlanStep, QueryPlan, RulePlan, StepKind, StratumPlan, plan_knowledge_base, plan_program,
+};
+pub use project::{
+    PROJECT_CONFIG_FILE, ProjectConfig, find_project_config, load_project_config,
+    parse_project_config,
+};
 pub use prover::{
-    ClaimCoverage, DOC_SPEC_SCHEMA_VERSION, DocBundleFormat, DocBundleOptions, DocContract,
-    DocModule, DocProject, DocQualityGate, DocReference, DocSelfDescription,
-    PROOF_TRACE_SCHEMA_VERSION, ProofSummary, ProofTrace, generate_doc_bundle,
-    generate_doc_bundle_with_options, has_failed_obligation, has_full_claim_coverage,
-    prove_program, write_proof_trace,
+    ClaimCoverage, ClaimPolicy, DOC_SPEC_SCHEMA_VERSION, DocBundleFormat, DocBundleOptions,
+    DocCategory, DocContract, DocDriftEntry, DocModule, DocProject, DocProofChange, DocQualityGate,
+    DocReference, DocSelfDescription, DocSelfdocDrift, ObligationTrace, PROOF_TRACE_SCHEMA_VERSION,
+    ProofSummary, ProofTrace, ProofTraceWriter, derivation_steps, diff_proof_traces,
+    diff_self_descriptions, generate_doc_bundle, generate_doc_bundle_with_options,
+    has_failed_obligation, has_full_claim_coverage, obligation_span, proof_trace_filename,
+    prove_program, prove_program_streaming, prove_program_with_timings, read_proof_trace,
+    write_proof_trace,
 };
 pub use reference_prover::{
     FunctionValue as ReferenceFunctionValue, ReferenceDerivedFacts, ReferenceEnv,
-    ReferenceObligationResult, ReferenceValue, prove_program_reference, reference_prove_program,
-    reference_prove_program_results, reference_solve_facts, reference_value_to_string,
+    ReferenceObligationResult, ReferenceValue, check_derivation_parity, prove_program_reference,
+    reference_derivation_steps, reference_prove_program, reference_prove_program_results,
+    reference_solve_facts, reference_value_to_string,
+};
+pub use refs::find_references;
+pub use slice::{SliceTarget, slice_program};
+pub use snapshot::{
+    KB_SNAPSHOT_SCHEMA_VERSION, KnowledgeBaseSnapshot, content_hash, kb_fingerprint,
+    read_kb_snapshot, solve_facts_with_snapshot, write_kb_snapshot,
 };
-pub use typecheck::{TypeReport, check_program};
+pub use stratify::{NegativeCycle, Strata, StratifyError, StratumEdge, compute_strata};
+pub use symbol::Symbol;
+pub use tags::{Tag, TagKind, collect_tags, render_ctags};
+pub use timings::{PhaseStats, PhaseTiming, PhaseTimings};
+pub use typecheck::{TypeReport, check_program, check_program_with_timings, type_at};
@@ -1,9 +1,19 @@
 use std::collections::{BTreeSet, HashMap, HashSet};
 
-use crate::ast::{AssertDecl, Defn, Expr, Param, Pattern, Program, Rule};
+use crate::ast::{
+    AssertDecl, Defn, Expr, ExprArena, ExprId, Param, Pattern, PatternId, Program, Rule,
+};
 use crate::diagnostics::Span;
-use crate::logic_engine::{DerivedFacts, KnowledgeBase, Value, solve_facts};
+use crate::eval::{
+    EvalError, EvalLimits, EvalValue, FunctionValue, Interpreter, formula_holds, instantiate_terms,
+    with_eval_stack,
+};
+use crate::fact_cache::{FixpointOptions, solve_facts_cached};
+use crate::fmt::render_logic_term;
+use crate::interval::ComparisonOp;
+use crate::logic_engine::{DISEQUALITY_PRED, DerivedFacts, Value};
 use crate::name_resolve::{normalize_program_aliases, resolve_program};
+use crate::symbol::Symbol;
 use crate::types::{Atom, Formula, LogicTerm, Type};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -52,8 +62,55 @@ impl LintDiagnostic {
 }
 
 #[derive(Debug, Clone, Copy, Default)]
-pub struct LintOptions {
+pub struct LintOptions<'a> {
     pub semantic_dup: bool,
+    pub complexity: ComplexityLimits,
+    pub sampling: Option<DupSampling>,
+    pub dup_limits: DupLimits,
+    pub intra_file_only: bool,
+    pub fixpoint: FixpointOptions<'a>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DupLimits {
+    pub eval_depth_limit: Option<usize>,
+    pub max_model_points: Option<usize>,
+    pub max_function_values: usize,
+}
+
+impl Default for DupLimits {
+    fn default() -> Self {
+        Self {
+            eval_depth_limit: None,
+            max_model_points: None,
+            max_function_values: MAX_FUNCTION_MODEL_VALUES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DupSampling {
+    pub samples: usize,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComplexityLimits {
+    pub max_expr_depth: usize,
+    pub max_defn_nodes: usize,
+    pub max_rule_body_atoms: usize,
+    pub max_assert_params: usize,
+}
+
+impl Default for ComplexityLimits {
+    fn default() -> Self {
+        Self {
+            max_expr_depth: 12,
+            max_defn_nodes: 200,
+            max_rule_body_atoms: 8,
+            max_assert_params: 6,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -72,28 +129,11 @@ impl SemanticDupEvidence {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct FunctionValue {
-    table: Vec<(Vec<EvalValue>, EvalValue)>,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum EvalValue {
-    Symbol(String),
-    Int(i64),
-    Bool(bool),
-    Adt {
-        ctor: String,
-        fields: Vec<EvalValue>,
-    },
-    Function(FunctionValue),
-}
-
 const BASE_EVAL_DEPTH_LIMIT: usize = 1024;
 const MAX_EVAL_DEPTH_LIMIT: usize = 4096;
 const MAX_FUNCTION_MODEL_VALUES: usize = 4096;
 
-pub fn lint_program(program: &Program, options: LintOptions) -> Vec<LintDiagnostic> {
+pub fn lint_program(program: &Program, options: LintOptions<'_>) -> Vec<LintDiagnostic> {
     let normalized = match normalize_program_aliases(program) {
         Ok(program) => program,
         Err(_) => return Vec::new(),
@@ -105,29 +145,41 @@ pub fn lint_program(program: &Program, options: LintOptions) -> Vec<LintDiagnost
         return out;
     }
 
-    out.extend(lint_exact_duplicates(&normalized));
+    out.extend(lint_exact_duplicates(&normalized, options.intra_file_only));
     out.extend(lint_unused_declarations(&normalized));
+    out.extend(lint_universe_values(&normalized));
+    out.extend(lint_constants_outside_universe(&normalized));
+    out.extend(lint_vacuous_asserts(&normalized, options.fixpoint));
+    out.extend(lint_complexity(&normalized, options.complexity));
 
     if options.semantic_dup {
-        out.extend(lint_semantic_duplicates(&normalized));
+        out.extend(lint_semantic_duplicates(&normalized, options));
     }
 
     out
 }
 
-fn lint_exact_duplicates(program: &Program) -> Vec<LintDiagnostic> {
+fn lint_exact_duplicates(program: &Program, intra_file_only: bool) -> Vec<LintDiagnostic> {
     let mut out = Vec::new();
+    let scope = |span: &Span| {
+        if intra_file_only {
+            span.file_id.clone()
+        } else {
+            None
+        }
+    };
 
-    let mut seen_fact: HashMap<String, Span> = HashMap::new();
+    let mut seen_fact: HashMap<(Option<String>, String), Span> = HashMap::new();
     for fact in &program.facts {
-        let key = normalize_fact(fact);
+        let key = (scope(&fact.span), normalize_fact(fact));
         if let Some(prev) = seen_fact.get(&key) {
             out.push(LintDiagnostic::warning(
                 "L-DUP-EXACT",
                 "duplicate",
                 format!(
-                    "重複した fact です: {}（最初の定義: {}:{}）",
-                    fact.name, prev.line, prev.column
+                    "重複した fact です: {}（最初の定義: {}）",
+                    fact.name,
+                    first_definition(prev, &fact.span)
                 ),
                 Some(fact.span.clone()),
                 None,
@@ -137,16 +189,17 @@ fn lint_exact_duplicates(program: &Program) -> Vec<LintDiagnostic> {
         }
     }
 
-    let mut seen_rule: HashMap<String, Span> = HashMap::new();
+    let mut seen_rule: HashMap<(Option<String>, String), Span> = HashMap::new();
     for rule in &program.rules {
-        let key = normalize_rule(rule);
+        let key = (scope(&rule.span), normalize_rule(rule));
         if let Some(prev) = seen_rule.get(&key) {
             out.push(LintDiagnostic::warning(
                 "L-DUP-EXACT",
                 "duplicate",
                 format!(
-                    "重複した rule です: {}（最初の定義: {}:{}）",
-                    rule.head.pred, prev.line, prev.column
+                    "重複した rule です: {}（最初の定義: {}）",
+                    rule.head.pred,
+                    first_definition(prev, &rule.span)
                 ),
                 Some(rule.span.clone()),
                 None,
@@ -156,16 +209,18 @@ fn lint_exact_duplicates(program: &Program) -> Vec<LintDiagnostic> {
         }
     }
 
-    let mut seen_assert: HashMap<String, (String, Span)> = HashMap::new();
+    let mut seen_assert: HashMap<(Option<String>, String), (String, Span)> = HashMap::new();
     for assertion in &program.asserts {
-        let key = normalize_assert(assertion);
+        let key = (scope(&assertion.span), normalize_assert(assertion));
         if let Some((prev_name, prev_span)) = seen_assert.get(&key) {
             out.push(LintDiagnostic::warning(
                 "L-DUP-EXACT",
                 "duplicate",
                 format!(
-                    "重複した assert です: {} と {}（最初の定義: {}:{}）",
-                    prev_name, assertion.name, prev_span.line, prev_span.column
+                    "重複した assert です: {} と {}（最初の定義: {}）",
+                    prev_name,
+                    assertion.name,
+                    first_definition(prev_span, &assertion.span)
                 ),
                 Some(assertion.span.clone()),
                 None,
@@ -175,16 +230,18 @@ fn lint_exact_duplicates(program: &Program) -> Vec<LintDiagnostic> {
         }
     }
 
-    let mut seen_defn: HashMap<String, (String, Span)> = HashMap::new();
+    let mut seen_defn: HashMap<(Option<String>, String), (String, Span)> = HashMap::new();
     for defn in &program.defns {
-        let key = normalize_defn(defn);
+        let key = (scope(&defn.span), normalize_defn(&program.exprs, defn));
         if let Some((prev_name, prev_span)) = seen_defn.get(&key) {
             out.push(LintDiagnostic::warning(
                 "L-DUP-EXACT",
                 "duplicate",
                 format!(
-                    "重複した defn です: {} と {}（最初の定義: {}:{}）",
-                    prev_name, defn.name, prev_span.line, prev_span.column
+                    "重複した defn です: {} と {}（最初の定義: {}）",
+                    prev_name,
+                    defn.name,
+                    first_definition(prev_span, &defn.span)
                 ),
                 Some(defn.span.clone()),
                 None,
@@ -197,7 +254,16 @@ fn lint_exact_duplicates(program: &Program) -> Vec<LintDiagnostic> {
     out
 }
 
-fn lint_semantic_duplicates(program: &Program) -> Vec<LintDiagnostic> {
+fn first_definition(prev: &Span, current: &Span) -> String {
+    match &prev.file_id {
+        Some(file) if prev.file_id != current.file_id => {
+            format!("{file}:{}:{}", prev.line, prev.column)
+        }
+        _ => format!("{}:{}", prev.line, prev.column),
+    }
+}
+
+fn lint_semantic_duplicates(program: &Program, options: LintOptions<'_>) -> Vec<LintDiagnostic> {
     let mut out = Vec::new();
 
     if let Some(missing) = missing_universe_types(program) {
@@ -214,7 +280,7 @@ fn lint_semantic_duplicates(program: &Program) -> Vec<LintDiagnostic> {
         return out;
     }
 
-    let Some(ctx) = build_semantic_dup_context(program) else {
+    let Some(ctx) = build_semantic_dup_context(program, options) else {
         return out;
     };
 
@@ -268,18 +334,20 @@ fn lint_semantic_duplicates(program: &Program) -> Vec<LintDiagnostic> {
         );
         defn_buckets.entry(sig).or_default().push(idx);
     }
-    for indices in defn_buckets.values() {
-        for i in 0..indices.len() {
-            for j in (i + 1)..indices.len() {
-                let a = &program.defns[indices[i]];
-                let b = &program.defns[indices[j]];
-                if normalize_defn(a) == normalize_defn(b) {
-                    continue;
-                }
-                if let Some(evidence) = defns_semantic_evidence(a, b, &ctx) {
-                    if evidence.depth_limited_points > 0 {
-                        let limit = evidence.eval_depth_limit.unwrap_or(BASE_EVAL_DEPTH_LIMIT);
-                        out.push(LintDiagnostic::warning(
+    let session_depth = ctx.limits.eval_depth_limit.unwrap_or(MAX_EVAL_DEPTH_LIMIT);
+    with_eval_stack(session_depth, || {
+        for indices in defn_buckets.values() {
+            for i in 0..indices.len() {
+                for j in (i + 1)..indices.len() {
+                    let a = &program.defns[indices[i]];
+                    let b = &program.defns[indices[j]];
+                    if normalize_defn(&program.exprs, a) == normalize_defn(&program.exprs, b) {
+                        continue;
+                    }
+                    if let Some(evidence) = defns_semantic_evidence(a, b, &ctx) {
+                        if evidence.depth_limited_points > 0 {
+                            let limit = evidence.eval_depth_limit.unwrap_or(BASE_EVAL_DEPTH_LIMIT);
+                            out.push(LintDiagnostic::warning(
                             "L-DUP-SKIP-EVAL-DEPTH",
                             "duplicate",
                             format!(
@@ -294,21 +362,22 @@ fn lint_semantic_duplicates(program: &Program) -> Vec<LintDiagnostic> {
                             Some(b.span.clone()),
                             None,
                         ));
+                        }
+                        if !evidence.equivalent() {
+                            continue;
+                        }
+                        out.push(LintDiagnostic::warning(
+                            "L-DUP-MAYBE",
+                            "duplicate",
+                            format!("defn {} と {} は等価実装の可能性があります", a.name, b.name),
+                            Some(b.span.clone()),
+                            Some(semantic_dup_confidence(evidence)),
+                        ));
                     }
-                    if !evidence.equivalent() {
-                        continue;
-                    }
-                    out.push(LintDiagnostic::warning(
-                        "L-DUP-MAYBE",
-                        "duplicate",
-                        format!("defn {} と {} は等価実装の可能性があります", a.name, b.name),
-                        Some(b.span.clone()),
-                        Some(semantic_dup_confidence(evidence)),
-                    ));
                 }
             }
         }
-    }
+    });
 
     let mut rule_buckets: HashMap<String, Vec<usize>> = HashMap::new();
     for (idx, rule) in program.rules.iter().enumerate() {
@@ -358,12 +427,16 @@ struct SemanticDupContext<'a> {
     universe: HashMap<String, Vec<Value>>,
     relation_schemas: HashMap<String, Vec<String>>,
     constructor_sigs: HashMap<String, ConstructorSig>,
-    defn_indices: HashMap<String, usize>,
+    sampling: Option<DupSampling>,
+    limits: DupLimits,
 }
 
-fn build_semantic_dup_context(program: &Program) -> Option<SemanticDupContext<'_>> {
-    let kb = KnowledgeBase::from_program(program).ok()?;
-    let derived = solve_facts(&kb).ok()?;
+fn build_semantic_dup_context<'p>(
+    program: &'p Program,
+    options: LintOptions<'_>,
+) -> Option<SemanticDupContext<'p>> {
+    let kb = options.fixpoint.knowledge_base(program).ok()?;
+    let derived = solve_facts_cached(&kb, options.fixpoint).ok()?;
     let universe = build_universe_values(program)?;
     let relation_schemas = program
         .relations
@@ -385,12 +458,6 @@ fn build_semantic_dup_context(program: &Program) -> Option<SemanticDupContext<'_
             })
         })
         .collect::<HashMap<_, _>>();
-    let defn_indices = program
-        .defns
-        .iter()
-        .enumerate()
-        .map(|(idx, defn)| (defn.name.clone(), idx))
-        .collect::<HashMap<_, _>>();
 
     Some(SemanticDupContext {
         program,
@@ -398,7 +465,8 @@ fn build_semantic_dup_context(program: &Program) -> Option<SemanticDupContext<'_
         universe,
         relation_schemas,
         constructor_sigs,
-        defn_indices,
+        sampling: options.sampling,
+        limits: options.dup_limits,
     })
 }
 
@@ -442,16 +510,15 @@ fn assertions_semantic_evidence(
     b: &AssertDecl,
     ctx: &SemanticDupContext<'_>,
 ) -> Option<SemanticDupEvidence> {
-    let tuples = enumerate_const_param_tuples(&a.params, &ctx.universe)?;
-    let total = tuples.len();
+    let (total, tuples) = enumerate_const_param_tuples(&a.params, ctx)?;
     let mut checked = 0usize;
 
     for tuple in tuples {
         let env_a = bind_params(&a.params, &tuple);
         let env_b = bind_params(&b.params, &tuple);
         checked += 1;
-        let a_ok = eval_formula_with_env(&a.formula, &ctx.derived, &env_a);
-        let b_ok = eval_formula_with_env(&b.formula, &ctx.derived, &env_b);
+        let a_ok = formula_holds(&a.formula, &ctx.derived, &env_a);
+        let b_ok = formula_holds(&b.formula, &ctx.derived, &env_b);
         if a_ok != b_ok {
             return Some(SemanticDupEvidence {
                 model_points: total,
@@ -499,13 +566,19 @@ struct RuleHeadSummary {
 
 fn rule_head_tuples(rule: &Rule, ctx: &SemanticDupContext<'_>) -> Option<RuleHeadSummary> {
     let vars = infer_rule_var_types(rule, &ctx.relation_schemas, &ctx.constructor_sigs)?;
-    let valuations = enumerate_named_valuations(&vars, &ctx.universe)?;
+    let limit = ctx
+        .sampling
+        .map(|sampling| sampling.samples)
+        .into_iter()
+        .chain(ctx.limits.max_model_points)
+        .min();
+    let valuations = enumerate_named_valuations(&vars, &ctx.universe, limit)?;
     let total_valuations = valuations.len();
     let mut evaluated_valuations = 0usize;
     let mut out = BTreeSet::new();
 
     for valuation in valuations {
-        if !eval_formula_with_env(&rule.body, &ctx.derived, &valuation) {
+        if !formula_holds(&rule.body, &ctx.derived, &valuation) {
             evaluated_valuations += 1;
             continue;
         }
@@ -612,24 +685,33 @@ fn defns_semantic_evidence(
     b: &Defn,
     ctx: &SemanticDupContext<'_>,
 ) -> Option<SemanticDupEvidence> {
-    let tuples = enumerate_eval_param_tuples(&a.params, &ctx.universe)?;
-    let total = tuples.len();
+    let (total, tuples) = enumerate_eval_param_tuples(&a.params, ctx)?;
     let mut checked = 0usize;
     let mut depth_limited_points = 0usize;
-    let eval_depth_limit = adaptive_eval_depth_limit(a, b);
-
+    let eval_depth_limit = ctx
+        .limits
+        .eval_depth_limit
+        .unwrap_or_else(|| adaptive_eval_depth_limit(&ctx.program.exprs, a, b));
+
+    let interpreter = Interpreter::new(ctx.program, &ctx.derived);
+    let limits = EvalLimits {
+        max_depth: eval_depth_limit,
+        max_steps: u64::MAX,
+    };
     for tuple in tuples {
-        let mut depth_limited = false;
-        let left = eval_defn_with_tuple(a, &tuple, ctx, 0, eval_depth_limit, &mut depth_limited);
-        let right = eval_defn_with_tuple(b, &tuple, ctx, 0, eval_depth_limit, &mut depth_limited);
-        let (Some(left), Some(right)) = (left, right) else {
-            if depth_limited {
+        let left = interpreter.call(&a.name, &tuple, limits);
+        let right = interpreter.call(&b.name, &tuple, limits);
+        let (Ok(left), Ok(right)) = (&left, &right) else {
+            if [left, right]
+                .iter()
+                .any(|result| matches!(result, Err(EvalError::DepthLimit(_))))
+            {
                 depth_limited_points += 1;
             }
             continue;
         };
         checked += 1;
-        if left != right {
+        if left.value != right.value {
             return Some(SemanticDupEvidence {
                 model_points: total,
                 checked_points: checked,
@@ -650,282 +732,19 @@ fn defns_semantic_evidence(
     })
 }
 
-fn adaptive_eval_depth_limit(a: &Defn, b: &Defn) -> usize {
-    let complexity = expr_node_count(&a.body).max(expr_node_count(&b.body));
+fn adaptive_eval_depth_limit(exprs: &ExprArena, a: &Defn, b: &Defn) -> usize {
+    let complexity = expr_node_count(exprs, a.body).max(expr_node_count(exprs, b.body));
     BASE_EVAL_DEPTH_LIMIT
         .saturating_add(complexity.saturating_mul(8))
         .min(MAX_EVAL_DEPTH_LIMIT)
 }
 
-fn expr_node_count(expr: &Expr) -> usize {
-    match expr {
-        Expr::Var { .. } | Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => 1,
-        Expr::Call { args, .. } => 1 + args.iter().map(expr_node_count).sum::<usize>(),
-        Expr::Let { bindings, body, .. } => {
-            1 + bindings
-                .iter()
-                .map(|(_, bexpr, _)| expr_node_count(bexpr))
-                .sum::<usize>()
-                + expr_node_count(body)
-        }
-        Expr::If {
-            cond,
-            then_branch,
-            else_branch,
-            ..
-        } => {
-            1 + expr_node_count(cond) + expr_node_count(then_branch) + expr_node_count(else_branch)
-        }
-        Expr::Match {
-            scrutinee, arms, ..
-        } => {
-            1 + expr_node_count(scrutinee)
-                + arms
-                    .iter()
-                    .map(|arm| expr_node_count(&arm.body))
-                    .sum::<usize>()
-        }
-    }
-}
-
-fn eval_defn_with_tuple(
-    defn: &Defn,
-    tuple: &[EvalValue],
-    ctx: &SemanticDupContext<'_>,
-    depth: usize,
-    depth_limit: usize,
-    depth_limited: &mut bool,
-) -> Option<EvalValue> {
-    if defn.params.len() != tuple.len() {
-        return None;
-    }
-    let mut env = HashMap::new();
-    for (param, value) in defn.params.iter().zip(tuple.iter()) {
-        env.insert(param.name.clone(), value.clone());
-    }
-    eval_expr_with_env(&defn.body, &env, ctx, depth, depth_limit, depth_limited)
-}
-
-fn eval_expr_with_env(
-    expr: &Expr,
-    env: &HashMap<String, EvalValue>,
-    ctx: &SemanticDupContext<'_>,
-    depth: usize,
-    depth_limit: usize,
-    depth_limited: &mut bool,
-) -> Option<EvalValue> {
-    if depth > depth_limit {
-        *depth_limited = true;
-        return None;
-    }
-
-    match expr {
-        Expr::Var { name, .. } => env.get(name).cloned(),
-        Expr::Symbol { value, .. } => Some(EvalValue::Symbol(value.clone())),
-        Expr::Int { value, .. } => Some(EvalValue::Int(*value)),
-        Expr::Bool { value, .. } => Some(EvalValue::Bool(*value)),
-        Expr::Call { name, args, .. } => {
-            let mut values = Vec::new();
-            for arg in args {
-                values.push(eval_expr_with_env(
-                    arg,
-                    env,
-                    ctx,
-                    depth + 1,
-                    depth_limit,
-                    depth_limited,
-                )?);
-            }
-
-            if let Some(EvalValue::Function(fun)) = env.get(name) {
-                return apply_function_value(fun, &values);
-            }
-
-            if let Some(idx) = ctx.defn_indices.get(name).copied() {
-                let defn = &ctx.program.defns[idx];
-                return eval_defn_with_tuple(
-                    defn,
-                    &values,
-                    ctx,
-                    depth + 1,
-                    depth_limit,
-                    depth_limited,
-                );
-            }
-            if ctx.constructor_sigs.contains_key(name) {
-                return Some(EvalValue::Adt {
-                    ctor: name.clone(),
-                    fields: values,
-                });
-            }
-            if let Some(schema) = ctx.relation_schemas.get(name) {
-                if schema.len() != values.len() {
-                    return None;
-                }
-                let tuple = values
-                    .iter()
-                    .map(eval_to_concrete)
-                    .collect::<Option<Vec<_>>>()?;
-                let exists = ctx
-                    .derived
-                    .facts
-                    .get(name)
-                    .map(|set| set.contains(&tuple))
-                    .unwrap_or(false);
-                return Some(EvalValue::Bool(exists));
-            }
-            None
-        }
-        Expr::Let { bindings, body, .. } => {
-            let mut local = env.clone();
-            for (name, bexpr, _) in bindings {
-                let value =
-                    eval_expr_with_env(bexpr, &local, ctx, depth + 1, depth_limit, depth_limited)?;
-                local.insert(name.clone(), value);
-            }
-            eval_expr_with_env(body, &local, ctx, depth + 1, depth_limit, depth_limited)
-        }
-        Expr::If {
-            cond,
-            then_branch,
-            else_branch,
-            ..
-        } => {
-            let cond_value =
-                eval_expr_with_env(cond, env, ctx, depth + 1, depth_limit, depth_limited)?;
-            match cond_value {
-                EvalValue::Bool(true) => {
-                    eval_expr_with_env(then_branch, env, ctx, depth + 1, depth_limit, depth_limited)
-                }
-                EvalValue::Bool(false) => {
-                    eval_expr_with_env(else_branch, env, ctx, depth + 1, depth_limit, depth_limited)
-                }
-                _ => None,
-            }
-        }
-        Expr::Match {
-            scrutinee, arms, ..
-        } => {
-            let target =
-                eval_expr_with_env(scrutinee, env, ctx, depth + 1, depth_limit, depth_limited)?;
-            for arm in arms {
-                let mut captures = HashMap::new();
-                if !matches_pattern_eval(&arm.pattern, &target, &mut captures) {
-                    continue;
-                }
-                let mut local = env.clone();
-                for (name, value) in captures {
-                    local.insert(name, value);
-                }
-                return eval_expr_with_env(
-                    &arm.body,
-                    &local,
-                    ctx,
-                    depth + 1,
-                    depth_limit,
-                    depth_limited,
-                );
-            }
-            None
-        }
-    }
-}
-
-fn apply_function_value(fun: &FunctionValue, args: &[EvalValue]) -> Option<EvalValue> {
-    fun.table
-        .iter()
-        .find(|(inputs, _)| inputs == args)
-        .map(|(_, output)| output.clone())
-}
-
-fn eval_to_concrete(value: &EvalValue) -> Option<Value> {
-    match value {
-        EvalValue::Symbol(s) => Some(Value::Symbol(s.clone())),
-        EvalValue::Int(i) => Some(Value::Int(*i)),
-        EvalValue::Bool(b) => Some(Value::Bool(*b)),
-        EvalValue::Adt { ctor, fields } => {
-            let concrete_fields = fields
-                .iter()
-                .map(eval_to_concrete)
-                .collect::<Option<Vec<_>>>()?;
-            Some(Value::Adt {
-                ctor: ctor.clone(),
-                fields: concrete_fields,
-            })
-        }
-        EvalValue::Function(_) => None,
-    }
-}
-
-fn concrete_to_eval(value: &Value) -> EvalValue {
-    match value {
-        Value::Symbol(s) => EvalValue::Symbol(s.clone()),
-        Value::Int(i) => EvalValue::Int(*i),
-        Value::Bool(b) => EvalValue::Bool(*b),
-        Value::Adt { ctor, fields } => EvalValue::Adt {
-            ctor: ctor.clone(),
-            fields: fields.iter().map(concrete_to_eval).collect(),
-        },
-    }
-}
-
-fn matches_pattern_eval(
-    pattern: &Pattern,
-    target: &EvalValue,
-    binds: &mut HashMap<String, EvalValue>,
-) -> bool {
-    match pattern {
-        Pattern::Wildcard { .. } => true,
-        Pattern::Var { name, .. } => {
-            if let Some(prev) = binds.get(name) {
-                prev == target
-            } else {
-                binds.insert(name.clone(), target.clone());
-                true
-            }
-        }
-        Pattern::Symbol { value, .. } => matches!(target, EvalValue::Symbol(s) if s == value),
-        Pattern::Int { value, .. } => matches!(target, EvalValue::Int(i) if i == value),
-        Pattern::Bool { value, .. } => matches!(target, EvalValue::Bool(b) if b == value),
-        Pattern::Ctor { name, args, .. } => {
-            let EvalValue::Adt { ctor, fields } = target else {
-                return false;
-            };
-            if name != ctor || args.len() != fields.len() {
-                return false;
-            }
-            for (p, value) in args.iter().zip(fields.iter()) {
-                if !matches_pattern_eval(p, value, binds) {
-                    return false;
-                }
-            }
-            true
-        }
-    }
-}
-
-fn eval_formula_with_env(
-    formula: &Formula,
-    derived: &DerivedFacts,
-    env: &HashMap<String, Value>,
-) -> bool {
-    match formula {
-        Formula::True => true,
-        Formula::Atom(atom) => {
-            let Some(tuple) = instantiate_terms(&atom.terms, env) else {
-                return false;
-            };
-            derived
-                .facts
-                .get(&atom.pred)
-                .map(|set| set.contains(&tuple))
-                .unwrap_or(false)
-        }
-        Formula::And(items) => items
-            .iter()
-            .all(|item| eval_formula_with_env(item, derived, env)),
-        Formula::Not(inner) => !eval_formula_with_env(inner, derived, env),
-    }
+fn expr_node_count(exprs: &ExprArena, expr: ExprId) -> usize {
+    1 + exprs[expr]
+        .children()
+        .into_iter()
+        .map(|child| expr_node_count(exprs, child))
+        .sum::<usize>()
 }
 
 fn bind_params(params: &[Param], values: &[Value]) -> HashMap<String, Value> {
@@ -938,39 +757,45 @@ fn bind_params(params: &[Param], values: &[Value]) -> HashMap<String, Value> {
 
 fn enumerate_const_param_tuples(
     params: &[Param],
-    universe: &HashMap<String, Vec<Value>>,
-) -> Option<Vec<Vec<Value>>> {
+    ctx: &SemanticDupContext<'_>,
+) -> Option<(usize, Vec<Vec<Value>>)> {
     let mut domains = Vec::new();
     for param in params {
         let key = type_key(&param.ty)?;
-        let values = universe.get(&key)?;
+        let values = ctx.universe.get(&key)?;
         if values.is_empty() {
             return None;
         }
         domains.push(values.clone());
     }
-    Some(enumerate_tuples(&domains))
+    select_tuples(&domains, ctx)
 }
 
 fn enumerate_eval_param_tuples(
     params: &[Param],
-    universe: &HashMap<String, Vec<Value>>,
-) -> Option<Vec<Vec<EvalValue>>> {
+    ctx: &SemanticDupContext<'_>,
+) -> Option<(usize, Vec<Vec<EvalValue>>)> {
     let mut domains = Vec::new();
     let mut cache = HashMap::new();
     for param in params {
-        let values = enumerate_eval_values_for_type(&param.ty, universe, &mut cache)?;
+        let values = enumerate_eval_values_for_type(
+            &param.ty,
+            &ctx.universe,
+            ctx.limits.max_function_values,
+            &mut cache,
+        )?;
         if values.is_empty() {
             return None;
         }
         domains.push(values);
     }
-    Some(enumerate_tuples(&domains))
+    select_tuples(&domains, ctx)
 }
 
 fn enumerate_eval_values_for_type(
     ty: &Type,
     universe: &HashMap<String, Vec<Value>>,
+    max_function_values: usize,
     cache: &mut HashMap<Type, Vec<EvalValue>>,
 ) -> Option<Vec<EvalValue>> {
     let normalized = match ty {
@@ -985,24 +810,27 @@ fn enumerate_eval_values_for_type(
         Type::Bool => universe
             .get("Bool")?
             .iter()
-            .map(concrete_to_eval)
+            .map(EvalValue::from_value)
             .collect::<Vec<_>>(),
         Type::Int => universe
             .get("Int")?
             .iter()
-            .map(concrete_to_eval)
+            .map(EvalValue::from_value)
             .collect::<Vec<_>>(),
         Type::Symbol => universe
             .get("Symbol")?
             .iter()
-            .map(concrete_to_eval)
+            .map(EvalValue::from_value)
             .collect::<Vec<_>>(),
         Type::Domain(name) | Type::Adt(name) => universe
             .get(name)?
             .iter()
-            .map(concrete_to_eval)
+            .map(EvalValue::from_value)
             .collect::<Vec<_>>(),
-        Type::Fun(args, ret) => enumerate_function_values(args, ret, universe, cache)?,
+        Type::Fun(args, ret) => {
+            enumerate_function_values(args, ret, universe, max_function_values, cache)?
+        }
+        Type::Error => return None,
         Type::Refine { .. } => unreachable!(),
     };
     cache.insert(normalized, values.clone());
@@ -1013,18 +841,19 @@ fn enumerate_function_values(
     args: &[Type],
     ret: &Type,
     universe: &HashMap<String, Vec<Value>>,
+    max_function_values: usize,
     cache: &mut HashMap<Type, Vec<EvalValue>>,
 ) -> Option<Vec<EvalValue>> {
     let mut arg_domains = Vec::new();
     for arg in args {
-        let values = enumerate_eval_values_for_type(arg, universe, cache)?;
+        let values = enumerate_eval_values_for_type(arg, universe, max_function_values, cache)?;
         if values.is_empty() {
             return None;
         }
         arg_domains.push(values);
     }
     let input_tuples = enumerate_tuples(&arg_domains);
-    let output_values = enumerate_eval_values_for_type(ret, universe, cache)?;
+    let output_values = enumerate_eval_values_for_type(ret, universe, max_function_values, cache)?;
     if output_values.is_empty() {
         return None;
     }
@@ -1032,7 +861,7 @@ fn enumerate_function_values(
     let output_count = output_values.len();
     let exponent = u32::try_from(input_tuples.len()).ok()?;
     let total = output_count.checked_pow(exponent)?;
-    if total > MAX_FUNCTION_MODEL_VALUES {
+    if total > max_function_values {
         return None;
     }
 
@@ -1066,6 +895,69 @@ fn increment_digits(digits: &mut [usize], base: usize) -> bool {
     false
 }
 
+fn select_tuples<T: Clone>(
+    domains: &[Vec<T>],
+    ctx: &SemanticDupContext<'_>,
+) -> Option<(usize, Vec<Vec<T>>)> {
+    let total = domains
+        .iter()
+        .try_fold(1usize, |acc, domain| acc.checked_mul(domain.len()));
+    let evaluated = match ctx.sampling {
+        Some(sampling) => total.map_or(sampling.samples, |total| total.min(sampling.samples)),
+        None => total.unwrap_or(usize::MAX),
+    };
+    if ctx
+        .limits
+        .max_model_points
+        .is_some_and(|limit| evaluated > limit)
+    {
+        return None;
+    }
+    let sampling = match (total, ctx.sampling) {
+        (Some(total), Some(sampling)) if total <= sampling.samples => None,
+        (_, sampling) => sampling,
+    };
+    let Some(sampling) = sampling else {
+        return Some((total?, enumerate_tuples(domains)));
+    };
+
+    let mut rng = SplitMix64(sampling.seed);
+    let mut picked = HashSet::new();
+    let mut out = Vec::with_capacity(sampling.samples);
+    while out.len() < sampling.samples {
+        let indices = domains
+            .iter()
+            .map(|domain| rng.below(domain.len()))
+            .collect::<Vec<_>>();
+        if picked.insert(indices.clone()) {
+            out.push(
+                indices
+                    .iter()
+                    .zip(domains)
+                    .map(|(&idx, domain)| domain[idx].clone())
+                    .collect(),
+            );
+        }
+    }
+    Some((total.unwrap_or(usize::MAX), out))
+}
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
 fn enumerate_tuples<T: Clone>(domains: &[Vec<T>]) -> Vec<Vec<T>> {
     let mut out = Vec::new();
     enumerate_tuples_inner(domains, 0, &mut Vec::new(), &mut out);
@@ -1093,15 +985,21 @@ fn enumerate_tuples_inner<T: Clone>(
 fn enumerate_named_valuations(
     vars: &[(String, String)],
     universe: &HashMap<String, Vec<Value>>,
+    limit: Option<usize>,
 ) -> Option<Vec<HashMap<String, Value>>> {
     let mut domains = Vec::new();
+    let mut total = 1usize;
     for (name, key) in vars {
         let values = universe.get(key)?;
         if values.is_empty() {
             return None;
         }
+        total = total.saturating_mul(values.len());
         domains.push((name.clone(), values.clone()));
     }
+    if limit.is_some_and(|limit| total > limit) {
+        return None;
+    }
 
     let mut out = Vec::new();
     enumerate_named(&domains, 0, &mut HashMap::new(), &mut out);
@@ -1126,36 +1024,10 @@ fn enumerate_named(
     }
 }
 
-fn instantiate_terms(terms: &[LogicTerm], env: &HashMap<String, Value>) -> Option<Vec<Value>> {
-    terms
-        .iter()
-        .map(|term| instantiate_term(term, env))
-        .collect::<Option<Vec<_>>>()
-}
-
-fn instantiate_term(term: &LogicTerm, env: &HashMap<String, Value>) -> Option<Value> {
-    match term {
-        LogicTerm::Var(name) => env.get(name).cloned(),
-        LogicTerm::Symbol(s) => Some(Value::Symbol(s.clone())),
-        LogicTerm::Int(i) => Some(Value::Int(*i)),
-        LogicTerm::Bool(b) => Some(Value::Bool(*b)),
-        LogicTerm::Ctor { name, args } => {
-            let fields = args
-                .iter()
-                .map(|arg| instantiate_term(arg, env))
-                .collect::<Option<Vec<_>>>()?;
-            Some(Value::Adt {
-                ctor: name.clone(),
-                fields,
-            })
-        }
-    }
-}
-
 fn logic_term_to_const_value(term: &LogicTerm) -> Option<Value> {
     match term {
         LogicTerm::Var(_) => None,
-        LogicTerm::Symbol(s) => Some(Value::Symbol(s.clone())),
+        LogicTerm::Symbol(s) => Some(Value::Symbol(Symbol::intern(s))),
         LogicTerm::Int(i) => Some(Value::Int(*i)),
         LogicTerm::Bool(b) => Some(Value::Bool(*b)),
         LogicTerm::Ctor { name, args } => {
@@ -1164,7 +1036,7 @@ fn logic_term_to_const_value(term: &LogicTerm) -> Option<Value> {
                 .map(logic_term_to_const_value)
                 .collect::<Option<Vec<_>>>()?;
             Some(Value::Adt {
-                ctor: name.clone(),
+                ctor: Symbol::intern(name),
                 fields,
             })
         }
@@ -1178,7 +1050,7 @@ fn type_key(ty: &Type) -> Option<String> {
         Type::Symbol => Some("Symbol".to_string()),
         Type::Domain(name) | Type::Adt(name) => Some(name.clone()),
         Type::Refine { base, .. } => type_key(base),
-        Type::Fun(_, _) => None,
+        Type::Fun(_, _) | Type::Error => None,
     }
 }
 
@@ -1238,6 +1110,7 @@ fn collect_type_keys(ty: &Type, out: &mut HashSet<String>) {
             collect_type_keys(ret, out);
         }
         Type::Refine { base, .. } => collect_type_keys(base, out),
+        Type::Error => {}
     }
 }
 
@@ -1285,13 +1158,16 @@ fn lint_unused_declarations(program: &Program) -> Vec<LintDiagnostic> {
             collect_type_keys(&param.ty, &mut used_universe);
         }
         collect_type_names(&defn.ret_type, &mut used_types);
-        collect_expr_calls(
-            &defn.body,
-            &relation_names,
-            &defn_names,
-            &mut used_relations,
-            &mut used_defns,
-        );
+        for expr in std::iter::once(defn.body).chain(defn.measure) {
+            collect_expr_calls(
+                &program.exprs,
+                expr,
+                &relation_names,
+                &defn_names,
+                &mut used_relations,
+                &mut used_defns,
+            );
+        }
     }
 
     // defn 自身は証明義務対象なので、自己使用として扱う。
@@ -1358,6 +1234,379 @@ fn lint_unused_declarations(program: &Program) -> Vec<LintDiagnostic> {
     out
 }
 
+// 名前解決は universe 値の最上位の形しか見ないため、重複と constructor 引数の
+// 型不一致はここで拾う。どちらも prover の列挙と semantic-dup のモデルを歪める。
+fn lint_universe_values(program: &Program) -> Vec<LintDiagnostic> {
+    let mut out = Vec::new();
+    let constructors = program
+        .data_decls
+        .iter()
+        .flat_map(|d| {
+            d.constructors
+                .iter()
+                .map(move |ctor| (ctor.name.as_str(), (d.name.as_str(), &ctor.fields)))
+        })
+        .collect::<HashMap<_, _>>();
+
+    for universe in &program.universes {
+        let mut seen = HashSet::new();
+        for value in &universe.values {
+            let rendered = render_logic_term(value);
+            if !seen.insert(normalize_logic_term(value, &mut AlphaState::default())) {
+                out.push(LintDiagnostic::warning(
+                    "L-UNIVERSE-DUP",
+                    "universe",
+                    format!(
+                        "universe {} に同じ値が重複しています: {rendered}",
+                        universe.ty_name
+                    ),
+                    Some(universe.span.clone()),
+                    None,
+                ));
+            }
+            let expected = Type::Domain(universe.ty_name.clone());
+            if let Some(field_ty) = universe_value_mismatch(value, &expected, &constructors) {
+                out.push(LintDiagnostic::warning(
+                    "L-UNIVERSE-TYPE",
+                    "universe",
+                    format!(
+                        "universe {} の値 {rendered} に {field_ty} 型でない部分があります",
+                        universe.ty_name
+                    ),
+                    Some(universe.span.clone()),
+                    None,
+                ));
+            }
+        }
+    }
+
+    out
+}
+
+// universe がある型の定数を fact / rule が使っていても、prover は universe だけを
+// 列挙するため、その定数に関する assert は検査されないまま proved になり得る。
+fn lint_constants_outside_universe(program: &Program) -> Vec<LintDiagnostic> {
+    let mut out = Vec::new();
+    let universes = program
+        .universes
+        .iter()
+        .map(|u| {
+            let values = u
+                .values
+                .iter()
+                .map(|v| normalize_logic_term(v, &mut AlphaState::default()))
+                .collect::<HashSet<_>>();
+            (u.ty_name.as_str(), values)
+        })
+        .collect::<HashMap<_, _>>();
+    let schemas = program
+        .relations
+        .iter()
+        .map(|r| (r.name.as_str(), &r.arg_sorts))
+        .collect::<HashMap<_, _>>();
+
+    let mut check = |kind: &str, pred: &str, terms: &[LogicTerm], span: &Span| {
+        let Some(sorts) = schemas.get(pred) else {
+            return;
+        };
+        for (term, sort) in terms.iter().zip(sorts.iter()) {
+            let Some(values) = universes.get(sort.as_str()) else {
+                continue;
+            };
+            if logic_term_to_const_value(term).is_none() {
+                continue;
+            }
+            if !values.contains(&normalize_logic_term(term, &mut AlphaState::default())) {
+                out.push(LintDiagnostic::warning(
+                    "L-UNIVERSE-MISSING",
+                    "universe",
+                    format!(
+                        "{kind} {pred} の値 {} は universe {sort} に含まれていません",
+                        render_logic_term(term)
+                    ),
+                    Some(span.clone()),
+                    None,
+                ));
+            }
+        }
+    };
+    for fact in &program.facts {
+        check("fact", &fact.name, &fact.terms, &fact.span);
+    }
+    for rule in &program.rules {
+        check("rule", &rule.head.pred, &rule.head.terms, &rule.span);
+        let mut atoms = Vec::new();
+        collect_formula_atoms(&rule.body, &mut atoms);
+        for atom in atoms {
+            check("rule", &atom.pred, &atom.terms, &rule.span);
+        }
+    }
+
+    out
+}
+
+// 前提に置いた relation が空だと `(not (and (r ...) ...))` 形の assert は常に真になり、
+// 要件が検査されないまま proved になる。fact も rule も無い relation は確実に空、
+// rule はあるが現在の fact から何も導出されない relation は fact 次第なので低めにする。
+const UNDEFINED_RELATION_CONFIDENCE: f64 = 0.95;
+const UNDERIVED_RELATION_CONFIDENCE: f64 = 0.6;
+
+fn lint_vacuous_asserts(program: &Program, fixpoint: FixpointOptions<'_>) -> Vec<LintDiagnostic> {
+    let mut out = Vec::new();
+    let defined = program
+        .facts
+        .iter()
+        .map(|f| f.name.as_str())
+        .chain(program.rules.iter().map(|r| r.head.pred.as_str()))
+        .collect::<HashSet<_>>();
+    let derived = fixpoint
+        .knowledge_base(program)
+        .ok()
+        .and_then(|kb| solve_facts_cached(&kb, fixpoint).ok());
+    let empty_confidence = |pred: &str| {
+        if !defined.contains(pred) {
+            Some(UNDEFINED_RELATION_CONFIDENCE)
+        } else if derived
+            .as_ref()
+            .is_some_and(|d| d.facts.get(pred).is_none_or(BTreeSet::is_empty))
+        {
+            Some(UNDERIVED_RELATION_CONFIDENCE)
+        } else {
+            None
+        }
+    };
+
+    for assertion in &program.asserts {
+        let Some((true, causes)) = constant_truth(&assertion.formula, &empty_confidence) else {
+            continue;
+        };
+        let Some(confidence) = causes.iter().map(|cause| cause.confidence).reduce(f64::min) else {
+            continue;
+        };
+        let names = |premise: bool| {
+            causes
+                .iter()
+                .filter(|cause| cause.premise == premise)
+                .map(|cause| cause.pred)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let reasons = [
+            (names(true), "前提の relation"),
+            (names(false), "否定している relation"),
+        ]
+        .into_iter()
+        .filter(|(names, _)| !names.is_empty())
+        .map(|(names, role)| format!("{role} {names}"))
+        .collect::<Vec<_>>();
+        let outcome = if causes.iter().all(|cause| cause.premise) {
+            "前提が成り立たない"
+        } else if causes.iter().all(|cause| !cause.premise) {
+            "どの値も否定している relation に当てはまらない"
+        } else {
+            "前提が成り立たないか、どの値も否定している relation に当てはまらない"
+        };
+        out.push(LintDiagnostic::warning(
+            "L-VACUOUS-ASSERT",
+            "vacuous",
+            format!(
+                "assert {} は{} が空のため常に真です（{outcome}）",
+                assertion.name,
+                reasons.join("・")
+            ),
+            Some(assertion.span.clone()),
+            Some(confidence),
+        ));
+    }
+
+    out
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EmptyCause<'a> {
+    pred: &'a str,
+    confidence: f64,
+    premise: bool,
+}
+
+fn constant_truth<'a>(
+    formula: &'a Formula,
+    empty_confidence: &impl Fn(&str) -> Option<f64>,
+) -> Option<(bool, Vec<EmptyCause<'a>>)> {
+    match formula {
+        Formula::True => Some((true, Vec::new())),
+        Formula::Atom(atom)
+            if ComparisonOp::from_pred(&atom.pred).is_some() || atom.pred == DISEQUALITY_PRED =>
+        {
+            None
+        }
+        Formula::Atom(atom) => empty_confidence(&atom.pred).map(|confidence| {
+            let cause = EmptyCause {
+                pred: atom.pred.as_str(),
+                confidence,
+                premise: false,
+            };
+            (false, vec![cause])
+        }),
+        Formula::Not(inner) => {
+            let (value, mut causes) = constant_truth(inner, empty_confidence)?;
+            if let Formula::And(items) = inner.as_ref()
+                && items
+                    .iter()
+                    .find(|item| matches!(constant_truth(item, empty_confidence), Some((false, _))))
+                    .is_some_and(|item| !matches!(item, Formula::Not(_)))
+            {
+                for cause in &mut causes {
+                    cause.premise = true;
+                }
+            }
+            Some((!value, causes))
+        }
+        Formula::And(items) => {
+            let values = items
+                .iter()
+                .map(|item| constant_truth(item, empty_confidence))
+                .collect::<Vec<_>>();
+            if let Some(falsified) = values.iter().flatten().find(|(value, _)| !value) {
+                return Some(falsified.clone());
+            }
+            let causes = values
+                .into_iter()
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
+                .flat_map(|(_, causes)| causes)
+                .collect();
+            Some((true, causes))
+        }
+    }
+}
+
+fn lint_complexity(program: &Program, limits: ComplexityLimits) -> Vec<LintDiagnostic> {
+    let mut out = Vec::new();
+    let mut report = |code: &'static str, message: String, span: &Span| {
+        out.push(LintDiagnostic::warning(
+            code,
+            "complexity",
+            message,
+            Some(span.clone()),
+            None,
+        ));
+    };
+
+    for defn in &program.defns {
+        let depth = expr_depth(&program.exprs, defn.body);
+        if depth > limits.max_expr_depth {
+            report(
+                "L-COMPLEX-EXPR-DEPTH",
+                format!(
+                    "defn {} の式のネストが深すぎます: {depth}（上限 {}）",
+                    defn.name, limits.max_expr_depth
+                ),
+                &defn.span,
+            );
+        }
+        let nodes = expr_node_count(&program.exprs, defn.body);
+        if nodes > limits.max_defn_nodes {
+            report(
+                "L-COMPLEX-DEFN-SIZE",
+                format!(
+                    "defn {} の本体が大きすぎます: {nodes} ノード（上限 {}）",
+                    defn.name, limits.max_defn_nodes
+                ),
+                &defn.span,
+            );
+        }
+    }
+    for rule in &program.rules {
+        let mut atoms = Vec::new();
+        collect_formula_atoms(&rule.body, &mut atoms);
+        if atoms.len() > limits.max_rule_body_atoms {
+            report(
+                "L-COMPLEX-RULE-BODY",
+                format!(
+                    "rule {} の本体の atom が多すぎます: {}（上限 {}）",
+                    rule.head.pred,
+                    atoms.len(),
+                    limits.max_rule_body_atoms
+                ),
+                &rule.span,
+            );
+        }
+    }
+    for assertion in &program.asserts {
+        if assertion.params.len() > limits.max_assert_params {
+            report(
+                "L-COMPLEX-ASSERT-PARAMS",
+                format!(
+                    "assert {} の引数が多すぎます: {}（上限 {}）",
+                    assertion.name,
+                    assertion.params.len(),
+                    limits.max_assert_params
+                ),
+                &assertion.span,
+            );
+        }
+    }
+
+    out
+}
+
+fn expr_depth(exprs: &ExprArena, expr: ExprId) -> usize {
+    1 + exprs[expr]
+        .children()
+        .into_iter()
+        .map(|child| expr_depth(exprs, child))
+        .max()
+        .unwrap_or(0)
+}
+
+fn collect_formula_atoms<'a>(formula: &'a Formula, out: &mut Vec<&'a Atom>) {
+    match formula {
+        Formula::True => {}
+        Formula::Atom(atom) => out.push(atom),
+        Formula::And(items) => {
+            for item in items {
+                collect_formula_atoms(item, out);
+            }
+        }
+        Formula::Not(inner) => collect_formula_atoms(inner, out),
+    }
+}
+
+fn universe_value_mismatch(
+    term: &LogicTerm,
+    expected: &Type,
+    constructors: &HashMap<&str, (&str, &Vec<Type>)>,
+) -> Option<String> {
+    let fits = match expected {
+        Type::Refine { base, .. } => return universe_value_mismatch(term, base, constructors),
+        Type::Fun(_, _) | Type::Error => true,
+        Type::Bool => matches!(term, LogicTerm::Bool(_)),
+        Type::Int => matches!(term, LogicTerm::Int(_)),
+        Type::Symbol => matches!(term, LogicTerm::Symbol(_)),
+        Type::Domain(name) | Type::Adt(name) => {
+            let is_data = constructors.values().any(|(owner, _)| owner == name);
+            if !is_data {
+                matches!(term, LogicTerm::Symbol(_))
+            } else if let LogicTerm::Ctor { name: ctor, args } = term
+                && let Some((owner, fields)) = constructors.get(ctor.as_str())
+                && owner == name
+                && fields.len() == args.len()
+            {
+                return args
+                    .iter()
+                    .zip(fields.iter())
+                    .find_map(|(arg, field)| universe_value_mismatch(arg, field, constructors));
+            } else {
+                false
+            }
+        }
+    };
+    (!fits).then(|| type_key(expected).unwrap_or_default())
+}
+
 fn collect_formula_relations(formula: &Formula, out: &mut HashSet<String>) {
     match formula {
         Formula::True => {}
@@ -1375,7 +1624,7 @@ fn collect_formula_relations(formula: &Formula, out: &mut HashSet<String>) {
 
 fn collect_type_names(ty: &Type, out: &mut HashSet<String>) {
     match ty {
-        Type::Bool | Type::Int | Type::Symbol => {}
+        Type::Bool | Type::Int | Type::Symbol | Type::Error => {}
         Type::Domain(name) | Type::Adt(name) => {
             out.insert(name.clone());
         }
@@ -1390,84 +1639,35 @@ fn collect_type_names(ty: &Type, out: &mut HashSet<String>) {
 }
 
 fn collect_expr_calls(
-    expr: &Expr,
+    exprs: &ExprArena,
+    expr: ExprId,
     relation_names: &HashSet<String>,
     defn_names: &HashSet<String>,
     used_relations: &mut HashSet<String>,
     used_defns: &mut HashSet<String>,
 ) {
-    match expr {
-        Expr::Var { .. } | Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => {}
-        Expr::Call { name, args, .. } => {
-            if relation_names.contains(name) {
-                used_relations.insert(name.clone());
-            }
-            if defn_names.contains(name) {
-                used_defns.insert(name.clone());
-            }
-            for arg in args {
-                collect_expr_calls(arg, relation_names, defn_names, used_relations, used_defns);
-            }
+    if let Expr::Call { name, .. } = &exprs[expr] {
+        if relation_names.contains(name) {
+            used_relations.insert(name.clone());
         }
-        Expr::Let { bindings, body, .. } => {
-            for (_, bexpr, _) in bindings {
-                collect_expr_calls(
-                    bexpr,
-                    relation_names,
-                    defn_names,
-                    used_relations,
-                    used_defns,
-                );
-            }
-            collect_expr_calls(body, relation_names, defn_names, used_relations, used_defns);
-        }
-        Expr::If {
-            cond,
-            then_branch,
-            else_branch,
-            ..
-        } => {
-            collect_expr_calls(cond, relation_names, defn_names, used_relations, used_defns);
-            collect_expr_calls(
-                then_branch,
-                relation_names,
-                defn_names,
-                used_relations,
-                used_defns,
-            );
-            collect_expr_calls(
-                else_branch,
-                relation_names,
-                defn_names,
-                used_relations,
-                used_defns,
-            );
-        }
-        Expr::Match {
-            scrutinee, arms, ..
-        } => {
-            collect_expr_calls(
-                scrutinee,
-                relation_names,
-                defn_names,
-                used_relations,
-                used_defns,
-            );
-            for arm in arms {
-                collect_expr_calls(
-                    &arm.body,
-                    relation_names,
-                    defn_names,
-                    used_relations,
-                    used_defns,
-                );
-            }
+        if defn_names.contains(name) {
+            used_defns.insert(name.clone());
         }
     }
+    for child in exprs[expr].children() {
+        collect_expr_calls(
+            exprs,
+            child,
+            relation_names,
+            defn_names,
+            used_relations,
+            used_defns,
+        );
+    }
 }
 
 #[derive(Default)]
-struct AlphaState {
+pub(crate) struct AlphaState {
     map: HashMap<String, String>,
     next: usize,
 }
@@ -1485,7 +1685,7 @@ impl AlphaState {
     }
 }
 
-fn normalize_fact(fact: &crate::ast::Fact) -> String {
+pub(crate) fn normalize_fact(fact: &crate::ast::Fact) -> String {
     format!(
         "{}({})",
         fact.name,
@@ -1497,14 +1697,17 @@ fn normalize_fact(fact: &crate::ast::Fact) -> String {
     )
 }
 
-fn normalize_rule(rule: &crate::ast::Rule) -> String {
+pub(crate) fn normalize_rule(rule: &crate::ast::Rule) -> String {
     let mut alpha = AlphaState::default();
     let head = normalize_atom(&rule.head, &mut alpha);
     let body = normalize_formula(&rule.body, &mut alpha);
-    format!("{head}<-{body}")
+    match rule.skolem {
+        Some(depth) => format!("{head}<-{body}:skolem{depth}"),
+        None => format!("{head}<-{body}"),
+    }
 }
 
-fn normalize_assert(assertion: &crate::ast::AssertDecl) -> String {
+pub(crate) fn normalize_assert(assertion: &crate::ast::AssertDecl) -> String {
     let mut alpha = AlphaState::default();
     let params = assertion
         .params
@@ -1519,7 +1722,7 @@ fn normalize_assert(assertion: &crate::ast::AssertDecl) -> String {
     format!("({params})=>{formula}")
 }
 
-fn normalize_defn(defn: &Defn) -> String {
+pub(crate) fn normalize_defn(exprs: &ExprArena, defn: &Defn) -> String {
     let mut alpha = AlphaState::default();
     let mut env = HashMap::new();
     let params = defn
@@ -1533,7 +1736,7 @@ fn normalize_defn(defn: &Defn) -> String {
         .collect::<Vec<_>>()
         .join(",");
     let ret = normalize_type(&defn.ret_type);
-    let body = normalize_expr(&defn.body, &mut env, &mut alpha);
+    let body = normalize_expr(exprs, defn.body, &mut env, &mut alpha);
     format!("({params})->{ret}:{body}")
 }
 
@@ -1547,7 +1750,7 @@ fn normalize_atom(atom: &Atom, alpha: &mut AlphaState) -> String {
     format!("{}({terms})", atom.pred)
 }
 
-fn normalize_logic_term(term: &LogicTerm, alpha: &mut AlphaState) -> String {
+pub(crate) fn normalize_logic_term(term: &LogicTerm, alpha: &mut AlphaState) -> String {
     match term {
         LogicTerm::Var(v) => alpha.name_for(v, "v"),
         LogicTerm::Symbol(s) => format!("'{}'", s),
@@ -1591,13 +1794,14 @@ fn collect_and_terms<'a>(items: &'a [Formula], out: &mut Vec<&'a Formula>) {
     }
 }
 
-fn normalize_type(ty: &Type) -> String {
+pub(crate) fn normalize_type(ty: &Type) -> String {
     match ty {
         Type::Bool => "Bool".to_string(),
         Type::Int => "Int".to_string(),
         Type::Symbol => "Symbol".to_string(),
         Type::Domain(n) => format!("Domain({n})"),
         Type::Adt(n) => format!("Adt({n})"),
+        Type::Error => "Error".to_string(),
         Type::Fun(args, ret) => format!(
             "(-> ({}) {})",
             args.iter()
@@ -1615,21 +1819,29 @@ fn normalize_type(ty: &Type) -> String {
     }
 }
 
+fn local_fn_key(name: &str) -> String {
+    format!("fn {name}")
+}
+
 fn normalize_expr(
-    expr: &Expr,
+    exprs: &ExprArena,
+    expr: ExprId,
     env: &mut HashMap<String, String>,
     alpha: &mut AlphaState,
 ) -> String {
-    match expr {
+    match &exprs[expr] {
         Expr::Var { name, .. } => env.get(name).cloned().unwrap_or_else(|| name.clone()),
         Expr::Symbol { value, .. } => format!("'{}'", value),
         Expr::Int { value, .. } => value.to_string(),
         Expr::Bool { value, .. } => value.to_string(),
+        Expr::Hole { .. } => "_?".to_string(),
         Expr::Call { name, args, .. } => format!(
             "({} {})",
-            name,
+            env.get(&local_fn_key(name))
+                .cloned()
+                .unwrap_or(name.clone()),
             args.iter()
-                .map(|a| normalize_expr(a, env, alpha))
+                .map(|a| normalize_expr(exprs, *a, env, alpha))
                 .collect::<Vec<_>>()
                 .join(" ")
         ),
@@ -1637,14 +1849,40 @@ fn normalize_expr(
             let mut local = env.clone();
             let mut rendered = Vec::new();
             for (name, bexpr, _) in bindings {
-                let b = normalize_expr(bexpr, &mut local, alpha);
+                let b = normalize_expr(exprs, *bexpr, &mut local, alpha);
                 let renamed = alpha.name_for(name, "l");
                 local.insert(name.clone(), renamed.clone());
                 rendered.push(format!("({renamed} {b})"));
             }
-            let body = normalize_expr(body, &mut local, alpha);
+            let body = normalize_expr(exprs, *body, &mut local, alpha);
             format!("(let ({}) {body})", rendered.join(" "))
         }
+        Expr::LetFn { fns, body, .. } => {
+            let mut local = env.clone();
+            let mut rendered = Vec::new();
+            for f in fns {
+                let mut fn_env = local.clone();
+                let params = f
+                    .params
+                    .iter()
+                    .map(|p| {
+                        let renamed = alpha.name_for(&p.name, "p");
+                        fn_env.insert(p.name.clone(), renamed.clone());
+                        format!("({renamed} {})", normalize_type(&p.ty))
+                    })
+                    .collect::<Vec<_>>();
+                let fn_body = normalize_expr(exprs, f.body, &mut fn_env, alpha);
+                let renamed = alpha.name_for(&local_fn_key(&f.name), "f");
+                local.insert(local_fn_key(&f.name), renamed.clone());
+                rendered.push(format!(
+                    "({renamed} ({}) {} {fn_body})",
+                    params.join(" "),
+                    normalize_type(&f.ret_type)
+                ));
+            }
+            let body = normalize_expr(exprs, *body, &mut local, alpha);
+            format!("(letfn ({}) {body})", rendered.join(" "))
+        }
         Expr::If {
             cond,
             then_branch,
@@ -1652,20 +1890,20 @@ fn normalize_expr(
             ..
         } => format!(
             "(if {} {} {})",
-            normalize_expr(cond, env, alpha),
-            normalize_expr(then_branch, env, alpha),
-            normalize_expr(else_branch, env, alpha)
+            normalize_expr(exprs, *cond, env, alpha),
+            normalize_expr(exprs, *then_branch, env, alpha),
+            normalize_expr(exprs, *else_branch, env, alpha)
         ),
         Expr::Match {
             scrutinee, arms, ..
         } => {
-            let scr = normalize_expr(scrutinee, env, alpha);
+            let scr = normalize_expr(exprs, *scrutinee, env, alpha);
             let rendered = arms
                 .iter()
                 .map(|arm| {
                     let mut arm_env = env.clone();
-                    let pat = normalize_pattern(&arm.pattern, &mut arm_env, alpha);
-                    let body = normalize_expr(&arm.body, &mut arm_env, alpha);
+                    let pat = normalize_pattern(exprs, arm.pattern, &mut arm_env, alpha);
+                    let body = normalize_expr(exprs, arm.body, &mut arm_env, alpha);
                     format!("({pat} {body})")
                 })
                 .collect::<Vec<_>>()
@@ -1676,11 +1914,12 @@ fn normalize_expr(
 }
 
 fn normalize_pattern(
-    pattern: &Pattern,
+    exprs: &ExprArena,
+    pattern: PatternId,
     env: &mut HashMap<String, String>,
     alpha: &mut AlphaState,
 ) -> String {
-    match pattern {
+    match &exprs[pattern] {
         Pattern::Wildcard { .. } => "_".to_string(),
         Pattern::Var { name, .. } => {
             let renamed = alpha.name_for(name, "m");
@@ -1693,7 +1932,7 @@ fn normalize_pattern(
         Pattern::Ctor { name, args, .. } => {
             let inner = args
                 .iter()
-                .map(|a| normalize_pattern(a, env, alpha))
+                .map(|a| normalize_pattern(exprs, *a, env, alpha))
                 .collect::<Vec<_>>()
                 .join(" ");
             format!("({name} {inner})")
@@ -1,20 +1,35 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+
+use serde::{Deserialize, Serialize};
 
 use crate::ast::{Program, Rule};
 use crate::diagnostics::Diagnostic;
 use crate::name_resolve::resolve_program;
 use crate::stratify::compute_strata;
+use crate::symbol::Symbol;
 use crate::types::{Atom, Formula, LogicTerm};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub const DISEQUALITY_PRED: &str = "!=";
+
+pub const PARALLEL_MIN_TUPLES: usize = 1024;
+
+pub fn disequality_holds(tuple: &[Value]) -> bool {
+    matches!(tuple, [lhs, rhs] if lhs != rhs)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Value {
-    Symbol(String),
+    Symbol(Symbol),
     Int(i64),
     Bool(bool),
-    Adt { ctor: String, fields: Vec<Value> },
+    Adt { ctor: Symbol, fields: Vec<Value> },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct GroundFact {
     pub pred: String,
     pub terms: Vec<Value>,
@@ -26,6 +41,7 @@ pub struct KnowledgeBase {
     pub facts: Vec<GroundFact>,
     pub rules: Vec<Rule>,
     pub strata: HashMap<String, usize>,
+    pub workers: NonZeroUsize,
 }
 
 impl KnowledgeBase {
@@ -35,7 +51,7 @@ impl KnowledgeBase {
             return Err(resolve_errors);
         }
 
-        let strata = compute_strata(program)?;
+        let strata = compute_strata(program)?.levels;
 
         let mut relation_schemas = HashMap::new();
         for rel in &program.relations {
@@ -66,9 +82,15 @@ impl KnowledgeBase {
             facts,
             rules: program.rules.clone(),
             strata,
+            workers: NonZeroUsize::MIN,
         })
     }
 
+    pub fn with_workers(mut self, workers: NonZeroUsize) -> Self {
+        self.workers = workers;
+        self
+    }
+
     pub fn with_extra_facts(&self, extra: Vec<GroundFact>) -> Self {
         let mut seen: HashSet<GroundFact> = self.facts.iter().cloned().collect();
         for f in extra {
@@ -79,6 +101,7 @@ impl KnowledgeBase {
             facts: seen.into_iter().collect(),
             rules: self.rules.clone(),
             strata: self.strata.clone(),
+            workers: self.workers,
         }
     }
 }
@@ -95,7 +118,7 @@ impl DerivedFacts {
         };
         let candidate: Vec<Value> = tuple
             .iter()
-            .map(|x| Value::Symbol((*x).to_string()))
+            .map(|x| Value::Symbol(Symbol::intern(x)))
             .collect();
         set.contains(&candidate)
     }
@@ -125,116 +148,485 @@ impl DerivedFacts {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DerivationStep {
+    pub stratum: usize,
+    pub iteration: usize,
+    pub rule: usize,
+    pub fact: String,
+    pub premises: Vec<String>,
+}
+
+impl fmt::Display for DerivationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "stratum {} iteration {} rule {}: {} <- [{}]",
+            self.stratum,
+            self.iteration,
+            self.rule,
+            self.fact,
+            self.premises.join(", ")
+        )
+    }
+}
+
 pub fn solve_facts(kb: &KnowledgeBase) -> Result<DerivedFacts, Vec<Diagnostic>> {
-    let mut db: HashMap<String, BTreeSet<Vec<Value>>> = HashMap::new();
-    for name in kb.relation_schemas.keys() {
-        db.insert(name.clone(), BTreeSet::new());
+    let mut iter = SolveFactsIter::new(kb, false)?;
+    while iter.advance()?.is_some() {}
+    Ok(iter.into_derived())
+}
+
+pub fn solve_facts_incremental(
+    kb: &KnowledgeBase,
+    base: &DerivedFacts,
+    extra: Vec<GroundFact>,
+) -> Result<DerivedFacts, Vec<Diagnostic>> {
+    let extended = kb.with_extra_facts(extra);
+    let mut iter = SolveFactsIter::new(&extended, false)?;
+    if !has_negation(&kb.rules) {
+        for (pred, tuples) in &base.facts {
+            iter.db
+                .entry(pred.clone())
+                .or_default()
+                .extend(tuples.iter().cloned());
+        }
     }
+    while iter.advance()?.is_some() {}
+    Ok(iter.into_derived())
+}
+
+fn has_negation(rules: &[Rule]) -> bool {
+    rules.iter().any(|rule| {
+        let mut positives = Vec::new();
+        let mut negatives = Vec::new();
+        flatten_formula(&rule.body, false, &mut positives, &mut negatives);
+        !negatives.is_empty()
+    })
+}
 
-    for fact in &kb.facts {
-        let Some(schema) = kb.relation_schemas.get(&fact.pred) else {
-            return Err(vec![Diagnostic::new(
-                "E-RESOLVE",
-                format!("undefined relation in fact: {}", fact.pred),
-                None,
-            )]);
+pub fn solve_facts_with_derivations(
+    kb: &KnowledgeBase,
+) -> Result<(DerivedFacts, Vec<DerivationStep>), Vec<Diagnostic>> {
+    let mut iter = SolveFactsIter::new(kb, true)?;
+    let mut steps = Vec::new();
+    while let Some((derived, premises)) = iter.advance()? {
+        steps.push(DerivationStep {
+            stratum: derived.stratum,
+            iteration: derived.iteration,
+            rule: derived.rule,
+            fact: fact_key(&derived.fact.pred, &derived.fact.terms),
+            premises,
+        });
+    }
+    steps.sort();
+    Ok((iter.into_derived(), steps))
+}
+
+pub fn solve_facts_iter(kb: &KnowledgeBase) -> Result<SolveFactsIter<'_>, Vec<Diagnostic>> {
+    SolveFactsIter::new(kb, false)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DerivedTuple {
+    pub stratum: usize,
+    pub iteration: usize,
+    pub rule: usize,
+    pub fact: GroundFact,
+}
+
+pub struct SolveFactsIter<'a> {
+    kb: &'a KnowledgeBase,
+    db: HashMap<String, BTreeSet<Vec<Value>>>,
+    strata: Vec<usize>,
+    stratum_index: usize,
+    rules: Vec<(usize, &'a Rule)>,
+    next_rule: usize,
+    iteration: usize,
+    changed: bool,
+    pending: VecDeque<(DerivedTuple, Vec<String>)>,
+    track_premises: bool,
+    finished: bool,
+}
+
+impl<'a> SolveFactsIter<'a> {
+    fn new(kb: &'a KnowledgeBase, track_premises: bool) -> Result<Self, Vec<Diagnostic>> {
+        let mut db: HashMap<String, BTreeSet<Vec<Value>>> = HashMap::new();
+        for name in kb.relation_schemas.keys() {
+            db.insert(name.clone(), BTreeSet::new());
+        }
+
+        for fact in &kb.facts {
+            let Some(schema) = kb.relation_schemas.get(&fact.pred) else {
+                return Err(vec![Diagnostic::new(
+                    "E-RESOLVE",
+                    format!("undefined relation in fact: {}", fact.pred),
+                    None,
+                )]);
+            };
+            if schema.len() != fact.terms.len() {
+                return Err(vec![Diagnostic::new(
+                    "E-RESOLVE",
+                    format!(
+                        "arity mismatch in fact {}: expected {}, got {}",
+                        fact.pred,
+                        schema.len(),
+                        fact.terms.len()
+                    ),
+                    None,
+                )]);
+            }
+            db.entry(fact.pred.clone())
+                .or_default()
+                .insert(fact.terms.clone());
+        }
+
+        let mut strata: Vec<usize> = kb.strata.values().copied().collect();
+        strata.sort_unstable();
+        strata.dedup();
+
+        let mut iter = Self {
+            kb,
+            db,
+            strata,
+            stratum_index: 0,
+            rules: Vec::new(),
+            next_rule: 0,
+            iteration: 0,
+            changed: false,
+            pending: VecDeque::new(),
+            track_premises,
+            finished: false,
         };
-        if schema.len() != fact.terms.len() {
-            return Err(vec![Diagnostic::new(
-                "E-RESOLVE",
-                format!(
-                    "arity mismatch in fact {}: expected {}, got {}",
-                    fact.pred,
-                    schema.len(),
-                    fact.terms.len()
-                ),
-                None,
-            )]);
+        iter.enter_stratum();
+        Ok(iter)
+    }
+
+    pub fn stratum_count(&self) -> usize {
+        self.strata.len()
+    }
+
+    pub fn derived(&self) -> DerivedFacts {
+        DerivedFacts {
+            facts: self.db.clone(),
         }
-        db.entry(fact.pred.clone())
-            .or_default()
-            .insert(fact.terms.clone());
     }
 
-    let mut strata_values: Vec<usize> = kb.strata.values().copied().collect();
-    strata_values.sort_unstable();
-    strata_values.dedup();
+    pub fn into_derived(self) -> DerivedFacts {
+        DerivedFacts { facts: self.db }
+    }
 
-    for stratum in strata_values {
-        let rules: Vec<&Rule> = kb
+    fn enter_stratum(&mut self) {
+        let Some(&stratum) = self.strata.get(self.stratum_index) else {
+            self.finished = true;
+            return;
+        };
+        let strata = &self.kb.strata;
+        self.rules = self
+            .kb
             .rules
             .iter()
-            .filter(|r| kb.strata.get(&r.head.pred).copied().unwrap_or(0) == stratum)
+            .enumerate()
+            .filter(|(_, r)| strata.get(&r.head.pred).copied().unwrap_or(0) == stratum)
             .collect();
+        self.next_rule = 0;
+        self.iteration = 1;
+        self.changed = false;
+    }
 
-        let mut changed = true;
-        while changed {
-            changed = false;
-            for rule in &rules {
-                let tuples = evaluate_rule(rule, &db)?;
-                let target = db.entry(rule.head.pred.clone()).or_default();
-                for tuple in tuples {
-                    if target.insert(tuple) {
-                        changed = true;
-                    }
+    fn advance(&mut self) -> Result<Option<(DerivedTuple, Vec<String>)>, Vec<Diagnostic>> {
+        loop {
+            if let Some(next) = self.pending.pop_front() {
+                return Ok(Some(next));
+            }
+            if self.finished {
+                return Ok(None);
+            }
+            let Some(&(index, rule)) = self.rules.get(self.next_rule) else {
+                if self.changed {
+                    self.next_rule = 0;
+                    self.iteration += 1;
+                    self.changed = false;
+                } else {
+                    self.stratum_index += 1;
+                    self.enter_stratum();
                 }
+                continue;
+            };
+            self.next_rule += 1;
+            let tuples = match evaluate_rule(rule, &self.db, self.kb.workers, self.track_premises) {
+                Ok(tuples) => tuples,
+                Err(diags) => {
+                    self.finished = true;
+                    return Err(diags);
+                }
+            };
+            let target = self.db.entry(rule.head.pred.clone()).or_default();
+            for (tuple, premises) in tuples {
+                if !target.insert(tuple.clone()) {
+                    continue;
+                }
+                self.changed = true;
+                self.pending.push_back((
+                    DerivedTuple {
+                        stratum: self.strata[self.stratum_index],
+                        iteration: self.iteration,
+                        rule: index,
+                        fact: GroundFact {
+                            pred: rule.head.pred.clone(),
+                            terms: tuple,
+                        },
+                    },
+                    premises,
+                ));
             }
         }
     }
+}
+
+impl Iterator for SolveFactsIter<'_> {
+    type Item = Result<DerivedTuple, Vec<Diagnostic>>;
 
-    Ok(DerivedFacts { facts: db })
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance()
+            .map(|next| next.map(|(derived, _)| derived))
+            .transpose()
+    }
 }
 
+impl std::iter::FusedIterator for SolveFactsIter<'_> {}
+
+// Head tuples produced by `rule`, each with its premises when `track` is
+// set (an empty list otherwise). When the relation of the first positive
+// atom is large and there are several workers, its tuples are split by
+// the hash of their first column and each part is joined on its own thread;
+// the parts are merged the same way a single thread merges assignments, so
+// the result does not depend on the number of workers.
 fn evaluate_rule(
     rule: &Rule,
     db: &HashMap<String, BTreeSet<Vec<Value>>>,
-) -> Result<BTreeSet<Vec<Value>>, Vec<Diagnostic>> {
-    let mut positives = Vec::new();
-    let mut negatives = Vec::new();
-    flatten_formula(&rule.body, false, &mut positives, &mut negatives);
-
-    let mut assignments: Vec<HashMap<String, Value>> = vec![HashMap::new()];
-
-    for atom in positives {
-        let mut next = Vec::new();
-        let tuples = db.get(&atom.pred).cloned().unwrap_or_default();
-        for assign in &assignments {
-            for tuple in &tuples {
-                if let Some(new_assign) = unify(atom, tuple, assign) {
-                    next.push(new_assign);
+    workers: NonZeroUsize,
+    track: bool,
+) -> Result<BTreeMap<Vec<Value>, Vec<String>>, Vec<Diagnostic>> {
+    let body = RuleBody::new(rule);
+    let workers = workers.get();
+    let first = body
+        .positives
+        .first()
+        .and_then(|atom| db.get(&atom.pred))
+        .filter(|tuples| workers > 1 && tuples.len() >= PARALLEL_MIN_TUPLES);
+    let Some(first) = first else {
+        return body.evaluate(rule, db, None, track);
+    };
+
+    let mut parts = vec![Vec::new(); workers];
+    for tuple in first {
+        parts[partition_of(tuple, workers)].push(tuple);
+    }
+    let results = std::thread::scope(|scope| {
+        let handles = parts
+            .iter()
+            .map(|part| scope.spawn(|| body.evaluate(rule, db, Some(part), track)))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("fixpoint worker panicked"))
+            .collect::<Vec<_>>()
+    });
+    let mut produced = BTreeMap::new();
+    for result in results {
+        for (tuple, premises) in result? {
+            keep_smallest_premises(&mut produced, tuple, premises);
+        }
+    }
+    Ok(produced)
+}
+
+fn partition_of(tuple: &[Value], workers: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    tuple.first().hash(&mut hasher);
+    (hasher.finish() % workers as u64) as usize
+}
+
+fn keep_smallest_premises(
+    produced: &mut BTreeMap<Vec<Value>, Vec<String>>,
+    tuple: Vec<Value>,
+    premises: Vec<String>,
+) {
+    match produced.get_mut(&tuple) {
+        Some(kept) if premises < *kept => *kept = premises,
+        Some(_) => {}
+        None => {
+            produced.insert(tuple, premises);
+        }
+    }
+}
+
+struct RuleBody<'a> {
+    positives: Vec<&'a Atom>,
+    distinct: Vec<&'a Atom>,
+    equal: Vec<&'a Atom>,
+    negatives: Vec<&'a Atom>,
+}
+
+impl<'a> RuleBody<'a> {
+    fn new(rule: &'a Rule) -> Self {
+        let mut positives = Vec::new();
+        let mut negatives = Vec::new();
+        flatten_formula(&rule.body, false, &mut positives, &mut negatives);
+        let (distinct, positives) = positives
+            .into_iter()
+            .partition(|atom| atom.pred == DISEQUALITY_PRED);
+        let (equal, negatives) = negatives
+            .into_iter()
+            .partition(|atom| atom.pred == DISEQUALITY_PRED);
+        Self {
+            positives,
+            distinct,
+            equal,
+            negatives,
+        }
+    }
+
+    fn evaluate(
+        &self,
+        rule: &Rule,
+        db: &HashMap<String, BTreeSet<Vec<Value>>>,
+        first: Option<&[&Vec<Value>]>,
+        track: bool,
+    ) -> Result<BTreeMap<Vec<Value>, Vec<String>>, Vec<Diagnostic>> {
+        let mut assignments: Vec<(HashMap<String, Value>, Vec<String>)> =
+            vec![(HashMap::new(), Vec::new())];
+
+        for (position, atom) in self.positives.iter().enumerate() {
+            let tuples: Vec<&Vec<Value>> = match first {
+                Some(part) if position == 0 => part.to_vec(),
+                _ => db.get(&atom.pred).into_iter().flatten().collect(),
+            };
+            let mut next = Vec::new();
+            for (assign, premises) in &assignments {
+                for tuple in &tuples {
+                    if let Some(new_assign) = unify(atom, tuple, assign) {
+                        let mut premises = premises.clone();
+                        if track {
+                            premises.push(fact_key(&atom.pred, tuple));
+                        }
+                        next.push((new_assign, premises));
+                    }
                 }
             }
+            assignments = next;
+            if assignments.is_empty() {
+                break;
+            }
         }
-        assignments = next;
-        if assignments.is_empty() {
-            break;
+
+        for (atom, expected) in self
+            .distinct
+            .iter()
+            .map(|atom| (atom, true))
+            .chain(self.equal.iter().map(|atom| (atom, false)))
+        {
+            assignments.retain(|(assign, _)| {
+                instantiate_terms(&atom.terms, assign)
+                    .is_ok_and(|tuple| disequality_holds(&tuple) == expected)
+            });
         }
+
+        for atom in &self.negatives {
+            let tuples = db.get(&atom.pred);
+            assignments.retain(|(assign, _)| {
+                let instantiated = instantiate_terms(&atom.terms, assign);
+                let Ok(instantiated) = instantiated else {
+                    return false;
+                };
+                !tuples.is_some_and(|tuples| tuples.contains(&instantiated))
+            });
+        }
+
+        let head_vars = rule
+            .skolem
+            .map(|_| head_vars(&rule.head))
+            .unwrap_or_default();
+        let mut produced = BTreeMap::<Vec<Value>, Vec<String>>::new();
+        for (mut assign, mut premises) in assignments {
+            if rule.skolem.is_some() {
+                bind_skolems(&rule.head.pred, &head_vars, &mut assign);
+            }
+            let tuple = instantiate_terms(&rule.head.terms, &assign).map_err(|e| {
+                vec![Diagnostic::new(
+                    "E-RESOLVE",
+                    format!("unbound head variable: {e}"),
+                    Some(rule.span.clone()),
+                )]
+            })?;
+            // The depth bound is what makes skolem rules terminate.
+            if rule
+                .skolem
+                .is_some_and(|depth| tuple.iter().any(|value| skolem_depth(value) > depth))
+            {
+                continue;
+            }
+            premises.sort();
+            premises.dedup();
+            keep_smallest_premises(&mut produced, tuple, premises);
+        }
+
+        Ok(produced)
     }
+}
 
-    for atom in negatives {
-        let tuples = db.get(&atom.pred).cloned().unwrap_or_default();
-        assignments.retain(|assign| {
-            let instantiated = instantiate_terms(&atom.terms, assign);
-            let Ok(instantiated) = instantiated else {
-                return false;
-            };
-            !tuples.contains(&instantiated)
-        });
+pub const SKOLEM_CTOR_PREFIX: &str = "sk:";
+
+fn head_vars(head: &Atom) -> Vec<String> {
+    fn collect(term: &LogicTerm, out: &mut Vec<String>) {
+        match term {
+            LogicTerm::Var(v) if !out.contains(v) => out.push(v.clone()),
+            LogicTerm::Ctor { args, .. } => args.iter().for_each(|arg| collect(arg, out)),
+            _ => {}
+        }
     }
+    let mut out = Vec::new();
+    head.terms.iter().for_each(|term| collect(term, &mut out));
+    out
+}
 
-    let mut produced = BTreeSet::new();
-    for assign in &assignments {
-        let tuple = instantiate_terms(&rule.head.terms, assign).map_err(|e| {
-            vec![Diagnostic::new(
-                "E-RESOLVE",
-                format!("unbound head variable: {e}"),
-                Some(rule.span.clone()),
-            )]
-        })?;
-        produced.insert(tuple);
+fn bind_skolems(pred: &str, head_vars: &[String], assign: &mut HashMap<String, Value>) {
+    let bound = head_vars
+        .iter()
+        .filter_map(|v| assign.get(v).cloned())
+        .collect::<Vec<_>>();
+    for v in head_vars {
+        if !assign.contains_key(v) {
+            let ctor = format!("{SKOLEM_CTOR_PREFIX}{pred}:{v}");
+            assign.insert(
+                v.clone(),
+                Value::Adt {
+                    ctor: Symbol::intern(&ctor),
+                    fields: bound.clone(),
+                },
+            );
+        }
     }
+}
 
-    Ok(produced)
+fn skolem_depth(value: &Value) -> usize {
+    match value {
+        Value::Adt { ctor, fields } => {
+            let inner = fields.iter().map(skolem_depth).max().unwrap_or(0);
+            inner + usize::from(ctor.starts_with(SKOLEM_CTOR_PREFIX))
+        }
+        Value::Symbol(_) | Value::Int(_) | Value::Bool(_) => 0,
+    }
+}
+
+pub(crate) fn fact_key(pred: &str, tuple: &[Value]) -> String {
+    let args = tuple
+        .iter()
+        .map(value_to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{pred}({args})")
 }
 
 fn unify(
@@ -271,7 +663,7 @@ fn unify_term(term: &LogicTerm, val: &Value, env: &mut HashMap<String, Value>) -
             let Value::Adt { ctor, fields } = val else {
                 return false;
             };
-            if name != ctor || args.len() != fields.len() {
+            if *ctor != *name || args.len() != fields.len() {
                 return false;
             }
             for (arg, field) in args.iter().zip(fields.iter()) {
@@ -297,7 +689,7 @@ fn instantiate_terms(
                 };
                 out.push(val.clone());
             }
-            LogicTerm::Symbol(s) => out.push(Value::Symbol(s.clone())),
+            LogicTerm::Symbol(s) => out.push(Value::Symbol(Symbol::intern(s))),
             LogicTerm::Int(i) => out.push(Value::Int(*i)),
             LogicTerm::Bool(b) => out.push(Value::Bool(*b)),
             LogicTerm::Ctor { name, args } => {
@@ -307,7 +699,7 @@ fn instantiate_terms(
                     fields.push(inner.remove(0));
                 }
                 out.push(Value::Adt {
-                    ctor: name.clone(),
+                    ctor: Symbol::intern(name),
                     fields,
                 });
             }
@@ -319,7 +711,7 @@ fn instantiate_terms(
 fn term_to_const_value(term: &LogicTerm) -> Option<Value> {
     match term {
         LogicTerm::Var(_) => None,
-        LogicTerm::Symbol(s) => Some(Value::Symbol(s.clone())),
+        LogicTerm::Symbol(s) => Some(Value::Symbol(Symbol::intern(s))),
         LogicTerm::Int(i) => Some(Value::Int(*i)),
         LogicTerm::Bool(b) => Some(Value::Bool(*b)),
         LogicTerm::Ctor { name, args } => {
@@ -328,14 +720,14 @@ fn term_to_const_value(term: &LogicTerm) -> Option<Value> {
                 fields.push(term_to_const_value(arg)?);
             }
             Some(Value::Adt {
-                ctor: name.clone(),
+                ctor: Symbol::intern(name),
                 fields,
             })
         }
     }
 }
 
-fn flatten_formula<'a>(
+pub(crate) fn flatten_formula<'a>(
     formula: &'a Formula,
     negated: bool,
     pos: &mut Vec<&'a Atom>,
@@ -359,9 +751,9 @@ fn flatten_formula<'a>(
     }
 }
 
-fn value_to_string(v: &Value) -> String {
+pub fn value_to_string(v: &Value) -> String {
     match v {
-        Value::Symbol(s) => s.clone(),
+        Value::Symbol(s) => s.to_string(),
         Value::Int(i) => i.to_string(),
         Value::Bool(b) => b.to_string(),
         Value::Adt { ctor, fields } => {
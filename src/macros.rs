@@ -0,0 +1,561 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::{Diagnostic, Span};
+use crate::edition::EditionFeature;
+use crate::grammar;
+use crate::parser::{SExpr, Source, make_span, quote_atom, read_sexprs, require_feature};
+
+const ELLIPSIS: &str = "...";
+
+const MAX_EXPANSION_DEPTH: usize = 64;
+const MAX_EXPANSION_STEPS: usize = 10_000;
+
+const RESERVED_NAMES: &[&str] = &[
+    "let", "letfn", "if", "match", "and", "not", "true", "false", "Refine", "Adt", "Bool", "Int",
+    "Symbol", "->", "_", ELLIPSIS,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpansionStep {
+    pub macro_name: String,
+    pub span: Span,
+    pub call: String,
+    pub expansion: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MacroExpansion {
+    pub steps: Vec<ExpansionStep>,
+    pub forms: Vec<String>,
+}
+
+pub fn expand_macros(src: &str) -> Result<MacroExpansion, Vec<Diagnostic>> {
+    let src = &Source::new(src);
+    let sexprs = read_sexprs(src)?;
+    let mut steps = Vec::new();
+    let (forms, errors) = expand_forms(src, sexprs, Some(&mut steps));
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(MacroExpansion {
+        steps,
+        forms: forms.iter().map(render_sexpr).collect(),
+    })
+}
+
+pub(crate) fn expand_forms(
+    src: &Source<'_>,
+    forms: Vec<SExpr>,
+    trace: Option<&mut Vec<ExpansionStep>>,
+) -> (Vec<SExpr>, Vec<Diagnostic>) {
+    if !forms.iter().any(is_macro_definition) {
+        return (forms, Vec::new());
+    }
+
+    let mut errors = Vec::new();
+    let mut macros: HashMap<String, MacroDef> = HashMap::new();
+    let mut rest = Vec::new();
+    for form in forms {
+        if !is_macro_definition(&form) {
+            rest.push(form);
+            continue;
+        }
+        match parse_defmacro(src, &form) {
+            Ok(definition) => {
+                if let Some(first) = macros.get(&definition.name) {
+                    let (start, end) = form.span_bounds();
+                    errors.push(
+                        Diagnostic::new(
+                            "E-RESOLVE",
+                            format!("duplicate macro: {}", definition.name),
+                            Some(make_span(src, start, end)),
+                        )
+                        .with_related(
+                            make_span(src, first.span.0, first.span.1),
+                            "first defined here",
+                        ),
+                    );
+                } else {
+                    macros.insert(definition.name.clone(), definition);
+                }
+            }
+            Err(diag) => errors.push(diag),
+        }
+    }
+
+    let mut expander = Expander {
+        src,
+        macros: &macros,
+        steps: 0,
+        trace,
+    };
+    let mut out = Vec::new();
+    for form in rest {
+        match expander.expand_items(std::slice::from_ref(&form), true, 0) {
+            Ok(expanded) => out.extend(expanded),
+            Err(diag) => errors.push(diag),
+        }
+    }
+    (out, errors)
+}
+
+fn is_macro_definition(form: &SExpr) -> bool {
+    matches!(form, SExpr::List(items, _, _)
+        if items.first().and_then(SExpr::as_atom).is_some_and(is_defmacro_head))
+}
+
+fn is_defmacro_head(head: &str) -> bool {
+    grammar::surface_form(head).is_some_and(|form| form.head == "defmacro")
+}
+
+struct MacroDef {
+    name: String,
+    params: Vec<String>,
+    rest: Option<String>,
+    templates: Vec<SExpr>,
+    binders: HashSet<String>,
+    span: (usize, usize),
+}
+
+fn parse_defmacro(src: &Source<'_>, form: &SExpr) -> Result<MacroDef, Diagnostic> {
+    let (start, end) = form.span_bounds();
+    let error = |message: String, (s, e): (usize, usize)| {
+        Diagnostic::new("E-PARSE", message, Some(make_span(src, s, e)))
+    };
+    let SExpr::List(items, _, _) = form else {
+        unreachable!("macro definitions are lists");
+    };
+    require_feature(src, EditionFeature::Macros, &items[0])?;
+    let (Some(name_node), Some(SExpr::List(param_nodes, _, _)), true) =
+        (items.get(1), items.get(2), items.len() > 3)
+    else {
+        return Err(error(
+            "defmacro expects a name, a parameter list and a template".to_string(),
+            (start, end),
+        ));
+    };
+
+    let name = bare_name(name_node).ok_or_else(|| {
+        error(
+            "macro name must be a bare name".to_string(),
+            name_node.span_bounds(),
+        )
+    })?;
+    if RESERVED_NAMES.contains(&name) || grammar::surface_form(name).is_some() {
+        return Err(error(
+            format!("macro name is reserved: {name}"),
+            name_node.span_bounds(),
+        ));
+    }
+
+    let mut params = Vec::new();
+    let mut rest = None;
+    for (idx, node) in param_nodes.iter().enumerate() {
+        let param = bare_name(node).ok_or_else(|| {
+            error(
+                "macro parameter must be a bare name".to_string(),
+                node.span_bounds(),
+            )
+        })?;
+        if param == ELLIPSIS {
+            return Err(error(
+                "'...' must follow the last macro parameter".to_string(),
+                node.span_bounds(),
+            ));
+        }
+        if params.iter().any(|p| p == param) {
+            return Err(error(
+                format!("duplicate macro parameter: {param}"),
+                node.span_bounds(),
+            ));
+        }
+        match param_nodes.get(idx + 1).and_then(SExpr::as_atom) {
+            Some(ELLIPSIS) if idx + 2 == param_nodes.len() => {
+                rest = Some(param.to_string());
+                break;
+            }
+            Some(ELLIPSIS) => {
+                return Err(error(
+                    "'...' must follow the last macro parameter".to_string(),
+                    param_nodes[idx + 1].span_bounds(),
+                ));
+            }
+            _ => params.push(param.to_string()),
+        }
+    }
+
+    let templates = items[3..].to_vec();
+    let mut binders = HashSet::new();
+    for template in &templates {
+        collect_binders(template, &mut binders);
+    }
+    for param in params.iter().chain(&rest) {
+        binders.remove(param);
+    }
+    Ok(MacroDef {
+        name: name.to_string(),
+        params,
+        rest,
+        templates,
+        binders,
+        span: (start, end),
+    })
+}
+
+fn bare_name(node: &SExpr) -> Option<&str> {
+    node.as_atom()
+        .filter(|atom| !atom.starts_with('"') && !atom.starts_with(':'))
+}
+
+fn collect_binders(node: &SExpr, out: &mut HashSet<String>) {
+    let items = match node {
+        SExpr::Atom(atom, _, _) => {
+            if atom.len() > 1 && atom.starts_with('?') {
+                out.insert(atom.clone());
+            }
+            return;
+        }
+        SExpr::List(items, _, _) => items,
+    };
+    match items.first().and_then(SExpr::as_atom) {
+        Some("let") => {
+            for binding in list_items(items.get(1)) {
+                collect_first_atom(binding, out);
+            }
+        }
+        Some("letfn") => {
+            for local in list_items(items.get(1)) {
+                collect_first_atom(local, out);
+                if let SExpr::List(parts, _, _) = local {
+                    collect_params(parts.get(1), out);
+                }
+            }
+        }
+        Some("match") => {
+            for arm in items.iter().skip(2) {
+                if let SExpr::List(parts, _, _) = arm {
+                    collect_pattern(parts.first(), out);
+                }
+            }
+        }
+        Some(head)
+            if grammar::surface_form(head)
+                .is_some_and(|form| matches!(form.head, "assert" | "defn")) =>
+        {
+            collect_params(items.get(2), out);
+            for (idx, item) in items.iter().enumerate() {
+                match item.as_atom() {
+                    Some(":引数" | ":params") => collect_params(items.get(idx + 1), out),
+                    Some(":節" | ":clauses") => {
+                        for clause in list_items(items.get(idx + 1)) {
+                            if let SExpr::List(parts, _, _) = clause {
+                                for pattern in list_items(parts.first()) {
+                                    collect_pattern(Some(pattern), out);
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+    for item in items {
+        collect_binders(item, out);
+    }
+}
+
+fn list_items(node: Option<&SExpr>) -> &[SExpr] {
+    match node {
+        Some(SExpr::List(items, _, _)) => items,
+        _ => &[],
+    }
+}
+
+fn collect_first_atom(node: &SExpr, out: &mut HashSet<String>) {
+    if let Some(name) = list_items(Some(node)).first().and_then(bare_name) {
+        out.insert(name.to_string());
+    }
+}
+
+fn collect_params(node: Option<&SExpr>, out: &mut HashSet<String>) {
+    for param in list_items(node) {
+        collect_first_atom(param, out);
+    }
+}
+
+fn collect_pattern(node: Option<&SExpr>, out: &mut HashSet<String>) {
+    match node {
+        Some(SExpr::Atom(atom, _, _))
+            if !matches!(atom.as_str(), "_" | "true" | "false")
+                && atom.parse::<i64>().is_err()
+                && !atom.starts_with(['"', ':']) =>
+        {
+            out.insert(atom.clone());
+        }
+        Some(SExpr::List(items, _, _)) => {
+            for item in items.iter().skip(1) {
+                collect_pattern(Some(item), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+struct Expander<'a, 'src> {
+    src: &'a Source<'src>,
+    macros: &'a HashMap<String, MacroDef>,
+    steps: usize,
+    trace: Option<&'a mut Vec<ExpansionStep>>,
+}
+
+impl Expander<'_, '_> {
+    fn expand_items(
+        &mut self,
+        items: &[SExpr],
+        splice: bool,
+        depth: usize,
+    ) -> Result<Vec<SExpr>, Diagnostic> {
+        let mut out = Vec::new();
+        for item in items {
+            let SExpr::List(children, start, end) = item else {
+                out.push(item.clone());
+                continue;
+            };
+            let head = children.first().and_then(SExpr::as_atom);
+            if let Some(definition) = head.and_then(|head| self.macros.get(head)) {
+                if depth >= MAX_EXPANSION_DEPTH {
+                    return Err(self.error(
+                        format!(
+                            "expansion of macro {} nests deeper than {MAX_EXPANSION_DEPTH} levels",
+                            definition.name
+                        ),
+                        (*start, *end),
+                    ));
+                }
+                let expansion = self.expand_call(definition, &children[1..], (*start, *end))?;
+                if !splice && expansion.len() != 1 {
+                    return Err(self.error(
+                        format!(
+                            "macro {} expands to {} forms where one is expected",
+                            definition.name,
+                            expansion.len()
+                        ),
+                        (*start, *end),
+                    ));
+                }
+                out.extend(self.expand_items(&expansion, splice, depth + 1)?);
+                continue;
+            }
+            if head.is_some_and(is_defmacro_head) {
+                let message = if depth == 0 {
+                    "defmacro is only allowed at the top level"
+                } else {
+                    "a macro expansion cannot define a macro"
+                };
+                return Err(self.error(message.to_string(), (*start, *end)));
+            }
+            let profiled = splice
+                && head.is_some_and(|head| {
+                    grammar::surface_form(head).is_some_and(|form| form.head == "when-profile")
+                });
+            let split = if profiled {
+                children.len().min(2)
+            } else {
+                children.len()
+            };
+            let mut expanded = self.expand_items(&children[..split], false, depth)?;
+            expanded.extend(self.expand_items(&children[split..], true, depth)?);
+            out.push(SExpr::List(expanded, *start, *end));
+        }
+        Ok(out)
+    }
+
+    fn expand_call(
+        &mut self,
+        definition: &MacroDef,
+        args: &[SExpr],
+        call: (usize, usize),
+    ) -> Result<Vec<SExpr>, Diagnostic> {
+        let arity = definition.params.len();
+        let arity_ok = match definition.rest {
+            Some(_) => args.len() >= arity,
+            None => args.len() == arity,
+        };
+        if !arity_ok {
+            let expected = match definition.rest {
+                Some(_) => format!("at least {arity}"),
+                None => arity.to_string(),
+            };
+            return Err(self.error(
+                format!(
+                    "macro {} expects {expected} arguments, got {}",
+                    definition.name,
+                    args.len()
+                ),
+                call,
+            ));
+        }
+        self.steps += 1;
+        if self.steps > MAX_EXPANSION_STEPS {
+            return Err(self.error(
+                format!("macro expansion exceeds {MAX_EXPANSION_STEPS} steps"),
+                call,
+            ));
+        }
+
+        let mut used = HashSet::new();
+        for arg in args {
+            collect_atoms(arg, &mut used);
+        }
+        let renames = definition
+            .binders
+            .iter()
+            .filter(|binder| used.contains(*binder))
+            .map(|binder| (binder.clone(), format!("{binder}%{}", self.steps)))
+            .collect::<HashMap<_, _>>();
+        let template = Template {
+            definition,
+            fixed: definition
+                .params
+                .iter()
+                .map(String::as_str)
+                .zip(args)
+                .collect(),
+            rest: &args[arity..],
+            renames,
+            call,
+        };
+        let expansion = template
+            .instantiate(&definition.templates, None)
+            .map_err(|(message, span)| self.error(message, span))?;
+
+        if let Some(trace) = self.trace.as_deref_mut() {
+            let mut call_items = vec![SExpr::Atom(definition.name.clone(), call.0, call.1)];
+            call_items.extend(args.iter().cloned());
+            trace.push(ExpansionStep {
+                macro_name: definition.name.clone(),
+                span: make_span(self.src, call.0, call.1),
+                call: render_sexpr(&SExpr::List(call_items, call.0, call.1)),
+                expansion: expansion.iter().map(render_sexpr).collect(),
+            });
+        }
+        Ok(expansion)
+    }
+
+    fn error(&self, message: String, (start, end): (usize, usize)) -> Diagnostic {
+        Diagnostic::new("E-PARSE", message, Some(make_span(self.src, start, end)))
+    }
+}
+
+fn collect_atoms(node: &SExpr, out: &mut HashSet<String>) {
+    match node {
+        SExpr::Atom(atom, _, _) => {
+            out.insert(atom.clone());
+        }
+        SExpr::List(items, _, _) => {
+            for item in items {
+                collect_atoms(item, out);
+            }
+        }
+    }
+}
+
+struct Template<'a> {
+    definition: &'a MacroDef,
+    fixed: HashMap<&'a str, &'a SExpr>,
+    rest: &'a [SExpr],
+    renames: HashMap<String, String>,
+    call: (usize, usize),
+}
+
+type TemplateError = (String, (usize, usize));
+
+impl Template<'_> {
+    fn instantiate(
+        &self,
+        templates: &[SExpr],
+        current: Option<&SExpr>,
+    ) -> Result<Vec<SExpr>, TemplateError> {
+        let mut out = Vec::new();
+        let mut idx = 0;
+        while idx < templates.len() {
+            let template = &templates[idx];
+            if template.as_atom() == Some(ELLIPSIS) {
+                return Err((
+                    "'...' in a macro template must follow a template".to_string(),
+                    template.span_bounds(),
+                ));
+            }
+            if templates.get(idx + 1).and_then(SExpr::as_atom) == Some(ELLIPSIS) {
+                let repeats = self.definition.rest.as_deref().is_some_and(|rest| {
+                    let mut atoms = HashSet::new();
+                    collect_atoms(template, &mut atoms);
+                    atoms.contains(rest)
+                });
+                if !repeats {
+                    return Err((
+                        "'...' in a macro template must follow a template using the rest parameter"
+                            .to_string(),
+                        templates[idx + 1].span_bounds(),
+                    ));
+                }
+                for arg in self.rest {
+                    out.push(self.substitute(template, Some(arg))?);
+                }
+                idx += 2;
+                continue;
+            }
+            out.push(self.substitute(template, current)?);
+            idx += 1;
+        }
+        Ok(out)
+    }
+
+    fn substitute(
+        &self,
+        template: &SExpr,
+        current: Option<&SExpr>,
+    ) -> Result<SExpr, TemplateError> {
+        let (start, end) = self.call;
+        match template {
+            SExpr::Atom(atom, s, e) => {
+                if let Some(arg) = self.fixed.get(atom.as_str()) {
+                    return Ok((*arg).clone());
+                }
+                if self.definition.rest.as_deref() == Some(atom.as_str()) {
+                    return current.cloned().ok_or_else(|| {
+                        (
+                            format!("macro parameter {atom} must be followed by '...'"),
+                            (*s, *e),
+                        )
+                    });
+                }
+                let text = self.renames.get(atom).unwrap_or(atom);
+                Ok(SExpr::Atom(text.clone(), start, end))
+            }
+            SExpr::List(items, _, _) => {
+                Ok(SExpr::List(self.instantiate(items, current)?, start, end))
+            }
+        }
+    }
+}
+
+pub(crate) fn render_sexpr(node: &SExpr) -> String {
+    match node {
+        SExpr::Atom(atom, _, _) => {
+            match atom
+                .strip_prefix('"')
+                .and_then(|inner| inner.strip_suffix('"'))
+            {
+                Some(inner) => quote_atom(inner),
+                None => atom.clone(),
+            }
+        }
+        SExpr::List(items, _, _) => format!(
+            "({})",
+            items.iter().map(render_sexpr).collect::<Vec<_>>().join(" ")
+        ),
+    }
+}
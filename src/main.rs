@@ -1,16 +1,33 @@
 use std::fs;
+use std::io::IsTerminal;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::process::Command as ProcessCommand;
+use std::time::{Duration, Instant};
 use std::{collections::HashSet, fmt::Write};
 
-use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap::{ArgAction, ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use dtl::grammar;
+use dtl::name_resolve::{normalize_program_aliases, resolve_program};
 use dtl::{
-    Diagnostic, DocBundleFormat, DocBundleOptions, FormatOptions, LintDiagnostic, LintOptions,
-    Program, ProofTrace, Span, check_program, format_source, generate_doc_bundle_with_options,
-    has_failed_obligation, has_full_claim_coverage, lint_program, parse_program_with_source,
-    prove_program, prove_program_reference, write_proof_trace,
+    ChangeKind, ClaimPolicy, Diagnostic, DocBundleFormat, DocBundleOptions, DocSelfDescription,
+    DupLimits, DupSampling, EvalLimits, EvalTrace, Evaluation, ExpansionStep, Explorer, FactCache,
+    FailureClass, FixpointOptions, FormatOptions, LintDiagnostic, LintOptions, ObligationTrace,
+    ParseOptions, PhaseStats, PhaseTimings, Program, ProjectConfig, ProofSummary, ProofTrace,
+    ProofTraceWriter, SliceTarget, Span, StepKind, StratumPlan, SurfaceKeywordMap, Symptom,
+    check_program, check_program_with_timings, collect_tags, compute_strata, derivation_steps,
+    diff_programs, diff_self_descriptions, evaluate, evaluate_traced, expand_macros,
+    failure_exit_code, find_project_config, find_references, format_source_with_keywords,
+    format_warnings, generate_doc_bundle_with_options, generate_jsonschema, generate_rust,
+    generate_sql, generate_typescript, has_failed_obligation, leaves_core_comments_unformatted,
+    lint_program, load_project_config, minimize_program, obligation_span,
+    parse_program_with_options, partially_evaluate, plan_program, proof_trace_filename,
+    prove_program_reference, prove_program_streaming, read_proof_trace, reference_derivation_steps,
+    render_ctags, render_program, run_entry_point, slice_program, solve_facts, surface_keywords,
+    write_proof_trace,
 };
 use serde::Serialize;
+use tracing_subscriber::EnvFilter;
 
 mod selfdoc;
 
@@ -20,25 +37,77 @@ mod selfdoc;
 struct Cli {
     #[command(subcommand)]
     command: Command,
+    /// Log progress to stderr (-v: phases and obligations, -vv: phase timings).
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    /// Disable progress logging; takes precedence over -v and `DTL_LOG`.
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Threads for evaluating rules over large relations in the fixpoint
+    /// (default: available parallelism). Results do not depend on it.
+    #[arg(short, long, global = true)]
+    jobs: Option<NonZeroUsize>,
+    /// Root for relative `import` paths, searched after the importing file's
+    /// directory and before `DTL_PATH` (repeatable).
+    #[arg(short = 'I', long = "include", global = true, value_name = "DIR")]
+    include: Vec<PathBuf>,
+    /// Load the facts, rules and asserts gated on this profile with
+    /// `when-profile` or `:profile` (repeatable).
+    #[arg(long = "profile", global = true, value_name = "NAME")]
+    profiles: Vec<String>,
 }
 
 #[derive(Debug, Subcommand)]
 enum Command {
     Check {
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+        #[arg(long, default_value_t = false)]
+        timings: bool,
+        #[arg(long)]
+        max_errors: Option<NonZeroUsize>,
+        /// Reuse derived facts from `.dtl-cache/` at the project root.
+        #[arg(long, default_value_t = false)]
+        cache: bool,
+    },
+    Strata {
         #[arg(required = true, num_args = 1..)]
         files: Vec<PathBuf>,
         #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
         format: OutputFormat,
     },
-    Prove {
+    Plan {
         #[arg(required = true, num_args = 1..)]
         files: Vec<PathBuf>,
         #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
         format: OutputFormat,
+    },
+    Prove {
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
         #[arg(long, value_enum, default_value_t = ProveEngine::Native)]
         engine: ProveEngine,
         #[arg(long)]
         out: Option<PathBuf>,
+        #[arg(long, default_value_t = false)]
+        timings: bool,
+        /// Write `proof-trace.json.zst` (zstd) instead of `proof-trace.json`.
+        #[arg(long, default_value_t = false, requires = "out")]
+        compress_trace: bool,
+        /// Include the rule applications of the fixpoint in the trace.
+        #[arg(long, default_value_t = false)]
+        derivations: bool,
+        /// Reuse derived facts from `.dtl-cache/` at the project root.
+        #[arg(long, default_value_t = false)]
+        cache: bool,
+        /// Fold constant expressions and instantiate rules over singleton
+        /// universes before proving.
+        #[arg(long, default_value_t = false)]
+        partial_eval: bool,
     },
     Doc {
         #[arg(required = true, num_args = 1..)]
@@ -51,16 +120,54 @@ enum Command {
         engine: ProveEngine,
         #[arg(long, default_value_t = false)]
         pdf: bool,
+        #[arg(long, default_value_t = false)]
+        timings: bool,
+        /// Write the bundle with a failed-obligation section even when proofs fail.
+        #[arg(long, default_value_t = false)]
+        allow_failed: bool,
+        /// proof-trace.json of an earlier run to list the obligation changes against.
+        #[arg(long)]
+        previous_trace: Option<PathBuf>,
+        /// Omit run-dependent values so identical inputs give identical bundles.
+        #[arg(long, default_value_t = false)]
+        deterministic: bool,
+        /// Write `proof-trace.json.zst` (zstd) instead of `proof-trace.json`.
+        #[arg(long, default_value_t = false)]
+        compress_trace: bool,
+        /// Fold constant expressions and instantiate rules over singleton
+        /// universes before proving.
+        #[arg(long, default_value_t = false)]
+        partial_eval: bool,
     },
     Lint {
         #[arg(required = true, num_args = 1..)]
         files: Vec<PathBuf>,
-        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
-        format: OutputFormat,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
         #[arg(long, default_value_t = false)]
         deny_warnings: bool,
         #[arg(long, default_value_t = false)]
         semantic_dup: bool,
+        #[arg(long, value_name = "N", requires = "semantic_dup")]
+        dup_sample: Option<usize>,
+        #[arg(
+            long,
+            value_name = "SEED",
+            default_value_t = 0,
+            requires = "dup_sample"
+        )]
+        dup_seed: u64,
+        #[arg(long, value_name = "N", requires = "semantic_dup")]
+        dup_depth_limit: Option<usize>,
+        #[arg(long, value_name = "N", requires = "semantic_dup")]
+        dup_max_model_points: Option<usize>,
+        #[arg(long, value_name = "N", requires = "semantic_dup")]
+        dup_max_function_values: Option<usize>,
+        #[arg(long, default_value_t = false)]
+        intra_file_only: bool,
+        /// Reuse derived facts from `.dtl-cache/` at the project root.
+        #[arg(long, default_value_t = false)]
+        cache: bool,
     },
     Fmt {
         #[arg(required = true, num_args = 1..)]
@@ -71,8 +178,9 @@ enum Command {
         stdout: bool,
     },
     Selfdoc {
-        #[arg(long, default_value = ".")]
-        repo: PathBuf,
+        /// Repository to describe; repeat to merge several into one model.
+        #[arg(long = "repo", default_value = ".")]
+        repos: Vec<PathBuf>,
         #[arg(long)]
         config: Option<PathBuf>,
         #[arg(long)]
@@ -83,22 +191,145 @@ enum Command {
         engine: ProveEngine,
         #[arg(long, default_value_t = false)]
         pdf: bool,
+        /// Omit run-dependent values so identical inputs give identical bundles.
+        #[arg(long, default_value_t = false)]
+        deterministic: bool,
     },
     Selfcheck {
-        #[arg(long, default_value = ".")]
-        repo: PathBuf,
+        /// Repository to check; repeat to merge several into one model.
+        #[arg(long = "repo", default_value = ".")]
+        repos: Vec<PathBuf>,
         #[arg(long)]
         config: Option<PathBuf>,
         #[arg(long)]
         out: PathBuf,
-        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
-        format: OutputFormat,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
         #[arg(long, value_enum, default_value_t = DocFormat::Json)]
         doc_format: DocFormat,
         #[arg(long, value_enum, default_value_t = ProveEngine::Native)]
         engine: ProveEngine,
         #[arg(long, default_value_t = false)]
         pdf: bool,
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+        /// Minimum share of claims (0.0-1.0) that must be proved or allowlisted.
+        #[arg(long, default_value_t = 1.0, value_parser = parse_min_coverage)]
+        min_coverage: f64,
+        /// Omit run-dependent values so identical inputs give identical bundles.
+        #[arg(long, default_value_t = false)]
+        deterministic: bool,
+    },
+    Codes {
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    Grammar {
+        #[arg(long, value_enum, default_value_t = GrammarFormat::Ebnf)]
+        format: GrammarFormat,
+    },
+    Refs {
+        name: String,
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    Tags {
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t = TagsFormat::Ctags)]
+        format: TagsFormat,
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    Slice {
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        #[arg(long, required_unless_present = "defn", conflicts_with = "defn")]
+        assert: Option<String>,
+        #[arg(long)]
+        defn: Option<String>,
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    Expand {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    Diff {
+        #[arg(long, required = true, num_args = 1..)]
+        old: Vec<PathBuf>,
+        #[arg(long, required = true, num_args = 1..)]
+        new: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    #[command(group(
+        ArgGroup::new("symptom")
+            .required(true)
+            .args(["code", "proof_failure", "panic"])
+    ))]
+    Minimize {
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        #[arg(long)]
+        code: Option<String>,
+        #[arg(long, default_value_t = false)]
+        proof_failure: bool,
+        #[arg(long, default_value_t = false)]
+        panic: bool,
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    Bench {
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        #[arg(long, value_enum, default_value_t = ProveEngine::Native)]
+        engine: ProveEngine,
+        #[arg(long, default_value = "10")]
+        iterations: NonZeroUsize,
+        #[arg(long, default_value_t = 2)]
+        warmup: usize,
+    },
+    Codegen {
+        #[arg(value_enum)]
+        target: CodegenTarget,
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    Eval {
+        file: PathBuf,
+        application: String,
+        #[arg(long, default_value_t = EvalLimits::default().max_depth)]
+        max_depth: usize,
+        #[arg(long, default_value_t = EvalLimits::default().max_steps)]
+        max_steps: u64,
+        #[arg(long)]
+        trace: bool,
+        #[arg(long, value_name = "N", requires = "trace")]
+        trace_depth: Option<usize>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    Run {
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        #[arg(long, default_value_t = EvalLimits::default().max_depth)]
+        max_depth: usize,
+        #[arg(long, default_value_t = EvalLimits::default().max_steps)]
+        max_steps: u64,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    Explore {
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
     },
 }
 
@@ -106,6 +337,37 @@ enum Command {
 enum OutputFormat {
     Text,
     Json,
+    Jsonl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ReportFormat {
+    Text,
+    Json,
+    Jsonl,
+    Github,
+}
+
+impl ReportFormat {
+    fn output(self) -> OutputFormat {
+        match self {
+            ReportFormat::Text | ReportFormat::Github => OutputFormat::Text,
+            ReportFormat::Json => OutputFormat::Json,
+            ReportFormat::Jsonl => OutputFormat::Jsonl,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GrammarFormat {
+    Ebnf,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TagsFormat {
+    Ctags,
+    Json,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -114,6 +376,14 @@ enum DocFormat {
     Json,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CodegenTarget {
+    Rust,
+    Typescript,
+    Jsonschema,
+    Sql,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 enum ProveEngine {
     Native,
@@ -127,6 +397,8 @@ struct JsonResponse {
     report: Option<JsonReport>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     diagnostics: Vec<JsonDiagnostic>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<PhaseTimings>,
 }
 
 #[derive(Debug, Serialize)]
@@ -146,9 +418,54 @@ struct JsonDiagnostic {
     #[serde(skip_serializing_if = "Option::is_none")]
     arg_indices: Option<Vec<usize>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    hint: Option<&'static str>,
+    hint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestion: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     span: Option<JsonSpan>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related: Vec<JsonRelatedSpan>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fixes: Vec<JsonTextEdit>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonTextEdit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    span: JsonSpan,
+    replacement: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRelatedSpan {
+    label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    span: JsonSpan,
+}
+
+#[derive(Debug, Serialize)]
+struct CheckErrorJsonResponse {
+    status: &'static str,
+    diagnostics: Vec<JsonDiagnostic>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<JsonDiagnosticSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<PhaseTimings>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonDiagnosticSummary {
+    total: usize,
+    omitted: usize,
+    files: Vec<JsonDiagnosticFileCount>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonDiagnosticFileCount {
+    source: Option<String>,
+    count: usize,
 }
 
 #[derive(Debug, Serialize)]
@@ -166,6 +483,187 @@ struct ProveJsonResponse {
     proof: Option<ProofTrace>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     diagnostics: Vec<JsonDiagnostic>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<PhaseTimings>,
+}
+
+#[derive(Debug, Serialize)]
+struct StrataJsonResponse {
+    status: &'static str,
+    strata: Vec<JsonStratum>,
+    edges: Vec<JsonStratumEdge>,
+}
+
+#[derive(Debug, Serialize)]
+struct PlanJsonResponse {
+    status: &'static str,
+    strata: Vec<StratumPlan>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonStratum {
+    stratum: usize,
+    relations: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonStratumEdge {
+    head: String,
+    dep: String,
+    negated: bool,
+    forces_order: bool,
+    span: JsonSpan,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonlEvent<T> {
+    event: &'static str,
+    #[serde(flatten)]
+    payload: T,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct JsonlDone {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<JsonReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<JsonDiagnosticSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proof: Option<ProofSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<PhaseTimings>,
+}
+
+#[derive(Debug, Serialize)]
+struct CodesJsonResponse {
+    status: &'static str,
+    exit_codes: Vec<JsonExitCode>,
+}
+
+#[derive(Debug, Serialize)]
+struct GrammarJsonResponse {
+    status: &'static str,
+    forms: &'static [grammar::FormGrammar],
+    productions: Vec<grammar::Production>,
+}
+
+#[derive(Debug, Serialize)]
+struct RefsJsonResponse {
+    status: &'static str,
+    symbol: String,
+    references: Vec<JsonReference>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReference {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    span: JsonSpan,
+}
+
+#[derive(Debug, Serialize)]
+struct EvalJsonResponse {
+    status: &'static str,
+    #[serde(flatten)]
+    result: JsonEvalResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace: Option<Vec<JsonTraceStep>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonTraceStep {
+    depth: usize,
+    kind: &'static str,
+    step: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonEvalResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry: Option<String>,
+    value: String,
+    steps: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct ExpandJsonResponse {
+    status: &'static str,
+    steps: Vec<JsonExpansionStep>,
+    forms: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonExpansionStep {
+    #[serde(rename = "macro")]
+    macro_name: String,
+    span: JsonSpan,
+    call: String,
+    expansion: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonExpandedForm<'a> {
+    text: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct TagsJsonResponse {
+    status: &'static str,
+    tags: Vec<JsonTag>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonTag {
+    name: String,
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    span: JsonSpan,
+}
+
+#[derive(Debug, Serialize)]
+struct DiffJsonResponse {
+    status: &'static str,
+    changes: Vec<JsonDeclChange>,
+    affected_obligations: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonDeclChange {
+    kind: &'static str,
+    name: String,
+    change: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonAffectedObligation<'a> {
+    id: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchJsonResponse {
+    status: &'static str,
+    iterations: usize,
+    warmup: usize,
+    phases: Vec<PhaseStats>,
+    fixpoint: JsonFixpointStats,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFixpointStats {
+    tuples: usize,
+    tuples_per_sec: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonExitCode {
+    code: i32,
+    class: &'static str,
+    description: &'static str,
+    diagnostic_codes: &'static [&'static str],
 }
 
 #[derive(Debug, Serialize)]
@@ -190,198 +688,943 @@ struct LintJsonDiagnostic {
 }
 
 fn main() {
-    let cli = Cli::parse();
+    // Usage errors share the IO/config exit code instead of clap's default 2,
+    // which is reserved for type errors.
+    let cli = Cli::try_parse().unwrap_or_else(|err| {
+        let _ = err.print();
+        std::process::exit(if err.use_stderr() {
+            FailureClass::Io.exit_code()
+        } else {
+            0
+        });
+    });
+    init_logging(cli.verbose, cli.quiet);
+    let load = &LoadOptions::new(cli.include, cli.profiles);
+    let fixpoint = FixpointOptions {
+        cache: None,
+        workers: cli
+            .jobs
+            .or_else(|| std::thread::available_parallelism().ok())
+            .unwrap_or(NonZeroUsize::MIN),
+    };
     let exit_code = match cli.command {
-        Command::Check { files, format } => run_check(&files, format),
+        Command::Check {
+            files,
+            format,
+            timings,
+            max_errors,
+            cache,
+        } => {
+            let cache = fact_cache(cache, &files);
+            run_check(
+                &files,
+                load,
+                format,
+                timings,
+                max_errors,
+                FixpointOptions {
+                    cache: cache.as_ref(),
+                    ..fixpoint
+                },
+            )
+        }
+        Command::Strata { files, format } => run_strata(&files, load, format),
+        Command::Plan { files, format } => run_plan(&files, load, fixpoint, format),
         Command::Prove {
             files,
             format,
             engine,
             out,
-        } => run_prove(&files, format, engine, out.as_deref()),
+            timings,
+            compress_trace,
+            derivations,
+            cache,
+            partial_eval,
+        } => {
+            let cache = fact_cache(cache, &files);
+            run_prove(
+                &files,
+                load,
+                format,
+                engine,
+                FixpointOptions {
+                    cache: cache.as_ref(),
+                    ..fixpoint
+                },
+                out.as_deref(),
+                ProveOutputOptions {
+                    timings,
+                    compress_trace,
+                    derivations,
+                    partial_eval,
+                },
+            )
+        }
         Command::Doc {
             files,
             out,
             format,
             engine,
             pdf,
-        } => run_doc(&files, &out, format, engine, pdf),
+            timings,
+            allow_failed,
+            previous_trace,
+            deterministic,
+            compress_trace,
+            partial_eval,
+        } => run_doc(
+            &files,
+            load,
+            &out,
+            format,
+            DocOptions {
+                engine,
+                fixpoint,
+                pdf,
+                timings,
+                allow_failed,
+                previous_trace: previous_trace.as_deref(),
+                deterministic,
+                compress_trace,
+                partial_eval,
+            },
+        ),
         Command::Lint {
             files,
             format,
             deny_warnings,
             semantic_dup,
-        } => run_lint(&files, format, deny_warnings, semantic_dup),
+            dup_sample,
+            dup_seed,
+            dup_depth_limit,
+            dup_max_model_points,
+            dup_max_function_values,
+            intra_file_only,
+            cache,
+        } => {
+            let cache = fact_cache(cache, &files);
+            run_lint(
+                &files,
+                load,
+                format,
+                deny_warnings,
+                LintOptions {
+                    semantic_dup,
+                    sampling: dup_sample.map(|samples| DupSampling {
+                        samples,
+                        seed: dup_seed,
+                    }),
+                    dup_limits: DupLimits {
+                        eval_depth_limit: dup_depth_limit,
+                        max_model_points: dup_max_model_points,
+                        max_function_values: dup_max_function_values
+                            .unwrap_or(DupLimits::default().max_function_values),
+                    },
+                    intra_file_only,
+                    fixpoint: FixpointOptions {
+                        cache: cache.as_ref(),
+                        ..fixpoint
+                    },
+                    ..LintOptions::default()
+                },
+            )
+        }
         Command::Fmt {
             files,
             check,
             stdout,
         } => run_fmt(&files, check, stdout),
         Command::Selfdoc {
-            repo,
+            repos,
             config,
             out,
             format,
             engine,
             pdf,
-        } => run_selfdoc(&repo, config.as_deref(), &out, format, engine, pdf),
+            deterministic,
+        } => run_selfdoc(
+            &repos,
+            config.as_deref(),
+            &out,
+            format,
+            SelfdocOptions {
+                load,
+                fixpoint,
+                engine,
+                pdf,
+                deterministic,
+            },
+        ),
         Command::Selfcheck {
-            repo,
+            repos,
             config,
             out,
             format,
             doc_format,
             engine,
             pdf,
+            baseline,
+            min_coverage,
+            deterministic,
         } => run_selfcheck(
-            &repo,
+            &repos,
             config.as_deref(),
             &out,
             format,
-            doc_format,
-            engine,
-            pdf,
+            SelfcheckOptions {
+                load,
+                fixpoint,
+                doc_format,
+                engine,
+                pdf,
+                baseline: baseline.as_deref(),
+                min_coverage,
+                deterministic,
+            },
+        ),
+        Command::Codes { format } => run_codes(format),
+        Command::Grammar { format } => run_grammar(format),
+        Command::Refs {
+            name,
+            files,
+            format,
+        } => run_refs(&name, &files, load, format),
+        Command::Tags { files, format, out } => run_tags(&files, load, format, out.as_deref()),
+        Command::Slice {
+            files,
+            assert,
+            defn,
+            out,
+        } => {
+            let target = match (assert, defn) {
+                (Some(name), _) => SliceTarget::Assert(name),
+                (None, Some(name)) => SliceTarget::Defn(name),
+                (None, None) => unreachable!("clap requires --assert or --defn"),
+            };
+            run_slice(&files, load, &target, out.as_deref())
+        }
+        Command::Expand { file, format } => run_expand(&file, format),
+        Command::Diff { old, new, format } => run_diff(&old, &new, load, format),
+        Command::Minimize {
+            files,
+            code,
+            proof_failure: _,
+            panic,
+            out,
+        } => {
+            let symptom = match code {
+                Some(code) => Symptom::Diagnostic(code),
+                None if panic => Symptom::Panic,
+                None => Symptom::ProofFailure,
+            };
+            run_minimize(&files, load, &symptom, out.as_deref())
+        }
+        Command::Bench {
+            files,
+            format,
+            engine,
+            iterations,
+            warmup,
+        } => run_bench(&files, load, fixpoint, format, engine, iterations, warmup),
+        Command::Codegen { target, files, out } => {
+            run_codegen(target, &files, load, out.as_deref())
+        }
+        Command::Eval {
+            file,
+            application,
+            max_depth,
+            max_steps,
+            trace,
+            trace_depth,
+            format,
+        } => run_eval(
+            &file,
+            load,
+            &application,
+            EvalLimits {
+                max_depth,
+                max_steps,
+            },
+            trace.then(|| EvalTrace {
+                max_depth: trace_depth,
+                ..EvalTrace::default()
+            }),
+            format,
+        ),
+        Command::Run {
+            files,
+            max_depth,
+            max_steps,
+            format,
+        } => run_run(
+            &files,
+            load,
+            EvalLimits {
+                max_depth,
+                max_steps,
+            },
+            format,
         ),
+        Command::Explore { files } => run_explore(&files, load),
     };
     std::process::exit(exit_code);
 }
 
-fn run_check(files: &[PathBuf], format: OutputFormat) -> i32 {
-    let program = match load_program(files) {
+fn init_logging(verbose: u8, quiet: bool) {
+    let filter = if quiet {
+        EnvFilter::new("off")
+    } else if verbose > 0 {
+        EnvFilter::new(match verbose {
+            1 => "dtl=info",
+            2 => "dtl=debug",
+            _ => "dtl=trace",
+        })
+    } else {
+        EnvFilter::try_from_env("DTL_LOG").unwrap_or_else(|_| EnvFilter::new("warn"))
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+fn run_check(
+    files: &[PathBuf],
+    load: &LoadOptions,
+    report: ReportFormat,
+    timings: bool,
+    max_errors: Option<NonZeroUsize>,
+    fixpoint: FixpointOptions<'_>,
+) -> i32 {
+    let format = report.output();
+    let mut phase_timings = PhaseTimings::new();
+    let program = match phase_timings.measure("parse", || load_program(files, load)) {
         Ok(program) => program,
         Err(diags) => {
-            emit_error(&diags, format);
-            return 1;
+            let diags = attach_source_if_missing(diags, files);
+            emit_check_errors(
+                &diags,
+                files.len(),
+                format,
+                timings.then_some(phase_timings),
+                max_errors,
+            );
+            emit_diagnostic_annotations(report, &diags, max_errors);
+            return failure_exit_code(&diags);
         }
     };
 
-    match check_program(&program) {
+    match check_program_with_timings(&program, fixpoint, &mut phase_timings) {
         Ok(report) => {
-            emit_ok(report.functions_checked, report.errors, format);
+            emit_ok(
+                report.functions_checked,
+                report.errors,
+                format,
+                timings.then_some(phase_timings),
+            );
             0
         }
         Err(diags) => {
             let diags = attach_source_if_missing(diags, files);
-            emit_error(&diags, format);
-            1
+            emit_check_errors(
+                &diags,
+                files.len(),
+                format,
+                timings.then_some(phase_timings),
+                max_errors,
+            );
+            emit_diagnostic_annotations(report, &diags, max_errors);
+            failure_exit_code(&diags)
         }
     }
 }
 
-fn run_prove(
+fn emit_check_errors(
+    diags: &[Diagnostic],
+    file_count: usize,
+    format: OutputFormat,
+    timings: Option<PhaseTimings>,
+    max_errors: Option<NonZeroUsize>,
+) {
+    let limit = max_errors.map_or(diags.len(), |max| max.get().min(diags.len()));
+    let omitted = diags.len() - limit;
+    let groups = group_by_source(diags);
+    match format {
+        OutputFormat::Text => {
+            if file_count > 1 {
+                let mut remaining = limit;
+                for (source, group) in &groups {
+                    eprintln!(
+                        "{}: {}",
+                        source.unwrap_or("<unknown>"),
+                        count_label(group.len(), "error")
+                    );
+                    for diag in group.iter().take(remaining) {
+                        eprintln!("  {diag}");
+                    }
+                    remaining = remaining.saturating_sub(group.len());
+                }
+            } else {
+                for diag in &diags[..limit] {
+                    eprintln!("{diag}");
+                }
+            }
+            if omitted > 0 {
+                eprintln!(
+                    "... {} omitted (--max-errors {limit})",
+                    count_label(omitted, "more error")
+                );
+            }
+            if file_count > 1 {
+                eprintln!(
+                    "{} in {}",
+                    count_label(diags.len(), "error"),
+                    count_label(groups.len(), "file")
+                );
+            }
+            emit_text_timings(timings.as_ref());
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            let shown = groups
+                .iter()
+                .flat_map(|(_, group)| group.iter().copied())
+                .take(limit)
+                .map(as_json_diagnostic)
+                .collect::<Vec<_>>();
+            let summary = (file_count > 1 || omitted > 0).then(|| JsonDiagnosticSummary {
+                total: diags.len(),
+                omitted,
+                files: groups
+                    .iter()
+                    .map(|(source, group)| JsonDiagnosticFileCount {
+                        source: source.map(ToOwned::to_owned),
+                        count: group.len(),
+                    })
+                    .collect(),
+            });
+            if format == OutputFormat::Json {
+                emit_json(CheckErrorJsonResponse {
+                    status: "error",
+                    diagnostics: shown,
+                    summary,
+                    timings,
+                });
+            } else {
+                for diag in shown {
+                    emit_jsonl("diagnostic", diag);
+                }
+                emit_jsonl(
+                    "done",
+                    JsonlDone {
+                        status: "error",
+                        summary,
+                        timings,
+                        ..JsonlDone::default()
+                    },
+                );
+            }
+        }
+    }
+}
+
+fn group_by_source(diags: &[Diagnostic]) -> Vec<(Option<&str>, Vec<&Diagnostic>)> {
+    let mut groups: Vec<(Option<&str>, Vec<&Diagnostic>)> = Vec::new();
+    for diag in diags {
+        match groups
+            .iter_mut()
+            .find(|(source, _)| *source == diag.source())
+        {
+            Some((_, group)) => group.push(diag),
+            None => groups.push((diag.source(), vec![diag])),
+        }
+    }
+    groups
+}
+
+fn count_label(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+fn run_plan(
     files: &[PathBuf],
+    load: &LoadOptions,
+    fixpoint: FixpointOptions<'_>,
     format: OutputFormat,
-    engine: ProveEngine,
-    out: Option<&Path>,
 ) -> i32 {
-    let program = match load_program(files) {
+    let plan = match load_program(files, load)
+        .and_then(|program| plan_program(&program, fixpoint.workers))
+    {
+        Ok(plan) => plan,
+        Err(diags) => {
+            let diags = attach_source_if_missing(diags, files);
+            emit_error(&diags, format);
+            return failure_exit_code(&diags);
+        }
+    };
+
+    match format {
+        OutputFormat::Text => {
+            for stratum in &plan.strata {
+                let recursive = if stratum.recursive {
+                    " (recursive)"
+                } else {
+                    ""
+                };
+                println!(
+                    "stratum {}: {}{recursive}",
+                    stratum.stratum,
+                    stratum.relations.join(" ")
+                );
+                for rule in &stratum.rules {
+                    let split = if rule.partitions > 1 {
+                        format!(", split {} ways", rule.partitions)
+                    } else {
+                        String::new()
+                    };
+                    println!(
+                        "  rule {} {}{}: est. rows {}, cost {}{split}",
+                        rule.rule,
+                        rule.head,
+                        format_span(Some(&rule.span)),
+                        rule.estimated_rows,
+                        rule.estimated_cost
+                    );
+                    for (index, step) in rule.steps.iter().enumerate() {
+                        let mut detail = vec![step.access.as_str().to_string()];
+                        if step.kind == StepKind::Scan && !step.bound_columns.is_empty() {
+                            let columns = step
+                                .bound_columns
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>();
+                            detail.push(format!("bound columns {}", columns.join(" ")));
+                        }
+                        if !step.join_vars.is_empty() {
+                            let label = if step.kind == StepKind::Scan {
+                                "join on"
+                            } else {
+                                "on"
+                            };
+                            detail.push(format!("{label} {}", step.join_vars.join(" ")));
+                        }
+                        if !step.binds.is_empty() {
+                            detail.push(format!("binds {}", step.binds.join(" ")));
+                        }
+                        if step.kind != StepKind::Filter {
+                            detail.push(format!("|{}| {}", step.relation, step.relation_rows));
+                        }
+                        detail.push(format!(
+                            "rows {} -> {}, cost {}",
+                            step.input_rows, step.output_rows, step.cost
+                        ));
+                        println!(
+                            "    {}. {} {}: {}",
+                            index + 1,
+                            step.kind.as_str(),
+                            step.atom,
+                            detail.join(", ")
+                        );
+                    }
+                }
+            }
+        }
+        OutputFormat::Json => emit_json(PlanJsonResponse {
+            status: "ok",
+            strata: plan.strata,
+        }),
+        OutputFormat::Jsonl => {
+            for stratum in plan.strata {
+                emit_jsonl("stratum", stratum);
+            }
+            emit_jsonl(
+                "done",
+                JsonlDone {
+                    status: "ok",
+                    ..JsonlDone::default()
+                },
+            );
+        }
+    }
+    0
+}
+
+fn run_strata(files: &[PathBuf], load: &LoadOptions, format: OutputFormat) -> i32 {
+    let program = match load_program(files, load) {
         Ok(program) => program,
         Err(diags) => {
             emit_error(&diags, format);
-            return 1;
+            return failure_exit_code(&diags);
         }
     };
 
-    let trace = match prove_with_engine(&program, engine) {
-        Ok(trace) => trace,
+    let strata = match normalize_program_aliases(&program).and_then(|normalized| {
+        let errors = resolve_program(&normalized);
+        if errors.is_empty() {
+            compute_strata(&normalized).map_err(Vec::from)
+        } else {
+            Err(errors)
+        }
+    }) {
+        Ok(strata) => {
+            tracing::info!(
+                relations = strata.levels.len(),
+                strata = strata.layers().len(),
+                "strata computed"
+            );
+            strata
+        }
         Err(diags) => {
             let diags = attach_source_if_missing(diags, files);
-            match format {
-                OutputFormat::Text => emit_error(&diags, OutputFormat::Text),
-                OutputFormat::Json => emit_json(ProveJsonResponse {
-                    status: "error",
-                    proof: None,
-                    diagnostics: diags.iter().map(as_json_diagnostic).collect(),
-                }),
+            emit_error(&diags, format);
+            return failure_exit_code(&diags);
+        }
+    };
+
+    match format {
+        OutputFormat::Text => {
+            for (level, relations) in strata.layers() {
+                println!("stratum {level}: {}", relations.join(" "));
+                for edge in strata.forcing_edges() {
+                    if strata.level(&edge.head) == Some(level) {
+                        let arrow = if edge.negated { "-(not)->" } else { "->" };
+                        println!(
+                            "  {} {arrow} {}{}",
+                            edge.head,
+                            edge.dep,
+                            format_span(Some(&edge.span))
+                        );
+                    }
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            let layers = strata
+                .layers()
+                .into_iter()
+                .map(|(stratum, relations)| JsonStratum { stratum, relations })
+                .collect::<Vec<_>>();
+            let edges = strata
+                .edges
+                .iter()
+                .map(|edge| JsonStratumEdge {
+                    head: edge.head.clone(),
+                    dep: edge.dep.clone(),
+                    negated: edge.negated,
+                    forces_order: strata.forces_order(edge),
+                    span: as_json_span(&edge.span),
+                })
+                .collect::<Vec<_>>();
+            if format == OutputFormat::Json {
+                emit_json(StrataJsonResponse {
+                    status: "ok",
+                    strata: layers,
+                    edges,
+                });
+            } else {
+                for layer in layers {
+                    emit_jsonl("stratum", layer);
+                }
+                for edge in edges {
+                    emit_jsonl("edge", edge);
+                }
+                emit_jsonl(
+                    "done",
+                    JsonlDone {
+                        status: "ok",
+                        ..JsonlDone::default()
+                    },
+                );
             }
-            return 1;
+        }
+    }
+    0
+}
+
+struct ProveOutputOptions {
+    timings: bool,
+    compress_trace: bool,
+    derivations: bool,
+    partial_eval: bool,
+}
+
+fn run_prove(
+    files: &[PathBuf],
+    load: &LoadOptions,
+    report: ReportFormat,
+    engine: ProveEngine,
+    fixpoint: FixpointOptions<'_>,
+    out: Option<&Path>,
+    options: ProveOutputOptions,
+) -> i32 {
+    let format = report.output();
+    let ProveOutputOptions {
+        timings,
+        compress_trace,
+        derivations,
+        partial_eval,
+    } = options;
+    let mut phase_timings = PhaseTimings::new();
+    let program = match phase_timings.measure("parse", || load_program(files, load)) {
+        Ok(program) => program,
+        Err(diags) => {
+            emit_error_with_timings(&diags, format, timings.then_some(phase_timings));
+            emit_diagnostic_annotations(report, &diags, None);
+            return failure_exit_code(&diags);
         }
     };
 
-    if let Some(out_dir) = out {
-        if let Err(err) = fs::create_dir_all(out_dir) {
-            let diag = Diagnostic::new(
+    let trace_path = out.map(|out_dir| out_dir.join(proof_trace_filename(compress_trace)));
+    let mut trace_writer = None;
+    if let Some((out_dir, path)) = out.zip(trace_path.as_deref()) {
+        let opened = match fs::create_dir_all(out_dir) {
+            Ok(()) => ProofTraceWriter::create(path),
+            Err(err) => Err(Diagnostic::new(
                 "E-IO",
                 format!(
                     "failed to create output directory {}: {err}",
                     out_dir.display()
                 ),
                 None,
-            );
-            emit_error(&[diag], format);
-            return 1;
+            )),
+        };
+        match opened {
+            Ok(writer) => trace_writer = Some(writer),
+            Err(diag) => {
+                emit_error(std::slice::from_ref(&diag), format);
+                emit_diagnostic_annotations(report, &[diag], None);
+                return FailureClass::Io.exit_code();
+            }
+        }
+    }
+    let mut trace_write_error = None;
+    let discard_trace = |writer: Option<ProofTraceWriter>| {
+        if let Some(path) = trace_path.as_deref().filter(|_| writer.is_some()) {
+            drop(writer);
+            let _ = fs::remove_file(path);
+        }
+    };
+
+    let stream = format == OutputFormat::Jsonl;
+    let proved =
+        partially_evaluate_if(program, partial_eval, &mut phase_timings).and_then(|program| {
+            let trace = prove_with_engine(
+                &program,
+                engine,
+                fixpoint,
+                &mut phase_timings,
+                |obligation| {
+                    if stream {
+                        emit_jsonl("obligation", obligation);
+                    }
+                    if let Some(writer) = trace_writer.as_mut()
+                        && trace_write_error.is_none()
+                        && let Err(diag) = writer.write_obligation(obligation)
+                    {
+                        trace_write_error = Some(diag);
+                    }
+                },
+            )?;
+            Ok((program, trace))
+        });
+    let (program, mut trace) = match proved {
+        Ok(proved) => proved,
+        Err(diags) => {
+            discard_trace(trace_writer);
+            let diags = attach_source_if_missing(diags, files);
+            let timings = timings.then_some(phase_timings);
+            match format {
+                OutputFormat::Text => {
+                    emit_error_with_timings(&diags, OutputFormat::Text, timings);
+                    emit_diagnostic_annotations(report, &diags, None);
+                }
+                OutputFormat::Json | OutputFormat::Jsonl => emit_prove_response(
+                    format,
+                    ProveJsonResponse {
+                        status: "error",
+                        proof: None,
+                        diagnostics: diags.iter().map(as_json_diagnostic).collect(),
+                        timings,
+                    },
+                ),
+            }
+            return failure_exit_code(&diags);
+        }
+    };
+
+    if derivations {
+        let steps = phase_timings.measure("derivations", || match engine {
+            ProveEngine::Native => derivation_steps(&program),
+            ProveEngine::Reference => reference_derivation_steps(&program),
+        });
+        match steps {
+            Ok(steps) => trace.derivations = Some(steps),
+            Err(diags) => {
+                discard_trace(trace_writer);
+                emit_error(&diags, format);
+                emit_diagnostic_annotations(report, &diags, None);
+                return failure_exit_code(&diags);
+            }
         }
-        let path = out_dir.join("proof-trace.json");
-        if let Err(diag) = write_proof_trace(&path, &trace) {
-            emit_error(&[diag], format);
-            return 1;
+    }
+
+    if let Some(writer) = trace_writer {
+        let written = match trace_write_error {
+            Some(diag) => Err(diag),
+            None => writer.finish(&trace),
+        };
+        if let Err(diag) = written {
+            emit_error(std::slice::from_ref(&diag), format);
+            emit_diagnostic_annotations(report, &[diag], None);
+            return FailureClass::Io.exit_code();
         }
     }
 
     let failed = has_failed_obligation(&trace);
+    let timings = timings.then_some(phase_timings);
     match format {
         OutputFormat::Text => {
             if failed {
                 eprintln!("proof failed");
-                for obligation in &trace.obligations {
-                    if obligation.result != "proved" {
-                        eprintln!("- {}", obligation.id);
-                    }
+                emit_failed_obligations(&trace);
+                if report == ReportFormat::Github {
+                    emit_obligation_annotations(&program, &trace);
                 }
             } else {
                 println!("ok");
             }
+            emit_text_timings(timings.as_ref());
         }
         OutputFormat::Json => {
             emit_json(ProveJsonResponse {
                 status: if failed { "error" } else { "ok" },
                 proof: Some(trace),
                 diagnostics: Vec::new(),
+                timings,
             });
         }
+        OutputFormat::Jsonl => emit_jsonl(
+            "done",
+            JsonlDone {
+                status: if failed { "error" } else { "ok" },
+                proof: Some(trace.summary),
+                timings,
+                ..JsonlDone::default()
+            },
+        ),
+    }
+
+    if failed {
+        FailureClass::Proof.exit_code()
+    } else {
+        0
+    }
+}
+
+fn emit_failed_obligations(trace: &ProofTrace) {
+    for obligation in &trace.obligations {
+        if obligation.result == "proved" {
+            continue;
+        }
+        match &obligation.message {
+            Some(message) => eprintln!("- {}: {message}", obligation.id),
+            None => eprintln!("- {}", obligation.id),
+        }
     }
+}
 
-    if failed { 1 } else { 0 }
+struct DocOptions<'a> {
+    fixpoint: FixpointOptions<'a>,
+    engine: ProveEngine,
+    pdf: bool,
+    timings: bool,
+    allow_failed: bool,
+    previous_trace: Option<&'a Path>,
+    deterministic: bool,
+    compress_trace: bool,
+    partial_eval: bool,
 }
 
 fn run_doc(
     files: &[PathBuf],
+    load: &LoadOptions,
     out: &Path,
     format: DocFormat,
-    engine: ProveEngine,
-    pdf: bool,
+    options: DocOptions<'_>,
 ) -> i32 {
-    let program = match load_program(files) {
+    let DocOptions {
+        fixpoint,
+        engine,
+        pdf,
+        timings,
+        allow_failed,
+        previous_trace,
+        deterministic,
+        compress_trace,
+        partial_eval,
+    } = options;
+    let previous_trace = match previous_trace.map(read_proof_trace).transpose() {
+        Ok(previous_trace) => previous_trace,
+        Err(diag) => {
+            eprintln!("{diag}");
+            return failure_exit_code(&[diag]);
+        }
+    };
+
+    let mut phase_timings = PhaseTimings::new();
+    let program = match phase_timings.measure("parse", || load_program(files, load)) {
         Ok(program) => program,
         Err(diags) => {
-            for d in diags {
+            for d in &diags {
                 eprintln!("{d}");
             }
-            return 1;
+            return failure_exit_code(&diags);
         }
     };
 
-    let trace = match prove_with_engine(&program, engine) {
-        Ok(trace) => trace,
+    let proved =
+        partially_evaluate_if(program, partial_eval, &mut phase_timings).and_then(|program| {
+            let trace = prove_with_engine(&program, engine, fixpoint, &mut phase_timings, |_| {})?;
+            Ok((program, trace))
+        });
+    let (program, trace) = match proved {
+        Ok(proved) => proved,
         Err(diags) => {
-            for d in attach_source_if_missing(diags, files) {
+            let diags = attach_source_if_missing(diags, files);
+            for d in &diags {
                 eprintln!("{d}");
             }
-            return 1;
+            return failure_exit_code(&diags);
         }
     };
 
-    if let Err(diags) = generate_doc_bundle_with_options(
-        &program,
-        &trace,
-        out,
-        as_doc_bundle_format(format),
-        DocBundleOptions::default(),
-    ) {
-        for d in diags {
+    let proof_micros = phase_timings.total_micros;
+    if let Err(diags) = phase_timings.measure("doc-render", || {
+        generate_doc_bundle_with_options(
+            &program,
+            &trace,
+            out,
+            as_doc_bundle_format(format),
+            DocBundleOptions {
+                allow_failed,
+                proof_micros: Some(proof_micros),
+                previous_trace,
+                deterministic,
+                compress_trace,
+                ..DocBundleOptions::default()
+            },
+        )
+    }) {
+        for d in &diags {
             eprintln!("{d}");
         }
-        return 1;
+        return failure_exit_code(&diags);
     }
 
     if pdf {
@@ -398,24 +1641,45 @@ fn run_doc(
         let _ = update_doc_index_pdf(out, false, false, None);
     }
 
+    if has_failed_obligation(&trace) {
+        eprintln!("proof failed");
+        emit_failed_obligations(&trace);
+        emit_text_timings(timings.then_some(&phase_timings));
+        return FailureClass::Proof.exit_code();
+    }
     println!("ok");
+    emit_text_timings(timings.then_some(&phase_timings));
     0
 }
 
+struct SelfdocOptions<'a> {
+    load: &'a LoadOptions,
+    fixpoint: FixpointOptions<'a>,
+    engine: ProveEngine,
+    pdf: bool,
+    deterministic: bool,
+}
+
 fn run_selfdoc(
-    repo: &Path,
+    repos: &[PathBuf],
     config: Option<&Path>,
     out: &Path,
     format: DocFormat,
-    engine: ProveEngine,
-    pdf: bool,
+    options: SelfdocOptions<'_>,
 ) -> i32 {
+    let SelfdocOptions {
+        load,
+        fixpoint,
+        engine,
+        pdf,
+        deterministic,
+    } = options;
     let subcommands = Cli::command()
         .get_subcommands()
         .map(|cmd| cmd.get_name().to_string())
         .collect::<Vec<_>>();
 
-    let prepared = match selfdoc::prepare_selfdoc(repo, config, out, &subcommands) {
+    let prepared = match selfdoc::prepare_selfdoc(repos, config, out, &subcommands) {
         Ok(prepared) => prepared,
         Err(selfdoc::PrepareError::MissingConfig { path, template }) => {
             eprintln!(
@@ -424,34 +1688,37 @@ fn run_selfdoc(
             );
             eprintln!("以下を {} に保存してください:", path.display());
             eprintln!("{template}");
-            return 2;
+            return FailureClass::Io.exit_code();
         }
         Err(selfdoc::PrepareError::Diagnostics(diags)) => {
-            for diag in diags {
+            for diag in &diags {
                 eprintln!("{diag}");
             }
-            return 1;
+            return failure_exit_code(&diags);
         }
     };
 
     let files = vec![prepared.generated_file.clone()];
-    let program = match load_program(&files) {
+    let program = match load_program_files(&files, load) {
         Ok(program) => program,
         Err(diags) => {
-            for d in diags {
+            for d in &diags {
                 eprintln!("{d}");
             }
-            return 1;
+            return failure_exit_code(&diags);
         }
     };
 
-    let mut trace = match prove_with_engine(&program, engine) {
+    let mut phase_timings = PhaseTimings::new();
+    let mut trace = match prove_with_engine(&program, engine, fixpoint, &mut phase_timings, |_| {})
+    {
         Ok(trace) => trace,
         Err(diags) => {
-            for d in attach_source_if_missing(diags, &files) {
+            let diags = attach_source_if_missing(diags, &files);
+            for d in &diags {
                 eprintln!("{d}");
             }
-            return 1;
+            return failure_exit_code(&diags);
         }
     };
     trace.profile = "selfdoc".to_string();
@@ -461,6 +1728,12 @@ fn run_selfdoc(
         profile: Some("selfdoc".to_string()),
         self_description: Some(prepared.self_description),
         intermediate_dsl: Some(prepared.generated_relative),
+        selfdoc_drift: None,
+        allow_failed: false,
+        proof_micros: Some(phase_timings.total_micros),
+        previous_trace: None,
+        compress_trace: false,
+        deterministic,
     };
     if let Err(diags) = generate_doc_bundle_with_options(
         &program,
@@ -469,10 +1742,10 @@ fn run_selfdoc(
         as_doc_bundle_format(format),
         options,
     ) {
-        for d in diags {
+        for d in &diags {
             eprintln!("{d}");
         }
-        return 1;
+        return failure_exit_code(&diags);
     }
 
     if pdf {
@@ -493,21 +1766,56 @@ fn run_selfdoc(
     0
 }
 
-fn run_selfcheck(
-    repo: &Path,
-    config: Option<&Path>,
-    out: &Path,
-    format: OutputFormat,
+struct SelfcheckOptions<'a> {
+    load: &'a LoadOptions,
+    fixpoint: FixpointOptions<'a>,
     doc_format: DocFormat,
     engine: ProveEngine,
     pdf: bool,
+    baseline: Option<&'a Path>,
+    min_coverage: f64,
+    deterministic: bool,
+}
+
+fn parse_min_coverage(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+        _ => Err(format!("expected a ratio between 0.0 and 1.0, got `{raw}`")),
+    }
+}
+
+fn run_selfcheck(
+    repos: &[PathBuf],
+    config: Option<&Path>,
+    out: &Path,
+    format: ReportFormat,
+    options: SelfcheckOptions<'_>,
 ) -> i32 {
+    let SelfcheckOptions {
+        load,
+        fixpoint,
+        doc_format,
+        engine,
+        pdf,
+        baseline,
+        min_coverage,
+        deterministic,
+    } = options;
     let subcommands = Cli::command()
         .get_subcommands()
         .map(|cmd| cmd.get_name().to_string())
         .collect::<Vec<_>>();
 
-    let prepared = match selfdoc::prepare_selfdoc(repo, config, out, &subcommands) {
+    let baseline = match baseline.map(|dir| (dir, selfdoc::load_baseline(dir))) {
+        None => None,
+        Some((dir, Ok(description))) => Some((dir, description)),
+        Some((_, Err(diags))) => {
+            emit_selfcheck_error(format, repos, &diags, None);
+            return failure_exit_code(&diags);
+        }
+    };
+
+    let prepared = match selfdoc::prepare_selfdoc(repos, config, out, &subcommands) {
         Ok(prepared) => prepared,
         Err(selfdoc::PrepareError::MissingConfig { path, template }) => {
             let diag = Diagnostic::new(
@@ -516,86 +1824,67 @@ fn run_selfcheck(
                 None,
             )
             .with_source(path.display().to_string());
-            match format {
+            match format.output() {
                 OutputFormat::Text => {
                     eprintln!("{diag}");
                     eprintln!("以下を {} に保存してください:", path.display());
                     eprintln!("{template}");
+                    if format == ReportFormat::Github {
+                        println!("{}", diagnostic_annotation(&diag, Some(repos)));
+                    }
                 }
-                OutputFormat::Json => {
-                    emit_json(ProveJsonResponse {
-                        status: "error",
-                        proof: None,
-                        diagnostics: vec![as_json_diagnostic(&diag)],
-                    });
+                OutputFormat::Json | OutputFormat::Jsonl => {
+                    emit_prove_response(
+                        format.output(),
+                        ProveJsonResponse {
+                            status: "error",
+                            proof: None,
+                            diagnostics: vec![as_json_diagnostic(&diag)],
+                            timings: None,
+                        },
+                    );
                 }
             }
-            return 2;
+            return FailureClass::Io.exit_code();
         }
         Err(selfdoc::PrepareError::Diagnostics(diags)) => {
-            match format {
-                OutputFormat::Text => {
-                    for diag in &diags {
-                        eprintln!("{diag}");
-                    }
-                }
-                OutputFormat::Json => {
-                    emit_json(ProveJsonResponse {
-                        status: "error",
-                        proof: None,
-                        diagnostics: diags.iter().map(as_json_diagnostic).collect(),
-                    });
-                }
-            }
-            return 1;
+            emit_selfcheck_error(format, repos, &diags, None);
+            return failure_exit_code(&diags);
         }
     };
 
     let files = vec![prepared.generated_file.clone()];
-    let program = match load_program(&files) {
+    let program = match load_program_files(&files, load) {
         Ok(program) => program,
         Err(diags) => {
-            match format {
-                OutputFormat::Text => {
-                    for d in &diags {
-                        eprintln!("{d}");
-                    }
-                }
-                OutputFormat::Json => {
-                    emit_json(ProveJsonResponse {
-                        status: "error",
-                        proof: None,
-                        diagnostics: diags.iter().map(as_json_diagnostic).collect(),
-                    });
-                }
-            }
-            return 1;
+            emit_selfcheck_error(format, repos, &diags, None);
+            return failure_exit_code(&diags);
         }
     };
 
-    let mut trace = match prove_with_engine(&program, engine) {
+    let mut phase_timings = PhaseTimings::new();
+    let mut trace = match prove_with_engine(&program, engine, fixpoint, &mut phase_timings, |_| {})
+    {
         Ok(trace) => trace,
         Err(diags) => {
             let diags = attach_source_if_missing(diags, &files);
-            match format {
-                OutputFormat::Text => {
-                    for d in &diags {
-                        eprintln!("{d}");
-                    }
-                }
-                OutputFormat::Json => {
-                    emit_json(ProveJsonResponse {
-                        status: "error",
-                        proof: None,
-                        diagnostics: diags.iter().map(as_json_diagnostic).collect(),
-                    });
-                }
-            }
-            return 1;
+            emit_selfcheck_error(format, repos, &diags, None);
+            return failure_exit_code(&diags);
         }
     };
     trace.profile = "selfdoc".to_string();
     trace.claim_coverage = prepared.claim_coverage;
+    let (allowed_unproved, unproved) = prepared
+        .unproved_claims
+        .into_iter()
+        .partition(|claim| prepared.allow_unproved.contains(claim));
+    let policy = ClaimPolicy {
+        min_coverage,
+        ratio: trace.claim_coverage.ratio(),
+        allowed_unproved,
+        unproved,
+    };
+    trace.claim_coverage.policy = Some(policy.clone());
 
     if let Err(err) = fs::create_dir_all(out) {
         let diag = Diagnostic::new(
@@ -603,205 +1892,1165 @@ fn run_selfcheck(
             format!("failed to create output directory {}: {err}", out.display()),
             None,
         );
-        match format {
-            OutputFormat::Text => eprintln!("{diag}"),
-            OutputFormat::Json => {
-                emit_json(ProveJsonResponse {
+        emit_selfcheck_error(format, repos, &[diag], Some(trace));
+        return FailureClass::Io.exit_code();
+    }
+    if let Err(diag) = write_proof_trace(&out.join("proof-trace.json"), &trace) {
+        emit_selfcheck_error(format, repos, &[diag], Some(trace));
+        return FailureClass::Io.exit_code();
+    }
+
+    let has_failed = has_failed_obligation(&trace);
+    let has_enough_coverage = policy.is_satisfied(&trace.claim_coverage);
+    if has_failed || !has_enough_coverage {
+        let mut diagnostics = Vec::new();
+        if !has_enough_coverage {
+            diagnostics.push(Diagnostic::new(
+                "E-SELFCHECK",
+                format!(
+                    "claim coverage が不足しています: {}/{}（min_coverage = {}、未証明: {}）",
+                    trace.claim_coverage.proved_claims,
+                    trace.claim_coverage.total_claims,
+                    policy.min_coverage,
+                    policy.unproved.join(", ")
+                ),
+                None,
+            ));
+        }
+        match format.output() {
+            OutputFormat::Text => {
+                if has_failed {
+                    eprintln!("selfcheck proof failed");
+                    emit_failed_obligations(&trace);
+                }
+                for diag in &diagnostics {
+                    eprintln!("{diag}");
+                }
+                if format == ReportFormat::Github {
+                    emit_selfcheck_annotations(
+                        repos,
+                        &prepared.self_description,
+                        &trace,
+                        &diagnostics,
+                    );
+                }
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                emit_prove_response(
+                    format.output(),
+                    ProveJsonResponse {
+                        status: "error",
+                        proof: Some(trace),
+                        diagnostics: diagnostics.iter().map(as_json_diagnostic).collect(),
+                        timings: None,
+                    },
+                );
+            }
+        }
+        return FailureClass::Proof.exit_code();
+    }
+
+    let selfdoc_drift = baseline.map(|(dir, description)| {
+        diff_self_descriptions(
+            &dir.display().to_string(),
+            &description,
+            &prepared.self_description,
+        )
+    });
+    let options = DocBundleOptions {
+        profile: Some("selfdoc".to_string()),
+        self_description: Some(prepared.self_description),
+        intermediate_dsl: Some(prepared.generated_relative),
+        selfdoc_drift,
+        allow_failed: false,
+        proof_micros: Some(phase_timings.total_micros),
+        previous_trace: None,
+        compress_trace: false,
+        deterministic,
+    };
+    if let Err(diags) = generate_doc_bundle_with_options(
+        &program,
+        &trace,
+        out,
+        as_doc_bundle_format(doc_format),
+        options,
+    ) {
+        emit_selfcheck_error(format, repos, &diags, Some(trace));
+        return failure_exit_code(&diags);
+    }
+
+    if pdf {
+        if doc_format == DocFormat::Markdown {
+            if let Err(message) = try_generate_pdf(out) {
+                eprintln!("warning: {message}");
+            }
+        } else {
+            let message = "JSON 形式では PDF 生成をスキップしました".to_string();
+            let _ = update_doc_index_pdf(out, true, false, Some(message.clone()));
+            eprintln!("warning: {message}");
+        }
+    } else {
+        let _ = update_doc_index_pdf(out, false, false, None);
+    }
+
+    match format.output() {
+        OutputFormat::Text => println!("ok"),
+        OutputFormat::Json | OutputFormat::Jsonl => emit_prove_response(
+            format.output(),
+            ProveJsonResponse {
+                status: "ok",
+                proof: Some(trace),
+                diagnostics: Vec::new(),
+                timings: None,
+            },
+        ),
+    }
+    0
+}
+
+fn emit_selfcheck_error(
+    format: ReportFormat,
+    repos: &[PathBuf],
+    diags: &[Diagnostic],
+    proof: Option<ProofTrace>,
+) {
+    match format.output() {
+        OutputFormat::Text => {
+            for diag in diags {
+                eprintln!("{diag}");
+            }
+            if format == ReportFormat::Github {
+                for diag in diags {
+                    println!("{}", diagnostic_annotation(diag, Some(repos)));
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            emit_prove_response(
+                format.output(),
+                ProveJsonResponse {
                     status: "error",
-                    proof: Some(trace),
-                    diagnostics: vec![as_json_diagnostic(&diag)],
+                    proof,
+                    diagnostics: diags.iter().map(as_json_diagnostic).collect(),
+                    timings: None,
+                },
+            );
+        }
+    }
+}
+
+fn emit_selfcheck_annotations(
+    repos: &[PathBuf],
+    description: &DocSelfDescription,
+    trace: &ProofTrace,
+    diagnostics: &[Diagnostic],
+) {
+    for obligation in &trace.obligations {
+        if obligation.result == "proved" {
+            continue;
+        }
+        let location = selfdoc::obligation_location(repos, description, obligation);
+        let message = obligation
+            .counterexample
+            .as_ref()
+            .and_then(|counterexample| counterexample.missing_goals.first())
+            .or(obligation.message.as_ref())
+            .map_or_else(|| "proof failed".to_string(), Clone::clone);
+        let (file, line) = match &location {
+            Some((file, line)) => (Some(annotation_path(repos, file)), *line),
+            None => (None, None),
+        };
+        println!(
+            "{}",
+            github_annotation(
+                "error",
+                file.as_deref(),
+                line,
+                None,
+                &obligation.id,
+                &message
+            )
+        );
+    }
+    let table = selfdoc::contract_table_location(repos);
+    for diag in diagnostics {
+        let (file, line) = match &table {
+            Some((file, line)) => (Some(annotation_path(repos, file)), Some(*line)),
+            None => (None, None),
+        };
+        println!(
+            "{}",
+            github_annotation(
+                "error",
+                file.as_deref(),
+                line,
+                None,
+                diag.code,
+                &diag.message
+            )
+        );
+    }
+}
+
+fn emit_diagnostic_annotations(
+    report: ReportFormat,
+    diags: &[Diagnostic],
+    max_errors: Option<NonZeroUsize>,
+) {
+    if report != ReportFormat::Github {
+        return;
+    }
+    let limit = max_errors.map_or(diags.len(), NonZeroUsize::get);
+    for diag in diags.iter().take(limit) {
+        println!("{}", diagnostic_annotation(diag, None));
+    }
+}
+
+fn emit_obligation_annotations(program: &Program, trace: &ProofTrace) {
+    for obligation in &trace.obligations {
+        if obligation.result == "proved" {
+            continue;
+        }
+        let span = obligation_span(program, &obligation.id);
+        let file = span
+            .and_then(|span| span.file_id.as_deref())
+            .map(|file| workspace_path(PathBuf::from(file)));
+        let mut message = obligation
+            .message
+            .clone()
+            .unwrap_or_else(|| "proof failed".to_string());
+        if let Some(counterexample) = &obligation.counterexample {
+            if !counterexample.valuation.is_empty() {
+                let valuation = counterexample
+                    .valuation
+                    .iter()
+                    .map(|nv| format!("{} = {}", nv.name, nv.value))
+                    .collect::<Vec<_>>();
+                message.push_str(&format!("\ncounterexample: {}", valuation.join(", ")));
+            }
+            if !counterexample.missing_goals.is_empty() {
+                message.push_str(&format!(
+                    "\nmissing: {}",
+                    counterexample.missing_goals.join(", ")
+                ));
+            }
+        }
+        println!(
+            "{}",
+            github_annotation(
+                "error",
+                file.as_deref(),
+                span.map(|span| span.line),
+                span.map(|span| span.column),
+                &obligation.id,
+                &message
+            )
+        );
+    }
+}
+
+fn diagnostic_annotation(diag: &Diagnostic, repos: Option<&[PathBuf]>) -> String {
+    let (file, line, col) = match &diag.span {
+        Some(span) => (
+            span.file_id.as_deref().or(diag.source()),
+            Some(span.line),
+            Some(span.column),
+        ),
+        None => (diag.source(), None, None),
+    };
+    let file = file.map(|file| match repos {
+        Some(repos) => annotation_path(repos, file),
+        None => workspace_path(PathBuf::from(file)),
+    });
+    let message = match diag.hint() {
+        Some(hint) => format!("{}\n{hint}", diag.message),
+        None => diag.message.clone(),
+    };
+    github_annotation("error", file.as_deref(), line, col, diag.code, &message)
+}
+
+fn github_annotation(
+    level: &str,
+    file: Option<&str>,
+    line: Option<usize>,
+    col: Option<usize>,
+    title: &str,
+    message: &str,
+) -> String {
+    let mut properties = Vec::new();
+    if let Some(file) = file {
+        properties.push(format!("file={}", escape_github_property(file)));
+    }
+    if let Some(line) = line {
+        properties.push(format!("line={line}"));
+    }
+    if let Some(col) = col {
+        properties.push(format!("col={col}"));
+    }
+    properties.push(format!("title={}", escape_github_property(title)));
+    format!(
+        "::{level} {}::{}",
+        properties.join(","),
+        escape_github_data(message)
+    )
+}
+
+fn escape_github_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_github_property(value: &str) -> String {
+    escape_github_data(value)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+fn annotation_path(repos: &[PathBuf], path: &str) -> String {
+    let model_file = selfdoc::model_file(repos, path);
+    let resolved = if model_file.exists() {
+        model_file
+    } else {
+        PathBuf::from(path)
+    };
+    workspace_path(resolved)
+}
+
+fn workspace_path(resolved: PathBuf) -> String {
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| resolved.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or(resolved);
+    let relative = relative.strip_prefix(".").unwrap_or(&relative);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+fn run_lint(
+    files: &[PathBuf],
+    load: &LoadOptions,
+    report: ReportFormat,
+    deny_warnings: bool,
+    mut options: LintOptions<'_>,
+) -> i32 {
+    let format = report.output();
+    let program = match load_program(files, load) {
+        Ok(program) => program,
+        Err(diags) => {
+            emit_error(&diags, format);
+            emit_diagnostic_annotations(report, &diags, None);
+            return failure_exit_code(&diags);
+        }
+    };
+
+    if let Ok(Some(config)) = load_project_config_for(files) {
+        options.complexity = config.complexity;
+    }
+    let mut diagnostics = lint_program(&program, options);
+    diagnostics = attach_lint_source_if_missing(diagnostics, files);
+
+    match format {
+        OutputFormat::Text => {
+            for diag in &diagnostics {
+                if let Some(source) = &diag.source {
+                    eprintln!(
+                        "{}: {} {} [{}]{}",
+                        source,
+                        diag.severity.as_str(),
+                        diag.lint_code,
+                        diag.category,
+                        format_span(diag.span.as_ref())
+                    );
+                } else {
+                    eprintln!(
+                        "{} {} [{}]{}",
+                        diag.severity.as_str(),
+                        diag.lint_code,
+                        diag.category,
+                        format_span(diag.span.as_ref())
+                    );
+                }
+                eprintln!("  {}", diag.message);
+            }
+            if report == ReportFormat::Github {
+                let level = if deny_warnings { "error" } else { "warning" };
+                for diag in &diagnostics {
+                    let (line, col) = diag
+                        .span
+                        .as_ref()
+                        .map_or((None, None), |span| (Some(span.line), Some(span.column)));
+                    let file = diag
+                        .span
+                        .as_ref()
+                        .and_then(|span| span.file_id.as_deref())
+                        .or(diag.source.as_deref())
+                        .map(|file| workspace_path(PathBuf::from(file)));
+                    println!(
+                        "{}",
+                        github_annotation(
+                            level,
+                            file.as_deref(),
+                            line,
+                            col,
+                            diag.lint_code,
+                            &diag.message
+                        )
+                    );
+                }
+            }
+            if diagnostics.is_empty() {
+                println!("ok");
+            }
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            let status = if diagnostics.is_empty() {
+                "ok"
+            } else if deny_warnings {
+                "error"
+            } else {
+                "ok"
+            };
+            if format == OutputFormat::Json {
+                emit_json(LintJsonResponse {
+                    status,
+                    diagnostics: diagnostics.iter().map(as_json_lint_diagnostic).collect(),
                 });
+            } else {
+                for diag in &diagnostics {
+                    emit_jsonl("lint", as_json_lint_diagnostic(diag));
+                }
+                emit_jsonl(
+                    "done",
+                    JsonlDone {
+                        status,
+                        ..JsonlDone::default()
+                    },
+                );
+            }
+        }
+    }
+
+    if deny_warnings && !diagnostics.is_empty() {
+        FailureClass::Lint.exit_code()
+    } else {
+        0
+    }
+}
+
+fn run_codes(format: OutputFormat) -> i32 {
+    let exit_codes = std::iter::once(JsonExitCode {
+        code: 0,
+        class: "ok",
+        description: "成功",
+        diagnostic_codes: &[],
+    })
+    .chain(FailureClass::ALL.iter().map(|class| JsonExitCode {
+        code: class.exit_code(),
+        class: class.as_str(),
+        description: class.describe(),
+        diagnostic_codes: class.diagnostic_codes(),
+    }))
+    .collect::<Vec<_>>();
+
+    match format {
+        OutputFormat::Text => {
+            for entry in &exit_codes {
+                println!("{}  {:<5}  {}", entry.code, entry.class, entry.description);
+                if !entry.diagnostic_codes.is_empty() {
+                    println!("         {}", entry.diagnostic_codes.join(", "));
+                }
+            }
+        }
+        OutputFormat::Json => emit_json(CodesJsonResponse {
+            status: "ok",
+            exit_codes,
+        }),
+        OutputFormat::Jsonl => {
+            for entry in exit_codes {
+                emit_jsonl("exit_code", entry);
+            }
+            emit_jsonl(
+                "done",
+                JsonlDone {
+                    status: "ok",
+                    ..JsonlDone::default()
+                },
+            );
+        }
+    }
+    0
+}
+
+fn run_grammar(format: GrammarFormat) -> i32 {
+    match format {
+        GrammarFormat::Ebnf => print!("{}", grammar::render_ebnf()),
+        GrammarFormat::Json => emit_json(GrammarJsonResponse {
+            status: "ok",
+            forms: grammar::FORMS,
+            productions: grammar::productions(),
+        }),
+    }
+    0
+}
+
+fn run_refs(name: &str, files: &[PathBuf], load: &LoadOptions, format: OutputFormat) -> i32 {
+    let program = match load_program(files, load) {
+        Ok(program) => program,
+        Err(diags) => {
+            let diags = attach_source_if_missing(diags, files);
+            emit_error(&diags, format);
+            return failure_exit_code(&diags);
+        }
+    };
+
+    let single_source = (files.len() == 1).then(|| files[0].display().to_string());
+    let references = find_references(&program, name)
+        .iter()
+        .map(|span| JsonReference {
+            source: span.file_id.clone().or_else(|| single_source.clone()),
+            span: as_json_span(span),
+        })
+        .collect::<Vec<_>>();
+    match format {
+        OutputFormat::Text => {
+            for reference in &references {
+                let source = reference.source.as_deref().unwrap_or("-");
+                println!("{source}:{}:{}", reference.span.line, reference.span.column);
             }
         }
-        return 1;
+        OutputFormat::Json => emit_json(RefsJsonResponse {
+            status: "ok",
+            symbol: name.to_string(),
+            references,
+        }),
+        OutputFormat::Jsonl => {
+            for reference in references {
+                emit_jsonl("reference", reference);
+            }
+            emit_jsonl(
+                "done",
+                JsonlDone {
+                    status: "ok",
+                    ..JsonlDone::default()
+                },
+            );
+        }
+    }
+    0
+}
+
+fn run_tags(files: &[PathBuf], load: &LoadOptions, format: TagsFormat, out: Option<&Path>) -> i32 {
+    let program = match load_program(files, load) {
+        Ok(program) => program,
+        Err(diags) => {
+            let diags = attach_source_if_missing(diags, files);
+            emit_error(&diags, OutputFormat::Text);
+            return failure_exit_code(&diags);
+        }
+    };
+
+    let tags = collect_tags(&program);
+    let default_file = files[0].display().to_string();
+    let rendered = match format {
+        TagsFormat::Ctags => render_ctags(&tags, &default_file),
+        TagsFormat::Json => {
+            let response = TagsJsonResponse {
+                status: "ok",
+                tags: tags
+                    .into_iter()
+                    .map(|tag| JsonTag {
+                        name: tag.name,
+                        kind: tag.kind.as_str(),
+                        scope: tag.scope,
+                        source: tag.span.file_id.clone(),
+                        span: as_json_span(&tag.span),
+                    })
+                    .collect(),
+            };
+            let json = serde_json::to_string(&response).expect("tags response is serializable");
+            format!("{json}\n")
+        }
+    };
+    match out {
+        Some(path) => {
+            if let Err(err) = fs::write(path, rendered) {
+                let diag = Diagnostic::new(
+                    "E-IO",
+                    format!("failed to write {}: {err}", path.display()),
+                    None,
+                );
+                emit_error(&[diag], OutputFormat::Text);
+                return FailureClass::Io.exit_code();
+            }
+        }
+        None => print!("{rendered}"),
+    }
+    0
+}
+
+fn run_slice(
+    files: &[PathBuf],
+    load: &LoadOptions,
+    target: &SliceTarget,
+    out: Option<&Path>,
+) -> i32 {
+    let sliced = load_program(files, load).and_then(|program| slice_program(&program, target));
+    let sliced = match sliced {
+        Ok(sliced) => sliced,
+        Err(diags) => {
+            let diags = attach_source_if_missing(diags, files);
+            emit_error(&diags, OutputFormat::Text);
+            return failure_exit_code(&diags);
+        }
+    };
+
+    let keywords = fs::read_to_string(&files[0])
+        .map(|src| surface_keywords(&src))
+        .unwrap_or_default();
+    let rendered = render_program(&sliced, keywords);
+    match out {
+        Some(path) => {
+            if let Err(err) = fs::write(path, rendered) {
+                let diag = Diagnostic::new(
+                    "E-IO",
+                    format!("failed to write {}: {err}", path.display()),
+                    None,
+                );
+                emit_error(&[diag], OutputFormat::Text);
+                return FailureClass::Io.exit_code();
+            }
+        }
+        None => print!("{rendered}"),
+    }
+    0
+}
+
+fn run_codegen(
+    target: CodegenTarget,
+    files: &[PathBuf],
+    load: &LoadOptions,
+    out: Option<&Path>,
+) -> i32 {
+    let generated = load_program(files, load).and_then(|program| match target {
+        CodegenTarget::Rust => generate_rust(&program),
+        CodegenTarget::Typescript => generate_typescript(&program),
+        CodegenTarget::Jsonschema => generate_jsonschema(&program),
+        CodegenTarget::Sql => generate_sql(&program),
+    });
+    let generated = match generated {
+        Ok(generated) => generated,
+        Err(diags) => {
+            let diags = attach_source_if_missing(diags, files);
+            emit_error(&diags, OutputFormat::Text);
+            return failure_exit_code(&diags);
+        }
+    };
+
+    match out {
+        Some(path) => {
+            if let Err(err) = fs::write(path, generated) {
+                let diag = Diagnostic::new(
+                    "E-IO",
+                    format!("failed to write {}: {err}", path.display()),
+                    None,
+                );
+                emit_error(&[diag], OutputFormat::Text);
+                return FailureClass::Io.exit_code();
+            }
+        }
+        None => print!("{generated}"),
+    }
+    0
+}
+
+fn run_eval(
+    file: &Path,
+    load: &LoadOptions,
+    application: &str,
+    limits: EvalLimits,
+    mut trace: Option<EvalTrace>,
+    format: OutputFormat,
+) -> i32 {
+    let files = [file.to_path_buf()];
+    let evaluated = load_program(&files, load).and_then(|program| match trace.as_mut() {
+        Some(trace) => evaluate_traced(&program, application, limits, trace),
+        None => evaluate(&program, application, limits),
+    });
+    let trace = trace.map(|trace| {
+        trace
+            .steps
+            .iter()
+            .map(|step| JsonTraceStep {
+                depth: step.depth,
+                kind: step.event.kind(),
+                step: step.event.to_string(),
+            })
+            .collect::<Vec<_>>()
+    });
+    for step in trace.iter().flatten() {
+        match format {
+            OutputFormat::Text => println!("{:width$}{}", "", step.step, width = step.depth * 2),
+            OutputFormat::Json => {}
+            OutputFormat::Jsonl => emit_jsonl("trace", step),
+        }
+    }
+    let evaluation = match evaluated {
+        Ok(evaluation) => evaluation,
+        Err(diags) => {
+            let diags = attach_source_if_missing(diags, &files);
+            emit_error(&diags, format);
+            return failure_exit_code(&diags);
+        }
+    };
+
+    emit_evaluation(None, &evaluation, trace, format);
+    0
+}
+
+fn run_run(files: &[PathBuf], load: &LoadOptions, limits: EvalLimits, format: OutputFormat) -> i32 {
+    match load_program(files, load).and_then(|program| run_entry_point(&program, limits)) {
+        Ok((entry, evaluation)) => {
+            emit_evaluation(Some(entry), &evaluation, None, format);
+            0
+        }
+        Err(diags) => {
+            let diags = attach_source_if_missing(diags, files);
+            emit_error(&diags, format);
+            failure_exit_code(&diags)
+        }
+    }
+}
+
+fn emit_evaluation(
+    entry: Option<String>,
+    evaluation: &Evaluation,
+    trace: Option<Vec<JsonTraceStep>>,
+    format: OutputFormat,
+) {
+    let result = JsonEvalResult {
+        entry,
+        value: evaluation.value.to_string(),
+        steps: evaluation.steps,
+    };
+    match format {
+        OutputFormat::Text => println!("{}", result.value),
+        OutputFormat::Json => emit_json(EvalJsonResponse {
+            status: "ok",
+            result,
+            trace,
+        }),
+        OutputFormat::Jsonl => {
+            emit_jsonl("result", result);
+            emit_jsonl(
+                "done",
+                JsonlDone {
+                    status: "ok",
+                    ..JsonlDone::default()
+                },
+            );
+        }
     }
-    if let Err(diag) = write_proof_trace(&out.join("proof-trace.json"), &trace) {
-        match format {
-            OutputFormat::Text => eprintln!("{diag}"),
-            OutputFormat::Json => {
-                emit_json(ProveJsonResponse {
-                    status: "error",
-                    proof: Some(trace),
-                    diagnostics: vec![as_json_diagnostic(&diag)],
-                });
+}
+
+fn run_explore(files: &[PathBuf], load: &LoadOptions) -> i32 {
+    let mut explorer = match load_program(files, load).and_then(|program| Explorer::new(&program)) {
+        Ok(explorer) => explorer,
+        Err(diags) => {
+            let diags = attach_source_if_missing(diags, files);
+            emit_error(&diags, OutputFormat::Text);
+            return failure_exit_code(&diags);
+        }
+    };
+    let clear = std::io::stdout().is_terminal();
+    let mut line = String::new();
+    loop {
+        if clear {
+            print!("\x1b[2J\x1b[H");
+        }
+        print!("{}> ", explorer.screen());
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        line.clear();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => {
+                println!();
+                return 0;
+            }
+            Ok(_) => {
+                if !explorer.command(&line) {
+                    return 0;
+                }
+            }
+            Err(err) => {
+                let diag = Diagnostic::new("E-IO", format!("failed to read input: {err}"), None);
+                emit_error(&[diag], OutputFormat::Text);
+                return FailureClass::Io.exit_code();
             }
         }
-        return 1;
     }
+}
 
-    let has_failed = has_failed_obligation(&trace);
-    let has_full_coverage =
-        has_full_claim_coverage(&trace) && trace.claim_coverage.total_claims > 0;
-    if has_failed || !has_full_coverage {
-        let mut diagnostics = Vec::new();
-        if !has_full_coverage {
-            diagnostics.push(Diagnostic::new(
-                "E-SELFCHECK",
-                format!(
-                    "claim coverage が不足しています: {}/{}",
-                    trace.claim_coverage.proved_claims, trace.claim_coverage.total_claims
-                ),
+fn run_expand(file: &Path, format: OutputFormat) -> i32 {
+    let source = file.display().to_string();
+    let expanded = fs::read_to_string(file)
+        .map_err(|err| {
+            vec![Diagnostic::new(
+                "E-IO",
+                format!("failed to read file: {err}"),
                 None,
-            ));
+            )]
+        })
+        .and_then(|src| expand_macros(&src).map(|expansion| (src, expansion)));
+    let (src, expansion) = match expanded {
+        Ok(expanded) => expanded,
+        Err(diags) => {
+            let diags = diags
+                .into_iter()
+                .map(|diag| diag.with_source(source.clone()))
+                .collect::<Vec<_>>();
+            emit_error(&diags, format);
+            return failure_exit_code(&diags);
         }
-        match format {
-            OutputFormat::Text => {
-                if has_failed {
-                    eprintln!("selfcheck proof failed");
-                    for obligation in &trace.obligations {
-                        if obligation.result != "proved" {
-                            eprintln!("- {}", obligation.id);
-                        }
-                    }
+    };
+
+    match format {
+        OutputFormat::Text => {
+            for line in src.lines().skip_while(|line| line.starts_with("#!")) {
+                let trimmed = line.trim();
+                if !trimmed.is_empty() && !trimmed.starts_with(';') {
+                    break;
                 }
-                for diag in diagnostics {
-                    eprintln!("{diag}");
+                if !trimmed.is_empty() {
+                    println!("{trimmed}");
                 }
             }
-            OutputFormat::Json => {
-                emit_json(ProveJsonResponse {
-                    status: "error",
-                    proof: Some(trace),
-                    diagnostics: diagnostics.iter().map(as_json_diagnostic).collect(),
-                });
+            for (idx, step) in expansion.steps.iter().enumerate() {
+                println!(
+                    "; {}. {} at {}:{}",
+                    idx + 1,
+                    step.macro_name,
+                    step.span.line,
+                    step.span.column
+                );
+                println!(";   {}", step.call);
+                for form in &step.expansion {
+                    println!(";   => {form}");
+                }
+            }
+            for form in &expansion.forms {
+                println!("{form}");
             }
         }
-        return 1;
-    }
-
-    let options = DocBundleOptions {
-        profile: Some("selfdoc".to_string()),
-        self_description: Some(prepared.self_description),
-        intermediate_dsl: Some(prepared.generated_relative),
-    };
-    if let Err(diags) = generate_doc_bundle_with_options(
-        &program,
-        &trace,
-        out,
-        as_doc_bundle_format(doc_format),
-        options,
-    ) {
-        match format {
-            OutputFormat::Text => {
-                for d in &diags {
-                    eprintln!("{d}");
-                }
+        OutputFormat::Json => emit_json(ExpandJsonResponse {
+            status: "ok",
+            steps: expansion.steps.iter().map(as_json_expansion_step).collect(),
+            forms: expansion.forms.clone(),
+        }),
+        OutputFormat::Jsonl => {
+            for step in &expansion.steps {
+                emit_jsonl("step", as_json_expansion_step(step));
             }
-            OutputFormat::Json => {
-                emit_json(ProveJsonResponse {
-                    status: "error",
-                    proof: Some(trace),
-                    diagnostics: diags.iter().map(as_json_diagnostic).collect(),
-                });
+            for form in &expansion.forms {
+                emit_jsonl("form", JsonExpandedForm { text: form });
             }
+            emit_jsonl(
+                "done",
+                JsonlDone {
+                    status: "ok",
+                    ..JsonlDone::default()
+                },
+            );
         }
-        return 1;
     }
+    0
+}
 
-    if pdf {
-        if doc_format == DocFormat::Markdown {
-            if let Err(message) = try_generate_pdf(out) {
-                eprintln!("warning: {message}");
-            }
-        } else {
-            let message = "JSON 形式では PDF 生成をスキップしました".to_string();
-            let _ = update_doc_index_pdf(out, true, false, Some(message.clone()));
-            eprintln!("warning: {message}");
-        }
-    } else {
-        let _ = update_doc_index_pdf(out, false, false, None);
+fn as_json_expansion_step(step: &ExpansionStep) -> JsonExpansionStep {
+    JsonExpansionStep {
+        macro_name: step.macro_name.clone(),
+        span: as_json_span(&step.span),
+        call: step.call.clone(),
+        expansion: step.expansion.clone(),
     }
+}
+
+fn run_diff(
+    old_files: &[PathBuf],
+    new_files: &[PathBuf],
+    load: &LoadOptions,
+    format: OutputFormat,
+) -> i32 {
+    let diff = load_program(old_files, load)
+        .map_err(|diags| attach_source_if_missing(diags, old_files))
+        .and_then(|old| {
+            load_program(new_files, load)
+                .map_err(|diags| attach_source_if_missing(diags, new_files))
+                .and_then(|new| diff_programs(&old, &new))
+        });
+    let diff = match diff {
+        Ok(diff) => diff,
+        Err(diags) => {
+            emit_error(&diags, format);
+            return failure_exit_code(&diags);
+        }
+    };
 
+    let changes = diff.changes.iter().map(|change| JsonDeclChange {
+        kind: change.kind.as_str(),
+        name: change.name.clone(),
+        change: change.change.as_str(),
+    });
     match format {
-        OutputFormat::Text => println!("ok"),
-        OutputFormat::Json => emit_json(ProveJsonResponse {
+        OutputFormat::Text => {
+            if diff.is_empty() {
+                println!("no changes");
+            }
+            for change in &diff.changes {
+                let marker = match change.change {
+                    ChangeKind::Added => '+',
+                    ChangeKind::Removed => '-',
+                    ChangeKind::Changed => '~',
+                };
+                println!("{marker} {} {}", change.kind.as_str(), change.name);
+            }
+            if !diff.affected_obligations.is_empty() {
+                println!("affected obligations:");
+                for id in &diff.affected_obligations {
+                    println!("  {id}");
+                }
+            }
+        }
+        OutputFormat::Json => emit_json(DiffJsonResponse {
             status: "ok",
-            proof: Some(trace),
-            diagnostics: Vec::new(),
+            changes: changes.collect(),
+            affected_obligations: diff.affected_obligations.clone(),
         }),
+        OutputFormat::Jsonl => {
+            for change in changes {
+                emit_jsonl("change", change);
+            }
+            for id in &diff.affected_obligations {
+                emit_jsonl("affected", JsonAffectedObligation { id });
+            }
+            emit_jsonl(
+                "done",
+                JsonlDone {
+                    status: "ok",
+                    ..JsonlDone::default()
+                },
+            );
+        }
     }
     0
 }
 
-fn run_lint(
+fn run_minimize(
     files: &[PathBuf],
-    format: OutputFormat,
-    deny_warnings: bool,
-    semantic_dup: bool,
+    load: &LoadOptions,
+    symptom: &Symptom,
+    out: Option<&Path>,
 ) -> i32 {
-    let program = match load_program(files) {
+    let program = match load_program(files, load) {
         Ok(program) => program,
         Err(diags) => {
-            emit_error(&diags, format);
-            return 1;
+            let diags = attach_source_if_missing(diags, files);
+            emit_error(&diags, OutputFormat::Text);
+            return failure_exit_code(&diags);
         }
     };
 
-    let mut diagnostics = lint_program(&program, LintOptions { semantic_dup });
-    diagnostics = attach_lint_source_if_missing(diagnostics, files);
+    let report = if *symptom == Symptom::Panic {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        let report = minimize_program(&program, symptom);
+        std::panic::set_hook(hook);
+        report
+    } else {
+        minimize_program(&program, symptom)
+    };
+    let report = match report {
+        Ok(report) => report,
+        Err(diag) => {
+            emit_error(&[diag], OutputFormat::Text);
+            return FailureClass::Io.exit_code();
+        }
+    };
+
+    let keywords = fs::read_to_string(&files[0])
+        .map(|src| surface_keywords(&src))
+        .unwrap_or_default();
+    let rendered = render_program(&report.program, keywords);
+    match out {
+        Some(path) => {
+            if let Err(err) = fs::write(path, rendered) {
+                let diag = Diagnostic::new(
+                    "E-IO",
+                    format!("failed to write {}: {err}", path.display()),
+                    None,
+                );
+                emit_error(&[diag], OutputFormat::Text);
+                return FailureClass::Io.exit_code();
+            }
+        }
+        None => print!("{rendered}"),
+    }
+    eprintln!(
+        "minimized {} -> {} declarations ({} runs, {})",
+        report.original_declarations,
+        report.remaining_declarations,
+        report.runs,
+        symptom.describe()
+    );
+    0
+}
+
+const BENCH_PHASES: [&str; 4] = ["parse", "check", "solve", "prove"];
+
+fn run_bench(
+    files: &[PathBuf],
+    load: &LoadOptions,
+    fixpoint: FixpointOptions<'_>,
+    format: OutputFormat,
+    engine: ProveEngine,
+    iterations: NonZeroUsize,
+    warmup: usize,
+) -> i32 {
+    let mut samples = BENCH_PHASES.map(|_| Vec::with_capacity(iterations.get()));
+    let mut tuples = 0;
+    for round in 0..warmup + iterations.get() {
+        let mut elapsed = [Duration::ZERO; BENCH_PHASES.len()];
+        let result = bench_round(files, load, fixpoint, engine, &mut elapsed);
+        match result {
+            Ok(derived) => tuples = derived,
+            Err(diags) => {
+                let diags = attach_source_if_missing(diags, files);
+                emit_error(&diags, format);
+                return failure_exit_code(&diags);
+            }
+        }
+        if round >= warmup {
+            for (phase, sample) in samples.iter_mut().zip(elapsed) {
+                phase.push(sample);
+            }
+        }
+    }
+
+    let phases = BENCH_PHASES
+        .iter()
+        .zip(&samples)
+        .filter_map(|(phase, samples)| PhaseStats::from_samples(phase, samples))
+        .collect::<Vec<_>>();
+    let solve_micros = phases
+        .iter()
+        .find(|stats| stats.phase == "solve")
+        .map_or(0, |stats| stats.mean_micros);
+    let tuples_per_sec =
+        u64::try_from(tuples as u128 * 1_000_000 / u128::from(solve_micros.max(1)))
+            .unwrap_or(u64::MAX);
+    let fixpoint_stats = JsonFixpointStats {
+        tuples,
+        tuples_per_sec,
+    };
 
     match format {
         OutputFormat::Text => {
-            for diag in &diagnostics {
-                if let Some(source) = &diag.source {
-                    eprintln!(
-                        "{}: {} {} [{}]{}",
-                        source,
-                        diag.severity.as_str(),
-                        diag.lint_code,
-                        diag.category,
-                        format_span(diag.span.as_ref())
-                    );
-                } else {
-                    eprintln!(
-                        "{} {} [{}]{}",
-                        diag.severity.as_str(),
-                        diag.lint_code,
-                        diag.category,
-                        format_span(diag.span.as_ref())
-                    );
-                }
-                eprintln!("  {}", diag.message);
-            }
-            if diagnostics.is_empty() {
-                println!("ok");
+            println!("bench: {} iterations ({warmup} warmup)", iterations.get());
+            let width = BENCH_PHASES
+                .iter()
+                .map(|phase| phase.len())
+                .max()
+                .unwrap_or(0);
+            for stats in &phases {
+                print!("{}", stats.render_text(width));
             }
+            println!(
+                "fixpoint: {} tuples, {} tuples/sec",
+                fixpoint_stats.tuples, fixpoint_stats.tuples_per_sec
+            );
         }
-        OutputFormat::Json => {
-            emit_json(LintJsonResponse {
-                status: if diagnostics.is_empty() {
-                    "ok"
-                } else if deny_warnings {
-                    "error"
-                } else {
-                    "ok"
+        OutputFormat::Json => emit_json(BenchJsonResponse {
+            status: "ok",
+            iterations: iterations.get(),
+            warmup,
+            phases,
+            fixpoint: fixpoint_stats,
+        }),
+        OutputFormat::Jsonl => {
+            for stats in phases {
+                emit_jsonl("phase", stats);
+            }
+            emit_jsonl("fixpoint", fixpoint_stats);
+            emit_jsonl(
+                "done",
+                JsonlDone {
+                    status: "ok",
+                    ..JsonlDone::default()
                 },
-                diagnostics: diagnostics.iter().map(as_json_lint_diagnostic).collect(),
-            });
+            );
         }
     }
+    0
+}
 
-    if deny_warnings && !diagnostics.is_empty() {
-        1
-    } else {
-        0
+fn bench_round(
+    files: &[PathBuf],
+    load: &LoadOptions,
+    fixpoint: FixpointOptions<'_>,
+    engine: ProveEngine,
+    elapsed: &mut [Duration; BENCH_PHASES.len()],
+) -> Result<usize, Vec<Diagnostic>> {
+    let started = Instant::now();
+    let program = load_program(files, load)?;
+    elapsed[0] = started.elapsed();
+
+    let started = Instant::now();
+    check_program(&program)?;
+    elapsed[1] = started.elapsed();
+
+    let kb = fixpoint.knowledge_base(&program)?;
+    let started = Instant::now();
+    let derived = solve_facts(&kb)?;
+    elapsed[2] = started.elapsed();
+
+    let started = Instant::now();
+    prove_with_engine(&program, engine, fixpoint, &mut PhaseTimings::new(), |_| {})?;
+    elapsed[3] = started.elapsed();
+
+    Ok(derived.facts.values().map(|tuples| tuples.len()).sum())
+}
+
+fn partially_evaluate_if(
+    program: Program,
+    enabled: bool,
+    timings: &mut PhaseTimings,
+) -> Result<Program, Vec<Diagnostic>> {
+    if !enabled {
+        return Ok(program);
     }
+    timings.measure("partial-eval", || partially_evaluate(&program))
 }
 
 fn prove_with_engine(
     program: &Program,
     engine: ProveEngine,
+    fixpoint: FixpointOptions<'_>,
+    timings: &mut PhaseTimings,
+    mut on_obligation: impl FnMut(&ObligationTrace),
 ) -> Result<ProofTrace, Vec<Diagnostic>> {
     match engine {
-        ProveEngine::Native => prove_program(program),
-        ProveEngine::Reference => prove_program_reference(program),
+        ProveEngine::Native => prove_program_streaming(program, fixpoint, timings, on_obligation),
+        ProveEngine::Reference => {
+            let trace = timings.measure("prove", || prove_program_reference(program))?;
+            trace.obligations.iter().for_each(&mut on_obligation);
+            Ok(trace)
+        }
     }
 }
 
 fn run_fmt(files: &[PathBuf], check: bool, stdout: bool) -> i32 {
     if stdout && files.len() != 1 {
         eprintln!("E-IO: --stdout requires exactly one input file");
-        return 1;
+        return FailureClass::Io.exit_code();
     }
 
     let mut has_diff = false;
@@ -810,18 +3059,34 @@ fn run_fmt(files: &[PathBuf], check: bool, stdout: bool) -> i32 {
             Ok(src) => src,
             Err(err) => {
                 eprintln!("{}: E-IO: failed to read file: {err}", file.display());
-                return 1;
+                return FailureClass::Io.exit_code();
+            }
+        };
+        let keywords = match load_project_config_near(file) {
+            Ok(config) => config.map(|config| config.keywords).unwrap_or_default(),
+            Err(diags) => {
+                for diag in &diags {
+                    eprintln!("{diag}");
+                }
+                return failure_exit_code(&diags);
             }
         };
-        let formatted = match format_source(&src, FormatOptions::default()) {
+        let formatted = match format_source_with_keywords(&src, FormatOptions::default(), &keywords)
+        {
             Ok(rendered) => rendered,
             Err(diags) => {
-                for diag in diags {
+                for diag in &diags {
                     eprintln!("{}: {}", file.display(), diag);
                 }
-                return 1;
+                return failure_exit_code(&diags);
             }
         };
+        for warning in format_warnings(&src, &keywords) {
+            eprintln!("{}: warning: {}", file.display(), warning);
+        }
+        if check && leaves_core_comments_unformatted(&src, &keywords) {
+            has_diff = true;
+        }
         if formatted != src {
             has_diff = true;
             if !check
@@ -829,7 +3094,7 @@ fn run_fmt(files: &[PathBuf], check: bool, stdout: bool) -> i32 {
                 && let Err(err) = fs::write(file, formatted.as_bytes())
             {
                 eprintln!("{}: E-IO: failed to write file: {err}", file.display());
-                return 1;
+                return FailureClass::Io.exit_code();
             }
         }
         if stdout {
@@ -837,7 +3102,11 @@ fn run_fmt(files: &[PathBuf], check: bool, stdout: bool) -> i32 {
         }
     }
 
-    if check && has_diff { 1 } else { 0 }
+    if check && has_diff {
+        FailureClass::Lint.exit_code()
+    } else {
+        0
+    }
 }
 
 fn as_doc_bundle_format(format: DocFormat) -> DocBundleFormat {
@@ -847,12 +3116,69 @@ fn as_doc_bundle_format(format: DocFormat) -> DocBundleFormat {
     }
 }
 
-fn load_program(files: &[PathBuf]) -> Result<Program, Vec<Diagnostic>> {
-    let mut state = LoadState::new();
+fn load_program(files: &[PathBuf], load: &LoadOptions) -> Result<Program, Vec<Diagnostic>> {
+    let config = load_project_config_for(files)?;
+    let keywords = config
+        .as_ref()
+        .map(|config| config.keywords.clone())
+        .unwrap_or_default();
+    let mut program = load_program_files_with_keywords(files, load, keywords)?;
+    if let Some(config) = &config {
+        config.apply(&mut program);
+    }
+    Ok(program)
+}
+
+fn fact_cache(enabled: bool, files: &[PathBuf]) -> Option<FactCache> {
+    if !enabled {
+        return None;
+    }
+    let start = files
+        .first()
+        .and_then(|file| file.parent())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let root = find_project_config(start)
+        .and_then(|config| config.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."));
+    Some(FactCache::new(&root))
+}
+
+fn load_project_config_for(files: &[PathBuf]) -> Result<Option<ProjectConfig>, Vec<Diagnostic>> {
+    let Some(first) = files.first() else {
+        return Ok(None);
+    };
+    load_project_config_near(first)
+}
+
+fn load_project_config_near(file: &Path) -> Result<Option<ProjectConfig>, Vec<Diagnostic>> {
+    let start = file.parent().unwrap_or(Path::new("."));
+    let start = if start.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        start
+    };
+    match find_project_config(start) {
+        Some(path) => load_project_config(&path).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn load_program_files(files: &[PathBuf], load: &LoadOptions) -> Result<Program, Vec<Diagnostic>> {
+    load_program_files_with_keywords(files, load, SurfaceKeywordMap::default())
+}
+
+fn load_program_files_with_keywords(
+    files: &[PathBuf],
+    load: &LoadOptions,
+    keywords: SurfaceKeywordMap,
+) -> Result<Program, Vec<Diagnostic>> {
+    let mut state = LoadState::new(&load.search_path, keywords);
     for file in files {
         load_program_file(file, &mut state);
     }
     if state.errors.is_empty() {
+        state.merged.select_profiles(&load.profiles);
         Ok(state.merged)
     } else {
         Err(state.errors)
@@ -860,25 +3186,29 @@ fn load_program(files: &[PathBuf]) -> Result<Program, Vec<Diagnostic>> {
 }
 
 #[derive(Debug)]
-struct LoadState {
+struct LoadState<'a> {
     merged: Program,
     errors: Vec<Diagnostic>,
     loaded: HashSet<PathBuf>,
     stack: Vec<PathBuf>,
+    search_path: &'a [PathBuf],
+    keywords: SurfaceKeywordMap,
 }
 
-impl LoadState {
-    fn new() -> Self {
+impl<'a> LoadState<'a> {
+    fn new(search_path: &'a [PathBuf], keywords: SurfaceKeywordMap) -> Self {
         Self {
             merged: Program::new(),
             errors: Vec::new(),
             loaded: HashSet::new(),
             stack: Vec::new(),
+            search_path,
+            keywords,
         }
     }
 }
 
-fn load_program_file(file: &Path, state: &mut LoadState) {
+fn load_program_file(file: &Path, state: &mut LoadState<'_>) {
     let normalized = normalize_path(file);
     if state.loaded.contains(&normalized) {
         return;
@@ -912,7 +3242,11 @@ fn load_program_file(file: &Path, state: &mut LoadState) {
     };
 
     let source = file.display().to_string();
-    let program = match parse_program_with_source(&src, &source) {
+    let options = ParseOptions {
+        source: Some(source.clone()),
+        keywords: state.keywords.clone(),
+    };
+    let program = match parse_program_with_options(&src, &options) {
         Ok(program) => program,
         Err(diags) => {
             state
@@ -923,8 +3257,30 @@ fn load_program_file(file: &Path, state: &mut LoadState) {
         }
     };
 
+    tracing::info!(file = %source, imports = program.imports.len(), "file loaded");
     for import in &program.imports {
-        let path = resolve_import_path(file, &import.path);
+        let path = match resolve_import_path(file, &import.path, state.search_path) {
+            Ok(path) => path,
+            Err(searched) => {
+                state.errors.push(
+                    Diagnostic::new(
+                        "E-IO",
+                        format!(
+                            "import not found: {} (searched: {})",
+                            import.path,
+                            searched
+                                .iter()
+                                .map(|path| path.display().to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                        Some(import.span.clone()),
+                    )
+                    .with_source(source.clone()),
+                );
+                continue;
+            }
+        };
         let norm = normalize_path(&path);
         if state.stack.contains(&norm) {
             state.errors.push(
@@ -943,7 +3299,7 @@ fn load_program_file(file: &Path, state: &mut LoadState) {
         load_program_file(&path, state);
     }
 
-    merge_program(&mut state.merged, program);
+    state.merged.append(program);
     state.loaded.insert(normalized);
     state.stack.pop();
 }
@@ -952,12 +3308,42 @@ fn normalize_path(path: &Path) -> PathBuf {
     fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
 }
 
-fn resolve_import_path(base: &Path, import_path: &str) -> PathBuf {
+#[derive(Debug, Default)]
+struct LoadOptions {
+    search_path: Vec<PathBuf>,
+    profiles: Vec<String>,
+}
+
+impl LoadOptions {
+    fn new(include: Vec<PathBuf>, profiles: Vec<String>) -> Self {
+        let mut search_path = include;
+        if let Some(path) = std::env::var_os("DTL_PATH") {
+            search_path
+                .extend(std::env::split_paths(&path).filter(|root| !root.as_os_str().is_empty()));
+        }
+        Self {
+            search_path,
+            profiles,
+        }
+    }
+}
+
+fn resolve_import_path(
+    base: &Path,
+    import_path: &str,
+    roots: &[PathBuf],
+) -> Result<PathBuf, Vec<PathBuf>> {
     let imported = PathBuf::from(import_path);
     if imported.is_absolute() {
-        imported
-    } else {
-        base.parent().unwrap_or(Path::new(".")).join(imported)
+        return Ok(imported);
+    }
+    let candidates = std::iter::once(base.parent().unwrap_or(Path::new(".")))
+        .chain(roots.iter().map(PathBuf::as_path))
+        .map(|root| root.join(&imported))
+        .collect::<Vec<_>>();
+    match candidates.iter().find(|candidate| candidate.is_file()) {
+        Some(found) => Ok(found.clone()),
+        None => Err(candidates),
     }
 }
 
@@ -977,19 +3363,6 @@ fn render_cycle(stack: &[PathBuf], target: &Path) -> String {
     rendered
 }
 
-fn merge_program(dst: &mut Program, src: Program) {
-    dst.imports.extend(src.imports);
-    dst.aliases.extend(src.aliases);
-    dst.sorts.extend(src.sorts);
-    dst.data_decls.extend(src.data_decls);
-    dst.relations.extend(src.relations);
-    dst.facts.extend(src.facts);
-    dst.rules.extend(src.rules);
-    dst.asserts.extend(src.asserts);
-    dst.universes.extend(src.universes);
-    dst.defns.extend(src.defns);
-}
-
 fn attach_source_if_missing(diags: Vec<Diagnostic>, files: &[PathBuf]) -> Vec<Diagnostic> {
     let single_source = if files.len() == 1 {
         Some(files[0].display().to_string())
@@ -1015,9 +3388,17 @@ fn attach_source_if_missing(diags: Vec<Diagnostic>, files: &[PathBuf]) -> Vec<Di
         .collect()
 }
 
-fn emit_ok(functions_checked: usize, errors: usize, format: OutputFormat) {
+fn emit_ok(
+    functions_checked: usize,
+    errors: usize,
+    format: OutputFormat,
+    timings: Option<PhaseTimings>,
+) {
     match format {
-        OutputFormat::Text => println!("ok"),
+        OutputFormat::Text => {
+            println!("ok");
+            emit_text_timings(timings.as_ref());
+        }
         OutputFormat::Json => emit_json(JsonResponse {
             status: "ok",
             report: Some(JsonReport {
@@ -1025,16 +3406,38 @@ fn emit_ok(functions_checked: usize, errors: usize, format: OutputFormat) {
                 errors,
             }),
             diagnostics: Vec::new(),
+            timings,
         }),
+        OutputFormat::Jsonl => emit_jsonl(
+            "done",
+            JsonlDone {
+                status: "ok",
+                report: Some(JsonReport {
+                    functions_checked,
+                    errors,
+                }),
+                timings,
+                ..JsonlDone::default()
+            },
+        ),
     }
 }
 
 fn emit_error(diags: &[Diagnostic], format: OutputFormat) {
+    emit_error_with_timings(diags, format, None);
+}
+
+fn emit_error_with_timings(
+    diags: &[Diagnostic],
+    format: OutputFormat,
+    timings: Option<PhaseTimings>,
+) {
     match format {
         OutputFormat::Text => {
             for d in diags {
                 eprintln!("{d}");
             }
+            emit_text_timings(timings.as_ref());
         }
         OutputFormat::Json => {
             let diagnostics = diags.iter().map(as_json_diagnostic).collect::<Vec<_>>();
@@ -1042,8 +3445,53 @@ fn emit_error(diags: &[Diagnostic], format: OutputFormat) {
                 status: "error",
                 report: None,
                 diagnostics,
+                timings,
             });
         }
+        OutputFormat::Jsonl => {
+            for diag in diags {
+                emit_jsonl("diagnostic", as_json_diagnostic(diag));
+            }
+            emit_jsonl(
+                "done",
+                JsonlDone {
+                    status: "error",
+                    timings,
+                    ..JsonlDone::default()
+                },
+            );
+        }
+    }
+}
+
+fn emit_prove_response(format: OutputFormat, response: ProveJsonResponse) {
+    if format != OutputFormat::Jsonl {
+        emit_json(response);
+        return;
+    }
+    for diag in response.diagnostics {
+        emit_jsonl("diagnostic", diag);
+    }
+    let proof = response.proof.map(|trace| {
+        for obligation in &trace.obligations {
+            emit_jsonl("obligation", obligation);
+        }
+        trace.summary
+    });
+    emit_jsonl(
+        "done",
+        JsonlDone {
+            status: response.status,
+            proof,
+            timings: response.timings,
+            ..JsonlDone::default()
+        },
+    );
+}
+
+fn emit_text_timings(timings: Option<&PhaseTimings>) {
+    if let Some(timings) = timings {
+        eprint!("{}", timings.render_text());
     }
 }
 
@@ -1052,6 +3500,10 @@ fn emit_json<T: Serialize>(output: T) {
     println!("{rendered}");
 }
 
+fn emit_jsonl<T: Serialize>(event: &'static str, payload: T) {
+    emit_json(JsonlEvent { event, payload });
+}
+
 fn as_json_diagnostic(diag: &Diagnostic) -> JsonDiagnostic {
     JsonDiagnostic {
         code: diag.code,
@@ -1060,7 +3512,26 @@ fn as_json_diagnostic(diag: &Diagnostic) -> JsonDiagnostic {
         reason: diag.reason().map(ToOwned::to_owned),
         arg_indices: diag.arg_indices().map(ToOwned::to_owned),
         hint: diag.hint(),
+        suggestion: diag.suggestion().map(ToOwned::to_owned),
         span: diag.span.as_ref().map(as_json_span),
+        related: diag
+            .related()
+            .iter()
+            .map(|related| JsonRelatedSpan {
+                label: related.label.clone(),
+                source: related.span.file_id.clone(),
+                span: as_json_span(&related.span),
+            })
+            .collect(),
+        fixes: diag
+            .fixes()
+            .iter()
+            .map(|fix| JsonTextEdit {
+                source: fix.span.file_id.clone(),
+                span: as_json_span(&fix.span),
+                replacement: fix.replacement.clone(),
+            })
+            .collect(),
     }
 }
 
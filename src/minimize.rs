@@ -0,0 +1,157 @@
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use crate::ast::Program;
+use crate::diagnostics::Diagnostic;
+use crate::prover::{has_failed_obligation, prove_program};
+use crate::typecheck::check_program;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Symptom {
+    Diagnostic(String),
+    ProofFailure,
+    Panic,
+}
+
+impl Symptom {
+    pub fn describe(&self) -> String {
+        match self {
+            Symptom::Diagnostic(code) => format!("diagnostic {code}"),
+            Symptom::ProofFailure => "proof failure".to_string(),
+            Symptom::Panic => "panic".to_string(),
+        }
+    }
+
+    pub fn reproduces(&self, program: &Program) -> bool {
+        let outcome = catch_unwind(AssertUnwindSafe(|| {
+            check_program(program)?;
+            prove_program(program)
+        }));
+        match (self, outcome) {
+            (Symptom::Panic, outcome) => outcome.is_err(),
+            (_, Err(_)) => false,
+            (Symptom::Diagnostic(code), Ok(Err(diags))) => diags.iter().any(|d| d.code == code),
+            (Symptom::Diagnostic(_), Ok(Ok(_))) => false,
+            (Symptom::ProofFailure, Ok(Ok(trace))) => has_failed_obligation(&trace),
+            (Symptom::ProofFailure, Ok(Err(_))) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MinimizeReport {
+    pub program: Program,
+    pub original_declarations: usize,
+    pub remaining_declarations: usize,
+    pub runs: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decl {
+    Alias(usize),
+    Const(usize),
+    Sort(usize),
+    Data(usize),
+    Relation(usize),
+    Fact(usize),
+    Rule(usize),
+    Assert(usize),
+    Universe(usize),
+    Defn(usize),
+}
+
+fn declarations(program: &Program) -> Vec<Decl> {
+    (0..program.aliases.len())
+        .map(Decl::Alias)
+        .chain((0..program.consts.len()).map(Decl::Const))
+        .chain((0..program.sorts.len()).map(Decl::Sort))
+        .chain((0..program.data_decls.len()).map(Decl::Data))
+        .chain((0..program.relations.len()).map(Decl::Relation))
+        .chain((0..program.facts.len()).map(Decl::Fact))
+        .chain((0..program.rules.len()).map(Decl::Rule))
+        .chain((0..program.asserts.len()).map(Decl::Assert))
+        .chain((0..program.universes.len()).map(Decl::Universe))
+        .chain((0..program.defns.len()).map(Decl::Defn))
+        .collect()
+}
+
+fn subset(program: &Program, decls: &[Decl]) -> Program {
+    let mut out = Program {
+        exprs: program.exprs.clone(),
+        ..Program::new()
+    };
+    for decl in decls {
+        match *decl {
+            Decl::Alias(idx) => out.aliases.push(program.aliases[idx].clone()),
+            Decl::Const(idx) => out.consts.push(program.consts[idx].clone()),
+            Decl::Sort(idx) => out.sorts.push(program.sorts[idx].clone()),
+            Decl::Data(idx) => out.data_decls.push(program.data_decls[idx].clone()),
+            Decl::Relation(idx) => out.relations.push(program.relations[idx].clone()),
+            Decl::Fact(idx) => out.facts.push(program.facts[idx].clone()),
+            Decl::Rule(idx) => out.rules.push(program.rules[idx].clone()),
+            Decl::Assert(idx) => out.asserts.push(program.asserts[idx].clone()),
+            Decl::Universe(idx) => out.universes.push(program.universes[idx].clone()),
+            Decl::Defn(idx) => out.defns.push(program.defns[idx].clone()),
+        }
+    }
+    out
+}
+
+pub fn minimize_program(
+    program: &Program,
+    symptom: &Symptom,
+) -> Result<MinimizeReport, Diagnostic> {
+    let all = declarations(program);
+    let mut runs = 1;
+    if !symptom.reproduces(&subset(program, &all)) {
+        return Err(Diagnostic::new(
+            "E-MINIMIZE",
+            format!(
+                "input does not exhibit the requested symptom: {}",
+                symptom.describe()
+            ),
+            None,
+        ));
+    }
+
+    let mut current = all.clone();
+    let mut granularity = 2;
+    while current.len() >= 2 {
+        let chunk = current.len().div_ceil(granularity);
+        let mut reduced = false;
+        for start in (0..current.len()).step_by(chunk) {
+            let end = (start + chunk).min(current.len());
+            let complement = current[..start]
+                .iter()
+                .chain(&current[end..])
+                .copied()
+                .collect::<Vec<_>>();
+            runs += 1;
+            if symptom.reproduces(&subset(program, &complement)) {
+                current = complement;
+                granularity = (granularity - 1).max(2);
+                reduced = true;
+                break;
+            }
+        }
+        if !reduced {
+            if granularity >= current.len() {
+                break;
+            }
+            granularity = (granularity * 2).min(current.len());
+        }
+    }
+
+    if current.len() == 1 {
+        runs += 1;
+        if symptom.reproduces(&Program::new()) {
+            current.clear();
+        }
+    }
+
+    Ok(MinimizeReport {
+        program: subset(program, &current),
+        original_declarations: all.len(),
+        remaining_declarations: current.len(),
+        runs,
+    })
+}
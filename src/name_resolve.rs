@@ -1,20 +1,69 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::ast::{Defn, Expr, Pattern, Program, Rule, UniverseDecl};
-use crate::diagnostics::Diagnostic;
+use crate::ast::{
+    ConstDecl, DataDecl, Defn, Expr, ExprArena, ExprId, LocalFn, Pattern, PatternId, Program, Rule,
+    UniverseDecl,
+};
+use crate::clauses::desugar_clauses;
+use crate::diagnostics::{Diagnostic, Span, closest_name};
+use crate::interval::{ArithOp, ComparisonOp, is_builtin_operator};
+use crate::logic_engine::DISEQUALITY_PRED;
 use crate::types::{Formula, LogicTerm, Type};
 
 #[derive(Debug, Clone)]
 struct ConstructorSig {
     data_name: String,
     arity: usize,
+    field_data: Vec<Option<String>>,
 }
 
 pub fn normalize_program_aliases(program: &Program) -> Result<Program, Vec<Diagnostic>> {
-    if program.aliases.is_empty() {
-        return Ok(program.clone());
+    let folded;
+    let program = if program.consts.is_empty() {
+        program
+    } else {
+        folded = fold_program_consts(program)?;
+        &folded
+    };
+    let mut normalized = if program.aliases.is_empty() {
+        program.clone()
+    } else {
+        resolve_program_aliases(program)?
+    };
+    desugar_program_clauses(&mut normalized)?;
+    Ok(normalized)
+}
+
+fn desugar_program_clauses(program: &mut Program) -> Result<(), Vec<Diagnostic>> {
+    let mut errors = Vec::new();
+    for defn in &mut program.defns {
+        if defn.clauses.is_empty() {
+            continue;
+        }
+        let desugared = desugar_clauses(
+            &mut program.exprs,
+            &defn.params,
+            &defn.clauses,
+            &program.data_decls,
+        );
+        errors.extend(desugared.errors);
+        for idx in desugared.unreachable {
+            errors.push(Diagnostic::new(
+                "E-MATCH",
+                format!("unreachable clause in {}", defn.name),
+                Some(defn.clauses[idx].span.clone()),
+            ));
+        }
+        defn.body = desugared.body;
     }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
 
+fn resolve_program_aliases(program: &Program) -> Result<Program, Vec<Diagnostic>> {
     let constructor_names = program
         .data_decls
         .iter()
@@ -75,13 +124,19 @@ pub fn normalize_program_aliases(program: &Program) -> Result<Program, Vec<Diagn
         return Err(errors);
     }
 
+    let canonical_names = constructor_names
+        .iter()
+        .chain(&relation_names)
+        .chain(&function_names)
+        .cloned()
+        .collect::<HashSet<_>>();
     let mut resolved_alias_map: HashMap<String, String> = HashMap::new();
     for alias in raw_alias_map.keys() {
         let mut stack = Vec::new();
         resolve_alias_target(
             alias,
             &raw_alias_map,
-            &constructor_names,
+            &canonical_names,
             &mut resolved_alias_map,
             &mut stack,
             &mut errors,
@@ -93,16 +148,23 @@ pub fn normalize_program_aliases(program: &Program) -> Result<Program, Vec<Diagn
 
     let mut normalized = program.clone();
     for fact in &mut normalized.facts {
+        normalize_name_alias(&mut fact.name, &resolved_alias_map);
         for term in &mut fact.terms {
             normalize_logic_term_alias(term, &resolved_alias_map);
         }
     }
     for rule in &mut normalized.rules {
+        normalize_name_alias(&mut rule.head.pred, &resolved_alias_map);
         for term in &mut rule.head.terms {
             normalize_logic_term_alias(term, &resolved_alias_map);
         }
         normalize_formula_alias(&mut rule.body, &resolved_alias_map);
     }
+    for relation in &mut normalized.relations {
+        for refinement in relation.arg_refinements.iter_mut().flatten() {
+            normalize_formula_alias(&mut refinement.formula, &resolved_alias_map);
+        }
+    }
     for assertion in &mut normalized.asserts {
         for param in &mut assertion.params {
             normalize_type_alias(&mut param.ty, &resolved_alias_map);
@@ -119,7 +181,17 @@ pub fn normalize_program_aliases(program: &Program) -> Result<Program, Vec<Diagn
             normalize_type_alias(&mut param.ty, &resolved_alias_map);
         }
         normalize_type_alias(&mut defn.ret_type, &resolved_alias_map);
-        normalize_expr_alias(&mut defn.body, &resolved_alias_map);
+        let exprs = &mut normalized.exprs;
+        normalize_expr_alias(exprs, defn.body, &resolved_alias_map);
+        if let Some(measure) = defn.measure {
+            normalize_expr_alias(exprs, measure, &resolved_alias_map);
+        }
+        for clause in &defn.clauses {
+            for pattern in &clause.patterns {
+                normalize_pattern_alias(exprs, *pattern, &resolved_alias_map);
+            }
+            normalize_expr_alias(exprs, clause.body, &resolved_alias_map);
+        }
     }
 
     Ok(normalized)
@@ -128,7 +200,7 @@ pub fn normalize_program_aliases(program: &Program) -> Result<Program, Vec<Diagn
 fn resolve_alias_target(
     alias: &str,
     raw_alias_map: &HashMap<String, (String, crate::diagnostics::Span)>,
-    constructor_names: &HashSet<String>,
+    canonical_names: &HashSet<String>,
     resolved_alias_map: &mut HashMap<String, String>,
     stack: &mut Vec<String>,
     errors: &mut Vec<Diagnostic>,
@@ -155,13 +227,13 @@ fn resolve_alias_target(
     let (direct_target, span) = raw_alias_map.get(alias)?;
     stack.push(alias.to_string());
 
-    let resolved = if constructor_names.contains(direct_target) {
+    let resolved = if canonical_names.contains(direct_target) {
         Some(direct_target.clone())
     } else if raw_alias_map.contains_key(direct_target) {
         resolve_alias_target(
             direct_target,
             raw_alias_map,
-            constructor_names,
+            canonical_names,
             resolved_alias_map,
             stack,
             errors,
@@ -185,6 +257,12 @@ fn resolve_alias_target(
     resolved
 }
 
+fn normalize_name_alias(name: &mut String, alias_map: &HashMap<String, String>) {
+    if let Some(canonical) = alias_map.get(name) {
+        *name = canonical.clone();
+    }
+}
+
 fn normalize_type_alias(ty: &mut Type, alias_map: &HashMap<String, String>) {
     match ty {
         Type::Fun(args, ret) => {
@@ -197,7 +275,7 @@ fn normalize_type_alias(ty: &mut Type, alias_map: &HashMap<String, String>) {
             normalize_type_alias(base, alias_map);
             normalize_formula_alias(formula, alias_map);
         }
-        Type::Bool | Type::Int | Type::Symbol | Type::Domain(_) | Type::Adt(_) => {}
+        Type::Bool | Type::Int | Type::Symbol | Type::Domain(_) | Type::Adt(_) | Type::Error => {}
     }
 }
 
@@ -205,6 +283,7 @@ fn normalize_formula_alias(formula: &mut Formula, alias_map: &HashMap<String, St
     match formula {
         Formula::True => {}
         Formula::Atom(atom) => {
+            normalize_name_alias(&mut atom.pred, alias_map);
             for term in &mut atom.terms {
                 normalize_logic_term_alias(term, alias_map);
             }
@@ -232,60 +311,396 @@ fn normalize_logic_term_alias(term: &mut LogicTerm, alias_map: &HashMap<String,
     }
 }
 
-fn normalize_expr_alias(expr: &mut Expr, alias_map: &HashMap<String, String>) {
-    match expr {
-        Expr::Var { .. } | Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => {}
-        Expr::Call { name, args, .. } => {
+// Clause bodies may be shared with the compiled defn body and so visited
+// twice; rewriting an alias to its canonical name is idempotent.
+fn normalize_expr_alias(exprs: &mut ExprArena, expr: ExprId, alias_map: &HashMap<String, String>) {
+    match &mut exprs[expr] {
+        Expr::Call { name, .. } => {
             if let Some(canonical) = alias_map.get(name) {
                 *name = canonical.clone();
             }
-            for arg in args {
-                normalize_expr_alias(arg, alias_map);
+        }
+        Expr::LetFn { fns, body, .. } => {
+            let fns = fns
+                .iter()
+                .map(|f| (f.name.clone(), f.body))
+                .collect::<Vec<_>>();
+            let body = *body;
+            let mut local_map = alias_map.clone();
+            for (name, fn_body) in fns {
+                normalize_expr_alias(exprs, fn_body, &local_map);
+                local_map.remove(&name);
             }
+            normalize_expr_alias(exprs, body, &local_map);
+            return;
         }
-        Expr::Let { bindings, body, .. } => {
-            for (_, bexpr, _) in bindings {
-                normalize_expr_alias(bexpr, alias_map);
+        Expr::Match { arms, .. } => {
+            let patterns = arms.iter().map(|arm| arm.pattern).collect::<Vec<_>>();
+            for pattern in patterns {
+                normalize_pattern_alias(exprs, pattern, alias_map);
             }
-            normalize_expr_alias(body, alias_map);
         }
-        Expr::If {
-            cond,
-            then_branch,
-            else_branch,
-            ..
-        } => {
-            normalize_expr_alias(cond, alias_map);
-            normalize_expr_alias(then_branch, alias_map);
-            normalize_expr_alias(else_branch, alias_map);
+        Expr::Var { .. }
+        | Expr::Symbol { .. }
+        | Expr::Int { .. }
+        | Expr::Bool { .. }
+        | Expr::Hole { .. }
+        | Expr::Let { .. }
+        | Expr::If { .. } => {}
+    }
+    for child in exprs[expr].children() {
+        normalize_expr_alias(exprs, child, alias_map);
+    }
+}
+
+fn normalize_pattern_alias(
+    exprs: &mut ExprArena,
+    pattern: PatternId,
+    alias_map: &HashMap<String, String>,
+) {
+    if let Pattern::Ctor { name, args, .. } = &mut exprs[pattern] {
+        if let Some(canonical) = alias_map.get(name) {
+            *name = canonical.clone();
         }
-        Expr::Match {
-            scrutinee, arms, ..
-        } => {
-            normalize_expr_alias(scrutinee, alias_map);
-            for arm in arms {
-                normalize_pattern_alias(&mut arm.pattern, alias_map);
-                normalize_expr_alias(&mut arm.body, alias_map);
+        for arg in args.clone() {
+            normalize_pattern_alias(exprs, arg, alias_map);
+        }
+    }
+}
+
+fn fold_program_consts(program: &Program) -> Result<Program, Vec<Diagnostic>> {
+    let values = const_values(program)?;
+    let mut folded = program.clone();
+    for fact in &mut folded.facts {
+        for term in &mut fact.terms {
+            substitute_term_consts(term, &values);
+        }
+    }
+    for rule in &mut folded.rules {
+        for term in &mut rule.head.terms {
+            substitute_term_consts(term, &values);
+        }
+        substitute_formula_consts(&mut rule.body, &values);
+    }
+    for relation in &mut folded.relations {
+        for refinement in relation.arg_refinements.iter_mut().flatten() {
+            substitute_formula_consts(&mut refinement.formula, &values);
+        }
+    }
+    for assertion in &mut folded.asserts {
+        for param in &mut assertion.params {
+            substitute_type_consts(&mut param.ty, &values);
+        }
+        substitute_formula_consts(&mut assertion.formula, &values);
+    }
+    for universe in &mut folded.universes {
+        for term in &mut universe.values {
+            substitute_term_consts(term, &values);
+        }
+    }
+    for defn in &mut folded.defns {
+        for param in &mut defn.params {
+            substitute_type_consts(&mut param.ty, &values);
+        }
+        substitute_type_consts(&mut defn.ret_type, &values);
+        let exprs = &mut folded.exprs;
+        substitute_expr_consts(exprs, defn.body, &values);
+        if let Some(measure) = defn.measure {
+            substitute_expr_consts(exprs, measure, &values);
+        }
+        for clause in &defn.clauses {
+            substitute_expr_consts(exprs, clause.body, &values);
+        }
+    }
+    Ok(folded)
+}
+
+pub(crate) fn fold_term_consts(
+    program: &Program,
+    term: &mut LogicTerm,
+) -> Result<(), Vec<Diagnostic>> {
+    if !program.consts.is_empty() {
+        substitute_term_consts(term, &const_values(program)?);
+    }
+    Ok(())
+}
+
+fn const_values(program: &Program) -> Result<HashMap<String, LogicTerm>, Vec<Diagnostic>> {
+    let mut errors = Vec::new();
+    let mut taken = HashMap::new();
+    for data in &program.data_decls {
+        for ctor in &data.constructors {
+            taken.insert(ctor.name.as_str(), "constructor");
+        }
+    }
+    for relation in &program.relations {
+        taken.insert(relation.name.as_str(), "relation");
+    }
+    for defn in &program.defns {
+        taken.insert(defn.name.as_str(), "function");
+    }
+    for alias in &program.aliases {
+        taken.insert(alias.alias.as_str(), "alias");
+    }
+    let mut decls: HashMap<&str, &ConstDecl> = HashMap::new();
+    for constant in &program.consts {
+        if let Some(first) = decls.get(constant.name.as_str()) {
+            errors.push(with_first_definition(
+                resolve_error(
+                    format!("duplicate constant declaration: {}", constant.name),
+                    &constant.span,
+                ),
+                Some(&first.span),
+            ));
+            continue;
+        }
+        if is_builtin_operator(&constant.name) {
+            errors.push(builtin_operator_error(
+                &constant.name,
+                "constant",
+                &constant.span,
+            ));
+        } else if let Some(kind) = taken.get(constant.name.as_str()) {
+            errors.push(resolve_error(
+                format!("constant conflicts with {kind}: {}", constant.name),
+                &constant.span,
+            ));
+        }
+        decls.insert(&constant.name, constant);
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut folder = ConstFolder {
+        exprs: &program.exprs,
+        decls,
+        values: HashMap::new(),
+        failed: HashSet::new(),
+        errors: Vec::new(),
+    };
+    for constant in &program.consts {
+        folder.fold_const(&constant.name, &mut Vec::new());
+    }
+    if !folder.errors.is_empty() {
+        return Err(folder.errors);
+    }
+    Ok(folder.values)
+}
+
+struct ConstFolder<'a> {
+    exprs: &'a ExprArena,
+    decls: HashMap<&'a str, &'a ConstDecl>,
+    values: HashMap<String, LogicTerm>,
+    failed: HashSet<String>,
+    errors: Vec<Diagnostic>,
+}
+
+impl ConstFolder<'_> {
+    fn fold_const(&mut self, name: &str, stack: &mut Vec<String>) -> Option<LogicTerm> {
+        if let Some(value) = self.values.get(name) {
+            return Some(value.clone());
+        }
+        if self.failed.contains(name) {
+            return None;
+        }
+        let decl = *self.decls.get(name)?;
+        if let Some(pos) = stack.iter().position(|item| item == name) {
+            let mut cycle = stack[pos..].to_vec();
+            cycle.push(name.to_string());
+            self.errors.push(resolve_error(
+                format!("cyclic constant definition: {}", cycle.join(" -> ")),
+                &decl.span,
+            ));
+            return None;
+        }
+        stack.push(name.to_string());
+        let value = self.fold_expr(decl.value, stack);
+        stack.pop();
+        let Some(value) = value else {
+            self.failed.insert(name.to_string());
+            return None;
+        };
+        self.values.insert(name.to_string(), value.clone());
+        Some(value)
+    }
+
+    fn fold_expr(&mut self, expr: ExprId, stack: &mut Vec<String>) -> Option<LogicTerm> {
+        let exprs = self.exprs;
+        match &exprs[expr] {
+            Expr::Int { value, .. } => Some(LogicTerm::Int(*value)),
+            Expr::Bool { value, .. } => Some(LogicTerm::Bool(*value)),
+            Expr::Symbol { value, .. } if self.decls.contains_key(value.as_str()) => {
+                self.fold_const(value, stack)
+            }
+            Expr::Symbol { value, .. } => Some(LogicTerm::Symbol(value.clone())),
+            Expr::Call {
+                name, args, span, ..
+            } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.fold_expr(*arg, stack))
+                    .collect::<Vec<_>>();
+                let args = args.into_iter().collect::<Option<Vec<_>>>()?;
+                if let Some(op) = ArithOp::from_name(name) {
+                    let [LogicTerm::Int(lhs), LogicTerm::Int(rhs)] = args.as_slice() else {
+                        self.errors.push(Diagnostic::new(
+                            "E-TYPE",
+                            format!("constant arithmetic expects 2 Int operands: {name}"),
+                            Some(span.clone()),
+                        ));
+                        return None;
+                    };
+                    let Some(value) = op.apply(*lhs, *rhs) else {
+                        self.errors.push(Diagnostic::new(
+                            "E-TYPE",
+                            format!("constant arithmetic overflows: ({name} {lhs} {rhs})"),
+                            Some(span.clone()),
+                        ));
+                        return None;
+                    };
+                    return Some(LogicTerm::Int(value));
+                }
+                if ComparisonOp::from_pred(name).is_some() {
+                    self.errors.push(Diagnostic::new(
+                        "E-TYPE",
+                        format!("comparison is not a constant value: {name}"),
+                        Some(span.clone()),
+                    ));
+                    return None;
+                }
+                Some(LogicTerm::Ctor {
+                    name: name.clone(),
+                    args,
+                })
+            }
+            Expr::Var { span, .. }
+            | Expr::Hole { span }
+            | Expr::Let { span, .. }
+            | Expr::LetFn { span, .. }
+            | Expr::If { span, .. }
+            | Expr::Match { span, .. } => {
+                self.errors.push(resolve_error(
+                    "defconst value must be built from literals, constants, constructors and + - *"
+                        .to_string(),
+                    span,
+                ));
+                None
             }
         }
     }
 }
 
-fn normalize_pattern_alias(pattern: &mut Pattern, alias_map: &HashMap<String, String>) {
-    match pattern {
-        Pattern::Ctor { name, args, .. } => {
-            if let Some(canonical) = alias_map.get(name) {
-                *name = canonical.clone();
+fn substitute_type_consts(ty: &mut Type, values: &HashMap<String, LogicTerm>) {
+    match ty {
+        Type::Fun(args, ret) => {
+            for arg in args {
+                substitute_type_consts(arg, values);
             }
+            substitute_type_consts(ret, values);
+        }
+        Type::Refine { base, formula, .. } => {
+            substitute_type_consts(base, values);
+            substitute_formula_consts(formula, values);
+        }
+        Type::Bool | Type::Int | Type::Symbol | Type::Domain(_) | Type::Adt(_) | Type::Error => {}
+    }
+}
+
+fn substitute_formula_consts(formula: &mut Formula, values: &HashMap<String, LogicTerm>) {
+    match formula {
+        Formula::True => {}
+        Formula::Atom(atom) => {
+            for term in &mut atom.terms {
+                substitute_term_consts(term, values);
+            }
+        }
+        Formula::And(items) => {
+            for item in items {
+                substitute_formula_consts(item, values);
+            }
+        }
+        Formula::Not(inner) => substitute_formula_consts(inner, values),
+    }
+}
+
+fn substitute_term_consts(term: &mut LogicTerm, values: &HashMap<String, LogicTerm>) {
+    match term {
+        LogicTerm::Symbol(name) => {
+            if let Some(value) = values.get(name) {
+                *term = value.clone();
+            }
+        }
+        LogicTerm::Ctor { args, .. } => {
             for arg in args {
-                normalize_pattern_alias(arg, alias_map);
+                substitute_term_consts(arg, values);
             }
         }
-        Pattern::Wildcard { .. }
-        | Pattern::Var { .. }
-        | Pattern::Symbol { .. }
-        | Pattern::Int { .. }
-        | Pattern::Bool { .. } => {}
+        LogicTerm::Var(_) | LogicTerm::Int(_) | LogicTerm::Bool(_) => {}
+    }
+}
+
+fn substitute_expr_consts(
+    exprs: &mut ExprArena,
+    expr: ExprId,
+    values: &HashMap<String, LogicTerm>,
+) {
+    match &mut exprs[expr] {
+        Expr::Symbol { value, span } => {
+            if let Some(term) = values.get(value) {
+                let span = span.clone();
+                exprs[expr] = term_to_expr(exprs, term, &span);
+            }
+            return;
+        }
+        Expr::LetFn { fns, .. } => {
+            for f in fns {
+                for param in &mut f.params {
+                    substitute_type_consts(&mut param.ty, values);
+                }
+                substitute_type_consts(&mut f.ret_type, values);
+            }
+        }
+        Expr::Var { .. }
+        | Expr::Int { .. }
+        | Expr::Bool { .. }
+        | Expr::Hole { .. }
+        | Expr::Call { .. }
+        | Expr::Let { .. }
+        | Expr::If { .. }
+        | Expr::Match { .. } => {}
+    }
+    for child in exprs[expr].children() {
+        substitute_expr_consts(exprs, child, values);
+    }
+}
+
+fn term_to_expr(exprs: &mut ExprArena, term: &LogicTerm, span: &Span) -> Expr {
+    match term {
+        LogicTerm::Int(value) => Expr::Int {
+            value: *value,
+            span: span.clone(),
+        },
+        LogicTerm::Bool(value) => Expr::Bool {
+            value: *value,
+            span: span.clone(),
+        },
+        LogicTerm::Symbol(value) | LogicTerm::Var(value) => Expr::Symbol {
+            value: value.clone(),
+            span: span.clone(),
+        },
+        LogicTerm::Ctor { name, args } => Expr::Call {
+            name: name.clone(),
+            name_span: span.clone(),
+            args: args
+                .iter()
+                .map(|arg| {
+                    let arg = term_to_expr(exprs, arg, span);
+                    exprs.alloc(arg)
+                })
+                .collect(),
+            span: span.clone(),
+        },
     }
 }
 
@@ -303,22 +718,41 @@ fn resolve_program_internal(program: &Program) -> Vec<Diagnostic> {
     let mut sort_set = HashSet::new();
     for s in &program.sorts {
         if !sort_set.insert(s.name.clone()) {
-            errors.push(Diagnostic::new(
-                "E-RESOLVE",
-                format!("duplicate sort: {}", s.name),
-                Some(s.span.clone()),
+            errors.push(with_first_definition(
+                Diagnostic::new(
+                    "E-RESOLVE",
+                    format!("duplicate sort: {}", s.name),
+                    Some(s.span.clone()),
+                ),
+                program
+                    .sorts
+                    .iter()
+                    .find(|other| other.name == s.name)
+                    .map(|first| &first.span),
             ));
         }
     }
 
     let mut data_map = HashMap::new();
     let mut constructor_map: HashMap<String, ConstructorSig> = HashMap::new();
+    let data_names = program
+        .data_decls
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect::<HashSet<_>>();
     for d in &program.data_decls {
         if data_map.insert(d.name.clone(), d).is_some() {
-            errors.push(Diagnostic::new(
-                "E-DATA",
-                format!("duplicate data declaration: {}", d.name),
-                Some(d.span.clone()),
+            errors.push(with_first_definition(
+                Diagnostic::new(
+                    "E-DATA",
+                    format!("duplicate data declaration: {}", d.name),
+                    Some(d.span.clone()),
+                ),
+                program
+                    .data_decls
+                    .iter()
+                    .find(|other| other.name == d.name)
+                    .map(|first| &first.span),
             ));
         }
         if sort_set.contains(&d.name) {
@@ -329,12 +763,24 @@ fn resolve_program_internal(program: &Program) -> Vec<Diagnostic> {
             ));
         }
         for ctor in &d.constructors {
+            if is_builtin_operator(&ctor.name) {
+                errors.push(builtin_operator_error(
+                    &ctor.name,
+                    "constructor",
+                    &ctor.span,
+                ));
+            }
             if constructor_map
                 .insert(
                     ctor.name.clone(),
                     ConstructorSig {
                         data_name: d.name.clone(),
                         arity: ctor.fields.len(),
+                        field_data: ctor
+                            .fields
+                            .iter()
+                            .map(|field| field_data_name(field, &data_names))
+                            .collect(),
                     },
                 )
                 .is_some()
@@ -347,41 +793,91 @@ fn resolve_program_internal(program: &Program) -> Vec<Diagnostic> {
             }
         }
     }
+    let uninhabited = uninhabited_data(&program.data_decls, &constructor_map);
+    for d in &program.data_decls {
+        if uninhabited.contains(&d.name) {
+            errors.push(Diagnostic::new(
+                "E-DATA",
+                format!(
+                    "data {} has no finite value: every constructor needs a value of a data type that has none",
+                    d.name
+                ),
+                Some(d.span.clone()),
+            ));
+        }
+    }
 
     let mut relation_arity: HashMap<String, usize> = HashMap::new();
     let mut relation_sorts: HashMap<String, Vec<String>> = HashMap::new();
     for r in &program.relations {
         if relation_arity.contains_key(&r.name) {
-            errors.push(Diagnostic::new(
-                "E-RESOLVE",
-                format!("duplicate relation: {}", r.name),
-                Some(r.span.clone()),
+            errors.push(with_first_definition(
+                Diagnostic::new(
+                    "E-RESOLVE",
+                    format!("duplicate relation: {}", r.name),
+                    Some(r.span.clone()),
+                ),
+                program
+                    .relations
+                    .iter()
+                    .find(|other| other.name == r.name)
+                    .map(|first| &first.span),
             ));
             continue;
         }
+        if is_builtin_operator(&r.name) {
+            errors.push(builtin_operator_error(&r.name, "relation", &r.span));
+        }
         for sort in &r.arg_sorts {
             if !is_known_type_name(sort, &sort_set, &data_map) {
-                errors.push(Diagnostic::new(
-                    "E-RESOLVE",
-                    format!("unknown sort in relation {}: {sort}", r.name),
-                    Some(r.span.clone()),
-                ));
+                errors.push(
+                    Diagnostic::new(
+                        "E-RESOLVE",
+                        format!("unknown sort in relation {}: {sort}", r.name),
+                        Some(r.span.clone()),
+                    )
+                    .with_suggestion(suggest_type_name(sort, &sort_set, &data_map)),
+                );
             }
         }
         relation_arity.insert(r.name.clone(), r.arg_sorts.len());
         relation_sorts.insert(r.name.clone(), r.arg_sorts.clone());
     }
+    for r in &program.relations {
+        for refinement in r.arg_refinements.iter().flatten() {
+            let scope = HashSet::from([refinement.var.clone()]);
+            if let Some(diag) = validate_formula(
+                &refinement.formula,
+                &relation_sorts,
+                &scope,
+                &constructor_map,
+                &r.span,
+            ) {
+                errors.push(diag);
+            }
+        }
+    }
 
     let mut function_sigs: HashMap<String, (Vec<Type>, Type)> = HashMap::new();
     for f in &program.defns {
         if function_sigs.contains_key(&f.name) {
-            errors.push(Diagnostic::new(
-                "E-RESOLVE",
-                format!("duplicate function: {}", f.name),
-                Some(f.span.clone()),
+            errors.push(with_first_definition(
+                Diagnostic::new(
+                    "E-RESOLVE",
+                    format!("duplicate function: {}", f.name),
+                    Some(f.span.clone()),
+                ),
+                program
+                    .defns
+                    .iter()
+                    .find(|other| other.name == f.name)
+                    .map(|first| &first.span),
             ));
             continue;
         }
+        if is_builtin_operator(&f.name) {
+            errors.push(builtin_operator_error(&f.name, "function", &f.span));
+        }
         let mut params = Vec::new();
         for p in &f.params {
             params.push(p.ty.clone());
@@ -391,11 +887,14 @@ fn resolve_program_internal(program: &Program) -> Vec<Diagnostic> {
 
     for fact in &program.facts {
         let Some(arity) = relation_arity.get(&fact.name) else {
-            errors.push(Diagnostic::new(
-                "E-RESOLVE",
-                format!("undefined relation in fact: {}", fact.name),
-                Some(fact.span.clone()),
-            ));
+            errors.push(
+                Diagnostic::new(
+                    "E-RESOLVE",
+                    format!("undefined relation in fact: {}", fact.name),
+                    Some(fact.span.clone()),
+                )
+                .with_suggestion(suggest_name(&fact.name, relation_arity.keys())),
+            );
             continue;
         };
         if *arity != fact.terms.len() {
@@ -429,10 +928,17 @@ fn resolve_program_internal(program: &Program) -> Vec<Diagnostic> {
     let mut assert_names = HashSet::new();
     for assertion in &program.asserts {
         if !assert_names.insert(assertion.name.clone()) {
-            errors.push(Diagnostic::new(
-                "E-RESOLVE",
-                format!("duplicate assert: {}", assertion.name),
-                Some(assertion.span.clone()),
+            errors.push(with_first_definition(
+                Diagnostic::new(
+                    "E-RESOLVE",
+                    format!("duplicate assert: {}", assertion.name),
+                    Some(assertion.span.clone()),
+                ),
+                program
+                    .asserts
+                    .iter()
+                    .find(|other| other.name == assertion.name)
+                    .map(|first| &first.span),
             ));
         }
 
@@ -445,28 +951,26 @@ fn resolve_program_internal(program: &Program) -> Vec<Diagnostic> {
                     Some(p.span.clone()),
                 ));
             }
-            if let Some(msg) = validate_type(
+            if let Some(diag) = validate_type(
                 &p.ty,
                 &sort_set,
                 &data_map,
                 &relation_sorts,
                 &param_names,
                 &constructor_map,
+                &p.span,
             ) {
-                errors.push(Diagnostic::new("E-RESOLVE", msg, Some(p.span.clone())));
+                errors.push(diag);
             }
         }
-        if let Some(msg) = validate_formula(
+        if let Some(diag) = validate_formula(
             &assertion.formula,
             &relation_sorts,
             &param_names,
             &constructor_map,
+            &assertion.span,
         ) {
-            errors.push(Diagnostic::new(
-                "E-RESOLVE",
-                msg,
-                Some(assertion.span.clone()),
-            ));
+            errors.push(diag);
         }
     }
 
@@ -479,15 +983,15 @@ fn resolve_program_internal(program: &Program) -> Vec<Diagnostic> {
     );
 
     for defn in &program.defns {
-        validate_defn(
+        errors.extend(validate_defn(
+            &program.exprs,
             defn,
             &sort_set,
             &data_map,
             &relation_sorts,
             &function_sigs,
             &constructor_map,
-            &mut errors,
-        );
+        ));
     }
 
     errors
@@ -503,11 +1007,14 @@ fn validate_universes(
     let mut seen = HashSet::new();
     for u in universes {
         if !is_known_type_name(&u.ty_name, sort_set, data_map) {
-            errors.push(Diagnostic::new(
-                "E-RESOLVE",
-                format!("unknown universe type: {}", u.ty_name),
-                Some(u.span.clone()),
-            ));
+            errors.push(
+                Diagnostic::new(
+                    "E-RESOLVE",
+                    format!("unknown universe type: {}", u.ty_name),
+                    Some(u.span.clone()),
+                )
+                .with_suggestion(suggest_type_name(&u.ty_name, sort_set, data_map)),
+            );
             continue;
         }
         if !seen.insert(u.ty_name.clone()) {
@@ -593,14 +1100,60 @@ fn validate_adt_value(
             args.len()
         ));
     }
-    for arg in args {
+    for (arg, field_data) in args.iter().zip(&sig.field_data) {
         if logic_term_contains_var(arg) {
             return Some("ADT universe value cannot contain variables".to_string());
         }
+        if let Some(field_data) = field_data
+            && let Some(msg) = validate_adt_value(arg, field_data, constructor_map)
+        {
+            return Some(msg);
+        }
     }
     None
 }
 
+fn field_data_name(ty: &Type, data_names: &HashSet<&str>) -> Option<String> {
+    match ty {
+        Type::Domain(name) | Type::Adt(name) if data_names.contains(name.as_str()) => {
+            Some(name.clone())
+        }
+        Type::Refine { base, .. } => field_data_name(base, data_names),
+        _ => None,
+    }
+}
+
+fn uninhabited_data(
+    data_decls: &[DataDecl],
+    constructor_map: &HashMap<String, ConstructorSig>,
+) -> Vec<String> {
+    let mut inhabited = HashSet::new();
+    loop {
+        let before = inhabited.len();
+        for d in data_decls {
+            let has_base = d.constructors.iter().any(|ctor| {
+                constructor_map.get(&ctor.name).is_some_and(|sig| {
+                    sig.field_data
+                        .iter()
+                        .flatten()
+                        .all(|field| inhabited.contains(field.as_str()))
+                })
+            });
+            if has_base {
+                inhabited.insert(d.name.as_str());
+            }
+        }
+        if inhabited.len() == before {
+            break;
+        }
+    }
+    data_decls
+        .iter()
+        .filter(|d| !inhabited.contains(d.name.as_str()))
+        .map(|d| d.name.clone())
+        .collect()
+}
+
 fn validate_rule(
     rule: &Rule,
     relation_arity: &HashMap<String, usize>,
@@ -608,11 +1161,14 @@ fn validate_rule(
     errors: &mut Vec<Diagnostic>,
 ) {
     let Some(head_arity) = relation_arity.get(&rule.head.pred) else {
-        errors.push(Diagnostic::new(
-            "E-RESOLVE",
-            format!("undefined relation in rule head: {}", rule.head.pred),
-            Some(rule.span.clone()),
-        ));
+        errors.push(
+            Diagnostic::new(
+                "E-RESOLVE",
+                format!("undefined relation in rule head: {}", rule.head.pred),
+                Some(rule.span.clone()),
+            )
+            .with_suggestion(suggest_name(&rule.head.pred, relation_arity.keys())),
+        );
         return;
     };
 
@@ -636,14 +1192,42 @@ fn validate_rule(
     let mut positives = Vec::new();
     let mut negatives = Vec::new();
     flatten_body(&rule.body, false, &mut positives, &mut negatives);
+    let mut disequalities = Vec::new();
+    for atoms in [&mut positives, &mut negatives] {
+        atoms.retain(|atom| {
+            let builtin = atom.pred == DISEQUALITY_PRED;
+            if builtin {
+                disequalities.push(*atom);
+            }
+            !builtin
+        });
+    }
+
+    for atom in &disequalities {
+        if atom.terms.len() != 2 {
+            errors.push(resolve_error(
+                format!(
+                    "{DISEQUALITY_PRED} expects 2 terms, got {}",
+                    atom.terms.len()
+                ),
+                &rule.span,
+            ));
+        }
+        for term in &atom.terms {
+            validate_constructor_term(term, constructor_map, errors, &rule.span);
+        }
+    }
 
     for atom in positives.iter().chain(negatives.iter()) {
         let Some(arity) = relation_arity.get(&atom.pred) else {
-            errors.push(Diagnostic::new(
-                "E-RESOLVE",
-                format!("undefined relation in rule body: {}", atom.pred),
-                Some(rule.span.clone()),
-            ));
+            errors.push(
+                Diagnostic::new(
+                    "E-RESOLVE",
+                    format!("undefined relation in rule body: {}", atom.pred),
+                    Some(rule.span.clone()),
+                )
+                .with_suggestion(suggest_name(&atom.pred, relation_arity.keys())),
+            );
             continue;
         };
         if *arity != atom.terms.len() {
@@ -671,8 +1255,25 @@ fn validate_rule(
         }
     }
 
-    for term in &rule.head.terms {
-        check_all_vars_bound(term, &positive_vars, errors, &rule.span, true);
+    if rule.skolem.is_some() {
+        let mut head_vars = HashSet::new();
+        for term in &rule.head.terms {
+            collect_vars_in_term(term, &mut head_vars);
+        }
+        if head_vars.is_subset(&positive_vars) {
+            errors.push(Diagnostic::new(
+                "E-RESOLVE",
+                format!(
+                    "rule for {} declares :skolem but binds every head variable in its positive body",
+                    rule.head.pred
+                ),
+                Some(rule.span.clone()),
+            ));
+        }
+    } else {
+        for term in &rule.head.terms {
+            check_all_vars_bound(term, &positive_vars, errors, &rule.span, true);
+        }
     }
 
     for atom in &negatives {
@@ -680,6 +1281,23 @@ fn validate_rule(
             check_all_vars_bound(term, &positive_vars, errors, &rule.span, false);
         }
     }
+
+    for atom in &disequalities {
+        let mut vars = HashSet::new();
+        for term in &atom.terms {
+            collect_vars_in_term(term, &mut vars);
+        }
+        let mut unbound = vars.difference(&positive_vars).collect::<Vec<_>>();
+        unbound.sort();
+        for v in unbound {
+            errors.push(resolve_error(
+                format!(
+                    "unsafe rule: {DISEQUALITY_PRED} variable ?{v} is not bound in positive body"
+                ),
+                &rule.span,
+            ));
+        }
+    }
 }
 
 fn check_all_vars_bound(
@@ -716,14 +1334,15 @@ fn check_all_vars_bound(
 }
 
 fn validate_defn(
+    exprs: &ExprArena,
     defn: &Defn,
     sort_set: &HashSet<String>,
     data_map: &HashMap<String, &crate::ast::DataDecl>,
     relation_sorts: &HashMap<String, Vec<String>>,
     function_sigs: &HashMap<String, (Vec<Type>, Type)>,
     constructor_map: &HashMap<String, ConstructorSig>,
-    errors: &mut Vec<Diagnostic>,
-) {
+) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
     let mut param_names = HashSet::new();
     for p in &defn.params {
         if !param_names.insert(p.name.clone()) {
@@ -733,37 +1352,76 @@ fn validate_defn(
                 Some(p.span.clone()),
             ));
         }
-        if let Some(msg) = validate_type(
+        if let Some(diag) = validate_type(
             &p.ty,
             sort_set,
             data_map,
             relation_sorts,
             &param_names,
             constructor_map,
+            &p.span,
         ) {
-            errors.push(Diagnostic::new("E-RESOLVE", msg, Some(p.span.clone())));
+            errors.push(diag);
         }
     }
 
-    if let Some(msg) = validate_type(
+    if let Some(diag) = validate_type(
         &defn.ret_type,
         sort_set,
         data_map,
         relation_sorts,
         &param_names,
         constructor_map,
+        &defn.span,
     ) {
-        errors.push(Diagnostic::new("E-RESOLVE", msg, Some(defn.span.clone())));
+        errors.push(diag);
     }
 
-    validate_expr_names(
-        &defn.body,
-        &param_names,
-        function_sigs,
-        relation_sorts,
-        constructor_map,
-        errors,
-    );
+    let mut local_fns = Vec::new();
+    collect_local_fns(exprs, defn.body, &mut local_fns);
+    for f in local_fns {
+        let param_names = f.params.iter().map(|p| p.name.clone()).collect();
+        for (ty, span) in f
+            .params
+            .iter()
+            .map(|p| (&p.ty, &p.span))
+            .chain([(&f.ret_type, &f.span)])
+        {
+            if let Some(diag) = validate_type(
+                ty,
+                sort_set,
+                data_map,
+                relation_sorts,
+                &param_names,
+                constructor_map,
+                span,
+            ) {
+                errors.push(diag);
+            }
+        }
+    }
+
+    for expr in std::iter::once(defn.body).chain(defn.measure) {
+        validate_expr_names(
+            exprs,
+            expr,
+            &param_names,
+            function_sigs,
+            relation_sorts,
+            constructor_map,
+            &mut errors,
+        );
+    }
+    errors
+}
+
+fn collect_local_fns<'a>(exprs: &'a ExprArena, expr: ExprId, out: &mut Vec<&'a LocalFn>) {
+    if let Expr::LetFn { fns, .. } = &exprs[expr] {
+        out.extend(fns);
+    }
+    for child in exprs[expr].children() {
+        collect_local_fns(exprs, child, out);
+    }
 }
 
 fn validate_type(
@@ -773,34 +1431,42 @@ fn validate_type(
     relation_sorts: &HashMap<String, Vec<String>>,
     scope: &HashSet<String>,
     constructor_map: &HashMap<String, ConstructorSig>,
-) -> Option<String> {
+    span: &Span,
+) -> Option<Diagnostic> {
     match ty {
-        Type::Bool | Type::Int | Type::Symbol => None,
+        Type::Bool | Type::Int | Type::Symbol | Type::Error => None,
         Type::Domain(s) => {
             if is_known_type_name(s, sort_set, data_map) {
                 None
             } else {
-                Some(format!("unknown type: {s}"))
+                Some(
+                    resolve_error(format!("unknown type: {s}"), span)
+                        .with_suggestion(suggest_type_name(s, sort_set, data_map)),
+                )
             }
         }
         Type::Adt(s) => {
             if data_map.contains_key(s) {
                 None
             } else {
-                Some(format!("unknown ADT type: {s}"))
+                Some(
+                    resolve_error(format!("unknown ADT type: {s}"), span)
+                        .with_suggestion(suggest_name(s, data_map.keys())),
+                )
             }
         }
         Type::Fun(args, ret) => {
             for a in args {
-                if let Some(msg) = validate_type(
+                if let Some(diag) = validate_type(
                     a,
                     sort_set,
                     data_map,
                     relation_sorts,
                     scope,
                     constructor_map,
+                    span,
                 ) {
-                    return Some(msg);
+                    return Some(diag);
                 }
             }
             validate_type(
@@ -810,22 +1476,24 @@ fn validate_type(
                 relation_sorts,
                 scope,
                 constructor_map,
+                span,
             )
         }
         Type::Refine { var, base, formula } => {
-            if let Some(msg) = validate_type(
+            if let Some(diag) = validate_type(
                 base,
                 sort_set,
                 data_map,
                 relation_sorts,
                 scope,
                 constructor_map,
+                span,
             ) {
-                return Some(msg);
+                return Some(diag);
             }
             let mut next_scope = scope.clone();
             next_scope.insert(var.clone());
-            validate_formula(formula, relation_sorts, &next_scope, constructor_map)
+            validate_formula(formula, relation_sorts, &next_scope, constructor_map, span)
         }
     }
 }
@@ -835,92 +1503,132 @@ fn validate_formula(
     relation_sorts: &HashMap<String, Vec<String>>,
     scope: &HashSet<String>,
     constructor_map: &HashMap<String, ConstructorSig>,
-) -> Option<String> {
+    span: &Span,
+) -> Option<Diagnostic> {
     match formula {
         Formula::True => None,
+        Formula::Atom(atom) if ComparisonOp::from_pred(&atom.pred).is_some() => {
+            let operands_ok = atom.terms.len() == 2
+                && atom.terms.iter().all(|term| match term {
+                    LogicTerm::Int(_) => true,
+                    LogicTerm::Var(v) => scope.contains(v),
+                    _ => false,
+                });
+            if operands_ok {
+                None
+            } else {
+                Some(resolve_error(
+                    format!(
+                        "comparison {} expects two Int literals or variables",
+                        atom.pred
+                    ),
+                    span,
+                ))
+            }
+        }
+        Formula::Atom(atom) if atom.pred == DISEQUALITY_PRED => {
+            if atom.terms.len() != 2 {
+                return Some(resolve_error(
+                    format!(
+                        "{DISEQUALITY_PRED} expects 2 terms, got {}",
+                        atom.terms.len()
+                    ),
+                    span,
+                ));
+            }
+            atom.terms
+                .iter()
+                .find_map(|term| validate_formula_term(term, scope, constructor_map, span))
+        }
         Formula::Atom(atom) => {
             let Some(sorts) = relation_sorts.get(&atom.pred) else {
-                return Some(format!(
-                    "unknown predicate in refinement/assert: {}",
-                    atom.pred
-                ));
+                return Some(
+                    resolve_error(
+                        format!("unknown predicate in refinement/assert: {}", atom.pred),
+                        span,
+                    )
+                    .with_suggestion(suggest_name(&atom.pred, relation_sorts.keys())),
+                );
             };
             if sorts.len() != atom.terms.len() {
-                return Some(format!(
-                    "arity mismatch in predicate {}: expected {}, got {}",
-                    atom.pred,
-                    sorts.len(),
-                    atom.terms.len()
+                return Some(resolve_error(
+                    format!(
+                        "arity mismatch in predicate {}: expected {}, got {}",
+                        atom.pred,
+                        sorts.len(),
+                        atom.terms.len()
+                    ),
+                    span,
                 ));
             }
             for t in &atom.terms {
-                match t {
-                    LogicTerm::Var(v) if !scope.contains(v) => {
-                        return Some(format!("unknown variable in formula: {v}"));
-                    }
-                    LogicTerm::Ctor { name, args } => {
-                        let Some(sig) = constructor_map.get(name) else {
-                            return Some(format!("unknown constructor in formula: {name}"));
-                        };
-                        if sig.arity != args.len() {
-                            return Some(format!(
-                                "constructor {} arity mismatch: expected {}, got {}",
-                                name,
-                                sig.arity,
-                                args.len()
-                            ));
-                        }
-                        for arg in args {
-                            if let Some(msg) = validate_formula_term(arg, scope, constructor_map) {
-                                return Some(msg);
-                            }
-                        }
-                    }
-                    _ => {}
+                if let Some(diag) = validate_formula_term(t, scope, constructor_map, span) {
+                    return Some(diag);
                 }
             }
             None
         }
         Formula::And(items) => {
             for item in items {
-                if let Some(msg) = validate_formula(item, relation_sorts, scope, constructor_map) {
-                    return Some(msg);
+                if let Some(diag) =
+                    validate_formula(item, relation_sorts, scope, constructor_map, span)
+                {
+                    return Some(diag);
                 }
             }
             None
         }
-        Formula::Not(inner) => validate_formula(inner, relation_sorts, scope, constructor_map),
+        Formula::Not(inner) => {
+            validate_formula(inner, relation_sorts, scope, constructor_map, span)
+        }
     }
 }
 
+fn builtin_operator_error(name: &str, kind: &str, span: &Span) -> Diagnostic {
+    resolve_error(
+        format!("{name} is a builtin operator and cannot be declared as a {kind}"),
+        span,
+    )
+}
+
 fn validate_formula_term(
     term: &LogicTerm,
     scope: &HashSet<String>,
     constructor_map: &HashMap<String, ConstructorSig>,
-) -> Option<String> {
+    span: &Span,
+) -> Option<Diagnostic> {
     match term {
         LogicTerm::Var(v) => {
             if scope.contains(v) {
                 None
             } else {
-                Some(format!("unknown variable in formula: {v}"))
+                Some(
+                    resolve_error(format!("unknown variable in formula: {v}"), span)
+                        .with_suggestion(suggest_name(v, scope)),
+                )
             }
         }
         LogicTerm::Ctor { name, args } => {
             let Some(sig) = constructor_map.get(name) else {
-                return Some(format!("unknown constructor in formula: {name}"));
+                return Some(
+                    resolve_error(format!("unknown constructor in formula: {name}"), span)
+                        .with_suggestion(suggest_name(name, constructor_map.keys())),
+                );
             };
             if sig.arity != args.len() {
-                return Some(format!(
-                    "constructor {} arity mismatch: expected {}, got {}",
-                    name,
-                    sig.arity,
-                    args.len()
+                return Some(resolve_error(
+                    format!(
+                        "constructor {} arity mismatch: expected {}, got {}",
+                        name,
+                        sig.arity,
+                        args.len()
+                    ),
+                    span,
                 ));
             }
             for arg in args {
-                if let Some(msg) = validate_formula_term(arg, scope, constructor_map) {
-                    return Some(msg);
+                if let Some(diag) = validate_formula_term(arg, scope, constructor_map, span) {
+                    return Some(diag);
                 }
             }
             None
@@ -930,38 +1638,60 @@ fn validate_formula_term(
 }
 
 fn validate_expr_names(
-    expr: &Expr,
+    exprs: &ExprArena,
+    expr: ExprId,
     scope: &HashSet<String>,
     function_sigs: &HashMap<String, (Vec<Type>, Type)>,
     relation_sorts: &HashMap<String, Vec<String>>,
     constructor_map: &HashMap<String, ConstructorSig>,
     errors: &mut Vec<Diagnostic>,
 ) {
-    match expr {
+    match &exprs[expr] {
         Expr::Var { name, span } => {
             if !scope.contains(name) {
-                errors.push(Diagnostic::new(
-                    "E-RESOLVE",
-                    format!("unknown variable: {name}"),
-                    Some(span.clone()),
-                ));
+                errors.push(
+                    Diagnostic::new(
+                        "E-RESOLVE",
+                        format!("unknown variable: {name}"),
+                        Some(span.clone()),
+                    )
+                    .with_suggestion(suggest_name(name, scope))
+                    .with_suggestion_fix(span),
+                );
             }
         }
-        Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => {}
-        Expr::Call { name, args, span } => {
+        Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } | Expr::Hole { .. } => {}
+        Expr::Call {
+            name,
+            name_span,
+            args,
+            span,
+        } => {
             if !function_sigs.contains_key(name)
                 && !relation_sorts.contains_key(name)
                 && !constructor_map.contains_key(name)
+                && ArithOp::from_name(name).is_none()
             {
-                errors.push(Diagnostic::new(
-                    "E-RESOLVE",
-                    format!("unknown function/relation/constructor: {name}"),
-                    Some(span.clone()),
-                ));
+                errors.push(
+                    Diagnostic::new(
+                        "E-RESOLVE",
+                        format!("unknown function/relation/constructor: {name}"),
+                        Some(span.clone()),
+                    )
+                    .with_suggestion(suggest_name(
+                        name,
+                        function_sigs
+                            .keys()
+                            .chain(relation_sorts.keys())
+                            .chain(constructor_map.keys()),
+                    ))
+                    .with_suggestion_fix(name_span),
+                );
             }
             for arg in args {
                 validate_expr_names(
-                    arg,
+                    exprs,
+                    *arg,
                     scope,
                     function_sigs,
                     relation_sorts,
@@ -978,7 +1708,8 @@ fn validate_expr_names(
             let mut local_scope = scope.clone();
             for (name, bexpr, bspan) in bindings {
                 validate_expr_names(
-                    bexpr,
+                    exprs,
+                    *bexpr,
                     &local_scope,
                     function_sigs,
                     relation_sorts,
@@ -995,7 +1726,8 @@ fn validate_expr_names(
                 local_scope.insert(name.clone());
             }
             validate_expr_names(
-                body,
+                exprs,
+                *body,
                 &local_scope,
                 function_sigs,
                 relation_sorts,
@@ -1003,6 +1735,60 @@ fn validate_expr_names(
                 errors,
             );
         }
+        Expr::LetFn { fns, body, .. } => {
+            let mut local_sigs = function_sigs.clone();
+            let mut local_scope = scope.clone();
+            for f in fns {
+                if local_scope.contains(&f.name)
+                    || local_sigs.contains_key(&f.name)
+                    || relation_sorts.contains_key(&f.name)
+                    || constructor_map.contains_key(&f.name)
+                    || ArithOp::from_name(&f.name).is_some()
+                {
+                    errors.push(Diagnostic::new(
+                        "E-RESOLVE",
+                        format!("local function shadows an existing name: {}", f.name),
+                        Some(f.span.clone()),
+                    ));
+                }
+                let mut fn_scope = local_scope.clone();
+                for p in &f.params {
+                    if !fn_scope.insert(p.name.clone()) {
+                        errors.push(Diagnostic::new(
+                            "E-RESOLVE",
+                            format!("duplicate or shadowed parameter name: {}", p.name),
+                            Some(p.span.clone()),
+                        ));
+                    }
+                }
+                validate_expr_names(
+                    exprs,
+                    f.body,
+                    &fn_scope,
+                    &local_sigs,
+                    relation_sorts,
+                    constructor_map,
+                    errors,
+                );
+                local_sigs.insert(
+                    f.name.clone(),
+                    (
+                        f.params.iter().map(|p| p.ty.clone()).collect(),
+                        f.ret_type.clone(),
+                    ),
+                );
+                local_scope.insert(f.name.clone());
+            }
+            validate_expr_names(
+                exprs,
+                *body,
+                &local_scope,
+                &local_sigs,
+                relation_sorts,
+                constructor_map,
+                errors,
+            );
+        }
         Expr::If {
             cond,
             then_branch,
@@ -1010,7 +1796,8 @@ fn validate_expr_names(
             ..
         } => {
             validate_expr_names(
-                cond,
+                exprs,
+                *cond,
                 scope,
                 function_sigs,
                 relation_sorts,
@@ -1018,7 +1805,8 @@ fn validate_expr_names(
                 errors,
             );
             validate_expr_names(
-                then_branch,
+                exprs,
+                *then_branch,
                 scope,
                 function_sigs,
                 relation_sorts,
@@ -1026,7 +1814,8 @@ fn validate_expr_names(
                 errors,
             );
             validate_expr_names(
-                else_branch,
+                exprs,
+                *else_branch,
                 scope,
                 function_sigs,
                 relation_sorts,
@@ -1038,7 +1827,8 @@ fn validate_expr_names(
             scrutinee, arms, ..
         } => {
             validate_expr_names(
-                scrutinee,
+                exprs,
+                *scrutinee,
                 scope,
                 function_sigs,
                 relation_sorts,
@@ -1048,10 +1838,11 @@ fn validate_expr_names(
             for arm in arms {
                 let mut arm_scope = scope.clone();
                 let mut vars = HashSet::new();
-                validate_pattern_names(&arm.pattern, constructor_map, &mut vars, errors);
+                validate_pattern_names(exprs, arm.pattern, constructor_map, &mut vars, errors);
                 arm_scope.extend(vars);
                 validate_expr_names(
-                    &arm.body,
+                    exprs,
+                    arm.body,
                     &arm_scope,
                     function_sigs,
                     relation_sorts,
@@ -1064,12 +1855,13 @@ fn validate_expr_names(
 }
 
 fn validate_pattern_names(
-    pattern: &Pattern,
+    exprs: &ExprArena,
+    pattern: PatternId,
     constructor_map: &HashMap<String, ConstructorSig>,
     vars: &mut HashSet<String>,
     errors: &mut Vec<Diagnostic>,
 ) {
-    match pattern {
+    match &exprs[pattern] {
         Pattern::Wildcard { .. }
         | Pattern::Symbol { .. }
         | Pattern::Int { .. }
@@ -1086,11 +1878,14 @@ fn validate_pattern_names(
         }
         Pattern::Ctor { name, args, span } => {
             let Some(sig) = constructor_map.get(name) else {
-                errors.push(Diagnostic::new(
-                    "E-RESOLVE",
-                    format!("unknown constructor in pattern: {name}"),
-                    Some(span.clone()),
-                ));
+                errors.push(
+                    Diagnostic::new(
+                        "E-RESOLVE",
+                        format!("unknown constructor in pattern: {name}"),
+                        Some(span.clone()),
+                    )
+                    .with_suggestion(suggest_name(name, constructor_map.keys())),
+                );
                 return;
             };
             if sig.arity != args.len() {
@@ -1106,7 +1901,7 @@ fn validate_pattern_names(
                 ));
             }
             for arg in args {
-                validate_pattern_names(arg, constructor_map, vars, errors);
+                validate_pattern_names(exprs, *arg, constructor_map, vars, errors);
             }
         }
     }
@@ -1145,11 +1940,14 @@ fn validate_constructor_term(
     match term {
         LogicTerm::Ctor { name, args } => {
             let Some(sig) = constructor_map.get(name) else {
-                errors.push(Diagnostic::new(
-                    "E-RESOLVE",
-                    format!("unknown constructor: {name}"),
-                    Some(span.clone()),
-                ));
+                errors.push(
+                    Diagnostic::new(
+                        "E-RESOLVE",
+                        format!("unknown constructor: {name}"),
+                        Some(span.clone()),
+                    )
+                    .with_suggestion(suggest_name(name, constructor_map.keys())),
+                );
                 return;
             };
             if sig.arity != args.len() {
@@ -1194,6 +1992,38 @@ fn logic_term_contains_var(term: &LogicTerm) -> bool {
     }
 }
 
+fn with_first_definition(diag: Diagnostic, first: Option<&Span>) -> Diagnostic {
+    match first {
+        Some(span) => diag.with_related(span.clone(), "first defined here"),
+        None => diag,
+    }
+}
+
+fn resolve_error(message: String, span: &Span) -> Diagnostic {
+    Diagnostic::new("E-RESOLVE", message, Some(span.clone()))
+}
+
+fn suggest_name<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a String>,
+) -> Option<String> {
+    closest_name(name, candidates.into_iter().map(String::as_str))
+}
+
+fn suggest_type_name(
+    name: &str,
+    sort_set: &HashSet<String>,
+    data_map: &HashMap<String, &crate::ast::DataDecl>,
+) -> Option<String> {
+    closest_name(
+        name,
+        ["Bool", "Int", "Symbol"]
+            .into_iter()
+            .chain(sort_set.iter().map(String::as_str))
+            .chain(data_map.keys().map(String::as_str)),
+    )
+}
+
 fn is_known_type_name(
     name: &str,
     sort_set: &HashSet<String>,
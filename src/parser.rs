@@ -1,15 +1,72 @@
-use std::collections::HashSet;
-use std::iter::Peekable;
-use std::str::CharIndices;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::{Deref, Range};
 
 use crate::ast::{
-    AliasDecl, AssertDecl, ConstructorDecl, DataDecl, Defn, Expr, Fact, ImportDecl, MatchArm,
-    Param, Pattern, Program, RelationDecl, Rule, SortDecl, UniverseDecl,
+    AliasDecl, ArgRefinement, AssertDecl, Clause, ConstDecl, ConstructorDecl, DataDecl, Defn, Expr,
+    ExprArena, ExprId, Fact, ImportDecl, LocalFn, MatchArm, NameUse, Param, Pattern, PatternId,
+    Program, RelationDecl, Rule, SortDecl, UniverseDecl,
 };
-use crate::diagnostics::{Diagnostic, make_span};
+use crate::clauses::desugar_clauses;
+use crate::diagnostics::{Diagnostic, LineIndex, Span, dedup_diagnostics};
+use crate::edition::{
+    Edition, EditionFeature, edition_from_pragma, feature_unavailable, source_edition,
+};
+use crate::grammar;
+use crate::logic_engine::DISEQUALITY_PRED;
+use crate::macros::expand_forms;
 use crate::types::{Atom, Formula, LogicTerm, Type};
 use unicode_normalization::UnicodeNormalization;
 
+pub(crate) struct Source<'a> {
+    text: &'a str,
+    line_index: LineIndex,
+    edition: Edition,
+    name_uses: RefCell<Vec<NameUse>>,
+}
+
+impl<'a> Source<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            line_index: LineIndex::new(text),
+            edition: source_edition(text),
+            name_uses: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn use_name(&self, name: &str, start: usize, end: usize) {
+        self.name_uses.borrow_mut().push(NameUse {
+            name: name.to_string(),
+            span: make_span(self, start, end),
+        });
+    }
+}
+
+impl Deref for Source<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.text
+    }
+}
+
+pub(crate) fn make_span(src: &Source<'_>, start: usize, end: usize) -> Span {
+    src.line_index.span(src.text, start, end)
+}
+
+pub(crate) fn require_feature(
+    src: &Source<'_>,
+    feature: EditionFeature,
+    node: &SExpr,
+) -> Result<(), Diagnostic> {
+    if src.edition.supports(feature) {
+        return Ok(());
+    }
+    let (start, end) = node.span_bounds();
+    Err(feature_unavailable(src, feature, src.edition, start, end))
+}
+
 #[derive(Debug, Clone)]
 struct Token {
     kind: TokenKind,
@@ -22,22 +79,23 @@ enum TokenKind {
     LParen,
     RParen,
     Atom(String),
+    DatumComment,
 }
 
 #[derive(Debug, Clone)]
-enum SExpr {
+pub(crate) enum SExpr {
     Atom(String, usize, usize),
     List(Vec<SExpr>, usize, usize),
 }
 
 impl SExpr {
-    fn span_bounds(&self) -> (usize, usize) {
+    pub(crate) fn span_bounds(&self) -> (usize, usize) {
         match self {
             SExpr::Atom(_, s, e) | SExpr::List(_, s, e) => (*s, *e),
         }
     }
 
-    fn as_atom(&self) -> Option<&str> {
+    pub(crate) fn as_atom(&self) -> Option<&str> {
         match self {
             SExpr::Atom(s, _, _) => Some(s),
             SExpr::List(_, _, _) => None,
@@ -45,61 +103,262 @@ impl SExpr {
     }
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SurfaceKeywordMap {
+    entries: BTreeMap<String, String>,
+}
+
+impl SurfaceKeywordMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, word: &str, canonical: &str) -> Result<(), String> {
+        let word: String = word.trim().nfc().collect();
+        let canonical: String = canonical.trim().nfc().collect();
+        if word.is_empty() {
+            return Err("surface keyword must not be empty".to_string());
+        }
+        if canonical_surface_head(&word).is_some() || grammar::is_surface_tag(&word) {
+            return Err(format!(
+                "surface keyword shadows a built-in keyword: {word}"
+            ));
+        }
+        if canonical.starts_with(':') {
+            if !grammar::is_surface_tag(&canonical) {
+                return Err(format!("unknown surface tag: {canonical}"));
+            }
+            if !word.starts_with(':') {
+                return Err(format!(
+                    "surface keyword for tag {canonical} must start with ':': {word}"
+                ));
+            }
+        } else {
+            if canonical_surface_head(&canonical).is_none() {
+                return Err(format!("unknown surface head: {canonical}"));
+            }
+            if word.starts_with(':') {
+                return Err(format!(
+                    "surface keyword for head {canonical} must not start with ':': {word}"
+                ));
+            }
+        }
+        self.entries.insert(word, canonical);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn canonical(&self, word: &str) -> Option<&str> {
+        self.entries.get(word).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(word, canonical)| (word.as_str(), canonical.as_str()))
+    }
+
+    pub fn word_for(&self, canonical: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(_, target)| target.as_str() == canonical)
+            .map(|(word, _)| word.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub source: Option<String>,
+    pub keywords: SurfaceKeywordMap,
+}
+
 pub fn parse_program(src: &str) -> Result<Program, Vec<Diagnostic>> {
-    parse_program_impl(src)
+    parse_program_impl(src, &SurfaceKeywordMap::default())
 }
 
 pub fn parse_program_with_source(src: &str, source: &str) -> Result<Program, Vec<Diagnostic>> {
-    let mut program = parse_program_impl(src)?;
+    let mut program = parse_program_impl(src, &SurfaceKeywordMap::default())?;
     attach_source_to_program_spans(&mut program, source);
     Ok(program)
 }
 
-fn parse_program_impl(src: &str) -> Result<Program, Vec<Diagnostic>> {
-    let tokens = lex(src)?;
-    let sexprs = parse_sexprs(src, &tokens)?;
+pub fn parse_program_with_options(
+    src: &str,
+    options: &ParseOptions,
+) -> Result<Program, Vec<Diagnostic>> {
+    let mut program = parse_program_impl(src, &options.keywords)?;
+    if let Some(source) = &options.source {
+        attach_source_to_program_spans(&mut program, source);
+    }
+    Ok(program)
+}
+
+pub fn parse_term(src: &str) -> Result<LogicTerm, Vec<Diagnostic>> {
+    let src = &Source::new(src);
+    match read_sexprs(src)?.as_slice() {
+        [node] => parse_const_term(src, node).map_err(|diag| vec![diag]),
+        _ => Err(vec![Diagnostic::new(
+            "E-PARSE",
+            "expected exactly one term",
+            None,
+        )]),
+    }
+}
+
+pub fn parse_program_lossy(src: &str) -> (Program, Vec<Diagnostic>) {
+    parse_program_lossy_with_options(src, &ParseOptions::default())
+}
+
+pub fn parse_program_lossy_with_options(
+    src: &str,
+    options: &ParseOptions,
+) -> (Program, Vec<Diagnostic>) {
+    let (mut program, errors) = parse_program_lossy_impl(src, &options.keywords);
+    if let Some(source) = &options.source {
+        attach_source_to_program_spans(&mut program, source);
+    }
+    (program, errors)
+}
+
+fn parse_program_lossy_impl(src: &str, keywords: &SurfaceKeywordMap) -> (Program, Vec<Diagnostic>) {
+    let src = &Source::new(src);
+    let mut errors = Vec::new();
+    let edition = edition_from_pragma(src)
+        .unwrap_or_else(|d| {
+            errors.push(d);
+            None
+        })
+        .unwrap_or(Edition::LATEST);
+    let (tokens, mut lex_errors) = lex_lossy(src);
+    errors.append(&mut lex_errors);
+    let (sexprs, mut sexpr_errors) = parse_sexprs_lossy(src, &tokens);
+    errors.append(&mut sexpr_errors);
+    let (mut sexprs, mut macro_errors) = expand_forms(src, sexprs, None);
+    errors.append(&mut macro_errors);
+    let rewritten = apply_surface_keywords(&mut sexprs, keywords);
+    if !edition.supports(EditionFeature::ProjectSurfaceKeywords) {
+        errors.extend(rewritten.into_iter().map(|(start, end)| {
+            feature_unavailable(
+                src,
+                EditionFeature::ProjectSurfaceKeywords,
+                edition,
+                start,
+                end,
+            )
+        }));
+    }
+    let mode = determine_syntax_mode(src, &sexprs).unwrap_or_else(|d| {
+        errors.push(d);
+        SyntaxMode::Core
+    });
+    let core_forms = match mode {
+        SyntaxMode::Core => sexprs,
+        SyntaxMode::Surface => {
+            let (desugared, mut desugar_errors) = desugar_surface_program_lossy(src, &sexprs);
+            errors.append(&mut desugar_errors);
+            desugared
+        }
+    };
+    let (program, mut form_errors) = parse_program_forms_lossy(src, &core_forms);
+    errors.append(&mut form_errors);
+    dedup_diagnostics(&mut errors);
+    (program, errors)
+}
+
+fn parse_program_impl(src: &str, keywords: &SurfaceKeywordMap) -> Result<Program, Vec<Diagnostic>> {
+    let src = &Source::new(src);
+    let edition = edition_from_pragma(src)
+        .map_err(|d| vec![d])?
+        .unwrap_or(Edition::LATEST);
+    let sexprs = read_sexprs(src)?;
+    let (mut sexprs, errors) = expand_forms(src, sexprs, None);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let rewritten = apply_surface_keywords(&mut sexprs, keywords);
+    if !edition.supports(EditionFeature::ProjectSurfaceKeywords) && !rewritten.is_empty() {
+        return Err(rewritten
+            .into_iter()
+            .map(|(start, end)| {
+                feature_unavailable(
+                    src,
+                    EditionFeature::ProjectSurfaceKeywords,
+                    edition,
+                    start,
+                    end,
+                )
+            })
+            .collect());
+    }
     let mode = determine_syntax_mode(src, &sexprs).map_err(|d| vec![d])?;
     match mode {
         SyntaxMode::Core => parse_program_forms(src, &sexprs),
         SyntaxMode::Surface => parse_program_surface_forms(src, &sexprs),
     }
+    .map_err(|mut errors| {
+        dedup_diagnostics(&mut errors);
+        errors
+    })
 }
 
-fn parse_program_core(src: &str) -> Result<Program, Vec<Diagnostic>> {
-    let tokens = lex(src)?;
-    let sexprs = parse_sexprs(src, &tokens)?;
-    parse_program_forms(src, &sexprs)
+fn parse_program_surface_forms(
+    src: &Source<'_>,
+    sexprs: &[SExpr],
+) -> Result<Program, Vec<Diagnostic>> {
+    let desugared = desugar_surface_program(src, sexprs)?;
+    parse_program_forms(src, &desugared)
 }
 
-fn parse_program_surface_forms(src: &str, sexprs: &[SExpr]) -> Result<Program, Vec<Diagnostic>> {
-    let desugared = desugar_surface_program(src, sexprs)?;
-    parse_program_core(&desugared)
+fn parse_program_forms(src: &Source<'_>, sexprs: &[SExpr]) -> Result<Program, Vec<Diagnostic>> {
+    let (program, errors) = parse_program_forms_lossy(src, sexprs);
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors)
+    }
 }
 
-fn parse_program_forms(src: &str, sexprs: &[SExpr]) -> Result<Program, Vec<Diagnostic>> {
+fn parse_program_forms_lossy(src: &Source<'_>, sexprs: &[SExpr]) -> (Program, Vec<Diagnostic>) {
     let mut program = Program::new();
     let mut errors = Vec::new();
 
+    src.name_uses.take();
     for form in sexprs {
-        match parse_toplevel(src, form) {
-            Ok(TopLevel::Import(i)) => program.imports.push(i),
-            Ok(TopLevel::Alias(a)) => program.aliases.push(a),
-            Ok(TopLevel::Sort(s)) => program.sorts.push(s),
-            Ok(TopLevel::Data(d)) => program.data_decls.push(d),
-            Ok(TopLevel::Relation(r)) => program.relations.push(r),
-            Ok(TopLevel::Fact(f)) => program.facts.push(f),
-            Ok(TopLevel::Rule(r)) => program.rules.push(r),
-            Ok(TopLevel::Assert(a)) => program.asserts.push(a),
-            Ok(TopLevel::Universe(u)) => program.universes.push(u),
-            Ok(TopLevel::Defn(d)) => program.defns.push(d),
-            Err(e) => errors.push(e),
+        let uses = src.name_uses.borrow().len();
+        match parse_toplevel(&mut program.exprs, src, form) {
+            Ok(item) => push_toplevel(&mut program, item),
+            Err(e) => {
+                src.name_uses.borrow_mut().truncate(uses);
+                errors.push(e);
+            }
         }
     }
+    program.name_uses = src.name_uses.take();
 
-    if errors.is_empty() {
-        Ok(program)
-    } else {
-        Err(errors)
+    (program, errors)
+}
+
+fn push_toplevel(program: &mut Program, item: TopLevel) {
+    match item {
+        TopLevel::Import(i) => program.imports.push(i),
+        TopLevel::Alias(a) => program.aliases.push(a),
+        TopLevel::Const(c) => program.consts.push(c),
+        TopLevel::Sort(s) => program.sorts.push(s),
+        TopLevel::Data(d) => program.data_decls.push(d),
+        TopLevel::Relation(r) => program.relations.push(r),
+        TopLevel::Fact(f) => program.facts.push(f),
+        TopLevel::Rule(r) => program.rules.push(r),
+        TopLevel::Assert(a) => program.asserts.push(a),
+        TopLevel::Universe(u) => program.universes.push(u),
+        TopLevel::Defn(d) => program.defns.push(d),
+        TopLevel::Profiled(items) => {
+            for item in items {
+                push_toplevel(program, item);
+            }
+        }
     }
 }
 
@@ -109,6 +368,13 @@ enum SyntaxMode {
     Surface,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SurfaceKeywords {
+    #[default]
+    Japanese,
+    English,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum SyntaxPragma {
     Core,
@@ -130,7 +396,27 @@ struct SyntaxMarker {
     end: usize,
 }
 
-fn determine_syntax_mode(src: &str, sexprs: &[SExpr]) -> Result<SyntaxMode, Diagnostic> {
+pub(crate) struct TopLevelLayout {
+    pub(crate) surface: bool,
+    pub(crate) forms: Vec<(usize, usize)>,
+}
+
+pub(crate) fn top_level_layout(
+    src: &str,
+    keywords: &SurfaceKeywordMap,
+) -> Result<TopLevelLayout, Vec<Diagnostic>> {
+    let src = &Source::new(src);
+    let tokens = lex(src)?;
+    let mut sexprs = parse_sexprs(src, &tokens)?;
+    apply_surface_keywords(&mut sexprs, keywords);
+    let mode = determine_syntax_mode(src, &sexprs).map_err(|d| vec![d])?;
+    Ok(TopLevelLayout {
+        surface: mode == SyntaxMode::Surface,
+        forms: sexprs.iter().map(SExpr::span_bounds).collect(),
+    })
+}
+
+fn determine_syntax_mode(src: &Source<'_>, sexprs: &[SExpr]) -> Result<SyntaxMode, Diagnostic> {
     if let Some(pragma_mode) = syntax_mode_from_pragma(src) {
         return match pragma_mode {
             SyntaxPragma::Core => Ok(SyntaxMode::Core),
@@ -141,7 +427,10 @@ fn determine_syntax_mode(src: &str, sexprs: &[SExpr]) -> Result<SyntaxMode, Diag
     determine_auto_syntax_mode(src, sexprs)
 }
 
-fn determine_auto_syntax_mode(src: &str, sexprs: &[SExpr]) -> Result<SyntaxMode, Diagnostic> {
+fn determine_auto_syntax_mode(
+    src: &Source<'_>,
+    sexprs: &[SExpr],
+) -> Result<SyntaxMode, Diagnostic> {
     let mut first_core: Option<SyntaxMarker> = None;
     let mut first_surface: Option<SyntaxMarker> = None;
 
@@ -176,7 +465,7 @@ fn determine_auto_syntax_mode(src: &str, sexprs: &[SExpr]) -> Result<SyntaxMode,
     }
 }
 
-fn syntax_auto_conflict_diag(src: &str, a: &SyntaxMarker, b: &SyntaxMarker) -> Diagnostic {
+fn syntax_auto_conflict_diag(src: &Source<'_>, a: &SyntaxMarker, b: &SyntaxMarker) -> Diagnostic {
     Diagnostic::new(
         "E-SYNTAX-AUTO",
         format!(
@@ -198,14 +487,20 @@ fn syntax_marker(form: &SExpr) -> Option<SyntaxMarker> {
     let head = list[0].as_atom()?;
 
     match head {
-        "型" | "データ" | "関係" | "事実" | "規則" | "検証" | "宇宙" | "関数" => {
-            Some(SyntaxMarker {
-                signal: SyntaxSignal::Surface,
-                reason: "日本語 Surface ヘッド",
-                start,
-                end,
-            })
-        }
+        "型"
+        | "データ"
+        | "関係"
+        | "事実"
+        | "規則"
+        | "検証"
+        | "宇宙"
+        | "関数"
+        | "プロファイル限定" => Some(SyntaxMarker {
+            signal: SyntaxSignal::Surface,
+            reason: "日本語 Surface ヘッド",
+            start,
+            end,
+        }),
         "プロジェクト" | "モジュール" | "参照" | "契約" | "品質ゲート" => {
             Some(SyntaxMarker {
                 signal: SyntaxSignal::Surface,
@@ -220,7 +515,14 @@ fn syntax_marker(form: &SExpr) -> Option<SyntaxMarker> {
             start,
             end,
         }),
+        "定数" => Some(SyntaxMarker {
+            signal: SyntaxSignal::Surface,
+            reason: "定数 Surface ヘッド",
+            start,
+            end,
+        }),
         "import" | "インポート" | "sort" => None,
+        "when-profile" => list.iter().skip(2).find_map(syntax_marker),
         "alias" => syntax_marker_from_tag_position(
             list,
             1,
@@ -229,6 +531,14 @@ fn syntax_marker(form: &SExpr) -> Option<SyntaxMarker> {
             start,
             end,
         ),
+        "defconst" => syntax_marker_from_tag_position(
+            list,
+            2,
+            "core defconst 形式",
+            "surface defconst タグ形式",
+            start,
+            end,
+        ),
         "data" => syntax_marker_from_tag_position(
             list,
             2,
@@ -324,7 +634,7 @@ fn syntax_marker_from_tag_position(
 }
 
 fn syntax_mode_from_pragma(src: &str) -> Option<SyntaxPragma> {
-    for line in src.lines() {
+    for line in src[shebang_len(src)..].lines() {
         let trimmed = line.trim();
         if trimmed.is_empty() {
             continue;
@@ -347,6 +657,78 @@ fn syntax_mode_from_pragma(src: &str) -> Option<SyntaxPragma> {
     None
 }
 
+pub fn surface_keywords(src: &str) -> SurfaceKeywords {
+    surface_keywords_with_map(src, &SurfaceKeywordMap::default())
+}
+
+pub(crate) fn surface_keywords_with_map(src: &str, custom: &SurfaceKeywordMap) -> SurfaceKeywords {
+    if let Some(keywords) = surface_keywords_from_pragma(src) {
+        return keywords;
+    }
+    let src = &Source::new(src);
+    let Ok(tokens) = lex(src) else {
+        return SurfaceKeywords::Japanese;
+    };
+    let Ok(mut sexprs) = parse_sexprs(src, &tokens) else {
+        return SurfaceKeywords::Japanese;
+    };
+    apply_surface_keywords(&mut sexprs, custom);
+    surface_keywords_of_forms(&sexprs)
+}
+
+fn surface_keywords_of_forms(sexprs: &[SExpr]) -> SurfaceKeywords {
+    let mut english = false;
+    for form in sexprs {
+        let Some(marker) = syntax_marker(form) else {
+            continue;
+        };
+        if marker.signal != SyntaxSignal::Surface {
+            continue;
+        }
+        let Some(head) = form_head(form) else {
+            continue;
+        };
+        if !head.is_ascii() {
+            return SurfaceKeywords::Japanese;
+        }
+        english = true;
+    }
+    if english {
+        SurfaceKeywords::English
+    } else {
+        SurfaceKeywords::Japanese
+    }
+}
+
+fn surface_keywords_from_pragma(src: &str) -> Option<SurfaceKeywords> {
+    for line in src[shebang_len(src)..].lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if !trimmed.starts_with(';') {
+            break;
+        }
+        let body = trimmed.trim_start_matches(';').trim();
+        let lower = body.to_ascii_lowercase();
+        if let Some(rest) = lower.strip_prefix("keywords:") {
+            return match rest.trim() {
+                "ja" | "japanese" => Some(SurfaceKeywords::Japanese),
+                "en" | "english" => Some(SurfaceKeywords::English),
+                _ => None,
+            };
+        }
+    }
+    None
+}
+
+fn form_head(form: &SExpr) -> Option<&str> {
+    match form {
+        SExpr::List(items, _, _) => items.first().and_then(SExpr::as_atom),
+        SExpr::Atom(_, _, _) => None,
+    }
+}
+
 fn looks_like_surface(src: &str) -> bool {
     const MARKERS: &[&str] = &[
         "(型",
@@ -391,6 +773,9 @@ fn attach_source_to_program_spans(program: &mut Program, source: &str) {
     for alias in &mut program.aliases {
         attach_span_source(&mut alias.span, source);
     }
+    for constant in &mut program.consts {
+        attach_span_source(&mut constant.span, source);
+    }
     for sort in &mut program.sorts {
         attach_span_source(&mut sort.span, source);
     }
@@ -423,11 +808,22 @@ fn attach_source_to_program_spans(program: &mut Program, source: &str) {
         for param in &mut defn.params {
             attach_span_source(&mut param.span, source);
         }
-        attach_expr_source(&mut defn.body, source);
+        for clause in &mut defn.clauses {
+            attach_span_source(&mut clause.span, source);
+        }
+    }
+    for expr in program.exprs.exprs_mut() {
+        attach_expr_source(expr, source);
+    }
+    for pattern in program.exprs.patterns_mut() {
+        attach_pattern_source(pattern, source);
+    }
+    for name_use in &mut program.name_uses {
+        attach_span_source(&mut name_use.span, source);
     }
 }
 
-fn attach_span_source(span: &mut crate::diagnostics::Span, source: &str) {
+fn attach_span_source(span: &mut Span, source: &str) {
     span.file_id = Some(source.to_string());
 }
 
@@ -437,44 +833,38 @@ fn attach_expr_source(expr: &mut Expr, source: &str) {
         | Expr::Symbol { span, .. }
         | Expr::Int { span, .. }
         | Expr::Bool { span, .. }
+        | Expr::Hole { span }
         | Expr::Call { span, .. }
         | Expr::Let { span, .. }
+        | Expr::LetFn { span, .. }
         | Expr::If { span, .. }
         | Expr::Match { span, .. } => attach_span_source(span, source),
     }
 
     match expr {
-        Expr::Var { .. } | Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => {}
-        Expr::Call { args, .. } => {
-            for arg in args {
-                attach_expr_source(arg, source);
+        Expr::Var { .. }
+        | Expr::Symbol { .. }
+        | Expr::Int { .. }
+        | Expr::Bool { .. }
+        | Expr::Hole { .. }
+        | Expr::If { .. } => {}
+        Expr::Call { name_span, .. } => attach_span_source(name_span, source),
+        Expr::Let { bindings, .. } => {
+            for (_, _, bspan) in bindings {
+                attach_span_source(bspan, source);
             }
         }
-        Expr::Let { bindings, body, .. } => {
-            for (_, bexpr, bspan) in bindings {
-                attach_span_source(bspan, source);
-                attach_expr_source(bexpr, source);
-            }
-            attach_expr_source(body, source);
-        }
-        Expr::If {
-            cond,
-            then_branch,
-            else_branch,
-            ..
-        } => {
-            attach_expr_source(cond, source);
-            attach_expr_source(then_branch, source);
-            attach_expr_source(else_branch, source);
-        }
-        Expr::Match {
-            scrutinee, arms, ..
-        } => {
-            attach_expr_source(scrutinee, source);
+        Expr::LetFn { fns, .. } => {
+            for f in fns {
+                attach_span_source(&mut f.span, source);
+                for param in &mut f.params {
+                    attach_span_source(&mut param.span, source);
+                }
+            }
+        }
+        Expr::Match { arms, .. } => {
             for arm in arms {
                 attach_span_source(&mut arm.span, source);
-                attach_pattern_source(&mut arm.pattern, source);
-                attach_expr_source(&mut arm.body, source);
             }
         }
     }
@@ -489,143 +879,400 @@ fn attach_pattern_source(pattern: &mut Pattern, source: &str) {
         | Pattern::Bool { span, .. }
         | Pattern::Ctor { span, .. } => attach_span_source(span, source),
     }
+}
 
-    if let Pattern::Ctor { args, .. } = pattern {
-        for arg in args {
-            attach_pattern_source(arg, source);
-        }
+pub(crate) fn read_sexprs(src: &Source<'_>) -> Result<Vec<SExpr>, Vec<Diagnostic>> {
+    let tokens = lex(src)?;
+    parse_sexprs(src, &tokens)
+}
+
+fn lex(src: &Source<'_>) -> Result<Vec<Token>, Vec<Diagnostic>> {
+    let (tokens, errors) = lex_lossy(src);
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
     }
 }
 
-fn lex(src: &str) -> Result<Vec<Token>, Vec<Diagnostic>> {
+fn lex_lossy(src: &Source<'_>) -> (Vec<Token>, Vec<Diagnostic>) {
+    let bytes = src.as_bytes();
     let mut tokens = Vec::new();
-    let mut it = src.char_indices().peekable();
     let mut errors = Vec::new();
+    let mut pos = shebang_len(src);
 
-    while let Some((idx, ch)) = it.next() {
-        if ch.is_whitespace() {
-            continue;
-        }
-        if ch == ';' {
-            skip_comment(&mut it);
+    while pos < bytes.len() {
+        let start = pos;
+        if block_comment_starts(bytes, pos, src.edition) {
+            if let Err(err) = consume_block_comment(bytes, &mut pos) {
+                errors.push(err.into_diagnostic(src));
+            }
             continue;
         }
-        if ch == '(' {
+        if datum_comment_starts(bytes, pos, src.edition) {
+            pos += 2;
             tokens.push(Token {
-                kind: TokenKind::LParen,
-                start: idx,
-                end: idx + ch.len_utf8(),
+                kind: TokenKind::DatumComment,
+                start,
+                end: pos,
             });
             continue;
         }
-        if ch == ')' {
-            tokens.push(Token {
-                kind: TokenKind::RParen,
-                start: idx,
-                end: idx + ch.len_utf8(),
-            });
+        if let Some(delimiter) = string_delimiter(bytes, pos, src.edition) {
+            match consume_string(src, delimiter, &mut pos) {
+                Ok(quoted) => tokens.push(Token {
+                    kind: TokenKind::Atom(quoted),
+                    start,
+                    end: pos,
+                }),
+                Err(err) => errors.push(err.into_diagnostic(src)),
+            }
             continue;
         }
-        if ch == '"' {
-            match consume_quoted_atom(src, idx, &mut it) {
-                Ok((quoted, end)) => {
-                    tokens.push(Token {
-                        kind: TokenKind::Atom(quoted),
-                        start: idx,
-                        end,
-                    });
+        match bytes[pos] {
+            b'(' => {
+                pos += 1;
+                tokens.push(Token {
+                    kind: TokenKind::LParen,
+                    start,
+                    end: pos,
+                });
+            }
+            b')' => {
+                pos += 1;
+                tokens.push(Token {
+                    kind: TokenKind::RParen,
+                    start,
+                    end: pos,
+                });
+            }
+            b';' => pos = line_end(bytes, pos),
+            _ => {
+                let width = whitespace_width(src, pos);
+                if width > 0 {
+                    pos += width;
+                    continue;
                 }
-                Err(err) => errors.push(err),
+                pos = atom_end(src, pos);
+                tokens.push(Token {
+                    kind: TokenKind::Atom(normalize_atom(&src[start..pos])),
+                    start,
+                    end: pos,
+                });
             }
-            continue;
         }
-
-        let (start, end) = consume_atom(idx, ch, &mut it);
-        let text = &src[start..end];
-        tokens.push(Token {
-            kind: TokenKind::Atom(normalize_atom(text)),
-            start,
-            end,
-        });
     }
 
-    if errors.is_empty() {
-        Ok(tokens)
+    (tokens, errors)
+}
+
+pub(crate) fn shebang_len(src: &str) -> usize {
+    if src.starts_with("#!") {
+        line_end(src.as_bytes(), 0)
     } else {
-        Err(errors)
+        0
+    }
+}
+
+// Delimiters are ASCII, so bytes of multi-byte characters never match them;
+// only whitespace needs the decoded character.
+fn whitespace_width(src: &str, pos: usize) -> usize {
+    let byte = src.as_bytes()[pos];
+    if byte.is_ascii() {
+        return usize::from(char::from(byte).is_whitespace());
+    }
+    match src[pos..].chars().next() {
+        Some(ch) if ch.is_whitespace() => ch.len_utf8(),
+        _ => 0,
     }
 }
 
-fn skip_comment(it: &mut Peekable<CharIndices<'_>>) {
-    while let Some((_, ch)) = it.peek().copied() {
-        if ch == '\n' {
+fn line_end(bytes: &[u8], pos: usize) -> usize {
+    bytes[pos..]
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map_or(bytes.len(), |offset| pos + offset)
+}
+
+fn atom_end(src: &str, mut pos: usize) -> usize {
+    let bytes = src.as_bytes();
+    while pos < bytes.len() {
+        if matches!(bytes[pos], b'(' | b')' | b';') || whitespace_width(src, pos) > 0 {
             break;
         }
-        it.next();
+        pos += 1;
+        while pos < bytes.len() && !src.is_char_boundary(pos) {
+            pos += 1;
+        }
     }
+    pos
 }
 
-fn consume_atom(start: usize, first: char, it: &mut Peekable<CharIndices<'_>>) -> (usize, usize) {
-    let mut end = start + first.len_utf8();
-    while let Some((idx, ch)) = it.peek().copied() {
-        if ch.is_whitespace() || ch == '(' || ch == ')' || ch == ';' {
-            break;
+struct LexError {
+    message: &'static str,
+    detail: Option<char>,
+    start: usize,
+    end: usize,
+}
+
+impl LexError {
+    fn into_diagnostic(self, src: &Source<'_>) -> Diagnostic {
+        let message = match self.detail {
+            Some(esc) => format!("{}: \\{esc}", self.message),
+            None => self.message.to_string(),
+        };
+        Diagnostic::new(
+            "E-PARSE",
+            message,
+            Some(make_span(src, self.start, self.end)),
+        )
+    }
+}
+
+fn block_comment_starts(bytes: &[u8], pos: usize, edition: Edition) -> bool {
+    bytes[pos..].starts_with(b"#|") && edition.supports(EditionFeature::BlockComments)
+}
+
+pub(crate) fn datum_comment_starts(bytes: &[u8], pos: usize, edition: Edition) -> bool {
+    bytes[pos..].starts_with(b"#;") && edition.supports(EditionFeature::DatumComments)
+}
+
+pub(crate) fn block_comment_end(src: &str, pos: usize, edition: Edition) -> Option<usize> {
+    if !block_comment_starts(src.as_bytes(), pos, edition) {
+        return None;
+    }
+    let mut end = pos;
+    let _ = consume_block_comment(src.as_bytes(), &mut end);
+    Some(end)
+}
+
+pub(crate) fn contains_code_comment(src: &str, edition: Edition) -> bool {
+    let bytes = src.as_bytes();
+    let mut pos = shebang_len(src);
+    while pos < bytes.len() {
+        if block_comment_starts(bytes, pos, edition) || datum_comment_starts(bytes, pos, edition) {
+            return true;
         }
-        it.next();
-        end = idx + ch.len_utf8();
+        if let Some(end) = string_literal_end(src, pos, edition) {
+            pos = end;
+            continue;
+        }
+        pos = match bytes[pos] {
+            b'(' | b')' => pos + 1,
+            b';' => line_end(bytes, pos),
+            _ => match whitespace_width(src, pos) {
+                0 => atom_end(src, pos),
+                width => pos + width,
+            },
+        };
     }
-    (start, end)
+    false
 }
 
-fn consume_quoted_atom(
+fn consume_block_comment(bytes: &[u8], pos: &mut usize) -> Result<(), LexError> {
+    let start = *pos;
+    let mut depth = 0usize;
+    while *pos < bytes.len() {
+        if bytes[*pos..].starts_with(b"#|") {
+            depth += 1;
+            *pos += 2;
+        } else if bytes[*pos..].starts_with(b"|#") {
+            depth -= 1;
+            *pos += 2;
+            if depth == 0 {
+                return Ok(());
+            }
+        } else {
+            *pos += 1;
+        }
+    }
+    Err(LexError {
+        message: "unterminated block comment",
+        detail: None,
+        start,
+        end: bytes.len(),
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StringDelimiter {
+    Quoted,
+    MultiLine,
+    Raw(usize),
+}
+
+fn string_delimiter(bytes: &[u8], pos: usize, edition: Edition) -> Option<StringDelimiter> {
+    let rest = &bytes[pos..];
+    if rest.starts_with(b"\"\"\"") && edition.supports(EditionFeature::MultiLineStrings) {
+        return Some(StringDelimiter::MultiLine);
+    }
+    if rest.first() == Some(&b'"') {
+        return Some(StringDelimiter::Quoted);
+    }
+    if !edition.supports(EditionFeature::RawStrings) {
+        return None;
+    }
+    let hashes = rest.iter().take_while(|byte| **byte == b'#').count();
+    (hashes > 0 && rest.get(hashes) == Some(&b'"')).then_some(StringDelimiter::Raw(hashes))
+}
+
+pub(crate) fn string_literal_end(src: &str, pos: usize, edition: Edition) -> Option<usize> {
+    let delimiter = string_delimiter(src.as_bytes(), pos, edition)?;
+    let mut end = pos;
+    let _ = consume_string(src, delimiter, &mut end);
+    Some(end)
+}
+
+fn consume_string(
     src: &str,
-    start: usize,
-    it: &mut Peekable<CharIndices<'_>>,
-) -> Result<(String, usize), Diagnostic> {
-    let mut body = String::new();
+    delimiter: StringDelimiter,
+    pos: &mut usize,
+) -> Result<String, LexError> {
+    match delimiter {
+        StringDelimiter::Quoted => consume_escaped_string(src, pos, false),
+        StringDelimiter::MultiLine => consume_escaped_string(src, pos, true),
+        StringDelimiter::Raw(hashes) => consume_raw_string(src, hashes, pos),
+    }
+}
 
-    while let Some((idx, ch)) = it.next() {
-        let end = idx + ch.len_utf8();
-        if ch == '"' {
-            return Ok((format!("\"{body}\""), end));
+fn consume_escaped_string(
+    src: &str,
+    pos: &mut usize,
+    multi_line: bool,
+) -> Result<String, LexError> {
+    let bytes = src.as_bytes();
+    let start = *pos;
+    *pos += if multi_line { 3 } else { 1 };
+    if multi_line {
+        if bytes[*pos..].starts_with(b"\n") {
+            *pos += 1;
+        } else if bytes[*pos..].starts_with(b"\r\n") {
+            *pos += 2;
         }
-        if ch == '\\' {
-            let Some((esc_idx, esc)) = it.next() else {
-                return Err(Diagnostic::new(
-                    "E-PARSE",
-                    "unterminated escape sequence in quoted atom",
-                    Some(make_span(src, idx, end)),
-                ));
+    }
+    let mut body = String::new();
+    let mut invalid_escape = None;
+
+    loop {
+        let Some(offset) = bytes[*pos..]
+            .iter()
+            .position(|byte| matches!(byte, b'"' | b'\\'))
+        else {
+            *pos = bytes.len();
+            return Err(LexError {
+                message: "unterminated quoted atom",
+                detail: None,
+                start,
+                end: src.len(),
+            });
+        };
+        body.push_str(&src[*pos..*pos + offset]);
+        let idx = *pos + offset;
+        if bytes[idx] == b'"' {
+            let quotes = if multi_line {
+                bytes[idx..]
+                    .iter()
+                    .take_while(|byte| **byte == b'"')
+                    .count()
+            } else {
+                1
             };
-            let esc_end = esc_idx + esc.len_utf8();
-            match esc {
-                '\\' => body.push('\\'),
-                '"' => body.push('"'),
-                'n' => body.push('\n'),
-                't' => body.push('\t'),
-                'r' => body.push('\r'),
-                _ => {
-                    return Err(Diagnostic::new(
-                        "E-PARSE",
-                        format!("unsupported escape sequence: \\{esc}"),
-                        Some(make_span(src, idx, esc_end)),
-                    ));
-                }
+            *pos = idx + quotes;
+            if multi_line && quotes < 3 {
+                body.extend(std::iter::repeat_n('"', quotes));
+                continue;
+            }
+            body.extend(std::iter::repeat_n('"', quotes.saturating_sub(3)));
+            return match invalid_escape {
+                Some(err) => Err(err),
+                None => Ok(format!("\"{body}\"")),
+            };
+        }
+        *pos = idx + 1;
+        let Some(esc) = src[*pos..].chars().next() else {
+            return Err(LexError {
+                message: "unterminated escape sequence in quoted atom",
+                detail: None,
+                start: idx,
+                end: idx + 1,
+            });
+        };
+        *pos += esc.len_utf8();
+        match esc {
+            '\\' => body.push('\\'),
+            '"' => body.push('"'),
+            'n' => body.push('\n'),
+            't' => body.push('\t'),
+            'r' => body.push('\r'),
+            _ => {
+                invalid_escape.get_or_insert(LexError {
+                    message: "unsupported escape sequence",
+                    detail: Some(esc),
+                    start: idx,
+                    end: *pos,
+                });
             }
-            continue;
         }
-        body.push(ch);
     }
+}
 
-    Err(Diagnostic::new(
-        "E-PARSE",
-        "unterminated quoted atom",
-        Some(make_span(src, start, src.len())),
-    ))
+fn consume_raw_string(src: &str, hashes: usize, pos: &mut usize) -> Result<String, LexError> {
+    let start = *pos;
+    let body_start = start + hashes + 1;
+    let closing = format!("\"{}", "#".repeat(hashes));
+    let Some(offset) = src[body_start..].find(&closing) else {
+        *pos = src.len();
+        return Err(LexError {
+            message: "unterminated raw string",
+            detail: None,
+            start,
+            end: src.len(),
+        });
+    };
+    *pos = body_start + offset + closing.len();
+    Ok(format!("\"{}\"", &src[body_start..body_start + offset]))
+}
+
+pub(crate) fn string_literals(src: &str) -> Vec<(String, Range<usize>)> {
+    let source = Source::new(src);
+    let (tokens, _) = lex_lossy(&source);
+    tokens
+        .into_iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::Atom(atom)
+                if string_delimiter(src.as_bytes(), token.start, source.edition).is_some() =>
+            {
+                Some((atom, token.start..token.end))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn quote_atom(value: &str) -> String {
+    if value.contains(['"', '\\']) && !value.chars().any(char::is_control) {
+        let mut hashes = String::from("#");
+        while value.contains(&format!("\"{hashes}")) {
+            hashes.push('#');
+        }
+        return format!("{hashes}\"{value}\"{hashes}");
+    }
+    let mut escaped = String::new();
+    for ch in value.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(ch),
+        }
+    }
+    format!("\"{escaped}\"")
 }
 
 fn normalize_atom(text: &str) -> String {
-    if is_quoted_atom(text) {
+    if text.is_ascii() || is_quoted_atom(text) {
         text.to_string()
     } else {
         text.nfc().collect()
@@ -643,14 +1290,15 @@ fn is_quoted_atom(text: &str) -> bool {
     quote_count >= 2
 }
 
-fn parse_sexprs(src: &str, tokens: &[Token]) -> Result<Vec<SExpr>, Vec<Diagnostic>> {
+fn parse_sexprs(src: &Source<'_>, tokens: &[Token]) -> Result<Vec<SExpr>, Vec<Diagnostic>> {
     let mut idx = 0usize;
     let mut forms = Vec::new();
     let mut errors = Vec::new();
 
     while idx < tokens.len() {
         match parse_one(src, tokens, &mut idx) {
-            Ok(form) => forms.push(form),
+            Ok(Some(form)) => forms.push(form),
+            Ok(None) => {}
             Err(e) => {
                 errors.push(e);
                 break;
@@ -665,70 +1313,203 @@ fn parse_sexprs(src: &str, tokens: &[Token]) -> Result<Vec<SExpr>, Vec<Diagnosti
     }
 }
 
-fn parse_one(src: &str, tokens: &[Token], idx: &mut usize) -> Result<SExpr, Diagnostic> {
-    if *idx >= tokens.len() {
-        return Err(Diagnostic::new(
-            "E-PARSE",
-            "unexpected EOF",
-            Some(make_span(src, src.len(), src.len())),
-        ));
-    }
+// After a malformed form, resume at the next '(' that starts a line: top-level
+// forms conventionally begin in column 1, so this skips the broken form
+// without swallowing the ones after it.
+fn parse_sexprs_lossy(src: &Source<'_>, tokens: &[Token]) -> (Vec<SExpr>, Vec<Diagnostic>) {
+    let mut idx = 0usize;
+    let mut forms = Vec::new();
+    let mut errors = Vec::new();
 
-    let t = &tokens[*idx];
-    match &t.kind {
-        TokenKind::Atom(s) => {
-            *idx += 1;
-            Ok(SExpr::Atom(s.clone(), t.start, t.end))
+    while idx < tokens.len() {
+        let form_start = idx;
+        match parse_one(src, tokens, &mut idx) {
+            Ok(Some(form)) => forms.push(form),
+            Ok(None) => {}
+            Err(e) => {
+                errors.push(e);
+                idx = (form_start + 1..tokens.len())
+                    .find(|&i| {
+                        matches!(tokens[i].kind, TokenKind::LParen)
+                            && src[..tokens[i].start]
+                                .chars()
+                                .next_back()
+                                .is_none_or(|c| c == '\n')
+                    })
+                    .unwrap_or(tokens.len());
+            }
         }
-        TokenKind::RParen => Err(Diagnostic::new(
-            "E-PARSE",
-            "unexpected ')'",
-            Some(make_span(src, t.start, t.end)),
-        )),
-        TokenKind::LParen => {
-            let start = t.start;
-            *idx += 1;
-            let mut items = Vec::new();
-            loop {
-                if *idx >= tokens.len() {
+    }
+
+    (forms, errors)
+}
+
+// Deeper nesting is rejected up front so that the recursive passes over the
+// tree (parsing, desugaring, type checking) cannot overflow the stack.
+const MAX_NESTING_DEPTH: usize = 2048;
+
+fn parse_one(
+    src: &Source<'_>,
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Result<Option<SExpr>, Diagnostic> {
+    let mut open: Vec<(usize, Vec<SExpr>)> = Vec::new();
+    let mut commented: Vec<Vec<usize>> = vec![Vec::new()];
+    loop {
+        let Some(t) = tokens.get(*idx) else {
+            if let Some((start, _)) = open.last() {
+                return Err(Diagnostic::new(
+                    "E-PARSE",
+                    "unbalanced parentheses",
+                    Some(make_span(src, *start, *start + 1)),
+                ));
+            }
+            return match commented[0].last() {
+                Some(start) => Err(dangling_datum_comment(src, *start)),
+                None => Ok(None),
+            };
+        };
+
+        let node = match &t.kind {
+            TokenKind::Atom(s) => {
+                *idx += 1;
+                SExpr::Atom(s.clone(), t.start, t.end)
+            }
+            TokenKind::DatumComment => {
+                *idx += 1;
+                if let Some(pending) = commented.last_mut() {
+                    pending.push(t.start);
+                }
+                continue;
+            }
+            TokenKind::RParen => {
+                if let Some(start) = commented.last().and_then(|pending| pending.last()) {
+                    return Err(dangling_datum_comment(src, *start));
+                }
+                let Some((start, items)) = open.pop() else {
                     return Err(Diagnostic::new(
                         "E-PARSE",
-                        "unbalanced parentheses",
-                        Some(make_span(src, start, start + 1)),
+                        "unexpected ')'",
+                        Some(make_span(src, t.start, t.end)),
+                    ));
+                };
+                *idx += 1;
+                commented.pop();
+                SExpr::List(items, start, t.end)
+            }
+            TokenKind::LParen => {
+                if open.len() >= MAX_NESTING_DEPTH {
+                    return Err(Diagnostic::new(
+                        "E-PARSE",
+                        format!("nesting exceeds the maximum depth of {MAX_NESTING_DEPTH}"),
+                        Some(make_span(src, t.start, t.end)),
                     ));
                 }
-                let cur = &tokens[*idx];
-                if matches!(cur.kind, TokenKind::RParen) {
-                    let end = cur.end;
-                    *idx += 1;
-                    return Ok(SExpr::List(items, start, end));
-                }
-                let node = parse_one(src, tokens, idx)?;
-                items.push(node);
+                *idx += 1;
+                open.push((t.start, Vec::new()));
+                commented.push(Vec::new());
+                continue;
             }
+        };
+
+        if commented
+            .last_mut()
+            .is_some_and(|pending| pending.pop().is_some())
+        {
+            continue;
         }
+        match open.last_mut() {
+            Some((_, items)) => items.push(node),
+            None => return Ok(Some(node)),
+        }
+    }
+}
+
+fn dangling_datum_comment(src: &Source<'_>, start: usize) -> Diagnostic {
+    Diagnostic::new(
+        "E-PARSE",
+        "datum comment '#;' is not followed by an expression",
+        Some(make_span(src, start, start + 2)),
+    )
+}
+
+fn desugar_surface_program(
+    src: &Source<'_>,
+    forms: &[SExpr],
+) -> Result<Vec<SExpr>, Vec<Diagnostic>> {
+    let (out, errors) = desugar_surface_program_lossy(src, forms);
+    if errors.is_empty() {
+        Ok(out)
+    } else {
+        Err(errors)
     }
 }
 
-fn desugar_surface_program(src: &str, forms: &[SExpr]) -> Result<String, Vec<Diagnostic>> {
+fn desugar_surface_program_lossy(
+    src: &Source<'_>,
+    forms: &[SExpr],
+) -> (Vec<SExpr>, Vec<Diagnostic>) {
     let mut errors = Vec::new();
     let mut out = Vec::new();
+    let keywords =
+        surface_keywords_from_pragma(src).unwrap_or_else(|| surface_keywords_of_forms(forms));
 
     for form in forms {
-        match desugar_surface_toplevel(src, form) {
-            Ok(rendered) => out.push(rendered),
+        match desugar_surface_toplevel(src, form, keywords) {
+            Ok(desugared) => out.extend(desugared),
             Err(err) => errors.push(err),
         }
     }
 
-    if errors.is_empty() {
-        Ok(out.join("\n"))
-    } else {
-        Err(errors)
-    }
+    (out, errors)
 }
 
-fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnostic> {
+fn desugar_surface_toplevel(
+    src: &Source<'_>,
+    form: &SExpr,
+    keywords: SurfaceKeywords,
+) -> Result<Vec<SExpr>, Diagnostic> {
+    desugar_surface_form(src, form, keywords).map_err(|mut err| {
+        if keywords == SurfaceKeywords::English
+            && let Some(form_grammar) = form_head(form).and_then(grammar::surface_form)
+        {
+            err.message = english_tag_spellings(&err.message, form_grammar);
+        }
+        err
+    })
+}
+
+fn english_tag_spellings(message: &str, form_grammar: &grammar::FormGrammar) -> String {
+    let mut out = String::new();
+    let mut rest = message;
+    while let Some(idx) = rest.find(':') {
+        out.push_str(&rest[..idx]);
+        let len = rest[idx..]
+            .find(|ch: char| ch.is_whitespace() || "/,()".contains(ch))
+            .unwrap_or(rest.len() - idx);
+        let tag = &rest[idx..idx + len];
+        let spellings = [
+            form_grammar.tag(tag),
+            grammar::DOC_TAG,
+            grammar::MESSAGE_TAG,
+            grammar::PROFILE_TAG,
+        ];
+        let english = spellings
+            .into_iter()
+            .find(|spellings| spellings.contains(&tag))
+            .and_then(|spellings| spellings.iter().find(|spelling| spelling.is_ascii()));
+        out.push_str(english.unwrap_or(&tag));
+        rest = &rest[idx + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn desugar_surface_form(
+    src: &Source<'_>,
+    form: &SExpr,
+    keywords: SurfaceKeywords,
+) -> Result<Vec<SExpr>, Diagnostic> {
     let (start, end) = form.span_bounds();
     let list = match form {
         SExpr::List(items, _, _) => items,
@@ -749,13 +1530,20 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
     }
 
     let head = atom_required(src, &list[0], "surface top-level head")?;
-    let Some(kind) = canonical_surface_head(&head) else {
+    let Some(form_grammar) = grammar::surface_form(&head) else {
         return Err(Diagnostic::new(
             "E-PARSE",
             format!("unknown top-level form: {head}"),
             Some(make_span(src, start, end)),
         ));
     };
+    let kind = form_grammar.head;
+    let head_node = &list[0];
+    let core_form = |core_head: &str, args: Vec<SExpr>| -> SExpr {
+        let mut items = vec![synth_atom(core_head, head_node)];
+        items.extend(args);
+        SExpr::List(items, start, end)
+    };
 
     match kind {
         "import" => {
@@ -766,17 +1554,20 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                     Some(make_span(src, start, end)),
                 ));
             }
-            Ok(format!("(import {})", sexpr_to_string(&list[1])))
+            Ok(vec![core_form("import", vec![list[1].clone()])])
         }
         "sort" => {
-            if list.len() != 2 {
+            if list.len() < 2 || (list.len() > 2 && !is_tag_atom(&list[2])) {
                 return Err(Diagnostic::new(
                     "E-PARSE",
                     "sort expects exactly 1 argument",
                     Some(make_span(src, start, end)),
                 ));
             }
-            Ok(format!("(sort {})", sexpr_to_string(&list[1])))
+            let tags = parse_tag_pairs(src, list, 2)?;
+            let mut core_args = vec![list[1].clone()];
+            core_args.extend(declaration_tag_args(&tags));
+            Ok(vec![core_form("sort", core_args)])
         }
         "alias" => {
             if list.len() < 3 {
@@ -798,21 +1589,42 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                 src,
                 form,
                 &tags,
-                &[":別名", ":alias"],
+                form_grammar.tag(":別名"),
                 "alias requires :別名",
             )?;
             let canonical = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":正規", ":canonical"],
+                form_grammar.tag(":正規"),
                 "alias requires :正規",
             )?;
-            Ok(format!(
-                "(alias {} {})",
-                sexpr_to_string(alias),
-                sexpr_to_string(canonical)
-            ))
+            Ok(vec![core_form(
+                "alias",
+                vec![alias.clone(), canonical.clone()],
+            )])
+        }
+        "defconst" => {
+            if list.len() < 4 || !is_tag_atom(&list[2]) {
+                return Err(Diagnostic::new(
+                    "E-PARSE",
+                    "defconst expects name and tag :値",
+                    Some(make_span(src, start, end)),
+                ));
+            }
+            atom_required(src, &list[1], "constant name")?;
+            let tags = parse_tag_pairs(src, list, 2)?;
+            let value = required_tag_value(
+                src,
+                form,
+                &tags,
+                form_grammar.tag(":値"),
+                "defconst requires :値",
+            )?;
+            Ok(vec![core_form(
+                "defconst",
+                vec![list[1].clone(), value.clone()],
+            )])
         }
         "data" => {
             if list.len() < 3 {
@@ -829,22 +1641,20 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                     Some(make_span(src, start, end)),
                 ));
             }
-            let name = atom_required(src, &list[1], "data name")?;
+            atom_required(src, &list[1], "data name")?;
             let tags = parse_tag_pairs(src, list, 2)?;
             let ctors = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":コンストラクタ", ":constructors", ":ctors"],
+                form_grammar.tag(":コンストラクタ"),
                 "data requires :コンストラクタ",
             )?;
             let ctor_items = as_list_items(src, ctors, "constructor list")?;
-            let rendered = ctor_items
-                .iter()
-                .map(sexpr_to_string)
-                .collect::<Vec<_>>()
-                .join(" ");
-            Ok(format!("(data {name} {rendered})"))
+            let mut args = vec![list[1].clone()];
+            args.extend(ctor_items.iter().cloned());
+            args.extend(declaration_tag_args(&tags));
+            Ok(vec![core_form("data", args)])
         }
         "relation" => {
             if list.len() < 3 {
@@ -861,16 +1671,22 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                     Some(make_span(src, start, end)),
                 ));
             }
-            let name = atom_required(src, &list[1], "relation name")?;
+            atom_required(src, &list[1], "relation name")?;
             let tags = parse_tag_pairs(src, list, 2)?;
             let args = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":引数", ":args"],
+                form_grammar.tag(":引数"),
                 "relation requires :引数",
             )?;
-            Ok(format!("(relation {name} {})", sexpr_to_string(args)))
+            let mut core_args = vec![list[1].clone(), args.clone()];
+            if let Some(key) = optional_tag_node(&tags, form_grammar.tag(":キー")) {
+                core_args.push(synth_atom(":key", key));
+                core_args.push(key.clone());
+            }
+            core_args.extend(declaration_tag_args(&tags));
+            Ok(vec![core_form("relation", core_args)])
         }
         "fact" => {
             if list.len() < 3 {
@@ -887,29 +1703,42 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                     Some(make_span(src, start, end)),
                 ));
             }
-            let name = atom_required(src, &list[1], "fact name")?;
+            atom_required(src, &list[1], "fact name")?;
             let tags = parse_tag_pairs(src, list, 2)?;
-            let terms =
-                required_tag_value(src, form, &tags, &[":項", ":terms"], "fact requires :項")?;
+            let terms = required_tag_value(
+                src,
+                form,
+                &tags,
+                form_grammar.tag(":項"),
+                "fact requires :項",
+            )?;
             let term_items = as_list_items(src, terms, "fact term list")?;
-            let rendered = term_items
-                .iter()
-                .map(sexpr_to_string)
-                .collect::<Vec<_>>()
-                .join(" ");
-            Ok(format!("(fact {name} {rendered})"))
+            let mut args = vec![list[1].clone()];
+            args.extend(term_items.iter().cloned());
+            Ok(vec![core_form("fact", args)])
         }
         "rule" => {
             let tags = parse_tag_pairs(src, list, 1)?;
-            let head =
-                required_tag_value(src, form, &tags, &[":頭", ":head"], "rule requires :頭")?;
-            let body =
-                required_tag_value(src, form, &tags, &[":本体", ":body"], "rule requires :本体")?;
-            Ok(format!(
-                "(rule {} {})",
-                sexpr_to_string(head),
-                sexpr_to_string(body)
-            ))
+            let head = required_tag_value(
+                src,
+                form,
+                &tags,
+                form_grammar.tag(":頭"),
+                "rule requires :頭",
+            )?;
+            let body = required_tag_value(
+                src,
+                form,
+                &tags,
+                form_grammar.tag(":本体"),
+                "rule requires :本体",
+            )?;
+            let mut core_args = vec![head.clone(), body.clone()];
+            if let Some(depth) = optional_tag_node(&tags, form_grammar.tag(":スコーレム")) {
+                core_args.push(synth_atom(":skolem", depth));
+                core_args.push(depth.clone());
+            }
+            Ok(vec![core_form("rule", core_args)])
         }
         "assert" => {
             if list.len() < 4 {
@@ -926,27 +1755,25 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                     Some(make_span(src, start, end)),
                 ));
             }
-            let name = atom_required(src, &list[1], "assert name")?;
+            atom_required(src, &list[1], "assert name")?;
             let tags = parse_tag_pairs(src, list, 2)?;
             let params = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":引数", ":params"],
+                form_grammar.tag(":引数"),
                 "assert requires :引数",
             )?;
             let formula = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":式", ":formula"],
+                form_grammar.tag(":式"),
                 "assert requires :式",
             )?;
-            Ok(format!(
-                "(assert {name} {} {})",
-                sexpr_to_string(params),
-                sexpr_to_string(formula)
-            ))
+            let mut core_args = vec![list[1].clone(), params.clone(), formula.clone()];
+            core_args.extend(declaration_tag_args(&tags));
+            Ok(vec![core_form("assert", core_args)])
         }
         "universe" => {
             if list.len() < 4 {
@@ -963,16 +1790,19 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                     Some(make_span(src, start, end)),
                 ));
             }
-            let ty_name = atom_required(src, &list[1], "universe type")?;
+            atom_required(src, &list[1], "universe type")?;
             let tags = parse_tag_pairs(src, list, 2)?;
             let values = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":値", ":values"],
+                form_grammar.tag(":値"),
                 "universe requires :値",
             )?;
-            Ok(format!("(universe {ty_name} {})", sexpr_to_string(values)))
+            Ok(vec![core_form(
+                "universe",
+                vec![list[1].clone(), values.clone()],
+            )])
         }
         "defn" => {
             if list.len() < 5 {
@@ -989,25 +1819,55 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                     Some(make_span(src, start, end)),
                 ));
             }
-            let name = atom_required(src, &list[1], "function name")?;
+            atom_required(src, &list[1], "function name")?;
             let tags = parse_tag_pairs(src, list, 2)?;
             let params = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":引数", ":params"],
+                form_grammar.tag(":引数"),
                 "defn requires :引数",
             )?;
-            let ret =
-                required_tag_value(src, form, &tags, &[":戻り", ":ret"], "defn requires :戻り")?;
-            let body =
-                required_tag_value(src, form, &tags, &[":本体", ":body"], "defn requires :本体")?;
-            Ok(format!(
-                "(defn {name} {} {} {})",
-                sexpr_to_string(params),
-                sexpr_to_string(ret),
-                sexpr_to_string(body)
-            ))
+            let ret = required_tag_value(
+                src,
+                form,
+                &tags,
+                form_grammar.tag(":戻り"),
+                "defn requires :戻り",
+            )?;
+            let mut core_args = vec![list[1].clone(), params.clone(), ret.clone()];
+            match (
+                optional_tag_node(&tags, form_grammar.tag(":本体")),
+                optional_tag_node(&tags, form_grammar.tag(":節")),
+            ) {
+                (Some(body), None) => core_args.push(body.clone()),
+                (None, Some(clauses)) => {
+                    core_args.push(synth_atom(":clauses", clauses));
+                    core_args.push(clauses.clone());
+                }
+                (Some(_), Some(_)) => {
+                    return Err(Diagnostic::new(
+                        "E-PARSE",
+                        "defn takes either :本体 or :節, not both",
+                        Some(make_span(src, start, end)),
+                    ));
+                }
+                (None, None) => {
+                    return Err(Diagnostic::new(
+                        "E-PARSE",
+                        "defn requires :本体",
+                        Some(make_span(src, start, end)),
+                    ));
+                }
+            }
+            for (tag, core_tag) in [(":尺度", ":measure"), (":減少", ":decreases")] {
+                if let Some(measure) = optional_tag_node(&tags, form_grammar.tag(tag)) {
+                    core_args.push(synth_atom(core_tag, measure));
+                    core_args.push(measure.clone());
+                }
+            }
+            core_args.extend(declaration_tag_args(&tags));
+            Ok(vec![core_form("defn", core_args)])
         }
         "project" => {
             if list.len() < 3 {
@@ -1029,21 +1889,24 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                 src,
                 form,
                 &tags,
-                &[":名前", ":name"],
+                form_grammar.tag(":名前"),
                 "project requires :名前",
             )?;
             let summary = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":概要", ":summary"],
+                form_grammar.tag(":概要"),
                 "project requires :概要",
             )?;
-            Ok(format!(
-                "(fact sd-project {} {})",
-                sexpr_to_string(name),
-                sexpr_to_string(summary)
-            ))
+            Ok(vec![core_form(
+                "fact",
+                vec![
+                    synth_atom("sd-project", head_node),
+                    name.clone(),
+                    summary.clone(),
+                ],
+            )])
         }
         "module" => {
             if list.len() < 4 {
@@ -1065,32 +1928,43 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                 src,
                 form,
                 &tags,
-                &[":名前", ":name"],
+                form_grammar.tag(":名前"),
                 "module requires :名前",
             )?;
             let path = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":パス", ":path"],
+                form_grammar.tag(":パス"),
                 "module requires :パス",
             )?;
             let category = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":カテゴリ", ":category"],
+                form_grammar.tag(":カテゴリ"),
                 "module requires :カテゴリ",
             )?;
-            Ok(format!(
-                "(fact exists {})\n(fact artifact {} {})\n(fact sd-module {} {} {})",
-                sexpr_to_string(path),
-                sexpr_to_string(path),
-                sexpr_to_string(category),
-                sexpr_to_string(name),
-                sexpr_to_string(path),
-                sexpr_to_string(category)
-            ))
+            Ok(vec![
+                core_form("fact", vec![synth_atom("exists", head_node), path.clone()]),
+                core_form(
+                    "fact",
+                    vec![
+                        synth_atom("artifact", head_node),
+                        path.clone(),
+                        category.clone(),
+                    ],
+                ),
+                core_form(
+                    "fact",
+                    vec![
+                        synth_atom("sd-module", head_node),
+                        name.clone(),
+                        path.clone(),
+                        category.clone(),
+                    ],
+                ),
+            ])
         }
         "reference" => {
             if list.len() < 3 {
@@ -1112,18 +1986,30 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                 src,
                 form,
                 &tags,
-                &[":元", ":from"],
+                form_grammar.tag(":元"),
                 "reference requires :元",
             )?;
-            let to =
-                required_tag_value(src, form, &tags, &[":先", ":to"], "reference requires :先")?;
-            Ok(format!(
-                "(fact ref {} {})\n(fact sd-reference {} {})",
-                sexpr_to_string(from),
-                sexpr_to_string(to),
-                sexpr_to_string(from),
-                sexpr_to_string(to)
-            ))
+            let to = required_tag_value(
+                src,
+                form,
+                &tags,
+                form_grammar.tag(":先"),
+                "reference requires :先",
+            )?;
+            Ok(vec![
+                core_form(
+                    "fact",
+                    vec![synth_atom("ref", head_node), from.clone(), to.clone()],
+                ),
+                core_form(
+                    "fact",
+                    vec![
+                        synth_atom("sd-reference", head_node),
+                        from.clone(),
+                        to.clone(),
+                    ],
+                ),
+            ])
         }
         "contract" => {
             if list.len() < 4 {
@@ -1145,33 +2031,50 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                 src,
                 form,
                 &tags,
-                &[":名前", ":name"],
+                form_grammar.tag(":名前"),
                 "contract requires :名前",
             )?;
             let source = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":出典", ":source"],
+                form_grammar.tag(":出典"),
                 "contract requires :出典",
             )?;
             let path = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":パス", ":path"],
+                form_grammar.tag(":パス"),
                 "contract requires :パス",
             )?;
-            Ok(format!(
-                "(fact contract-doc {} {})\n(fact contract-impl {} {})\n(fact sd-contract {} {} {})",
-                sexpr_to_string(name),
-                sexpr_to_string(source),
-                sexpr_to_string(name),
-                sexpr_to_string(path),
-                sexpr_to_string(name),
-                sexpr_to_string(source),
-                sexpr_to_string(path)
-            ))
+            Ok(vec![
+                core_form(
+                    "fact",
+                    vec![
+                        synth_atom("contract-doc", head_node),
+                        name.clone(),
+                        source.clone(),
+                    ],
+                ),
+                core_form(
+                    "fact",
+                    vec![
+                        synth_atom("contract-impl", head_node),
+                        name.clone(),
+                        path.clone(),
+                    ],
+                ),
+                core_form(
+                    "fact",
+                    vec![
+                        synth_atom("sd-contract", head_node),
+                        name.clone(),
+                        source.clone(),
+                        path.clone(),
+                    ],
+                ),
+            ])
         }
         "quality-gate" => {
             if list.len() < 4 {
@@ -1193,39 +2096,82 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
                 src,
                 form,
                 &tags,
-                &[":名前", ":name"],
+                form_grammar.tag(":名前"),
                 "quality-gate requires :名前",
             )?;
             let command = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":コマンド", ":command"],
+                form_grammar.tag(":コマンド"),
                 "quality-gate requires :コマンド",
             )?;
             let source = required_tag_value(
                 src,
                 form,
                 &tags,
-                &[":出典", ":source"],
+                form_grammar.tag(":出典"),
                 "quality-gate requires :出典",
             )?;
-            let required = match optional_tag_value(&tags, &[":必須", ":required"]) {
+            let required_node = optional_tag_node(&tags, form_grammar.tag(":必須"));
+            let required = match required_node.and_then(SExpr::as_atom) {
                 Some("true") | Some("yes") => "yes",
                 Some("false") | Some("no") => "no",
                 Some(_) | None => "yes",
             };
-            Ok(format!(
-                "(fact gate-source {} {})\n(fact gate-required {} {})\n(fact sd-quality-gate {} {} {} {})",
-                sexpr_to_string(name),
-                sexpr_to_string(source),
-                sexpr_to_string(name),
-                required,
-                sexpr_to_string(name),
-                sexpr_to_string(command),
-                sexpr_to_string(source),
-                required
-            ))
+            let required = synth_atom(required, required_node.unwrap_or(head_node));
+            Ok(vec![
+                core_form(
+                    "fact",
+                    vec![
+                        synth_atom("gate-source", head_node),
+                        name.clone(),
+                        source.clone(),
+                    ],
+                ),
+                core_form(
+                    "fact",
+                    vec![
+                        synth_atom("gate-required", head_node),
+                        name.clone(),
+                        required.clone(),
+                    ],
+                ),
+                core_form(
+                    "fact",
+                    vec![
+                        synth_atom("sd-quality-gate", head_node),
+                        name.clone(),
+                        command.clone(),
+                        source.clone(),
+                        required,
+                    ],
+                ),
+            ])
+        }
+        "when-profile" => {
+            if list.len() < 2 {
+                return Err(Diagnostic::new(
+                    "E-PARSE",
+                    "when-profile expects a profile name and forms",
+                    Some(make_span(src, start, end)),
+                ));
+            }
+            let mut core_args = vec![list[1].clone()];
+            for inner in &list[2..] {
+                let inner_head = match inner {
+                    SExpr::List(items, _, _) => items.first().and_then(SExpr::as_atom),
+                    SExpr::Atom(_, _, _) => None,
+                };
+                if !inner_head
+                    .and_then(canonical_surface_head)
+                    .is_some_and(|kind| matches!(kind, "fact" | "rule" | "assert"))
+                {
+                    return Err(profile_form_error(src, inner));
+                }
+                core_args.extend(desugar_surface_toplevel(src, inner, keywords)?);
+            }
+            Ok(vec![core_form("when-profile", core_args)])
         }
         _ => Err(Diagnostic::new(
             "E-PARSE",
@@ -1235,29 +2181,52 @@ fn desugar_surface_toplevel(src: &str, form: &SExpr) -> Result<String, Diagnosti
     }
 }
 
-fn canonical_surface_head(head: &str) -> Option<&'static str> {
-    match head {
-        "import" | "インポート" => Some("import"),
-        "alias" | "同義語" => Some("alias"),
-        "sort" | "型" => Some("sort"),
-        "data" | "データ" => Some("data"),
-        "relation" | "関係" => Some("relation"),
-        "fact" | "事実" => Some("fact"),
-        "rule" | "規則" => Some("rule"),
-        "assert" | "検証" => Some("assert"),
-        "universe" | "宇宙" => Some("universe"),
-        "defn" | "関数" => Some("defn"),
-        "project" | "プロジェクト" => Some("project"),
-        "module" | "モジュール" => Some("module"),
-        "reference" | "参照" => Some("reference"),
-        "contract" | "契約" => Some("contract"),
-        "quality-gate" | "品質ゲート" => Some("quality-gate"),
-        _ => None,
+fn apply_surface_keywords(
+    forms: &mut [SExpr],
+    keywords: &SurfaceKeywordMap,
+) -> Vec<(usize, usize)> {
+    let mut rewritten = Vec::new();
+    if keywords.is_empty() {
+        return rewritten;
+    }
+    rewrite_surface_keywords(forms, keywords, &mut rewritten);
+    rewritten
+}
+
+fn rewrite_surface_keywords(
+    forms: &mut [SExpr],
+    keywords: &SurfaceKeywordMap,
+    rewritten: &mut Vec<(usize, usize)>,
+) {
+    for form in forms {
+        let SExpr::List(items, _, _) = form else {
+            continue;
+        };
+        for (idx, item) in items.iter_mut().enumerate() {
+            let SExpr::Atom(text, start, end) = item else {
+                continue;
+            };
+            if (idx == 0 || text.starts_with(':'))
+                && let Some(canonical) = keywords.canonical(text)
+            {
+                *text = canonical.to_string();
+                rewritten.push((*start, *end));
+            }
+        }
+        if items.len() > 2
+            && items[0].as_atom().and_then(canonical_surface_head) == Some("when-profile")
+        {
+            rewrite_surface_keywords(&mut items[2..], keywords, rewritten);
+        }
     }
 }
 
+fn canonical_surface_head(head: &str) -> Option<&'static str> {
+    grammar::surface_form(head).map(|form| form.head)
+}
+
 fn parse_tag_pairs<'a>(
-    src: &str,
+    src: &Source<'_>,
     list: &'a [SExpr],
     start_idx: usize,
 ) -> Result<Vec<(String, &'a SExpr)>, Diagnostic> {
@@ -1300,7 +2269,7 @@ fn parse_tag_pairs<'a>(
 }
 
 fn required_tag_value<'a>(
-    src: &str,
+    src: &Source<'_>,
     form: &SExpr,
     tags: &[(String, &'a SExpr)],
     candidates: &[&str],
@@ -1319,17 +2288,17 @@ fn required_tag_value<'a>(
     ))
 }
 
-fn optional_tag_value<'a>(tags: &[(String, &'a SExpr)], candidates: &[&str]) -> Option<&'a str> {
+fn optional_tag_node<'a>(tags: &[(String, &'a SExpr)], candidates: &[&str]) -> Option<&'a SExpr> {
     for candidate in candidates {
         if let Some((_, value)) = tags.iter().find(|(key, _)| key == candidate) {
-            return value.as_atom();
+            return Some(*value);
         }
     }
     None
 }
 
 fn as_list_items<'a>(
-    src: &str,
+    src: &Source<'_>,
     node: &'a SExpr,
     expected: &str,
 ) -> Result<&'a [SExpr], Diagnostic> {
@@ -1346,49 +2315,117 @@ fn as_list_items<'a>(
     }
 }
 
-fn sexpr_to_string(node: &SExpr) -> String {
-    match node {
-        SExpr::Atom(a, _, _) => render_atom(a),
-        SExpr::List(items, _, _) => {
-            let inner = items
-                .iter()
-                .map(sexpr_to_string)
-                .collect::<Vec<_>>()
-                .join(" ");
-            format!("({inner})")
-        }
+fn declaration_tag_args(tags: &[(String, &SExpr)]) -> Vec<SExpr> {
+    let mut out = Vec::new();
+    for (key, value) in tags {
+        let core_key = if grammar::DOC_TAG.contains(&key.as_str()) {
+            ":doc"
+        } else if grammar::MESSAGE_TAG.contains(&key.as_str()) {
+            ":message"
+        } else if grammar::PROFILE_TAG.contains(&key.as_str()) {
+            ":profile"
+        } else if grammar::is_surface_tag(key) {
+            continue;
+        } else {
+            key.as_str()
+        };
+        out.push(synth_atom(core_key, value));
+        out.push((*value).clone());
     }
+    out
 }
 
-fn render_atom(atom: &str) -> String {
-    let needs_quote = atom.is_empty()
-        || atom.chars().any(|ch| {
-            ch.is_whitespace() || matches!(ch, '(' | ')' | ';' | '"' | '\\' | '\n' | '\r' | '\t')
-        });
-    if !needs_quote {
-        return atom.to_string();
-    }
-    let mut escaped = String::new();
-    for ch in atom.chars() {
-        match ch {
-            '\\' => escaped.push_str("\\\\"),
-            '"' => escaped.push_str("\\\""),
-            '\n' => escaped.push_str("\\n"),
-            '\t' => escaped.push_str("\\t"),
-            '\r' => escaped.push_str("\\r"),
-            _ => escaped.push(ch),
+fn synth_atom(text: &str, origin: &SExpr) -> SExpr {
+    let (start, end) = origin.span_bounds();
+    SExpr::Atom(text.to_string(), start, end)
+}
+
+fn is_tag_atom(node: &SExpr) -> bool {
+    node.as_atom().is_some_and(|a| a.starts_with(':'))
+}
+
+#[derive(Debug, Default)]
+struct DeclarationTags {
+    doc: Option<String>,
+    message: Option<String>,
+    profile: Option<String>,
+    attributes: BTreeMap<String, String>,
+}
+
+fn parse_declaration_tags(
+    src: &Source<'_>,
+    list: &[SExpr],
+    positional: usize,
+    kind: &str,
+) -> Result<DeclarationTags, Diagnostic> {
+    let form_grammar = grammar::form(kind);
+    let mut out = DeclarationTags::default();
+    for (idx, (key, value)) in parse_tag_pairs(src, list, positional)?
+        .into_iter()
+        .enumerate()
+    {
+        let feature = match key.as_str() {
+            ":profile" => EditionFeature::Profiles,
+            ":measure" | ":decreases" => EditionFeature::TerminationMeasures,
+            ":key" => EditionFeature::RelationKeys,
+            _ => EditionFeature::DeclarationTags,
+        };
+        require_feature(src, feature, &list[positional + 2 * idx])?;
+        let duplicate = match key.as_str() {
+            ":doc" if form_grammar.is_some_and(|form| form.doc) => out
+                .doc
+                .replace(text_value(src, value, "doc string")?)
+                .is_some(),
+            ":message" if form_grammar.is_some_and(|form| form.message) => out
+                .message
+                .replace(text_value(src, value, "assert message")?)
+                .is_some(),
+            ":profile" if kind == "assert" => {
+                out.profile.replace(profile_name(src, value)?).is_some()
+            }
+            ":measure" | ":decreases" if kind == "defn" => false,
+            ":key" if kind == "relation" => false,
+            ":doc" | ":message" | ":profile" => {
+                let (s, e) = list[0].span_bounds();
+                return Err(Diagnostic::new(
+                    "E-PARSE",
+                    format!("{kind} does not accept {key}"),
+                    Some(make_span(src, s, e)),
+                ));
+            }
+            _ => out
+                .attributes
+                .insert(
+                    key.trim_start_matches(':').to_string(),
+                    text_value(src, value, "attribute value")?,
+                )
+                .is_some(),
+        };
+        if duplicate {
+            let (s, e) = value.span_bounds();
+            return Err(Diagnostic::new(
+                "E-PARSE",
+                format!("duplicate {kind} tag: {key}"),
+                Some(make_span(src, s, e)),
+            ));
         }
     }
-    format!("\"{escaped}\"")
+    Ok(out)
 }
 
-fn is_tag_atom(node: &SExpr) -> bool {
-    node.as_atom().is_some_and(|a| a.starts_with(':'))
+fn text_value(src: &Source<'_>, node: &SExpr, expected: &str) -> Result<String, Diagnostic> {
+    let text = atom_required(src, node, expected)?;
+    if is_quoted_atom(&text) {
+        Ok(text[1..text.len() - 1].to_string())
+    } else {
+        Ok(text)
+    }
 }
 
 enum TopLevel {
     Import(ImportDecl),
     Alias(AliasDecl),
+    Const(ConstDecl),
     Sort(SortDecl),
     Data(DataDecl),
     Relation(RelationDecl),
@@ -1397,9 +2434,14 @@ enum TopLevel {
     Assert(AssertDecl),
     Universe(UniverseDecl),
     Defn(Defn),
+    Profiled(Vec<TopLevel>),
 }
 
-fn parse_toplevel(src: &str, form: &SExpr) -> Result<TopLevel, Diagnostic> {
+fn parse_toplevel(
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
+    form: &SExpr,
+) -> Result<TopLevel, Diagnostic> {
     let (start, end) = form.span_bounds();
     let list = match form {
         SExpr::List(items, _, _) => items,
@@ -1430,6 +2472,7 @@ fn parse_toplevel(src: &str, form: &SExpr) -> Result<TopLevel, Diagnostic> {
     match head {
         "import" => parse_import(src, list),
         "alias" => parse_alias(src, list),
+        "defconst" => parse_defconst(exprs, src, list),
         "sort" => parse_sort(src, list),
         "data" => parse_data(src, list),
         "relation" => parse_relation(src, list),
@@ -1437,7 +2480,8 @@ fn parse_toplevel(src: &str, form: &SExpr) -> Result<TopLevel, Diagnostic> {
         "rule" => parse_rule(src, list),
         "assert" => parse_assert(src, list),
         "universe" => parse_universe(src, list),
-        "defn" => parse_defn(src, list),
+        "defn" => parse_defn(exprs, src, list),
+        "when-profile" => parse_when_profile(exprs, src, list),
         _ => Err(Diagnostic::new(
             "E-PARSE",
             format!("unknown top-level form: {head}"),
@@ -1446,7 +2490,67 @@ fn parse_toplevel(src: &str, form: &SExpr) -> Result<TopLevel, Diagnostic> {
     }
 }
 
-fn parse_import(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
+fn parse_when_profile(
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
+    list: &[SExpr],
+) -> Result<TopLevel, Diagnostic> {
+    require_feature(src, EditionFeature::Profiles, &list[0])?;
+    if list.len() < 2 {
+        let (s, e) = list[0].span_bounds();
+        return Err(Diagnostic::new(
+            "E-PARSE",
+            "when-profile expects a profile name and forms",
+            Some(make_span(src, s, e)),
+        ));
+    }
+    let profile = profile_name(src, &list[1])?;
+    let mut forms = Vec::new();
+    for form in &list[2..] {
+        let mut item = parse_toplevel(exprs, src, form)?;
+        let slot = match &mut item {
+            TopLevel::Fact(fact) => &mut fact.profile,
+            TopLevel::Rule(rule) => &mut rule.profile,
+            TopLevel::Assert(assertion) => &mut assertion.profile,
+            _ => return Err(profile_form_error(src, form)),
+        };
+        if let Some(other) = slot.as_ref().filter(|other| **other != profile) {
+            let (s, e) = form.span_bounds();
+            return Err(Diagnostic::new(
+                "E-PARSE",
+                format!("conflicting profiles: {other} inside when-profile {profile}"),
+                Some(make_span(src, s, e)),
+            ));
+        }
+        *slot = Some(profile.clone());
+        forms.push(item);
+    }
+    Ok(TopLevel::Profiled(forms))
+}
+
+fn profile_name(src: &Source<'_>, node: &SExpr) -> Result<String, Diagnostic> {
+    let name = atom_required(src, node, "profile name")?;
+    if is_quoted_atom(&name) || name.starts_with(':') {
+        let (s, e) = node.span_bounds();
+        return Err(Diagnostic::new(
+            "E-PARSE",
+            format!("profile name must be a bare name: {name}"),
+            Some(make_span(src, s, e)),
+        ));
+    }
+    Ok(name)
+}
+
+fn profile_form_error(src: &Source<'_>, form: &SExpr) -> Diagnostic {
+    let (s, e) = form.span_bounds();
+    Diagnostic::new(
+        "E-PARSE",
+        "when-profile accepts only fact, rule and assert forms",
+        Some(make_span(src, s, e)),
+    )
+}
+
+fn parse_import(src: &Source<'_>, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     if list.len() != 2 {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
@@ -1457,7 +2561,11 @@ fn parse_import(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     }
 
     let path = atom_required(src, &list[1], "import path")?;
-    let path = path.trim_matches('"').to_string();
+    let path = path
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+        .unwrap_or(&path)
+        .to_string();
     let (s, e) = list[0].span_bounds();
     Ok(TopLevel::Import(ImportDecl {
         path,
@@ -1465,7 +2573,7 @@ fn parse_import(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     }))
 }
 
-fn parse_alias(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
+fn parse_alias(src: &Source<'_>, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     if list.len() != 3 {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
@@ -1484,8 +2592,49 @@ fn parse_alias(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     }))
 }
 
-fn parse_sort(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
-    if list.len() != 2 {
+fn parse_defconst(
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
+    list: &[SExpr],
+) -> Result<TopLevel, Diagnostic> {
+    require_feature(src, EditionFeature::Constants, &list[0])?;
+    if list.len() != 3 {
+        let (s, e) = list[0].span_bounds();
+        return Err(Diagnostic::new(
+            "E-PARSE",
+            "defconst expects name and value",
+            Some(make_span(src, s, e)),
+        ));
+    }
+    let name = atom_required(src, &list[1], "constant name")?;
+    let value = parse_expr(exprs, src, &list[2], &HashSet::new())?;
+    if let Some(span) = non_constant_expr_span(exprs, value) {
+        return Err(Diagnostic::new(
+            "E-PARSE",
+            "defconst value must be built from literals, constants, constructors and + - *",
+            Some(span.clone()),
+        ));
+    }
+    let (s, e) = list[0].span_bounds();
+    Ok(TopLevel::Const(ConstDecl {
+        name,
+        value,
+        span: make_span(src, s, e),
+    }))
+}
+
+fn non_constant_expr_span(exprs: &ExprArena, expr: ExprId) -> Option<&Span> {
+    match &exprs[expr] {
+        Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => None,
+        Expr::Call { args, .. } => args
+            .iter()
+            .find_map(|arg| non_constant_expr_span(exprs, *arg)),
+        other => Some(other.span()),
+    }
+}
+
+fn parse_sort(src: &Source<'_>, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
+    if list.len() < 2 || (list.len() > 2 && !is_tag_atom(&list[2])) {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
             "E-PARSE",
@@ -1494,14 +2643,16 @@ fn parse_sort(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
         ));
     }
     let name = atom_required(src, &list[1], "sort name")?;
+    let tags = parse_declaration_tags(src, list, 2, "sort")?;
     let (s, e) = list[0].span_bounds();
     Ok(TopLevel::Sort(SortDecl {
         name,
+        attributes: tags.attributes,
         span: make_span(src, s, e),
     }))
 }
 
-fn parse_data(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
+fn parse_data(src: &Source<'_>, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     if list.len() < 3 {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
@@ -1511,9 +2662,22 @@ fn parse_data(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
         ));
     }
     let name = atom_required(src, &list[1], "data name")?;
+    let tag_start = list
+        .iter()
+        .skip(2)
+        .position(is_tag_atom)
+        .map_or(list.len(), |idx| idx + 2);
+    if tag_start == 2 {
+        let (s, e) = list[0].span_bounds();
+        return Err(Diagnostic::new(
+            "E-PARSE",
+            "data expects type name and at least one constructor",
+            Some(make_span(src, s, e)),
+        ));
+    }
 
     let mut ctors = Vec::new();
-    for node in list.iter().skip(2) {
+    for node in &list[2..tag_start] {
         let ctor_list = match node {
             SExpr::List(items, _, _) => items,
             _ => {
@@ -1546,16 +2710,18 @@ fn parse_data(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
         });
     }
 
+    let tags = parse_declaration_tags(src, list, tag_start, "data")?;
     let (s, e) = list[0].span_bounds();
     Ok(TopLevel::Data(DataDecl {
         name,
         constructors: ctors,
+        attributes: tags.attributes,
         span: make_span(src, s, e),
     }))
 }
 
-fn parse_relation(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
-    if list.len() != 3 {
+fn parse_relation(src: &Source<'_>, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
+    if list.len() < 3 || (list.len() > 3 && !is_tag_atom(&list[3])) {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
             "E-PARSE",
@@ -1576,18 +2742,95 @@ fn parse_relation(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
         }
     };
     let mut arg_sorts = Vec::new();
+    let mut arg_refinements = Vec::new();
     for item in sort_list {
-        arg_sorts.push(atom_required(src, item, "sort name")?);
+        let (sort, refinement) = parse_relation_arg(src, item)?;
+        arg_sorts.push(sort);
+        arg_refinements.push(refinement);
     }
+    let key = parse_relation_key(src, list, arg_sorts.len())?;
+    let tags = parse_declaration_tags(src, list, 3, "relation")?;
     let (s, e) = list[0].span_bounds();
     Ok(TopLevel::Relation(RelationDecl {
         name,
         arg_sorts,
+        arg_refinements,
+        key,
+        doc: tags.doc,
+        attributes: tags.attributes,
         span: make_span(src, s, e),
     }))
 }
 
-fn parse_fact(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
+fn parse_relation_key(
+    src: &Source<'_>,
+    list: &[SExpr],
+    arity: usize,
+) -> Result<Option<Vec<usize>>, Diagnostic> {
+    let mut key = None;
+    for (tag, value) in parse_tag_pairs(src, list, 3)? {
+        if tag != ":key" {
+            continue;
+        }
+        let (s, e) = value.span_bounds();
+        let positions = match value {
+            SExpr::List(items, _, _) if !items.is_empty() => items
+                .iter()
+                .map(|item| item.as_atom()?.parse::<usize>().ok())
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        let positions = positions.filter(|positions| {
+            let distinct = positions.iter().collect::<HashSet<_>>();
+            distinct.len() == positions.len()
+                && positions.iter().all(|&pos| (1..=arity).contains(&pos))
+        });
+        let Some(positions) = positions else {
+            return Err(Diagnostic::new(
+                "E-PARSE",
+                format!("relation :key expects distinct argument positions from 1 to {arity}"),
+                Some(make_span(src, s, e)),
+            ));
+        };
+        if key.replace(positions).is_some() {
+            return Err(Diagnostic::new(
+                "E-PARSE",
+                "duplicate relation tag: :key",
+                Some(make_span(src, s, e)),
+            ));
+        }
+    }
+    Ok(key)
+}
+
+fn parse_relation_arg(
+    src: &Source<'_>,
+    node: &SExpr,
+) -> Result<(String, Option<ArgRefinement>), Diagnostic> {
+    let SExpr::List(items, _, _) = node else {
+        let sort = atom_required(src, node, "sort name")?;
+        let (s, e) = node.span_bounds();
+        src.use_name(&sort, s, e);
+        return Ok((sort, None));
+    };
+    if items.len() != 4 || items[0].as_atom() != Some("Refine") {
+        let (s, e) = node.span_bounds();
+        return Err(Diagnostic::new(
+            "E-PARSE",
+            "relation argument must be a sort name or (Refine var sort formula)",
+            Some(make_span(src, s, e)),
+        ));
+    }
+    require_feature(src, EditionFeature::RelationRefinements, node)?;
+    let var = atom_required(src, &items[1], "refinement variable")?;
+    let sort = atom_required(src, &items[2], "sort name")?;
+    let (s, e) = items[2].span_bounds();
+    src.use_name(&sort, s, e);
+    let formula = parse_refine_formula(src, &items[3], &HashSet::from([var.clone()]))?;
+    Ok((sort, Some(ArgRefinement { var, formula })))
+}
+
+fn parse_fact(src: &Source<'_>, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     if list.len() < 2 {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
@@ -1597,6 +2840,8 @@ fn parse_fact(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
         ));
     }
     let name = atom_required(src, &list[1], "fact predicate")?;
+    let (s, e) = list[1].span_bounds();
+    src.use_name(&name, s, e);
     let mut terms = Vec::new();
     for item in list.iter().skip(2) {
         let term = parse_const_term(src, item)?;
@@ -1606,31 +2851,53 @@ fn parse_fact(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     Ok(TopLevel::Fact(Fact {
         name,
         terms,
+        profile: None,
         span: make_span(src, s, e),
     }))
 }
 
-fn parse_rule(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
-    if list.len() != 3 {
+fn parse_rule(src: &Source<'_>, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
+    let skolem_tag = list.len() == 5 && list[3].as_atom() == Some(":skolem");
+    if list.len() != 3 && !skolem_tag {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
             "E-PARSE",
-            "rule expects head and body",
+            "rule expects head and body, optionally followed by :skolem N",
             Some(make_span(src, s, e)),
         ));
     }
     let head = parse_rule_atom(src, &list[1])?;
     let body = parse_rule_formula(src, &list[2])?;
+    let skolem = if skolem_tag {
+        require_feature(src, EditionFeature::SkolemRules, &list[3])?;
+        let depth = list[4]
+            .as_atom()
+            .and_then(|atom| atom.parse::<usize>().ok())
+            .filter(|depth| *depth > 0);
+        let Some(depth) = depth else {
+            let (s, e) = list[4].span_bounds();
+            return Err(Diagnostic::new(
+                "E-PARSE",
+                "rule :skolem expects a positive depth bound",
+                Some(make_span(src, s, e)),
+            ));
+        };
+        Some(depth)
+    } else {
+        None
+    };
     let (s, e) = list[0].span_bounds();
     Ok(TopLevel::Rule(Rule {
         head,
         body,
+        skolem,
+        profile: None,
         span: make_span(src, s, e),
     }))
 }
 
-fn parse_assert(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
-    if list.len() != 4 {
+fn parse_assert(src: &Source<'_>, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
+    if list.len() < 4 || (list.len() > 4 && !is_tag_atom(&list[4])) {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
             "E-PARSE",
@@ -1685,16 +2952,21 @@ fn parse_assert(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     }
 
     let formula = parse_refine_formula(src, &list[3], &scope)?;
+    let tags = parse_declaration_tags(src, list, 4, "assert")?;
     let (s, e) = list[0].span_bounds();
     Ok(TopLevel::Assert(AssertDecl {
         name,
         params,
         formula,
+        doc: tags.doc,
+        message: tags.message,
+        attributes: tags.attributes,
+        profile: tags.profile,
         span: make_span(src, s, e),
     }))
 }
 
-fn parse_universe(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
+fn parse_universe(src: &Source<'_>, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     if list.len() != 3 {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
@@ -1704,6 +2976,8 @@ fn parse_universe(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
         ));
     }
     let ty_name = atom_required(src, &list[1], "universe type")?;
+    let (s, e) = list[1].span_bounds();
+    src.use_name(&ty_name, s, e);
     let values_node = match &list[2] {
         SExpr::List(items, _, _) => items,
         node => {
@@ -1729,8 +3003,17 @@ fn parse_universe(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     }))
 }
 
-fn parse_defn(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
-    if list.len() != 5 {
+fn parse_defn(
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
+    list: &[SExpr],
+) -> Result<TopLevel, Diagnostic> {
+    let with_clauses = list.get(4).and_then(SExpr::as_atom) == Some(":clauses");
+    if with_clauses {
+        require_feature(src, EditionFeature::ClauseDefns, &list[4])?;
+    }
+    let tags_from = if with_clauses { 6 } else { 5 };
+    if list.len() < tags_from || (list.len() > tags_from && !is_tag_atom(&list[tags_from])) {
         let (s, e) = list[0].span_bounds();
         return Err(Diagnostic::new(
             "E-PARSE",
@@ -1787,24 +3070,160 @@ fn parse_defn(src: &str, list: &[SExpr]) -> Result<TopLevel, Diagnostic> {
     }
 
     let ret_type = parse_type(src, &list[3], &param_scope)?;
-    let body = parse_expr(src, &list[4], &param_scope)?;
+    let (body, clauses) = if with_clauses {
+        let clauses = parse_clauses(exprs, src, &list[5], &params, &param_scope)?;
+        let body = desugar_clauses(exprs, &params, &clauses, &[]).body;
+        (body, clauses)
+    } else {
+        (parse_expr(exprs, src, &list[4], &param_scope)?, Vec::new())
+    };
+    let measure = parse_defn_measure(exprs, src, list, tags_from, &param_scope)?;
+    let tags = parse_declaration_tags(src, list, tags_from, "defn")?;
     let (s, e) = list[0].span_bounds();
     Ok(TopLevel::Defn(Defn {
         name,
         params,
         ret_type,
         body,
+        measure,
+        clauses,
+        doc: tags.doc,
+        attributes: tags.attributes,
         span: make_span(src, s, e),
     }))
 }
 
-fn parse_type(src: &str, node: &SExpr, scope: &HashSet<String>) -> Result<Type, Diagnostic> {
+fn parse_clauses(
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
+    node: &SExpr,
+    params: &[Param],
+    param_scope: &HashSet<String>,
+) -> Result<Vec<Clause>, Diagnostic> {
+    let (s, e) = node.span_bounds();
+    let items = match node {
+        SExpr::List(items, _, _) if !items.is_empty() => items,
+        _ => {
+            return Err(Diagnostic::new(
+                "E-PARSE",
+                "defn :clauses expects a non-empty list of clauses",
+                Some(make_span(src, s, e)),
+            ));
+        }
+    };
+    let mut clauses = Vec::new();
+    for item in items {
+        let (cs, ce) = item.span_bounds();
+        let clause_span = make_span(src, cs, ce);
+        let (patterns_node, body_node) = match item {
+            SExpr::List(parts, _, _) if parts.len() == 2 => (&parts[0], &parts[1]),
+            _ => {
+                return Err(Diagnostic::new(
+                    "E-PARSE",
+                    "clause must be ((pattern ...) body)",
+                    Some(clause_span),
+                ));
+            }
+        };
+        let pattern_nodes = match patterns_node {
+            SExpr::List(nodes, _, _) if nodes.len() == params.len() => nodes,
+            _ => {
+                let (ps, pe) = patterns_node.span_bounds();
+                return Err(Diagnostic::new(
+                    "E-PARSE",
+                    format!(
+                        "clause expects one pattern per parameter ({})",
+                        params.len()
+                    ),
+                    Some(make_span(src, ps, pe)),
+                ));
+            }
+        };
+        let mut bindings = HashSet::new();
+        let mut patterns = Vec::new();
+        for pattern_node in pattern_nodes {
+            let mut pattern_bindings = HashSet::new();
+            patterns.push(parse_pattern(
+                exprs,
+                src,
+                pattern_node,
+                &mut pattern_bindings,
+            )?);
+            if let Some(name) = pattern_bindings
+                .iter()
+                .find(|name| bindings.contains(*name))
+            {
+                let (ps, pe) = pattern_node.span_bounds();
+                return Err(Diagnostic::new(
+                    "E-PARSE",
+                    format!("clause binds {name} more than once"),
+                    Some(make_span(src, ps, pe)),
+                ));
+            }
+            bindings.extend(pattern_bindings);
+        }
+        let mut scope = param_scope.clone();
+        scope.extend(bindings);
+        clauses.push(Clause {
+            patterns,
+            body: parse_expr(exprs, src, body_node, &scope)?,
+            span: clause_span,
+        });
+    }
+    Ok(clauses)
+}
+
+fn parse_defn_measure(
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
+    list: &[SExpr],
+    tags_from: usize,
+    param_scope: &HashSet<String>,
+) -> Result<Option<ExprId>, Diagnostic> {
+    let mut measure = None;
+    for (key, value) in parse_tag_pairs(src, list, tags_from)? {
+        let expr = match key.as_str() {
+            ":measure" => parse_expr(exprs, src, value, param_scope)?,
+            ":decreases" => {
+                let (s, e) = value.span_bounds();
+                let param = value.as_atom().filter(|name| param_scope.contains(*name));
+                let Some(param) = param else {
+                    return Err(Diagnostic::new(
+                        "E-PARSE",
+                        "defn :decreases expects a parameter name",
+                        Some(make_span(src, s, e)),
+                    ));
+                };
+                exprs.alloc(Expr::Var {
+                    name: param.to_string(),
+                    span: make_span(src, s, e),
+                })
+            }
+            _ => continue,
+        };
+        if measure.replace(expr).is_some() {
+            let (s, e) = value.span_bounds();
+            return Err(Diagnostic::new(
+                "E-PARSE",
+                "defn accepts a single termination measure (:measure or :decreases)",
+                Some(make_span(src, s, e)),
+            ));
+        }
+    }
+    Ok(measure)
+}
+
+fn parse_type(src: &Source<'_>, node: &SExpr, scope: &HashSet<String>) -> Result<Type, Diagnostic> {
     if let Some(atom) = node.as_atom() {
         return Ok(match atom {
             "Bool" => Type::Bool,
             "Int" => Type::Int,
             "Symbol" => Type::Symbol,
-            other => Type::Domain(other.to_string()),
+            other => {
+                let (s, e) = node.span_bounds();
+                src.use_name(other, s, e);
+                Type::Domain(other.to_string())
+            }
         });
     }
 
@@ -1880,6 +3299,8 @@ fn parse_type(src: &str, node: &SExpr, scope: &HashSet<String>) -> Result<Type,
                 ));
             }
             let name = atom_required(src, &list[1], "ADT name")?;
+            let (s, e) = list[1].span_bounds();
+            src.use_name(&name, s, e);
             Ok(Type::Adt(name))
         }
         _ => {
@@ -1893,7 +3314,7 @@ fn parse_type(src: &str, node: &SExpr, scope: &HashSet<String>) -> Result<Type,
     }
 }
 
-fn parse_rule_formula(src: &str, node: &SExpr) -> Result<Formula, Diagnostic> {
+fn parse_rule_formula(src: &Source<'_>, node: &SExpr) -> Result<Formula, Diagnostic> {
     if let Some(atom) = node.as_atom() {
         return if atom == "true" {
             Ok(Formula::True)
@@ -1952,7 +3373,7 @@ fn parse_rule_formula(src: &str, node: &SExpr) -> Result<Formula, Diagnostic> {
 }
 
 fn parse_refine_formula(
-    src: &str,
+    src: &Source<'_>,
     node: &SExpr,
     var_scope: &HashSet<String>,
 ) -> Result<Formula, Diagnostic> {
@@ -2014,6 +3435,11 @@ fn parse_refine_formula(
         }
         _ => {
             let pred = head;
+            if pred == DISEQUALITY_PRED {
+                require_feature(src, EditionFeature::Disequality, &list[0])?;
+            }
+            let (s, e) = list[0].span_bounds();
+            src.use_name(&pred, s, e);
             let mut terms = Vec::new();
             for t in list.iter().skip(1) {
                 terms.push(parse_formula_term(src, t, var_scope)?);
@@ -2023,7 +3449,7 @@ fn parse_refine_formula(
     }
 }
 
-fn parse_rule_atom(src: &str, node: &SExpr) -> Result<Atom, Diagnostic> {
+fn parse_rule_atom(src: &Source<'_>, node: &SExpr) -> Result<Atom, Diagnostic> {
     let list = match node {
         SExpr::List(items, _, _) => items,
         SExpr::Atom(_, s, e) => {
@@ -2043,6 +3469,11 @@ fn parse_rule_atom(src: &str, node: &SExpr) -> Result<Atom, Diagnostic> {
         ));
     }
     let pred = atom_required(src, &list[0], "predicate name")?;
+    if pred == DISEQUALITY_PRED {
+        require_feature(src, EditionFeature::Disequality, &list[0])?;
+    }
+    let (s, e) = list[0].span_bounds();
+    src.use_name(&pred, s, e);
     let mut terms = Vec::new();
     for t in list.iter().skip(1) {
         terms.push(parse_rule_term(src, t)?);
@@ -2050,30 +3481,41 @@ fn parse_rule_atom(src: &str, node: &SExpr) -> Result<Atom, Diagnostic> {
     Ok(Atom { pred, terms })
 }
 
-fn parse_expr(src: &str, node: &SExpr, scope: &HashSet<String>) -> Result<Expr, Diagnostic> {
+fn parse_expr(
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
+    node: &SExpr,
+    scope: &HashSet<String>,
+) -> Result<ExprId, Diagnostic> {
     if let Some(atom) = node.as_atom() {
         let (s, e) = node.span_bounds();
-        return if atom == "true" || atom == "false" {
-            Ok(Expr::Bool {
+        let expr = if atom == "true" || atom == "false" {
+            Expr::Bool {
                 value: atom == "true",
                 span: make_span(src, s, e),
-            })
+            }
         } else if let Ok(i) = atom.parse::<i64>() {
-            Ok(Expr::Int {
+            Expr::Int {
                 value: i,
                 span: make_span(src, s, e),
-            })
+            }
+        } else if atom == "_?" {
+            require_feature(src, EditionFeature::TypedHoles, node)?;
+            Expr::Hole {
+                span: make_span(src, s, e),
+            }
         } else if scope.contains(atom) {
-            Ok(Expr::Var {
+            Expr::Var {
                 name: atom.to_string(),
                 span: make_span(src, s, e),
-            })
+            }
         } else {
-            Ok(Expr::Symbol {
+            Expr::Symbol {
                 value: atom.to_string(),
                 span: make_span(src, s, e),
-            })
+            }
         };
+        return Ok(exprs.alloc(expr));
     }
 
     let list = match node {
@@ -2092,7 +3534,7 @@ fn parse_expr(src: &str, node: &SExpr, scope: &HashSet<String>) -> Result<Expr,
     let head = atom_required(src, &list[0], "expression head")?;
     let (s, e) = node.span_bounds();
 
-    match head.as_str() {
+    let expr = match head.as_str() {
         "let" => {
             if list.len() != 3 {
                 return Err(Diagnostic::new(
@@ -2137,18 +3579,18 @@ fn parse_expr(src: &str, node: &SExpr, scope: &HashSet<String>) -> Result<Expr,
                     ));
                 }
                 let bname = atom_required(src, &pair[0], "binding name")?;
-                let bexpr = parse_expr(src, &pair[1], &local_scope)?;
+                let bexpr = parse_expr(exprs, src, &pair[1], &local_scope)?;
                 let (bs, be) = b.span_bounds();
                 bindings.push((bname.clone(), bexpr, make_span(src, bs, be)));
                 local_scope.insert(bname);
             }
 
-            let body = parse_expr(src, &list[2], &local_scope)?;
-            Ok(Expr::Let {
+            let body = parse_expr(exprs, src, &list[2], &local_scope)?;
+            Expr::Let {
                 bindings,
-                body: Box::new(body),
+                body,
                 span: make_span(src, s, e),
-            })
+            }
         }
         "if" => {
             if list.len() != 4 {
@@ -2158,33 +3600,133 @@ fn parse_expr(src: &str, node: &SExpr, scope: &HashSet<String>) -> Result<Expr,
                     Some(make_span(src, s, e)),
                 ));
             }
-            let cond = parse_expr(src, &list[1], scope)?;
-            let then_branch = parse_expr(src, &list[2], scope)?;
-            let else_branch = parse_expr(src, &list[3], scope)?;
-            Ok(Expr::If {
-                cond: Box::new(cond),
-                then_branch: Box::new(then_branch),
-                else_branch: Box::new(else_branch),
+            let cond = parse_expr(exprs, src, &list[1], scope)?;
+            let then_branch = parse_expr(exprs, src, &list[2], scope)?;
+            let else_branch = parse_expr(exprs, src, &list[3], scope)?;
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
                 span: make_span(src, s, e),
-            })
+            }
+        }
+        "match" => parse_match_expr(exprs, src, node, list, scope)?,
+        "letfn" => {
+            require_feature(src, EditionFeature::LocalFunctions, &list[0])?;
+            if list.len() != 3 {
+                return Err(Diagnostic::new(
+                    "E-PARSE",
+                    "letfn expects functions and body",
+                    Some(make_span(src, s, e)),
+                ));
+            }
+            let fns_list = match &list[1] {
+                SExpr::List(items, _, _) => items,
+                n => {
+                    let (fs, fe) = n.span_bounds();
+                    return Err(Diagnostic::new(
+                        "E-PARSE",
+                        "letfn functions must be a list",
+                        Some(make_span(src, fs, fe)),
+                    ));
+                }
+            };
+            let mut fns = Vec::new();
+            for f in fns_list {
+                fns.push(parse_local_fn(exprs, src, f, scope)?);
+            }
+            let body = parse_expr(exprs, src, &list[2], scope)?;
+            Expr::LetFn {
+                fns,
+                body,
+                span: make_span(src, s, e),
+            }
         }
-        "match" => parse_match_expr(src, node, list, scope),
         _ => {
             let mut args = Vec::new();
             for a in list.iter().skip(1) {
-                args.push(parse_expr(src, a, scope)?);
+                args.push(parse_expr(exprs, src, a, scope)?);
             }
-            Ok(Expr::Call {
+            let (ns, ne) = list[0].span_bounds();
+            Expr::Call {
                 name: head,
+                name_span: make_span(src, ns, ne),
                 args,
                 span: make_span(src, s, e),
-            })
+            }
+        }
+    };
+    Ok(exprs.alloc(expr))
+}
+
+fn parse_local_fn(
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
+    node: &SExpr,
+    scope: &HashSet<String>,
+) -> Result<LocalFn, Diagnostic> {
+    let (s, e) = node.span_bounds();
+    let list = match node {
+        SExpr::List(items, _, _) if items.len() == 4 => items,
+        _ => {
+            return Err(Diagnostic::new(
+                "E-PARSE",
+                "local function must be (name params return-type body)",
+                Some(make_span(src, s, e)),
+            ));
+        }
+    };
+    let name = atom_required(src, &list[0], "local function name")?;
+    let params_list = match &list[1] {
+        SExpr::List(items, _, _) => items,
+        n => {
+            let (ps, pe) = n.span_bounds();
+            return Err(Diagnostic::new(
+                "E-PARSE",
+                "local function params must be a list",
+                Some(make_span(src, ps, pe)),
+            ));
         }
+    };
+
+    let mut params = Vec::new();
+    let mut param_scope = scope.clone();
+    for p in params_list {
+        let (ps, pe) = p.span_bounds();
+        let item = match p {
+            SExpr::List(items, _, _) if items.len() == 2 => items,
+            _ => {
+                return Err(Diagnostic::new(
+                    "E-PARSE",
+                    "parameter must be (name type)",
+                    Some(make_span(src, ps, pe)),
+                ));
+            }
+        };
+        let pname = atom_required(src, &item[0], "parameter name")?;
+        let ty = parse_type(src, &item[1], &HashSet::new())?;
+        params.push(Param {
+            name: pname.clone(),
+            ty,
+            span: make_span(src, ps, pe),
+        });
+        param_scope.insert(pname);
     }
+
+    let ret_type = parse_type(src, &list[2], &param_scope)?;
+    let body = parse_expr(exprs, src, &list[3], &param_scope)?;
+    Ok(LocalFn {
+        name,
+        params,
+        ret_type,
+        body,
+        span: make_span(src, s, e),
+    })
 }
 
 fn parse_match_expr(
-    src: &str,
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
     node: &SExpr,
     list: &[SExpr],
     scope: &HashSet<String>,
@@ -2198,7 +3740,7 @@ fn parse_match_expr(
         ));
     }
 
-    let scrutinee = parse_expr(src, &list[1], scope)?;
+    let scrutinee = parse_expr(exprs, src, &list[1], scope)?;
     let mut arms = Vec::new();
 
     for arm_node in list.iter().skip(2) {
@@ -2223,10 +3765,10 @@ fn parse_match_expr(
         }
 
         let mut pattern_bindings = HashSet::new();
-        let pattern = parse_pattern(src, &arm_items[0], &mut pattern_bindings)?;
+        let pattern = parse_pattern(exprs, src, &arm_items[0], &mut pattern_bindings)?;
         let mut local_scope = scope.clone();
         local_scope.extend(pattern_bindings);
-        let body = parse_expr(src, &arm_items[1], &local_scope)?;
+        let body = parse_expr(exprs, src, &arm_items[1], &local_scope)?;
         let (as_, ae) = arm_node.span_bounds();
         arms.push(MatchArm {
             pattern,
@@ -2236,41 +3778,42 @@ fn parse_match_expr(
     }
 
     Ok(Expr::Match {
-        scrutinee: Box::new(scrutinee),
+        scrutinee,
         arms,
         span: make_span(src, s, e),
     })
 }
 
 fn parse_pattern(
-    src: &str,
+    exprs: &mut ExprArena,
+    src: &Source<'_>,
     node: &SExpr,
     bindings: &mut HashSet<String>,
-) -> Result<Pattern, Diagnostic> {
+) -> Result<PatternId, Diagnostic> {
     if let Some(atom) = node.as_atom() {
         let (s, e) = node.span_bounds();
         if atom == "_" {
-            return Ok(Pattern::Wildcard {
+            return Ok(exprs.alloc_pattern(Pattern::Wildcard {
                 span: make_span(src, s, e),
-            });
+            }));
         }
         if atom == "true" || atom == "false" {
-            return Ok(Pattern::Bool {
+            return Ok(exprs.alloc_pattern(Pattern::Bool {
                 value: atom == "true",
                 span: make_span(src, s, e),
-            });
+            }));
         }
         if let Ok(i) = atom.parse::<i64>() {
-            return Ok(Pattern::Int {
+            return Ok(exprs.alloc_pattern(Pattern::Int {
                 value: i,
                 span: make_span(src, s, e),
-            });
+            }));
         }
         bindings.insert(atom.to_string());
-        return Ok(Pattern::Var {
+        return Ok(exprs.alloc_pattern(Pattern::Var {
             name: atom.to_string(),
             span: make_span(src, s, e),
-        });
+        }));
     }
 
     let list = match node {
@@ -2287,19 +3830,21 @@ fn parse_pattern(
     }
 
     let ctor = atom_required(src, &list[0], "pattern constructor")?;
+    let (s, e) = list[0].span_bounds();
+    src.use_name(&ctor, s, e);
     let mut args = Vec::new();
     for child in list.iter().skip(1) {
-        args.push(parse_pattern(src, child, bindings)?);
+        args.push(parse_pattern(exprs, src, child, bindings)?);
     }
     let (s, e) = node.span_bounds();
-    Ok(Pattern::Ctor {
+    Ok(exprs.alloc_pattern(Pattern::Ctor {
         name: ctor,
         args,
         span: make_span(src, s, e),
-    })
+    }))
 }
 
-fn parse_rule_term(src: &str, node: &SExpr) -> Result<LogicTerm, Diagnostic> {
+fn parse_rule_term(src: &Source<'_>, node: &SExpr) -> Result<LogicTerm, Diagnostic> {
     match node {
         SExpr::Atom(atom, s, e) => parse_rule_atom_term(src, atom, *s, *e),
         SExpr::List(items, s, e) => {
@@ -2311,6 +3856,8 @@ fn parse_rule_term(src: &str, node: &SExpr) -> Result<LogicTerm, Diagnostic> {
                 ));
             }
             let name = atom_required(src, &items[0], "constructor name")?;
+            let (name_start, name_end) = items[0].span_bounds();
+            src.use_name(&name, name_start, name_end);
             let mut args = Vec::new();
             for child in items.iter().skip(1) {
                 args.push(parse_rule_term(src, child)?);
@@ -2321,7 +3868,7 @@ fn parse_rule_term(src: &str, node: &SExpr) -> Result<LogicTerm, Diagnostic> {
 }
 
 fn parse_rule_atom_term(
-    src: &str,
+    src: &Source<'_>,
     atom: &str,
     start: usize,
     end: usize,
@@ -2345,16 +3892,17 @@ fn parse_rule_atom_term(
         }
         return Ok(LogicTerm::Var(rest.to_string()));
     }
+    src.use_name(atom, start, end);
     Ok(LogicTerm::Symbol(atom.to_string()))
 }
 
 fn parse_formula_term(
-    src: &str,
+    src: &Source<'_>,
     node: &SExpr,
     scope: &HashSet<String>,
 ) -> Result<LogicTerm, Diagnostic> {
     match node {
-        SExpr::Atom(atom, _, _) => {
+        SExpr::Atom(atom, s, e) => {
             if atom == "true" {
                 return Ok(LogicTerm::Bool(true));
             }
@@ -2367,6 +3915,7 @@ fn parse_formula_term(
             if scope.contains(atom) {
                 return Ok(LogicTerm::Var(atom.clone()));
             }
+            src.use_name(atom, *s, *e);
             Ok(LogicTerm::Symbol(atom.clone()))
         }
         SExpr::List(items, s, e) => {
@@ -2378,6 +3927,8 @@ fn parse_formula_term(
                 ));
             }
             let name = atom_required(src, &items[0], "constructor name")?;
+            let (name_start, name_end) = items[0].span_bounds();
+            src.use_name(&name, name_start, name_end);
             let mut args = Vec::new();
             for child in items.iter().skip(1) {
                 args.push(parse_formula_term(src, child, scope)?);
@@ -2387,7 +3938,7 @@ fn parse_formula_term(
     }
 }
 
-fn parse_const_term(src: &str, node: &SExpr) -> Result<LogicTerm, Diagnostic> {
+fn parse_const_term(src: &Source<'_>, node: &SExpr) -> Result<LogicTerm, Diagnostic> {
     match node {
         SExpr::Atom(atom, s, e) => {
             if atom == "true" {
@@ -2406,6 +3957,7 @@ fn parse_const_term(src: &str, node: &SExpr) -> Result<LogicTerm, Diagnostic> {
                     Some(make_span(src, *s, *e)),
                 ));
             }
+            src.use_name(atom, *s, *e);
             Ok(LogicTerm::Symbol(atom.clone()))
         }
         SExpr::List(items, s, e) => {
@@ -2417,6 +3969,8 @@ fn parse_const_term(src: &str, node: &SExpr) -> Result<LogicTerm, Diagnostic> {
                 ));
             }
             let name = atom_required(src, &items[0], "constructor name")?;
+            let (name_start, name_end) = items[0].span_bounds();
+            src.use_name(&name, name_start, name_end);
             let mut args = Vec::new();
             for child in items.iter().skip(1) {
                 args.push(parse_const_term(src, child)?);
@@ -2426,7 +3980,7 @@ fn parse_const_term(src: &str, node: &SExpr) -> Result<LogicTerm, Diagnostic> {
     }
 }
 
-fn atom_required(src: &str, node: &SExpr, expected: &str) -> Result<String, Diagnostic> {
+fn atom_required(src: &Source<'_>, node: &SExpr, expected: &str) -> Result<String, Diagnostic> {
     match node {
         SExpr::Atom(s, _, _) => Ok(s.clone()),
         SExpr::List(_, s, e) => Err(Diagnostic::new(
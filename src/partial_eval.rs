@@ -0,0 +1,484 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Expr, ExprArena, ExprId, LocalFn, MatchArm, Pattern, PatternId, Program};
+use crate::diagnostics::{Diagnostic, Span};
+use crate::interval::ArithOp;
+use crate::name_resolve::normalize_program_aliases;
+use crate::typecheck::check_program;
+use crate::types::{Formula, LogicTerm};
+
+pub fn partially_evaluate(program: &Program) -> Result<Program, Vec<Diagnostic>> {
+    check_program(program)?;
+    let mut program = normalize_program_aliases(program)?;
+    let constructors = program
+        .data_decls
+        .iter()
+        .flat_map(|data| data.constructors.iter().map(|ctor| ctor.name.clone()))
+        .collect::<HashSet<_>>();
+    let mut folder = ExprFolder {
+        exprs: &mut program.exprs,
+        constructors: &constructors,
+        folded: 0,
+    };
+    let empty = HashMap::new();
+    for defn in &mut program.defns {
+        defn.body = folder.fold(defn.body, &empty);
+        defn.measure = defn.measure.map(|measure| folder.fold(measure, &empty));
+        for clause in &mut defn.clauses {
+            clause.body = folder.fold(clause.body, &empty);
+        }
+    }
+    let folded = folder.folded;
+    let instantiated = instantiate_singleton_rules(&mut program);
+    tracing::info!(folded, instantiated, "partial evaluation finished");
+    Ok(program)
+}
+
+struct ExprFolder<'a> {
+    exprs: &'a mut ExprArena,
+    constructors: &'a HashSet<String>,
+    folded: usize,
+}
+
+impl ExprFolder<'_> {
+    // `env` maps the variables bound to ground values. Returns `id` when
+    // nothing under it changes; rewritten nodes are allocated anew, so
+    // subtrees shared between a defn body and its clauses stay intact.
+    fn fold(&mut self, id: ExprId, env: &HashMap<String, LogicTerm>) -> ExprId {
+        match self.exprs[id].clone() {
+            Expr::Var { name, span } => match env.get(&name) {
+                Some(value) => {
+                    self.folded += 1;
+                    self.alloc_value(value, &span)
+                }
+                None => id,
+            },
+            Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } | Expr::Hole { .. } => id,
+            Expr::Call {
+                name,
+                name_span,
+                args,
+                span,
+            } => {
+                let folded_args = args
+                    .iter()
+                    .map(|arg| self.fold(*arg, env))
+                    .collect::<Vec<_>>();
+                if let Some(op) = ArithOp::from_name(&name)
+                    && let Some(folded) = self.fold_arith(op, &folded_args, &span)
+                {
+                    self.folded += 1;
+                    return folded;
+                }
+                if folded_args == args {
+                    return id;
+                }
+                self.exprs.alloc(Expr::Call {
+                    name,
+                    name_span,
+                    args: folded_args,
+                    span,
+                })
+            }
+            Expr::Let {
+                bindings,
+                body,
+                span,
+            } => {
+                let mut scope = env.clone();
+                let mut kept = Vec::with_capacity(bindings.len());
+                for (name, value, binding_span) in &bindings {
+                    let value = self.fold(*value, &scope);
+                    if let Some(term) = self.ground_value(value) {
+                        self.folded += 1;
+                        scope.insert(name.clone(), term);
+                    } else {
+                        scope.remove(name);
+                        kept.push((name.clone(), value, binding_span.clone()));
+                    }
+                }
+                let folded_body = self.fold(body, &scope);
+                if kept.is_empty() {
+                    return folded_body;
+                }
+                if kept == bindings && folded_body == body {
+                    return id;
+                }
+                self.exprs.alloc(Expr::Let {
+                    bindings: kept,
+                    body: folded_body,
+                    span,
+                })
+            }
+            Expr::LetFn { fns, body, span } => {
+                let folded_fns = fns
+                    .iter()
+                    .map(|f| {
+                        let mut scope = env.clone();
+                        for param in &f.params {
+                            scope.remove(&param.name);
+                        }
+                        LocalFn {
+                            body: self.fold(f.body, &scope),
+                            ..f.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let folded_body = self.fold(body, env);
+                if folded_fns == fns && folded_body == body {
+                    return id;
+                }
+                self.exprs.alloc(Expr::LetFn {
+                    fns: folded_fns,
+                    body: folded_body,
+                    span,
+                })
+            }
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+                span,
+            } => {
+                let folded_cond = self.fold(cond, env);
+                if let Expr::Bool { value, .. } = self.exprs[folded_cond] {
+                    self.folded += 1;
+                    let taken = if value { then_branch } else { else_branch };
+                    return self.fold(taken, env);
+                }
+                let folded_then = self.fold(then_branch, env);
+                let folded_else = self.fold(else_branch, env);
+                if (folded_cond, folded_then, folded_else) == (cond, then_branch, else_branch) {
+                    return id;
+                }
+                self.exprs.alloc(Expr::If {
+                    cond: folded_cond,
+                    then_branch: folded_then,
+                    else_branch: folded_else,
+                    span,
+                })
+            }
+            Expr::Match {
+                scrutinee,
+                arms,
+                span,
+            } => {
+                let folded_scrutinee = self.fold(scrutinee, env);
+                if let Some(value) = self.ground_value(folded_scrutinee) {
+                    for arm in &arms {
+                        let mut scope = env.clone();
+                        let mut bound = HashMap::new();
+                        if bind_pattern(self.exprs, arm.pattern, &value, &mut bound) {
+                            self.folded += 1;
+                            scope.extend(bound);
+                            return self.fold(arm.body, &scope);
+                        }
+                    }
+                }
+                let folded_arms = arms
+                    .iter()
+                    .map(|arm| {
+                        let mut scope = env.clone();
+                        remove_pattern_vars(self.exprs, arm.pattern, &mut scope);
+                        MatchArm {
+                            body: self.fold(arm.body, &scope),
+                            ..arm.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if folded_scrutinee == scrutinee && folded_arms == arms {
+                    return id;
+                }
+                self.exprs.alloc(Expr::Match {
+                    scrutinee: folded_scrutinee,
+                    arms: folded_arms,
+                    span,
+                })
+            }
+        }
+    }
+
+    fn fold_arith(&mut self, op: ArithOp, args: &[ExprId], span: &Span) -> Option<ExprId> {
+        let [lhs, rhs] = args else {
+            return None;
+        };
+        let int = |id: &ExprId| match self.exprs[*id] {
+            Expr::Int { value, .. } => Some(value),
+            _ => None,
+        };
+        match (op, int(lhs), int(rhs)) {
+            (_, Some(a), Some(b)) => {
+                let value = op.apply(a, b)?;
+                Some(self.exprs.alloc(Expr::Int {
+                    value,
+                    span: span.clone(),
+                }))
+            }
+            (ArithOp::Add | ArithOp::Sub, _, Some(0)) | (ArithOp::Mul, _, Some(1)) => Some(*lhs),
+            (ArithOp::Add, Some(0), _) | (ArithOp::Mul, Some(1), _) => Some(*rhs),
+            _ => None,
+        }
+    }
+
+    fn ground_value(&self, id: ExprId) -> Option<LogicTerm> {
+        match &self.exprs[id] {
+            Expr::Int { value, .. } => Some(LogicTerm::Int(*value)),
+            Expr::Bool { value, .. } => Some(LogicTerm::Bool(*value)),
+            Expr::Symbol { value, .. } => Some(LogicTerm::Symbol(value.clone())),
+            Expr::Call { name, args, .. } if self.constructors.contains(name) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.ground_value(*arg))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(LogicTerm::Ctor {
+                    name: name.clone(),
+                    args,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn alloc_value(&mut self, value: &LogicTerm, span: &Span) -> ExprId {
+        let span = span.clone();
+        let expr = match value {
+            LogicTerm::Int(value) => Expr::Int {
+                value: *value,
+                span,
+            },
+            LogicTerm::Bool(value) => Expr::Bool {
+                value: *value,
+                span,
+            },
+            LogicTerm::Symbol(value) => Expr::Symbol {
+                value: value.clone(),
+                span,
+            },
+            LogicTerm::Ctor { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.alloc_value(arg, &span))
+                    .collect();
+                Expr::Call {
+                    name: name.clone(),
+                    name_span: span.clone(),
+                    args,
+                    span,
+                }
+            }
+            LogicTerm::Var(_) => unreachable!("ground values have no variables"),
+        };
+        self.exprs.alloc(expr)
+    }
+}
+
+fn bind_pattern(
+    exprs: &ExprArena,
+    pattern: PatternId,
+    value: &LogicTerm,
+    bound: &mut HashMap<String, LogicTerm>,
+) -> bool {
+    match &exprs[pattern] {
+        Pattern::Wildcard { .. } => true,
+        Pattern::Var { name, .. } => match bound.get(name) {
+            Some(previous) => previous == value,
+            None => {
+                bound.insert(name.clone(), value.clone());
+                true
+            }
+        },
+        Pattern::Symbol {
+            value: expected, ..
+        } => matches!(value, LogicTerm::Symbol(actual) if actual == expected),
+        Pattern::Int {
+            value: expected, ..
+        } => matches!(value, LogicTerm::Int(actual) if actual == expected),
+        Pattern::Bool {
+            value: expected, ..
+        } => matches!(value, LogicTerm::Bool(actual) if actual == expected),
+        Pattern::Ctor { name, args, .. } => match value {
+            LogicTerm::Ctor {
+                name: ctor,
+                args: fields,
+            } if ctor == name && fields.len() == args.len() => args
+                .iter()
+                .zip(fields)
+                .all(|(arg, field)| bind_pattern(exprs, *arg, field, bound)),
+            _ => false,
+        },
+    }
+}
+
+fn remove_pattern_vars(
+    exprs: &ExprArena,
+    pattern: PatternId,
+    scope: &mut HashMap<String, LogicTerm>,
+) {
+    match &exprs[pattern] {
+        Pattern::Var { name, .. } => {
+            scope.remove(name);
+        }
+        Pattern::Ctor { args, .. } => {
+            for arg in args {
+                remove_pattern_vars(exprs, *arg, scope);
+            }
+        }
+        Pattern::Wildcard { .. }
+        | Pattern::Symbol { .. }
+        | Pattern::Int { .. }
+        | Pattern::Bool { .. } => {}
+    }
+}
+
+fn instantiate_singleton_rules(program: &mut Program) -> usize {
+    let singletons = singleton_sorts(program)
+        .into_iter()
+        .map(|(sort, value)| (sort.to_string(), value.clone()))
+        .collect::<HashMap<_, _>>();
+    if singletons.is_empty() {
+        return 0;
+    }
+    let schemas = program
+        .relations
+        .iter()
+        .map(|relation| (relation.name.clone(), relation.arg_sorts.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut instantiated = 0;
+    for rule in &mut program.rules {
+        if rule.skolem.is_some() {
+            continue;
+        }
+        let mut values = HashMap::new();
+        for (var, sort) in positive_var_sorts(&rule.body, &schemas) {
+            if let Some(value) = singletons.get(sort) {
+                values.insert(var.to_string(), value.clone());
+            }
+        }
+        if values.is_empty() {
+            continue;
+        }
+        for term in &mut rule.head.terms {
+            substitute_term_vars(term, &values);
+        }
+        substitute_formula_vars(&mut rule.body, &values);
+        instantiated += 1;
+    }
+    instantiated
+}
+
+// A sort qualifies when its universe holds a single value and every tuple
+// the fixpoint can derive carries that value at the sort's positions: facts
+// and rule heads only write it there, a head variable at such a position is
+// bound at one in the positive body, and no skolem rule fills one.
+fn singleton_sorts(program: &Program) -> HashMap<&str, &LogicTerm> {
+    let mut singletons = program
+        .universes
+        .iter()
+        .filter_map(|universe| match universe.values.as_slice() {
+            [value] => Some((universe.ty_name.as_str(), value)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    if singletons.is_empty() {
+        return singletons;
+    }
+    let schemas = program
+        .relations
+        .iter()
+        .map(|relation| (relation.name.clone(), relation.arg_sorts.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut disqualified = HashSet::new();
+    for fact in &program.facts {
+        let Some(sorts) = schemas.get(&fact.name) else {
+            continue;
+        };
+        for (term, sort) in fact.terms.iter().zip(sorts) {
+            if singletons
+                .get(sort.as_str())
+                .is_some_and(|value| *value != term)
+            {
+                disqualified.insert(sort.as_str());
+            }
+        }
+    }
+    for rule in &program.rules {
+        let Some(sorts) = schemas.get(&rule.head.pred) else {
+            continue;
+        };
+        let bound = positive_var_sorts(&rule.body, &schemas);
+        for (term, sort) in rule.head.terms.iter().zip(sorts) {
+            let Some(value) = singletons.get(sort.as_str()) else {
+                continue;
+            };
+            let written = match term {
+                LogicTerm::Var(var) => {
+                    rule.skolem.is_none() && bound.contains(&(var.as_str(), sort.as_str()))
+                }
+                term => *value == term,
+            };
+            if !written {
+                disqualified.insert(sort.as_str());
+            }
+        }
+    }
+    singletons.retain(|sort, _| !disqualified.contains(sort));
+    singletons
+}
+
+fn positive_var_sorts<'a>(
+    formula: &'a Formula,
+    schemas: &'a HashMap<String, Vec<String>>,
+) -> HashSet<(&'a str, &'a str)> {
+    let mut out = HashSet::new();
+    let mut stack = vec![formula];
+    while let Some(formula) = stack.pop() {
+        match formula {
+            Formula::Atom(atom) => {
+                let Some(sorts) = schemas.get(&atom.pred) else {
+                    continue;
+                };
+                for (term, sort) in atom.terms.iter().zip(sorts) {
+                    if let LogicTerm::Var(var) = term {
+                        out.insert((var.as_str(), sort.as_str()));
+                    }
+                }
+            }
+            Formula::And(items) => stack.extend(items),
+            Formula::True | Formula::Not(_) => {}
+        }
+    }
+    out
+}
+
+fn substitute_formula_vars(formula: &mut Formula, values: &HashMap<String, LogicTerm>) {
+    match formula {
+        Formula::True => {}
+        Formula::Atom(atom) => {
+            for term in &mut atom.terms {
+                substitute_term_vars(term, values);
+            }
+        }
+        Formula::And(items) => {
+            for item in items {
+                substitute_formula_vars(item, values);
+            }
+        }
+        Formula::Not(inner) => substitute_formula_vars(inner, values),
+    }
+}
+
+fn substitute_term_vars(term: &mut LogicTerm, values: &HashMap<String, LogicTerm>) {
+    match term {
+        LogicTerm::Var(var) => {
+            if let Some(value) = values.get(var) {
+                *term = value.clone();
+            }
+        }
+        LogicTerm::Ctor { args, .. } => {
+            for arg in args {
+                substitute_term_vars(arg, values);
+            }
+        }
+        LogicTerm::Symbol(_) | LogicTerm::Int(_) | LogicTerm::Bool(_) => {}
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::num::NonZeroUsize;
+
+use serde::Serialize;
+
+use crate::ast::{Program, Rule};
+use crate::diagnostics::{Diagnostic, Span};
+use crate::logic_engine::{DISEQUALITY_PRED, KnowledgeBase, PARALLEL_MIN_TUPLES, flatten_formula};
+use crate::name_resolve::normalize_program_aliases;
+use crate::types::{Atom, LogicTerm};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryPlan {
+    pub strata: Vec<StratumPlan>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StratumPlan {
+    pub stratum: usize,
+    pub relations: Vec<String>,
+    pub recursive: bool,
+    pub rules: Vec<RulePlan>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RulePlan {
+    pub rule: usize,
+    pub head: String,
+    pub steps: Vec<PlanStep>,
+    pub estimated_rows: u64,
+    pub estimated_cost: u64,
+    pub partitions: usize,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepKind {
+    Scan,
+    Filter,
+    AntiJoin,
+}
+
+impl StepKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepKind::Scan => "scan",
+            StepKind::Filter => "filter",
+            StepKind::AntiJoin => "anti-join",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    FullScan,
+    TupleLookup,
+    Builtin,
+}
+
+impl Access {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Access::FullScan => "full scan",
+            Access::TupleLookup => "tuple lookup",
+            Access::Builtin => "builtin",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlanStep {
+    pub kind: StepKind,
+    pub relation: String,
+    pub atom: String,
+    pub access: Access,
+    pub bound_columns: Vec<usize>,
+    pub join_vars: Vec<String>,
+    pub binds: Vec<String>,
+    pub relation_rows: u64,
+    pub input_rows: u64,
+    pub output_rows: u64,
+    pub cost: u64,
+}
+
+pub fn plan_program(
+    program: &Program,
+    workers: NonZeroUsize,
+) -> Result<QueryPlan, Vec<Diagnostic>> {
+    let normalized = normalize_program_aliases(program)?;
+    let kb = KnowledgeBase::from_program(&normalized)?.with_workers(workers);
+    Ok(plan_knowledge_base(&kb))
+}
+
+pub fn plan_knowledge_base(kb: &KnowledgeBase) -> QueryPlan {
+    let mut rows: HashMap<&str, u64> = HashMap::new();
+    for fact in &kb.facts {
+        *rows.entry(fact.pred.as_str()).or_default() += 1;
+    }
+
+    let mut levels = kb.strata.values().copied().collect::<Vec<_>>();
+    levels.sort_unstable();
+    levels.dedup();
+
+    let level_of = |pred: &str| kb.strata.get(pred).copied().unwrap_or(0);
+    let mut strata = Vec::new();
+    for stratum in levels {
+        let mut relations = kb
+            .strata
+            .iter()
+            .filter(|(_, level)| **level == stratum)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        relations.sort();
+
+        let heads = kb
+            .rules
+            .iter()
+            .filter(|rule| level_of(&rule.head.pred) == stratum)
+            .map(|rule| rule.head.pred.as_str())
+            .collect::<HashSet<_>>();
+        let mut recursive = false;
+        let mut rules = Vec::new();
+        for (index, rule) in kb.rules.iter().enumerate() {
+            if level_of(&rule.head.pred) != stratum {
+                continue;
+            }
+            let plan = plan_rule(index, rule, &rows, kb.workers);
+            recursive |= plan
+                .steps
+                .iter()
+                .any(|step| step.kind == StepKind::Scan && heads.contains(step.relation.as_str()));
+            let head_rows = rows.entry(rule.head.pred.as_str()).or_default();
+            *head_rows = head_rows.saturating_add(plan.estimated_rows);
+            rules.push(plan);
+        }
+        strata.push(StratumPlan {
+            stratum,
+            relations,
+            recursive,
+            rules,
+        });
+    }
+    QueryPlan { strata }
+}
+
+fn plan_rule(
+    index: usize,
+    rule: &Rule,
+    rows: &HashMap<&str, u64>,
+    workers: NonZeroUsize,
+) -> RulePlan {
+    let mut positives = Vec::new();
+    let mut negatives = Vec::new();
+    flatten_formula(&rule.body, false, &mut positives, &mut negatives);
+    let (distinct, positives): (Vec<_>, Vec<_>) = positives
+        .into_iter()
+        .partition(|atom| atom.pred == DISEQUALITY_PRED);
+    let (equal, negatives): (Vec<_>, Vec<_>) = negatives
+        .into_iter()
+        .partition(|atom| atom.pred == DISEQUALITY_PRED);
+
+    let mut bound = BTreeSet::new();
+    let mut current = 1_u64;
+    let mut steps = Vec::new();
+    for atom in positives {
+        let relation_rows = rows.get(atom.pred.as_str()).copied().unwrap_or(0);
+        let mut step = step_for(StepKind::Scan, atom, &mut bound, relation_rows, current);
+        let arity = atom.terms.len();
+        let free = arity - step.bound_columns.len();
+        let matches = if relation_rows == 0 || free == arity {
+            relation_rows
+        } else {
+            (relation_rows as f64)
+                .powf(free as f64 / arity as f64)
+                .ceil() as u64
+        };
+        step.output_rows = current.saturating_mul(matches);
+        step.cost = current.saturating_mul(relation_rows);
+        current = step.output_rows;
+        steps.push(step);
+    }
+    for atom in distinct.into_iter().chain(equal) {
+        let mut step = step_for(StepKind::Filter, atom, &mut bound, 0, current);
+        step.access = Access::Builtin;
+        steps.push(step);
+    }
+    for atom in negatives {
+        let relation_rows = rows.get(atom.pred.as_str()).copied().unwrap_or(0);
+        let mut step = step_for(StepKind::AntiJoin, atom, &mut bound, relation_rows, current);
+        step.access = Access::TupleLookup;
+        steps.push(step);
+    }
+
+    RulePlan {
+        rule: index,
+        head: render_atom(&rule.head),
+        estimated_cost: steps
+            .iter()
+            .fold(0_u64, |sum, step| sum.saturating_add(step.cost)),
+        estimated_rows: current,
+        partitions: steps
+            .first()
+            .filter(|step| {
+                step.kind == StepKind::Scan && step.relation_rows >= PARALLEL_MIN_TUPLES as u64
+            })
+            .map_or(1, |_| workers.get()),
+        steps,
+        span: rule.span.clone(),
+    }
+}
+
+fn step_for(
+    kind: StepKind,
+    atom: &Atom,
+    bound: &mut BTreeSet<String>,
+    relation_rows: u64,
+    input_rows: u64,
+) -> PlanStep {
+    let mut bound_columns = Vec::new();
+    let mut join_vars = Vec::new();
+    let mut binds = Vec::new();
+    for (column, term) in atom.terms.iter().enumerate() {
+        let mut vars = Vec::new();
+        term_vars(term, &mut vars);
+        let mut fixed = true;
+        for var in vars {
+            if bound.contains(var) {
+                if !join_vars.contains(var) && !binds.contains(var) {
+                    join_vars.push(var.clone());
+                }
+            } else if binds.contains(var) {
+            } else {
+                binds.push(var.clone());
+                fixed = false;
+            }
+        }
+        if fixed {
+            bound_columns.push(column);
+        }
+    }
+    if kind == StepKind::Scan {
+        bound.extend(binds.iter().cloned());
+    } else {
+        binds.clear();
+    }
+    PlanStep {
+        kind,
+        relation: atom.pred.clone(),
+        atom: render_atom(atom),
+        access: Access::FullScan,
+        bound_columns,
+        join_vars,
+        binds,
+        relation_rows,
+        input_rows,
+        output_rows: input_rows,
+        cost: input_rows,
+    }
+}
+
+fn term_vars<'a>(term: &'a LogicTerm, out: &mut Vec<&'a String>) {
+    match term {
+        LogicTerm::Var(v) => out.push(v),
+        LogicTerm::Ctor { args, .. } => args.iter().for_each(|arg| term_vars(arg, out)),
+        LogicTerm::Symbol(_) | LogicTerm::Int(_) | LogicTerm::Bool(_) => {}
+    }
+}
+
+fn render_atom(atom: &Atom) -> String {
+    let mut rendered = format!("({}", atom.pred);
+    for term in &atom.terms {
+        rendered.push(' ');
+        rendered.push_str(&term.to_string());
+    }
+    rendered.push(')');
+    rendered
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::ast::{AliasDecl, Program};
+use crate::diagnostics::{Diagnostic, make_span_with_file};
+use crate::lint::ComplexityLimits;
+use crate::parser::SurfaceKeywordMap;
+
+pub const PROJECT_CONFIG_FILE: &str = "dtl.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectConfig {
+    pub path: Option<PathBuf>,
+    pub aliases: Vec<AliasDecl>,
+    pub keywords: SurfaceKeywordMap,
+    pub complexity: ComplexityLimits,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProjectConfig {
+    #[serde(default)]
+    aliases: BTreeMap<String, toml::Spanned<String>>,
+    #[serde(default)]
+    syntax: RawSyntaxConfig,
+    #[serde(default)]
+    lint: RawLintConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSyntaxConfig {
+    #[serde(default)]
+    keywords: BTreeMap<String, toml::Spanned<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct RawLintConfig {
+    max_expr_depth: Option<usize>,
+    max_defn_nodes: Option<usize>,
+    max_rule_body_atoms: Option<usize>,
+    max_assert_params: Option<usize>,
+}
+
+impl RawLintConfig {
+    fn limits(&self) -> ComplexityLimits {
+        let defaults = ComplexityLimits::default();
+        ComplexityLimits {
+            max_expr_depth: self.max_expr_depth.unwrap_or(defaults.max_expr_depth),
+            max_defn_nodes: self.max_defn_nodes.unwrap_or(defaults.max_defn_nodes),
+            max_rule_body_atoms: self
+                .max_rule_body_atoms
+                .unwrap_or(defaults.max_rule_body_atoms),
+            max_assert_params: self.max_assert_params.unwrap_or(defaults.max_assert_params),
+        }
+    }
+}
+
+impl ProjectConfig {
+    pub fn apply(&self, program: &mut Program) {
+        program.aliases.extend(self.aliases.iter().cloned());
+    }
+}
+
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    let start = fs::canonicalize(start).unwrap_or_else(|_| start.to_path_buf());
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn load_project_config(path: &Path) -> Result<ProjectConfig, Vec<Diagnostic>> {
+    let source = path.display().to_string();
+    let src = fs::read_to_string(path).map_err(|err| {
+        vec![
+            Diagnostic::new(
+                "E-CONFIG",
+                format!("failed to read project config: {err}"),
+                None,
+            )
+            .with_source(source.clone()),
+        ]
+    })?;
+    let mut config = parse_project_config(&src, &source)?;
+    config.path = Some(path.to_path_buf());
+    Ok(config)
+}
+
+pub fn parse_project_config(src: &str, file_id: &str) -> Result<ProjectConfig, Vec<Diagnostic>> {
+    let raw: RawProjectConfig = toml::from_str(src).map_err(|err| {
+        let span = err
+            .span()
+            .map(|range| make_span_with_file(src, range.start, range.end, Some(file_id)));
+        vec![
+            Diagnostic::new(
+                "E-CONFIG",
+                format!("invalid project config: {}", err.message()),
+                span,
+            )
+            .with_source(file_id.to_string()),
+        ]
+    })?;
+
+    let mut errors = Vec::new();
+    let mut aliases = Vec::new();
+    for (alias, canonical) in raw.aliases {
+        let range = canonical.span();
+        let span = make_span_with_file(src, range.start, range.end, Some(file_id));
+        let alias: String = alias.trim().nfc().collect();
+        let canonical: String = canonical.into_inner().trim().nfc().collect();
+        if alias.is_empty() || canonical.is_empty() {
+            errors.push(
+                Diagnostic::new(
+                    "E-CONFIG",
+                    format!("alias entries must name both sides: {alias:?} = {canonical:?}"),
+                    Some(span),
+                )
+                .with_source(file_id.to_string()),
+            );
+            continue;
+        }
+        aliases.push(AliasDecl {
+            alias,
+            canonical,
+            span,
+        });
+    }
+
+    let mut keywords = SurfaceKeywordMap::new();
+    for (word, canonical) in raw.syntax.keywords {
+        let range = canonical.span();
+        let span = make_span_with_file(src, range.start, range.end, Some(file_id));
+        if let Err(message) = keywords.insert(&word, canonical.get_ref()) {
+            errors.push(
+                Diagnostic::new("E-CONFIG", message, Some(span)).with_source(file_id.to_string()),
+            );
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(ProjectConfig {
+            path: None,
+            aliases,
+            keywords,
+            complexity: raw.lint.limits(),
+        })
+    } else {
+        Err(errors)
+    }
+}
@@ -1,18 +1,33 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::io::{BufReader, BufWriter, Write};
 use std::path::Path;
+use std::rc::Rc;
+use std::time::Instant;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::ast::{Defn, Expr, Pattern, Program};
+use crate::ast::{
+    DataDecl, Defn, Expr, ExprArena, ExprId, LocalFn, Pattern, PatternId, Program, RelationDecl,
+};
 use crate::diagnostics::{Diagnostic, Span};
-use crate::logic_engine::{DerivedFacts, GroundFact, KnowledgeBase, Value, solve_facts};
+use crate::diff::ChangeKind;
+use crate::fact_cache::{FixpointOptions, solve_facts_cached};
+use crate::fmt::{FormExcerpt, form_excerpts, render_expr};
+use crate::interval::{ArithOp, ComparisonOp};
+use crate::logic_engine::{
+    DISEQUALITY_PRED, DerivationStep, DerivedFacts, GroundFact, KnowledgeBase, SKOLEM_CTOR_PREFIX,
+    Value, disequality_holds, solve_facts, solve_facts_with_derivations,
+};
 use crate::name_resolve::{normalize_program_aliases, resolve_program};
+use crate::refs::find_references;
 use crate::stratify::compute_strata;
-use crate::typecheck::check_program;
+use crate::symbol::Symbol;
+use crate::timings::{PhaseTimings, format_micros};
+use crate::typecheck::{check_program_with_timings, recursive_groups};
 use crate::types::{Atom, Formula, LogicTerm, Type};
 
-pub const PROOF_TRACE_SCHEMA_VERSION: &str = "2.2.0";
+pub const PROOF_TRACE_SCHEMA_VERSION: &str = "2.3.0";
 pub const DOC_SPEC_SCHEMA_VERSION: &str = "2.0.0";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -21,7 +36,7 @@ pub enum DocBundleFormat {
     Json,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProofTrace {
     pub schema_version: String,
     pub profile: String,
@@ -29,22 +44,53 @@ pub struct ProofTrace {
     pub summary: ProofSummary,
     pub claim_coverage: ClaimCoverage,
     pub obligations: Vec<ObligationTrace>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivations: Option<Vec<DerivationStep>>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProofSummary {
     pub total: usize,
     pub proved: usize,
     pub failed: usize,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaimCoverage {
     pub total_claims: usize,
     pub proved_claims: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<ClaimPolicy>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl ClaimCoverage {
+    pub fn ratio(&self) -> f64 {
+        if self.total_claims == 0 {
+            return 0.0;
+        }
+        self.proved_claims as f64 / self.total_claims as f64
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimPolicy {
+    pub min_coverage: f64,
+    pub ratio: f64,
+    pub allowed_unproved: Vec<String>,
+    pub unproved: Vec<String>,
+}
+
+impl ClaimPolicy {
+    pub fn is_satisfied(&self, coverage: &ClaimCoverage) -> bool {
+        if coverage.total_claims == 0 {
+            return false;
+        }
+        let covered = coverage.proved_claims + self.allowed_unproved.len();
+        covered as f64 / coverage.total_claims as f64 >= self.min_coverage
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObligationTrace {
     pub id: String,
     pub kind: String,
@@ -54,15 +100,17 @@ pub struct ObligationTrace {
     pub derived: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub counterexample: Option<CounterexampleTrace>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NameValue {
     pub name: String,
     pub value: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CounterexampleTrace {
     pub valuation: Vec<NameValue>,
     pub premises: Vec<String>,
@@ -73,6 +121,7 @@ pub struct CounterexampleTrace {
 struct ObligationSpec {
     id: String,
     kind: String,
+    message: Option<String>,
     goal: Formula,
     body: ObligationBody,
     vars: Vec<QuantifiedVarSpec>,
@@ -81,7 +130,8 @@ struct ObligationSpec {
 #[derive(Debug, Clone)]
 enum ObligationBody {
     Assert,
-    Refine(Expr),
+    Refine { body: ExprId, result_var: String },
+    Measure { defn: String },
 }
 
 #[derive(Debug, Clone)]
@@ -105,9 +155,24 @@ struct CallKey {
 
 struct PremiseEvalContext<'a> {
     kb: &'a KnowledgeBase,
+    exprs: &'a ExprArena,
     relation_names: &'a HashSet<String>,
     constructor_names: &'a HashSet<String>,
     defn_map: &'a HashMap<String, &'a Defn>,
+    recursive_groups: &'a HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone)]
+struct MeasureFrame {
+    name: String,
+    measure: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+struct MeasureViolation {
+    depth: usize,
+    caller_measure: i64,
+    callee_measure: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -120,18 +185,23 @@ struct JsonSpec {
     data_declarations: Vec<JsonSpecDataDecl>,
     relations: Vec<JsonSpecRelation>,
     assertions: Vec<JsonSpecAssertion>,
+    functions: Vec<JsonSpecFunction>,
     proof_status: Vec<JsonSpecProofStatus>,
 }
 
 #[derive(Debug, Serialize)]
 struct JsonSpecSort {
     name: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
 struct JsonSpecDataDecl {
     name: String,
     constructors: Vec<JsonSpecConstructor>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -144,11 +214,32 @@ struct JsonSpecConstructor {
 struct JsonSpecRelation {
     name: String,
     arg_sorts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    doc: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
 struct JsonSpecAssertion {
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    doc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonSpecFunction {
+    name: String,
+    params: Vec<String>,
+    ret: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    doc: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -161,6 +252,8 @@ struct JsonSpecProofStatus {
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct DocSelfDescription {
     pub project: Option<DocProject>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<DocCategory>,
     pub modules: Vec<DocModule>,
     pub references: Vec<DocReference>,
     pub contracts: Vec<DocContract>,
@@ -173,6 +266,13 @@ pub struct DocProject {
     pub summary: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct DocCategory {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct DocModule {
     pub name: String,
@@ -201,31 +301,203 @@ pub struct DocQualityGate {
     pub required: bool,
 }
 
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DocSelfdocDrift {
+    pub baseline: String,
+    pub modules: Vec<DocDriftEntry>,
+    pub references: Vec<DocDriftEntry>,
+    pub contracts: Vec<DocDriftEntry>,
+    pub quality_gates: Vec<DocDriftEntry>,
+}
+
+impl DocSelfdocDrift {
+    pub fn is_empty(&self) -> bool {
+        self.groups().iter().all(|(_, entries)| entries.is_empty())
+    }
+
+    fn groups(&self) -> [(&'static str, &[DocDriftEntry]); 4] {
+        [
+            ("モジュール", &self.modules),
+            ("参照", &self.references),
+            ("契約", &self.contracts),
+            ("品質ゲート", &self.quality_gates),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocDriftEntry {
+    pub change: String,
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<serde_json::Value>,
+}
+
+pub fn diff_self_descriptions(
+    baseline: &str,
+    before: &DocSelfDescription,
+    after: &DocSelfDescription,
+) -> DocSelfdocDrift {
+    DocSelfdocDrift {
+        baseline: baseline.to_string(),
+        modules: drift_entries(&before.modules, &after.modules, |m| m.path.clone()),
+        references: drift_entries(&before.references, &after.references, |r| {
+            format!("{} -> {}", r.from, r.to)
+        }),
+        contracts: drift_entries(&before.contracts, &after.contracts, |c| c.name.clone()),
+        quality_gates: drift_entries(&before.quality_gates, &after.quality_gates, |g| {
+            g.name.clone()
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocProofChange {
+    pub change: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+impl DocProofChange {
+    pub fn is_transition(&self) -> bool {
+        self.before.is_some() && self.after.is_some() && self.before != self.after
+    }
+}
+
+pub fn diff_proof_traces(before: &ProofTrace, after: &ProofTrace) -> Vec<DocProofChange> {
+    let index = |trace: &ProofTrace| {
+        trace
+            .obligations
+            .iter()
+            .map(|o| {
+                let value = serde_json::to_value(o).expect("serialize obligation trace");
+                (o.id.clone(), (o.result.clone(), value))
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+    let before = index(before);
+    let after = index(after);
+    let ids = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+    ids.into_iter()
+        .filter_map(|id| {
+            let change = match (before.get(id), after.get(id)) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(a), Some(b)) if a != b => ChangeKind::Changed,
+                _ => return None,
+            };
+            Some(DocProofChange {
+                change: change.as_str().to_string(),
+                id: id.clone(),
+                before: before.get(id).map(|(result, _)| result.clone()),
+                after: after.get(id).map(|(result, _)| result.clone()),
+            })
+        })
+        .collect()
+}
+
+fn drift_entries<T: Serialize>(
+    before: &[T],
+    after: &[T],
+    key: impl Fn(&T) -> String,
+) -> Vec<DocDriftEntry> {
+    let index = |items: &[T]| {
+        items
+            .iter()
+            .map(|item| {
+                let value = serde_json::to_value(item).expect("serialize self-description entry");
+                (key(item), value)
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+    let before = index(before);
+    let after = index(after);
+    let keys = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+    keys.into_iter()
+        .filter_map(|key| {
+            let change = match (before.get(key), after.get(key)) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(a), Some(b)) if a != b => ChangeKind::Changed,
+                _ => return None,
+            };
+            Some(DocDriftEntry {
+                change: change.as_str().to_string(),
+                key: key.clone(),
+                before: before.get(key).cloned(),
+                after: after.get(key).cloned(),
+            })
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct DocBundleOptions {
     pub profile: Option<String>,
     pub self_description: Option<DocSelfDescription>,
     pub intermediate_dsl: Option<String>,
+    pub selfdoc_drift: Option<DocSelfdocDrift>,
+    pub allow_failed: bool,
+    pub proof_micros: Option<u64>,
+    pub previous_trace: Option<ProofTrace>,
+    pub compress_trace: bool,
+    pub deterministic: bool,
 }
 
-pub fn prove_program(program: &Program) -> Result<ProofTrace, Vec<Diagnostic>> {
+pub fn derivation_steps(program: &Program) -> Result<Vec<DerivationStep>, Vec<Diagnostic>> {
     let normalized = normalize_program_aliases(program)?;
-    let mut errors = resolve_program(&normalized);
+    let kb = KnowledgeBase::from_program(&normalized)?;
+    let (_, steps) = solve_facts_with_derivations(&kb).map_err(wrap_as_prove_error)?;
+    Ok(steps)
+}
+
+pub fn prove_program(program: &Program) -> Result<ProofTrace, Vec<Diagnostic>> {
+    prove_program_with_timings(program, &mut PhaseTimings::new())
+}
+
+pub fn prove_program_with_timings(
+    program: &Program,
+    timings: &mut PhaseTimings,
+) -> Result<ProofTrace, Vec<Diagnostic>> {
+    prove_program_streaming(program, FixpointOptions::default(), timings, |_| {})
+}
+
+pub fn prove_program_streaming(
+    program: &Program,
+    fixpoint: FixpointOptions<'_>,
+    timings: &mut PhaseTimings,
+    mut on_obligation: impl FnMut(&ObligationTrace),
+) -> Result<ProofTrace, Vec<Diagnostic>> {
+    let (normalized, mut errors) = timings.measure("resolve", || {
+        normalize_program_aliases(program).map(|normalized| {
+            let errors = resolve_program(&normalized);
+            (normalized, errors)
+        })
+    })?;
     if !errors.is_empty() {
         return Err(errors);
     }
-    if let Err(mut e) = compute_strata(&normalized) {
-        errors.append(&mut e);
+    if let Err(e) = timings.measure("stratify", || compute_strata(&normalized)) {
+        errors.extend(e.diagnostics);
         return Err(errors);
     }
-    if let Err(mut e) = check_program(&normalized) {
+    if let Err(mut e) = check_program_with_timings(&normalized, fixpoint, timings) {
         errors.append(&mut e);
         return Err(errors);
     }
 
-    let kb = KnowledgeBase::from_program(&normalized)?;
-    let derived = solve_facts(&kb).map_err(wrap_as_prove_error)?;
-    let universe_map = build_universe_map(&normalized)?;
+    let (kb, derived) = timings.measure("fixpoint", || -> Result<_, Vec<Diagnostic>> {
+        let kb = fixpoint.knowledge_base(&normalized)?;
+        let derived = solve_facts_cached(&kb, fixpoint).map_err(wrap_as_prove_error)?;
+        Ok((kb, derived))
+    })?;
+    let mut universe_map = build_universe_map(&normalized)?;
+    add_skolem_witnesses(&mut universe_map, &kb, &derived);
     let obligations = build_obligations(&normalized);
     let relation_names = normalized
         .relations
@@ -242,28 +514,31 @@ pub fn prove_program(program: &Program) -> Result<ProofTrace, Vec<Diagnostic>> {
         .iter()
         .map(|defn| (defn.name.clone(), defn))
         .collect::<HashMap<_, _>>();
+    let recursive_groups = recursive_groups(&normalized);
     let premise_eval = PremiseEvalContext {
         kb: &kb,
+        exprs: &normalized.exprs,
         relation_names: &relation_names,
         constructor_names: &constructor_names,
         defn_map: &defn_map,
+        recursive_groups: &recursive_groups,
     };
 
     let mut traces = Vec::new();
     for obligation in obligations {
+        let obligation_started = Instant::now();
+        let obligation_phase = format!("prove:{}", obligation.id);
         let valuations = enumerate_valuations(&obligation.vars, &universe_map)?;
 
         let mut failed = None;
         for valuation in valuations {
             let goal = substitute_formula_values(&obligation.goal, &valuation);
-            if let Some(premises) = evaluate_obligation_failure(
+            if let Some((premises, goal)) = evaluate_obligation_failure(
+                &premise_eval,
                 &obligation,
                 &valuation,
                 &goal,
                 &derived,
-                &relation_names,
-                &constructor_names,
-                &defn_map,
             )? {
                 let minimized = if premises.is_empty() {
                     premises
@@ -290,6 +565,7 @@ pub fn prove_program(program: &Program) -> Result<ProofTrace, Vec<Diagnostic>> {
                     premises: render_premises(&premises),
                     missing_goals: render_missing_goals(&goal, &derived),
                 }),
+                message: obligation.message,
             });
         } else {
             traces.push(ObligationTrace {
@@ -300,12 +576,19 @@ pub fn prove_program(program: &Program) -> Result<ProofTrace, Vec<Diagnostic>> {
                 premises: Vec::new(),
                 derived: Vec::new(),
                 counterexample: None,
+                message: None,
             });
         }
+        timings.record(&obligation_phase, obligation_started.elapsed());
+        if let Some(trace) = traces.last() {
+            tracing::info!(id = %trace.id, result = %trace.result, "obligation checked");
+            on_obligation(trace);
+        }
     }
 
     let proved = traces.iter().filter(|o| o.result == "proved").count();
     let total = traces.len();
+    tracing::info!(total, proved, failed = total - proved, "proof finished");
     Ok(ProofTrace {
         schema_version: PROOF_TRACE_SCHEMA_VERSION.to_string(),
         profile: "standard".to_string(),
@@ -318,8 +601,10 @@ pub fn prove_program(program: &Program) -> Result<ProofTrace, Vec<Diagnostic>> {
         claim_coverage: ClaimCoverage {
             total_claims: total,
             proved_claims: proved,
+            policy: None,
         },
         obligations: traces,
+        derivations: None,
     })
 }
 
@@ -332,22 +617,179 @@ pub fn has_full_claim_coverage(trace: &ProofTrace) -> bool {
 }
 
 pub fn write_proof_trace(path: &Path, trace: &ProofTrace) -> Result<(), Diagnostic> {
-    let rendered = serde_json::to_string_pretty(trace).map_err(|e| {
+    let mut writer = ProofTraceWriter::create(path)?;
+    for obligation in &trace.obligations {
+        writer.write_obligation(obligation)?;
+    }
+    writer.finish(trace)
+}
+
+pub struct ProofTraceWriter {
+    path: std::path::PathBuf,
+    sink: TraceSink,
+    obligations: usize,
+}
+
+enum TraceSink {
+    Plain(BufWriter<fs::File>),
+    Zstd(zstd::Encoder<'static, BufWriter<fs::File>>),
+}
+
+impl Write for TraceSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            TraceSink::Plain(file) => file.write(buf),
+            TraceSink::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            TraceSink::Plain(file) => file.flush(),
+            TraceSink::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProofTraceTail<'a> {
+    profile: &'a str,
+    engine: &'a str,
+    summary: &'a ProofSummary,
+    claim_coverage: &'a ClaimCoverage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    derivations: Option<&'a Vec<DerivationStep>>,
+}
+
+impl ProofTraceWriter {
+    pub fn create(path: &Path) -> Result<Self, Diagnostic> {
+        let file = BufWriter::new(fs::File::create(path).map_err(|e| write_error(path, e))?);
+        let sink = if is_zstd_path(path) {
+            TraceSink::Zstd(zstd::Encoder::new(file, 0).map_err(|e| write_error(path, e))?)
+        } else {
+            TraceSink::Plain(file)
+        };
+        let mut writer = Self {
+            path: path.to_path_buf(),
+            sink,
+            obligations: 0,
+        };
+        let schema_version =
+            serde_json::to_string(PROOF_TRACE_SCHEMA_VERSION).expect("a string serializes");
+        writer.write(
+            format!("{{\n  \"schema_version\": {schema_version},\n  \"obligations\": [").as_bytes(),
+        )?;
+        Ok(writer)
+    }
+
+    pub fn write_obligation(&mut self, obligation: &ObligationTrace) -> Result<(), Diagnostic> {
+        let json = serde_json::to_vec_pretty(obligation).map_err(serialize_error)?;
+        let separator: &[u8] = if self.obligations == 0 { b"\n" } else { b",\n" };
+        self.write(separator)?;
+        self.write_indented(&json, b"    ")?;
+        self.obligations += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self, trace: &ProofTrace) -> Result<(), Diagnostic> {
+        let tail = ProofTraceTail {
+            profile: &trace.profile,
+            engine: &trace.engine,
+            summary: &trace.summary,
+            claim_coverage: &trace.claim_coverage,
+            derivations: trace.derivations.as_ref(),
+        };
+        let json = serde_json::to_vec_pretty(&tail).map_err(serialize_error)?;
+        let fields = json
+            .strip_prefix(b"{\n")
+            .and_then(|rest| rest.strip_suffix(b"\n}"))
+            .expect("a struct serializes to a pretty object");
+        self.write(if self.obligations == 0 {
+            b"]"
+        } else {
+            b"\n  ]"
+        })?;
+        self.write(b",\n")?;
+        self.write(fields)?;
+        self.write(b"\n}")?;
+        let mut file = match self.sink {
+            TraceSink::Plain(file) => file,
+            TraceSink::Zstd(encoder) => encoder.finish().map_err(|e| write_error(&self.path, e))?,
+        };
+        file.flush().map_err(|e| write_error(&self.path, e))
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Diagnostic> {
+        self.sink
+            .write_all(bytes)
+            .map_err(|e| write_error(&self.path, e))
+    }
+
+    fn write_indented(&mut self, json: &[u8], indent: &[u8]) -> Result<(), Diagnostic> {
+        for (index, line) in json.split(|byte| *byte == b'\n').enumerate() {
+            if index > 0 {
+                self.write(b"\n")?;
+            }
+            self.write(indent)?;
+            self.write(line)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_error(path: &Path, e: std::io::Error) -> Diagnostic {
+    Diagnostic::new(
+        "E-IO",
+        format!("failed to write {}: {e}", path.display()),
+        None,
+    )
+}
+
+fn serialize_error(e: serde_json::Error) -> Diagnostic {
+    Diagnostic::new(
+        "E-IO",
+        format!("failed to serialize proof trace: {e}"),
+        None,
+    )
+}
+
+pub fn read_proof_trace(path: &Path) -> Result<ProofTrace, Diagnostic> {
+    let file = fs::File::open(path).map_err(|e| {
         Diagnostic::new(
             "E-IO",
-            format!("failed to serialize proof trace: {e}"),
+            format!("failed to read {}: {e}", path.display()),
             None,
         )
     })?;
-    fs::write(path, rendered).map_err(|e| {
+    let reader = BufReader::new(file);
+    let parsed = if is_zstd_path(path) {
+        zstd::Decoder::with_buffer(reader)
+            .map_err(serde_json::Error::io)
+            .and_then(serde_json::from_reader)
+    } else {
+        serde_json::from_reader(reader)
+    };
+    parsed.map_err(|e| {
         Diagnostic::new(
             "E-IO",
-            format!("failed to write {}: {e}", path.display()),
+            format!("failed to parse proof trace {}: {e}", path.display()),
             None,
         )
     })
 }
 
+pub(crate) fn is_zstd_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "zst")
+}
+
+pub fn proof_trace_filename(compress: bool) -> &'static str {
+    if compress {
+        "proof-trace.json.zst"
+    } else {
+        "proof-trace.json"
+    }
+}
+
 pub fn generate_doc_bundle(
     program: &Program,
     trace: &ProofTrace,
@@ -364,7 +806,8 @@ pub fn generate_doc_bundle_with_options(
     format: DocBundleFormat,
     options: DocBundleOptions,
 ) -> Result<(), Vec<Diagnostic>> {
-    if has_failed_obligation(trace) {
+    let has_failed = has_failed_obligation(trace);
+    if has_failed && !options.allow_failed {
         return Err(vec![Diagnostic::new(
             "E-PROVE",
             "cannot generate documentation because there are unproved obligations",
@@ -383,7 +826,8 @@ pub fn generate_doc_bundle_with_options(
         )]
     })?;
 
-    let proof_path = out_dir.join("proof-trace.json");
+    let trace_filename = proof_trace_filename(options.compress_trace);
+    let proof_path = out_dir.join(trace_filename);
     write_proof_trace(&proof_path, trace).map_err(|d| vec![d])?;
 
     let profile = options
@@ -391,8 +835,19 @@ pub fn generate_doc_bundle_with_options(
         .clone()
         .unwrap_or_else(|| trace.profile.clone());
     let self_description = options.self_description.unwrap_or_default();
+    let drift = options.selfdoc_drift;
+    let proof_changes = options
+        .previous_trace
+        .as_ref()
+        .map(|previous| diff_proof_traces(previous, trace));
+    let extras = SpecExtras {
+        out_dir,
+        drift: drift.as_ref(),
+        proof_changes: proof_changes.as_deref(),
+        proof_micros: options.proof_micros.filter(|_| !options.deterministic),
+    };
     let (spec_filename, spec_content) =
-        render_spec_content(program, trace, format, &profile, &self_description)?;
+        render_spec_content(program, trace, format, &profile, &self_description, &extras)?;
     let spec_path = out_dir.join(spec_filename);
     fs::write(&spec_path, spec_content).map_err(|e| {
         vec![Diagnostic::new(
@@ -402,11 +857,47 @@ pub fn generate_doc_bundle_with_options(
         )]
     })?;
 
+    let badge_path = out_dir.join("badge.json");
+    let badge = serde_json::to_string_pretty(&render_badge(trace)).expect("serialize badge");
+    fs::write(&badge_path, badge).map_err(|e| {
+        vec![Diagnostic::new(
+            "E-IO",
+            format!("failed to write {}: {e}", badge_path.display()),
+            None,
+        )]
+    })?;
+
+    let mut files = vec![spec_filename, trace_filename, "badge.json"];
+    if let Some(drift) = &drift {
+        let drift_path = out_dir.join("selfdoc-drift.json");
+        let rendered = serde_json::to_string_pretty(drift).expect("serialize selfdoc drift");
+        fs::write(&drift_path, rendered).map_err(|e| {
+            vec![Diagnostic::new(
+                "E-IO",
+                format!("failed to write {}: {e}", drift_path.display()),
+                None,
+            )]
+        })?;
+        files.push("selfdoc-drift.json");
+    }
+    if let Some(changes) = &proof_changes {
+        let changes_path = out_dir.join("proof-changes.json");
+        let rendered = serde_json::to_string_pretty(changes).expect("serialize proof changes");
+        fs::write(&changes_path, rendered).map_err(|e| {
+            vec![Diagnostic::new(
+                "E-IO",
+                format!("failed to write {}: {e}", changes_path.display()),
+                None,
+            )]
+        })?;
+        files.push("proof-changes.json");
+    }
+
     let index = serde_json::json!({
         "schema_version": DOC_SPEC_SCHEMA_VERSION,
         "profile": profile,
-        "files": [spec_filename, "proof-trace.json"],
-        "status": "ok",
+        "files": files,
+        "status": if has_failed { "failed" } else { "ok" },
         "intermediate": {
             "dsl": options.intermediate_dsl
         }
@@ -427,17 +918,25 @@ pub fn generate_doc_bundle_with_options(
     Ok(())
 }
 
+struct SpecExtras<'a> {
+    out_dir: &'a Path,
+    drift: Option<&'a DocSelfdocDrift>,
+    proof_changes: Option<&'a [DocProofChange]>,
+    proof_micros: Option<u64>,
+}
+
 fn render_spec_content(
     program: &Program,
     trace: &ProofTrace,
     format: DocBundleFormat,
     profile: &str,
     self_description: &DocSelfDescription,
+    extras: &SpecExtras<'_>,
 ) -> Result<(&'static str, String), Vec<Diagnostic>> {
     match format {
         DocBundleFormat::Markdown => Ok((
             "spec.md",
-            render_spec_markdown(program, trace, profile, self_description),
+            render_spec_markdown(program, trace, profile, self_description, extras),
         )),
         DocBundleFormat::Json => {
             let spec = render_spec_json(program, trace, profile, self_description.clone());
@@ -453,11 +952,472 @@ fn render_spec_content(
     }
 }
 
+fn render_drift_markdown(out: &mut String, drift: &DocSelfdocDrift) {
+    out.push_str("## ベースラインとの差分\n");
+    out.push_str(&format!("- ベースライン: `{}`\n", drift.baseline));
+    if drift.is_empty() {
+        out.push_str("- 差分はありません\n\n");
+        return;
+    }
+    out.push('\n');
+    for (title, entries) in drift.groups() {
+        if entries.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("### {title}\n"));
+        for entry in entries {
+            let label = match entry.change.as_str() {
+                "added" => "追加",
+                "removed" => "削除",
+                _ => "変更",
+            };
+            out.push_str(&format!("- {label}: `{}`\n", entry.key));
+        }
+        out.push('\n');
+    }
+}
+
+fn render_proof_changes_markdown(out: &mut String, changes: &[DocProofChange]) {
+    out.push_str("## 前回検証からの変更\n");
+    if changes.is_empty() {
+        out.push_str("- 変更はありません\n\n");
+        return;
+    }
+    let transitions = changes.iter().filter(|c| c.is_transition()).count();
+    out.push_str(&format!(
+        "- {} 件の証明義務が変わりました（うち状態遷移 {transitions} 件）\n\n",
+        changes.len()
+    ));
+    out.push_str("| 証明義務 | 変更 | 前回 | 今回 |\n");
+    out.push_str("| --- | --- | --- | --- |\n");
+    for change in changes {
+        let label = match change.change.as_str() {
+            "added" => "追加",
+            "removed" => "削除",
+            _ if change.is_transition() => "状態遷移",
+            _ => "変更",
+        };
+        let result = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map_or_else(|| "-".to_string(), |r| format!("`{r}`"))
+        };
+        out.push_str(&format!(
+            "| `{}` | {label} | {} | {} |\n",
+            change.id,
+            result(&change.before),
+            result(&change.after)
+        ));
+    }
+    out.push('\n');
+}
+
+const DOC_FACT_PREVIEW: usize = 5;
+
+fn render_glossary_markdown(out: &mut String, program: &Program) {
+    enum Term<'a> {
+        Sort,
+        Data(&'a DataDecl),
+        Relation(&'a RelationDecl),
+    }
+    let mut terms = BTreeMap::new();
+    for sort in &program.sorts {
+        terms.insert(sort.name.as_str(), Term::Sort);
+    }
+    for data in &program.data_decls {
+        terms.insert(data.name.as_str(), Term::Data(data));
+    }
+    for rel in &program.relations {
+        terms.insert(rel.name.as_str(), Term::Relation(rel));
+    }
+    if terms.is_empty() {
+        return;
+    }
+    let term_link = |name: &str| {
+        if terms.contains_key(name) {
+            format!("[`{name}`](#term-{name})")
+        } else {
+            format!("`{name}`")
+        }
+    };
+
+    out.push_str("## 用語集\n");
+    for (name, term) in &terms {
+        let uses = find_references(program, name).len();
+        let mut parts = Vec::new();
+        let kind = match term {
+            Term::Sort => "sort",
+            Term::Data(data) => {
+                let ctors = data
+                    .constructors
+                    .iter()
+                    .map(|ctor| format!("`{}`", ctor.name))
+                    .collect::<Vec<_>>();
+                if !ctors.is_empty() {
+                    parts.push(format!("コンストラクタ {}", ctors.join(", ")));
+                }
+                "data"
+            }
+            Term::Relation(rel) => {
+                let args = rel
+                    .arg_sorts
+                    .iter()
+                    .map(|sort| term_link(sort))
+                    .collect::<Vec<_>>();
+                parts.push(format!("引数 ({})", args.join(", ")));
+                "relation"
+            }
+        };
+        parts.push(format!("使用 {uses} 件"));
+        match term {
+            Term::Sort | Term::Data(_) => {
+                let relations = program
+                    .relations
+                    .iter()
+                    .filter(|rel| rel.arg_sorts.iter().any(|sort| sort == name))
+                    .map(|rel| term_link(&rel.name))
+                    .collect::<Vec<_>>();
+                if !relations.is_empty() {
+                    parts.push(format!("引数に取る relation {}", relations.join(", ")));
+                }
+            }
+            Term::Relation(rel) => {
+                parts.push(format!("[依存と制約](#relation-{})", rel.name));
+                if let Some(doc) = &rel.doc {
+                    parts.push(doc.split_whitespace().collect::<Vec<_>>().join(" "));
+                }
+            }
+        }
+        out.push_str(&format!(
+            "- <a id=\"term-{name}\"></a>`{name}`（{kind}）: {}\n",
+            parts.join(" / ")
+        ));
+    }
+    out.push('\n');
+}
+
+fn bundle_path<'a>(file: &'a str, out_dir: &Path) -> std::borrow::Cow<'a, str> {
+    match Path::new(file).strip_prefix(out_dir) {
+        Ok(relative) => relative.to_string_lossy().into_owned().into(),
+        Err(_) => file.into(),
+    }
+}
+
+struct SourceExcerpts<'a> {
+    out_dir: &'a Path,
+    files: HashMap<String, Vec<FormExcerpt>>,
+}
+
+impl<'a> SourceExcerpts<'a> {
+    fn new(out_dir: &'a Path) -> Self {
+        Self {
+            out_dir,
+            files: HashMap::new(),
+        }
+    }
+
+    fn excerpt(&mut self, span: &Span) -> Option<&str> {
+        let file = span.file_id.as_deref()?;
+        let forms = self.files.entry(file.to_string()).or_insert_with(|| {
+            fs::read_to_string(file)
+                .map(|src| form_excerpts(&src))
+                .unwrap_or_default()
+        });
+        forms
+            .iter()
+            .find(|form| form.start <= span.start && span.start < form.end)
+            .map(|form| form.text.as_str())
+    }
+
+    fn push(&mut self, out: &mut String, indent: &str, span: &Span) {
+        let out_dir = self.out_dir;
+        let Some(text) = self.excerpt(span) else {
+            return;
+        };
+        let file = bundle_path(span.file_id.as_deref().unwrap_or_default(), out_dir);
+        out.push_str(&format!(
+            "{indent}<details><summary>ソース（{file}:{}）</summary>\n\n",
+            span.line
+        ));
+        out.push_str(&format!("{indent}```dtl\n"));
+        for line in text.lines() {
+            if line.is_empty() {
+                out.push('\n');
+            } else {
+                out.push_str(&format!("{indent}{line}\n"));
+            }
+        }
+        out.push_str(&format!("{indent}```\n\n{indent}</details>\n"));
+    }
+}
+
+fn render_failed_obligations_markdown(
+    out: &mut String,
+    program: &Program,
+    trace: &ProofTrace,
+    out_dir: &Path,
+) {
+    out.push_str("## 失敗した証明義務\n\n");
+    for obligation in &trace.obligations {
+        if obligation.result == "proved" {
+            continue;
+        }
+        out.push_str(&format!("### `{}`\n", obligation.id));
+        out.push_str(&format!("- 結果: `{}`\n", obligation.result));
+        if let Some((formula, span)) = obligation_source(program, &obligation.id) {
+            let file = bundle_path(span.file_id.as_deref().unwrap_or("<input>"), out_dir);
+            out.push_str(&format!("- 位置: `{file}:{}:{}`\n", span.line, span.column));
+            out.push_str(&format!("- 式: `{formula}`\n"));
+        }
+        if let Some(message) = &obligation.message {
+            out.push_str(&format!("- メッセージ: {message}\n"));
+        }
+        if let Some(counterexample) = &obligation.counterexample {
+            if counterexample.valuation.is_empty() {
+                out.push_str("- 反例の代入: なし\n");
+            } else {
+                let valuation = counterexample
+                    .valuation
+                    .iter()
+                    .map(|nv| format!("`{} = {}`", nv.name, nv.value))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                out.push_str(&format!("- 反例の代入: {valuation}\n"));
+            }
+            for (label, items) in [
+                ("前提", &counterexample.premises),
+                ("成立しない goal", &counterexample.missing_goals),
+            ] {
+                if items.is_empty() {
+                    continue;
+                }
+                out.push_str(&format!("- {label}:\n"));
+                for item in items {
+                    out.push_str(&format!("  - `{item}`\n"));
+                }
+            }
+        }
+        out.push('\n');
+    }
+}
+
+pub fn obligation_span<'a>(program: &'a Program, id: &str) -> Option<&'a Span> {
+    obligation_source(program, id).map(|(_, span)| span)
+}
+
+pub(crate) fn obligation_source<'a>(program: &'a Program, id: &str) -> Option<(String, &'a Span)> {
+    if let Some(name) = id.strip_prefix("assert::") {
+        let assertion = program.asserts.iter().find(|a| a.name == name)?;
+        return Some((formula_to_string(&assertion.formula), &assertion.span));
+    }
+    if let Some(name) = id.strip_prefix("measure::") {
+        let defn = program.defns.iter().find(|d| d.name == name)?;
+        return Some((render_expr(&program.exprs, defn.measure?), &defn.span));
+    }
+    let name = id.strip_prefix("defn::")?;
+    let defn = program.defns.iter().find(|d| d.name == name)?;
+    Some((type_to_string(&defn.ret_type), &defn.span))
+}
+
+fn render_dependency_markdown(out: &mut String, program: &Program) {
+    if program.relations.is_empty() {
+        return;
+    }
+    let mut rule_deps = BTreeMap::<&str, BTreeSet<String>>::new();
+    for rule in &program.rules {
+        let mut preds = BTreeSet::new();
+        collect_formula_preds(&rule.body, &mut preds);
+        rule_deps
+            .entry(rule.head.pred.as_str())
+            .or_default()
+            .extend(preds);
+    }
+    let relation_names = program
+        .relations
+        .iter()
+        .map(|rel| rel.name.as_str())
+        .collect::<HashSet<_>>();
+    let mut constrained_by = BTreeMap::<&str, Vec<&str>>::new();
+    let assert_deps = program
+        .asserts
+        .iter()
+        .map(|assertion| {
+            let mut direct = BTreeSet::new();
+            collect_formula_preds(&assertion.formula, &mut direct);
+            let mut seen = BTreeSet::new();
+            let mut stack = direct.into_iter().collect::<Vec<_>>();
+            while let Some(pred) = stack.pop() {
+                if !relation_names.contains(pred.as_str()) || !seen.insert(pred.clone()) {
+                    continue;
+                }
+                stack.extend(rule_deps.get(pred.as_str()).into_iter().flatten().cloned());
+            }
+            for pred in &seen {
+                if let Some(name) = relation_names.get(pred.as_str()) {
+                    constrained_by
+                        .entry(name)
+                        .or_default()
+                        .push(assertion.name.as_str());
+                }
+            }
+            (assertion.name.as_str(), seen)
+        })
+        .collect::<Vec<_>>();
+
+    out.push_str("## 依存と制約\n\n");
+    for (name, deps) in &assert_deps {
+        out.push_str(&format!("<a id=\"assert-{name}\"></a>\n"));
+        out.push_str(&format!("### assert `{name}`\n"));
+        if deps.is_empty() {
+            out.push_str("- 依存 relation なし\n\n");
+        } else {
+            let links = deps
+                .iter()
+                .map(|dep| format!("[`{dep}`](#relation-{dep})"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            out.push_str(&format!("- 依存 relation: {links}\n\n"));
+        }
+    }
+    for rel in &program.relations {
+        let name = rel.name.as_str();
+        out.push_str(&format!("<a id=\"relation-{name}\"></a>\n"));
+        out.push_str(&format!("### relation `{name}`\n"));
+        let rules = program
+            .rules
+            .iter()
+            .filter(|rule| rule.head.pred == name)
+            .collect::<Vec<_>>();
+        if !rules.is_empty() {
+            out.push_str(&format!("- rule: {} 件\n", rules.len()));
+            for rule in rules {
+                out.push_str(&format!(
+                    "  - `{} <- {}`\n",
+                    formula_to_string(&Formula::Atom(rule.head.clone())),
+                    formula_to_string(&rule.body)
+                ));
+            }
+        }
+        let facts = program
+            .facts
+            .iter()
+            .filter(|fact| fact.name == name)
+            .collect::<Vec<_>>();
+        if !facts.is_empty() {
+            out.push_str(&format!("- fact: {} 件\n", facts.len()));
+            for fact in facts.iter().take(DOC_FACT_PREVIEW) {
+                let atom = Atom {
+                    pred: fact.name.clone(),
+                    terms: fact.terms.clone(),
+                };
+                out.push_str(&format!(
+                    "  - `{}`\n",
+                    formula_to_string(&Formula::Atom(atom))
+                ));
+            }
+            if facts.len() > DOC_FACT_PREVIEW {
+                out.push_str(&format!("  - ほか {} 件\n", facts.len() - DOC_FACT_PREVIEW));
+            }
+        }
+        if let Some(deps) = rule_deps.get(name) {
+            let links = deps
+                .iter()
+                .filter(|dep| relation_names.contains(dep.as_str()))
+                .map(|dep| format!("[`{dep}`](#relation-{dep})"))
+                .collect::<Vec<_>>();
+            if !links.is_empty() {
+                out.push_str(&format!("- 依存 relation: {}\n", links.join(", ")));
+            }
+        }
+        match constrained_by.get(name) {
+            Some(asserts) => {
+                let links = asserts
+                    .iter()
+                    .map(|assertion| format!("[`{assertion}`](#assert-{assertion})"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                out.push_str(&format!("- 制約する assert: {links}\n"));
+            }
+            None => out.push_str("- 制約する assert なし\n"),
+        }
+        out.push('\n');
+    }
+}
+
+struct ObligationCounts {
+    total: usize,
+    proved: usize,
+    failed: usize,
+    timeout: usize,
+}
+
+impl ObligationCounts {
+    fn of(trace: &ProofTrace) -> Self {
+        let count = |result: &str| {
+            trace
+                .obligations
+                .iter()
+                .filter(|o| o.result == result)
+                .count()
+        };
+        let total = trace.obligations.len();
+        let proved = count("proved");
+        let timeout = count("timeout");
+        Self {
+            total,
+            proved,
+            failed: total - proved - timeout,
+            timeout,
+        }
+    }
+}
+
+fn render_badge(trace: &ProofTrace) -> serde_json::Value {
+    let counts = ObligationCounts::of(trace);
+    let (message, color) = if counts.total == 0 {
+        ("no obligations".to_string(), "lightgrey")
+    } else if counts.proved == counts.total {
+        (
+            format!("{}/{} proved", counts.proved, counts.total),
+            "brightgreen",
+        )
+    } else {
+        (format!("{}/{} proved", counts.proved, counts.total), "red")
+    };
+    serde_json::json!({
+        "schemaVersion": 1,
+        "label": "dtl proof",
+        "message": message,
+        "color": color,
+    })
+}
+
+fn render_summary_table(out: &mut String, trace: &ProofTrace, proof_micros: Option<u64>) {
+    let counts = ObligationCounts::of(trace);
+    let coverage = &trace.claim_coverage;
+    out.push_str("## 検証サマリー\n");
+    out.push_str("| 項目 | 値 |\n|---|---|\n");
+    out.push_str(&format!("| 証明義務 | {} |\n", counts.total));
+    out.push_str(&format!("| proved | {} |\n", counts.proved));
+    out.push_str(&format!("| failed | {} |\n", counts.failed));
+    out.push_str(&format!("| timeout | {} |\n", counts.timeout));
+    out.push_str(&format!(
+        "| claim coverage | {}/{}（{:.1}%） |\n",
+        coverage.proved_claims,
+        coverage.total_claims,
+        coverage.ratio() * 100.0
+    ));
+    out.push_str(&format!("| engine | `{}` |\n", trace.engine));
+    let duration = proof_micros.map_or_else(|| "-".to_string(), format_micros);
+    out.push_str(&format!("| 所要時間 | {duration} |\n\n"));
+}
+
 fn render_spec_markdown(
     program: &Program,
     trace: &ProofTrace,
     profile: &str,
     self_description: &DocSelfDescription,
+    extras: &SpecExtras<'_>,
 ) -> String {
     let mut out = String::new();
     let proved = trace
@@ -468,8 +1428,13 @@ fn render_spec_markdown(
     let failed = trace.obligations.len().saturating_sub(proved);
 
     out.push_str("# ドメイン仕様書\n\n");
-    out.push_str("この文書は `dtl doc` により自動生成された検証済み仕様です。");
+    if failed == 0 {
+        out.push_str("この文書は `dtl doc` により自動生成された検証済み仕様です。");
+    } else {
+        out.push_str("この文書は `dtl doc` により自動生成された仕様で、未証明の義務を含みます。");
+    }
     out.push_str("記述内容はプログラム定義と証明結果を同期したものです。\n\n");
+    render_summary_table(&mut out, trace, extras.proof_micros);
 
     out.push_str("## 概要\n");
     out.push_str(&format!(
@@ -492,12 +1457,18 @@ fn render_spec_markdown(
         failed
     ));
 
+    if failed > 0 {
+        render_failed_obligations_markdown(&mut out, program, trace, extras.out_dir);
+    }
+
+    let mut sources = SourceExcerpts::new(extras.out_dir);
     out.push_str("## 型定義\n");
     if program.sorts.is_empty() && program.data_decls.is_empty() {
         out.push_str("- 定義なし\n");
     } else {
         for sort in &program.sorts {
             out.push_str(&format!("- sort `{}`\n", sort.name));
+            sources.push(&mut out, "  ", &sort.span);
         }
         for data in &program.data_decls {
             out.push_str(&format!("- data `{}`\n", data.name));
@@ -514,6 +1485,7 @@ fn render_spec_markdown(
                     out.push_str(&format!("  - `{}`({})\n", ctor.name, fields));
                 }
             }
+            sources.push(&mut out, "  ", &data.span);
         }
     }
     out.push('\n');
@@ -525,28 +1497,82 @@ fn render_spec_markdown(
         for rel in &program.relations {
             let args = rel.arg_sorts.join(", ");
             out.push_str(&format!("- relation `{}`({})\n", rel.name, args));
+            push_doc_line(&mut out, "  ", rel.doc.as_deref());
+            sources.push(&mut out, "  ", &rel.span);
+        }
+    }
+    if !program.defns.is_empty() {
+        out.push_str("- defn\n");
+        for defn in &program.defns {
+            let params = defn
+                .params
+                .iter()
+                .map(|p| format!("{}: {}", p.name, type_to_string(&p.ty)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            out.push_str(&format!(
+                "  - `{}`({}) -> {}\n",
+                defn.name,
+                params,
+                type_to_string(&defn.ret_type)
+            ));
+            push_doc_line(&mut out, "    ", defn.doc.as_deref());
+            sources.push(&mut out, "    ", &defn.span);
         }
     }
     if !program.asserts.is_empty() {
         out.push_str("- assert\n");
         for a in &program.asserts {
             out.push_str(&format!("  - `{}`\n", a.name));
+            push_doc_line(&mut out, "    ", a.doc.as_deref());
+            if let Some(message) = &a.message {
+                out.push_str(&format!("    - 失敗時メッセージ: {message}\n"));
+            }
+            sources.push(&mut out, "    ", &a.span);
         }
     }
     out.push('\n');
 
+    render_dependency_markdown(&mut out, program);
+    render_glossary_markdown(&mut out, program);
+
     out.push_str("## 証明結果\n");
     for o in &trace.obligations {
         out.push_str(&format!("- `{}`: `{}`\n", o.id, o.result));
     }
     out.push('\n');
 
+    if let Some(changes) = extras.proof_changes {
+        render_proof_changes_markdown(&mut out, changes);
+    }
+
     if let Some(project) = &self_description.project {
         out.push_str("## 自己記述プロジェクト\n");
         out.push_str(&format!("- 名前: `{}`\n", project.name));
         out.push_str(&format!("- 概要: {}\n\n", project.summary));
     }
 
+    if !self_description.categories.is_empty() {
+        out.push_str("## 自己記述カテゴリ\n");
+        for category in &self_description.categories {
+            let count = self_description
+                .modules
+                .iter()
+                .filter(|module| module.category == category.name)
+                .count();
+            out.push_str(&format!("- `{}`（{count} 件）", category.name));
+            if let Some(description) = &category.description {
+                out.push_str(&format!(": {description}"));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    if let Some(drift) = extras.drift {
+        render_drift_markdown(&mut out, drift);
+    }
+
     out.push_str("## Mermaid: 型・関係図\n\n");
     out.push_str("```mermaid\n");
     out.push_str("erDiagram\n");
@@ -602,7 +1628,7 @@ fn render_spec_markdown(
 
     for rule in &program.rules {
         if let Some(head_id) = relation_ids.get(&rule.head.pred) {
-            let mut refs = HashSet::new();
+            let mut refs = BTreeSet::new();
             collect_formula_preds(&rule.body, &mut refs);
             for pred in refs {
                 if let Some(from_id) = relation_ids.get(&pred) {
@@ -616,8 +1642,11 @@ fn render_spec_markdown(
         let Some(defn_id) = defn_ids.get(&defn.name) else {
             continue;
         };
-        let mut refs = HashSet::new();
-        collect_expr_call_names(&defn.body, &mut refs);
+        let mut refs = BTreeSet::new();
+        collect_expr_call_names(&program.exprs, defn.body, &mut refs);
+        if let Some(measure) = defn.measure {
+            collect_expr_call_names(&program.exprs, measure, &mut refs);
+        }
         for name in refs {
             if let Some(rel_id) = relation_ids.get(&name) {
                 out.push_str(&format!("  {defn_id} --> {rel_id}\n"));
@@ -631,7 +1660,7 @@ fn render_spec_markdown(
         let Some(assert_id) = assert_ids.get(&assertion.name) else {
             continue;
         };
-        let mut refs = HashSet::new();
+        let mut refs = BTreeSet::new();
         collect_formula_preds(&assertion.formula, &mut refs);
         for pred in refs {
             if let Some(rel_id) = relation_ids.get(&pred) {
@@ -657,7 +1686,7 @@ fn render_spec_markdown(
     out
 }
 
-fn collect_formula_preds(formula: &Formula, out: &mut HashSet<String>) {
+fn collect_formula_preds(formula: &Formula, out: &mut BTreeSet<String>) {
     match formula {
         Formula::True => {}
         Formula::Atom(atom) => {
@@ -672,39 +1701,12 @@ fn collect_formula_preds(formula: &Formula, out: &mut HashSet<String>) {
     }
 }
 
-fn collect_expr_call_names(expr: &Expr, out: &mut HashSet<String>) {
-    match expr {
-        Expr::Var { .. } | Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => {}
-        Expr::Call { name, args, .. } => {
-            out.insert(name.clone());
-            for arg in args {
-                collect_expr_call_names(arg, out);
-            }
-        }
-        Expr::Let { bindings, body, .. } => {
-            for (_, bexpr, _) in bindings {
-                collect_expr_call_names(bexpr, out);
-            }
-            collect_expr_call_names(body, out);
-        }
-        Expr::If {
-            cond,
-            then_branch,
-            else_branch,
-            ..
-        } => {
-            collect_expr_call_names(cond, out);
-            collect_expr_call_names(then_branch, out);
-            collect_expr_call_names(else_branch, out);
-        }
-        Expr::Match {
-            scrutinee, arms, ..
-        } => {
-            collect_expr_call_names(scrutinee, out);
-            for arm in arms {
-                collect_expr_call_names(&arm.body, out);
-            }
-        }
+fn collect_expr_call_names(exprs: &ExprArena, expr: ExprId, out: &mut BTreeSet<String>) {
+    if let Expr::Call { name, .. } = &exprs[expr] {
+        out.insert(name.clone());
+    }
+    for child in exprs[expr].children() {
+        collect_expr_call_names(exprs, child, out);
     }
 }
 
@@ -724,6 +1726,7 @@ fn render_spec_json(
             .iter()
             .map(|sort| JsonSpecSort {
                 name: sort.name.clone(),
+                attributes: sort.attributes.clone(),
             })
             .collect(),
         data_declarations: program
@@ -739,6 +1742,7 @@ fn render_spec_json(
                         fields: ctor.fields.iter().map(type_to_string).collect(),
                     })
                     .collect(),
+                attributes: decl.attributes.clone(),
             })
             .collect(),
         relations: program
@@ -747,6 +1751,8 @@ fn render_spec_json(
             .map(|rel| JsonSpecRelation {
                 name: rel.name.clone(),
                 arg_sorts: rel.arg_sorts.clone(),
+                doc: rel.doc.clone(),
+                attributes: rel.attributes.clone(),
             })
             .collect(),
         assertions: program
@@ -754,6 +1760,20 @@ fn render_spec_json(
             .iter()
             .map(|assertion| JsonSpecAssertion {
                 name: assertion.name.clone(),
+                doc: assertion.doc.clone(),
+                message: assertion.message.clone(),
+                attributes: assertion.attributes.clone(),
+            })
+            .collect(),
+        functions: program
+            .defns
+            .iter()
+            .map(|defn| JsonSpecFunction {
+                name: defn.name.clone(),
+                params: defn.params.iter().map(|p| type_to_string(&p.ty)).collect(),
+                ret: type_to_string(&defn.ret_type),
+                doc: defn.doc.clone(),
+                attributes: defn.attributes.clone(),
             })
             .collect(),
         proof_status: trace
@@ -768,6 +1788,13 @@ fn render_spec_json(
     }
 }
 
+fn push_doc_line(out: &mut String, indent: &str, doc: Option<&str>) {
+    if let Some(doc) = doc {
+        let text = doc.split_whitespace().collect::<Vec<_>>().join(" ");
+        out.push_str(&format!("{indent}- 説明: {text}\n"));
+    }
+}
+
 fn type_to_string(ty: &Type) -> String {
     match ty {
         Type::Bool => "Bool".to_string(),
@@ -775,6 +1802,7 @@ fn type_to_string(ty: &Type) -> String {
         Type::Symbol => "Symbol".to_string(),
         Type::Domain(s) => s.clone(),
         Type::Adt(s) => s.clone(),
+        Type::Error => "?".to_string(),
         Type::Fun(args, ret) => format!(
             "(-> ({}) {})",
             args.iter()
@@ -837,25 +1865,68 @@ fn build_universe_map(program: &Program) -> Result<HashMap<String, Vec<Value>>,
     Ok(map)
 }
 
+fn add_skolem_witnesses(
+    universe_map: &mut HashMap<String, Vec<Value>>,
+    kb: &KnowledgeBase,
+    derived: &DerivedFacts,
+) {
+    let mut preds = derived.facts.keys().collect::<Vec<_>>();
+    preds.sort();
+    for pred in preds {
+        let Some(sorts) = kb.relation_schemas.get(pred) else {
+            continue;
+        };
+        for tuple in &derived.facts[pred] {
+            for (sort, value) in sorts.iter().zip(tuple) {
+                let Value::Adt { ctor, .. } = value else {
+                    continue;
+                };
+                if let Some(values) = universe_map.get_mut(sort)
+                    && ctor.as_str().starts_with(SKOLEM_CTOR_PREFIX)
+                    && !values.contains(value)
+                {
+                    values.push(value.clone());
+                }
+            }
+        }
+    }
+}
+
 fn build_obligations(program: &Program) -> Vec<ObligationSpec> {
     let mut obligations = Vec::new();
 
     for defn in &program.defns {
-        if let Type::Refine { formula, .. } = &defn.ret_type {
-            let vars = defn
-                .params
-                .iter()
-                .map(|p| QuantifiedVarSpec {
-                    name: p.name.clone(),
-                    ty: p.ty.clone(),
-                    span: p.span.clone(),
-                })
-                .collect::<Vec<_>>();
+        let vars = defn
+            .params
+            .iter()
+            .map(|p| QuantifiedVarSpec {
+                name: p.name.clone(),
+                ty: p.ty.clone(),
+                span: p.span.clone(),
+            })
+            .collect::<Vec<_>>();
+        if defn.measure.is_some() {
+            obligations.push(ObligationSpec {
+                id: format!("measure::{}", defn.name),
+                kind: "measure".to_string(),
+                message: None,
+                goal: Formula::True,
+                body: ObligationBody::Measure {
+                    defn: defn.name.clone(),
+                },
+                vars: vars.clone(),
+            });
+        }
+        if let Type::Refine { var, formula, .. } = &defn.ret_type {
             obligations.push(ObligationSpec {
                 id: format!("defn::{}", defn.name),
                 kind: "defn".to_string(),
+                message: None,
                 goal: formula.clone(),
-                body: ObligationBody::Refine(defn.body.clone()),
+                body: ObligationBody::Refine {
+                    body: defn.body,
+                    result_var: var.clone(),
+                },
                 vars,
             });
         }
@@ -865,6 +1936,7 @@ fn build_obligations(program: &Program) -> Vec<ObligationSpec> {
         obligations.push(ObligationSpec {
             id: format!("assert::{}", assertion.name),
             kind: "assert".to_string(),
+            message: assertion.message.clone(),
             goal: assertion.formula.clone(),
             body: ObligationBody::Assert,
             vars: assertion
@@ -883,36 +1955,40 @@ fn build_obligations(program: &Program) -> Vec<ObligationSpec> {
 }
 
 fn evaluate_obligation_failure(
+    ctx: &PremiseEvalContext<'_>,
     obligation: &ObligationSpec,
     valuation: &HashMap<String, Value>,
     goal: &Formula,
     derived: &DerivedFacts,
-    relation_names: &HashSet<String>,
-    constructor_names: &HashSet<String>,
-    defn_map: &HashMap<String, &Defn>,
-) -> Result<Option<Vec<GroundFact>>, Vec<Diagnostic>> {
+) -> Result<Option<(Vec<GroundFact>, Formula)>, Vec<Diagnostic>> {
     match &obligation.body {
         ObligationBody::Assert => {
             if eval_formula(goal, derived) {
                 Ok(None)
             } else {
-                Ok(Some(Vec::new()))
+                Ok(Some((Vec::new(), goal.clone())))
             }
         }
-        ObligationBody::Refine(body) => {
-            let outcome = evaluate_refine_body(
-                body,
-                valuation,
-                derived,
-                relation_names,
-                constructor_names,
-                defn_map,
-            )?;
+        ObligationBody::Measure { defn } => {
+            Ok(measure_obligation_failure(ctx, defn, valuation, derived)?
+                .map(|goal| (Vec::new(), goal)))
+        }
+        ObligationBody::Refine { body, result_var } => {
+            let outcome = evaluate_refine_body(*body, valuation, derived, ctx)?;
             match outcome.value {
-                Value::Bool(true) if !eval_formula(goal, derived) => {
-                    Ok(Some(outcome.positive_facts.into_iter().collect()))
-                }
+                Value::Bool(true) if !eval_formula(goal, derived) => Ok(Some((
+                    outcome.positive_facts.into_iter().collect(),
+                    goal.clone(),
+                ))),
                 Value::Bool(_) => Ok(None),
+                value @ Value::Int(_) => {
+                    let goal = bind_result_var(goal, result_var, value);
+                    if eval_formula(&goal, derived) {
+                        Ok(None)
+                    } else {
+                        Ok(Some((outcome.positive_facts.into_iter().collect(), goal)))
+                    }
+                }
                 other => Err(vec![Diagnostic::new(
                     "E-PROVE",
                     format!(
@@ -926,39 +2002,172 @@ fn evaluate_obligation_failure(
     }
 }
 
+fn bind_result_var(goal: &Formula, result_var: &str, value: Value) -> Formula {
+    substitute_formula_values(goal, &HashMap::from([(result_var.to_string(), value)]))
+}
+
 fn evaluate_refine_body(
-    expr: &Expr,
+    expr: ExprId,
     valuation: &HashMap<String, Value>,
     derived: &DerivedFacts,
-    relation_names: &HashSet<String>,
-    constructor_names: &HashSet<String>,
-    defn_map: &HashMap<String, &Defn>,
+    ctx: &PremiseEvalContext<'_>,
 ) -> Result<ExprEvalResult, Vec<Diagnostic>> {
-    let mut state = ExprEvalState {
-        derived,
-        relation_names,
-        constructor_names,
-        defn_map,
-        cache: HashMap::new(),
-        active_calls: HashSet::new(),
+    evaluate_expr(expr, valuation, &mut ExprEvalState::new(derived, ctx))
+}
+
+fn measure_obligation_failure(
+    ctx: &PremiseEvalContext<'_>,
+    defn_name: &str,
+    valuation: &HashMap<String, Value>,
+    derived: &DerivedFacts,
+) -> Result<Option<Formula>, Vec<Diagnostic>> {
+    let Some(defn) = ctx.defn_map.get(defn_name) else {
+        return Ok(None);
+    };
+    let mut state = ExprEvalState::new(derived, ctx);
+    let args = defn
+        .params
+        .iter()
+        .map(|param| valuation.get(&param.name).cloned())
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default();
+    let measure = evaluate_measure(defn, &args, &mut state)?;
+    state.frames.push(MeasureFrame {
+        name: defn.name.clone(),
+        measure,
+    });
+    match evaluate_expr(defn.body, valuation, &mut state) {
+        Ok(_) => Ok(None),
+        Err(errors) => match state.violation {
+            Some(violation) if violation.depth == 1 => Ok(Some(measure_goal(
+                violation.callee_measure,
+                violation.caller_measure,
+            ))),
+            _ => Err(errors),
+        },
+    }
+}
+
+fn measure_goal(callee_measure: i64, caller_measure: i64) -> Formula {
+    let compare = |op: ComparisonOp, lhs: i64, rhs: i64| {
+        Formula::Atom(Atom {
+            pred: op.as_str().to_string(),
+            terms: vec![LogicTerm::Int(lhs), LogicTerm::Int(rhs)],
+        })
+    };
+    Formula::And(vec![
+        compare(ComparisonOp::Le, 0, callee_measure),
+        compare(ComparisonOp::Lt, callee_measure, caller_measure),
+    ])
+}
+
+fn evaluate_measure(
+    defn: &Defn,
+    args: &[Value],
+    state: &mut ExprEvalState<'_>,
+) -> Result<Option<i64>, Vec<Diagnostic>> {
+    let Some(measure) = defn.measure else {
+        return Ok(None);
+    };
+    let env = defn
+        .params
+        .iter()
+        .map(|param| param.name.clone())
+        .zip(args.iter().cloned())
+        .collect::<HashMap<_, _>>();
+    match evaluate_expr(measure, &env, state)?.value {
+        Value::Int(value) => Ok(Some(value)),
+        other => Err(vec![Diagnostic::new(
+            "E-PROVE",
+            format!(
+                "termination measure of {} did not evaluate to Int: {}",
+                defn.name,
+                value_to_string(&other)
+            ),
+            Some(state.exprs.span(measure).clone()),
+        )]),
+    }
+}
+
+fn check_measure_decrease(
+    callee: &str,
+    callee_measure: i64,
+    span: &Span,
+    state: &mut ExprEvalState<'_>,
+) -> Result<(), Vec<Diagnostic>> {
+    let Some(frame) = state.frames.last() else {
+        return Ok(());
     };
-    evaluate_expr(expr, valuation, &mut state)
+    let Some(caller_measure) = frame.measure else {
+        return Ok(());
+    };
+    let group = state.recursive_groups.get(&frame.name);
+    if group.is_none() || state.recursive_groups.get(callee) != group {
+        return Ok(());
+    }
+    if (0..caller_measure).contains(&callee_measure) {
+        return Ok(());
+    }
+    let message = format!(
+        "termination measure does not decrease in {} -> {callee}: {caller_measure} -> {callee_measure}",
+        frame.name
+    );
+    state.violation = Some(MeasureViolation {
+        depth: state.frames.len(),
+        caller_measure,
+        callee_measure,
+    });
+    Err(vec![Diagnostic::new(
+        "E-PROVE",
+        message,
+        Some(span.clone()),
+    )])
 }
 
 struct ExprEvalState<'a> {
     derived: &'a DerivedFacts,
+    exprs: &'a ExprArena,
     relation_names: &'a HashSet<String>,
     constructor_names: &'a HashSet<String>,
     defn_map: &'a HashMap<String, &'a Defn>,
+    recursive_groups: &'a HashMap<String, usize>,
     cache: HashMap<CallKey, ExprEvalResult>,
     active_calls: HashSet<CallKey>,
+    frames: Vec<MeasureFrame>,
+    violation: Option<MeasureViolation>,
+    local_fns: Vec<Rc<LocalClosure>>,
+}
+
+struct LocalClosure {
+    def: LocalFn,
+    env: HashMap<String, Value>,
+}
+
+impl<'a> ExprEvalState<'a> {
+    fn new(derived: &'a DerivedFacts, ctx: &PremiseEvalContext<'a>) -> Self {
+        Self {
+            derived,
+            exprs: ctx.exprs,
+            relation_names: ctx.relation_names,
+            constructor_names: ctx.constructor_names,
+            defn_map: ctx.defn_map,
+            recursive_groups: ctx.recursive_groups,
+            cache: HashMap::new(),
+            active_calls: HashSet::new(),
+            frames: Vec::new(),
+            violation: None,
+            local_fns: Vec::new(),
+        }
+    }
 }
 
 fn evaluate_expr(
-    expr: &Expr,
+    id: ExprId,
     env: &HashMap<String, Value>,
     state: &mut ExprEvalState<'_>,
 ) -> Result<ExprEvalResult, Vec<Diagnostic>> {
+    let exprs = state.exprs;
+    let expr = &exprs[id];
     match expr {
         Expr::Var { name, .. } => env
             .get(name)
@@ -975,7 +2184,7 @@ fn evaluate_expr(
                 )]
             }),
         Expr::Symbol { value, .. } => Ok(ExprEvalResult {
-            value: Value::Symbol(value.clone()),
+            value: Value::Symbol(Symbol::intern(value)),
             positive_facts: HashSet::new(),
         }),
         Expr::Int { value, .. } => Ok(ExprEvalResult {
@@ -986,11 +2195,16 @@ fn evaluate_expr(
             value: Value::Bool(*value),
             positive_facts: HashSet::new(),
         }),
+        Expr::Hole { span } => Err(vec![Diagnostic::new(
+            "E-PROVE",
+            "typed hole cannot be evaluated",
+            Some(span.clone()),
+        )]),
         Expr::Call { name, args, .. } if state.relation_names.contains(name) => {
             let mut terms = Vec::with_capacity(args.len());
             let mut positive_facts = HashSet::new();
             for arg in args {
-                let result = evaluate_expr(arg, env, state)?;
+                let result = evaluate_expr(*arg, env, state)?;
                 positive_facts.extend(result.positive_facts);
                 terms.push(result.value);
             }
@@ -1015,19 +2229,26 @@ fn evaluate_expr(
             let mut fields = Vec::with_capacity(args.len());
             let mut positive_facts = HashSet::new();
             for arg in args {
-                let result = evaluate_expr(arg, env, state)?;
+                let result = evaluate_expr(*arg, env, state)?;
                 positive_facts.extend(result.positive_facts);
                 fields.push(result.value);
             }
             Ok(ExprEvalResult {
                 value: Value::Adt {
-                    ctor: name.clone(),
+                    ctor: Symbol::intern(name),
                     fields,
                 },
                 positive_facts,
             })
         }
         Expr::Call { name, args, .. } => {
+            if let Some(op) = ArithOp::from_name(name) {
+                return evaluate_arith(op, args, expr.span(), env, state);
+            }
+            if let Some(closure) = state.local_fns.iter().rev().find(|f| f.def.name == *name) {
+                let closure = Rc::clone(closure);
+                return evaluate_local_call(&closure, args, expr.span(), env, state);
+            }
             let Some(defn) = state.defn_map.get(name) else {
                 return Err(vec![Diagnostic::new(
                     "E-PROVE",
@@ -1051,11 +2272,16 @@ fn evaluate_expr(
             let mut arg_values = Vec::with_capacity(args.len());
             let mut arg_facts = HashSet::new();
             for arg in args {
-                let result = evaluate_expr(arg, env, state)?;
+                let result = evaluate_expr(*arg, env, state)?;
                 arg_facts.extend(result.positive_facts);
                 arg_values.push(result.value);
             }
 
+            let measure = evaluate_measure(defn, &arg_values, state)?;
+            if let Some(callee_measure) = measure {
+                check_measure_decrease(name, callee_measure, expr.span(), state)?;
+            }
+
             let key = CallKey {
                 name: name.clone(),
                 args: arg_values.clone(),
@@ -1080,7 +2306,12 @@ fn evaluate_expr(
             for (param, value) in defn.params.iter().zip(arg_values.iter()) {
                 call_env.insert(param.name.clone(), value.clone());
             }
-            let result = evaluate_expr(&defn.body, &call_env, state)?;
+            state.frames.push(MeasureFrame {
+                name: name.clone(),
+                measure,
+            });
+            let result = evaluate_expr(defn.body, &call_env, state)?;
+            state.frames.pop();
             state.active_calls.remove(&key);
             state.cache.insert(key, result.clone());
 
@@ -1095,27 +2326,39 @@ fn evaluate_expr(
             let mut local_env = env.clone();
             let mut positive_facts = HashSet::new();
             for (name, bexpr, _) in bindings {
-                let result = evaluate_expr(bexpr, &local_env, state)?;
+                let result = evaluate_expr(*bexpr, &local_env, state)?;
                 positive_facts.extend(result.positive_facts);
                 local_env.insert(name.clone(), result.value);
             }
-            let result = evaluate_expr(body, &local_env, state)?;
+            let result = evaluate_expr(*body, &local_env, state)?;
             positive_facts.extend(result.positive_facts.clone());
             Ok(ExprEvalResult {
                 value: result.value,
                 positive_facts,
             })
         }
+        Expr::LetFn { fns, body, .. } => {
+            let depth = state.local_fns.len();
+            for f in fns {
+                state.local_fns.push(Rc::new(LocalClosure {
+                    def: f.clone(),
+                    env: env.clone(),
+                }));
+            }
+            let result = evaluate_expr(*body, env, state);
+            state.local_fns.truncate(depth);
+            result
+        }
         Expr::If {
             cond,
             then_branch,
             else_branch,
             ..
         } => {
-            let cond_result = evaluate_expr(cond, env, state)?;
+            let cond_result = evaluate_expr(*cond, env, state)?;
             match cond_result.value {
                 Value::Bool(true) => {
-                    let branch_result = evaluate_expr(then_branch, env, state)?;
+                    let branch_result = evaluate_expr(*then_branch, env, state)?;
                     let mut positive_facts = cond_result.positive_facts;
                     positive_facts.extend(branch_result.positive_facts.clone());
                     Ok(ExprEvalResult {
@@ -1123,25 +2366,25 @@ fn evaluate_expr(
                         positive_facts,
                     })
                 }
-                Value::Bool(false) => evaluate_expr(else_branch, env, state),
+                Value::Bool(false) => evaluate_expr(*else_branch, env, state),
                 other => Err(vec![Diagnostic::new(
                     "E-PROVE",
                     format!(
                         "if condition did not evaluate to Bool: {}",
                         value_to_string(&other)
                     ),
-                    Some(cond.span().clone()),
+                    Some(exprs.span(*cond).clone()),
                 )]),
             }
         }
         Expr::Match {
             scrutinee, arms, ..
         } => {
-            let scrutinee_result = evaluate_expr(scrutinee, env, state)?;
+            let scrutinee_result = evaluate_expr(*scrutinee, env, state)?;
             for arm in arms {
                 let mut branch_env = env.clone();
-                if bind_pattern(&arm.pattern, &scrutinee_result.value, &mut branch_env) {
-                    let branch_result = evaluate_expr(&arm.body, &branch_env, state)?;
+                if bind_pattern(exprs, arm.pattern, &scrutinee_result.value, &mut branch_env) {
+                    let branch_result = evaluate_expr(arm.body, &branch_env, state)?;
                     let mut positive_facts = scrutinee_result.positive_facts.clone();
                     positive_facts.extend(branch_result.positive_facts.clone());
                     return Ok(ExprEvalResult {
@@ -1159,8 +2402,49 @@ fn evaluate_expr(
     }
 }
 
-fn bind_pattern(pattern: &Pattern, value: &Value, env: &mut HashMap<String, Value>) -> bool {
-    match pattern {
+// Local functions are never recursive, so their calls skip the call cache
+// and the measure frames.
+fn evaluate_local_call(
+    closure: &LocalClosure,
+    args: &[ExprId],
+    span: &Span,
+    env: &HashMap<String, Value>,
+    state: &mut ExprEvalState<'_>,
+) -> Result<ExprEvalResult, Vec<Diagnostic>> {
+    if closure.def.params.len() != args.len() {
+        return Err(vec![Diagnostic::new(
+            "E-PROVE",
+            format!(
+                "arity mismatch during expression evaluation: {} expected {}, got {}",
+                closure.def.name,
+                closure.def.params.len(),
+                args.len()
+            ),
+            Some(span.clone()),
+        )]);
+    }
+    let mut call_env = closure.env.clone();
+    let mut positive_facts = HashSet::new();
+    for (param, arg) in closure.def.params.iter().zip(args) {
+        let result = evaluate_expr(*arg, env, state)?;
+        positive_facts.extend(result.positive_facts);
+        call_env.insert(param.name.clone(), result.value);
+    }
+    let result = evaluate_expr(closure.def.body, &call_env, state)?;
+    positive_facts.extend(result.positive_facts);
+    Ok(ExprEvalResult {
+        value: result.value,
+        positive_facts,
+    })
+}
+
+fn bind_pattern(
+    exprs: &ExprArena,
+    pattern: PatternId,
+    value: &Value,
+    env: &mut HashMap<String, Value>,
+) -> bool {
+    match &exprs[pattern] {
         Pattern::Wildcard { .. } => true,
         Pattern::Var { name, .. } => match env.get(name) {
             Some(bound) => bound == value,
@@ -1181,7 +2465,7 @@ fn bind_pattern(pattern: &Pattern, value: &Value, env: &mut HashMap<String, Valu
         Pattern::Ctor { name, args, .. } => match value {
             Value::Adt { ctor, fields } if ctor == name && fields.len() == args.len() => {
                 for (arg, field) in args.iter().zip(fields.iter()) {
-                    if !bind_pattern(arg, field, env) {
+                    if !bind_pattern(exprs, *arg, field, env) {
                         return false;
                     }
                 }
@@ -1252,6 +2536,11 @@ fn type_key(ty: &Type) -> Result<String, Vec<Diagnostic>> {
             "function-typed quantified variables are not supported in prove",
             None,
         )]),
+        Type::Error => Err(vec![Diagnostic::new(
+            "E-PROVE",
+            "quantified variable has no type",
+            None,
+        )]),
     }
 }
 
@@ -1295,20 +2584,20 @@ fn substitute_term_value(term: &LogicTerm, valuation: &HashMap<String, Value>) -
 
 fn value_to_logic_term(value: &Value) -> LogicTerm {
     match value {
-        Value::Symbol(s) => LogicTerm::Symbol(s.clone()),
+        Value::Symbol(s) => LogicTerm::Symbol(s.to_string()),
         Value::Int(i) => LogicTerm::Int(*i),
         Value::Bool(b) => LogicTerm::Bool(*b),
         Value::Adt { ctor, fields } => LogicTerm::Ctor {
-            name: ctor.clone(),
+            name: ctor.to_string(),
             args: fields.iter().map(value_to_logic_term).collect(),
         },
     }
 }
 
-fn logic_term_to_const_value(term: &LogicTerm) -> Option<Value> {
+pub(crate) fn logic_term_to_const_value(term: &LogicTerm) -> Option<Value> {
     match term {
         LogicTerm::Var(_) => None,
-        LogicTerm::Symbol(s) => Some(Value::Symbol(s.clone())),
+        LogicTerm::Symbol(s) => Some(Value::Symbol(Symbol::intern(s))),
         LogicTerm::Int(i) => Some(Value::Int(*i)),
         LogicTerm::Bool(b) => Some(Value::Bool(*b)),
         LogicTerm::Ctor { name, args } => {
@@ -1317,13 +2606,50 @@ fn logic_term_to_const_value(term: &LogicTerm) -> Option<Value> {
                 fields.push(logic_term_to_const_value(arg)?);
             }
             Some(Value::Adt {
-                ctor: name.clone(),
+                ctor: Symbol::intern(name),
                 fields,
             })
         }
     }
 }
 
+fn evaluate_arith(
+    op: ArithOp,
+    args: &[ExprId],
+    span: &Span,
+    env: &HashMap<String, Value>,
+    state: &mut ExprEvalState<'_>,
+) -> Result<ExprEvalResult, Vec<Diagnostic>> {
+    let mut operands = Vec::with_capacity(args.len());
+    let mut positive_facts = HashSet::new();
+    for arg in args {
+        let result = evaluate_expr(*arg, env, state)?;
+        positive_facts.extend(result.positive_facts);
+        operands.push(result.value);
+    }
+    let name = op.as_str();
+    let value = match operands.as_slice() {
+        [Value::Int(lhs), Value::Int(rhs)] => op.apply(*lhs, *rhs).ok_or_else(|| {
+            vec![Diagnostic::new(
+                "E-PROVE",
+                format!("integer overflow in ({name} {lhs} {rhs})"),
+                Some(span.clone()),
+            )]
+        })?,
+        _ => {
+            return Err(vec![Diagnostic::new(
+                "E-PROVE",
+                format!("operator {name} expects two Int values"),
+                Some(span.clone()),
+            )]);
+        }
+    };
+    Ok(ExprEvalResult {
+        value: Value::Int(value),
+        positive_facts,
+    })
+}
+
 fn eval_formula(formula: &Formula, derived: &DerivedFacts) -> bool {
     match formula {
         Formula::True => true,
@@ -1336,17 +2662,27 @@ fn eval_formula(formula: &Formula, derived: &DerivedFacts) -> bool {
             else {
                 return false;
             };
-            derived
-                .facts
-                .get(&atom.pred)
-                .map(|set| set.contains(&tuple))
-                .unwrap_or(false)
+            ground_atom_holds(&atom.pred, &tuple, derived)
         }
         Formula::And(items) => items.iter().all(|item| eval_formula(item, derived)),
         Formula::Not(inner) => !eval_formula(inner, derived),
     }
 }
 
+fn ground_atom_holds(pred: &str, tuple: &[Value], derived: &DerivedFacts) -> bool {
+    if pred == DISEQUALITY_PRED {
+        return disequality_holds(tuple);
+    }
+    if let Some(op) = ComparisonOp::from_pred(pred) {
+        return matches!(tuple, [Value::Int(lhs), Value::Int(rhs)] if op.holds(*lhs, *rhs));
+    }
+    derived
+        .facts
+        .get(pred)
+        .map(|set| set.contains(tuple))
+        .unwrap_or(false)
+}
+
 fn obligation_fails_with_premises(
     ctx: &PremiseEvalContext<'_>,
     obligation: &ObligationSpec,
@@ -1358,17 +2694,17 @@ fn obligation_fails_with_premises(
         solve_facts(&ctx.kb.with_extra_facts(premises.to_vec())).map_err(wrap_as_prove_error)?;
     match &obligation.body {
         ObligationBody::Assert => Ok(!eval_formula(goal, &derived)),
-        ObligationBody::Refine(body) => {
-            let outcome = evaluate_refine_body(
-                body,
-                valuation,
-                &derived,
-                ctx.relation_names,
-                ctx.constructor_names,
-                ctx.defn_map,
-            )?;
+        ObligationBody::Measure { defn } => {
+            Ok(measure_obligation_failure(ctx, defn, valuation, &derived)?.is_some())
+        }
+        ObligationBody::Refine { body, result_var } => {
+            let outcome = evaluate_refine_body(*body, valuation, &derived, ctx)?;
             match outcome.value {
                 Value::Bool(value) => Ok(value && !eval_formula(goal, &derived)),
+                value @ Value::Int(_) => Ok(!eval_formula(
+                    &bind_result_var(goal, result_var, value),
+                    &derived,
+                )),
                 other => Err(vec![Diagnostic::new(
                     "E-PROVE",
                     format!(
@@ -1527,12 +2863,7 @@ fn collect_missing_goals(formula: &Formula, derived: &DerivedFacts, out: &mut BT
                 out.insert(format!("{}(non-ground)", atom.pred));
                 return;
             };
-            let exists = derived
-                .facts
-                .get(&atom.pred)
-                .map(|set| set.contains(&tuple))
-                .unwrap_or(false);
-            if !exists {
+            if !ground_atom_holds(&atom.pred, &tuple, derived) {
                 let args = tuple
                     .iter()
                     .map(value_to_string)
@@ -1556,7 +2887,7 @@ fn collect_missing_goals(formula: &Formula, derived: &DerivedFacts, out: &mut BT
 
 fn value_to_string(v: &Value) -> String {
     match v {
-        Value::Symbol(s) => s.clone(),
+        Value::Symbol(s) => s.to_string(),
         Value::Int(i) => i.to_string(),
         Value::Bool(b) => b.to_string(),
         Value::Adt { ctor, fields } => {
@@ -0,0 +1,56 @@
+use crate::ast::Program;
+use crate::diagnostics::Diagnostic;
+use crate::logic_engine::DerivationStep;
+use crate::prover::ProofTrace;
+use crate::reference_prover::{
+    prepare_program, prove_program_reference, reference_derivation_steps, reference_eval_formula,
+    reference_prove_program_results, reference_solve_facts,
+};
+use crate::types::Formula;
+
+pub use crate::reference_prover::{
+    FunctionValue, ReferenceDerivedFacts as DerivedFacts, ReferenceEnv as Env,
+    ReferenceObligationResult as ObligationResult, ReferenceValue as Value,
+    check_derivation_parity, reference_value_to_string as value_to_string,
+};
+
+pub fn solve_facts(program: &Program) -> Result<DerivedFacts, Vec<Diagnostic>> {
+    reference_solve_facts(&prepare_program(program)?)
+}
+
+pub fn derivation_steps(program: &Program) -> Result<Vec<DerivationStep>, Vec<Diagnostic>> {
+    reference_derivation_steps(program)
+}
+
+pub fn prove(program: &Program) -> Result<Vec<ObligationResult>, Vec<Diagnostic>> {
+    reference_prove_program_results(&prepare_program(program)?)
+}
+
+pub fn prove_trace(program: &Program) -> Result<ProofTrace, Vec<Diagnostic>> {
+    prove_program_reference(program)
+}
+
+pub fn check_assert(program: &Program, name: &str) -> Result<ObligationResult, Vec<Diagnostic>> {
+    obligation(program, &format!("assert::{name}"))
+}
+
+pub fn check_refine(program: &Program, name: &str) -> Result<ObligationResult, Vec<Diagnostic>> {
+    obligation(program, &format!("defn::{name}"))
+}
+
+pub fn eval_formula(formula: &Formula, env: &Env, facts: &DerivedFacts) -> Result<bool, String> {
+    reference_eval_formula(formula, env, facts)
+}
+
+fn obligation(program: &Program, id: &str) -> Result<ObligationResult, Vec<Diagnostic>> {
+    prove(program)?
+        .into_iter()
+        .find(|result| result.id == id)
+        .ok_or_else(|| {
+            vec![Diagnostic::new(
+                "E-RESOLVE",
+                format!("unknown obligation: {id}"),
+                None,
+            )]
+        })
+}
@@ -1,15 +1,22 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
 
-use crate::ast::{Defn, Expr, Param, Pattern, Program};
+use crate::ast::{
+    Defn, Expr, ExprArena, ExprId, LocalFn, Param, Pattern, PatternId, Program, Rule,
+};
 use crate::diagnostics::Diagnostic;
-use crate::logic_engine::{GroundFact, Value};
+use crate::logic_engine::{
+    DerivationStep, GroundFact, KnowledgeBase, SKOLEM_CTOR_PREFIX, Value,
+    solve_facts_with_derivations,
+};
 use crate::name_resolve::{normalize_program_aliases, resolve_program};
 use crate::prover::{
     ClaimCoverage, CounterexampleTrace, NameValue, ObligationTrace, PROOF_TRACE_SCHEMA_VERSION,
     ProofSummary, ProofTrace,
 };
 use crate::stratify::compute_strata;
-use crate::typecheck::check_program;
+use crate::symbol::Symbol;
+use crate::typecheck::{check_program, recursive_groups};
 use crate::types::{Atom, Formula, LogicTerm, Type};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -82,7 +89,8 @@ struct ReferenceObligation<'a> {
 #[derive(Debug, Clone)]
 enum ReferenceObligationBody<'a> {
     Assert,
-    Refine { expr: &'a Expr, result_var: String },
+    Refine { expr: ExprId, result_var: String },
+    Measure { defn: &'a Defn },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -99,24 +107,44 @@ struct ReferenceCallKey {
 
 struct ReferenceEvalState<'a> {
     derived: &'a ReferenceDerivedFacts,
+    exprs: &'a ExprArena,
     relation_names: &'a HashSet<String>,
     constructor_names: &'a HashSet<String>,
     defn_map: &'a HashMap<String, &'a Defn>,
+    recursive_groups: &'a HashMap<String, usize>,
     cache: HashMap<ReferenceCallKey, ReferenceExprResult>,
     active_calls: HashSet<ReferenceCallKey>,
+    call_stack: Vec<(String, Option<i64>)>,
+    measure_violation: Option<(usize, i64, i64)>,
+    local_fns: Vec<Rc<(LocalFn, ReferenceEnv)>>,
 }
 
+static MEASURE_GOAL: Formula = Formula::True;
+
 const MAX_FUNCTION_MODEL_VALUES: usize = 4096;
 
 pub fn prove_program_reference(program: &Program) -> Result<ProofTrace, Vec<Diagnostic>> {
     let normalized = prepare_program(program)?;
     let results = reference_prove_program_results(&normalized)?;
     let derived = reference_solve_facts(&normalized)?;
+    let messages = normalized
+        .asserts
+        .iter()
+        .filter_map(|assertion| {
+            let message = assertion.message.clone()?;
+            Some((format!("assert::{}", assertion.name), message))
+        })
+        .collect::<HashMap<_, _>>();
 
     let obligations = results
         .into_iter()
         .map(|result| {
             let failed = result.result == "failed";
+            let message = if failed {
+                messages.get(&result.id).cloned()
+            } else {
+                None
+            };
             ObligationTrace {
                 id: result.id,
                 kind: result.kind,
@@ -137,15 +165,20 @@ pub fn prove_program_reference(program: &Program) -> Result<ProofTrace, Vec<Diag
                 } else {
                     None
                 },
+                message,
             }
         })
         .collect::<Vec<_>>();
+    for obligation in &obligations {
+        tracing::info!(id = %obligation.id, result = %obligation.result, "obligation checked");
+    }
 
     let proved = obligations
         .iter()
         .filter(|item| item.result == "proved")
         .count();
     let total = obligations.len();
+    tracing::info!(total, proved, failed = total - proved, "proof finished");
     Ok(ProofTrace {
         schema_version: PROOF_TRACE_SCHEMA_VERSION.to_string(),
         profile: "standard".to_string(),
@@ -158,20 +191,59 @@ pub fn prove_program_reference(program: &Program) -> Result<ProofTrace, Vec<Diag
         claim_coverage: ClaimCoverage {
             total_claims: total,
             proved_claims: proved,
+            policy: None,
         },
         obligations,
+        derivations: None,
     })
 }
 
 pub fn reference_solve_facts(program: &Program) -> Result<ReferenceDerivedFacts, Vec<Diagnostic>> {
-    reference_solve_facts_with_extra(program, &[])
+    reference_solve_facts_with_extra(program, &[], None)
+}
+
+pub fn reference_derivation_steps(
+    program: &Program,
+) -> Result<Vec<DerivationStep>, Vec<Diagnostic>> {
+    reference_steps(&prepare_program(program)?)
+}
+
+fn reference_steps(normalized: &Program) -> Result<Vec<DerivationStep>, Vec<Diagnostic>> {
+    let mut steps = Vec::new();
+    reference_solve_facts_with_extra(normalized, &[], Some(&mut steps))?;
+    steps.sort();
+    Ok(steps)
+}
+
+pub fn check_derivation_parity(program: &Program) -> Result<Vec<DerivationStep>, Vec<Diagnostic>> {
+    let normalized = prepare_program(program)?;
+    let kb = KnowledgeBase::from_program(&normalized)?;
+    let (_, native) = solve_facts_with_derivations(&kb)?;
+    let reference = reference_steps(&normalized)?;
+    let describe = |step: Option<&DerivationStep>| {
+        step.map_or_else(|| "no step".to_string(), ToString::to_string)
+    };
+    let length = native.len().max(reference.len());
+    if let Some(index) = (0..length).find(|&i| native.get(i) != reference.get(i)) {
+        return Err(vec![Diagnostic::new(
+            "E-PROVE",
+            format!(
+                "native and reference derivations differ at step {index}: native {}, reference {}",
+                describe(native.get(index)),
+                describe(reference.get(index))
+            ),
+            None,
+        )]);
+    }
+    Ok(native)
 }
 
 pub fn reference_prove_program_results(
     program: &Program,
 ) -> Result<Vec<ReferenceObligationResult>, Vec<Diagnostic>> {
     let derived = reference_solve_facts(program)?;
-    let universe_map = build_universe_map(program).map_err(as_prove_error)?;
+    let mut universe_map = build_universe_map(program).map_err(as_prove_error)?;
+    add_skolem_witnesses(&mut universe_map, program, &derived);
     let relation_names = program
         .relations
         .iter()
@@ -187,6 +259,20 @@ pub fn reference_prove_program_results(
         .iter()
         .map(|defn| (defn.name.clone(), defn))
         .collect::<HashMap<_, _>>();
+    let recursive_groups = recursive_groups(program);
+    let new_state = || ReferenceEvalState {
+        derived: &derived,
+        exprs: &program.exprs,
+        relation_names: &relation_names,
+        constructor_names: &constructor_names,
+        defn_map: &defn_map,
+        recursive_groups: &recursive_groups,
+        cache: HashMap::new(),
+        active_calls: HashSet::new(),
+        call_stack: Vec::new(),
+        measure_violation: None,
+        local_fns: Vec::new(),
+    };
 
     let mut out = Vec::new();
     for obligation in build_obligations(program) {
@@ -216,20 +302,36 @@ pub fn reference_prove_program_results(
                         })
                     }
                 }
+                ReferenceObligationBody::Measure { defn } => {
+                    match reference_measure_violation(defn, &valuation, &mut new_state())
+                        .map_err(as_prove_error)?
+                    {
+                        None => None,
+                        Some((callee_measure, caller_measure)) => {
+                            let goal = Formula::And(vec![
+                                reference_comparison("<=", 0, callee_measure),
+                                reference_comparison("<", callee_measure, caller_measure),
+                            ]);
+                            Some(ReferenceObligationResult {
+                                id: obligation.id.clone(),
+                                kind: obligation.kind.clone(),
+                                result: "failed".to_string(),
+                                valuation: valuation.clone(),
+                                premises: BTreeSet::new(),
+                                missing_goals: collect_missing_goals(&goal, &valuation, &derived)
+                                    .map_err(as_prove_error)?,
+                            })
+                        }
+                    }
+                }
                 ReferenceObligationBody::Refine { expr, result_var } => {
-                    let expr_result = reference_eval_expr_with_context(
-                        expr,
-                        &valuation,
-                        &derived,
-                        &defn_map,
-                        &relation_names,
-                        &constructor_names,
-                    )
-                    .map_err(as_prove_error)?;
+                    let expr_result =
+                        reference_eval_expr_inner(*expr, &valuation, &mut new_state())
+                            .map_err(as_prove_error)?;
                     let mut goal_env = valuation.clone();
                     goal_env.insert(result_var.clone(), expr_result.value.clone());
                     match expr_result.value {
-                        ReferenceValue::Bool(true) => {
+                        ReferenceValue::Bool(true) | ReferenceValue::Int(_) => {
                             if reference_eval_formula(obligation.goal, &goal_env, &derived)
                                 .map_err(as_prove_error)?
                             {
@@ -298,11 +400,7 @@ pub fn reference_eval_formula(
         Formula::True => Ok(true),
         Formula::Atom(atom) => {
             let tuple = instantiate_logic_terms(&atom.terms, env)?;
-            Ok(derived
-                .facts
-                .get(&atom.pred)
-                .map(|tuples| tuples.contains(&tuple))
-                .unwrap_or(false))
+            Ok(reference_atom_holds(&atom.pred, &tuple, derived))
         }
         Formula::And(items) => {
             for item in items {
@@ -316,6 +414,43 @@ pub fn reference_eval_formula(
     }
 }
 
+fn reference_atom_holds(
+    pred: &str,
+    tuple: &[ReferenceValue],
+    derived: &ReferenceDerivedFacts,
+) -> bool {
+    let compare: fn(&i64, &i64) -> bool = match pred {
+        "!=" => return matches!(tuple, [lhs, rhs] if lhs != rhs),
+        "<" => i64::lt,
+        "<=" => i64::le,
+        ">" => i64::gt,
+        ">=" => i64::ge,
+        _ => {
+            return derived
+                .facts
+                .get(pred)
+                .map(|tuples| tuples.contains(tuple))
+                .unwrap_or(false);
+        }
+    };
+    matches!(tuple, [ReferenceValue::Int(lhs), ReferenceValue::Int(rhs)] if compare(lhs, rhs))
+}
+
+fn reference_arith(name: &str, args: &[ReferenceValue]) -> Option<Result<ReferenceValue, String>> {
+    let apply: fn(i64, i64) -> Option<i64> = match name {
+        "+" => i64::checked_add,
+        "-" => i64::checked_sub,
+        "*" => i64::checked_mul,
+        _ => return None,
+    };
+    Some(match args {
+        [ReferenceValue::Int(lhs), ReferenceValue::Int(rhs)] => apply(*lhs, *rhs)
+            .map(ReferenceValue::Int)
+            .ok_or_else(|| format!("integer overflow in ({name} {lhs} {rhs})")),
+        _ => Err(format!("operator {name} expects two Int values")),
+    })
+}
+
 pub fn reference_value_to_string(value: &ReferenceValue) -> String {
     match value {
         ReferenceValue::Symbol(symbol) => symbol.clone(),
@@ -351,14 +486,14 @@ pub fn reference_value_to_string(value: &ReferenceValue) -> String {
     }
 }
 
-fn prepare_program(program: &Program) -> Result<Program, Vec<Diagnostic>> {
+pub(crate) fn prepare_program(program: &Program) -> Result<Program, Vec<Diagnostic>> {
     let normalized = normalize_program_aliases(program)?;
     let mut errors = resolve_program(&normalized);
     if !errors.is_empty() {
         return Err(errors);
     }
-    if let Err(mut e) = compute_strata(&normalized) {
-        errors.append(&mut e);
+    if let Err(e) = compute_strata(&normalized) {
+        errors.extend(e.diagnostics);
         return Err(errors);
     }
     if let Err(mut e) = check_program(&normalized) {
@@ -371,13 +506,14 @@ fn prepare_program(program: &Program) -> Result<Program, Vec<Diagnostic>> {
 fn reference_solve_facts_with_extra(
     program: &Program,
     extra: &[GroundFact],
+    mut steps: Option<&mut Vec<DerivationStep>>,
 ) -> Result<ReferenceDerivedFacts, Vec<Diagnostic>> {
     let relation_schemas = program
         .relations
         .iter()
         .map(|rel| (rel.name.clone(), rel.arg_sorts.len()))
         .collect::<HashMap<_, _>>();
-    let strata = compute_strata(program)?;
+    let strata = compute_strata(program)?.levels;
 
     let mut db = program
         .relations
@@ -437,18 +573,30 @@ fn reference_solve_facts_with_extra(
         let rules = program
             .rules
             .iter()
-            .filter(|rule| strata.get(&rule.head.pred).copied().unwrap_or(0) == stratum)
+            .enumerate()
+            .filter(|(_, rule)| strata.get(&rule.head.pred).copied().unwrap_or(0) == stratum)
             .collect::<Vec<_>>();
         let mut changed = true;
+        let mut iteration = 0;
         while changed {
             changed = false;
-            for rule in &rules {
-                let produced =
-                    reference_apply_rule(&rule.head, &rule.body, &db).map_err(as_resolve_error)?;
+            iteration += 1;
+            for (index, rule) in &rules {
+                let produced = reference_apply_rule(rule, &db).map_err(as_resolve_error)?;
                 let target = db.entry(rule.head.pred.clone()).or_default();
-                for tuple in produced {
-                    if target.insert(tuple) {
-                        changed = true;
+                for (tuple, premises) in produced {
+                    if !target.insert(tuple.clone()) {
+                        continue;
+                    }
+                    changed = true;
+                    if let Some(steps) = steps.as_deref_mut() {
+                        steps.push(DerivationStep {
+                            stratum,
+                            iteration,
+                            rule: *index,
+                            fact: reference_ground_fact_key(&rule.head.pred, &tuple),
+                            premises: premises.into_iter().collect(),
+                        });
                     }
                 }
             }
@@ -458,31 +606,54 @@ fn reference_solve_facts_with_extra(
     Ok(ReferenceDerivedFacts { facts: db })
 }
 
-fn reference_eval_expr_with_context(
-    expr: &Expr,
-    env: &ReferenceEnv,
-    derived: &ReferenceDerivedFacts,
-    defn_map: &HashMap<String, &Defn>,
-    relation_names: &HashSet<String>,
-    constructor_names: &HashSet<String>,
-) -> Result<ReferenceExprResult, String> {
-    let mut state = ReferenceEvalState {
-        derived,
-        relation_names,
-        constructor_names,
-        defn_map,
-        cache: HashMap::new(),
-        active_calls: HashSet::new(),
+fn reference_measure_violation(
+    defn: &Defn,
+    valuation: &ReferenceEnv,
+    state: &mut ReferenceEvalState<'_>,
+) -> Result<Option<(i64, i64)>, String> {
+    let measure = reference_measure(defn, valuation, state)?;
+    state.call_stack.push((defn.name.clone(), measure));
+    match reference_eval_expr_inner(defn.body, valuation, state) {
+        Ok(_) => Ok(None),
+        Err(message) => match state.measure_violation {
+            Some((1, callee_measure, caller_measure)) => Ok(Some((callee_measure, caller_measure))),
+            _ => Err(message),
+        },
+    }
+}
+
+fn reference_measure(
+    defn: &Defn,
+    call_env: &ReferenceEnv,
+    state: &mut ReferenceEvalState<'_>,
+) -> Result<Option<i64>, String> {
+    let Some(measure) = defn.measure else {
+        return Ok(None);
     };
-    reference_eval_expr_inner(expr, env, &mut state)
+    match reference_eval_expr_inner(measure, call_env, state)?.value {
+        ReferenceValue::Int(value) => Ok(Some(value)),
+        other => Err(format!(
+            "termination measure of {} did not evaluate to Int: {}",
+            defn.name,
+            reference_value_to_string(&other)
+        )),
+    }
+}
+
+fn reference_comparison(pred: &str, lhs: i64, rhs: i64) -> Formula {
+    Formula::Atom(Atom {
+        pred: pred.to_string(),
+        terms: vec![LogicTerm::Int(lhs), LogicTerm::Int(rhs)],
+    })
 }
 
 fn reference_eval_expr_inner(
-    expr: &Expr,
+    expr: ExprId,
     env: &ReferenceEnv,
     state: &mut ReferenceEvalState<'_>,
 ) -> Result<ReferenceExprResult, String> {
-    match expr {
+    let exprs = state.exprs;
+    match &exprs[expr] {
         Expr::Var { name, .. } => env
             .get(name)
             .cloned()
@@ -503,11 +674,12 @@ fn reference_eval_expr_inner(
             value: ReferenceValue::Bool(*value),
             positive_facts: BTreeSet::new(),
         }),
+        Expr::Hole { .. } => Err("typed hole cannot be evaluated".to_string()),
         Expr::Call { name, args, .. } => {
             let mut arg_values = Vec::new();
             let mut positive_facts = BTreeSet::new();
             for arg in args {
-                let result = reference_eval_expr_inner(arg, env, state)?;
+                let result = reference_eval_expr_inner(*arg, env, state)?;
                 positive_facts.extend(result.positive_facts);
                 arg_values.push(result.value);
             }
@@ -522,6 +694,29 @@ fn reference_eval_expr_inner(
                 });
             }
 
+            if let Some(local) = state.local_fns.iter().rev().find(|f| f.0.name == *name) {
+                let local = Rc::clone(local);
+                let (def, local_env) = local.as_ref();
+                if def.params.len() != arg_values.len() {
+                    return Err(format!(
+                        "arity mismatch during expression evaluation: {} expected {}, got {}",
+                        name,
+                        def.params.len(),
+                        arg_values.len()
+                    ));
+                }
+                let mut call_env = local_env.clone();
+                for (param, value) in def.params.iter().zip(arg_values) {
+                    call_env.insert(param.name.clone(), value);
+                }
+                let result = reference_eval_expr_inner(def.body, &call_env, state)?;
+                positive_facts.extend(result.positive_facts);
+                return Ok(ReferenceExprResult {
+                    value: result.value,
+                    positive_facts,
+                });
+            }
+
             if state.relation_names.contains(name) {
                 let truth = state
                     .derived
@@ -548,6 +743,13 @@ fn reference_eval_expr_inner(
                 });
             }
 
+            if let Some(value) = reference_arith(name, &arg_values) {
+                return Ok(ReferenceExprResult {
+                    value: value?,
+                    positive_facts,
+                });
+            }
+
             let Some(defn) = state.defn_map.get(name) else {
                 return Err(format!(
                     "unknown call target during expression evaluation: {name}"
@@ -562,6 +764,28 @@ fn reference_eval_expr_inner(
                 ));
             }
 
+            let mut call_env = ReferenceEnv::new();
+            for (param, value) in defn.params.iter().zip(arg_values.iter()) {
+                call_env.insert(param.name.clone(), value.clone());
+            }
+            let measure = reference_measure(defn, &call_env, state)?;
+            if let (Some(callee_measure), Some((caller, Some(caller_measure)))) =
+                (measure, state.call_stack.last())
+            {
+                let same_group = state
+                    .recursive_groups
+                    .get(caller)
+                    .is_some_and(|group| state.recursive_groups.get(name) == Some(group));
+                if same_group && !(callee_measure >= 0 && callee_measure < *caller_measure) {
+                    let message = format!(
+                        "termination measure does not decrease in {caller} -> {name}: {caller_measure} -> {callee_measure}"
+                    );
+                    state.measure_violation =
+                        Some((state.call_stack.len(), callee_measure, *caller_measure));
+                    return Err(message);
+                }
+            }
+
             let key = ReferenceCallKey {
                 name: name.clone(),
                 args: arg_values.clone(),
@@ -578,11 +802,9 @@ fn reference_eval_expr_inner(
                 return Err(format!("recursive evaluation cycle detected in {name}"));
             }
 
-            let mut call_env = ReferenceEnv::new();
-            for (param, value) in defn.params.iter().zip(arg_values.iter()) {
-                call_env.insert(param.name.clone(), value.clone());
-            }
-            let result = reference_eval_expr_inner(&defn.body, &call_env, state)?;
+            state.call_stack.push((name.clone(), measure));
+            let result = reference_eval_expr_inner(defn.body, &call_env, state)?;
+            state.call_stack.pop();
             state.active_calls.remove(&key);
             state.cache.insert(key, result.clone());
 
@@ -597,27 +819,36 @@ fn reference_eval_expr_inner(
             let mut local_env = env.clone();
             let mut positive_facts = BTreeSet::new();
             for (name, bound_expr, _) in bindings {
-                let result = reference_eval_expr_inner(bound_expr, &local_env, state)?;
+                let result = reference_eval_expr_inner(*bound_expr, &local_env, state)?;
                 positive_facts.extend(result.positive_facts);
                 local_env.insert(name.clone(), result.value);
             }
-            let result = reference_eval_expr_inner(body, &local_env, state)?;
+            let result = reference_eval_expr_inner(*body, &local_env, state)?;
             positive_facts.extend(result.positive_facts.clone());
             Ok(ReferenceExprResult {
                 value: result.value,
                 positive_facts,
             })
         }
+        Expr::LetFn { fns, body, .. } => {
+            let depth = state.local_fns.len();
+            for f in fns {
+                state.local_fns.push(Rc::new((f.clone(), env.clone())));
+            }
+            let result = reference_eval_expr_inner(*body, env, state);
+            state.local_fns.truncate(depth);
+            result
+        }
         Expr::If {
             cond,
             then_branch,
             else_branch,
             ..
         } => {
-            let cond_result = reference_eval_expr_inner(cond, env, state)?;
+            let cond_result = reference_eval_expr_inner(*cond, env, state)?;
             match cond_result.value {
                 ReferenceValue::Bool(true) => {
-                    let branch = reference_eval_expr_inner(then_branch, env, state)?;
+                    let branch = reference_eval_expr_inner(*then_branch, env, state)?;
                     let mut positive_facts = cond_result.positive_facts;
                     positive_facts.extend(branch.positive_facts.clone());
                     Ok(ReferenceExprResult {
@@ -626,7 +857,7 @@ fn reference_eval_expr_inner(
                     })
                 }
                 ReferenceValue::Bool(false) => {
-                    let branch = reference_eval_expr_inner(else_branch, env, state)?;
+                    let branch = reference_eval_expr_inner(*else_branch, env, state)?;
                     let mut positive_facts = cond_result.positive_facts;
                     positive_facts.extend(branch.positive_facts.clone());
                     Ok(ReferenceExprResult {
@@ -643,11 +874,11 @@ fn reference_eval_expr_inner(
         Expr::Match {
             scrutinee, arms, ..
         } => {
-            let scrutinee_result = reference_eval_expr_inner(scrutinee, env, state)?;
+            let scrutinee_result = reference_eval_expr_inner(*scrutinee, env, state)?;
             for arm in arms {
                 let mut branch_env = env.clone();
-                if bind_pattern(&arm.pattern, &scrutinee_result.value, &mut branch_env) {
-                    let branch = reference_eval_expr_inner(&arm.body, &branch_env, state)?;
+                if bind_pattern(exprs, arm.pattern, &scrutinee_result.value, &mut branch_env) {
+                    let branch = reference_eval_expr_inner(arm.body, &branch_env, state)?;
                     let mut positive_facts = scrutinee_result.positive_facts.clone();
                     positive_facts.extend(branch.positive_facts.clone());
                     return Ok(ReferenceExprResult {
@@ -664,6 +895,15 @@ fn reference_eval_expr_inner(
 fn build_obligations(program: &Program) -> Vec<ReferenceObligation<'_>> {
     let mut obligations = Vec::new();
     for defn in &program.defns {
+        if defn.measure.is_some() {
+            obligations.push(ReferenceObligation {
+                id: format!("measure::{}", defn.name),
+                kind: "measure".to_string(),
+                goal: &MEASURE_GOAL,
+                params: &defn.params,
+                body: ReferenceObligationBody::Measure { defn },
+            });
+        }
         if let Type::Refine { var, formula, .. } = &defn.ret_type {
             obligations.push(ReferenceObligation {
                 id: format!("defn::{}", defn.name),
@@ -671,7 +911,7 @@ fn build_obligations(program: &Program) -> Vec<ReferenceObligation<'_>> {
                 goal: formula,
                 params: &defn.params,
                 body: ReferenceObligationBody::Refine {
-                    expr: &defn.body,
+                    expr: defn.body,
                     result_var: var.clone(),
                 },
             });
@@ -703,6 +943,33 @@ fn build_universe_map(program: &Program) -> Result<BTreeMap<String, Vec<Value>>,
     Ok(map)
 }
 
+fn add_skolem_witnesses(
+    universe_map: &mut BTreeMap<String, Vec<Value>>,
+    program: &Program,
+    derived: &ReferenceDerivedFacts,
+) {
+    let mut relations = program.relations.iter().collect::<Vec<_>>();
+    relations.sort_by(|a, b| a.name.cmp(&b.name));
+    for relation in relations {
+        for tuple in derived.facts.get(&relation.name).into_iter().flatten() {
+            for (sort, value) in relation.arg_sorts.iter().zip(tuple) {
+                let ReferenceValue::Adt { ctor, .. } = value else {
+                    continue;
+                };
+                if !ctor.starts_with(SKOLEM_CTOR_PREFIX) {
+                    continue;
+                }
+                if let Some(values) = universe_map.get_mut(sort)
+                    && let Some(value) = reference_to_concrete(value)
+                    && !values.contains(&value)
+                {
+                    values.push(value);
+                }
+            }
+        }
+    }
+}
+
 fn enumerate_valuations(
     params: &[Param],
     universe_map: &BTreeMap<String, Vec<Value>>,
@@ -763,6 +1030,7 @@ fn enumerate_reference_values_for_type(
             .map(concrete_to_reference)
             .collect(),
         Type::Fun(args, ret) => enumerate_function_values(args, ret, universe, cache)?,
+        Type::Error => return Err("quantified variable has no type".to_string()),
         Type::Refine { .. } => unreachable!(),
     };
     cache.insert(normalized, values.clone());
@@ -875,22 +1143,24 @@ fn increment_digits(digits: &mut [usize], base: usize) -> bool {
 }
 
 fn reference_apply_rule(
-    head: &Atom,
-    body: &Formula,
+    rule: &Rule,
     db: &BTreeMap<String, BTreeSet<Vec<ReferenceValue>>>,
-) -> Result<BTreeSet<Vec<ReferenceValue>>, String> {
+) -> Result<BTreeMap<Vec<ReferenceValue>, BTreeSet<String>>, String> {
+    let head = &rule.head;
     let mut positives = Vec::new();
     let mut negatives = Vec::new();
-    flatten_formula(body, false, &mut positives, &mut negatives);
+    flatten_formula(&rule.body, false, &mut positives, &mut negatives);
 
-    let mut envs = vec![ReferenceEnv::new()];
-    for atom in positives {
+    let mut envs = vec![(ReferenceEnv::new(), BTreeSet::new())];
+    for atom in positives.iter().filter(|atom| atom.pred != "!=") {
         let tuples = db.get(&atom.pred).cloned().unwrap_or_default();
         let mut next = Vec::new();
-        for env in &envs {
+        for (env, premises) in &envs {
             for tuple in &tuples {
                 if let Some(bound) = unify_atom(atom, tuple, env) {
-                    next.push(bound);
+                    let mut premises = premises.clone();
+                    premises.insert(reference_ground_fact_key(&atom.pred, tuple));
+                    next.push((bound, premises));
                 }
             }
         }
@@ -900,22 +1170,99 @@ fn reference_apply_rule(
         }
     }
 
-    for atom in negatives {
+    let disequalities = positives
+        .iter()
+        .map(|atom| (atom, true))
+        .chain(negatives.iter().map(|atom| (atom, false)))
+        .filter(|(atom, _)| atom.pred == "!=");
+    for (atom, distinct) in disequalities {
+        envs.retain(|(env, _)| {
+            matches!(
+                instantiate_logic_terms(&atom.terms, env).as_deref(),
+                Ok([lhs, rhs]) if (lhs != rhs) == distinct
+            )
+        });
+    }
+
+    for atom in negatives.iter().filter(|atom| atom.pred != "!=") {
         let tuples = db.get(&atom.pred).cloned().unwrap_or_default();
-        envs.retain(|env| {
+        envs.retain(|(env, _)| {
             instantiate_logic_terms(&atom.terms, env)
                 .map(|tuple| !tuples.contains(&tuple))
                 .unwrap_or(false)
         });
     }
 
-    let mut produced = BTreeSet::new();
-    for env in &envs {
-        produced.insert(instantiate_logic_terms(&head.terms, env)?);
+    let mut produced = BTreeMap::<_, BTreeSet<String>>::new();
+    for (mut env, premises) in envs {
+        if rule.skolem.is_some() {
+            let mut head_vars = Vec::new();
+            for term in &head.terms {
+                collect_term_vars_in_order(term, &mut head_vars);
+            }
+            let witness_args = head_vars
+                .iter()
+                .filter_map(|var| env.get(var).cloned())
+                .collect::<Vec<_>>();
+            for var in &head_vars {
+                if !env.contains_key(var) {
+                    let witness = ReferenceValue::Adt {
+                        ctor: format!("{SKOLEM_CTOR_PREFIX}{}:{var}", head.pred),
+                        fields: witness_args.clone(),
+                    };
+                    env.insert(var.clone(), witness);
+                }
+            }
+        }
+        let tuple = instantiate_logic_terms(&head.terms, &env)?;
+        if let Some(depth) = rule.skolem
+            && tuple
+                .iter()
+                .any(|value| reference_skolem_depth(value) > depth)
+        {
+            continue;
+        }
+        match produced.get_mut(&tuple) {
+            Some(kept) if premises < *kept => *kept = premises,
+            Some(_) => {}
+            None => {
+                produced.insert(tuple, premises);
+            }
+        }
     }
     Ok(produced)
 }
 
+fn collect_term_vars_in_order(term: &LogicTerm, out: &mut Vec<String>) {
+    match term {
+        LogicTerm::Var(var) => {
+            if !out.contains(var) {
+                out.push(var.clone());
+            }
+        }
+        LogicTerm::Ctor { args, .. } => {
+            for arg in args {
+                collect_term_vars_in_order(arg, out);
+            }
+        }
+        LogicTerm::Symbol(_) | LogicTerm::Int(_) | LogicTerm::Bool(_) => {}
+    }
+}
+
+fn reference_skolem_depth(value: &ReferenceValue) -> usize {
+    match value {
+        ReferenceValue::Adt { ctor, fields } => {
+            let own = if ctor.starts_with(SKOLEM_CTOR_PREFIX) {
+                1
+            } else {
+                0
+            };
+            own + fields.iter().map(reference_skolem_depth).max().unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
 fn flatten_formula<'a>(
     formula: &'a Formula,
     negated: bool,
@@ -1014,8 +1361,13 @@ fn instantiate_logic_term(term: &LogicTerm, env: &ReferenceEnv) -> Result<Refere
     }
 }
 
-fn bind_pattern(pattern: &Pattern, value: &ReferenceValue, env: &mut ReferenceEnv) -> bool {
-    match pattern {
+fn bind_pattern(
+    exprs: &ExprArena,
+    pattern: PatternId,
+    value: &ReferenceValue,
+    env: &mut ReferenceEnv,
+) -> bool {
+    match &exprs[pattern] {
         Pattern::Wildcard { .. } => true,
         Pattern::Var { name, .. } => match env.get(name) {
             Some(bound) => bound == value,
@@ -1042,7 +1394,7 @@ fn bind_pattern(pattern: &Pattern, value: &ReferenceValue, env: &mut ReferenceEn
         Pattern::Ctor { name, args, .. } => match value {
             ReferenceValue::Adt { ctor, fields } if ctor == name && fields.len() == args.len() => {
                 for (arg, field) in args.iter().zip(fields.iter()) {
-                    if !bind_pattern(arg, field, env) {
+                    if !bind_pattern(exprs, *arg, field, env) {
                         return false;
                     }
                 }
@@ -1079,11 +1431,11 @@ fn logic_term_to_reference_const(term: &LogicTerm) -> Option<ReferenceValue> {
 fn logic_term_to_concrete_const(term: &LogicTerm) -> Option<Value> {
     match term {
         LogicTerm::Var(_) => None,
-        LogicTerm::Symbol(symbol) => Some(Value::Symbol(symbol.clone())),
+        LogicTerm::Symbol(symbol) => Some(Value::Symbol(Symbol::intern(symbol))),
         LogicTerm::Int(value) => Some(Value::Int(*value)),
         LogicTerm::Bool(value) => Some(Value::Bool(*value)),
         LogicTerm::Ctor { name, args } => Some(Value::Adt {
-            ctor: name.clone(),
+            ctor: Symbol::intern(name),
             fields: args
                 .iter()
                 .map(logic_term_to_concrete_const)
@@ -1094,16 +1446,32 @@ fn logic_term_to_concrete_const(term: &LogicTerm) -> Option<Value> {
 
 fn concrete_to_reference(value: &Value) -> ReferenceValue {
     match value {
-        Value::Symbol(symbol) => ReferenceValue::Symbol(symbol.clone()),
+        Value::Symbol(symbol) => ReferenceValue::Symbol(symbol.to_string()),
         Value::Int(value) => ReferenceValue::Int(*value),
         Value::Bool(value) => ReferenceValue::Bool(*value),
         Value::Adt { ctor, fields } => ReferenceValue::Adt {
-            ctor: ctor.clone(),
+            ctor: ctor.to_string(),
             fields: fields.iter().map(concrete_to_reference).collect(),
         },
     }
 }
 
+fn reference_to_concrete(value: &ReferenceValue) -> Option<Value> {
+    match value {
+        ReferenceValue::Symbol(symbol) => Some(Value::Symbol(Symbol::intern(symbol))),
+        ReferenceValue::Int(value) => Some(Value::Int(*value)),
+        ReferenceValue::Bool(value) => Some(Value::Bool(*value)),
+        ReferenceValue::Adt { ctor, fields } => Some(Value::Adt {
+            ctor: Symbol::intern(ctor),
+            fields: fields
+                .iter()
+                .map(reference_to_concrete)
+                .collect::<Option<_>>()?,
+        }),
+        ReferenceValue::Function(_) => None,
+    }
+}
+
 fn collect_missing_goals(
     formula: &Formula,
     env: &ReferenceEnv,
@@ -1124,12 +1492,7 @@ fn collect_missing_goals_inner(
         Formula::True => Ok(()),
         Formula::Atom(atom) => {
             let tuple = instantiate_logic_terms(&atom.terms, env)?;
-            let exists = derived
-                .facts
-                .get(&atom.pred)
-                .map(|tuples| tuples.contains(&tuple))
-                .unwrap_or(false);
-            if !exists {
+            if !reference_atom_holds(&atom.pred, &tuple, derived) {
                 out.insert(reference_ground_fact_key(&atom.pred, &tuple));
             }
             Ok(())
@@ -1213,6 +1576,7 @@ fn type_label(ty: &Type) -> String {
         Type::Int => "Int".to_string(),
         Type::Symbol => "Symbol".to_string(),
         Type::Domain(name) | Type::Adt(name) => name.clone(),
+        Type::Error => "?".to_string(),
         Type::Fun(args, ret) => format!(
             "(-> ({}) {})",
             args.iter().map(type_label).collect::<Vec<_>>().join(" "),
@@ -0,0 +1,129 @@
+use crate::ast::{Expr, ExprArena, ExprId, Pattern, PatternId, Program};
+use crate::diagnostics::Span;
+use crate::name_resolve::normalize_program_aliases;
+
+pub fn find_references(program: &Program, symbol: &str) -> Vec<Span> {
+    let canonical = canonical_name(program, symbol);
+    let normalized;
+    let resolved = match normalize_program_aliases(program) {
+        Ok(resolved) => {
+            normalized = resolved;
+            &normalized
+        }
+        Err(_) => program,
+    };
+    let mut refs = References {
+        exprs: &resolved.exprs,
+        symbol: &canonical,
+        spans: Vec::new(),
+    };
+
+    for name_use in &program.name_uses {
+        if canonical_name(program, &name_use.name) == canonical {
+            refs.push(&name_use.span);
+        }
+    }
+    for defn in &resolved.defns {
+        refs.visit_expr(defn.body);
+        if let Some(measure) = defn.measure {
+            refs.visit_expr(measure);
+        }
+    }
+
+    let mut spans = refs.spans;
+    spans.sort_by(|a, b| (&a.file_id, a.start, a.end).cmp(&(&b.file_id, b.start, b.end)));
+    spans.dedup();
+    spans
+}
+
+fn canonical_name(program: &Program, symbol: &str) -> String {
+    let mut name = symbol;
+    for _ in 0..=program.aliases.len() {
+        match program.aliases.iter().find(|alias| alias.alias == name) {
+            Some(alias) => name = &alias.canonical,
+            None => break,
+        }
+    }
+    name.to_string()
+}
+
+struct References<'a> {
+    exprs: &'a ExprArena,
+    symbol: &'a str,
+    spans: Vec<Span>,
+}
+
+impl References<'_> {
+    fn push(&mut self, span: &Span) {
+        self.spans.push(span.clone());
+    }
+
+    fn visit_expr(&mut self, expr: ExprId) {
+        let exprs = self.exprs;
+        match &exprs[expr] {
+            Expr::Var { .. } | Expr::Int { .. } | Expr::Bool { .. } | Expr::Hole { .. } => {}
+            Expr::Symbol { value, span } => {
+                if value == self.symbol {
+                    self.push(span);
+                }
+            }
+            Expr::Call {
+                name,
+                name_span,
+                args,
+                ..
+            } => {
+                if name == self.symbol {
+                    self.push(name_span);
+                }
+                for arg in args {
+                    self.visit_expr(*arg);
+                }
+            }
+            Expr::Let { bindings, body, .. } => {
+                for (_, value, _) in bindings {
+                    self.visit_expr(*value);
+                }
+                self.visit_expr(*body);
+            }
+            Expr::LetFn { fns, body, .. } => {
+                for f in fns {
+                    self.visit_expr(f.body);
+                }
+                self.visit_expr(*body);
+            }
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.visit_expr(*cond);
+                self.visit_expr(*then_branch);
+                self.visit_expr(*else_branch);
+            }
+            Expr::Match {
+                scrutinee, arms, ..
+            } => {
+                self.visit_expr(*scrutinee);
+                for arm in arms {
+                    self.visit_pattern(arm.pattern);
+                    self.visit_expr(arm.body);
+                }
+            }
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: PatternId) {
+        let exprs = self.exprs;
+        match &exprs[pattern] {
+            Pattern::Symbol { value, span } if value == self.symbol => self.push(span),
+            Pattern::Ctor { args, .. } => {
+                for arg in args {
+                    self.visit_pattern(*arg);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -2,9 +2,11 @@ use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fs;
 use std::path::{Component, Path, PathBuf};
 
+use dtl::types::LogicTerm;
 use dtl::{
-    ClaimCoverage, Diagnostic, DocContract, DocModule, DocProject, DocQualityGate, DocReference,
-    DocSelfDescription,
+    ClaimCoverage, Diagnostic, DocCategory, DocContract, DocModule, DocProject, DocQualityGate,
+    DocReference, DocSelfDescription, ObligationTrace, content_hash, parse_program_with_source,
+    quote_atom,
 };
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
@@ -13,6 +15,11 @@ use serde::{Deserialize, Serialize};
 use serde_yaml::Value as YamlValue;
 
 const DEFAULT_CONFIG_FILENAME: &str = ".dtl-selfdoc.toml";
+const GENERATED_FILENAME: &str = "selfdoc.generated.dtl";
+const SCAN_CACHE_FILENAME: &str = "selfdoc-cache.json";
+const SCAN_CACHE_VERSION: u32 = 1;
+const CONTRACT_TABLE_START: &str = "<!-- selfdoc:cli-contracts:start -->";
+const CONTRACT_TABLE_END: &str = "<!-- selfdoc:cli-contracts:end -->";
 
 const CONFIG_TEMPLATE: &str = r#"version = 1
 
@@ -73,6 +80,15 @@ patterns = ["examples/**"]
 [[classify]]
 category = "config"
 patterns = ["Cargo.toml", "Cargo.lock", "rust-toolchain.toml", ".gitignore", ".dtl-selfdoc.toml"]
+
+# 組み込み以外のカテゴリは [[category]] で宣言してから classify で使う。
+# [[category]]
+# name = "proto"
+# description = "gRPC のスキーマ定義"
+
+# selfcheck で未証明のまま許容する claim（`cli::<subcommand>`）。
+# [selfcheck]
+# allow_unproved = ["cli::bench"]
 "#;
 
 #[derive(Debug, Clone)]
@@ -81,6 +97,8 @@ pub struct PreparedSelfdoc {
     pub generated_relative: String,
     pub self_description: DocSelfDescription,
     pub claim_coverage: ClaimCoverage,
+    pub unproved_claims: Vec<String>,
+    pub allow_unproved: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -94,6 +112,10 @@ struct SelfdocConfig {
     version: u32,
     scan: ScanConfig,
     classify: Vec<ClassifyRuleConfig>,
+    #[serde(default, rename = "category")]
+    categories: Vec<CategoryConfig>,
+    #[serde(default)]
+    selfcheck: SelfcheckConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -104,13 +126,26 @@ struct ScanConfig {
     use_gitignore: bool,
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct SelfcheckConfig {
+    #[serde(default)]
+    allow_unproved: Vec<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ClassifyRuleConfig {
     category: String,
     patterns: Vec<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Deserialize)]
+struct CategoryConfig {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 #[serde(rename_all = "snake_case")]
 enum FileCategory {
     Source,
@@ -123,6 +158,7 @@ enum FileCategory {
     Config,
     Asset,
     Other,
+    Custom(String),
 }
 
 impl FileCategory {
@@ -142,7 +178,16 @@ impl FileCategory {
         }
     }
 
-    fn as_str(self) -> &'static str {
+    fn resolve(raw: &str, declared: &[CategoryConfig]) -> Option<Self> {
+        Self::parse(raw).or_else(|| {
+            declared
+                .iter()
+                .any(|category| category.name == raw)
+                .then(|| Self::Custom(raw.to_string()))
+        })
+    }
+
+    fn as_str(&self) -> &str {
         match self {
             Self::Source => "source",
             Self::Test => "test",
@@ -154,6 +199,7 @@ impl FileCategory {
             Self::Config => "config",
             Self::Asset => "asset",
             Self::Other => "other",
+            Self::Custom(name) => name,
         }
     }
 }
@@ -190,7 +236,7 @@ struct SelfdocContract {
     path: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct SelfdocGate {
     name: String,
     command: String,
@@ -198,16 +244,70 @@ struct SelfdocGate {
     required: bool,
 }
 
+#[derive(Debug, Clone, Serialize)]
+struct SelfdocDependency {
+    package: String,
+    name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SelfdocFeature {
+    name: String,
+    enables: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SelfdocDependencyUse {
+    name: String,
+    path: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ManifestFacts {
+    dependencies: Vec<SelfdocDependency>,
+    features: Vec<SelfdocFeature>,
+    dependency_uses: Vec<SelfdocDependencyUse>,
+}
+
 #[derive(Debug)]
 struct PreparedData {
     project: SelfdocProject,
+    categories: Vec<CategoryConfig>,
     modules: Vec<SelfdocModule>,
     references: Vec<SelfdocLink>,
     contracts: Vec<SelfdocContract>,
     quality_gates: Vec<SelfdocGate>,
+    manifest: ManifestFacts,
     extra_exists_paths: Vec<String>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct FileScan {
+    references: Vec<String>,
+    repo_relative: bool,
+    rust_mods: Vec<(String, Option<String>)>,
+    rust_uses: Vec<Vec<String>>,
+    rust_path_roots: Vec<String>,
+    contract_annotations: Vec<String>,
+    gates: Vec<SelfdocGate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedScan {
+    len: u64,
+    modified: Option<(u64, u32)>,
+    hash: String,
+    scan: FileScan,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ScanCache {
+    version: u32,
+    config_hash: String,
+    written: Option<(u64, u32)>,
+    files: BTreeMap<String, CachedScan>,
+}
+
 pub fn default_config_template() -> &'static str {
     CONFIG_TEMPLATE
 }
@@ -217,15 +317,246 @@ pub fn default_config_path(repo: &Path) -> PathBuf {
 }
 
 pub fn prepare_selfdoc(
-    repo: &Path,
+    repos: &[PathBuf],
     config_override: Option<&Path>,
     out_dir: &Path,
     cli_subcommands: &[String],
 ) -> Result<PreparedSelfdoc, PrepareError> {
-    let repo = fs::canonicalize(repo).unwrap_or_else(|_| repo.to_path_buf());
+    let workspace = Workspace::new(repos).map_err(PrepareError::Diagnostics)?;
+
+    let mut merged = RepoExtraction::default();
+    let mut cli_contracts = None;
+    let mut allow_unproved = Vec::new();
+    for (idx, root) in workspace.roots.iter().enumerate() {
+        let primary = idx == 0;
+        let extracted = extract_repository(
+            &workspace,
+            root,
+            config_override,
+            out_dir,
+            cli_subcommands,
+            primary,
+        )?;
+        if primary {
+            cli_contracts = extracted.cli_contracts;
+            allow_unproved = extracted.allow_unproved;
+        }
+        merged.artifacts.extend(extracted.artifacts);
+        merged.references.extend(extracted.references);
+        merged.extra_exists.extend(extracted.extra_exists);
+        merged.gates.extend(extracted.gates);
+        merged
+            .manifest
+            .dependencies
+            .extend(extracted.manifest.dependencies);
+        merged.manifest.features.extend(extracted.manifest.features);
+        merged
+            .manifest
+            .dependency_uses
+            .extend(extracted.manifest.dependency_uses);
+        for category in extracted.categories {
+            if !merged.categories.iter().any(|c| c.name == category.name) {
+                merged.categories.push(category);
+            }
+        }
+    }
+    let cli_contracts = cli_contracts.expect("primary repository extracts CLI contracts");
+
+    merged.artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+    merged
+        .references
+        .sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+    merged.extra_exists.sort();
+    merged.extra_exists.dedup();
+    merged.gates.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut data = build_prepared_data(
+        &workspace.roots[0].path,
+        &merged.artifacts,
+        &merged.references,
+        &merged.extra_exists,
+        &cli_contracts.contracts,
+        &merged.gates,
+        merged.manifest,
+    );
+    data.categories = merged.categories;
+
+    let rendered = render_selfdoc_program(&data);
+    fs::create_dir_all(out_dir).map_err(|err| {
+        PrepareError::Diagnostics(vec![diag(
+            "E-IO",
+            format!("出力ディレクトリを作成できません: {err}"),
+            Some(out_dir.display().to_string()),
+        )])
+    })?;
+
+    let generated_file = out_dir.join(GENERATED_FILENAME);
+    fs::write(&generated_file, rendered.as_bytes()).map_err(|err| {
+        PrepareError::Diagnostics(vec![diag(
+            "E-IO",
+            format!("自己記述 DSL を書き込めません: {err}"),
+            Some(generated_file.display().to_string()),
+        )])
+    })?;
+
+    Ok(PreparedSelfdoc {
+        generated_file,
+        generated_relative: GENERATED_FILENAME.to_string(),
+        self_description: DocSelfDescription {
+            project: Some(DocProject {
+                name: data.project.name,
+                summary: data.project.summary,
+            }),
+            categories: data
+                .categories
+                .iter()
+                .map(|c| DocCategory {
+                    name: c.name.clone(),
+                    description: c.description.clone(),
+                })
+                .collect(),
+            modules: data
+                .modules
+                .iter()
+                .map(|m| DocModule {
+                    name: m.name.clone(),
+                    path: m.path.clone(),
+                    category: m.category.clone(),
+                })
+                .collect(),
+            references: data
+                .references
+                .iter()
+                .map(|r| DocReference {
+                    from: r.from.clone(),
+                    to: r.to.clone(),
+                })
+                .collect(),
+            contracts: data
+                .contracts
+                .iter()
+                .map(|c| DocContract {
+                    name: c.name.clone(),
+                    source: c.source.clone(),
+                    path: c.path.clone(),
+                })
+                .collect(),
+            quality_gates: data
+                .quality_gates
+                .iter()
+                .map(|g| DocQualityGate {
+                    name: g.name.clone(),
+                    command: g.command.clone(),
+                    source: g.source.clone(),
+                    required: g.required,
+                })
+                .collect(),
+        },
+        claim_coverage: ClaimCoverage {
+            total_claims: cli_contracts.total_claims,
+            proved_claims: cli_contracts.proved_claims,
+            policy: None,
+        },
+        unproved_claims: cli_contracts.unproved_claims,
+        allow_unproved,
+    })
+}
+
+struct Workspace {
+    roots: Vec<WorkspaceRoot>,
+}
+
+struct WorkspaceRoot {
+    namespace: String,
+    path: PathBuf,
+}
+
+impl Workspace {
+    fn new(repos: &[PathBuf]) -> Result<Self, Vec<Diagnostic>> {
+        let mut roots: Vec<WorkspaceRoot> = Vec::new();
+        let mut errors = Vec::new();
+        for repo in repos {
+            let path = fs::canonicalize(repo).unwrap_or_else(|_| repo.clone());
+            let namespace = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("project")
+                .to_string();
+            if roots.iter().any(|root| root.namespace == namespace) {
+                errors.push(diag(
+                    "E-SELFDOC-CONFIG",
+                    format!(
+                        "名前空間（ディレクトリ名）が重複するリポジトリがあります: {namespace}"
+                    ),
+                    Some(repo.display().to_string()),
+                ));
+                continue;
+            }
+            roots.push(WorkspaceRoot { namespace, path });
+        }
+        if errors.is_empty() {
+            Ok(Self { roots })
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn is_merged(&self) -> bool {
+        self.roots.len() > 1
+    }
+
+    fn qualify(&self, root: &WorkspaceRoot, path: &str) -> String {
+        if self.is_merged() {
+            format!("{}/{path}", root.namespace)
+        } else {
+            path.to_string()
+        }
+    }
+
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        if !self.is_merged() {
+            return Some(self.roots[0].path.join(path));
+        }
+        let (namespace, rest) = path.split_once('/').unwrap_or((path, ""));
+        self.roots
+            .iter()
+            .find(|root| root.namespace == namespace)
+            .map(|root| root.path.join(rest))
+    }
+
+    fn cache_file(&self, root: &WorkspaceRoot, out_dir: &Path) -> PathBuf {
+        if self.is_merged() {
+            out_dir.join(format!("selfdoc-cache.{}.json", root.namespace))
+        } else {
+            out_dir.join(SCAN_CACHE_FILENAME)
+        }
+    }
+}
+
+#[derive(Default)]
+struct RepoExtraction {
+    artifacts: Vec<Artifact>,
+    references: Vec<SelfdocLink>,
+    extra_exists: Vec<String>,
+    cli_contracts: Option<CliContractExtraction>,
+    gates: Vec<SelfdocGate>,
+    manifest: ManifestFacts,
+    categories: Vec<CategoryConfig>,
+    allow_unproved: Vec<String>,
+}
+
+fn extract_repository(
+    workspace: &Workspace,
+    root: &WorkspaceRoot,
+    config_override: Option<&Path>,
+    out_dir: &Path,
+    cli_subcommands: &[String],
+    primary: bool,
+) -> Result<RepoExtraction, PrepareError> {
+    let repo = root.path.as_path();
     let config_path = config_override
         .map(PathBuf::from)
-        .unwrap_or_else(|| default_config_path(&repo));
+        .unwrap_or_else(|| default_config_path(repo));
 
     if !config_path.exists() {
         return Err(PrepareError::MissingConfig {
@@ -251,6 +582,20 @@ pub fn prepare_selfdoc(
     })?;
 
     let mut errors = validate_config(&config, &config_path);
+    for claim in &config.selfcheck.allow_unproved {
+        let known = claim
+            .strip_prefix("cli::")
+            .is_some_and(|name| cli_subcommands.iter().any(|sub| sub == name));
+        if !known {
+            errors.push(diag(
+                "E-SELFDOC-CONFIG",
+                format!(
+                    "selfcheck.allow_unproved に未知の claim があります: {claim}（`cli::<subcommand>` 形式で指定してください）"
+                ),
+                Some(config_path.display().to_string()),
+            ));
+        }
+    }
     if !errors.is_empty() {
         return Err(PrepareError::Diagnostics(errors));
     }
@@ -259,16 +604,16 @@ pub fn prepare_selfdoc(
         .map_err(PrepareError::Diagnostics)?;
     let exclude = compile_globset(&config.scan.exclude, "E-SELFDOC-CONFIG", &config_path)
         .map_err(PrepareError::Diagnostics)?;
-    let classify_rules = compile_classify_rules(&config.classify, &config_path)
-        .map_err(PrepareError::Diagnostics)?;
+    let classify_rules =
+        compile_classify_rules(&config, &config_path).map_err(PrepareError::Diagnostics)?;
 
     let gitignore = if config.scan.use_gitignore {
-        Some(build_gitignore(&repo, &config_path).map_err(PrepareError::Diagnostics)?)
+        Some(build_gitignore(repo, &config_path).map_err(PrepareError::Diagnostics)?)
     } else {
         None
     };
 
-    let paths = scan_paths(&repo, &include, &exclude, gitignore.as_ref());
+    let paths = scan_paths(repo, &include, &exclude, gitignore.as_ref());
     if paths.is_empty() {
         return Err(PrepareError::Diagnostics(vec![diag(
             "E-SELFDOC-SCAN",
@@ -282,7 +627,7 @@ pub fn prepare_selfdoc(
         let mut matched = HashSet::new();
         for (category, matcher) in &classify_rules {
             if matcher.is_match(&path) {
-                matched.insert(*category);
+                matched.insert(category.clone());
             }
         }
         if matched.len() != 1 {
@@ -300,7 +645,7 @@ pub fn prepare_selfdoc(
             ));
             continue;
         }
-        let category = *matched.iter().next().expect("single match");
+        let category = matched.into_iter().next().expect("single match");
         artifacts.push(Artifact { path, category });
     }
 
@@ -310,98 +655,204 @@ pub fn prepare_selfdoc(
 
     artifacts.sort_by(|a, b| a.path.cmp(&b.path));
 
-    let reference_result = extract_references(&repo, &artifacts);
+    let cache_file = workspace.cache_file(root, out_dir);
+    let mut cache = ScanCache::load(&cache_file, &scan_config_hash(&config_body));
+    let scanned = scan_artifacts(repo, &artifacts, &mut cache);
+    tracing::info!(
+        repo = %root.namespace,
+        files = scanned.scans.len(),
+        rescanned = scanned.rescanned,
+        "selfdoc scan finished"
+    );
+    cache.save(&cache_file).map_err(PrepareError::Diagnostics)?;
+    if !scanned.errors.is_empty() {
+        return Err(PrepareError::Diagnostics(scanned.errors));
+    }
+    let scans = scanned.scans;
+
+    let reference_result = extract_references(workspace, root, &scans);
     if !reference_result.errors.is_empty() {
         return Err(PrepareError::Diagnostics(reference_result.errors));
     }
 
-    let cli_contracts = extract_cli_contracts(&repo, cli_subcommands);
-    if !cli_contracts.errors.is_empty() {
-        return Err(PrepareError::Diagnostics(cli_contracts.errors));
+    let cli_contracts = if primary {
+        let mut extracted = extract_cli_contracts(repo, &scans, cli_subcommands);
+        if !extracted.errors.is_empty() {
+            return Err(PrepareError::Diagnostics(extracted.errors));
+        }
+        for contract in &mut extracted.contracts {
+            contract.source = workspace.qualify(root, &contract.source);
+            contract.path = workspace.qualify(root, &contract.path);
+        }
+        Some(extracted)
+    } else {
+        None
+    };
+
+    let mut gates = extract_quality_gates(&scans);
+    for gate in &mut gates {
+        gate.name = workspace.qualify(root, &gate.name);
+        gate.source = workspace.qualify(root, &gate.source);
     }
 
-    let quality_gates = extract_quality_gates(&repo, &artifacts);
-    if !quality_gates.errors.is_empty() {
-        return Err(PrepareError::Diagnostics(quality_gates.errors));
+    let mut manifest = extract_cargo_manifests(repo, &artifacts, &scans);
+    if !manifest.errors.is_empty() {
+        return Err(PrepareError::Diagnostics(manifest.errors));
+    }
+    for usage in &mut manifest.facts.dependency_uses {
+        usage.path = workspace.qualify(root, &usage.path);
     }
 
-    let data = build_prepared_data(
-        &repo,
-        &artifacts,
-        &reference_result.references,
-        &reference_result.extra_exists,
-        &cli_contracts.contracts,
-        &quality_gates.gates,
-    );
+    for artifact in &mut artifacts {
+        artifact.path = workspace.qualify(root, &artifact.path);
+    }
 
-    let rendered = render_selfdoc_program(&data);
-    fs::create_dir_all(out_dir).map_err(|err| {
-        PrepareError::Diagnostics(vec![diag(
-            "E-IO",
-            format!("出力ディレクトリを作成できません: {err}"),
-            Some(out_dir.display().to_string()),
-        )])
-    })?;
+    Ok(RepoExtraction {
+        artifacts,
+        references: reference_result.references,
+        extra_exists: reference_result.extra_exists,
+        cli_contracts,
+        gates,
+        manifest: manifest.facts,
+        categories: config.categories,
+        allow_unproved: config.selfcheck.allow_unproved,
+    })
+}
 
-    let generated_file = out_dir.join("selfdoc.generated.dtl");
-    fs::write(&generated_file, rendered.as_bytes()).map_err(|err| {
-        PrepareError::Diagnostics(vec![diag(
-            "E-IO",
-            format!("自己記述 DSL を書き込めません: {err}"),
-            Some(generated_file.display().to_string()),
-        )])
+pub fn load_baseline(dir: &Path) -> Result<DocSelfDescription, Vec<Diagnostic>> {
+    let path = dir.join(GENERATED_FILENAME);
+    let source = path.display().to_string();
+    let body = fs::read_to_string(&path).map_err(|err| {
+        vec![diag(
+            "E-SELFDOC-BASELINE",
+            format!("ベースラインの自己記述 DSL を読み込めません: {err}"),
+            Some(source.clone()),
+        )]
+    })?;
+    let program = parse_program_with_source(&body, &source).map_err(|diags| {
+        diags
+            .into_iter()
+            .map(|d| {
+                diag(
+                    "E-SELFDOC-BASELINE",
+                    format!("ベースラインの自己記述 DSL を解析できません: {}", d.message),
+                    Some(source.clone()),
+                )
+            })
+            .collect::<Vec<_>>()
     })?;
 
-    Ok(PreparedSelfdoc {
-        generated_file,
-        generated_relative: "selfdoc.generated.dtl".to_string(),
-        self_description: DocSelfDescription {
-            project: Some(DocProject {
-                name: data.project.name,
-                summary: data.project.summary,
+    let mut description = DocSelfDescription::default();
+    for fact in &program.facts {
+        let terms = fact
+            .terms
+            .iter()
+            .map(|term| match term {
+                LogicTerm::Symbol(value) => value
+                    .strip_prefix('"')
+                    .and_then(|rest| rest.strip_suffix('"'))
+                    .unwrap_or(value)
+                    .to_string(),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>();
+        match (fact.name.as_str(), terms.as_slice()) {
+            ("sd-project", [name, summary]) => {
+                description.project = Some(DocProject {
+                    name: name.clone(),
+                    summary: summary.clone(),
+                });
+            }
+            ("sd-module", [name, path, category]) => description.modules.push(DocModule {
+                name: name.clone(),
+                path: path.clone(),
+                category: category.clone(),
             }),
-            modules: data
-                .modules
-                .iter()
-                .map(|m| DocModule {
-                    name: m.name.clone(),
-                    path: m.path.clone(),
-                    category: m.category.clone(),
-                })
-                .collect(),
-            references: data
-                .references
-                .iter()
-                .map(|r| DocReference {
-                    from: r.from.clone(),
-                    to: r.to.clone(),
-                })
-                .collect(),
-            contracts: data
-                .contracts
-                .iter()
-                .map(|c| DocContract {
-                    name: c.name.clone(),
-                    source: c.source.clone(),
-                    path: c.path.clone(),
+            ("sd-reference", [from, to]) => description.references.push(DocReference {
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            ("sd-contract", [name, source, path]) => description.contracts.push(DocContract {
+                name: name.clone(),
+                source: source.clone(),
+                path: path.clone(),
+            }),
+            ("sd-quality-gate", [name, command, source, required]) => {
+                description.quality_gates.push(DocQualityGate {
+                    name: name.clone(),
+                    command: command.clone(),
+                    source: source.clone(),
+                    required: required == "yes",
                 })
-                .collect(),
-            quality_gates: data
-                .quality_gates
+            }
+            _ => {}
+        }
+    }
+    Ok(description)
+}
+
+pub fn obligation_location(
+    repos: &[PathBuf],
+    description: &DocSelfDescription,
+    obligation: &ObligationTrace,
+) -> Option<(String, Option<usize>)> {
+    let goal = obligation.counterexample.as_ref()?.missing_goals.first()?;
+    let quoted = Regex::new(r#""((?:[^"\\]|\\.)*)""#).expect("valid quoted atom regex");
+    let names = quoted
+        .captures_iter(goal)
+        .filter_map(|caps| decode_escaped(&caps[1]).ok())
+        .collect::<Vec<_>>();
+    let file = names
+        .iter()
+        .find_map(|name| {
+            let contract = description.contracts.iter().find(|c| &c.name == name);
+            let gate = description.quality_gates.iter().find(|g| &g.name == name);
+            contract
+                .map(|c| c.source.clone())
+                .or_else(|| gate.map(|g| g.source.clone()))
+        })
+        .or_else(|| {
+            names
                 .iter()
-                .map(|g| DocQualityGate {
-                    name: g.name.clone(),
-                    command: g.command.clone(),
-                    source: g.source.clone(),
-                    required: g.required,
-                })
-                .collect(),
-        },
-        claim_coverage: ClaimCoverage {
-            total_claims: cli_contracts.total_claims,
-            proved_claims: cli_contracts.proved_claims,
-        },
-    })
+                .find(|name| description.modules.iter().any(|m| &m.path == *name))
+                .cloned()
+        })?;
+
+    let body = fs::read_to_string(model_file(repos, &file)).unwrap_or_default();
+    let line = names
+        .iter()
+        .filter(|name| **name != file)
+        .flat_map(|name| {
+            let base = Path::new(name)
+                .file_name()
+                .map(|base| base.to_string_lossy().into_owned());
+            std::iter::once(name.clone()).chain(base)
+        })
+        .find_map(|needle| body.lines().position(|line| line.contains(&needle)))
+        .map(|idx| idx + 1);
+    Some((file, line))
+}
+
+pub fn contract_table_location(repos: &[PathBuf]) -> Option<(String, usize)> {
+    let workspace = Workspace::new(repos).ok()?;
+    let root = workspace.roots.first()?;
+    ["README.md", "docs/language-spec.md"]
+        .into_iter()
+        .find_map(|source| {
+            let body = fs::read_to_string(root.path.join(source)).ok()?;
+            let idx = body
+                .lines()
+                .position(|line| line.contains(CONTRACT_TABLE_START))?;
+            Some((workspace.qualify(root, source), idx + 1))
+        })
+}
+
+pub fn model_file(repos: &[PathBuf], path: &str) -> PathBuf {
+    Workspace::new(repos)
+        .ok()
+        .filter(|_| Path::new(path).is_relative())
+        .and_then(|workspace| workspace.resolve(path))
+        .unwrap_or_else(|| PathBuf::from(path))
 }
 
 fn validate_config(config: &SelfdocConfig, source: &Path) -> Vec<Diagnostic> {
@@ -427,11 +878,43 @@ fn validate_config(config: &SelfdocConfig, source: &Path) -> Vec<Diagnostic> {
             Some(source.display().to_string()),
         ));
     }
+    let mut declared = BTreeSet::new();
+    for category in &config.categories {
+        let valid = category
+            .name
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_ascii_alphabetic())
+            && category
+                .name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-');
+        if !valid {
+            errors.push(diag(
+                "E-SELFDOC-CONFIG",
+                format!(
+                    "category.name は英字で始まる英数字・`_`・`-` のみ使えます: {}",
+                    category.name
+                ),
+                Some(source.display().to_string()),
+            ));
+        }
+        if !declared.insert(category.name.as_str()) {
+            errors.push(diag(
+                "E-SELFDOC-CONFIG",
+                format!("category が重複して宣言されています: {}", category.name),
+                Some(source.display().to_string()),
+            ));
+        }
+    }
     for rule in &config.classify {
-        if FileCategory::parse(&rule.category).is_none() {
+        if FileCategory::resolve(&rule.category, &config.categories).is_none() {
             errors.push(diag(
                 "E-SELFDOC-CONFIG",
-                format!("無効な category: {}", rule.category),
+                format!(
+                    "無効な category: {}（組み込みカテゴリか [[category]] で宣言した名前を指定してください）",
+                    rule.category
+                ),
                 Some(source.display().to_string()),
             ));
         }
@@ -476,12 +959,12 @@ fn compile_globset(
 }
 
 fn compile_classify_rules(
-    rules: &[ClassifyRuleConfig],
+    config: &SelfdocConfig,
     source: &Path,
 ) -> Result<Vec<(FileCategory, GlobSet)>, Vec<Diagnostic>> {
     let mut out = Vec::new();
-    for rule in rules {
-        let Some(category) = FileCategory::parse(&rule.category) else {
+    for rule in &config.classify {
+        let Some(category) = FileCategory::resolve(&rule.category, &config.categories) else {
             continue;
         };
         let matcher = compile_globset(&rule.patterns, "E-SELFDOC-CONFIG", source)?;
@@ -573,92 +1056,46 @@ struct ReferenceExtraction {
     errors: Vec<Diagnostic>,
 }
 
-fn extract_references(repo: &Path, artifacts: &[Artifact]) -> ReferenceExtraction {
+fn extract_references(
+    workspace: &Workspace,
+    root: &WorkspaceRoot,
+    scans: &BTreeMap<String, FileScan>,
+) -> ReferenceExtraction {
     let mut references = Vec::new();
     let mut extra_exists = BTreeSet::new();
     let mut errors = Vec::new();
+    let rust_references = extract_rust_module_references(&root.path, scans);
 
-    let import_re =
-        Regex::new(r#"\(\s*import\s+\"((?:\\.|[^\"\\])*)\"\s*\)"#).expect("valid import regex");
-    let link_re = Regex::new(r#"\[[^\]]+\]\(([^)]+)\)"#).expect("valid markdown link regex");
-    let include_re =
-        Regex::new(r#"\{\{#include\s+([^\s\}]+)\s*\}\}"#).expect("valid include regex");
-
-    for artifact in artifacts {
-        let abs = repo.join(&artifact.path);
-        let Ok(body) = fs::read_to_string(&abs) else {
-            continue;
-        };
-        let ext = abs
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_ascii_lowercase();
-
-        let mut candidates = Vec::new();
-        let mut yaml_paths = false;
-        match ext.as_str() {
-            "dtl" => {
-                for caps in import_re.captures_iter(&body) {
-                    let Some(raw) = caps.get(1) else {
-                        continue;
-                    };
-                    match decode_escaped(raw.as_str()) {
-                        Ok(path) => candidates.push(path),
-                        Err(message) => errors.push(diag(
-                            "E-SELFDOC-REF",
-                            format!("import パスのエスケープが不正です: {message}"),
-                            Some(artifact.path.clone()),
-                        )),
-                    }
-                }
-            }
-            "md" => {
-                for caps in link_re.captures_iter(&body) {
-                    let Some(raw) = caps.get(1) else {
-                        continue;
-                    };
-                    if let Some(target) = normalize_markdown_target(raw.as_str()) {
-                        candidates.push(target);
-                    }
-                }
-                for caps in include_re.captures_iter(&body) {
-                    let Some(raw) = caps.get(1) else {
-                        continue;
-                    };
-                    candidates.push(raw.as_str().to_string());
-                }
-            }
-            "yml" | "yaml" => {
-                yaml_paths = true;
-                match serde_yaml::from_str::<YamlValue>(&body) {
-                    Ok(value) => collect_yaml_paths(&value, &mut candidates),
-                    Err(err) => {
-                        errors.push(diag(
-                            "E-SELFDOC-REF",
-                            format!("YAML 解析に失敗しました: {err}"),
-                            Some(artifact.path.clone()),
-                        ));
-                    }
-                }
-            }
-            _ => {}
-        }
-
-        for candidate in candidates {
-            let Some(target_rel) = (if yaml_paths {
-                normalize_yaml_reference_target(&candidate)
+    for (local_path, scan) in scans {
+        let path = &workspace.qualify(root, local_path);
+        let candidates = scan
+            .references
+            .iter()
+            .map(|candidate| (candidate, scan.repo_relative))
+            .chain(
+                rust_references
+                    .get(local_path)
+                    .into_iter()
+                    .flatten()
+                    .map(|target| (target, true)),
+            );
+        for (candidate, repo_relative) in candidates {
+            let Some(target_rel) = (if repo_relative {
+                normalize_yaml_reference_target(candidate)
+                    .map(|target| workspace.qualify(root, &target))
             } else {
-                normalize_reference_target(&artifact.path, &candidate)
+                normalize_reference_target(path, candidate)
             }) else {
                 continue;
             };
-            let target_abs = repo.join(&target_rel);
+            let Some(target_abs) = workspace.resolve(&target_rel) else {
+                continue;
+            };
             if !target_abs.exists() {
                 errors.push(diag(
                     "E-SELFDOC-REF",
-                    format!("参照先が存在しません: {} -> {}", artifact.path, target_rel),
-                    Some(artifact.path.clone()),
+                    format!("参照先が存在しません: {} -> {}", path, target_rel),
+                    Some(path.clone()),
                 ));
                 continue;
             }
@@ -666,7 +1103,7 @@ fn extract_references(repo: &Path, artifacts: &[Artifact]) -> ReferenceExtractio
                 extra_exists.insert(target_rel.clone());
             }
             references.push(SelfdocLink {
-                from: artifact.path.clone(),
+                from: path.to_string(),
                 to: target_rel,
             });
         }
@@ -688,6 +1125,287 @@ fn extract_references(repo: &Path, artifacts: &[Artifact]) -> ReferenceExtractio
     }
 }
 
+struct ScanPatterns {
+    import: Regex,
+    link: Regex,
+    include: Regex,
+    rust_mod: Regex,
+    path_attr: Regex,
+    rust_use: Regex,
+    rust_path_root: Regex,
+    contract: Regex,
+}
+
+impl ScanPatterns {
+    fn new() -> Self {
+        Self {
+            import: Regex::new(r#"\(\s*import\s+\"((?:\\.|[^\"\\])*)\"\s*\)"#)
+                .expect("valid import regex"),
+            link: Regex::new(r#"\[[^\]]+\]\(([^)]+)\)"#).expect("valid markdown link regex"),
+            include: Regex::new(r#"\{\{#include\s+([^\s\}]+)\s*\}\}"#)
+                .expect("valid include regex"),
+            rust_mod: Regex::new(
+                r"(?m)^[ \t]*((?:#\[[^\]]*\]\s*)*)(?:pub(?:\([^)]*\))?\s+)?mod\s+(?:r#)?([A-Za-z_][A-Za-z0-9_]*)\s*;",
+            )
+            .expect("valid mod regex"),
+            path_attr: Regex::new(r#"#\[\s*path\s*=\s*"([^"]+)"\s*\]"#)
+                .expect("valid path attribute regex"),
+            rust_use: Regex::new(
+                r"(?m)^[ \t]*(?:#\[[^\]]*\]\s*)*(?:pub(?:\([^)]*\))?\s+)?use\s+crate::([^;]*);",
+            )
+            .expect("valid use regex"),
+            rust_path_root: Regex::new(
+                r"\b([A-Za-z_][A-Za-z0-9_]*)\s*::|\bextern\s+crate\s+([A-Za-z_][A-Za-z0-9_]*)|\buse\s+([A-Za-z_][A-Za-z0-9_]*)\s*(?:;|\bas\b)",
+            )
+            .expect("valid path root regex"),
+            contract: Regex::new(
+                r#"(?m)^[ \t]*(?:#!?\[doc\s*=\s*"\s*dtl-contract:([^"]*)"\s*\]|//[/!]?[ \t]*dtl-contract:(.*)$)"#,
+            )
+            .expect("valid contract annotation regex"),
+        }
+    }
+}
+
+fn scan_file(
+    patterns: &ScanPatterns,
+    artifact: &Artifact,
+    body: &str,
+) -> Result<FileScan, Vec<Diagnostic>> {
+    let mut scan = FileScan::default();
+    let mut errors = Vec::new();
+    let path = Path::new(&artifact.path);
+    let ext = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+
+    match ext.as_str() {
+        "dtl" => {
+            for caps in patterns.import.captures_iter(body) {
+                let Some(raw) = caps.get(1) else {
+                    continue;
+                };
+                match decode_escaped(raw.as_str()) {
+                    Ok(path) => scan.references.push(path),
+                    Err(message) => errors.push(diag(
+                        "E-SELFDOC-REF",
+                        format!("import パスのエスケープが不正です: {message}"),
+                        Some(artifact.path.clone()),
+                    )),
+                }
+            }
+        }
+        "md" => {
+            for caps in patterns.link.captures_iter(body) {
+                let Some(raw) = caps.get(1) else {
+                    continue;
+                };
+                if let Some(target) = normalize_markdown_target(raw.as_str()) {
+                    scan.references.push(target);
+                }
+            }
+            for caps in patterns.include.captures_iter(body) {
+                let Some(raw) = caps.get(1) else {
+                    continue;
+                };
+                scan.references.push(raw.as_str().to_string());
+            }
+        }
+        "yml" | "yaml" => {
+            scan.repo_relative = true;
+            match serde_yaml::from_str::<YamlValue>(body) {
+                Ok(value) => {
+                    collect_yaml_paths(&value, &mut scan.references);
+                    if artifact.category == FileCategory::Ci {
+                        scan.gates = workflow_gates(&artifact.path, &value);
+                    }
+                }
+                Err(err) => {
+                    errors.push(diag(
+                        "E-SELFDOC-REF",
+                        format!("YAML 解析に失敗しました: {err}"),
+                        Some(artifact.path.clone()),
+                    ));
+                }
+            }
+        }
+        "rs" => {
+            for caps in patterns.rust_mod.captures_iter(body) {
+                let explicit = patterns
+                    .path_attr
+                    .captures(&caps[1])
+                    .map(|attr| attr[1].to_string());
+                scan.rust_mods.push((caps[2].to_string(), explicit));
+            }
+            for caps in patterns.rust_use.captures_iter(body) {
+                scan.rust_uses.extend(expand_use_tree(&caps[1]));
+            }
+            let roots = patterns
+                .rust_path_root
+                .captures_iter(body)
+                .filter_map(|caps| (1..=3).find_map(|idx| caps.get(idx)))
+                .map(|m| m.as_str().to_string())
+                .collect::<BTreeSet<_>>();
+            scan.rust_path_roots = roots.into_iter().collect();
+            for caps in patterns.contract.captures_iter(body) {
+                if let Some(payload) = caps.get(1).or_else(|| caps.get(2)) {
+                    scan.contract_annotations.push(payload.as_str().to_string());
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    if let Some(runner) = TaskRunner::from_file_name(file_name) {
+        match runner.targets(body) {
+            Ok(targets) => {
+                scan.gates = targets
+                    .into_iter()
+                    .map(|target| SelfdocGate {
+                        name: format!("{}:{target}", artifact.path),
+                        command: runner.invocation(&target),
+                        source: artifact.path.clone(),
+                        required: true,
+                    })
+                    .collect()
+            }
+            Err(message) => {
+                errors.push(diag("E-SELFDOC-GATE", message, Some(artifact.path.clone())))
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(scan)
+    } else {
+        Err(errors)
+    }
+}
+
+struct ScanOutcome {
+    scans: BTreeMap<String, FileScan>,
+    errors: Vec<Diagnostic>,
+    rescanned: usize,
+}
+
+fn scan_artifacts(repo: &Path, artifacts: &[Artifact], cache: &mut ScanCache) -> ScanOutcome {
+    let patterns = ScanPatterns::new();
+    let mut scans = BTreeMap::new();
+    let mut errors = Vec::new();
+    let mut rescanned = 0;
+    let mut files = BTreeMap::new();
+
+    for artifact in artifacts {
+        let abs = repo.join(&artifact.path);
+        let Ok(meta) = fs::metadata(&abs) else {
+            continue;
+        };
+        let modified = meta.modified().ok().and_then(|time| {
+            let since = time.duration_since(std::time::UNIX_EPOCH).ok()?;
+            Some((since.as_secs(), since.subsec_nanos()))
+        });
+        let previous = cache.files.remove(&artifact.path);
+        if let Some(entry) = &previous
+            && entry.len == meta.len()
+            && entry.modified.is_some()
+            && entry.modified == modified
+            && !cache.is_racy(entry.modified)
+        {
+            scans.insert(artifact.path.clone(), entry.scan.clone());
+            files.insert(artifact.path.clone(), entry.clone());
+            continue;
+        }
+
+        let Ok(body) = fs::read_to_string(&abs) else {
+            continue;
+        };
+        let hash = content_hash(body.as_bytes());
+        let scan = match previous {
+            Some(entry) if entry.hash == hash => entry.scan,
+            _ => {
+                rescanned += 1;
+                match scan_file(&patterns, artifact, &body) {
+                    Ok(scan) => scan,
+                    Err(diags) => {
+                        errors.extend(diags);
+                        continue;
+                    }
+                }
+            }
+        };
+        scans.insert(artifact.path.clone(), scan.clone());
+        files.insert(
+            artifact.path.clone(),
+            CachedScan {
+                len: meta.len(),
+                modified,
+                hash,
+                scan,
+            },
+        );
+    }
+
+    cache.files = files;
+    ScanOutcome {
+        scans,
+        errors,
+        rescanned,
+    }
+}
+
+impl ScanCache {
+    fn load(path: &Path, config_hash: &str) -> Self {
+        let cached = fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<ScanCache>(&bytes).ok())
+            .filter(|cache| {
+                cache.version == SCAN_CACHE_VERSION && cache.config_hash == config_hash
+            });
+        cached.unwrap_or_else(|| ScanCache {
+            version: SCAN_CACHE_VERSION,
+            config_hash: config_hash.to_string(),
+            written: None,
+            files: BTreeMap::new(),
+        })
+    }
+
+    fn save(&mut self, path: &Path) -> Result<(), Vec<Diagnostic>> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        self.written = Some((now.as_secs(), now.subsec_nanos()));
+        let rendered = serde_json::to_string(self).expect("serialize selfdoc cache");
+        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))
+            .and_then(|()| fs::write(path, rendered))
+            .map_err(|err| {
+                vec![diag(
+                    "E-IO",
+                    format!("走査キャッシュを書き込めません: {err}"),
+                    Some(path.display().to_string()),
+                )]
+            })
+    }
+
+    // キャッシュ書き込みの直前 2 秒以内に更新されたファイルは、同じ時刻のまま
+    // 書き換えられている可能性があるため時刻だけでは信用しない。
+    fn is_racy(&self, modified: Option<(u64, u32)>) -> bool {
+        match (modified, self.written) {
+            (Some((modified, _)), Some((written, _))) => modified + 2 > written,
+            _ => true,
+        }
+    }
+}
+
+fn scan_config_hash(config_body: &str) -> String {
+    let mut key = Vec::new();
+    key.extend_from_slice(env!("CARGO_PKG_VERSION").as_bytes());
+    key.push(0);
+    key.extend_from_slice(config_body.as_bytes());
+    content_hash(&key)
+}
+
 fn collect_yaml_paths(value: &YamlValue, out: &mut Vec<String>) {
     match value {
         YamlValue::Mapping(map) => {
@@ -720,10 +1438,8 @@ fn collect_yaml_paths(value: &YamlValue, out: &mut Vec<String>) {
 
 fn collect_path_values(value: &YamlValue, out: &mut Vec<String>) {
     match value {
-        YamlValue::String(s) => {
-            if is_local_path_value(s) {
-                out.push(s.to_string());
-            }
+        YamlValue::String(s) if is_local_path_value(s) => {
+            out.push(s.to_string());
         }
         YamlValue::Sequence(seq) => {
             for item in seq {
@@ -753,6 +1469,150 @@ fn is_local_path_value(path: &str) -> bool {
     true
 }
 
+fn extract_rust_module_references(
+    repo: &Path,
+    scans: &BTreeMap<String, FileScan>,
+) -> BTreeMap<String, Vec<String>> {
+    let rust_files = scans
+        .iter()
+        .filter(|(path, _)| path.ends_with(".rs"))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut children: BTreeMap<&str, Vec<(String, String)>> = BTreeMap::new();
+    let mut child_files = BTreeSet::new();
+    for (path, scan) in &rust_files {
+        for (name, explicit) in &scan.rust_mods {
+            let Some(target) = resolve_rust_module_file(repo, path, name, explicit.as_deref())
+            else {
+                continue;
+            };
+            child_files.insert(target.clone());
+            children
+                .entry(path.as_str())
+                .or_default()
+                .push((name.clone(), target));
+        }
+    }
+
+    let mut module_of: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+    let mut file_of: BTreeMap<(String, Vec<String>), String> = BTreeMap::new();
+    for root in rust_files
+        .keys()
+        .filter(|path| !child_files.contains(**path))
+    {
+        let mut queue = vec![((*root).clone(), Vec::new())];
+        while let Some((file, segments)) = queue.pop() {
+            if module_of.contains_key(&file) {
+                continue;
+            }
+            module_of.insert(file.clone(), ((*root).clone(), segments.clone()));
+            file_of
+                .entry(((*root).clone(), segments.clone()))
+                .or_insert_with(|| file.clone());
+            for (name, target) in children.get(file.as_str()).into_iter().flatten() {
+                let mut next = segments.clone();
+                next.push(name.clone());
+                queue.push((target.clone(), next));
+            }
+        }
+    }
+
+    let mut out: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (path, edges) in &children {
+        let targets = out.entry(path.to_string()).or_default();
+        targets.extend(edges.iter().map(|(_, target)| target.clone()));
+    }
+    for (path, scan) in &rust_files {
+        let Some((root, _)) = module_of.get(*path) else {
+            continue;
+        };
+        for segments in &scan.rust_uses {
+            let target = (1..=segments.len())
+                .rev()
+                .find_map(|len| file_of.get(&(root.clone(), segments[..len].to_vec())))
+                .unwrap_or(root);
+            if target != *path {
+                out.entry((*path).clone()).or_default().push(target.clone());
+            }
+        }
+    }
+    out
+}
+
+fn resolve_rust_module_file(
+    repo: &Path,
+    from: &str,
+    name: &str,
+    explicit: Option<&str>,
+) -> Option<String> {
+    let file = Path::new(from);
+    let dir = file.parent().unwrap_or(Path::new(""));
+    if let Some(explicit) = explicit {
+        return normalize_path_relative(&dir.join(explicit))
+            .filter(|target| repo.join(target).is_file());
+    }
+    let file_name = file.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let dir_name = dir.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let is_root = matches!(file_name, "lib.rs" | "main.rs" | "mod.rs" | "build.rs")
+        || matches!(dir_name, "tests" | "benches" | "examples" | "bin");
+    let base = if is_root {
+        dir.to_path_buf()
+    } else {
+        dir.join(file.file_stem()?)
+    };
+    [
+        base.join(format!("{name}.rs")),
+        base.join(name).join("mod.rs"),
+    ]
+    .iter()
+    .filter_map(|candidate| normalize_path_relative(candidate))
+    .find(|target| repo.join(target).is_file())
+}
+
+fn expand_use_tree(tree: &str) -> Vec<Vec<String>> {
+    let mut out = Vec::new();
+    expand_use_tree_into(tree, &mut Vec::new(), &mut out);
+    out
+}
+
+fn expand_use_tree_into(tree: &str, prefix: &mut Vec<String>, out: &mut Vec<Vec<String>>) {
+    let tree = tree.trim();
+    let depth = prefix.len();
+    if let Some(open) = tree.find('{') {
+        prefix.extend(use_path_segments(&tree[..open]));
+        let inner = tree[open + 1..].trim_end();
+        let inner = inner.strip_suffix('}').unwrap_or(inner);
+        let mut level = 0usize;
+        let mut start = 0;
+        for (idx, ch) in inner.char_indices() {
+            match ch {
+                '{' => level += 1,
+                '}' => level = level.saturating_sub(1),
+                ',' if level == 0 => {
+                    expand_use_tree_into(&inner[start..idx], prefix, out);
+                    start = idx + 1;
+                }
+                _ => {}
+            }
+        }
+        expand_use_tree_into(&inner[start..], prefix, out);
+    } else {
+        let path = tree.split_whitespace().next().unwrap_or("");
+        prefix.extend(use_path_segments(path));
+        if !prefix.is_empty() {
+            out.push(prefix.clone());
+        }
+    }
+    prefix.truncate(depth);
+}
+
+fn use_path_segments(path: &str) -> impl Iterator<Item = String> + '_ {
+    path.split("::")
+        .map(|segment| segment.trim().trim_start_matches("r#"))
+        .filter(|segment| !segment.is_empty() && *segment != "self" && *segment != "*")
+        .map(ToOwned::to_owned)
+}
+
 fn normalize_markdown_target(raw: &str) -> Option<String> {
     let mut s = raw.trim();
     if s.is_empty() {
@@ -865,12 +1725,14 @@ struct CliContractExtraction {
     errors: Vec<Diagnostic>,
     total_claims: usize,
     proved_claims: usize,
+    unproved_claims: Vec<String>,
 }
 
-fn extract_cli_contracts(repo: &Path, subcommands: &[String]) -> CliContractExtraction {
-    const START_MARKER: &str = "<!-- selfdoc:cli-contracts:start -->";
-    const END_MARKER: &str = "<!-- selfdoc:cli-contracts:end -->";
-
+fn extract_cli_contracts(
+    repo: &Path,
+    scans: &BTreeMap<String, FileScan>,
+    subcommands: &[String],
+) -> CliContractExtraction {
     let expected = subcommands.iter().cloned().collect::<BTreeSet<_>>();
     let mut contracts_by_subcommand: BTreeMap<String, SelfdocContract> = BTreeMap::new();
     let mut errors = Vec::new();
@@ -889,7 +1751,7 @@ fn extract_cli_contracts(repo: &Path, subcommands: &[String]) -> CliContractExtr
     ];
 
     for (source, body) in docs {
-        let section = extract_tagged_section(&body, START_MARKER, END_MARKER);
+        let section = extract_tagged_section(&body, CONTRACT_TABLE_START, CONTRACT_TABLE_END);
         if let Some(section) = section {
             table_found = true;
             let parsed = parse_contract_table(section, source, &expected);
@@ -919,7 +1781,26 @@ fn extract_cli_contracts(repo: &Path, subcommands: &[String]) -> CliContractExtr
         docs_without_table.push((source, body));
     }
 
-    if !table_found {
+    let annotations = extract_contract_annotations(scans, &expected);
+    errors.extend(annotations.errors);
+    let annotation_found = !annotations.entries.is_empty();
+    for contract in annotations.entries {
+        let key = contract.name.trim_start_matches("cli::").to_string();
+        if let Some(prev) = contracts_by_subcommand.get(&key) {
+            errors.push(diag(
+                "E-SELFDOC-CONTRACT",
+                format!(
+                    "CLI 契約が複数の出典で重複しています: {} ({}, {})",
+                    contract.name, prev.source, contract.source
+                ),
+                Some(contract.source.clone()),
+            ));
+            continue;
+        }
+        contracts_by_subcommand.insert(key, contract);
+    }
+
+    if !table_found && !annotation_found {
         for (source, body) in docs_without_table {
             for name in &expected {
                 let pattern = format!("dtl {name}");
@@ -941,15 +1822,84 @@ fn extract_cli_contracts(repo: &Path, subcommands: &[String]) -> CliContractExtr
         ));
     }
 
+    let unproved_claims = expected
+        .iter()
+        .filter(|name| !contracts_by_subcommand.contains_key(*name))
+        .map(|name| format!("cli::{name}"))
+        .collect();
     let contracts = contracts_by_subcommand.into_values().collect::<Vec<_>>();
     CliContractExtraction {
         total_claims: expected.len(),
         proved_claims: contracts.len(),
+        unproved_claims,
         contracts,
         errors,
     }
 }
 
+struct ContractAnnotations {
+    entries: Vec<SelfdocContract>,
+    errors: Vec<Diagnostic>,
+}
+
+fn extract_contract_annotations(
+    scans: &BTreeMap<String, FileScan>,
+    expected: &BTreeSet<String>,
+) -> ContractAnnotations {
+    let mut entries: Vec<SelfdocContract> = Vec::new();
+    let mut errors = Vec::new();
+    for (path, scan) in scans {
+        for payload in &scan.contract_annotations {
+            let Some((name, impl_path)) = payload.split_once("->") else {
+                errors.push(diag(
+                    "E-SELFDOC-CONTRACT",
+                    format!(
+                        "dtl-contract 注釈は `cli::NAME -> PATH` 形式で記述してください: `{}`",
+                        payload.trim()
+                    ),
+                    Some(path.clone()),
+                ));
+                continue;
+            };
+            let name = name.trim();
+            let impl_path = normalize_table_cell(impl_path);
+            let Some(subcommand) = name.strip_prefix("cli::") else {
+                errors.push(diag(
+                    "E-SELFDOC-CONTRACT",
+                    format!("未対応の契約名です（`cli::` のみ対応）: `{name}`"),
+                    Some(path.clone()),
+                ));
+                continue;
+            };
+            if !expected.contains(subcommand) {
+                errors.push(diag(
+                    "E-SELFDOC-CONTRACT",
+                    format!("未知の subcommand です: `{subcommand}`"),
+                    Some(path.clone()),
+                ));
+                continue;
+            }
+            if let Some(prev) = entries.iter().find(|c| c.name == name) {
+                errors.push(diag(
+                    "E-SELFDOC-CONTRACT",
+                    format!(
+                        "CLI 契約が複数の出典で重複しています: {name} ({}, {path})",
+                        prev.source
+                    ),
+                    Some(path.clone()),
+                ));
+                continue;
+            }
+            entries.push(SelfdocContract {
+                name: name.to_string(),
+                source: path.clone(),
+                path: impl_path,
+            });
+        }
+    }
+    ContractAnnotations { entries, errors }
+}
+
 struct ParsedContractTable {
     entries: Vec<(String, String)>,
     errors: Vec<Diagnostic>,
@@ -1080,77 +2030,144 @@ fn normalize_table_cell(cell: &str) -> String {
         .to_string()
 }
 
-struct QualityGateExtraction {
-    gates: Vec<SelfdocGate>,
-    errors: Vec<Diagnostic>,
+fn extract_quality_gates(scans: &BTreeMap<String, FileScan>) -> Vec<SelfdocGate> {
+    let mut gates = scans
+        .values()
+        .flat_map(|scan| scan.gates.iter().cloned())
+        .collect::<Vec<_>>();
+    gates.sort_by(|a, b| a.name.cmp(&b.name));
+    gates.dedup_by(|a, b| a.name == b.name);
+    gates
 }
 
-fn extract_quality_gates(repo: &Path, artifacts: &[Artifact]) -> QualityGateExtraction {
+fn workflow_gates(source: &str, workflow: &YamlValue) -> Vec<SelfdocGate> {
     let mut gates = Vec::new();
-    let mut errors = Vec::new();
+    let Some(jobs) = yaml_get_map(workflow, "jobs") else {
+        return gates;
+    };
+    let workflow_stem = Path::new(source)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("workflow");
+    for (job_name_val, job_body) in jobs {
+        let Some(job_name) = job_name_val.as_str() else {
+            continue;
+        };
+        let Some(steps) = yaml_get_seq(job_body, "steps") else {
+            continue;
+        };
+        for (idx, step) in steps.iter().enumerate() {
+            let Some(run) = yaml_get_str(step, "run") else {
+                continue;
+            };
+            let required = !yaml_get_bool(step, "continue-on-error").unwrap_or(false);
+            gates.push(SelfdocGate {
+                name: format!("{workflow_stem}:{job_name}:{}", idx + 1),
+                command: run.trim().to_string(),
+                source: source.to_string(),
+                required,
+            });
+        }
+    }
+    gates
+}
 
-    for artifact in artifacts {
-        if artifact.category != FileCategory::Ci {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TaskRunner {
+    Just,
+    Make,
+    Npm,
+}
+
+impl TaskRunner {
+    fn from_file_name(name: &str) -> Option<Self> {
+        match name {
+            "justfile" | "Justfile" | ".justfile" => Some(Self::Just),
+            "Makefile" | "makefile" | "GNUmakefile" => Some(Self::Make),
+            "package.json" => Some(Self::Npm),
+            _ => None,
+        }
+    }
+
+    fn invocation(self, target: &str) -> String {
+        match self {
+            Self::Just => format!("just {target}"),
+            Self::Make => format!("make {target}"),
+            Self::Npm => format!("npm run {target}"),
+        }
+    }
+
+    fn targets(self, body: &str) -> Result<Vec<String>, String> {
+        match self {
+            Self::Just => Ok(just_recipes(body)),
+            Self::Make => Ok(make_targets(body)),
+            Self::Npm => {
+                let value = serde_json::from_str::<serde_json::Value>(body)
+                    .map_err(|err| format!("package.json を解析できません: {err}"))?;
+                Ok(value
+                    .get("scripts")
+                    .and_then(|scripts| scripts.as_object())
+                    .map(|scripts| scripts.keys().cloned().collect())
+                    .unwrap_or_default())
+            }
+        }
+    }
+}
+
+fn just_recipes(body: &str) -> Vec<String> {
+    let mut recipes = Vec::new();
+    let mut private = false;
+    for line in body.lines() {
+        if line.starts_with('[') {
+            private |= line.contains("private");
+            continue;
+        }
+        if line.is_empty() || line.starts_with([' ', '\t', '#']) {
             continue;
         }
-        let path = Path::new(&artifact.path);
-        let Some(ext) = path.extension().and_then(|s| s.to_str()) else {
+        let was_private = std::mem::take(&mut private);
+        let Some(colon) = line.find(':') else {
             continue;
         };
-        if ext != "yml" && ext != "yaml" {
+        if line[colon + 1..].starts_with('=') {
             continue;
         }
-
-        let abs = repo.join(&artifact.path);
-        let Ok(body) = fs::read_to_string(&abs) else {
+        let header = line[..colon].trim_start_matches('@');
+        let Some(name) = header.split_whitespace().next() else {
             continue;
         };
-        let parsed = match serde_yaml::from_str::<YamlValue>(&body) {
-            Ok(value) => value,
-            Err(err) => {
-                errors.push(diag(
-                    "E-SELFDOC-GATE",
-                    format!("workflow YAML を解析できません: {err}"),
-                    Some(artifact.path.clone()),
-                ));
-                continue;
-            }
-        };
+        let is_ident = name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-');
+        if !is_ident || name.starts_with('_') || was_private {
+            continue;
+        }
+        recipes.push(name.to_string());
+    }
+    recipes
+}
 
-        let Some(jobs) = yaml_get_map(&parsed, "jobs") else {
+fn make_targets(body: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    for line in body.lines() {
+        if line.is_empty() || line.starts_with([' ', '\t', '#']) {
+            continue;
+        }
+        let Some(colon) = line.find(':') else {
             continue;
         };
-        for (job_name_val, job_body) in jobs {
-            let Some(job_name) = job_name_val.as_str() else {
-                continue;
-            };
-            let Some(steps) = yaml_get_seq(job_body, "steps") else {
-                continue;
-            };
-            for (idx, step) in steps.iter().enumerate() {
-                let Some(run) = yaml_get_str(step, "run") else {
-                    continue;
-                };
-                let required = !yaml_get_bool(step, "continue-on-error").unwrap_or(false);
-                let workflow_stem = path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("workflow");
-                let gate_id = format!("{workflow_stem}:{job_name}:{}", idx + 1);
-                gates.push(SelfdocGate {
-                    name: gate_id,
-                    command: run.trim().to_string(),
-                    source: artifact.path.clone(),
-                    required,
-                });
-            }
+        let head = &line[..colon];
+        let rest = line[colon + 1..].trim_start_matches(':');
+        if head.contains('=') || rest.starts_with('=') {
+            continue;
         }
+        targets.extend(
+            head.split_whitespace()
+                .filter(|t| !t.starts_with('.') && !t.contains(['%', '$']))
+                .map(ToOwned::to_owned),
+        );
     }
-
-    gates.sort_by(|a, b| a.name.cmp(&b.name));
-    gates.dedup_by(|a, b| a.name == b.name);
-
-    QualityGateExtraction { gates, errors }
+    targets
 }
 
 fn yaml_get_map<'a>(value: &'a YamlValue, key: &str) -> Option<&'a serde_yaml::Mapping> {
@@ -1185,6 +2202,117 @@ fn yaml_get_bool(value: &YamlValue, key: &str) -> Option<bool> {
     child.as_bool()
 }
 
+struct ManifestExtraction {
+    facts: ManifestFacts,
+    errors: Vec<Diagnostic>,
+}
+
+fn extract_cargo_manifests(
+    repo: &Path,
+    artifacts: &[Artifact],
+    scans: &BTreeMap<String, FileScan>,
+) -> ManifestExtraction {
+    const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+    let mut facts = ManifestFacts::default();
+    let mut errors = Vec::new();
+
+    for artifact in artifacts {
+        let manifest_path = Path::new(&artifact.path);
+        if manifest_path.file_name().and_then(|s| s.to_str()) != Some("Cargo.toml") {
+            continue;
+        }
+        let Ok(body) = fs::read_to_string(repo.join(&artifact.path)) else {
+            continue;
+        };
+        let manifest = match toml::from_str::<toml::Table>(&body) {
+            Ok(table) => table,
+            Err(err) => {
+                errors.push(diag(
+                    "E-SELFDOC-MANIFEST",
+                    format!("Cargo.toml を解析できません: {err}"),
+                    Some(artifact.path.clone()),
+                ));
+                continue;
+            }
+        };
+        let Some(package) = manifest
+            .get("package")
+            .and_then(|p| p.get("name"))
+            .and_then(|n| n.as_str())
+        else {
+            continue;
+        };
+
+        let mut tables = DEPENDENCY_TABLES
+            .iter()
+            .filter_map(|key| manifest.get(*key).and_then(|v| v.as_table()))
+            .collect::<Vec<_>>();
+        if let Some(targets) = manifest.get("target").and_then(|v| v.as_table()) {
+            for target in targets.values() {
+                tables.extend(
+                    DEPENDENCY_TABLES
+                        .iter()
+                        .filter_map(|key| target.get(*key).and_then(|v| v.as_table())),
+                );
+            }
+        }
+        let names = tables
+            .iter()
+            .flat_map(|table| table.keys().cloned())
+            .collect::<BTreeSet<_>>();
+
+        let manifest_dir = manifest_path
+            .parent()
+            .map(to_posix_path)
+            .unwrap_or_default();
+        let sources = scans
+            .iter()
+            .filter(|(path, _)| path.ends_with(".rs"))
+            .filter(|(path, _)| {
+                manifest_dir.is_empty() || path.starts_with(&format!("{manifest_dir}/"))
+            })
+            .collect::<Vec<_>>();
+
+        for name in names {
+            let ident = name.replace('-', "_");
+            for (path, scan) in &sources {
+                if scan.rust_path_roots.contains(&ident) {
+                    facts.dependency_uses.push(SelfdocDependencyUse {
+                        name: name.clone(),
+                        path: (*path).clone(),
+                    });
+                }
+            }
+            facts.dependencies.push(SelfdocDependency {
+                package: package.to_string(),
+                name,
+            });
+        }
+
+        if let Some(features) = manifest.get("features").and_then(|v| v.as_table()) {
+            for (feature, enables) in features {
+                let entries = enables.as_array().into_iter().flatten();
+                for entry in entries.filter_map(|e| e.as_str()) {
+                    facts.features.push(SelfdocFeature {
+                        name: feature.clone(),
+                        enables: entry.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    facts
+        .dependency_uses
+        .sort_by(|a, b| (&a.name, &a.path).cmp(&(&b.name, &b.path)));
+    facts
+        .dependency_uses
+        .dedup_by(|a, b| a.name == b.name && a.path == b.path);
+
+    ManifestExtraction { facts, errors }
+}
+
 fn build_prepared_data(
     repo: &Path,
     artifacts: &[Artifact],
@@ -1192,6 +2320,7 @@ fn build_prepared_data(
     extra_exists: &[String],
     contracts: &[SelfdocContract],
     gates: &[SelfdocGate],
+    manifest: ManifestFacts,
 ) -> PreparedData {
     let readme = fs::read_to_string(repo.join("README.md")).unwrap_or_default();
     let (project_name, project_summary) = infer_project_info(repo, &readme);
@@ -1210,10 +2339,12 @@ fn build_prepared_data(
             name: project_name,
             summary: project_summary,
         },
+        categories: Vec::new(),
         modules,
         references: references.to_vec(),
         contracts: contracts.to_vec(),
         quality_gates: gates.to_vec(),
+        manifest,
         extra_exists_paths: extra_exists.to_vec(),
     }
 }
@@ -1271,7 +2402,10 @@ fn render_selfdoc_program(data: &PreparedData) -> String {
     out.push_str("(関係 contract-doc :引数 (Ident Path))\n");
     out.push_str("(関係 contract-impl :引数 (Ident Path))\n");
     out.push_str("(関係 gate-source :引数 (Ident Path))\n");
-    out.push_str("(関係 gate-required :引数 (Ident Flag))\n\n");
+    out.push_str("(関係 gate-required :引数 (Ident Flag))\n");
+    out.push_str("(関係 depends-on :引数 (Ident Ident))\n");
+    out.push_str("(関係 feature-enables :引数 (Ident Ident))\n");
+    out.push_str("(関係 dep-use :引数 (Ident Path))\n\n");
 
     out.push_str("(関係 sd-project :引数 (Ident Symbol))\n");
     out.push_str("(関係 sd-module :引数 (Ident Path Category))\n");
@@ -1287,6 +2421,7 @@ fn render_selfdoc_program(data: &PreparedData) -> String {
     let reference_consistency_formula = build_reference_fact_consistency_formula(&data.references);
     let contract_consistency_formula = build_contract_fact_consistency_formula(&data.contracts);
     let gate_consistency_formula = build_gate_fact_consistency_formula(&data.quality_gates);
+    let dependency_formula = build_dependency_referenced_formula(&data.manifest);
     out.push_str(&format!(
         "(検証 ref_target_exists :引数 () :式 {ref_target_formula})\n"
     ));
@@ -1299,6 +2434,9 @@ fn render_selfdoc_program(data: &PreparedData) -> String {
     out.push_str(&format!(
         "(検証 gate_source_exists :引数 () :式 {gate_source_formula})\n"
     ));
+    out.push_str(&format!(
+        "(検証 dependency_referenced :引数 () :式 {dependency_formula})\n"
+    ));
     out.push_str(&format!(
         "(検証 module_artifact_consistency :引数 () :式 {module_artifact_formula})\n"
     ));
@@ -1354,6 +2492,28 @@ fn render_selfdoc_program(data: &PreparedData) -> String {
         ));
     }
 
+    for dependency in &data.manifest.dependencies {
+        out.push_str(&format!(
+            "(事実 depends-on :項 ({} {}))\n",
+            quote_atom(&dependency.package),
+            quote_atom(&dependency.name)
+        ));
+    }
+    for feature in &data.manifest.features {
+        out.push_str(&format!(
+            "(事実 feature-enables :項 ({} {}))\n",
+            quote_atom(&feature.name),
+            quote_atom(&feature.enables)
+        ));
+    }
+    for usage in &data.manifest.dependency_uses {
+        out.push_str(&format!(
+            "(事実 dep-use :項 ({} {}))\n",
+            quote_atom(&usage.name),
+            quote_atom(&usage.path)
+        ));
+    }
+
     for extra in &data.extra_exists_paths {
         out.push_str(&format!("(事実 exists :項 ({}))\n", quote_atom(extra)));
     }
@@ -1404,6 +2564,9 @@ fn collect_path_universe(data: &PreparedData) -> Vec<String> {
     for gate in &data.quality_gates {
         values.insert(gate.source.clone());
     }
+    for usage in &data.manifest.dependency_uses {
+        values.insert(usage.path.clone());
+    }
     for extra in &data.extra_exists_paths {
         values.insert(extra.clone());
     }
@@ -1422,6 +2585,14 @@ fn collect_ident_universe(data: &PreparedData) -> Vec<String> {
     for gate in &data.quality_gates {
         values.insert(gate.name.clone());
     }
+    for dependency in &data.manifest.dependencies {
+        values.insert(dependency.package.clone());
+        values.insert(dependency.name.clone());
+    }
+    for feature in &data.manifest.features {
+        values.insert(feature.name.clone());
+        values.insert(feature.enables.clone());
+    }
     values.into_iter().collect()
 }
 
@@ -1430,22 +2601,10 @@ fn collect_category_universe(data: &PreparedData) -> Vec<String> {
     for module in &data.modules {
         values.insert(module.category.clone());
     }
-    values.into_iter().collect()
-}
-
-fn quote_atom(value: &str) -> String {
-    let mut escaped = String::new();
-    for ch in value.chars() {
-        match ch {
-            '\\' => escaped.push_str("\\\\"),
-            '"' => escaped.push_str("\\\""),
-            '\n' => escaped.push_str("\\n"),
-            '\t' => escaped.push_str("\\t"),
-            '\r' => escaped.push_str("\\r"),
-            _ => escaped.push(ch),
-        }
+    for category in &data.categories {
+        values.insert(category.name.clone());
     }
-    format!("\"{escaped}\"")
+    values.into_iter().collect()
 }
 
 fn build_ref_target_exists_formula(references: &[SelfdocLink]) -> String {
@@ -1556,6 +2715,31 @@ fn build_gate_fact_consistency_formula(gates: &[SelfdocGate]) -> String {
     fold_and_formula(clauses)
 }
 
+fn build_dependency_referenced_formula(manifest: &ManifestFacts) -> String {
+    let clauses = manifest.dependencies.iter().map(|dependency| {
+        let mut conjuncts = vec![format!(
+            "(depends-on {} {})",
+            quote_atom(&dependency.package),
+            quote_atom(&dependency.name)
+        )];
+        conjuncts.extend(
+            manifest
+                .dependency_uses
+                .iter()
+                .filter(|usage| usage.name == dependency.name)
+                .map(|usage| {
+                    format!(
+                        "(not (dep-use {} {}))",
+                        quote_atom(&usage.name),
+                        quote_atom(&usage.path)
+                    )
+                }),
+        );
+        format!("(not {})", fold_and_formula(conjuncts))
+    });
+    fold_and_formula(clauses)
+}
+
 fn fold_and_formula<I>(clauses: I) -> String
 where
     I: IntoIterator<Item = String>,
@@ -1616,6 +2800,41 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    fn scan_all(repo: &Path, artifacts: &[Artifact]) -> BTreeMap<String, FileScan> {
+        let scanned = scan_artifacts(repo, artifacts, &mut ScanCache::default());
+        assert!(scanned.errors.is_empty(), "{:?}", scanned.errors);
+        scanned.scans
+    }
+
+    fn workspace(repos: &[&Path]) -> Workspace {
+        let repos = repos
+            .iter()
+            .map(|repo| repo.to_path_buf())
+            .collect::<Vec<_>>();
+        Workspace::new(&repos).expect("workspace")
+    }
+
+    #[test]
+    fn quote_atom_round_trips_through_parser() {
+        for value in [
+            "docs/a b.md",
+            r#"cargo run -- check "x.dtl""#,
+            r#"C:\dtl\"#,
+            "quote \"# inside",
+            "line1\nline2\t\\",
+        ] {
+            let src = format!("(relation p (Symbol)) (fact p {})", quote_atom(value));
+            let program = parse_program_with_source(&src, "test.dtl").expect("parse");
+            assert_eq!(
+                program.facts[0].terms,
+                vec![LogicTerm::Symbol(format!("\"{value}\""))],
+                "{src}"
+            );
+        }
+        assert_eq!(quote_atom(r#"a"b"#), r##"#"a"b"#"##);
+        assert_eq!(quote_atom("x\"#y"), r###"##"x"#y"##"###);
+    }
+
     #[test]
     fn config_validation_rejects_invalid_category() {
         let config = SelfdocConfig {
@@ -1629,11 +2848,66 @@ mod tests {
                 category: "invalid".to_string(),
                 patterns: vec!["**".to_string()],
             }],
+            categories: vec![],
+            selfcheck: SelfcheckConfig::default(),
         };
         let errs = validate_config(&config, Path::new(".dtl-selfdoc.toml"));
         assert!(errs.iter().any(|d| d.code == "E-SELFDOC-CONFIG"));
     }
 
+    #[test]
+    fn config_validation_accepts_declared_categories_only() {
+        let config: SelfdocConfig = toml::from_str(
+            r#"version = 1
+
+[scan]
+include = ["**"]
+exclude = []
+
+[[category]]
+name = "proto"
+description = "gRPC 定義"
+
+[[category]]
+name = "proto"
+
+[[category]]
+name = "bad name"
+
+[[classify]]
+category = "proto"
+patterns = ["**/*.proto"]
+
+[[classify]]
+category = "schema"
+patterns = ["**/*.json"]
+"#,
+        )
+        .expect("valid toml");
+        let errs = validate_config(&config, Path::new(".dtl-selfdoc.toml"));
+        let messages = errs.iter().map(|d| d.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages.len(), 3, "{messages:?}");
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.contains("重複") && m.contains("proto"))
+        );
+        assert!(messages.iter().any(|m| m.contains("bad name")));
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.contains("無効な category: schema"))
+        );
+        assert_eq!(
+            FileCategory::resolve("proto", &config.categories),
+            Some(FileCategory::Custom("proto".to_string()))
+        );
+        assert_eq!(
+            FileCategory::resolve("doc", &config.categories),
+            Some(FileCategory::Doc)
+        );
+    }
+
     #[test]
     fn scan_paths_honors_include_exclude_and_gitignore() {
         let dir = tempdir().expect("tempdir");
@@ -1650,6 +2924,49 @@ mod tests {
         assert_eq!(scanned, vec!["keep.txt".to_string()]);
     }
 
+    #[test]
+    fn extract_references_resolves_links_into_sibling_repositories() {
+        let dir = tempdir().expect("tempdir");
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        fs::create_dir_all(a.join("docs")).expect("mkdir a");
+        fs::create_dir_all(&b).expect("mkdir b");
+        fs::write(
+            a.join("docs/guide.md"),
+            "[own](../README.md)\n[spec](../../b/spec.md)\n[outside](../../c/x.md)\n",
+        )
+        .expect("write guide");
+        fs::write(a.join("README.md"), "# a\n").expect("write readme");
+        fs::write(b.join("spec.md"), "# spec\n").expect("write spec");
+
+        let artifacts = vec![Artifact {
+            path: "docs/guide.md".to_string(),
+            category: FileCategory::Doc,
+        }];
+        let workspace = workspace(&[&a, &b]);
+        let extracted =
+            extract_references(&workspace, &workspace.roots[0], &scan_all(&a, &artifacts));
+        assert!(extracted.errors.is_empty(), "{:?}", extracted.errors);
+        let links = extracted
+            .references
+            .iter()
+            .map(|r| (r.from.as_str(), r.to.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            vec![
+                ("a/docs/guide.md", "a/README.md"),
+                ("a/docs/guide.md", "b/spec.md"),
+            ]
+        );
+
+        fs::remove_file(b.join("spec.md")).expect("remove spec");
+        let extracted =
+            extract_references(&workspace, &workspace.roots[0], &scan_all(&a, &artifacts));
+        assert_eq!(extracted.errors.len(), 1);
+        assert_eq!(extracted.errors[0].code, "E-SELFDOC-REF");
+    }
+
     #[test]
     fn extract_references_reads_dtl_md_and_yaml() {
         let dir = tempdir().expect("tempdir");
@@ -1684,7 +3001,14 @@ mod tests {
             },
         ];
 
-        let extracted = extract_references(dir.path(), &artifacts);
+        let extracted = {
+            let workspace = workspace(&[dir.path()]);
+            extract_references(
+                &workspace,
+                &workspace.roots[0],
+                &scan_all(dir.path(), &artifacts),
+            )
+        };
         assert!(extracted.errors.is_empty());
         assert!(
             extracted
@@ -1699,4 +3023,279 @@ mod tests {
                 .any(|r| r.from == ".github/workflows/ci.yml" && r.to == "docs/spec.dtl")
         );
     }
+
+    #[test]
+    fn extract_references_follows_rust_mod_and_use_crate() {
+        let dir = tempdir().expect("tempdir");
+        fs::create_dir_all(dir.path().join("src/a")).expect("mkdir a");
+        fs::create_dir_all(dir.path().join("src/b")).expect("mkdir b");
+        fs::create_dir_all(dir.path().join("src/gen")).expect("mkdir gen");
+
+        fs::write(
+            dir.path().join("src/lib.rs"),
+            "mod a;\npub mod b;\n#[path = \"gen/c.rs\"]\nmod c;\n// mod missing;\npub struct Top;\n",
+        )
+        .expect("write lib");
+        fs::write(
+            dir.path().join("src/a.rs"),
+            "use crate::b::{self, Item};\nuse crate::c::x as y;\nmod inner;\n",
+        )
+        .expect("write a");
+        fs::write(
+            dir.path().join("src/a/inner.rs"),
+            "use crate::{\n    Top,\n    a::helper,\n};\n",
+        )
+        .expect("write inner");
+        fs::write(dir.path().join("src/b/mod.rs"), "pub struct Item;\n").expect("write b");
+        fs::write(dir.path().join("src/gen/c.rs"), "pub fn x() {}\n").expect("write c");
+
+        let artifacts = [
+            "src/lib.rs",
+            "src/a.rs",
+            "src/a/inner.rs",
+            "src/b/mod.rs",
+            "src/gen/c.rs",
+        ]
+        .iter()
+        .map(|path| Artifact {
+            path: path.to_string(),
+            category: FileCategory::Source,
+        })
+        .collect::<Vec<_>>();
+
+        let extracted = {
+            let workspace = workspace(&[dir.path()]);
+            extract_references(
+                &workspace,
+                &workspace.roots[0],
+                &scan_all(dir.path(), &artifacts),
+            )
+        };
+        assert!(extracted.errors.is_empty());
+        let edges = extracted
+            .references
+            .iter()
+            .map(|r| (r.from.as_str(), r.to.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                ("src/a.rs", "src/a/inner.rs"),
+                ("src/a.rs", "src/b/mod.rs"),
+                ("src/a.rs", "src/gen/c.rs"),
+                ("src/a/inner.rs", "src/a.rs"),
+                ("src/a/inner.rs", "src/lib.rs"),
+                ("src/lib.rs", "src/a.rs"),
+                ("src/lib.rs", "src/b/mod.rs"),
+                ("src/lib.rs", "src/gen/c.rs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_cargo_manifests_records_dependencies_features_and_uses() {
+        let dir = tempdir().expect("tempdir");
+        fs::create_dir_all(dir.path().join("src")).expect("mkdir src");
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\n\n[dependencies]\nserde-json = \"1\"\nunused = \"1\"\n\n[features]\ndefault = [\"dep:serde-json\"]\n",
+        )
+        .expect("write manifest");
+        fs::write(
+            dir.path().join("src/lib.rs"),
+            "pub fn f() -> serde_json::Value { todo!() }\n",
+        )
+        .expect("write lib");
+
+        let artifacts = ["Cargo.toml", "src/lib.rs"]
+            .iter()
+            .map(|path| Artifact {
+                path: path.to_string(),
+                category: FileCategory::Source,
+            })
+            .collect::<Vec<_>>();
+
+        let scans = scan_all(dir.path(), &artifacts);
+        let extracted = extract_cargo_manifests(dir.path(), &artifacts, &scans);
+        assert!(extracted.errors.is_empty());
+        let facts = extracted.facts;
+        let deps = facts
+            .dependencies
+            .iter()
+            .map(|d| (d.package.as_str(), d.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(deps, vec![("demo", "serde-json"), ("demo", "unused")]);
+        assert_eq!(facts.features.len(), 1);
+        assert_eq!(facts.features[0].enables, "dep:serde-json");
+        assert_eq!(facts.dependency_uses.len(), 1);
+        assert_eq!(facts.dependency_uses[0].path, "src/lib.rs");
+
+        let formula = build_dependency_referenced_formula(&facts);
+        assert!(formula.contains(
+            "(not (and (depends-on \"demo\" \"serde-json\") (not (dep-use \"serde-json\" \"src/lib.rs\"))))"
+        ));
+        assert!(formula.contains("(not (depends-on \"demo\" \"unused\"))"));
+    }
+
+    #[test]
+    fn extract_quality_gates_reads_task_runners() {
+        let dir = tempdir().expect("tempdir");
+        fs::write(
+            dir.path().join("justfile"),
+            "set shell := [\"bash\", \"-c\"]\nalias t := test\n\ntest filter='': build\n    cargo test {{filter}}\n\n[private]\nhelper:\n    echo hi\n\n_hidden:\n    echo no\n\n@build:\n    cargo build\n",
+        )
+        .expect("write justfile");
+        fs::write(
+            dir.path().join("Makefile"),
+            "CARGO := cargo\n.PHONY: lint check\n\nlint check: fmt\n\t$(CARGO) clippy\n\n%.o: %.c\n\tcc -c $<\n\nfmt:\n\t$(CARGO) fmt\n",
+        )
+        .expect("write makefile");
+        fs::write(
+            dir.path().join("package.json"),
+            "{\"name\": \"x\", \"scripts\": {\"test\": \"vitest\", \"lint\": \"eslint .\"}}\n",
+        )
+        .expect("write package.json");
+
+        let artifacts = ["Makefile", "justfile", "package.json"]
+            .iter()
+            .map(|path| Artifact {
+                path: path.to_string(),
+                category: FileCategory::Config,
+            })
+            .collect::<Vec<_>>();
+
+        let extracted = extract_quality_gates(&scan_all(dir.path(), &artifacts));
+        let gates = extracted
+            .iter()
+            .map(|g| (g.name.as_str(), g.command.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gates,
+            vec![
+                ("Makefile:check", "make check"),
+                ("Makefile:fmt", "make fmt"),
+                ("Makefile:lint", "make lint"),
+                ("justfile:build", "just build"),
+                ("justfile:test", "just test"),
+                ("package.json:lint", "npm run lint"),
+                ("package.json:test", "npm run test"),
+            ]
+        );
+        assert!(extracted.iter().all(|g| g.required));
+
+        fs::write(dir.path().join("package.json"), "{").expect("break package.json");
+        let broken = scan_artifacts(dir.path(), &artifacts, &mut ScanCache::default());
+        assert!(broken.errors.iter().any(|d| d.code == "E-SELFDOC-GATE"));
+    }
+
+    #[test]
+    fn extract_cli_contracts_merges_rust_annotations() {
+        let dir = tempdir().expect("tempdir");
+        fs::create_dir_all(dir.path().join("src")).expect("mkdir src");
+        fs::write(
+            dir.path().join("README.md"),
+            "<!-- selfdoc:cli-contracts:start -->\n| subcommand | impl_path |\n| --- | --- |\n| check | src/main.rs |\n<!-- selfdoc:cli-contracts:end -->\n",
+        )
+        .expect("write readme");
+        fs::write(
+            dir.path().join("src/main.rs"),
+            "#[doc = \"dtl-contract: cli::prove -> src/prove.rs\"]\nfn prove() {}\n    // dtl-contract: cli::lint -> `src/main.rs`\nfn main() {}\n",
+        )
+        .expect("write main");
+
+        let artifacts = vec![Artifact {
+            path: "src/main.rs".to_string(),
+            category: FileCategory::Source,
+        }];
+        let subcommands = ["check", "prove", "lint", "fmt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+
+        let extracted =
+            extract_cli_contracts(dir.path(), &scan_all(dir.path(), &artifacts), &subcommands);
+        assert!(extracted.errors.is_empty());
+        assert_eq!(extracted.total_claims, 4);
+        assert_eq!(extracted.proved_claims, 3);
+        let contracts = extracted
+            .contracts
+            .iter()
+            .map(|c| (c.name.as_str(), c.source.as_str(), c.path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            contracts,
+            vec![
+                ("cli::check", "README.md", "src/main.rs"),
+                ("cli::lint", "src/main.rs", "src/main.rs"),
+                ("cli::prove", "src/main.rs", "src/prove.rs"),
+            ]
+        );
+
+        fs::write(
+            dir.path().join("src/main.rs"),
+            "// dtl-contract: cli::check -> src/main.rs\n// dtl-contract: cli::nope -> src/main.rs\n",
+        )
+        .expect("rewrite main");
+        let conflicting =
+            extract_cli_contracts(dir.path(), &scan_all(dir.path(), &artifacts), &subcommands);
+        assert_eq!(conflicting.errors.len(), 2);
+        assert!(
+            conflicting
+                .errors
+                .iter()
+                .all(|d| d.code == "E-SELFDOC-CONTRACT")
+        );
+    }
+
+    #[test]
+    fn scan_artifacts_reuses_cache_until_content_or_config_changes() {
+        let dir = tempdir().expect("tempdir");
+        let out = dir.path().join("out");
+        fs::create_dir_all(dir.path().join("docs")).expect("mkdir docs");
+        fs::write(dir.path().join("README.md"), "[spec](docs/spec.md)\n").expect("write readme");
+        fs::write(dir.path().join("docs/spec.md"), "# spec\n").expect("write spec");
+        let artifacts = ["README.md", "docs/spec.md"]
+            .iter()
+            .map(|path| Artifact {
+                path: path.to_string(),
+                category: FileCategory::Doc,
+            })
+            .collect::<Vec<_>>();
+
+        let mut cache = ScanCache::load(&out.join(SCAN_CACHE_FILENAME), "config-a");
+        let first = scan_artifacts(dir.path(), &artifacts, &mut cache);
+        assert_eq!(first.rescanned, 2);
+        cache
+            .save(&out.join(SCAN_CACHE_FILENAME))
+            .expect("save cache");
+        assert!(out.join(SCAN_CACHE_FILENAME).exists());
+
+        let mut cache = ScanCache::load(&out.join(SCAN_CACHE_FILENAME), "config-a");
+        cache
+            .files
+            .get_mut("README.md")
+            .expect("cached readme")
+            .scan
+            .references = vec!["docs/other.md".to_string()];
+        let second = scan_artifacts(dir.path(), &artifacts, &mut cache);
+        assert_eq!(second.rescanned, 0);
+        assert_eq!(second.scans["README.md"].references, vec!["docs/other.md"]);
+        cache
+            .save(&out.join(SCAN_CACHE_FILENAME))
+            .expect("save cache");
+
+        fs::write(dir.path().join("README.md"), "[spec](./docs/spec.md)\n").expect("edit readme");
+        let mut cache = ScanCache::load(&out.join(SCAN_CACHE_FILENAME), "config-a");
+        let third = scan_artifacts(dir.path(), &artifacts, &mut cache);
+        assert_eq!(third.rescanned, 1);
+        assert_eq!(third.scans["README.md"].references, vec!["./docs/spec.md"]);
+        cache
+            .save(&out.join(SCAN_CACHE_FILENAME))
+            .expect("save cache");
+
+        let mut cache = ScanCache::load(&out.join(SCAN_CACHE_FILENAME), "config-b");
+        assert!(cache.files.is_empty());
+        let fourth = scan_artifacts(dir.path(), &artifacts, &mut cache);
+        assert_eq!(fourth.rescanned, 2);
+    }
 }
@@ -0,0 +1,273 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ast::{Expr, ExprArena, ExprId, Pattern, PatternId, Program};
+use crate::diagnostics::{Diagnostic, closest_name};
+use crate::name_resolve::normalize_program_aliases;
+use crate::types::{Formula, LogicTerm, Type};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SliceTarget {
+    Assert(String),
+    Defn(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Item {
+    Relation(String),
+    Defn(String),
+    Type(String),
+    Ctor(String),
+}
+
+#[derive(Default)]
+struct Slicer {
+    seen: BTreeSet<Item>,
+    queue: Vec<Item>,
+}
+
+impl Slicer {
+    fn push(&mut self, item: Item) {
+        if self.seen.insert(item.clone()) {
+            self.queue.push(item);
+        }
+    }
+
+    fn visit_type(&mut self, ty: &Type) {
+        match ty {
+            Type::Bool | Type::Int | Type::Symbol | Type::Error => {}
+            Type::Domain(name) | Type::Adt(name) => self.push(Item::Type(name.clone())),
+            Type::Fun(args, ret) => {
+                for arg in args {
+                    self.visit_type(arg);
+                }
+                self.visit_type(ret);
+            }
+            Type::Refine { base, formula, .. } => {
+                self.visit_type(base);
+                self.visit_formula(formula);
+            }
+        }
+    }
+
+    fn visit_formula(&mut self, formula: &Formula) {
+        match formula {
+            Formula::True => {}
+            Formula::Atom(atom) => {
+                self.push(Item::Relation(atom.pred.clone()));
+                self.push(Item::Defn(atom.pred.clone()));
+                for term in &atom.terms {
+                    self.visit_term(term);
+                }
+            }
+            Formula::And(items) => {
+                for item in items {
+                    self.visit_formula(item);
+                }
+            }
+            Formula::Not(inner) => self.visit_formula(inner),
+        }
+    }
+
+    fn visit_term(&mut self, term: &LogicTerm) {
+        match term {
+            LogicTerm::Var(_) | LogicTerm::Int(_) | LogicTerm::Bool(_) => {}
+            LogicTerm::Symbol(name) => self.push(Item::Ctor(name.clone())),
+            LogicTerm::Ctor { name, args } => {
+                self.push(Item::Ctor(name.clone()));
+                for arg in args {
+                    self.visit_term(arg);
+                }
+            }
+        }
+    }
+
+    fn visit_expr(&mut self, exprs: &ExprArena, expr: ExprId) {
+        match &exprs[expr] {
+            Expr::Var { .. } | Expr::Int { .. } | Expr::Bool { .. } | Expr::Hole { .. } => {}
+            Expr::Symbol { value, .. } => self.push(Item::Ctor(value.clone())),
+            Expr::Call { name, .. } => {
+                self.push(Item::Relation(name.clone()));
+                self.push(Item::Defn(name.clone()));
+                self.push(Item::Ctor(name.clone()));
+            }
+            Expr::LetFn { fns, .. } => {
+                for f in fns {
+                    for param in &f.params {
+                        self.visit_type(&param.ty);
+                    }
+                    self.visit_type(&f.ret_type);
+                }
+            }
+            Expr::Match { arms, .. } => {
+                for arm in arms {
+                    self.visit_pattern(exprs, arm.pattern);
+                }
+            }
+            Expr::Let { .. } | Expr::If { .. } => {}
+        }
+        for child in exprs[expr].children() {
+            self.visit_expr(exprs, child);
+        }
+    }
+
+    fn visit_pattern(&mut self, exprs: &ExprArena, pattern: PatternId) {
+        if let Pattern::Ctor { name, args, .. } = &exprs[pattern] {
+            self.push(Item::Ctor(name.clone()));
+            for arg in args {
+                self.visit_pattern(exprs, *arg);
+            }
+        }
+    }
+}
+
+pub fn slice_program(program: &Program, target: &SliceTarget) -> Result<Program, Vec<Diagnostic>> {
+    let program = normalize_program_aliases(program)?;
+    let mut slicer = Slicer::default();
+    let mut out = Program::new();
+
+    match target {
+        SliceTarget::Assert(name) => {
+            let Some(assert) = program.asserts.iter().find(|assert| &assert.name == name) else {
+                return Err(vec![unknown_target(
+                    "assert",
+                    name,
+                    program.asserts.iter().map(|assert| assert.name.as_str()),
+                )]);
+            };
+            for param in &assert.params {
+                slicer.visit_type(&param.ty);
+            }
+            slicer.visit_formula(&assert.formula);
+            out.asserts.push(assert.clone());
+        }
+        SliceTarget::Defn(name) => {
+            if !program.defns.iter().any(|defn| &defn.name == name) {
+                return Err(vec![unknown_target(
+                    "defn",
+                    name,
+                    program.defns.iter().map(|defn| defn.name.as_str()),
+                )]);
+            }
+            slicer.push(Item::Defn(name.clone()));
+        }
+    }
+
+    let ctor_owner = program
+        .data_decls
+        .iter()
+        .flat_map(|data| {
+            data.constructors
+                .iter()
+                .map(move |ctor| (ctor.name.as_str(), data.name.as_str()))
+        })
+        .collect::<HashMap<_, _>>();
+
+    while let Some(item) = slicer.queue.pop() {
+        match item {
+            Item::Relation(name) => {
+                for relation in program.relations.iter().filter(|r| r.name == name) {
+                    for sort in &relation.arg_sorts {
+                        slicer.push(Item::Type(sort.clone()));
+                    }
+                }
+                for fact in program.facts.iter().filter(|fact| fact.name == name) {
+                    for term in &fact.terms {
+                        slicer.visit_term(term);
+                    }
+                }
+                for rule in program.rules.iter().filter(|rule| rule.head.pred == name) {
+                    for term in &rule.head.terms {
+                        slicer.visit_term(term);
+                    }
+                    slicer.visit_formula(&rule.body);
+                }
+            }
+            Item::Defn(name) => {
+                for defn in program.defns.iter().filter(|defn| defn.name == name) {
+                    for param in &defn.params {
+                        slicer.visit_type(&param.ty);
+                    }
+                    slicer.visit_type(&defn.ret_type);
+                    slicer.visit_expr(&program.exprs, defn.body);
+                    if let Some(measure) = defn.measure {
+                        slicer.visit_expr(&program.exprs, measure);
+                    }
+                }
+            }
+            Item::Type(name) => {
+                for data in program.data_decls.iter().filter(|data| data.name == name) {
+                    for ctor in &data.constructors {
+                        for field in &ctor.fields {
+                            slicer.visit_type(field);
+                        }
+                    }
+                }
+                for universe in program.universes.iter().filter(|u| u.ty_name == name) {
+                    for value in &universe.values {
+                        slicer.visit_term(value);
+                    }
+                }
+            }
+            Item::Ctor(name) => {
+                if let Some(owner) = ctor_owner.get(name.as_str()) {
+                    slicer.push(Item::Type((*owner).to_string()));
+                }
+            }
+        }
+    }
+
+    let has = |item: Item| slicer.seen.contains(&item);
+    out.sorts = program
+        .sorts
+        .iter()
+        .filter(|sort| has(Item::Type(sort.name.clone())))
+        .cloned()
+        .collect();
+    out.data_decls = program
+        .data_decls
+        .iter()
+        .filter(|data| has(Item::Type(data.name.clone())))
+        .cloned()
+        .collect();
+    out.relations = program
+        .relations
+        .iter()
+        .filter(|relation| has(Item::Relation(relation.name.clone())))
+        .cloned()
+        .collect();
+    out.facts = program
+        .facts
+        .iter()
+        .filter(|fact| has(Item::Relation(fact.name.clone())))
+        .cloned()
+        .collect();
+    out.rules = program
+        .rules
+        .iter()
+        .filter(|rule| has(Item::Relation(rule.head.pred.clone())))
+        .cloned()
+        .collect();
+    out.universes = program
+        .universes
+        .iter()
+        .filter(|universe| has(Item::Type(universe.ty_name.clone())))
+        .cloned()
+        .collect();
+    out.defns = program
+        .defns
+        .iter()
+        .filter(|defn| has(Item::Defn(defn.name.clone())))
+        .cloned()
+        .collect();
+    out.exprs = program.exprs;
+    Ok(out)
+}
+
+fn unknown_target<'a>(
+    kind: &str,
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Diagnostic {
+    Diagnostic::new("E-RESOLVE", format!("unknown {kind}: {name}"), None)
+        .with_suggestion(closest_name(name, candidates))
+}
@@ -0,0 +1,223 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::{BufReader, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::Rule;
+use crate::diagnostics::Diagnostic;
+use crate::logic_engine::{DerivedFacts, GroundFact, KnowledgeBase, Value, solve_facts};
+use crate::prover::is_zstd_path;
+use crate::types::{Atom, Formula};
+
+pub const KB_SNAPSHOT_SCHEMA_VERSION: &str = "1.0.0";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnowledgeBaseSnapshot {
+    pub schema_version: String,
+    pub fingerprint: String,
+    pub relation_schemas: BTreeMap<String, Vec<String>>,
+    pub facts: BTreeSet<GroundFact>,
+    pub rules: Vec<Rule>,
+    pub strata: BTreeMap<String, usize>,
+    pub derived: BTreeMap<String, BTreeSet<Vec<Value>>>,
+}
+
+impl KnowledgeBaseSnapshot {
+    pub fn new(kb: &KnowledgeBase, derived: &DerivedFacts) -> Self {
+        Self {
+            schema_version: KB_SNAPSHOT_SCHEMA_VERSION.to_string(),
+            fingerprint: kb_fingerprint(kb),
+            relation_schemas: kb
+                .relation_schemas
+                .iter()
+                .map(|(name, sorts)| (name.clone(), sorts.clone()))
+                .collect(),
+            facts: kb.facts.iter().cloned().collect(),
+            rules: kb.rules.clone(),
+            strata: kb
+                .strata
+                .iter()
+                .map(|(name, level)| (name.clone(), *level))
+                .collect(),
+            derived: derived
+                .facts
+                .iter()
+                .map(|(pred, tuples)| (pred.clone(), tuples.clone()))
+                .collect(),
+        }
+    }
+
+    /// Whether the snapshot was taken from a knowledge base with the same
+    /// relations, facts, rules and strata as `kb`. The fingerprint only
+    /// rules out most mismatches quickly: the stored declarations themselves
+    /// are compared, so a hash collision or an edited snapshot is never
+    /// mistaken for a match.
+    pub fn matches(&self, kb: &KnowledgeBase) -> bool {
+        self.fingerprint == kb_fingerprint(kb)
+            && self.relation_schemas.len() == kb.relation_schemas.len()
+            && kb
+                .relation_schemas
+                .iter()
+                .all(|(name, sorts)| self.relation_schemas.get(name) == Some(sorts))
+            && self.facts.iter().collect::<BTreeSet<_>>() == kb.facts.iter().collect()
+            && self.rules.len() == kb.rules.len()
+            && self.rules.iter().zip(&kb.rules).all(|(stored, rule)| {
+                (&stored.head, &stored.body, stored.skolem) == (&rule.head, &rule.body, rule.skolem)
+            })
+            && self.strata.len() == kb.strata.len()
+            && kb
+                .strata
+                .iter()
+                .all(|(name, level)| self.strata.get(name) == Some(level))
+    }
+
+    pub fn knowledge_base(&self) -> KnowledgeBase {
+        KnowledgeBase {
+            relation_schemas: self
+                .relation_schemas
+                .iter()
+                .map(|(name, sorts)| (name.clone(), sorts.clone()))
+                .collect(),
+            facts: self.facts.iter().cloned().collect(),
+            rules: self.rules.clone(),
+            strata: self
+                .strata
+                .iter()
+                .map(|(name, level)| (name.clone(), *level))
+                .collect(),
+            workers: NonZeroUsize::MIN,
+        }
+    }
+
+    pub fn derived_facts(&self) -> DerivedFacts {
+        DerivedFacts {
+            facts: self
+                .derived
+                .iter()
+                .map(|(pred, tuples)| (pred.clone(), tuples.clone()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+}
+
+pub fn solve_facts_with_snapshot(
+    kb: &KnowledgeBase,
+    snapshot: Option<&KnowledgeBaseSnapshot>,
+) -> Result<DerivedFacts, Vec<Diagnostic>> {
+    match snapshot {
+        Some(snapshot) if snapshot.matches(kb) => Ok(snapshot.derived_facts()),
+        _ => solve_facts(kb),
+    }
+}
+
+#[derive(Serialize)]
+struct CanonicalKnowledgeBase<'a> {
+    relation_schemas: BTreeMap<&'a str, &'a [String]>,
+    facts: BTreeSet<&'a GroundFact>,
+    rules: Vec<(&'a Atom, &'a Formula, Option<usize>)>,
+    strata: BTreeMap<&'a str, usize>,
+}
+
+pub fn kb_fingerprint(kb: &KnowledgeBase) -> String {
+    let canonical = CanonicalKnowledgeBase {
+        relation_schemas: kb
+            .relation_schemas
+            .iter()
+            .map(|(name, sorts)| (name.as_str(), sorts.as_slice()))
+            .collect(),
+        facts: kb.facts.iter().collect(),
+        rules: kb
+            .rules
+            .iter()
+            .map(|rule| (&rule.head, &rule.body, rule.skolem))
+            .collect(),
+        strata: kb
+            .strata
+            .iter()
+            .map(|(name, level)| (name.as_str(), *level))
+            .collect(),
+    };
+    let bytes = serde_json::to_vec(&canonical).expect("knowledge base serializes to JSON");
+    content_hash(&bytes)
+}
+
+pub fn content_hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
+
+pub fn write_kb_snapshot(path: &Path, snapshot: &KnowledgeBaseSnapshot) -> Result<(), Diagnostic> {
+    let io_error = |e: std::io::Error| {
+        Diagnostic::new(
+            "E-IO",
+            format!("failed to write {}: {e}", path.display()),
+            None,
+        )
+    };
+    let serialize_error = |e: serde_json::Error| {
+        Diagnostic::new(
+            "E-IO",
+            format!("failed to serialize knowledge base snapshot: {e}"),
+            None,
+        )
+    };
+    let file = BufWriter::new(fs::File::create(path).map_err(io_error)?);
+    if is_zstd_path(path) {
+        let mut encoder = zstd::Encoder::new(file, 0).map_err(io_error)?;
+        serde_json::to_writer(&mut encoder, snapshot).map_err(serialize_error)?;
+        encoder
+            .finish()
+            .map_err(io_error)?
+            .flush()
+            .map_err(io_error)
+    } else {
+        let mut file = file;
+        serde_json::to_writer(&mut file, snapshot).map_err(serialize_error)?;
+        file.flush().map_err(io_error)
+    }
+}
+
+pub fn read_kb_snapshot(path: &Path) -> Result<KnowledgeBaseSnapshot, Diagnostic> {
+    let file = fs::File::open(path).map_err(|e| {
+        Diagnostic::new(
+            "E-IO",
+            format!("failed to read {}: {e}", path.display()),
+            None,
+        )
+    })?;
+    let reader = BufReader::new(file);
+    let parsed: Result<KnowledgeBaseSnapshot, _> = if is_zstd_path(path) {
+        zstd::Decoder::with_buffer(reader)
+            .map_err(serde_json::Error::io)
+            .and_then(serde_json::from_reader)
+    } else {
+        serde_json::from_reader(reader)
+    };
+    let snapshot = parsed.map_err(|e| {
+        Diagnostic::new(
+            "E-IO",
+            format!(
+                "failed to parse knowledge base snapshot {}: {e}",
+                path.display()
+            ),
+            None,
+        )
+    })?;
+    if snapshot.schema_version != KB_SNAPSHOT_SCHEMA_VERSION {
+        return Err(Diagnostic::new(
+            "E-IO",
+            format!(
+                "unsupported knowledge base snapshot schema {} in {} (expected {KB_SNAPSHOT_SCHEMA_VERSION})",
+                snapshot.schema_version,
+                path.display()
+            ),
+            None,
+        ));
+    }
+    Ok(snapshot)
+}
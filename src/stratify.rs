@@ -1,10 +1,91 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
 use crate::ast::Program;
-use crate::diagnostics::Diagnostic;
+use crate::diagnostics::{Diagnostic, Span};
+use crate::logic_engine::DISEQUALITY_PRED;
 use crate::types::Formula;
 
-pub fn compute_strata(program: &Program) -> Result<HashMap<String, usize>, Vec<Diagnostic>> {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Strata {
+    pub levels: HashMap<String, usize>,
+    pub edges: Vec<StratumEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StratumEdge {
+    pub head: String,
+    pub dep: String,
+    pub negated: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegativeCycle {
+    pub edges: Vec<StratumEdge>,
+}
+
+impl NegativeCycle {
+    pub fn relations(&self) -> Vec<&str> {
+        let mut out = Vec::with_capacity(self.edges.len() + 1);
+        if let Some(first) = self.edges.first() {
+            out.push(first.head.as_str());
+        }
+        out.extend(self.edges.iter().map(|edge| edge.dep.as_str()));
+        out
+    }
+
+    pub fn negated_edges(&self) -> impl Iterator<Item = &StratumEdge> {
+        self.edges.iter().filter(|edge| edge.negated)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StratifyError {
+    pub diagnostics: Vec<Diagnostic>,
+    pub cycle: Option<NegativeCycle>,
+}
+
+impl From<StratifyError> for Vec<Diagnostic> {
+    fn from(err: StratifyError) -> Self {
+        err.diagnostics
+    }
+}
+
+impl Strata {
+    pub fn level(&self, relation: &str) -> Option<usize> {
+        self.levels.get(relation).copied()
+    }
+
+    pub fn layers(&self) -> Vec<(usize, Vec<String>)> {
+        let mut layers: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for (name, level) in &self.levels {
+            layers.entry(*level).or_default().push(name.clone());
+        }
+        for names in layers.values_mut() {
+            names.sort();
+        }
+        layers.into_iter().collect()
+    }
+
+    pub fn forces_order(&self, edge: &StratumEdge) -> bool {
+        let head = self.level(&edge.head).unwrap_or(0);
+        let dep = self.level(&edge.dep).unwrap_or(0);
+        if edge.negated {
+            head == dep + 1
+        } else {
+            head == dep && head > 0
+        }
+    }
+
+    pub fn forcing_edges(&self) -> Vec<&StratumEdge> {
+        self.edges
+            .iter()
+            .filter(|edge| self.forces_order(edge))
+            .collect()
+    }
+}
+
+pub fn compute_strata(program: &Program) -> Result<Strata, StratifyError> {
     let relation_names: HashSet<String> =
         program.relations.iter().map(|r| r.name.clone()).collect();
     let n = relation_names.len().max(1);
@@ -15,6 +96,7 @@ pub fn compute_strata(program: &Program) -> Result<HashMap<String, usize>, Vec<D
 
     let mut dependencies = Vec::new();
     let mut errors = Vec::new();
+    let mut self_negation = None;
 
     for rule in &program.rules {
         let mut pos = Vec::new();
@@ -22,49 +104,56 @@ pub fn compute_strata(program: &Program) -> Result<HashMap<String, usize>, Vec<D
         flatten_formula(&rule.body, false, &mut pos, &mut neg);
 
         for p in pos {
-            dependencies.push((
-                rule.head.pred.clone(),
-                p.pred.clone(),
-                false,
-                rule.span.clone(),
-            ));
+            dependencies.push(StratumEdge {
+                head: rule.head.pred.clone(),
+                dep: p.pred.clone(),
+                negated: false,
+                span: rule.span.clone(),
+            });
         }
         for p in neg {
+            let edge = StratumEdge {
+                head: rule.head.pred.clone(),
+                dep: p.pred.clone(),
+                negated: true,
+                span: rule.span.clone(),
+            };
             if p.pred == rule.head.pred {
                 errors.push(Diagnostic::new(
                     "E-STRATIFY",
                     format!("self-negation detected on relation {}", p.pred),
                     Some(rule.span.clone()),
                 ));
+                self_negation.get_or_insert_with(|| NegativeCycle {
+                    edges: vec![edge.clone()],
+                });
             }
-            dependencies.push((
-                rule.head.pred.clone(),
-                p.pred.clone(),
-                true,
-                rule.span.clone(),
-            ));
+            dependencies.push(edge);
         }
     }
 
     if !errors.is_empty() {
-        return Err(errors);
+        return Err(StratifyError {
+            diagnostics: errors,
+            cycle: self_negation,
+        });
     }
 
     for _ in 0..(n * n + 1) {
         let mut changed = false;
-        for (head, dep, is_neg, _) in &dependencies {
-            let sh = *strata.get(head).unwrap_or(&0);
-            let sd = *strata.get(dep).unwrap_or(&0);
-            let required = if *is_neg { sd + 1 } else { sd };
+        for edge in &dependencies {
+            let sh = *strata.get(&edge.head).unwrap_or(&0);
+            let sd = *strata.get(&edge.dep).unwrap_or(&0);
+            let required = if edge.negated { sd + 1 } else { sd };
             if sh < required {
-                strata.insert(head.clone(), required);
+                strata.insert(edge.head.clone(), required);
                 if required > n {
-                    errors.push(Diagnostic::new(
-                        "E-STRATIFY",
-                        "negative dependency cycle detected",
-                        None,
-                    ));
-                    return Err(errors);
+                    let cycle = find_minimal_negative_cycle(&dependencies);
+                    errors.push(negative_cycle_diagnostic(cycle.as_ref()));
+                    return Err(StratifyError {
+                        diagnostics: errors,
+                        cycle,
+                    });
                 }
                 changed = true;
             }
@@ -74,27 +163,146 @@ pub fn compute_strata(program: &Program) -> Result<HashMap<String, usize>, Vec<D
         }
     }
 
-    for (head, dep, is_neg, span) in &dependencies {
-        let sh = *strata.get(head).unwrap_or(&0);
-        let sd = *strata.get(dep).unwrap_or(&0);
-        if (!is_neg && sh < sd) || (*is_neg && sh <= sd) {
+    for edge in &dependencies {
+        let sh = *strata.get(&edge.head).unwrap_or(&0);
+        let sd = *strata.get(&edge.dep).unwrap_or(&0);
+        if (!edge.negated && sh < sd) || (edge.negated && sh <= sd) {
             errors.push(Diagnostic::new(
                 "E-STRATIFY",
                 format!(
                     "stratification constraint violated: {} {} {}",
-                    head,
-                    if *is_neg { ">" } else { ">=" },
-                    dep
+                    edge.head,
+                    if edge.negated { ">" } else { ">=" },
+                    edge.dep
                 ),
-                Some(span.clone()),
+                Some(edge.span.clone()),
             ));
         }
     }
 
     if errors.is_empty() {
-        Ok(strata)
+        Ok(Strata {
+            levels: strata,
+            edges: dependencies,
+        })
     } else {
-        Err(errors)
+        Err(StratifyError {
+            diagnostics: errors,
+            cycle: None,
+        })
+    }
+}
+
+fn negative_cycle_diagnostic(cycle: Option<&NegativeCycle>) -> Diagnostic {
+    let Some(cycle) = cycle else {
+        return Diagnostic::new("E-STRATIFY", "negative dependency cycle detected", None);
+    };
+
+    let negative = &cycle.edges[0];
+    let mut path = negative.head.clone();
+    for edge in &cycle.edges {
+        let arrow = if edge.negated { " -(not)-> " } else { " -> " };
+        path.push_str(arrow);
+        path.push_str(&edge.dep);
+    }
+    let mut locations = Vec::new();
+    for edge in &cycle.edges {
+        let location = format_rule_location(&edge.span, negative.span.file_id.as_deref());
+        if !locations.contains(&location) {
+            locations.push(location);
+        }
+    }
+
+    let mut diagnostic = Diagnostic::new(
+        "E-STRATIFY",
+        format!(
+            "negative dependency cycle detected: {path} (rules at {}); break the cycle by removing `(not ({} ...))` from the rule for {} or by deriving {} without depending on {}",
+            locations.join(", "),
+            negative.dep,
+            negative.head,
+            negative.dep,
+            negative.head
+        ),
+        Some(negative.span.clone()),
+    )
+    .with_reason("negative_cycle");
+    for edge in &cycle.edges[1..] {
+        let label = if edge.negated {
+            format!("rule for {} negates {} here", edge.head, edge.dep)
+        } else {
+            format!("rule for {} depends on {} here", edge.head, edge.dep)
+        };
+        diagnostic = diagnostic.with_related(edge.span.clone(), label);
+    }
+    diagnostic
+}
+
+fn find_minimal_negative_cycle(dependencies: &[StratumEdge]) -> Option<NegativeCycle> {
+    let mut outgoing: HashMap<&str, Vec<&StratumEdge>> = HashMap::new();
+    for edge in dependencies {
+        outgoing.entry(edge.head.as_str()).or_default().push(edge);
+    }
+
+    let mut best: Option<Vec<&StratumEdge>> = None;
+    for negative in dependencies.iter().filter(|edge| edge.negated) {
+        let Some(back) = shortest_path(&outgoing, &negative.dep, &negative.head) else {
+            continue;
+        };
+        if best
+            .as_ref()
+            .is_none_or(|current| back.len() + 1 < current.len())
+        {
+            let mut cycle = vec![negative];
+            cycle.extend(back);
+            best = Some(cycle);
+        }
+    }
+    best.map(|edges| NegativeCycle {
+        edges: edges.into_iter().cloned().collect(),
+    })
+}
+
+fn shortest_path<'a>(
+    outgoing: &HashMap<&str, Vec<&'a StratumEdge>>,
+    from: &str,
+    to: &str,
+) -> Option<Vec<&'a StratumEdge>> {
+    if from == to {
+        return Some(Vec::new());
+    }
+
+    let mut previous: HashMap<&str, &'a StratumEdge> = HashMap::new();
+    let mut visited: HashSet<&str> = HashSet::from([from]);
+    let mut queue = VecDeque::from([from]);
+    while let Some(current) = queue.pop_front() {
+        for edge in outgoing.get(current).into_iter().flatten() {
+            if !visited.insert(edge.dep.as_str()) {
+                continue;
+            }
+            previous.insert(edge.dep.as_str(), edge);
+            if edge.dep == to {
+                let mut path = Vec::new();
+                let mut cursor = to;
+                while cursor != from {
+                    let edge = previous[cursor];
+                    path.push(edge);
+                    cursor = edge.head.as_str();
+                }
+                path.reverse();
+                return Some(path);
+            }
+            queue.push_back(edge.dep.as_str());
+        }
+    }
+    None
+}
+
+fn format_rule_location(span: &Span, primary_file: Option<&str>) -> String {
+    match span.file_id.as_deref() {
+        Some(file) if Some(file) != primary_file => {
+            format!("{file}:{}:{}", span.line, span.column)
+        }
+        _ => format!("{}:{}", span.line, span.column),
     }
 }
 
@@ -106,6 +314,7 @@ fn flatten_formula<'a>(
 ) {
     match formula {
         Formula::True => {}
+        Formula::Atom(atom) if atom.pred == DISEQUALITY_PRED => {}
         Formula::Atom(atom) => {
             if negated {
                 neg.push(atom);
@@ -0,0 +1,135 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, OnceLock, RwLock, Weak};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+#[derive(Default)]
+struct Interner {
+    names: HashMap<Box<str>, Weak<str>>,
+    swept_len: usize,
+}
+
+static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        let interner = INTERNER.get_or_init(Default::default);
+        let live = |interner: &Interner| interner.names.get(name).and_then(Weak::upgrade);
+        if let Some(interned) = live(&interner.read().unwrap_or_else(|e| e.into_inner())) {
+            return Symbol(interned);
+        }
+        let mut interner = interner.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = live(&interner) {
+            return Symbol(existing);
+        }
+        // Freed names are swept whenever the table has doubled since the
+        // last sweep, which keeps it proportional to the names in use.
+        if interner.names.len() >= 2 * interner.swept_len.max(64) {
+            interner.names.retain(|_, weak| weak.strong_count() > 0);
+            interner.swept_len = interner.names.len();
+        }
+        let interned: Arc<str> = Arc::from(name);
+        interner
+            .names
+            .insert(name.into(), Arc::downgrade(&interned));
+        Symbol(interned)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(Arc::as_ptr(&self.0), state);
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            Ordering::Equal
+        } else {
+            self.0.cmp(&other.0)
+        }
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::intern(&name)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Symbol::intern(&name))
+    }
+}
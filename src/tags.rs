@@ -0,0 +1,122 @@
+use std::fmt::Write;
+
+use crate::ast::Program;
+use crate::diagnostics::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagKind {
+    Sort,
+    Data,
+    Constructor,
+    Relation,
+    Alias,
+    Const,
+    Assert,
+    Defn,
+}
+
+impl TagKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TagKind::Sort => "sort",
+            TagKind::Data => "data",
+            TagKind::Constructor => "constructor",
+            TagKind::Relation => "relation",
+            TagKind::Alias => "alias",
+            TagKind::Const => "constant",
+            TagKind::Assert => "assert",
+            TagKind::Defn => "defn",
+        }
+    }
+
+    pub fn letter(self) -> char {
+        match self {
+            TagKind::Sort => 's',
+            TagKind::Data => 'd',
+            TagKind::Constructor => 'c',
+            TagKind::Relation => 'r',
+            TagKind::Alias => 'l',
+            TagKind::Const => 'k',
+            TagKind::Assert => 'a',
+            TagKind::Defn => 'f',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    pub kind: TagKind,
+    pub scope: Option<String>,
+    pub span: Span,
+}
+
+pub fn collect_tags(program: &Program) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    let mut push = |name: &str, kind: TagKind, scope: Option<&str>, span: &Span| {
+        tags.push(Tag {
+            name: name.to_string(),
+            kind,
+            scope: scope.map(ToOwned::to_owned),
+            span: span.clone(),
+        });
+    };
+    for sort in &program.sorts {
+        push(&sort.name, TagKind::Sort, None, &sort.span);
+    }
+    for data in &program.data_decls {
+        push(&data.name, TagKind::Data, None, &data.span);
+        for ctor in &data.constructors {
+            push(
+                &ctor.name,
+                TagKind::Constructor,
+                Some(&data.name),
+                &ctor.span,
+            );
+        }
+    }
+    for relation in &program.relations {
+        push(&relation.name, TagKind::Relation, None, &relation.span);
+    }
+    for alias in &program.aliases {
+        push(&alias.alias, TagKind::Alias, None, &alias.span);
+    }
+    for constant in &program.consts {
+        push(&constant.name, TagKind::Const, None, &constant.span);
+    }
+    for assertion in &program.asserts {
+        push(&assertion.name, TagKind::Assert, None, &assertion.span);
+    }
+    for defn in &program.defns {
+        push(&defn.name, TagKind::Defn, None, &defn.span);
+    }
+    tags.sort_by(|a, b| {
+        (&a.name, &a.span.file_id, a.span.start).cmp(&(&b.name, &b.span.file_id, b.span.start))
+    });
+    tags
+}
+
+pub fn render_ctags(tags: &[Tag], default_file: &str) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "!_TAG_FILE_FORMAT\t2\t/extended format; --format=1 will not append ;\" to lines/\n",
+    );
+    out.push_str("!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/\n");
+    out.push_str("!_TAG_PROGRAM_NAME\tdtl\t//\n");
+    for tag in tags {
+        let file = tag.span.file_id.as_deref().unwrap_or(default_file);
+        let _ = write!(
+            out,
+            "{}\t{file}\t{};\"\t{}\tline:{}",
+            tag.name,
+            tag.span.line,
+            tag.kind.letter(),
+            tag.span.line
+        );
+        if let Some(scope) = &tag.scope {
+            let _ = write!(out, "\tdata:{scope}");
+        }
+        out.push('\n');
+    }
+    out
+}
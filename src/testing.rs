@@ -0,0 +1,120 @@
+use std::fmt::Write;
+
+use crate::diagnostics::{Diagnostic, Span};
+use crate::parser::parse_program;
+use crate::prover::{ObligationTrace, ProofTrace, prove_program};
+use crate::typecheck::check_program;
+
+pub fn check_source_snapshot(src: &str) -> String {
+    let result = parse_program(src).and_then(|program| check_program(&program));
+    match result {
+        Ok(report) => format!(
+            "status: ok\nfunctions_checked: {}\n",
+            report.functions_checked
+        ),
+        Err(diags) => diagnostics_snapshot(&diags),
+    }
+}
+
+pub fn prove_source_snapshot(src: &str) -> String {
+    match parse_program(src).and_then(|program| prove_program(&program)) {
+        Ok(trace) => proof_snapshot(&trace),
+        Err(diags) => diagnostics_snapshot(&diags),
+    }
+}
+
+pub fn proof_snapshot(trace: &ProofTrace) -> String {
+    let status = if trace.summary.failed == 0 {
+        "ok"
+    } else {
+        "failed"
+    };
+    let mut out = format!(
+        "status: {status}\nobligations: {} proved, {} failed, {} total\n",
+        trace.summary.proved, trace.summary.failed, trace.summary.total
+    );
+    for obligation in &trace.obligations {
+        push_obligation(&mut out, obligation);
+    }
+    out
+}
+
+pub fn diagnostics_snapshot(diags: &[Diagnostic]) -> String {
+    let mut sorted = diags.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|diag| {
+        (
+            diag.span.as_ref().map(|span| (span.line, span.column)),
+            diag.code,
+            diag.message.as_str(),
+        )
+    });
+
+    let mut out = String::from("status: error\n");
+    for diag in sorted {
+        let _ = writeln!(
+            out,
+            "{} {}: {}",
+            diag.code,
+            render_position(diag.span.as_ref()),
+            diag.message
+        );
+        if let Some(reason) = diag.reason() {
+            let _ = writeln!(out, "  reason: {reason}");
+        }
+        if let Some(indices) = diag.arg_indices() {
+            let indices = indices
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = writeln!(out, "  arg_indices: {indices}");
+        }
+        if let Some(suggestion) = diag.suggestion() {
+            let _ = writeln!(out, "  suggestion: {suggestion}");
+        }
+        for related in diag.related() {
+            let _ = writeln!(
+                out,
+                "  related: {} at {}",
+                related.label,
+                render_position(Some(&related.span))
+            );
+        }
+        for fix in diag.fixes() {
+            let _ = writeln!(
+                out,
+                "  fix: {} replace {} bytes with {:?}",
+                render_position(Some(&fix.span)),
+                fix.span.end - fix.span.start,
+                fix.replacement
+            );
+        }
+    }
+    out
+}
+
+fn push_obligation(out: &mut String, obligation: &ObligationTrace) {
+    let _ = writeln!(out, "{}: {}", obligation.id, obligation.result);
+    if let Some(message) = &obligation.message {
+        let _ = writeln!(out, "  message: {message}");
+    }
+    let Some(counterexample) = &obligation.counterexample else {
+        return;
+    };
+    for binding in &counterexample.valuation {
+        let _ = writeln!(out, "  let {} = {}", binding.name, binding.value);
+    }
+    for premise in &counterexample.premises {
+        let _ = writeln!(out, "  premise: {premise}");
+    }
+    for goal in &counterexample.missing_goals {
+        let _ = writeln!(out, "  missing: {goal}");
+    }
+}
+
+fn render_position(span: Option<&Span>) -> String {
+    match span {
+        Some(span) => format!("{}:{}", span.line, span.column),
+        None => "-".to_string(),
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PhaseTimings {
+    pub phases: Vec<PhaseTiming>,
+    pub total_micros: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub micros: u64,
+}
+
+impl PhaseTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, phase: &str, elapsed: Duration) {
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        tracing::debug!(phase, micros, "phase finished");
+        self.total_micros = self.total_micros.saturating_add(micros);
+        if let Some(existing) = self.phases.iter_mut().find(|p| p.phase == phase) {
+            existing.micros = existing.micros.saturating_add(micros);
+        } else {
+            self.phases.push(PhaseTiming {
+                phase: phase.to_string(),
+                micros,
+            });
+        }
+    }
+
+    pub fn measure<T>(&mut self, phase: &str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let out = f();
+        self.record(phase, started.elapsed());
+        out
+    }
+
+    pub fn render_text(&self) -> String {
+        let width = self
+            .phases
+            .iter()
+            .map(|p| p.phase.chars().count())
+            .chain(std::iter::once("total".len()))
+            .max()
+            .unwrap_or(0);
+        let mut out = String::from("timings:\n");
+        for phase in &self.phases {
+            out.push_str(&format!(
+                "  {:<width$}  {}\n",
+                phase.phase,
+                format_micros(phase.micros)
+            ));
+        }
+        out.push_str(&format!(
+            "  {:<width$}  {}\n",
+            "total",
+            format_micros(self.total_micros)
+        ));
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PhaseStats {
+    pub phase: String,
+    pub samples: usize,
+    pub mean_micros: u64,
+    pub p95_micros: u64,
+    pub min_micros: u64,
+    pub max_micros: u64,
+}
+
+impl PhaseStats {
+    pub fn from_samples(phase: &str, samples: &[Duration]) -> Option<Self> {
+        let mut micros = samples
+            .iter()
+            .map(|sample| u64::try_from(sample.as_micros()).unwrap_or(u64::MAX))
+            .collect::<Vec<_>>();
+        micros.sort_unstable();
+        let len = micros.len();
+        let min_micros = *micros.first()?;
+        let max_micros = *micros.last()?;
+        let total = micros
+            .iter()
+            .fold(0u128, |acc, value| acc + u128::from(*value));
+        let mean_micros = u64::try_from(total / len as u128).unwrap_or(u64::MAX);
+        let rank = (len * 95).div_ceil(100).max(1);
+        Some(Self {
+            phase: phase.to_string(),
+            samples: len,
+            mean_micros,
+            p95_micros: micros[rank - 1],
+            min_micros,
+            max_micros,
+        })
+    }
+
+    pub fn render_text(&self, width: usize) -> String {
+        format!(
+            "  {:<width$}  mean {:>12}  p95 {:>12}  min {:>12}  max {:>12}\n",
+            self.phase,
+            format_micros(self.mean_micros),
+            format_micros(self.p95_micros),
+            format_micros(self.min_micros),
+            format_micros(self.max_micros)
+        )
+    }
+}
+
+pub fn format_micros(micros: u64) -> String {
+    format!("{}.{:03} ms", micros / 1000, micros % 1000)
+}
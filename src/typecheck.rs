@@ -1,11 +1,25 @@
+use std::cell::{OnceCell, RefCell};
 use std::collections::{HashMap, HashSet};
-
-use crate::ast::{Defn, Expr, MatchArm, Pattern, Program};
-use crate::diagnostics::Diagnostic;
-use crate::logic_engine::{DerivedFacts, GroundFact, KnowledgeBase, Value, solve_facts};
+use std::time::Instant;
+
+use crate::ast::{
+    Defn, Expr, ExprArena, ExprId, LocalFn, MatchArm, Pattern, PatternId, Program, RelationDecl,
+};
+use crate::diagnostics::{Diagnostic, Span, closest_name, dedup_diagnostics};
+use crate::fact_cache::{FixpointOptions, solve_facts_cached};
+use crate::fmt::render_type;
+use crate::interval::{
+    ArithOp, ComparisonOp, Interval, mentions_comparison, split_conjuncts, var_interval,
+};
+use crate::logic_engine::{
+    DISEQUALITY_PRED, DerivedFacts, GroundFact, KnowledgeBase, Value, disequality_holds,
+    solve_facts, solve_facts_incremental, solve_facts_iter, value_to_string,
+};
 use crate::name_resolve::{normalize_program_aliases, resolve_program};
 use crate::reference_prover::reference_prove_program_results;
 use crate::stratify::compute_strata;
+use crate::symbol::Symbol;
+use crate::timings::PhaseTimings;
 use crate::types::{Atom, Formula, LogicTerm, Type};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -19,6 +33,7 @@ struct FunctionSig {
     params: Vec<Type>,
     ret: Type,
     param_names: Vec<String>,
+    span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -28,12 +43,15 @@ struct ConstructorSig {
 }
 
 #[derive(Debug, Clone)]
-struct TypeContext {
+struct TypeContext<'a> {
+    exprs: &'a ExprArena,
     relation_sigs: HashMap<String, Vec<Type>>,
     function_sigs: HashMap<String, FunctionSig>,
     constructor_sigs: HashMap<String, ConstructorSig>,
     data_constructors: HashMap<String, Vec<String>>,
     kb_template: KnowledgeBase,
+    kb_derived: OnceCell<Option<DerivedFacts>>,
+    entailments: RefCell<HashMap<(Formula, Formula), bool>>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -43,8 +61,10 @@ struct OriginInfo {
 }
 
 struct RecursionEdgeRule<'a> {
+    exprs: &'a ExprArena,
     caller_name: &'a str,
     caller_adt_param_indices: &'a HashSet<usize>,
+    caller_measured: bool,
     scc_callee_rules: &'a HashMap<String, CalleeRule>,
 }
 
@@ -53,59 +73,102 @@ struct CalleeRule {
     callee_name: String,
     adt_param_indices: HashSet<usize>,
     param_len: usize,
+    measured: bool,
 }
 
-const TOTAL_REASON_NON_TAIL_CALL: &str = "non_tail_recursive_call";
 const TOTAL_REASON_ARITY_MISMATCH: &str = "recursive_call_arity_mismatch";
 const TOTAL_REASON_NO_ADT_PARAM: &str = "no_adt_parameter";
 const TOTAL_REASON_NON_DECREASING_ARG: &str = "non_decreasing_argument";
+const TOTAL_REASON_RECURSIVE_MEASURE: &str = "recursive_measure";
+
+const MAX_LEXICOGRAPHIC_CANDIDATES: usize = 4096;
+
+#[derive(Debug, Clone)]
+struct RecursiveCall {
+    caller: String,
+    callee: String,
+    span: Span,
+    descents: Vec<(usize, usize, bool)>,
+    positions: Vec<usize>,
+}
+
+#[derive(Default)]
+struct TotalityOutput {
+    errors: Vec<Diagnostic>,
+    calls: Vec<RecursiveCall>,
+}
 
 pub fn check_program(program: &Program) -> Result<TypeReport, Vec<Diagnostic>> {
-    let normalized = normalize_program_aliases(program)?;
-    let mut errors = resolve_program(&normalized);
+    check_program_with_timings(
+        program,
+        FixpointOptions::default(),
+        &mut PhaseTimings::new(),
+    )
+}
+
+pub fn check_program_with_timings(
+    program: &Program,
+    fixpoint: FixpointOptions<'_>,
+    timings: &mut PhaseTimings,
+) -> Result<TypeReport, Vec<Diagnostic>> {
+    check_phases(program, fixpoint, timings).map_err(|mut errors| {
+        dedup_diagnostics(&mut errors);
+        errors
+    })
+}
+
+fn check_phases(
+    program: &Program,
+    fixpoint: FixpointOptions<'_>,
+    timings: &mut PhaseTimings,
+) -> Result<TypeReport, Vec<Diagnostic>> {
+    let (normalized, mut errors) = timings.measure("resolve", || {
+        normalize_program_aliases(program).map(|normalized| {
+            let errors = resolve_program(&normalized);
+            (normalized, errors)
+        })
+    })?;
     if !errors.is_empty() {
         return Err(errors);
     }
 
-    if let Err(mut stratify_errors) = compute_strata(&normalized) {
-        errors.append(&mut stratify_errors);
-        return Err(errors);
+    match timings.measure("stratify", || compute_strata(&normalized)) {
+        Ok(strata) => tracing::info!(
+            relations = strata.levels.len(),
+            strata = strata.layers().len(),
+            "strata computed"
+        ),
+        Err(stratify_error) => {
+            errors.extend(stratify_error.diagnostics);
+            return Err(errors);
+        }
     }
 
-    let mut totality_errors = check_totality(&normalized);
+    let mut totality_errors = timings.measure("typecheck", || check_totality(&normalized));
     if !totality_errors.is_empty() {
         errors.append(&mut totality_errors);
         return Err(errors);
     }
 
-    let kb = KnowledgeBase::from_program(&normalized)?;
-    let _ = solve_facts(&kb)?;
-
-    let data_names: HashSet<String> = normalized
-        .data_decls
-        .iter()
-        .map(|d| d.name.clone())
-        .collect();
-    let relation_sigs = build_relation_sigs(&normalized, &data_names);
-    let function_sigs = build_function_sigs(&normalized, &data_names);
-    let constructor_sigs = build_constructor_sigs(&normalized, &data_names);
-    let data_constructors = build_data_constructor_map(&normalized);
-
-    let ctx = TypeContext {
-        relation_sigs,
-        function_sigs,
-        constructor_sigs,
-        data_constructors,
-        kb_template: kb,
-    };
+    let (kb, derived) = timings.measure("fixpoint", || -> Result<_, Vec<Diagnostic>> {
+        let kb = fixpoint.knowledge_base(&normalized)?;
+        let derived = solve_facts_cached(&kb, fixpoint)?;
+        Ok((kb, derived))
+    })?;
+    let typecheck_started = Instant::now();
+    errors.extend(check_relation_refinements(&normalized, &kb, &derived));
+    errors.extend(check_relation_keys(&normalized, &kb, &derived));
+    let ctx = build_type_context(&normalized, kb, Some(derived));
 
     for defn in &normalized.defns {
         if let Err(mut e) = check_defn(defn, &normalized, &ctx) {
             errors.append(&mut e);
         }
     }
+    timings.record("typecheck", typecheck_started.elapsed());
 
     if errors.is_empty() {
+        tracing::info!(functions = normalized.defns.len(), "typecheck finished");
         Ok(TypeReport {
             functions_checked: normalized.defns.len(),
             errors: 0,
@@ -115,15 +178,216 @@ pub fn check_program(program: &Program) -> Result<TypeReport, Vec<Diagnostic>> {
     }
 }
 
-fn check_totality(program: &Program) -> Vec<Diagnostic> {
+pub(crate) fn checked_and_solved(
+    program: &Program,
+) -> Result<(Program, DerivedFacts), Vec<Diagnostic>> {
+    check_program(program)?;
+    let program = normalize_program_aliases(program)?;
+    let kb = KnowledgeBase::from_program(&program)?;
+    let derived = solve_facts(&kb)?;
+    Ok((program, derived))
+}
+
+pub fn type_at(program: &Program, file: &str, offset: usize) -> Option<Type> {
+    let normalized = normalize_program_aliases(program).ok()?;
+    let kb = KnowledgeBase::from_program(&normalized).ok()?;
+    let ctx = build_type_context(&normalized, kb, None);
+    let cursor = Cursor { file, offset };
+
+    for assertion in &normalized.asserts {
+        if let Some(param) = assertion.params.iter().find(|p| cursor.covers(&p.span)) {
+            return Some(canonicalize_type_for_ctx(&param.ty, &ctx));
+        }
+    }
+    for defn in &normalized.defns {
+        let mut env = HashMap::new();
+        for param in &defn.params {
+            let ty = canonicalize_type_for_ctx(&param.ty, &ctx);
+            if cursor.covers(&param.span) {
+                return Some(ty);
+            }
+            env.insert(param.name.clone(), ty);
+        }
+        if let Some(ty) = type_at_expr(defn.body, &env, &ctx, &cursor) {
+            return Some(ty);
+        }
+    }
+    None
+}
+
+struct Cursor<'a> {
+    file: &'a str,
+    offset: usize,
+}
+
+impl Cursor<'_> {
+    fn covers(&self, span: &Span) -> bool {
+        span.file_id.as_deref().is_none_or(|id| id == self.file)
+            && span.start <= self.offset
+            && self.offset < span.end
+    }
+}
+
+fn type_at_expr(
+    id: ExprId,
+    env: &HashMap<String, Type>,
+    ctx: &TypeContext,
+    cursor: &Cursor<'_>,
+) -> Option<Type> {
+    let expr = &ctx.exprs[id];
+    if !cursor.covers(expr.span()) {
+        return None;
+    }
+    let inner = match expr {
+        Expr::Var { .. }
+        | Expr::Symbol { .. }
+        | Expr::Int { .. }
+        | Expr::Bool { .. }
+        | Expr::Hole { .. } => None,
+        Expr::Call { args, .. } => args
+            .iter()
+            .find_map(|arg| type_at_expr(*arg, env, ctx, cursor)),
+        Expr::Let { bindings, body, .. } => {
+            let mut local_env = env.clone();
+            for (name, bexpr, bspan) in bindings {
+                if let Some(ty) = type_at_expr(*bexpr, &local_env, ctx, cursor) {
+                    return Some(ty);
+                }
+                let ty = infer_expr(*bexpr, &local_env, ctx).ok()?;
+                if cursor.covers(bspan) {
+                    return Some(ty);
+                }
+                local_env.insert(name.clone(), ty);
+            }
+            type_at_expr(*body, &local_env, ctx, cursor)
+        }
+        Expr::LetFn { fns, body, .. } => {
+            let mut local_env = env.clone();
+            for f in fns {
+                if cursor.covers(&f.span) {
+                    if let Some(param) = f.params.iter().find(|p| cursor.covers(&p.span)) {
+                        return Some(canonicalize_type_for_ctx(&param.ty, ctx));
+                    }
+                    return type_at_expr(f.body, &local_fn_env(f, &local_env, ctx), ctx, cursor);
+                }
+                local_env.insert(f.name.clone(), local_fn_type(f, ctx));
+            }
+            type_at_expr(*body, &local_env, ctx, cursor)
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            let then_env = narrow_by_condition(*cond, env, ctx);
+            type_at_expr(*cond, env, ctx, cursor)
+                .or_else(|| {
+                    type_at_expr(*then_branch, then_env.as_ref().unwrap_or(env), ctx, cursor)
+                })
+                .or_else(|| type_at_expr(*else_branch, env, ctx, cursor))
+        }
+        Expr::Match {
+            scrutinee, arms, ..
+        } => {
+            if let Some(ty) = type_at_expr(*scrutinee, env, ctx, cursor) {
+                return Some(ty);
+            }
+            let scrutinee_ty = infer_expr(*scrutinee, env, ctx).ok()?;
+            let arm = arms.iter().find(|arm| cursor.covers(&arm.span))?;
+            let mut arm_env = env.clone();
+            bind_pattern(arm.pattern, &scrutinee_ty, &mut arm_env, ctx).ok()?;
+            refine_arm_env(
+                ctx.exprs,
+                *scrutinee,
+                &scrutinee_ty,
+                arm.pattern,
+                env,
+                &mut arm_env,
+            );
+            if let Some(ty) = type_at_pattern(arm.pattern, &arm_env, ctx, cursor) {
+                return Some(ty);
+            }
+            type_at_expr(arm.body, &arm_env, ctx, cursor)
+        }
+    };
+    inner.or_else(|| infer_expr(id, env, ctx).ok())
+}
+
+fn type_at_pattern(
+    id: PatternId,
+    env: &HashMap<String, Type>,
+    ctx: &TypeContext,
+    cursor: &Cursor<'_>,
+) -> Option<Type> {
+    let pattern = &ctx.exprs[id];
+    if !cursor.covers(pattern.span()) {
+        return None;
+    }
+    match pattern {
+        Pattern::Wildcard { .. } => None,
+        Pattern::Var { name, .. } => env.get(name).cloned(),
+        Pattern::Symbol { .. } => Some(Type::Symbol),
+        Pattern::Int { .. } => Some(Type::Int),
+        Pattern::Bool { .. } => Some(Type::Bool),
+        Pattern::Ctor { name, args, .. } => args
+            .iter()
+            .find_map(|arg| type_at_pattern(*arg, env, ctx, cursor))
+            .or_else(|| ctx.constructor_sigs.get(name).map(|sig| sig.ret.clone())),
+    }
+}
+
+fn build_type_context(
+    program: &Program,
+    kb: KnowledgeBase,
+    derived: Option<DerivedFacts>,
+) -> TypeContext<'_> {
+    let data_names: HashSet<String> = program.data_decls.iter().map(|d| d.name.clone()).collect();
+    TypeContext {
+        exprs: &program.exprs,
+        relation_sigs: build_relation_sigs(program, &data_names),
+        function_sigs: build_function_sigs(program, &data_names),
+        constructor_sigs: build_constructor_sigs(program, &data_names),
+        data_constructors: build_data_constructor_map(program),
+        kb_template: kb,
+        kb_derived: derived.map(Some).map(OnceCell::from).unwrap_or_default(),
+        entailments: RefCell::default(),
+    }
+}
+
+fn defn_call_graph(program: &Program) -> (HashSet<String>, HashMap<String, HashSet<String>>) {
     let function_names: HashSet<String> = program.defns.iter().map(|d| d.name.clone()).collect();
     let mut calls: HashMap<String, HashSet<String>> = HashMap::new();
-
     for defn in &program.defns {
         let mut called = HashSet::new();
-        collect_function_calls(&defn.body, &function_names, &mut called);
+        for expr in std::iter::once(defn.body).chain(defn.measure) {
+            collect_function_calls(&program.exprs, expr, &function_names, &mut called);
+        }
         calls.insert(defn.name.clone(), called);
     }
+    (function_names, calls)
+}
+
+fn is_recursive_component(component: &[String], calls: &HashMap<String, HashSet<String>>) -> bool {
+    component.len() > 1
+        || component
+            .first()
+            .and_then(|name| calls.get(name).map(|nexts| nexts.contains(name)))
+            .unwrap_or(false)
+}
+
+pub(crate) fn recursive_groups(program: &Program) -> HashMap<String, usize> {
+    let (function_names, calls) = defn_call_graph(program);
+    strongly_connected_components(&function_names, &calls)
+        .into_iter()
+        .filter(|component| is_recursive_component(component, &calls))
+        .enumerate()
+        .flat_map(|(idx, component)| component.into_iter().map(move |name| (name, idx)))
+        .collect()
+}
+
+fn check_totality(program: &Program) -> Vec<Diagnostic> {
+    let (function_names, calls) = defn_call_graph(program);
 
     let mut errors = Vec::new();
     let data_names: HashSet<String> = program.data_decls.iter().map(|d| d.name.clone()).collect();
@@ -137,18 +401,36 @@ fn check_totality(program: &Program) -> Vec<Diagnostic> {
     let components = strongly_connected_components(&function_names, &calls);
 
     for component in components {
-        let recursive_component = if component.len() > 1 {
-            true
-        } else {
-            component
-                .first()
-                .and_then(|name| calls.get(name).map(|nexts| nexts.contains(name)))
-                .unwrap_or(false)
-        };
-        if !recursive_component {
+        if !is_recursive_component(&component, &calls) {
             continue;
         }
 
+        for name in &component {
+            let Some(measure) = defn_map.get(name).and_then(|defn| defn.measure) else {
+                continue;
+            };
+            let mut called = HashSet::new();
+            collect_function_calls(&program.exprs, measure, &function_names, &mut called);
+            let mut recursive = called
+                .into_iter()
+                .filter(|callee| component.contains(callee))
+                .collect::<Vec<_>>();
+            recursive.sort();
+            for callee in recursive {
+                errors.push(
+                    Diagnostic::new(
+                        "E-TOTAL",
+                        format!(
+                            "termination measure of {name} calls {callee} of its own recursive group"
+                        ),
+                        Some(program.exprs.span(measure).clone()),
+                    )
+                    .with_reason(TOTAL_REASON_RECURSIVE_MEASURE),
+                );
+            }
+        }
+
+        let mut output = TotalityOutput::default();
         let mut scc_callee_rules = HashMap::new();
         for callee_name in &component {
             if let Some(defn) = defn_map.get(callee_name) {
@@ -161,6 +443,7 @@ fn check_totality(program: &Program) -> Vec<Diagnostic> {
                             .cloned()
                             .unwrap_or_default(),
                         param_len: defn.params.len(),
+                        measured: defn.measure.is_some(),
                     },
                 );
             }
@@ -186,17 +469,156 @@ fn check_totality(program: &Program) -> Vec<Diagnostic> {
             }
 
             let rule = RecursionEdgeRule {
+                exprs: &program.exprs,
                 caller_name,
                 caller_adt_param_indices,
+                caller_measured: defn.measure.is_some(),
                 scc_callee_rules: &scc_callee_rules,
             };
-            collect_totality_violations(&defn.body, true, &origin_env, &rule, &mut errors);
+            collect_totality_violations(defn.body, &origin_env, &rule, &mut output);
+        }
+        errors.append(&mut output.errors);
+        for call in unordered_recursive_calls(output.calls, &adt_param_indices_map) {
+            errors.push(non_decreasing_call_error(&call));
         }
     }
 
     errors
 }
 
+// Removes the calls ordered by each component of a lexicographic order on
+// the group and returns the calls no order covers. A component either picks
+// an ADT parameter of every function, lowering calls whose argument there is
+// a strict subterm of the caller's parameter there, or ranks the functions,
+// lowering calls into a lower-ranked function. A component is usable once
+// every remaining call keeps or lowers it, and removing calls only makes
+// more components usable, so taking usable components greedily finds an
+// order whenever one exists.
+fn unordered_recursive_calls(
+    mut calls: Vec<RecursiveCall>,
+    adt_param_indices_map: &HashMap<String, HashSet<usize>>,
+) -> Vec<RecursiveCall> {
+    while !calls.is_empty() {
+        let before = calls.len();
+        if let Some(assignment) = parameter_component(&calls, adt_param_indices_map) {
+            calls.retain(|call| call_descent(call, &assignment) != Some(true));
+        } else {
+            remove_calls_across_groups(&mut calls);
+        }
+        if calls.len() == before {
+            break;
+        }
+    }
+    calls
+}
+
+fn parameter_component(
+    calls: &[RecursiveCall],
+    adt_param_indices_map: &HashMap<String, HashSet<usize>>,
+) -> Option<HashMap<String, usize>> {
+    let mut names = calls
+        .iter()
+        .flat_map(|call| [call.caller.clone(), call.callee.clone()])
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    let candidates = names
+        .iter()
+        .map(|name| {
+            let mut positions = adt_param_indices_map
+                .get(name)
+                .map(|indices| indices.iter().copied().collect::<Vec<_>>())
+                .unwrap_or_default();
+            positions.sort_unstable();
+            positions
+        })
+        .collect::<Vec<_>>();
+    if candidates.iter().any(Vec::is_empty) {
+        return None;
+    }
+
+    let mut choice = vec![0; names.len()];
+    for _ in 0..MAX_LEXICOGRAPHIC_CANDIDATES {
+        let assignment = names
+            .iter()
+            .zip(&choice)
+            .zip(&candidates)
+            .map(|((name, idx), positions)| (name.clone(), positions[*idx]))
+            .collect::<HashMap<_, _>>();
+        let descents = calls
+            .iter()
+            .map(|call| call_descent(call, &assignment))
+            .collect::<Vec<_>>();
+        if descents.iter().all(Option::is_some) && descents.contains(&Some(true)) {
+            return Some(assignment);
+        }
+
+        let mut slot = choice.len();
+        loop {
+            if slot == 0 {
+                return None;
+            }
+            slot -= 1;
+            choice[slot] += 1;
+            if choice[slot] < candidates[slot].len() {
+                break;
+            }
+            choice[slot] = 0;
+        }
+    }
+    None
+}
+
+fn call_descent(call: &RecursiveCall, assignment: &HashMap<String, usize>) -> Option<bool> {
+    let caller_position = assignment.get(&call.caller)?;
+    let callee_position = assignment.get(&call.callee)?;
+    call.descents
+        .iter()
+        .find(|(callee, caller, _)| callee == callee_position && caller == caller_position)
+        .map(|(_, _, strict)| *strict)
+}
+
+fn remove_calls_across_groups(calls: &mut Vec<RecursiveCall>) {
+    let mut function_names = HashSet::new();
+    let mut graph: HashMap<String, HashSet<String>> = HashMap::new();
+    for call in calls.iter() {
+        function_names.insert(call.caller.clone());
+        function_names.insert(call.callee.clone());
+        graph
+            .entry(call.caller.clone())
+            .or_default()
+            .insert(call.callee.clone());
+    }
+    let group_of = strongly_connected_components(&function_names, &graph)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(idx, component)| component.into_iter().map(move |name| (name, idx)))
+        .collect::<HashMap<_, _>>();
+    calls.retain(|call| group_of.get(&call.caller) == group_of.get(&call.callee));
+}
+
+fn non_decreasing_call_error(call: &RecursiveCall) -> Diagnostic {
+    let edge = format!("{} -> {}", call.caller, call.callee);
+    let one_based = call.positions.iter().map(|idx| idx + 1).collect::<Vec<_>>();
+    let one_based_text = one_based
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let message = if call.descents.iter().any(|(_, _, strict)| *strict) {
+        format!(
+            "recursive call is not lexicographically decreasing in {edge}: argument index [{one_based_text}]"
+        )
+    } else {
+        format!(
+            "recursive call is not structurally decreasing in {edge}: argument index [{one_based_text}]"
+        )
+    };
+    Diagnostic::new("E-TOTAL", message, Some(call.span.clone()))
+        .with_reason(TOTAL_REASON_NON_DECREASING_ARG)
+        .with_arg_indices(one_based)
+}
+
 fn adt_param_indices(defn: &Defn, data_names: &HashSet<String>) -> HashSet<usize> {
     defn.params
         .iter()
@@ -298,42 +720,50 @@ fn strongly_connected_components(
 }
 
 fn collect_totality_violations(
-    expr: &Expr,
-    is_tail_position: bool,
+    id: ExprId,
     origin_env: &HashMap<String, OriginInfo>,
     rule: &RecursionEdgeRule<'_>,
-    errors: &mut Vec<Diagnostic>,
+    output: &mut TotalityOutput,
 ) {
+    let expr = &rule.exprs[id];
     match expr {
-        Expr::Var { .. } | Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => {}
-        Expr::Call { name, args, span } => {
+        Expr::Var { .. }
+        | Expr::Symbol { .. }
+        | Expr::Int { .. }
+        | Expr::Bool { .. }
+        | Expr::Hole { .. } => {}
+        Expr::Call {
+            name, args, span, ..
+        } => {
             for arg in args {
-                collect_totality_violations(arg, false, origin_env, rule, errors);
+                collect_totality_violations(*arg, origin_env, rule, output);
             }
 
             if let Some(callee_rule) = rule.scc_callee_rules.get(name) {
-                check_recursive_call(
-                    args,
-                    span,
-                    is_tail_position,
-                    origin_env,
-                    rule,
-                    callee_rule,
-                    errors,
-                );
+                check_recursive_call(args, span, origin_env, rule, callee_rule, output);
             }
         }
         Expr::Let { bindings, body, .. } => {
             let mut local_env = origin_env.clone();
             for (name, bexpr, _) in bindings {
-                collect_totality_violations(bexpr, false, &local_env, rule, errors);
-                if let Some(origin) = origin_of_expr(bexpr, &local_env) {
+                collect_totality_violations(*bexpr, &local_env, rule, output);
+                if let Some(origin) = origin_of_expr(rule.exprs, *bexpr, &local_env) {
                     local_env.insert(name.clone(), origin);
                 } else {
                     local_env.remove(name);
                 }
             }
-            collect_totality_violations(body, is_tail_position, &local_env, rule, errors);
+            collect_totality_violations(*body, &local_env, rule, output);
+        }
+        Expr::LetFn { fns, body, .. } => {
+            for f in fns {
+                let mut fn_env = origin_env.clone();
+                for param in &f.params {
+                    fn_env.remove(&param.name);
+                }
+                collect_totality_violations(f.body, &fn_env, rule, output);
+            }
+            collect_totality_violations(*body, origin_env, rule, output);
         }
         Expr::If {
             cond,
@@ -341,49 +771,36 @@ fn collect_totality_violations(
             else_branch,
             ..
         } => {
-            collect_totality_violations(cond, false, origin_env, rule, errors);
-            collect_totality_violations(then_branch, is_tail_position, origin_env, rule, errors);
-            collect_totality_violations(else_branch, is_tail_position, origin_env, rule, errors);
+            for branch in [cond, then_branch, else_branch] {
+                collect_totality_violations(*branch, origin_env, rule, output);
+            }
         }
         Expr::Match {
             scrutinee, arms, ..
         } => {
-            collect_totality_violations(scrutinee, false, origin_env, rule, errors);
+            collect_totality_violations(*scrutinee, origin_env, rule, output);
 
-            let scrutinee_origin = origin_of_expr(scrutinee, origin_env);
+            let scrutinee_origin = origin_of_expr(rule.exprs, *scrutinee, origin_env);
             for arm in arms {
                 let mut arm_env = origin_env.clone();
-                bind_pattern_origins(&arm.pattern, scrutinee_origin, &mut arm_env);
-                collect_totality_violations(&arm.body, is_tail_position, &arm_env, rule, errors);
+                bind_pattern_origins(rule.exprs, arm.pattern, scrutinee_origin, &mut arm_env);
+                collect_totality_violations(arm.body, &arm_env, rule, output);
             }
         }
     }
 }
 
 fn check_recursive_call(
-    args: &[Expr],
+    args: &[ExprId],
     span: &crate::diagnostics::Span,
-    is_tail_position: bool,
     origin_env: &HashMap<String, OriginInfo>,
     edge_rule: &RecursionEdgeRule<'_>,
     callee_rule: &CalleeRule,
-    errors: &mut Vec<Diagnostic>,
+    output: &mut TotalityOutput,
 ) {
     let edge = format!("{} -> {}", edge_rule.caller_name, callee_rule.callee_name);
-    if !is_tail_position {
-        errors.push(
-            Diagnostic::new(
-                "E-TOTAL",
-                format!("recursive call is not in tail position: {edge}"),
-                Some(span.clone()),
-            )
-            .with_reason(TOTAL_REASON_NON_TAIL_CALL),
-        );
-        return;
-    }
-
     if args.len() != callee_rule.param_len {
-        errors.push(
+        output.errors.push(
             Diagnostic::new(
                 "E-TOTAL",
                 format!(
@@ -398,8 +815,12 @@ fn check_recursive_call(
         return;
     }
 
+    if edge_rule.caller_measured && callee_rule.measured {
+        return;
+    }
+
     if callee_rule.adt_param_indices.is_empty() {
-        errors.push(
+        output.errors.push(
             Diagnostic::new(
                 "E-TOTAL",
                 format!("recursive call target has no ADT parameter in {edge}"),
@@ -416,62 +837,46 @@ fn check_recursive_call(
         .copied()
         .collect::<Vec<_>>();
     positions.sort_unstable();
-    let has_decreasing_arg = positions.iter().any(|idx| {
-        is_strict_subterm_of_caller_adt_param(
-            args.get(*idx)
-                .expect("recursive call arity already validated"),
-            origin_env,
-            edge_rule.caller_adt_param_indices,
-        )
+    let descents = positions
+        .iter()
+        .filter_map(|idx| {
+            let arg = args
+                .get(*idx)
+                .expect("recursive call arity already validated");
+            let origin = origin_of_expr(edge_rule.exprs, *arg, origin_env)?;
+            edge_rule
+                .caller_adt_param_indices
+                .contains(&origin.param_index)
+                .then_some((*idx, origin.param_index, origin.strict_subterm))
+        })
+        .collect::<Vec<_>>();
+    output.calls.push(RecursiveCall {
+        caller: edge_rule.caller_name.to_string(),
+        callee: callee_rule.callee_name.clone(),
+        span: span.clone(),
+        descents,
+        positions,
     });
-
-    if !has_decreasing_arg {
-        let one_based = positions.iter().map(|idx| idx + 1).collect::<Vec<_>>();
-        let one_based_text = one_based
-            .iter()
-            .map(ToString::to_string)
-            .collect::<Vec<_>>()
-            .join(", ");
-        errors.push(
-            Diagnostic::new(
-                "E-TOTAL",
-                format!(
-                    "recursive call is not structurally decreasing in {edge}: argument index [{one_based_text}]"
-                ),
-                Some(span.clone()),
-            )
-            .with_reason(TOTAL_REASON_NON_DECREASING_ARG)
-            .with_arg_indices(one_based),
-        );
-    }
-}
-
-fn is_strict_subterm_of_caller_adt_param(
-    expr: &Expr,
-    origin_env: &HashMap<String, OriginInfo>,
-    caller_adt_param_indices: &HashSet<usize>,
-) -> bool {
-    let Expr::Var { name, .. } = expr else {
-        return false;
-    };
-    let Some(origin) = origin_env.get(name) else {
-        return false;
-    };
-    caller_adt_param_indices.contains(&origin.param_index) && origin.strict_subterm
 }
 
-fn origin_of_expr(expr: &Expr, env: &HashMap<String, OriginInfo>) -> Option<OriginInfo> {
-    let Expr::Var { name, .. } = expr else {
+fn origin_of_expr(
+    exprs: &ExprArena,
+    id: ExprId,
+    env: &HashMap<String, OriginInfo>,
+) -> Option<OriginInfo> {
+    let Expr::Var { name, .. } = &exprs[id] else {
         return None;
     };
     env.get(name).copied()
 }
 
 fn bind_pattern_origins(
-    pattern: &Pattern,
+    exprs: &ExprArena,
+    id: PatternId,
     scrutinee_origin: Option<OriginInfo>,
     env: &mut HashMap<String, OriginInfo>,
 ) {
+    let pattern = &exprs[id];
     match pattern {
         Pattern::Wildcard { .. }
         | Pattern::Symbol { .. }
@@ -490,51 +895,25 @@ fn bind_pattern_origins(
                 strict_subterm: true,
             });
             for arg in args {
-                bind_pattern_origins(arg, child_origin, env);
+                bind_pattern_origins(exprs, *arg, child_origin, env);
             }
         }
     }
 }
 
 fn collect_function_calls(
-    expr: &Expr,
+    exprs: &ExprArena,
+    expr: ExprId,
     function_names: &HashSet<String>,
     out: &mut HashSet<String>,
 ) {
-    match expr {
-        Expr::Var { .. } | Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => {}
-        Expr::Call { name, args, .. } => {
-            if function_names.contains(name) {
-                out.insert(name.clone());
-            }
-            for arg in args {
-                collect_function_calls(arg, function_names, out);
-            }
-        }
-        Expr::Let { bindings, body, .. } => {
-            for (_, bexpr, _) in bindings {
-                collect_function_calls(bexpr, function_names, out);
-            }
-            collect_function_calls(body, function_names, out);
-        }
-        Expr::If {
-            cond,
-            then_branch,
-            else_branch,
-            ..
-        } => {
-            collect_function_calls(cond, function_names, out);
-            collect_function_calls(then_branch, function_names, out);
-            collect_function_calls(else_branch, function_names, out);
-        }
-        Expr::Match {
-            scrutinee, arms, ..
-        } => {
-            collect_function_calls(scrutinee, function_names, out);
-            for arm in arms {
-                collect_function_calls(&arm.body, function_names, out);
-            }
-        }
+    if let Expr::Call { name, .. } = &exprs[expr]
+        && function_names.contains(name)
+    {
+        out.insert(name.clone());
+    }
+    for child in exprs[expr].children() {
+        collect_function_calls(exprs, child, function_names, out);
     }
 }
 
@@ -555,6 +934,22 @@ fn build_relation_sigs(
     map
 }
 
+fn remove_extra_args(
+    diag: Diagnostic,
+    exprs: &ExprArena,
+    args: &[ExprId],
+    expected: usize,
+) -> Diagnostic {
+    match (args.get(expected), args.last()) {
+        (Some(first_extra), Some(last)) => {
+            let mut span = exprs.span(*first_extra).clone();
+            span.end = exprs.span(*last).end;
+            diag.with_fix(span, "")
+        }
+        _ => diag,
+    }
+}
+
 fn build_function_sigs(
     program: &Program,
     data_names: &HashSet<String>,
@@ -571,6 +966,7 @@ fn build_function_sigs(
                     .collect(),
                 ret: canonicalize_type(&f.ret_type, data_names),
                 param_names: f.params.iter().map(|p| p.name.clone()).collect(),
+                span: f.span.clone(),
             },
         );
     }
@@ -617,48 +1013,417 @@ fn check_defn(defn: &Defn, program: &Program, ctx: &TypeContext) -> Result<(), V
         env.insert(p.name.clone(), canonicalize_type_for_ctx(&p.ty, ctx));
     }
 
-    let actual = infer_expr(&defn.body, &env, ctx)?;
     let expected = canonicalize_type_for_ctx(&defn.ret_type, ctx);
+    let mut holes = Vec::new();
+    if let Some(measure) = &defn.measure {
+        collect_holes(*measure, Some(&Type::Int), &env, ctx, &mut holes);
+    }
+    collect_holes(defn.body, Some(&expected), &env, ctx, &mut holes);
+    if !holes.is_empty() {
+        return Err(holes);
+    }
+
+    let mut errors = Vec::new();
+    if let Some(measure) = &defn.measure {
+        let ty = infer_expr_into(*measure, &env, ctx, &mut errors);
+        check_subtype(
+            &ty,
+            &Type::Int,
+            ctx,
+            ctx.exprs.span(*measure),
+            "termination measure must be Int",
+            &mut errors,
+        );
+    }
+
+    let actual = infer_expr_into(defn.body, &env, ctx, &mut errors);
+    let actual = with_value_interval(actual, &expected, defn.body, &env, ctx);
     match is_subtype(&actual, &expected, ctx) {
-        Ok(()) => Ok(()),
+        Ok(()) => {}
         Err(e)
             if e.code == "E-ENTAIL"
+                && errors.is_empty()
                 && matches!(expected, Type::Refine { .. })
-                && semantic_refinement_check_passes(program, &defn.name) =>
-        {
-            Ok(())
+                && semantic_refinement_check_passes(program, &defn.name) => {}
+        Err(mut e) => {
+            if e.span.is_none() {
+                e.span = Some(defn.span.clone());
+            }
+            errors.push(e);
         }
-        Err(e) => Err(vec![e]),
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
     }
 }
 
-fn semantic_refinement_check_passes(program: &Program, defn_name: &str) -> bool {
-    let Ok(results) = reference_prove_program_results(program) else {
-        return false;
-    };
-    results
-        .iter()
-        .find(|item| item.id == format!("defn::{defn_name}"))
-        .map(|item| item.result == "proved")
-        .unwrap_or(false)
-}
-
-fn infer_expr(
-    expr: &Expr,
+fn collect_holes(
+    id: ExprId,
+    expected: Option<&Type>,
     env: &HashMap<String, Type>,
     ctx: &TypeContext,
-) -> Result<Type, Vec<Diagnostic>> {
+    out: &mut Vec<Diagnostic>,
+) {
+    let expr = &ctx.exprs[id];
     match expr {
-        Expr::Var { name, span } => env.get(name).cloned().ok_or_else(|| {
-            vec![Diagnostic::new(
-                "E-TYPE",
-                format!("unknown variable: {name}"),
-                Some(span.clone()),
-            )]
+        Expr::Hole { span } => out.push(hole_diagnostic(span, expected, env, ctx)),
+        Expr::Var { .. } | Expr::Symbol { .. } | Expr::Int { .. } | Expr::Bool { .. } => {}
+        Expr::Call { name, args, .. } => {
+            let params = if let Some(Type::Fun(params, _)) = env.get(name) {
+                params.clone()
+            } else if let Some(sig) = ctx.function_sigs.get(name) {
+                sig.params.clone()
+            } else if let Some(sig) = ctx.constructor_sigs.get(name) {
+                sig.fields.clone()
+            } else if let Some(sig) = ctx.relation_sigs.get(name) {
+                sig.clone()
+            } else if ArithOp::from_name(name).is_some() {
+                vec![Type::Int; args.len()]
+            } else {
+                Vec::new()
+            };
+            for (idx, arg) in args.iter().enumerate() {
+                collect_holes(*arg, params.get(idx), env, ctx, out);
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            let mut local_env = env.clone();
+            for (name, bexpr, _) in bindings {
+                collect_holes(*bexpr, None, &local_env, ctx, out);
+                match infer_expr(*bexpr, &local_env, ctx) {
+                    Ok(ty) => local_env.insert(name.clone(), ty),
+                    Err(_) => local_env.remove(name),
+                };
+            }
+            collect_holes(*body, expected, &local_env, ctx, out);
+        }
+        Expr::LetFn { fns, body, .. } => {
+            let mut local_env = env.clone();
+            for f in fns {
+                let ret = canonicalize_type_for_ctx(&f.ret_type, ctx);
+                let fn_env = local_fn_env(f, &local_env, ctx);
+                collect_holes(f.body, Some(&ret), &fn_env, ctx, out);
+                local_env.insert(f.name.clone(), local_fn_type(f, ctx));
+            }
+            collect_holes(*body, expected, &local_env, ctx, out);
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            collect_holes(*cond, Some(&Type::Bool), env, ctx, out);
+            let then_env = narrow_by_condition(*cond, env, ctx);
+            collect_holes(
+                *then_branch,
+                expected,
+                then_env.as_ref().unwrap_or(env),
+                ctx,
+                out,
+            );
+            collect_holes(*else_branch, expected, env, ctx, out);
+        }
+        Expr::Match {
+            scrutinee, arms, ..
+        } => {
+            collect_holes(*scrutinee, None, env, ctx, out);
+            let scrutinee_ty = infer_expr(*scrutinee, env, ctx).ok();
+            for arm in arms {
+                let mut arm_env = env.clone();
+                if let Some(ty) = &scrutinee_ty
+                    && bind_pattern(arm.pattern, ty, &mut arm_env, ctx).is_ok()
+                {
+                    refine_arm_env(ctx.exprs, *scrutinee, ty, arm.pattern, env, &mut arm_env);
+                }
+                collect_holes(arm.body, expected, &arm_env, ctx, out);
+            }
+        }
+    }
+}
+
+fn hole_diagnostic(
+    span: &Span,
+    expected: Option<&Type>,
+    env: &HashMap<String, Type>,
+    ctx: &TypeContext,
+) -> Diagnostic {
+    let mut bindings = env.iter().collect::<Vec<_>>();
+    bindings.sort_by(|a, b| a.0.cmp(b.0));
+    let mut message = match expected {
+        Some(ty) => format!("typed hole: expected {}", surface_type_name(ty)),
+        None => "typed hole: expected type is unknown here".to_string(),
+    };
+    if !bindings.is_empty() {
+        let listed = bindings
+            .iter()
+            .map(|(name, ty)| format!("{name}: {}", surface_type_name(ty)))
+            .collect::<Vec<_>>();
+        message.push_str(&format!("; in scope: {}", listed.join(", ")));
+    }
+    if let Some(expected) = expected {
+        let fits = |ty: &Type| type_base(ty) == type_base(expected);
+        let mut candidates = bindings
+            .iter()
+            .filter(|(_, ty)| fits(ty))
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        let mut globals = Vec::new();
+        if fits(&Type::Bool) {
+            globals.extend(ctx.relation_sigs.keys().cloned());
+        }
+        globals.extend(
+            ctx.function_sigs
+                .iter()
+                .filter(|(_, sig)| fits(&sig.ret))
+                .map(|(name, _)| name.clone()),
+        );
+        globals.extend(
+            ctx.constructor_sigs
+                .iter()
+                .filter(|(_, sig)| fits(&sig.ret))
+                .map(|(name, _)| name.clone()),
+        );
+        globals.sort();
+        candidates.extend(globals);
+        if !candidates.is_empty() {
+            message.push_str(&format!("; candidates: {}", candidates.join(", ")));
+        }
+    }
+    Diagnostic::new("E-HOLE", message, Some(span.clone()))
+}
+
+fn surface_type_name(ty: &Type) -> String {
+    fn surface(ty: &Type) -> Type {
+        match ty {
+            Type::Adt(name) => Type::Domain(name.clone()),
+            Type::Fun(args, ret) => {
+                Type::Fun(args.iter().map(surface).collect(), Box::new(surface(ret)))
+            }
+            Type::Refine { var, base, formula } => Type::Refine {
+                var: var.clone(),
+                base: Box::new(surface(base)),
+                formula: formula.clone(),
+            },
+            other => other.clone(),
+        }
+    }
+    render_type(&surface(ty))
+}
+
+fn semantic_refinement_check_passes(program: &Program, defn_name: &str) -> bool {
+    let Ok(results) = reference_prove_program_results(program) else {
+        return false;
+    };
+    results
+        .iter()
+        .find(|item| item.id == format!("defn::{defn_name}"))
+        .map(|item| item.result == "proved")
+        .unwrap_or(false)
+}
+
+fn with_value_interval(
+    actual: Type,
+    expected: &Type,
+    expr: ExprId,
+    env: &HashMap<String, Type>,
+    ctx: &TypeContext,
+) -> Type {
+    let Type::Refine { base, .. } = expected else {
+        return actual;
+    };
+    if **base != Type::Int || type_base(&actual) != &Type::Int {
+        return actual;
+    }
+    let interval = expr_interval(expr, env, &HashMap::new(), ctx);
+    if interval == type_interval(&actual) {
+        return actual;
+    }
+    let var = match &actual {
+        Type::Refine { var, .. } => var.clone(),
+        _ => "n".to_string(),
+    };
+    let Some(bounds) = interval.to_formula(&var) else {
+        return actual;
+    };
+    let formula = match &actual {
+        Type::Refine { formula, .. } => Formula::And(vec![formula.clone(), bounds]),
+        _ => bounds,
+    };
+    Type::Refine {
+        var,
+        base: Box::new(Type::Int),
+        formula,
+    }
+}
+
+fn type_base(ty: &Type) -> &Type {
+    match ty {
+        Type::Refine { base, .. } => type_base(base),
+        other => other,
+    }
+}
+
+fn type_interval(ty: &Type) -> Interval {
+    match ty {
+        Type::Refine { var, base, formula } => {
+            type_interval(base).meet(&var_interval(formula, var))
+        }
+        _ => Interval::TOP,
+    }
+}
+
+fn expr_interval(
+    id: ExprId,
+    env: &HashMap<String, Type>,
+    locals: &HashMap<String, Interval>,
+    ctx: &TypeContext,
+) -> Interval {
+    let expr = &ctx.exprs[id];
+    match expr {
+        Expr::Int { value, .. } => Interval::point(*value),
+        Expr::Var { name, .. } => locals
+            .get(name)
+            .copied()
+            .or_else(|| env.get(name).map(type_interval))
+            .unwrap_or(Interval::TOP),
+        Expr::Let { bindings, body, .. } => {
+            let mut locals = locals.clone();
+            for (name, value, _) in bindings {
+                let interval = expr_interval(*value, env, &locals, ctx);
+                locals.insert(name.clone(), interval);
+            }
+            expr_interval(*body, env, &locals, ctx)
+        }
+        Expr::LetFn { fns, body, .. } => {
+            let mut env = env.clone();
+            for f in fns {
+                env.insert(f.name.clone(), local_fn_type(f, ctx));
+            }
+            expr_interval(*body, &env, locals, ctx)
+        }
+        Expr::If {
+            then_branch,
+            else_branch,
+            ..
+        } => expr_interval(*then_branch, env, locals, ctx).join(&expr_interval(
+            *else_branch,
+            env,
+            locals,
+            ctx,
+        )),
+        Expr::Match { arms, .. } => arms
+            .iter()
+            .map(|arm| {
+                let mut locals = locals.clone();
+                let mut bound = Vec::new();
+                pattern_vars(ctx.exprs, arm.pattern, &mut bound);
+                for name in bound {
+                    locals.insert(name, Interval::TOP);
+                }
+                expr_interval(arm.body, env, &locals, ctx)
+            })
+            .reduce(|a, b| a.join(&b))
+            .unwrap_or(Interval::TOP),
+        Expr::Call { name, args, .. } => {
+            if let Some(Type::Fun(_, ret)) = env.get(name) {
+                type_interval(ret)
+            } else if let Some(sig) = ctx.function_sigs.get(name) {
+                type_interval(&sig.ret)
+            } else if let (Some(op), [lhs, rhs]) = (ArithOp::from_name(name), args.as_slice()) {
+                op.apply_interval(
+                    expr_interval(*lhs, env, locals, ctx),
+                    expr_interval(*rhs, env, locals, ctx),
+                )
+            } else {
+                Interval::TOP
+            }
+        }
+        Expr::Symbol { .. } | Expr::Bool { .. } | Expr::Hole { .. } => Interval::TOP,
+    }
+}
+
+fn local_fn_type(f: &LocalFn, ctx: &TypeContext) -> Type {
+    Type::Fun(
+        f.params
+            .iter()
+            .map(|p| canonicalize_type_for_ctx(&p.ty, ctx))
+            .collect(),
+        Box::new(canonicalize_type_for_ctx(&f.ret_type, ctx)),
+    )
+}
+
+fn local_fn_env(
+    f: &LocalFn,
+    env: &HashMap<String, Type>,
+    ctx: &TypeContext,
+) -> HashMap<String, Type> {
+    let mut fn_env = env.clone();
+    for param in &f.params {
+        fn_env.insert(
+            param.name.clone(),
+            canonicalize_type_for_ctx(&param.ty, ctx),
+        );
+    }
+    fn_env
+}
+
+fn pattern_vars(exprs: &ExprArena, id: PatternId, out: &mut Vec<String>) {
+    let pattern = &exprs[id];
+    match pattern {
+        Pattern::Var { name, .. } => out.push(name.clone()),
+        Pattern::Ctor { args, .. } => {
+            for arg in args {
+                pattern_vars(exprs, *arg, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn infer_expr(
+    expr: ExprId,
+    env: &HashMap<String, Type>,
+    ctx: &TypeContext,
+) -> Result<Type, Vec<Diagnostic>> {
+    let mut errors = Vec::new();
+    let ty = infer_expr_into(expr, env, ctx, &mut errors);
+    if errors.is_empty() {
+        Ok(ty)
+    } else {
+        Err(errors)
+    }
+}
+
+fn infer_expr_into(
+    id: ExprId,
+    env: &HashMap<String, Type>,
+    ctx: &TypeContext,
+    errors: &mut Vec<Diagnostic>,
+) -> Type {
+    let expr = &ctx.exprs[id];
+    match expr {
+        Expr::Var { name, span } => env.get(name).cloned().unwrap_or_else(|| {
+            errors.push(
+                Diagnostic::new(
+                    "E-TYPE",
+                    format!("unknown variable: {name}"),
+                    Some(span.clone()),
+                )
+                .with_suggestion(closest_name(name, env.keys().map(String::as_str)))
+                .with_suggestion_fix(span),
+            );
+            Type::Error
         }),
-        Expr::Symbol { .. } => Ok(Type::Symbol),
-        Expr::Int { .. } => Ok(Type::Int),
-        Expr::Bool { .. } => Ok(Type::Bool),
+        Expr::Symbol { .. } => Type::Symbol,
+        Expr::Int { .. } => Type::Int,
+        Expr::Bool { .. } => Type::Bool,
+        Expr::Hole { span } => {
+            errors.push(hole_diagnostic(span, None, env, ctx));
+            Type::Error
+        }
         Expr::Let {
             bindings,
             body,
@@ -666,10 +1431,29 @@ fn infer_expr(
         } => {
             let mut local_env = env.clone();
             for (name, bexpr, _) in bindings {
-                let ty = infer_expr(bexpr, &local_env, ctx)?;
+                let ty = infer_expr_into(*bexpr, &local_env, ctx, errors);
                 local_env.insert(name.clone(), ty);
             }
-            infer_expr(body, &local_env, ctx)
+            infer_expr_into(*body, &local_env, ctx, errors)
+        }
+        Expr::LetFn { fns, body, .. } => {
+            let mut local_env = env.clone();
+            for f in fns {
+                let ret = canonicalize_type_for_ctx(&f.ret_type, ctx);
+                let fn_env = local_fn_env(f, &local_env, ctx);
+                let actual = infer_expr_into(f.body, &fn_env, ctx, errors);
+                let actual = with_value_interval(actual, &ret, f.body, &fn_env, ctx);
+                check_subtype(
+                    &actual,
+                    &ret,
+                    ctx,
+                    ctx.exprs.span(f.body),
+                    "local function return type mismatch",
+                    errors,
+                );
+                local_env.insert(f.name.clone(), local_fn_type(f, ctx));
+            }
+            infer_expr_into(*body, &local_env, ctx, errors)
         }
         Expr::If {
             cond,
@@ -677,160 +1461,413 @@ fn infer_expr(
             else_branch,
             span,
         } => {
-            let cond_ty = infer_expr(cond, env, ctx)?;
-            ensure_subtype(
+            let cond_ty = infer_expr_into(*cond, env, ctx, errors);
+            check_subtype(
                 &cond_ty,
                 &Type::Bool,
                 ctx,
                 span,
                 "if condition must be Bool",
-            )?;
+                errors,
+            );
 
-            let t_ty = infer_expr(then_branch, env, ctx)?;
-            let e_ty = infer_expr(else_branch, env, ctx)?;
+            let then_env = narrow_by_condition(*cond, env, ctx);
+            let t_ty = infer_expr_into(*then_branch, then_env.as_ref().unwrap_or(env), ctx, errors);
+            let e_ty = infer_expr_into(*else_branch, env, ctx, errors);
 
             if is_subtype(&t_ty, &e_ty, ctx).is_ok() {
-                Ok(e_ty)
+                e_ty
             } else if is_subtype(&e_ty, &t_ty, ctx).is_ok() {
-                Ok(t_ty)
+                t_ty
             } else {
-                Err(vec![Diagnostic::new(
+                errors.push(Diagnostic::new(
                     "E-TYPE",
                     "if branches have incompatible types",
                     Some(span.clone()),
-                )])
+                ));
+                Type::Error
             }
         }
         Expr::Match {
             scrutinee,
             arms,
             span,
-        } => infer_match_expr(scrutinee, arms, span, env, ctx),
-        Expr::Call { name, args, span } => {
-            if let Some(sig) = ctx.function_sigs.get(name) {
-                if sig.params.len() != args.len() {
-                    return Err(vec![Diagnostic::new(
-                        "E-TYPE",
-                        format!(
-                            "function {} arity mismatch: expected {}, got {}",
-                            name,
-                            sig.params.len(),
-                            args.len()
+        } => infer_match_expr(*scrutinee, arms, span, env, ctx, errors),
+        Expr::Call {
+            name,
+            name_span,
+            args,
+            span,
+        } => {
+            if let Some(Type::Fun(params, ret)) = env.get(name) {
+                if params.len() != args.len() {
+                    errors.push(remove_extra_args(
+                        Diagnostic::new(
+                            "E-TYPE",
+                            format!(
+                                "function {} arity mismatch: expected {}, got {}",
+                                name,
+                                params.len(),
+                                args.len()
+                            ),
+                            Some(span.clone()),
                         ),
-                        Some(span.clone()),
-                    )]);
+                        ctx.exprs,
+                        args,
+                        params.len(),
+                    ));
+                    return Type::Error;
+                }
+                for (arg, expected) in args.iter().zip(params.iter()) {
+                    let actual = infer_expr_into(*arg, env, ctx, errors);
+                    let actual = with_value_interval(actual, expected, *arg, env, ctx);
+                    check_subtype(
+                        &actual,
+                        expected,
+                        ctx,
+                        ctx.exprs.span(*arg),
+                        "function argument type mismatch",
+                        errors,
+                    );
+                }
+                ret.as_ref().clone()
+            } else if let Some(sig) = ctx.function_sigs.get(name) {
+                if sig.params.len() != args.len() {
+                    errors.push(remove_extra_args(
+                        Diagnostic::new(
+                            "E-TYPE",
+                            format!(
+                                "function {} arity mismatch: expected {}, got {}",
+                                name,
+                                sig.params.len(),
+                                args.len()
+                            ),
+                            Some(span.clone()),
+                        )
+                        .with_related(sig.span.clone(), "function signature"),
+                        ctx.exprs,
+                        args,
+                        sig.params.len(),
+                    ));
+                    return Type::Error;
                 }
 
                 let mut substitution = HashMap::new();
                 for (idx, (arg, expected)) in args.iter().zip(sig.params.iter()).enumerate() {
-                    let actual = infer_expr(arg, env, ctx)?;
-                    ensure_subtype(
+                    let actual = infer_expr_into(*arg, env, ctx, errors);
+                    let actual = with_value_interval(actual, expected, *arg, env, ctx);
+                    check_subtype(
                         &actual,
                         expected,
                         ctx,
-                        arg.span(),
+                        ctx.exprs.span(*arg),
                         "function argument type mismatch",
-                    )?;
-                    if let Some(term) = expr_to_logic_term(arg, ctx) {
+                        errors,
+                    );
+                    if let Some(term) = expr_to_logic_term(*arg, ctx) {
                         substitution.insert(sig.param_names[idx].clone(), term);
                     }
                 }
 
-                Ok(substitute_type(&sig.ret, &substitution))
+                substitute_type(&sig.ret, &substitution)
             } else if let Some(sig) = ctx.constructor_sigs.get(name) {
                 if sig.fields.len() != args.len() {
-                    return Err(vec![Diagnostic::new(
-                        "E-TYPE",
-                        format!(
-                            "constructor {} arity mismatch: expected {}, got {}",
-                            name,
-                            sig.fields.len(),
-                            args.len()
+                    errors.push(remove_extra_args(
+                        Diagnostic::new(
+                            "E-TYPE",
+                            format!(
+                                "constructor {} arity mismatch: expected {}, got {}",
+                                name,
+                                sig.fields.len(),
+                                args.len()
+                            ),
+                            Some(span.clone()),
                         ),
-                        Some(span.clone()),
-                    )]);
+                        ctx.exprs,
+                        args,
+                        sig.fields.len(),
+                    ));
+                    return Type::Error;
                 }
                 for (arg, expected) in args.iter().zip(sig.fields.iter()) {
-                    let actual = infer_expr(arg, env, ctx)?;
-                    ensure_subtype(
+                    let actual = infer_expr_into(*arg, env, ctx, errors);
+                    check_subtype(
                         &actual,
                         expected,
                         ctx,
-                        arg.span(),
+                        ctx.exprs.span(*arg),
                         "constructor argument type mismatch",
-                    )?;
+                        errors,
+                    );
                 }
-                Ok(sig.ret.clone())
+                sig.ret.clone()
             } else if let Some(rel_sig) = ctx.relation_sigs.get(name) {
                 if rel_sig.len() != args.len() {
-                    return Err(vec![Diagnostic::new(
-                        "E-TYPE",
-                        format!(
-                            "relation {} arity mismatch: expected {}, got {}",
-                            name,
-                            rel_sig.len(),
-                            args.len()
+                    errors.push(remove_extra_args(
+                        Diagnostic::new(
+                            "E-TYPE",
+                            format!(
+                                "relation {} arity mismatch: expected {}, got {}",
+                                name,
+                                rel_sig.len(),
+                                args.len()
+                            ),
+                            Some(span.clone()),
                         ),
-                        Some(span.clone()),
-                    )]);
+                        ctx.exprs,
+                        args,
+                        rel_sig.len(),
+                    ));
+                    return Type::Error;
                 }
 
                 let mut terms = Vec::new();
                 for (arg, expected) in args.iter().zip(rel_sig.iter()) {
-                    let actual = infer_expr(arg, env, ctx)?;
-                    ensure_subtype(
+                    let actual = infer_expr_into(*arg, env, ctx, errors);
+                    check_subtype(
                         &actual,
                         expected,
                         ctx,
-                        arg.span(),
+                        ctx.exprs.span(*arg),
                         "relation argument type mismatch",
-                    )?;
-                    let Some(term) = expr_to_logic_term(arg, ctx) else {
-                        return Err(vec![Diagnostic::new(
+                        errors,
+                    );
+                    match expr_to_logic_term(*arg, ctx) {
+                        Some(term) => terms.push(term),
+                        None => errors.push(Diagnostic::new(
                             "E-TYPE",
                             "relation argument must be variable/literal/constructor",
-                            Some(arg.span().clone()),
-                        )]);
-                    };
-                    terms.push(term);
+                            Some(ctx.exprs.span(*arg).clone()),
+                        )),
+                    }
+                }
+                if terms.len() != args.len() {
+                    return Type::Bool;
                 }
 
-                Ok(Type::Refine {
+                Type::Refine {
                     var: "b".to_string(),
                     base: Box::new(Type::Bool),
                     formula: Formula::Atom(Atom {
                         pred: name.clone(),
                         terms,
                     }),
-                })
+                }
+            } else if let Some(op) = ArithOp::from_name(name) {
+                if args.len() != 2 {
+                    errors.push(remove_extra_args(
+                        Diagnostic::new(
+                            "E-TYPE",
+                            format!(
+                                "operator {} arity mismatch: expected 2, got {}",
+                                op.as_str(),
+                                args.len()
+                            ),
+                            Some(span.clone()),
+                        ),
+                        ctx.exprs,
+                        args,
+                        2,
+                    ));
+                    return Type::Int;
+                }
+                for arg in args {
+                    let actual = infer_expr_into(*arg, env, ctx, errors);
+                    check_subtype(
+                        &actual,
+                        &Type::Int,
+                        ctx,
+                        ctx.exprs.span(*arg),
+                        "operator argument type mismatch",
+                        errors,
+                    );
+                }
+                Type::Int
             } else {
-                Err(vec![Diagnostic::new(
-                    "E-TYPE",
-                    format!("unknown function or relation or constructor: {name}"),
-                    Some(span.clone()),
-                )])
+                errors.push(
+                    Diagnostic::new(
+                        "E-TYPE",
+                        format!("unknown function or relation or constructor: {name}"),
+                        Some(span.clone()),
+                    )
+                    .with_suggestion(closest_name(
+                        name,
+                        ctx.function_sigs
+                            .keys()
+                            .chain(ctx.relation_sigs.keys())
+                            .chain(ctx.constructor_sigs.keys())
+                            .map(String::as_str),
+                    ))
+                    .with_suggestion_fix(name_span),
+                );
+                Type::Error
+            }
+        }
+    }
+}
+
+fn narrow_by_condition(
+    cond: ExprId,
+    env: &HashMap<String, Type>,
+    ctx: &TypeContext,
+) -> Option<HashMap<String, Type>> {
+    let cond = &ctx.exprs[cond];
+    let Expr::Call { name, args, .. } = cond else {
+        return None;
+    };
+    if ctx.function_sigs.contains_key(name) || !ctx.relation_sigs.contains_key(name) {
+        return None;
+    }
+    let terms = args
+        .iter()
+        .map(|arg| expr_to_logic_term(*arg, ctx))
+        .collect::<Option<Vec<_>>>()?;
+    let atom = Formula::Atom(Atom {
+        pred: name.clone(),
+        terms,
+    });
+    let mut narrowed = env.clone();
+    let mut changed = false;
+    for arg in args {
+        let Expr::Var { name: var, .. } = &ctx.exprs[*arg] else {
+            continue;
+        };
+        let Some(ty) = narrowed
+            .get(var)
+            .and_then(|ty| conjoin_refinement(ty, var, &atom))
+        else {
+            continue;
+        };
+        narrowed.insert(var.clone(), ty);
+        changed = true;
+    }
+    changed.then_some(narrowed)
+}
+
+fn conjoin_refinement(ty: &Type, var: &str, formula: &Formula) -> Option<Type> {
+    match ty {
+        Type::Refine {
+            var: bound,
+            base,
+            formula: existing,
+        } => {
+            let existing = rename_formula_var(existing, bound, var);
+            if existing == *formula
+                || matches!(&existing, Formula::And(items) if items.contains(formula))
+            {
+                return None;
             }
+            Some(Type::Refine {
+                var: var.to_string(),
+                base: base.clone(),
+                formula: Formula::And(vec![existing, formula.clone()]),
+            })
         }
+        other => Some(Type::Refine {
+            var: var.to_string(),
+            base: Box::new(other.clone()),
+            formula: formula.clone(),
+        }),
+    }
+}
+
+fn refine_arm_env(
+    exprs: &ExprArena,
+    scrutinee: ExprId,
+    scrutinee_ty: &Type,
+    pattern: PatternId,
+    outer: &HashMap<String, Type>,
+    arm_env: &mut HashMap<String, Type>,
+) {
+    if !matches!(exprs[pattern], Pattern::Ctor { .. }) {
+        return;
+    }
+    let mut bound = Vec::new();
+    pattern_vars(exprs, pattern, &mut bound);
+    if bound.iter().any(|name| outer.contains_key(name)) {
+        return;
+    }
+    let term = pattern_to_logic_term(exprs, pattern, &mut 0);
+
+    if let Type::Refine { var, formula, .. } = scrutinee_ty {
+        let known = substitute_formula(formula, &HashMap::from([(var.clone(), term.clone())]));
+        for name in &bound {
+            if let Some(ty) = arm_env
+                .get(name)
+                .and_then(|ty| conjoin_refinement(ty, name, &known))
+            {
+                arm_env.insert(name.clone(), ty);
+            }
+        }
+    }
+
+    let Expr::Var {
+        name: scrutinee_var,
+        ..
+    } = &exprs[scrutinee]
+    else {
+        return;
+    };
+    if let Some(ty) = arm_env.get(scrutinee_var).and_then(|ty| {
+        let matched = Formula::atom(
+            MATCHED_PRED,
+            vec![LogicTerm::Var(scrutinee_var.clone()), term.clone()],
+        );
+        conjoin_refinement(ty, scrutinee_var, &matched)
+    }) {
+        arm_env.insert(scrutinee_var.clone(), ty);
+    }
+    let subst = HashMap::from([(scrutinee_var.clone(), term)]);
+    for (name, ty) in arm_env.iter_mut() {
+        if name == scrutinee_var {
+            continue;
+        }
+        if let Type::Refine { var, formula, .. } = ty
+            && var != scrutinee_var
+            && collect_vars(formula).contains(scrutinee_var)
+        {
+            *formula = substitute_formula(formula, &subst);
+        }
+    }
+}
+
+fn pattern_to_logic_term(exprs: &ExprArena, id: PatternId, wildcards: &mut usize) -> LogicTerm {
+    let pattern = &exprs[id];
+    match pattern {
+        Pattern::Wildcard { .. } => {
+            *wildcards += 1;
+            LogicTerm::Var(format!("__wildcard{wildcards}"))
+        }
+        Pattern::Var { name, .. } => LogicTerm::Var(name.clone()),
+        Pattern::Symbol { value, .. } => LogicTerm::Symbol(value.clone()),
+        Pattern::Int { value, .. } => LogicTerm::Int(*value),
+        Pattern::Bool { value, .. } => LogicTerm::Bool(*value),
+        Pattern::Ctor { name, args, .. } => LogicTerm::Ctor {
+            name: name.clone(),
+            args: args
+                .iter()
+                .map(|arg| pattern_to_logic_term(exprs, *arg, wildcards))
+                .collect(),
+        },
     }
 }
 
 fn infer_match_expr(
-    scrutinee: &Expr,
+    scrutinee: ExprId,
     arms: &[MatchArm],
     span: &crate::diagnostics::Span,
     env: &HashMap<String, Type>,
     ctx: &TypeContext,
-) -> Result<Type, Vec<Diagnostic>> {
-    let scrutinee_ty = infer_expr(scrutinee, env, ctx)?;
+    errors: &mut Vec<Diagnostic>,
+) -> Type {
+    let scrutinee_ty = infer_expr_into(scrutinee, env, ctx, errors);
     let mut branch_ty: Option<Type> = None;
 
-    let mut errors = Vec::new();
-    let mut covered_all = false;
-    let mut covered_bool = HashSet::new();
-    let mut covered_ctor = HashSet::new();
+    let column = [type_base(&scrutinee_ty).clone()];
+    let mut rows: Vec<Vec<CoverPattern>> = Vec::new();
 
     for arm in arms {
-        if covered_all {
+        if rows.iter().any(|row| matches!(row[0], CoverPattern::Any)) {
             errors.push(Diagnostic::new(
                 "E-MATCH",
                 "unreachable match arm",
@@ -840,36 +1877,41 @@ fn infer_match_expr(
         }
 
         let mut arm_env = env.clone();
-        let arm_key = bind_pattern(&arm.pattern, &scrutinee_ty, &mut arm_env, ctx)?;
-
-        match &arm_key {
-            PatternKey::Any => {
-                covered_all = true;
-            }
-            PatternKey::Bool(v) => {
-                if covered_bool.contains(v) {
-                    errors.push(Diagnostic::new(
-                        "E-MATCH",
-                        "unreachable duplicate boolean pattern",
-                        Some(arm.span.clone()),
-                    ));
-                }
-                covered_bool.insert(*v);
+        if let Err(mut pattern_errors) = bind_pattern(arm.pattern, &scrutinee_ty, &mut arm_env, ctx)
+        {
+            errors.append(&mut pattern_errors);
+            let mut names = Vec::new();
+            pattern_vars(ctx.exprs, arm.pattern, &mut names);
+            for name in names {
+                arm_env.insert(name, Type::Error);
             }
-            PatternKey::Ctor(name) => {
-                if covered_ctor.contains(name) {
-                    errors.push(Diagnostic::new(
-                        "E-MATCH",
-                        format!("unreachable duplicate constructor pattern: {name}"),
-                        Some(arm.span.clone()),
-                    ));
+        }
+        refine_arm_env(
+            ctx.exprs,
+            scrutinee,
+            &scrutinee_ty,
+            arm.pattern,
+            env,
+            &mut arm_env,
+        );
+
+        let row = vec![cover_pattern(ctx.exprs, arm.pattern)];
+        if !is_useful(&rows, &row, &column, ctx) {
+            let message = match &ctx.exprs[arm.pattern] {
+                Pattern::Bool { .. } => "unreachable duplicate boolean pattern".to_string(),
+                Pattern::Ctor { name, .. } => {
+                    format!("unreachable duplicate constructor pattern: {name}")
                 }
-                covered_ctor.insert(name.clone());
-            }
-            PatternKey::Other => {}
+                _ => "unreachable match arm".to_string(),
+            };
+            errors.push(Diagnostic::new("E-MATCH", message, Some(arm.span.clone())));
         }
+        rows.push(row);
 
-        let ty = infer_expr(&arm.body, &arm_env, ctx)?;
+        let ty = infer_expr_into(arm.body, &arm_env, ctx, errors);
+        if ty == Type::Error {
+            continue;
+        }
         if let Some(prev) = &branch_ty {
             if is_subtype(&ty, prev, ctx).is_ok() {
             } else if is_subtype(prev, &ty, ctx).is_ok() {
@@ -886,13 +1928,7 @@ fn infer_match_expr(
         }
     }
 
-    if !is_exhaustive(
-        &scrutinee_ty,
-        covered_all,
-        &covered_bool,
-        &covered_ctor,
-        ctx,
-    ) {
+    if scrutinee_ty != Type::Error && is_useful(&rows, &[CoverPattern::Any], &column, ctx) {
         errors.push(Diagnostic::new(
             "E-MATCH",
             "non-exhaustive match",
@@ -900,43 +1936,27 @@ fn infer_match_expr(
         ));
     }
 
-    if errors.is_empty() {
-        Ok(branch_ty.unwrap_or(Type::Bool))
+    branch_ty.unwrap_or(if arms.is_empty() {
+        Type::Bool
     } else {
-        Err(errors)
-    }
-}
-
-#[derive(Debug, Clone)]
-enum PatternKey {
-    Any,
-    Bool(bool),
-    Ctor(String),
-    Other,
+        Type::Error
+    })
 }
 
 fn bind_pattern(
-    pattern: &Pattern,
+    id: PatternId,
     expected: &Type,
     env: &mut HashMap<String, Type>,
     ctx: &TypeContext,
-) -> Result<PatternKey, Vec<Diagnostic>> {
+) -> Result<(), Vec<Diagnostic>> {
+    let pattern = &ctx.exprs[id];
     match pattern {
-        Pattern::Wildcard { .. } => Ok(PatternKey::Any),
+        Pattern::Wildcard { .. } => Ok(()),
         Pattern::Var { name, .. } => {
-            if let Some(prev) = env.get(name)
-                && is_subtype(expected, prev, ctx).is_err()
-            {
-                return Err(vec![Diagnostic::new(
-                    "E-MATCH",
-                    format!("pattern variable type mismatch: {name}"),
-                    Some(pattern.span().clone()),
-                )]);
-            }
             env.insert(name.clone(), expected.clone());
-            Ok(PatternKey::Any)
+            Ok(())
         }
-        Pattern::Bool { value, .. } => {
+        Pattern::Bool { .. } => {
             ensure_subtype(
                 expected,
                 &Type::Bool,
@@ -944,7 +1964,7 @@ fn bind_pattern(
                 pattern.span(),
                 "pattern expects Bool",
             )?;
-            Ok(PatternKey::Bool(*value))
+            Ok(())
         }
         Pattern::Int { .. } => {
             ensure_subtype(
@@ -954,7 +1974,7 @@ fn bind_pattern(
                 pattern.span(),
                 "pattern expects Int",
             )?;
-            Ok(PatternKey::Other)
+            Ok(())
         }
         Pattern::Symbol { .. } => {
             ensure_subtype(
@@ -964,15 +1984,21 @@ fn bind_pattern(
                 pattern.span(),
                 "pattern expects Symbol",
             )?;
-            Ok(PatternKey::Other)
+            Ok(())
         }
         Pattern::Ctor { name, args, .. } => {
             let Some(sig) = ctx.constructor_sigs.get(name) else {
-                return Err(vec![Diagnostic::new(
-                    "E-MATCH",
-                    format!("unknown constructor in pattern: {name}"),
-                    Some(pattern.span().clone()),
-                )]);
+                return Err(vec![
+                    Diagnostic::new(
+                        "E-MATCH",
+                        format!("unknown constructor in pattern: {name}"),
+                        Some(pattern.span().clone()),
+                    )
+                    .with_suggestion(closest_name(
+                        name,
+                        ctx.constructor_sigs.keys().map(String::as_str),
+                    )),
+                ]);
             };
             if sig.fields.len() != args.len() {
                 return Err(vec![Diagnostic::new(
@@ -988,41 +2014,150 @@ fn bind_pattern(
             }
             ensure_subtype(
                 &sig.ret,
-                expected,
+                type_base(expected),
                 ctx,
                 pattern.span(),
                 "pattern constructor type mismatch",
             )?;
 
             for (child, child_expected) in args.iter().zip(sig.fields.iter()) {
-                let _ = bind_pattern(child, child_expected, env, ctx)?;
+                bind_pattern(*child, child_expected, env, ctx)?;
             }
-            Ok(PatternKey::Ctor(name.clone()))
+            Ok(())
         }
     }
 }
 
-fn is_exhaustive(
-    scrutinee_ty: &Type,
-    covered_all: bool,
-    covered_bool: &HashSet<bool>,
-    covered_ctor: &HashSet<String>,
+#[derive(Debug, Clone)]
+enum CoverPattern {
+    Any,
+    Ctor(String, Vec<CoverPattern>),
+    Literal(String),
+}
+
+fn cover_pattern(exprs: &ExprArena, id: PatternId) -> CoverPattern {
+    let pattern = &exprs[id];
+    match pattern {
+        Pattern::Wildcard { .. } | Pattern::Var { .. } => CoverPattern::Any,
+        Pattern::Bool { value, .. } => CoverPattern::Ctor(value.to_string(), Vec::new()),
+        Pattern::Int { value, .. } => CoverPattern::Literal(value.to_string()),
+        Pattern::Symbol { value, .. } => CoverPattern::Literal(value.clone()),
+        Pattern::Ctor { name, args, .. } => CoverPattern::Ctor(
+            name.clone(),
+            args.iter().map(|arg| cover_pattern(exprs, *arg)).collect(),
+        ),
+    }
+}
+
+// Whether some value of the `column` types matches `row` but none of `rows`
+// (the usefulness check of Maranget, "Warnings for pattern matching").
+fn is_useful(
+    rows: &[Vec<CoverPattern>],
+    row: &[CoverPattern],
+    column: &[Type],
     ctx: &TypeContext,
 ) -> bool {
-    if covered_all {
-        return true;
+    let Some((head, rest)) = row.split_first() else {
+        return rows.is_empty();
+    };
+    let (head_ty, rest_tys) = column.split_first().expect("one type per column");
+    match head {
+        CoverPattern::Ctor(name, args) => {
+            let fields = constructor_fields(name, head_ty, ctx);
+            let next_row = args.iter().chain(rest).cloned().collect::<Vec<_>>();
+            let next_column = fields.into_iter().chain(rest_tys.iter().cloned());
+            is_useful(
+                &specialize_rows(rows, name, args.len()),
+                &next_row,
+                &next_column.collect::<Vec<_>>(),
+                ctx,
+            )
+        }
+        CoverPattern::Literal(value) => {
+            let specialized = rows
+                .iter()
+                .filter(|r| match &r[0] {
+                    CoverPattern::Literal(other) => other == value,
+                    CoverPattern::Any => true,
+                    CoverPattern::Ctor(..) => false,
+                })
+                .map(|r| r[1..].to_vec())
+                .collect::<Vec<_>>();
+            is_useful(&specialized, rest, rest_tys, ctx)
+        }
+        CoverPattern::Any => {
+            let signature = complete_signature(head_ty, ctx).filter(|ctors| {
+                ctors.iter().all(|ctor| {
+                    rows.iter()
+                        .any(|r| matches!(&r[0], CoverPattern::Ctor(name, _) if name == ctor))
+                })
+            });
+            match signature {
+                Some(ctors) => ctors.iter().any(|ctor| {
+                    let fields = constructor_fields(ctor, head_ty, ctx);
+                    let next_row = vec![CoverPattern::Any; fields.len()]
+                        .into_iter()
+                        .chain(rest.iter().cloned())
+                        .collect::<Vec<_>>();
+                    let arity = fields.len();
+                    let next_column = fields
+                        .into_iter()
+                        .chain(rest_tys.iter().cloned())
+                        .collect::<Vec<_>>();
+                    is_useful(
+                        &specialize_rows(rows, ctor, arity),
+                        &next_row,
+                        &next_column,
+                        ctx,
+                    )
+                }),
+                None => {
+                    let default = rows
+                        .iter()
+                        .filter(|r| matches!(r[0], CoverPattern::Any))
+                        .map(|r| r[1..].to_vec())
+                        .collect::<Vec<_>>();
+                    is_useful(&default, rest, rest_tys, ctx)
+                }
+            }
+        }
     }
+}
 
-    match scrutinee_ty {
-        Type::Bool => covered_bool.contains(&true) && covered_bool.contains(&false),
-        Type::Adt(name) => {
-            let Some(ctors) = ctx.data_constructors.get(name) else {
-                return false;
+fn specialize_rows(rows: &[Vec<CoverPattern>], name: &str, arity: usize) -> Vec<Vec<CoverPattern>> {
+    rows.iter()
+        .filter_map(|r| {
+            let fields = match &r[0] {
+                CoverPattern::Ctor(other, args) if other == name => args.clone(),
+                CoverPattern::Any => vec![CoverPattern::Any; arity],
+                _ => return None,
             };
-            ctors.iter().all(|c| covered_ctor.contains(c))
-        }
-        _ => false,
+            Some(fields.into_iter().chain(r[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+fn complete_signature(ty: &Type, ctx: &TypeContext) -> Option<Vec<String>> {
+    match type_base(ty) {
+        Type::Bool => Some(vec!["true".to_string(), "false".to_string()]),
+        Type::Adt(name) => ctx.data_constructors.get(name).cloned(),
+        _ => None,
+    }
+}
+
+fn constructor_fields(name: &str, ty: &Type, ctx: &TypeContext) -> Vec<Type> {
+    if matches!(type_base(ty), Type::Bool) {
+        return Vec::new();
     }
+    ctx.constructor_sigs
+        .get(name)
+        .map(|sig| {
+            sig.fields
+                .iter()
+                .map(|field| type_base(field).clone())
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 fn ensure_subtype(
@@ -1041,8 +2176,22 @@ fn ensure_subtype(
     })
 }
 
+fn check_subtype(
+    actual: &Type,
+    expected: &Type,
+    ctx: &TypeContext,
+    span: &crate::diagnostics::Span,
+    message: &str,
+    errors: &mut Vec<Diagnostic>,
+) {
+    if let Err(mut e) = ensure_subtype(actual, expected, ctx, span, message) {
+        errors.append(&mut e);
+    }
+}
+
 fn is_subtype(actual: &Type, expected: &Type, ctx: &TypeContext) -> Result<(), Diagnostic> {
     match (actual, expected) {
+        (Type::Error, _) | (_, Type::Error) => Ok(()),
         (_, Type::Refine { var, base, formula }) => {
             let (left_base, left_formula) = match actual {
                 Type::Refine {
@@ -1095,6 +2244,76 @@ fn is_subtype(actual: &Type, expected: &Type, ctx: &TypeContext) -> Result<(), D
 }
 
 fn entails(lhs: &Formula, rhs: &Formula, ctx: &TypeContext) -> bool {
+    if let Some((lhs, rhs)) = substitute_matched_terms(lhs, rhs) {
+        return entails(&lhs, &rhs, ctx);
+    }
+    if !mentions_comparison(lhs) && !mentions_comparison(rhs) {
+        return entails_relations(lhs, rhs, ctx);
+    }
+    let assumed = split_conjuncts(lhs);
+    let goal = split_conjuncts(rhs);
+    if assumed.is_unsatisfiable() {
+        return true;
+    }
+    if !assumed.implies_comparisons_of(&goal) {
+        return false;
+    }
+    goal.rest.is_empty() || entails_relations(&assumed.rest_formula(), &goal.rest_formula(), ctx)
+}
+
+fn entails_relations(lhs: &Formula, rhs: &Formula, ctx: &TypeContext) -> bool {
+    let key = (lhs.clone(), rhs.clone());
+    if let Some(&cached) = ctx.entailments.borrow().get(&key) {
+        return cached;
+    }
+    let result = solve_entailment(lhs, rhs, ctx);
+    ctx.entailments.borrow_mut().insert(key, result);
+    result
+}
+
+const FRESH_PREFIX: &str = "__v_";
+
+// `(__matched v T)` says the variable `v` is the term `T`: a match arm puts
+// it on its scrutinee variable. It never reaches the knowledge base.
+const MATCHED_PRED: &str = "__matched";
+
+fn substitute_matched_terms(lhs: &Formula, rhs: &Formula) -> Option<(Formula, Formula)> {
+    fn collect(formula: &Formula, subst: &mut HashMap<String, LogicTerm>) -> bool {
+        match formula {
+            Formula::Atom(atom) if atom.pred == MATCHED_PRED => {
+                if let [LogicTerm::Var(var), term] = atom.terms.as_slice() {
+                    subst.entry(var.clone()).or_insert_with(|| term.clone());
+                }
+                true
+            }
+            Formula::And(items) => items
+                .iter()
+                .fold(false, |found, item| collect(item, subst) | found),
+            _ => false,
+        }
+    }
+    fn strip(formula: &Formula) -> Formula {
+        match formula {
+            Formula::Atom(atom) if atom.pred == MATCHED_PRED => Formula::True,
+            Formula::And(items) => Formula::And(items.iter().map(strip).collect()),
+            other => other.clone(),
+        }
+    }
+    let mut subst = HashMap::new();
+    if !collect(lhs, &mut subst) {
+        return None;
+    }
+    Some((
+        substitute_formula(&strip(lhs), &subst),
+        substitute_formula(rhs, &subst),
+    ))
+}
+
+// Assumes the atoms of `lhs` about fresh constants, one per variable, and
+// checks `rhs` in the resulting fixpoint. Vacuously true when `lhs` itself
+// does not hold there. Fresh constants may stand for equal values, so a
+// `!=` between them holds only when `lhs` assumes it.
+fn solve_entailment(lhs: &Formula, rhs: &Formula, ctx: &TypeContext) -> bool {
     let vars = collect_vars(lhs)
         .into_iter()
         .chain(collect_vars(rhs))
@@ -1102,39 +2321,243 @@ fn entails(lhs: &Formula, rhs: &Formula, ctx: &TypeContext) -> bool {
 
     let var_map: HashMap<String, Value> = vars
         .into_iter()
-        .map(|v| (v.clone(), Value::Symbol(format!("__v_{v}"))))
+        .map(|v| {
+            (
+                v.clone(),
+                Value::Symbol(Symbol::intern(&format!("{FRESH_PREFIX}{v}"))),
+            )
+        })
         .collect();
 
-    let assumptions = positive_atoms(lhs)
+    let (distinct, atoms): (Vec<_>, Vec<_>) = positive_atoms(lhs)
         .into_iter()
-        .filter_map(|a| atom_to_ground_fact(&a, &var_map))
+        .partition(|a| a.pred == DISEQUALITY_PRED);
+    let assumptions = atoms
+        .iter()
+        .filter_map(|a| atom_to_ground_fact(a, &var_map))
         .collect::<Vec<_>>();
+    let distinct = distinct
+        .iter()
+        .filter_map(|a| atom_to_ground_tuple(a, &var_map))
+        .filter_map(|(_, tuple)| <[Value; 2]>::try_from(tuple).ok())
+        .collect::<HashSet<_>>();
 
-    let kb = ctx.kb_template.with_extra_facts(assumptions);
-    let Ok(derived) = solve_facts(&kb) else {
+    let Some(base) = ctx
+        .kb_derived
+        .get_or_init(|| solve_facts(&ctx.kb_template).ok())
+    else {
+        return false;
+    };
+    let Ok(derived) = solve_facts_incremental(&ctx.kb_template, base, assumptions) else {
         return false;
     };
 
-    if !eval_formula(lhs, &derived, &var_map) {
+    if !eval_formula(lhs, &derived, &var_map, &distinct, true) {
         return true;
     }
-    eval_formula(rhs, &derived, &var_map)
+    eval_formula(rhs, &derived, &var_map, &distinct, false)
 }
 
-fn eval_formula(formula: &Formula, derived: &DerivedFacts, vars: &HashMap<String, Value>) -> bool {
+fn eval_formula(
+    formula: &Formula,
+    derived: &DerivedFacts,
+    vars: &HashMap<String, Value>,
+    distinct: &HashSet<[Value; 2]>,
+    undecided: bool,
+) -> bool {
     match formula {
         Formula::True => true,
-        Formula::Atom(atom) => atom_to_ground_tuple(atom, vars)
-            .map(|(pred, tuple)| {
-                derived
-                    .facts
-                    .get(&pred)
-                    .map(|set| set.contains(&tuple))
-                    .unwrap_or(false)
+        Formula::Atom(atom) if atom.pred == DISEQUALITY_PRED => atom_to_ground_tuple(atom, vars)
+            .map(|(_, tuple)| match tuple.as_slice() {
+                [lhs, rhs] if lhs == rhs => false,
+                [lhs, rhs]
+                    if distinct.contains(&[lhs.clone(), rhs.clone()])
+                        || distinct.contains(&[rhs.clone(), lhs.clone()]) =>
+                {
+                    true
+                }
+                [lhs, rhs] if is_fresh(lhs) || is_fresh(rhs) => undecided,
+                _ => disequality_holds(&tuple),
             })
             .unwrap_or(false),
-        Formula::And(items) => items.iter().all(|f| eval_formula(f, derived, vars)),
-        Formula::Not(inner) => !eval_formula(inner, derived, vars),
+        Formula::Atom(atom) => {
+            let Some((pred, tuple)) = atom_to_ground_tuple(atom, vars) else {
+                return false;
+            };
+            let Some(op) = ComparisonOp::from_pred(&pred) else {
+                return derived
+                    .facts
+                    .get(&pred)
+                    .is_some_and(|set| set.contains(&tuple));
+            };
+            match tuple.as_slice() {
+                [Value::Int(lhs), Value::Int(rhs)] => op.holds(*lhs, *rhs),
+                [lhs, rhs] if is_fresh(lhs) || is_fresh(rhs) => undecided,
+                _ => false,
+            }
+        }
+        Formula::And(items) => items
+            .iter()
+            .all(|f| eval_formula(f, derived, vars, distinct, undecided)),
+        Formula::Not(inner) => !eval_formula(inner, derived, vars, distinct, !undecided),
+    }
+}
+
+fn check_relation_refinements(
+    program: &Program,
+    kb: &KnowledgeBase,
+    derived: &DerivedFacts,
+) -> Vec<Diagnostic> {
+    let mut violations = Vec::new();
+    for relation in &program.relations {
+        let Some(tuples) = derived.facts.get(&relation.name) else {
+            continue;
+        };
+        for (idx, refinement) in relation.arg_refinements.iter().enumerate() {
+            let Some(refinement) = refinement else {
+                continue;
+            };
+            for tuple in tuples {
+                let vars = HashMap::from([(refinement.var.clone(), tuple[idx].clone())]);
+                if !eval_formula(&refinement.formula, derived, &vars, &HashSet::new(), false) {
+                    let refined = Type::Refine {
+                        var: refinement.var.clone(),
+                        base: Box::new(Type::Domain(relation.arg_sorts[idx].clone())),
+                        formula: refinement.formula.clone(),
+                    };
+                    violations.push((relation, tuple, idx, render_type(&refined)));
+                }
+            }
+        }
+    }
+    if violations.is_empty() {
+        return Vec::new();
+    }
+
+    let derived_by = first_deriving_rules(kb);
+    violations
+        .into_iter()
+        .map(|(relation, tuple, idx, refined)| {
+            let refinement = format!("argument {} refinement {refined}", idx + 1);
+            tuple_violation(
+                program,
+                &derived_by,
+                relation,
+                tuple,
+                "E-ENTAIL",
+                &refinement,
+                "violates",
+            )
+        })
+        .collect()
+}
+
+fn check_relation_keys(
+    program: &Program,
+    kb: &KnowledgeBase,
+    derived: &DerivedFacts,
+) -> Vec<Diagnostic> {
+    let mut violations = Vec::new();
+    for relation in &program.relations {
+        let (Some(key), Some(tuples)) = (&relation.key, derived.facts.get(&relation.name)) else {
+            continue;
+        };
+        let mut seen = HashMap::new();
+        for tuple in tuples {
+            let key_values = key.iter().map(|&position| &tuple[position - 1]);
+            if let Some(first) = seen.insert(key_values.collect::<Vec<_>>(), tuple) {
+                violations.push((relation, tuple, first));
+            }
+        }
+    }
+    if violations.is_empty() {
+        return Vec::new();
+    }
+
+    let derived_by = first_deriving_rules(kb);
+    violations
+        .into_iter()
+        .map(|(relation, tuple, first)| {
+            let key = format!(
+                "its :key, as {} has the same key",
+                render_tuple(&relation.name, first)
+            );
+            tuple_violation(
+                program,
+                &derived_by,
+                relation,
+                tuple,
+                "E-KEY",
+                &key,
+                "breaks",
+            )
+        })
+        .collect()
+}
+
+fn first_deriving_rules(kb: &KnowledgeBase) -> HashMap<GroundFact, usize> {
+    let mut derived_by = HashMap::new();
+    if let Ok(steps) = solve_facts_iter(kb) {
+        for step in steps.map_while(Result::ok) {
+            derived_by.entry(step.fact).or_insert(step.rule);
+        }
+    }
+    derived_by
+}
+
+fn render_tuple(pred: &str, tuple: &[Value]) -> String {
+    let args = tuple.iter().map(value_to_string).collect::<Vec<_>>();
+    format!("({pred} {})", args.join(" "))
+}
+
+fn tuple_violation(
+    program: &Program,
+    derived_by: &HashMap<GroundFact, usize>,
+    relation: &RelationDecl,
+    tuple: &[Value],
+    code: &'static str,
+    constraint: &str,
+    verb: &str,
+) -> Diagnostic {
+    let no_vars = HashMap::new();
+    let declared = program.facts.iter().find(|fact| {
+        fact.name == relation.name
+            && fact
+                .terms
+                .iter()
+                .map(|term| logic_term_to_value(term, &no_vars))
+                .eq(tuple.iter().cloned().map(Some))
+    });
+    if let Some(fact) = declared {
+        return Diagnostic::new(
+            code,
+            format!("fact {} {verb} {constraint}", relation.name),
+            Some(fact.span.clone()),
+        );
+    }
+    let fact = GroundFact {
+        pred: relation.name.clone(),
+        terms: tuple.to_vec(),
+    };
+    let span = derived_by
+        .get(&fact)
+        .map_or(&relation.span, |rule| &program.rules[*rule].span);
+    Diagnostic::new(
+        code,
+        format!(
+            "rule for {} derives {}, which {verb} {constraint}",
+            relation.name,
+            render_tuple(&relation.name, tuple)
+        ),
+        Some(span.clone()),
+    )
+}
+
+fn is_fresh(value: &Value) -> bool {
+    match value {
+        Value::Symbol(name) => name.starts_with(FRESH_PREFIX),
+        Value::Adt { fields, .. } => fields.iter().any(is_fresh),
+        Value::Int(_) | Value::Bool(_) => false,
     }
 }
 
@@ -1161,7 +2584,7 @@ fn atom_to_ground_tuple(
 fn logic_term_to_value(term: &LogicTerm, vars: &HashMap<String, Value>) -> Option<Value> {
     match term {
         LogicTerm::Var(name) => vars.get(name).cloned(),
-        LogicTerm::Symbol(s) => Some(Value::Symbol(s.clone())),
+        LogicTerm::Symbol(s) => Some(Value::Symbol(Symbol::intern(s))),
         LogicTerm::Int(i) => Some(Value::Int(*i)),
         LogicTerm::Bool(b) => Some(Value::Bool(*b)),
         LogicTerm::Ctor { name, args } => {
@@ -1170,7 +2593,7 @@ fn logic_term_to_value(term: &LogicTerm, vars: &HashMap<String, Value>) -> Optio
                 fields.push(logic_term_to_value(arg, vars)?);
             }
             Some(Value::Adt {
-                ctor: name.clone(),
+                ctor: Symbol::intern(name),
                 fields,
             })
         }
@@ -1276,6 +2699,7 @@ fn substitute_type(ty: &Type, subst: &HashMap<String, LogicTerm>) -> Type {
         Type::Symbol => Type::Symbol,
         Type::Domain(s) => Type::Domain(s.clone()),
         Type::Adt(s) => Type::Adt(s.clone()),
+        Type::Error => Type::Error,
         Type::Fun(args, ret) => Type::Fun(
             args.iter().map(|a| substitute_type(a, subst)).collect(),
             Box::new(substitute_type(ret, subst)),
@@ -1324,26 +2748,28 @@ fn substitute_term(term: &LogicTerm, subst: &HashMap<String, LogicTerm>) -> Logi
     }
 }
 
-fn expr_to_logic_term(expr: &Expr, ctx: &TypeContext) -> Option<LogicTerm> {
+fn expr_to_logic_term(id: ExprId, ctx: &TypeContext) -> Option<LogicTerm> {
+    let expr = &ctx.exprs[id];
     match expr {
         Expr::Var { name, .. } => Some(LogicTerm::Var(name.clone())),
         Expr::Symbol { value, .. } => Some(LogicTerm::Symbol(value.clone())),
         Expr::Int { value, .. } => Some(LogicTerm::Int(*value)),
         Expr::Bool { value, .. } => Some(LogicTerm::Bool(*value)),
+        Expr::Hole { .. } => None,
         Expr::Call { name, args, .. } => {
             if !ctx.constructor_sigs.contains_key(name) {
                 return None;
             }
             let mut terms = Vec::new();
             for arg in args {
-                terms.push(expr_to_logic_term(arg, ctx)?);
+                terms.push(expr_to_logic_term(*arg, ctx)?);
             }
             Some(LogicTerm::Ctor {
                 name: name.clone(),
                 args: terms,
             })
         }
-        Expr::Let { .. } | Expr::If { .. } | Expr::Match { .. } => None,
+        Expr::Let { .. } | Expr::LetFn { .. } | Expr::If { .. } | Expr::Match { .. } => None,
     }
 }
 
@@ -1360,6 +2786,7 @@ fn canonicalize_type(ty: &Type, data_names: &HashSet<String>) -> Type {
         Type::Bool => Type::Bool,
         Type::Int => Type::Int,
         Type::Symbol => Type::Symbol,
+        Type::Error => Type::Error,
         Type::Fun(args, ret) => Type::Fun(
             args.iter()
                 .map(|a| canonicalize_type(a, data_names))
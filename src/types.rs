@@ -1,5 +1,7 @@
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Type {
     Bool,
@@ -13,6 +15,10 @@ pub enum Type {
         base: Box<Type>,
         formula: Formula,
     },
+    /// Stand-in for an expression that failed to type check, so checking can
+    /// continue past it. It is compatible with every type and never appears
+    /// in a successful result.
+    Error,
 }
 
 impl Type {
@@ -31,7 +37,7 @@ impl Type {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum LogicTerm {
     Var(String),
     Symbol(String),
@@ -40,13 +46,13 @@ pub enum LogicTerm {
     Ctor { name: String, args: Vec<LogicTerm> },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Atom {
     pub pred: String,
     pub terms: Vec<LogicTerm>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Formula {
     True,
     Atom(Atom),
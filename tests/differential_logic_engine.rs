@@ -2,6 +2,7 @@ mod support;
 
 use std::collections::{BTreeMap, BTreeSet};
 
+use dtl::{DerivationStep, check_derivation_parity, parse_program};
 use proptest::prelude::*;
 use support::program_generators::logic_program_sources;
 use support::reference_semantics::reference_solve_facts;
@@ -33,6 +34,7 @@ fn logic_engine_matches_reference_on_curated_fixtures() {
     for path in [
         "semantics/negative-stratified/basic.dtl",
         "semantics/negative-stratified/adt_passthrough.dtl",
+        "semantics/disequality/team_peers.dtl",
     ] {
         let src = read_fixture(path);
         let program = prepare_program_from_source(&src);
@@ -42,6 +44,53 @@ fn logic_engine_matches_reference_on_curated_fixtures() {
     }
 }
 
+#[test]
+fn derivation_steps_match_reference_on_curated_fixtures() {
+    for path in [
+        "semantics/negative-stratified/basic.dtl",
+        "semantics/negative-stratified/adt_passthrough.dtl",
+        "semantics/disequality/team_peers.dtl",
+        "semantics/alias-canonicalization/with_alias.dtl",
+        "semantics/assert-counterexample/everyone_allowed.dtl",
+    ] {
+        let src = read_fixture(path);
+        let program = parse_program(&src).expect("fixture should parse");
+        if let Err(diags) = check_derivation_parity(&program) {
+            panic!("derivation mismatch: {path}\nsource:\n{src}\n{diags:#?}");
+        }
+    }
+}
+
+#[test]
+fn derivation_steps_record_rounds_and_premises() {
+    let src = r#"
+        (sort Node)
+        (relation edge (Node Node))
+        (relation reach (Node Node))
+        (fact edge a b)
+        (fact edge b c)
+        (rule (reach ?x ?y) (edge ?x ?y))
+        (rule (reach ?x ?z) (and (edge ?x ?y) (reach ?y ?z)))
+    "#;
+    let program = parse_program(src).expect("source should parse");
+    let steps = check_derivation_parity(&program).expect("engines should agree");
+    let step = |iteration, rule, fact: &str, premises: &[&str]| DerivationStep {
+        stratum: 0,
+        iteration,
+        rule,
+        fact: fact.to_string(),
+        premises: premises.iter().map(|p| p.to_string()).collect(),
+    };
+    assert_eq!(
+        steps,
+        vec![
+            step(1, 0, "reach(a,b)", &["edge(a,b)"]),
+            step(1, 0, "reach(b,c)", &["edge(b,c)"]),
+            step(1, 1, "reach(a,c)", &["edge(a,b)", "reach(b,c)"]),
+        ]
+    );
+}
+
 proptest! {
     #![proptest_config(ProptestConfig {
         cases: 64,
@@ -56,4 +105,12 @@ proptest! {
         eprintln!("generated logic source:\n{src}");
         prop_assert_eq!(production, reference);
     }
+
+    #[test]
+    fn derivation_steps_match_reference_on_generated_programs(src in logic_program_sources()) {
+        let program = prepare_program_from_source(&src);
+        eprintln!("generated logic source:\n{src}");
+        let parity = check_derivation_parity(&program).map(|_| ());
+        prop_assert!(parity.is_ok(), "{:?}", parity);
+    }
 }
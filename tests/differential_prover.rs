@@ -85,6 +85,11 @@ fn prover_matches_reference_on_curated_fixtures() {
         "semantics/alias-canonicalization/with_alias.dtl",
         "semantics/recursive-defn/list_allows.dtl",
         "semantics/assert-counterexample/everyone_allowed.dtl",
+        "semantics/int-interval/bounded_score.dtl",
+        "semantics/termination-measure/countdown.dtl",
+        "semantics/local-fn/captured_bonus.dtl",
+        "semantics/defconst/retry_budget.dtl",
+        "semantics/multi-clause/role_access.dtl",
     ] {
         let src = read_fixture(path);
         let program = prepare_program_from_source(&src);
@@ -156,6 +161,34 @@ fn prove_reports_inclusion_minimal_premises() {
     }
 }
 
+#[test]
+fn reference_module_decides_single_obligations() {
+    let src = read_fixture("semantics/assert-counterexample/everyone_allowed.dtl");
+    let program = parse_program(&src).expect("parse should succeed");
+    let result =
+        dtl::reference::check_assert(&program, "everyone-allowed").expect("assert obligation");
+    assert_eq!(result.result, "failed");
+    assert_eq!(
+        result
+            .valuation
+            .get("u")
+            .map(dtl::reference::value_to_string)
+            .as_deref(),
+        Some("(bob)")
+    );
+    assert_eq!(
+        dtl::reference::solve_facts(&program)
+            .expect("reference solve")
+            .relation_facts("allowed"),
+        BTreeSet::from([vec!["(alice)".to_string()]])
+    );
+
+    let errs = dtl::reference::check_refine(&program, "everyone-allowed")
+        .expect_err("no refinement obligation");
+    assert!(errs.iter().any(|diag| diag.code == "E-RESOLVE"
+        && diag.message == "unknown obligation: defn::everyone-allowed"));
+}
+
 #[test]
 fn prove_rejects_missing_universe_fixture() {
     let src = read_fixture("semantics/errors/missing_universe.dtl");
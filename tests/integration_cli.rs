@@ -1,6 +1,6 @@
 use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
-use serde_json::Value;
+use serde_json::{Value, json};
 use std::fs;
 use std::path::Path;
 use tempfile::tempdir;
@@ -13,6 +13,21 @@ const SELF_COMMANDS: &[&str] = &[
     "fmt",
     "selfdoc",
     "selfcheck",
+    "strata",
+    "plan",
+    "codes",
+    "grammar",
+    "bench",
+    "slice",
+    "minimize",
+    "diff",
+    "expand",
+    "refs",
+    "tags",
+    "codegen",
+    "eval",
+    "run",
+    "explore",
 ];
 
 fn write_selfcheck_repo(dir: &Path, rows: &[(&str, &str)]) {
@@ -95,6 +110,205 @@ fn cli_returns_zero_for_valid_program() {
         .stdout(predicate::str::contains("ok"));
 }
 
+#[test]
+fn cli_check_timings_are_reported_on_stderr() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("ok.dtl");
+    fs::write(
+        &path,
+        r#"
+        (sort Subject)
+        (relation allowed (Subject))
+        (fact allowed alice)
+        (defn can ((u Subject)) Bool (allowed u))
+        "#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("check").arg(&path).arg("--timings");
+    cmd.assert().success().stdout("ok\n").stderr(
+        predicate::str::contains("timings:")
+            .and(predicate::str::contains("parse"))
+            .and(predicate::str::contains("resolve"))
+            .and(predicate::str::contains("stratify"))
+            .and(predicate::str::contains("typecheck"))
+            .and(predicate::str::contains("fixpoint"))
+            .and(predicate::str::contains("total")),
+    );
+}
+
+#[test]
+fn cli_strata_prints_layers_and_forcing_edges() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("strata.dtl");
+    fs::write(
+        &path,
+        r#"(sort Subject)
+(relation seed (Subject))
+(relation blocked (Subject))
+(relation allowed (Subject))
+(fact seed a)
+(rule (blocked ?x) (seed ?x))
+(rule (allowed ?x) (and (seed ?x) (not (blocked ?x))))
+"#,
+    )
+    .expect("write");
+
+    let mut text = cargo_bin_cmd!("dtl");
+    text.arg("strata").arg(&path);
+    text.assert()
+        .success()
+        .stdout("stratum 0: blocked seed\nstratum 1: allowed\n  allowed -(not)-> blocked at 7:2\n");
+
+    let mut json = cargo_bin_cmd!("dtl");
+    let output = json
+        .arg("strata")
+        .arg(&path)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+    assert!(output.stderr.is_empty());
+    let value: Value = serde_json::from_slice(&output.stdout).expect("json");
+    assert_eq!(value["status"], "ok");
+    assert_eq!(value["strata"][1]["stratum"], 1);
+    assert_eq!(value["strata"][1]["relations"][0], "allowed");
+    let forcing = value["edges"]
+        .as_array()
+        .expect("edges")
+        .iter()
+        .filter(|edge| edge["forces_order"] == true)
+        .collect::<Vec<_>>();
+    assert_eq!(forcing.len(), 1);
+    assert_eq!(forcing[0]["head"], "allowed");
+    assert_eq!(forcing[0]["dep"], "blocked");
+    assert_eq!(forcing[0]["negated"], true);
+    assert_eq!(forcing[0]["span"]["line"], 7);
+}
+
+#[test]
+fn cli_plan_prints_join_order_and_estimates() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("plan.dtl");
+    fs::write(
+        &path,
+        r#"(sort Subject)
+(relation seed (Subject))
+(relation blocked (Subject))
+(relation allowed (Subject))
+(fact seed a)
+(fact seed b)
+(fact blocked b)
+(rule (allowed ?x) (and (seed ?x) (not (blocked ?x))))
+"#,
+    )
+    .expect("write");
+
+    let mut text = cargo_bin_cmd!("dtl");
+    text.arg("plan").arg(&path);
+    text.assert().success().stdout(
+        "stratum 0: blocked seed\nstratum 1: allowed\n  \
+         rule 0 (allowed x) at 8:2: est. rows 2, cost 4\n    \
+         1. scan (seed x): full scan, binds x, |seed| 2, rows 1 -> 2, cost 2\n    \
+         2. anti-join (blocked x): tuple lookup, on x, |blocked| 1, rows 2 -> 2, cost 2\n",
+    );
+
+    let mut json = cargo_bin_cmd!("dtl");
+    let output = json
+        .arg("plan")
+        .arg(&path)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+    let value: Value = serde_json::from_slice(&output.stdout).expect("json");
+    assert_eq!(value["status"], "ok");
+    let rule = &value["strata"][1]["rules"][0];
+    assert_eq!(rule["head"], "(allowed x)");
+    assert_eq!(rule["steps"][0]["access"], "full_scan");
+    assert_eq!(rule["steps"][1]["kind"], "anti_join");
+    assert_eq!(rule["span"]["line"], 8);
+}
+
+#[test]
+fn cli_strata_reports_negative_cycle() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("cycle.dtl");
+    fs::write(
+        &path,
+        r#"(sort Subject)
+(relation seed (Subject))
+(relation p (Subject))
+(relation q (Subject))
+(rule (p ?x) (and (seed ?x) (not (q ?x))))
+(rule (q ?x) (p ?x))
+"#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("strata").arg(&path);
+    cmd.assert().failure().stderr(
+        predicate::str::contains("E-STRATIFY").and(predicate::str::contains("p -(not)-> q -> p")),
+    );
+}
+
+#[test]
+fn cli_check_applies_project_aliases_from_dtl_toml() {
+    let dir = tempdir().expect("tempdir");
+    fs::write(
+        dir.path().join("dtl.toml"),
+        "[aliases]\n\"アクセス可能\" = \"can-access\"\n",
+    )
+    .expect("write config");
+    let nested = dir.path().join("policy");
+    fs::create_dir_all(&nested).expect("mkdir");
+    let path = nested.join("ok.dtl");
+    fs::write(
+        &path,
+        r#"(sort Subject)
+(relation can-access (Subject))
+(fact アクセス可能 alice)
+"#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("check").arg(&path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("ok"));
+
+    let mut lint = cargo_bin_cmd!("dtl");
+    lint.arg("lint").arg(&path).arg("--format").arg("json");
+    lint.assert().success().stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn cli_check_reports_invalid_project_config() {
+    let dir = tempdir().expect("tempdir");
+    fs::write(dir.path().join("dtl.toml"), "[aliases]\nx = 1\n").expect("write config");
+    let path = dir.path().join("ok.dtl");
+    fs::write(&path, "(sort Subject)\n").expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("check").arg(&path).arg("--format").arg("json");
+    let output = cmd.assert().failure().get_output().stdout.clone();
+    let json: Value = serde_json::from_slice(&output).expect("json");
+    assert_eq!(json["diagnostics"][0]["code"], "E-CONFIG");
+    assert!(
+        json["diagnostics"][0]["source"]
+            .as_str()
+            .is_some_and(|source| source.ends_with("dtl.toml")),
+        "json={json}"
+    );
+}
+
 #[test]
 fn cli_returns_one_for_invalid_program() {
     let dir = tempdir().expect("tempdir");
@@ -219,6 +433,66 @@ fn cli_reports_missing_import_file() {
         .stderr(predicate::str::contains("missing.dtl"));
 }
 
+#[test]
+fn cli_resolves_imports_through_include_dirs_and_dtl_path() {
+    let dir = tempdir().expect("tempdir");
+    let lib = dir.path().join("lib");
+    let env = dir.path().join("env");
+    let app = dir.path().join("app");
+    for sub in [&lib, &env, &app] {
+        fs::create_dir_all(sub.join("common")).expect("mkdir");
+    }
+    fs::write(lib.join("common/types.dtl"), "(sort Subject)\n").expect("write");
+    fs::write(env.join("common/types.dtl"), "(sort Shadowed)\n").expect("write");
+    fs::write(env.join("common/res.dtl"), "(sort Resource)\n").expect("write");
+    let entry = app.join("main.dtl");
+    fs::write(
+        &entry,
+        r#"
+        (import "common/types.dtl")
+        (import "common/res.dtl")
+        (relation can (Subject Resource))
+        "#,
+    )
+    .expect("write");
+
+    cargo_bin_cmd!("dtl")
+        .env("DTL_PATH", &env)
+        .arg("check")
+        .arg("-I")
+        .arg(&lib)
+        .arg(&entry)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ok"));
+
+    let output = cargo_bin_cmd!("dtl")
+        .env_remove("DTL_PATH")
+        .arg("check")
+        .arg("--include")
+        .arg(&lib)
+        .arg(&entry)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .failure()
+        .code(4)
+        .get_output()
+        .clone();
+    let json: Value = serde_json::from_slice(&output.stdout).expect("json");
+    let diag = &json["diagnostics"][0];
+    assert_eq!(diag["code"], "E-IO");
+    assert_eq!(diag["span"]["line"], 3);
+    assert_eq!(
+        diag["message"],
+        format!(
+            "import not found: common/res.dtl (searched: {}, {})",
+            app.join("common/res.dtl").display(),
+            lib.join("common/res.dtl").display()
+        )
+    );
+}
+
 #[test]
 fn cli_reports_import_cycle() {
     let dir = tempdir().expect("tempdir");
@@ -697,8 +971,8 @@ fn cli_selfcheck_succeeds_with_full_claim_coverage() {
     let value: Value = serde_json::from_slice(&output).expect("valid selfcheck json");
     assert_eq!(value["status"], "ok");
     assert_eq!(value["proof"]["engine"], "native");
-    assert_eq!(value["proof"]["claim_coverage"]["total_claims"], 7);
-    assert_eq!(value["proof"]["claim_coverage"]["proved_claims"], 7);
+    assert_eq!(value["proof"]["claim_coverage"]["total_claims"], 22);
+    assert_eq!(value["proof"]["claim_coverage"]["proved_claims"], 22);
     assert_eq!(value["proof"]["summary"]["failed"], 0);
     assert!(out.join("spec.json").exists());
     assert!(out.join("proof-trace.json").exists());
@@ -734,8 +1008,8 @@ fn cli_selfcheck_reference_engine_succeeds_with_full_claim_coverage() {
     let value: Value = serde_json::from_slice(&output).expect("valid selfcheck json");
     assert_eq!(value["status"], "ok");
     assert_eq!(value["proof"]["engine"], "reference");
-    assert_eq!(value["proof"]["claim_coverage"]["total_claims"], 7);
-    assert_eq!(value["proof"]["claim_coverage"]["proved_claims"], 7);
+    assert_eq!(value["proof"]["claim_coverage"]["total_claims"], 22);
+    assert_eq!(value["proof"]["claim_coverage"]["proved_claims"], 22);
     assert_eq!(value["proof"]["summary"]["failed"], 0);
     assert!(out.join("spec.json").exists());
     assert!(out.join("proof-trace.json").exists());
@@ -770,8 +1044,8 @@ fn cli_selfcheck_fails_when_claim_coverage_is_insufficient() {
     let value: Value = serde_json::from_slice(&output).expect("valid selfcheck json");
     assert_eq!(value["status"], "error");
     assert_eq!(value["proof"]["summary"]["failed"], 0);
-    assert_eq!(value["proof"]["claim_coverage"]["total_claims"], 7);
-    assert_eq!(value["proof"]["claim_coverage"]["proved_claims"], 6);
+    assert_eq!(value["proof"]["claim_coverage"]["total_claims"], 22);
+    assert_eq!(value["proof"]["claim_coverage"]["proved_claims"], 21);
     assert!(
         value["diagnostics"]
             .as_array()
@@ -782,17 +1056,12 @@ fn cli_selfcheck_fails_when_claim_coverage_is_insufficient() {
 }
 
 #[test]
-fn cli_selfcheck_fails_when_selfdoc_obligation_fails() {
+fn cli_selfcheck_min_coverage_accepts_partial_claim_coverage() {
     let dir = tempdir().expect("tempdir");
     let rows = SELF_COMMANDS
         .iter()
-        .map(|name| {
-            if *name == "check" {
-                ("check", "src/missing.rs")
-            } else {
-                (*name, "src/main.rs")
-            }
-        })
+        .filter(|name| **name != "tags")
+        .map(|name| (*name, "src/main.rs"))
         .collect::<Vec<_>>();
     write_selfcheck_repo(dir.path(), &rows);
 
@@ -806,49 +1075,149 @@ fn cli_selfcheck_fails_when_selfdoc_obligation_fails() {
         .arg(&out)
         .arg("--format")
         .arg("json")
+        .arg("--min-coverage")
+        .arg("0.9")
         .assert()
-        .failure()
+        .success()
         .get_output()
         .stdout
         .clone();
 
     let value: Value = serde_json::from_slice(&output).expect("valid selfcheck json");
-    assert_eq!(value["status"], "error");
-    assert_eq!(value["proof"]["claim_coverage"]["total_claims"], 7);
-    assert_eq!(value["proof"]["claim_coverage"]["proved_claims"], 7);
-    assert!(
-        value["proof"]["summary"]["failed"]
-            .as_u64()
-            .expect("failed count")
-            > 0
+    assert_eq!(value["status"], "ok");
+    let coverage = &value["proof"]["claim_coverage"];
+    assert_eq!(coverage["proved_claims"], 21);
+    assert_eq!(
+        coverage["policy"],
+        json!({
+            "min_coverage": 0.9,
+            "ratio": 0.9545454545454546,
+            "allowed_unproved": [],
+            "unproved": ["cli::tags"]
+        })
+    );
+    let trace: Value =
+        serde_json::from_slice(&fs::read(out.join("proof-trace.json")).expect("read trace"))
+            .expect("valid trace");
+    assert_eq!(
+        trace["claim_coverage"]["policy"]["ratio"],
+        0.9545454545454546
     );
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("selfcheck")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&out)
+        .arg("--min-coverage")
+        .arg("0.96")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("E-SELFCHECK"));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("selfcheck")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&out)
+        .arg("--min-coverage")
+        .arg("1.5")
+        .assert()
+        .code(4);
 }
 
 #[test]
-fn cli_selfcheck_text_missing_config_returns_code_2() {
+fn cli_selfcheck_allowlist_waives_unproved_claims() {
     let dir = tempdir().expect("tempdir");
-    let out = dir.path().join("out");
+    let rows = SELF_COMMANDS
+        .iter()
+        .filter(|name| **name != "tags")
+        .map(|name| (*name, "src/main.rs"))
+        .collect::<Vec<_>>();
+    write_selfcheck_repo(dir.path(), &rows);
+    let config_path = dir.path().join(".dtl-selfdoc.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    fs::write(
+        &config_path,
+        format!("{config}\n[selfcheck]\nallow_unproved = [\"cli::tags\"]\n"),
+    )
+    .expect("write config");
+
     let mut cmd = cargo_bin_cmd!("dtl");
-    cmd.arg("selfcheck")
+    let output = cmd
+        .arg("selfcheck")
         .arg("--repo")
         .arg(dir.path())
         .arg("--out")
-        .arg(&out)
+        .arg(dir.path().join("out"))
+        .arg("--format")
+        .arg("json")
         .assert()
-        .code(2)
-        .stderr(predicate::str::contains("E-SELFDOC-CONFIG"));
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let value: Value = serde_json::from_slice(&output).expect("valid selfcheck json");
+    let policy = &value["proof"]["claim_coverage"]["policy"];
+    assert_eq!(policy["min_coverage"], 1.0);
+    assert_eq!(policy["allowed_unproved"], json!(["cli::tags"]));
+    assert_eq!(policy["unproved"], json!([]));
+
+    fs::write(
+        &config_path,
+        format!("{config}\n[selfcheck]\nallow_unproved = [\"cli::missing\"]\n"),
+    )
+    .expect("write config");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("selfcheck")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(dir.path().join("out"))
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .code(4)
+        .get_output()
+        .stdout
+        .clone();
+    let value: Value = serde_json::from_slice(&output).expect("valid selfcheck json");
+    assert_eq!(value["diagnostics"][0]["code"], "E-SELFDOC-CONFIG");
 }
 
 #[test]
-fn cli_selfcheck_text_reports_claim_coverage_shortfall() {
+fn cli_selfcheck_reports_drift_against_baseline() {
     let dir = tempdir().expect("tempdir");
     let rows = SELF_COMMANDS
         .iter()
-        .filter(|name| **name != "selfcheck")
         .map(|name| (*name, "src/main.rs"))
         .collect::<Vec<_>>();
     write_selfcheck_repo(dir.path(), &rows);
 
+    let baseline = dir.path().join("baseline");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("selfcheck")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&baseline)
+        .assert()
+        .success();
+    assert!(!baseline.join("selfdoc-drift.json").exists());
+
+    fs::write(dir.path().join("src/lib.rs"), "pub fn f() {}\n").expect("write lib");
+    let workflow = dir.path().join(".github/workflows/ci.yml");
+    let body = fs::read_to_string(&workflow).expect("read workflow");
+    fs::write(
+        &workflow,
+        body.replace("cargo test", "cargo test --workspace"),
+    )
+    .expect("write workflow");
+
     let out = dir.path().join("out");
     let mut cmd = cargo_bin_cmd!("dtl");
     cmd.arg("selfcheck")
@@ -856,35 +1225,1427 @@ fn cli_selfcheck_text_reports_claim_coverage_shortfall() {
         .arg(dir.path())
         .arg("--out")
         .arg(&out)
+        .arg("--doc-format")
+        .arg("markdown")
+        .arg("--baseline")
+        .arg(&baseline)
         .assert()
-        .failure()
-        .stdout(predicate::str::is_empty())
-        .stderr(predicate::str::contains("E-SELFCHECK"));
+        .success();
+
+    let drift: Value =
+        serde_json::from_slice(&fs::read(out.join("selfdoc-drift.json")).expect("read drift"))
+            .expect("valid drift json");
+    assert_eq!(drift["baseline"], baseline.display().to_string());
+    assert_eq!(
+        drift["modules"],
+        json!([{
+            "change": "added",
+            "key": "src/lib.rs",
+            "after": {"name": "src/lib.rs", "path": "src/lib.rs", "category": "source"}
+        }])
+    );
+    assert_eq!(drift["references"], json!([]));
+    assert_eq!(drift["contracts"], json!([]));
+    let gates = drift["quality_gates"].as_array().expect("gates array");
+    assert_eq!(gates.len(), 1);
+    assert_eq!(gates[0]["change"], "changed");
+    assert_eq!(gates[0]["before"]["command"], "cargo test");
+    assert_eq!(gates[0]["after"]["command"], "cargo test --workspace");
+
+    let spec = fs::read_to_string(out.join("spec.md")).expect("read spec");
+    assert!(spec.contains("## ベースラインとの差分"));
+    assert!(spec.contains("- 追加: `src/lib.rs`"));
+    let index: Value =
+        serde_json::from_slice(&fs::read(out.join("doc-index.json")).expect("read index"))
+            .expect("valid index");
+    assert!(
+        index["files"]
+            .as_array()
+            .expect("files array")
+            .contains(&json!("selfdoc-drift.json"))
+    );
 }
 
 #[test]
-fn cli_selfcheck_text_with_json_doc_pdf_prints_skip_warning() {
+fn cli_selfcheck_merges_several_repositories() {
     let dir = tempdir().expect("tempdir");
     let rows = SELF_COMMANDS
         .iter()
         .map(|name| (*name, "src/main.rs"))
         .collect::<Vec<_>>();
-    write_selfcheck_repo(dir.path(), &rows);
+    let service = dir.path().join("service");
+    let shared = dir.path().join("shared");
+    write_selfcheck_repo(&service, &rows);
+    write_selfcheck_repo(&shared, &[]);
+    let readme = service.join("README.md");
+    let body = fs::read_to_string(&readme).expect("read readme");
+    fs::write(&readme, format!("{body}\n[shared](../shared/README.md)\n")).expect("write readme");
 
     let out = dir.path().join("out");
     let mut cmd = cargo_bin_cmd!("dtl");
     cmd.arg("selfcheck")
         .arg("--repo")
-        .arg(dir.path())
+        .arg(&service)
+        .arg("--repo")
+        .arg(&shared)
         .arg("--out")
         .arg(&out)
-        .arg("--doc-format")
-        .arg("json")
-        .arg("--pdf")
         .assert()
-        .success()
-        .stdout(predicate::str::contains("ok"))
+        .success();
+
+    let generated = fs::read_to_string(out.join("selfdoc.generated.dtl")).expect("read dsl");
+    assert!(generated.contains("(参照 :元 \"service/README.md\" :先 \"shared/README.md\")"));
+    assert!(generated.contains(":パス \"shared/src/main.rs\""));
+    assert!(generated.contains("(契約 :名前 \"cli::check\" :出典 \"service/README.md\""));
+    assert!(out.join("selfdoc-cache.service.json").exists());
+    assert!(out.join("selfdoc-cache.shared.json").exists());
+
+    fs::write(&readme, format!("{body}\n[gone](../shared/gone.md)\n")).expect("write readme");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("selfcheck")
+        .arg("--repo")
+        .arg(&service)
+        .arg("--repo")
+        .arg(&shared)
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .failure()
         .stderr(predicate::str::contains(
-            "JSON 形式では PDF 生成をスキップしました",
+            "service/README.md -> shared/gone.md",
         ));
 }
+
+#[test]
+fn cli_selfcheck_fails_when_baseline_is_missing() {
+    let dir = tempdir().expect("tempdir");
+    let rows = SELF_COMMANDS
+        .iter()
+        .map(|name| (*name, "src/main.rs"))
+        .collect::<Vec<_>>();
+    write_selfcheck_repo(dir.path(), &rows);
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("selfcheck")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(dir.path().join("out"))
+        .arg("--format")
+        .arg("json")
+        .arg("--baseline")
+        .arg(dir.path().join("missing"))
+        .assert()
+        .code(4)
+        .get_output()
+        .stdout
+        .clone();
+
+    let value: Value = serde_json::from_slice(&output).expect("valid selfcheck json");
+    assert_eq!(value["status"], "error");
+    assert_eq!(value["diagnostics"][0]["code"], "E-SELFDOC-BASELINE");
+}
+
+#[test]
+fn cli_selfcheck_fails_when_selfdoc_obligation_fails() {
+    let dir = tempdir().expect("tempdir");
+    let rows = SELF_COMMANDS
+        .iter()
+        .map(|name| {
+            if *name == "check" {
+                ("check", "src/missing.rs")
+            } else {
+                (*name, "src/main.rs")
+            }
+        })
+        .collect::<Vec<_>>();
+    write_selfcheck_repo(dir.path(), &rows);
+
+    let out = dir.path().join("out");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("selfcheck")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&out)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .failure()
+        .get_output()
+        .stdout
+        .clone();
+
+    let value: Value = serde_json::from_slice(&output).expect("valid selfcheck json");
+    assert_eq!(value["status"], "error");
+    assert_eq!(value["proof"]["claim_coverage"]["total_claims"], 22);
+    assert_eq!(value["proof"]["claim_coverage"]["proved_claims"], 22);
+    assert!(
+        value["proof"]["summary"]["failed"]
+            .as_u64()
+            .expect("failed count")
+            > 0
+    );
+}
+
+#[test]
+fn cli_selfcheck_github_format_annotates_failing_files() {
+    let dir = tempdir().expect("tempdir");
+    let rows = SELF_COMMANDS
+        .iter()
+        .filter(|name| **name != "tags")
+        .map(|name| {
+            if *name == "check" {
+                ("check", "src/missing.rs")
+            } else {
+                (*name, "src/main.rs")
+            }
+        })
+        .collect::<Vec<_>>();
+    write_selfcheck_repo(dir.path(), &rows);
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .current_dir(dir.path())
+        .arg("selfcheck")
+        .arg("--repo")
+        .arg(".")
+        .arg("--out")
+        .arg("out")
+        .arg("--format")
+        .arg("github")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("selfcheck proof failed"))
+        .get_output()
+        .stdout
+        .clone();
+
+    let stdout = String::from_utf8(output).expect("utf8 stdout");
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2, "{stdout}");
+    assert!(
+        lines[0]
+            .starts_with("::error file=README.md,line=6,title=assert%3A%3Acontract_impl_exists::"),
+        "{stdout}"
+    );
+    assert!(lines[0].contains("src/missing.rs"));
+    assert!(
+        lines[1].starts_with("::error file=README.md,line=3,title=E-SELFCHECK::"),
+        "{stdout}"
+    );
+    assert!(lines[1].contains("cli::tags"));
+
+    fs::remove_file(dir.path().join(".dtl-selfdoc.toml")).expect("remove config");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.current_dir(dir.path())
+        .arg("selfcheck")
+        .arg("--out")
+        .arg("out")
+        .arg("--format")
+        .arg("github")
+        .assert()
+        .code(4)
+        .stdout(predicate::str::starts_with(
+            "::error file=.dtl-selfdoc.toml,title=E-SELFDOC-CONFIG::",
+        ));
+}
+
+#[test]
+fn cli_check_prove_and_lint_github_format_annotate_source_lines() {
+    let dir = tempdir().expect("tempdir");
+    fs::write(
+        dir.path().join("broken.dtl"),
+        "(sort Subject)\n(relation admin (Subject))\n(rule (can-delete ?u) (admin ?u))\n",
+    )
+    .expect("write");
+    fs::write(
+        dir.path().join("policy.dtl"),
+        r#"(sort Subject)
+(relation admin (Subject))
+(relation can-delete (Subject))
+(universe Subject (alice bob))
+(fact admin alice)
+(rule (can-delete ?u) (admin ?u))
+(assert everyone-deletes ((u Subject)) (can-delete u))
+(assert admins-delete ((u Subject)) (can-delete u))
+"#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.current_dir(dir.path())
+        .arg("check")
+        .arg("broken.dtl")
+        .arg("--format")
+        .arg("github")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("E-RESOLVE"))
+        .stdout(predicate::str::starts_with(
+            "::error file=broken.dtl,line=3,col=2,title=E-RESOLVE::undefined relation in rule head: can-delete",
+        ));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .current_dir(dir.path())
+        .arg("prove")
+        .arg("policy.dtl")
+        .arg("--format")
+        .arg("github")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("proof failed"))
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8(output).expect("utf8 stdout");
+    assert_eq!(
+        stdout.lines().next(),
+        Some(
+            "::error file=policy.dtl,line=7,col=2,title=assert%3A%3Aeveryone-deletes::proof failed\
+             %0Acounterexample: u = bob%0Amissing: can-delete(bob)"
+        ),
+        "{stdout}"
+    );
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.current_dir(dir.path())
+        .arg("lint")
+        .arg("policy.dtl")
+        .arg("--format")
+        .arg("github")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "::warning file=policy.dtl,line=8,col=2,title=L-DUP-EXACT::",
+        ));
+}
+
+#[test]
+fn cli_selfcheck_text_missing_config_returns_io_exit_code() {
+    let dir = tempdir().expect("tempdir");
+    let out = dir.path().join("out");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("selfcheck")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("E-SELFDOC-CONFIG"));
+}
+
+#[test]
+fn cli_selfcheck_text_reports_claim_coverage_shortfall() {
+    let dir = tempdir().expect("tempdir");
+    let rows = SELF_COMMANDS
+        .iter()
+        .filter(|name| **name != "selfcheck")
+        .map(|name| (*name, "src/main.rs"))
+        .collect::<Vec<_>>();
+    write_selfcheck_repo(dir.path(), &rows);
+
+    let out = dir.path().join("out");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("selfcheck")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .failure()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("E-SELFCHECK"));
+}
+
+#[test]
+fn cli_selfcheck_text_with_json_doc_pdf_prints_skip_warning() {
+    let dir = tempdir().expect("tempdir");
+    let rows = SELF_COMMANDS
+        .iter()
+        .map(|name| (*name, "src/main.rs"))
+        .collect::<Vec<_>>();
+    write_selfcheck_repo(dir.path(), &rows);
+
+    let out = dir.path().join("out");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("selfcheck")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&out)
+        .arg("--doc-format")
+        .arg("json")
+        .arg("--pdf")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ok"))
+        .stderr(predicate::str::contains(
+            "JSON 形式では PDF 生成をスキップしました",
+        ));
+}
+
+#[test]
+fn cli_check_groups_multi_file_diagnostics_and_truncates_with_max_errors() {
+    let dir = tempdir().expect("tempdir");
+    let facts = dir.path().join("facts.dtl");
+    let funcs = dir.path().join("funcs.dtl");
+    fs::write(
+        &facts,
+        "(sort Subject)\n(relation allowed (Subject))\n(fact alowed alice)\n(fact denied bob)\n",
+    )
+    .expect("write");
+    fs::write(&funcs, "(defn f ((x Int)) Int (unknown-call x))\n").expect("write");
+    let facts_name = facts.display().to_string();
+    let funcs_name = funcs.display().to_string();
+
+    let mut text_cmd = cargo_bin_cmd!("dtl");
+    text_cmd
+        .arg("check")
+        .arg(&facts)
+        .arg(&funcs)
+        .arg("--max-errors")
+        .arg("2")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "{facts_name}: 2 errors\n"
+        )))
+        .stderr(predicate::str::contains(format!("{funcs_name}: 1 error\n")))
+        .stderr(predicate::str::contains("unknown-call").not())
+        .stderr(predicate::str::contains(
+            "... 1 more error omitted (--max-errors 2)\n",
+        ))
+        .stderr(predicate::str::contains("3 errors in 2 files\n"));
+
+    let mut json_cmd = cargo_bin_cmd!("dtl");
+    let output = json_cmd
+        .arg("check")
+        .arg(&facts)
+        .arg(&funcs)
+        .arg("--max-errors")
+        .arg("1")
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let value: Value = serde_json::from_slice(&output).expect("valid json");
+    assert_eq!(
+        value["diagnostics"].as_array().expect("diagnostics").len(),
+        1
+    );
+    assert_eq!(
+        value["summary"],
+        json!({
+            "total": 3,
+            "omitted": 2,
+            "files": [
+                {"source": facts_name, "count": 2},
+                {"source": funcs_name, "count": 1}
+            ]
+        })
+    );
+}
+
+#[test]
+fn cli_json_diagnostics_include_related_spans() {
+    let dir = tempdir().expect("tempdir");
+    let schema = dir.path().join("schema.dtl");
+    let policy = dir.path().join("policy.dtl");
+    fs::write(&schema, "(sort Subject)\n").expect("write");
+    fs::write(&policy, "(sort Subject)\n").expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("check")
+        .arg(&schema)
+        .arg(&policy)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let value: Value = serde_json::from_slice(&output).expect("valid json");
+    let diag = &value["diagnostics"][0];
+    assert_eq!(diag["message"], "duplicate sort: Subject");
+    assert_eq!(diag["source"], policy.display().to_string().as_str());
+    assert_eq!(
+        diag["related"],
+        json!([{
+            "label": "first defined here",
+            "source": schema.display().to_string(),
+            "span": {"start": 1, "end": 5, "line": 1, "column": 2}
+        }])
+    );
+}
+
+#[test]
+fn cli_json_diagnostics_include_fixes() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("typo.dtl");
+    fs::write(
+        &path,
+        "(defn fib ((x Int)) Int x)\n(defn g ((x Int)) Int (fibb x))\n",
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("check")
+        .arg(&path)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let value: Value = serde_json::from_slice(&output).expect("valid json");
+    assert_eq!(
+        value["diagnostics"][0]["fixes"],
+        json!([{
+            "source": path.display().to_string(),
+            "span": {"start": 50, "end": 54, "line": 2, "column": 24},
+            "replacement": "fib"
+        }])
+    );
+}
+
+#[test]
+fn cli_check_jsonl_emits_diagnostic_events_then_done() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("typo.dtl");
+    fs::write(
+        &path,
+        "(defn fib ((x Int)) Int x)\n(defn g ((x Int)) Int (fibb x))\n",
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("check")
+        .arg(&path)
+        .arg("--format")
+        .arg("jsonl")
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let events = String::from_utf8(output)
+        .expect("utf8")
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).expect("json line"))
+        .collect::<Vec<_>>();
+    assert_eq!(events.len(), 2, "events={events:?}");
+    assert_eq!(events[0]["event"], "diagnostic");
+    assert_eq!(events[0]["code"], "E-RESOLVE");
+    assert_eq!(events[1], json!({"event": "done", "status": "error"}));
+
+    fs::write(&path, "(defn fib ((x Int)) Int x)\n").expect("write");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("check")
+        .arg(&path)
+        .arg("--format")
+        .arg("jsonl")
+        .assert()
+        .success()
+        .stdout(predicate::str::diff(
+            "{\"event\":\"done\",\"status\":\"ok\",\"report\":{\"functions_checked\":1,\"errors\":0}}\n",
+        ));
+}
+
+#[test]
+fn cli_exit_codes_distinguish_failure_classes() {
+    let dir = tempdir().expect("tempdir");
+    let cases = [
+        ("parse.dtl", "(relation allowed (Subject)", "check", 1),
+        ("type.dtl", "(defn f ((x Int)) Bool x)\n", "check", 2),
+        (
+            "proof.dtl",
+            "(data Subject (alice) (bob))\n(relation allowed (Subject))\n(fact allowed (alice))\n(universe Subject ((alice) (bob)))\n(assert everyone ((u Subject)) (allowed u))\n",
+            "prove",
+            3,
+        ),
+    ];
+    for (name, src, subcommand, code) in cases {
+        let path = dir.path().join(name);
+        fs::write(&path, src).expect("write");
+        let mut cmd = cargo_bin_cmd!("dtl");
+        cmd.arg(subcommand).arg(&path).assert().code(code);
+    }
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("check")
+        .arg(dir.path().join("missing.dtl"))
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("E-IO"));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("check").arg("--no-such-flag").assert().code(4);
+
+    let unformatted = dir.path().join("fmt.dtl");
+    fs::write(&unformatted, "(sort   Subject)\n").expect("write");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("fmt")
+        .arg("--check")
+        .arg(&unformatted)
+        .assert()
+        .code(5);
+}
+
+#[test]
+fn cli_codes_lists_exit_code_mapping() {
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("codes")
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let value: Value = serde_json::from_slice(&output).expect("json");
+    let entries = value["exit_codes"].as_array().expect("exit_codes");
+    let classes = entries
+        .iter()
+        .map(|entry| {
+            (
+                entry["code"].as_i64().expect("code"),
+                entry["class"].clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        classes,
+        vec![
+            (0, json!("ok")),
+            (1, json!("parse")),
+            (2, json!("type")),
+            (3, json!("proof")),
+            (4, json!("io")),
+            (5, json!("lint")),
+            (6, json!("eval")),
+        ]
+    );
+    assert!(
+        entries[1]["diagnostic_codes"]
+            .as_array()
+            .expect("diagnostic_codes")
+            .contains(&json!("E-PARSE"))
+    );
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("codes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3  proof"));
+}
+
+#[test]
+fn cli_verbosity_flags_control_progress_logging() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("progress.dtl");
+    fs::write(
+        &path,
+        "(data Subject (alice) (bob))\n(relation allowed (Subject))\n(fact allowed (alice))\n(universe Subject ((alice) (bob)))\n(assert alice-allowed () (allowed (alice)))\n",
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("prove")
+        .arg(&path)
+        .arg("-v")
+        .env_remove("DTL_LOG")
+        .assert()
+        .success()
+        .stdout("ok\n")
+        .stderr(
+            predicate::str::contains("file loaded")
+                .and(predicate::str::contains("strata computed"))
+                .and(predicate::str::contains(
+                    "obligation checked id=assert::alice-allowed result=proved",
+                ))
+                .and(predicate::str::contains(
+                    "proof finished total=1 proved=1 failed=0",
+                ))
+                .and(predicate::str::contains("phase finished").not()),
+        );
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("-vv")
+        .arg("check")
+        .arg(&path)
+        .env_remove("DTL_LOG")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "phase finished phase=\"typecheck\"",
+        ));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("check")
+        .arg(&path)
+        .env("DTL_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("typecheck finished"));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("check")
+        .arg(&path)
+        .arg("-q")
+        .arg("-v")
+        .env("DTL_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn cli_grammar_describes_core_and_surface_forms() {
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("grammar")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "CoreDefn = \"(\" \"defn\" Name Params Type ( Expr | \":clauses\" Clauses )",
+        ))
+        .stdout(predicate::str::contains("( \"defn\" | \"関数\" )"));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .args(["grammar", "--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let value: Value = serde_json::from_slice(&output).expect("json");
+    assert_eq!(value["status"], json!("ok"));
+    let forms = value["forms"].as_array().expect("forms");
+    let project = forms
+        .iter()
+        .find(|form| form["head"] == json!("project"))
+        .expect("project form");
+    assert_eq!(project["japanese"], json!("プロジェクト"));
+    assert_eq!(project["core"], Value::Null);
+    assert!(
+        value["productions"]
+            .as_array()
+            .expect("productions")
+            .iter()
+            .any(
+                |production| production["name"] == json!("SurfaceQualityGate")
+                    && production["dialect"] == json!("surface")
+            )
+    );
+}
+
+#[test]
+fn cli_bench_reports_phase_statistics() {
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("bench")
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/complex_policy_import_entry.dtl"
+        ))
+        .args(["--iterations", "3", "--warmup", "1", "--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let value: Value = serde_json::from_slice(&output).expect("json");
+    assert_eq!(value["iterations"], json!(3));
+    assert_eq!(value["warmup"], json!(1));
+    let phases = value["phases"]
+        .as_array()
+        .expect("phases")
+        .iter()
+        .map(|phase| {
+            assert_eq!(phase["samples"], json!(3));
+            assert!(phase["p95_micros"].as_u64() >= phase["min_micros"].as_u64());
+            phase["phase"].clone()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        phases,
+        vec![
+            json!("parse"),
+            json!("check"),
+            json!("solve"),
+            json!("prove")
+        ]
+    );
+    assert!(value["fixpoint"]["tuples"].as_u64().expect("tuples") > 0);
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("bench")
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/access_control_ng_unknown_relation.dtl"
+        ))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("E-RESOLVE"));
+}
+
+#[test]
+fn cli_slice_emits_a_checkable_sub_program() {
+    let dir = tempdir().expect("tempdir");
+    let out = dir.path().join("slice.dtl");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("slice")
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/complex_policy_import_entry.dtl"
+        ))
+        .args(["--defn", "有効ユーザー存在", "--out"])
+        .arg(&out)
+        .assert()
+        .success();
+    let sliced = fs::read_to_string(&out).expect("slice output");
+    assert!(sliced.contains("(関数 有効ユーザー存在"));
+    assert!(!sliced.contains("注文許可"));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("prove").arg(&out).assert().success();
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("slice")
+        .arg(&out)
+        .args(["--assert", "missing"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown assert: missing"));
+}
+
+#[test]
+fn cli_codegen_rust_writes_a_module_and_reports_name_collisions() {
+    let dir = tempdir().expect("tempdir");
+    let out = dir.path().join("generated.rs");
+    let example = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/examples/access_control_ok.dtl"
+    );
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.args(["codegen", "rust", example, "--out"])
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout("");
+    let generated = fs::read_to_string(&out).expect("codegen output");
+    assert!(generated.starts_with("// @generated by `dtl codegen rust`"));
+    assert!(generated.contains("pub fn can_read(u: Subject, r: Resource) -> bool {"));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.args(["codegen", "rust", example])
+        .assert()
+        .success()
+        .stdout(generated);
+
+    let path = dir.path().join("collide.dtl");
+    fs::write(&path, "(sort access-level)\n(sort AccessLevel)\n").expect("write");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.args(["codegen", "rust"])
+        .arg(&path)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "E-CODEGEN: access-level and AccessLevel both become the Rust name AccessLevel",
+        ));
+}
+
+#[test]
+fn cli_codegen_typescript_prints_types_and_validators() {
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.args([
+        "codegen",
+        "typescript",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/examples/my_first_policy.dtl"),
+    ])
+    .assert()
+    .success()
+    .stdout(predicate::str::starts_with(
+        "// @generated by `dtl codegen typescript`",
+    ))
+    .stdout(predicate::str::contains(
+        "export function isSubject(value: unknown): value is Subject {",
+    ));
+}
+
+#[test]
+fn cli_codegen_jsonschema_prints_a_schema_document() {
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.args([
+        "codegen",
+        "jsonschema",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/examples/my_first_policy.dtl"),
+    ])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(
+        "\"$schema\": \"https://json-schema.org/draft/2020-12/schema\"",
+    ))
+    .stdout(predicate::str::contains("\"Subject\": {"));
+}
+
+#[test]
+fn cli_codegen_sql_prints_create_table_statements() {
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.args([
+        "codegen",
+        "sql",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/examples/my_first_policy.dtl"),
+    ])
+    .assert()
+    .success()
+    .stdout(predicate::str::starts_with(
+        "-- @generated by `dtl codegen sql`",
+    ))
+    .stdout(predicate::str::contains("CREATE TABLE \""));
+}
+
+#[test]
+fn cli_eval_prints_the_value_of_a_defn_application() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("discount.dtl");
+    fs::write(
+        &path,
+        r#"
+(data Tier (gold) (silver))
+(defn discount ((tier Tier) (amount (Refine n Int (>= n 0)))) Int
+  (match tier
+    ((gold) (+ amount 1))
+    ((silver) amount)))
+"#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("eval")
+        .arg(&path)
+        .arg("(discount gold 120)")
+        .assert()
+        .success()
+        .stdout("121\n");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("eval")
+        .arg(&path)
+        .arg("(discount silver 7)")
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let value: Value = serde_json::from_slice(&output).expect("valid eval json");
+    assert_eq!(value["status"], "ok");
+    assert_eq!(value["value"], "7");
+    assert!(value["steps"].as_u64().expect("steps") > 0);
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("eval")
+        .arg(&path)
+        .arg("(discount silver 7)")
+        .arg("--trace")
+        .assert()
+        .success()
+        .stdout(
+            "call discount tier=(silver) amount=7\n  match (silver) -> arm 2 (silver)\nreturn discount = 7\n7\n",
+        );
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("eval")
+        .arg(&path)
+        .arg("(discount gold -1)")
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains(
+            "E-EVAL: argument amount of discount: -1 does not satisfy",
+        ));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("eval")
+        .arg(&path)
+        .arg("(discount gold")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::starts_with(
+            "<application>: E-PARSE: unbalanced parentheses at 1:1",
+        ));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("eval")
+        .arg(&path)
+        .arg("(refund 1)")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::starts_with(
+            "<application>: E-RESOLVE: unknown defn: refund",
+        ));
+}
+
+#[test]
+fn cli_eval_reports_deep_recursion_past_a_large_depth_limit() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("count.dtl");
+    fs::write(
+        &path,
+        r#"
+(defn count ((n Int)) Int
+  (match n
+    (0 0)
+    (_ (+ 1 (count (- n 1)))))
+  :decreases n)
+"#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("eval")
+        .arg(&path)
+        .arg("(count 100000)")
+        .arg("--max-depth")
+        .arg("1000000")
+        .arg("--max-steps")
+        .arg("100000000")
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains(
+            "E-EVAL: evaluation exceeded the depth limit of 65536",
+        ));
+}
+
+#[test]
+fn cli_run_evaluates_the_entrypoint() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("decide.dtl");
+    fs::write(
+        &path,
+        r#"
+(data Decision (allow) (deny))
+(defn decide ((attempts Int)) Decision
+  (match attempts
+    (0 (allow))
+    (_ (deny))))
+(defn main () Decision (decide 3))
+"#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("run")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout("(deny)\n");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("run")
+        .arg(&path)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let value: Value = serde_json::from_slice(&output).expect("valid run json");
+    assert_eq!(value["status"], "ok");
+    assert_eq!(value["entry"], "main");
+    assert_eq!(value["value"], "(deny)");
+
+    let empty = dir.path().join("empty.dtl");
+    fs::write(&empty, "(defn answer () Int 42)\n").expect("write");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("run")
+        .arg(&empty)
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("E-EVAL: no entrypoint"));
+}
+
+#[test]
+fn cli_explore_reads_commands_from_stdin() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("policy.dtl");
+    fs::write(
+        &path,
+        r#"
+(sort Subject)
+(relation admin (Subject))
+(relation can-delete (Subject))
+(universe Subject (alice bob))
+(fact admin alice)
+(rule (can-delete ?u) (admin ?u))
+(assert everyone-deletes ((u Subject)) (can-delete u))
+"#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("explore")
+        .arg(&path)
+        .write_stdin("1\n1\nq\n")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8(output).expect("utf8");
+    assert!(stdout.contains("  1. [failed] assert::everyone-deletes\n"));
+    assert!(stdout.contains("反例の代入: u = bob\n"));
+    assert!(stdout.contains("can-delete(bob)\n状態: 導出されていない\n"));
+    assert!(stdout.contains("  - (rule (can-delete ?u) (admin ?u))\n"));
+}
+
+#[test]
+fn cli_minimize_shrinks_a_failing_program() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("failing.dtl");
+    fs::write(
+        &path,
+        r#"
+(sort Subject)
+(relation admin (Subject))
+(relation unrelated (Subject))
+(fact admin alice)
+(fact unrelated bob)
+(universe Subject (alice bob))
+(assert all-admins ((u Subject)) (admin u))
+(defn is-admin ((u Subject)) Bool (admin u))
+"#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("minimize")
+        .arg(&path)
+        .arg("--proof-failure")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("minimized 8 -> "))
+        .get_output()
+        .stdout
+        .clone();
+    let minimized = String::from_utf8(output).expect("utf8");
+    assert!(minimized.contains("all-admins"));
+    assert!(!minimized.contains("is-admin"));
+    assert!(!minimized.contains("unrelated"));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("minimize")
+        .arg(&path)
+        .args(["--code", "E-TOTAL"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("E-MINIMIZE"));
+}
+
+#[test]
+fn cli_diff_reports_changed_declarations_and_affected_obligations() {
+    let dir = tempdir().expect("tempdir");
+    let old = dir.path().join("old.dtl");
+    let new = dir.path().join("new.dtl");
+    let base = r#"
+(sort Subject)
+(relation admin (Subject))
+(relation allowed (Subject))
+(fact admin alice)
+(rule (allowed ?u) (admin ?u))
+(universe Subject (alice bob))
+(assert admins-allowed ((u Subject)) (not (and (admin u) (not (allowed u)))))
+(assert alice-known ((u Subject)) (admin alice))
+"#;
+    fs::write(&old, base).expect("write old");
+    fs::write(&new, base.replace("(fact admin alice)", "(fact admin bob)")).expect("write new");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("diff")
+        .arg("--old")
+        .arg(&old)
+        .arg("--new")
+        .arg(&new)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("~ fact admin"))
+        .stdout(predicate::str::contains("assert::alice-known"));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("diff")
+        .arg("--old")
+        .arg(&old)
+        .arg("--new")
+        .arg(&new)
+        .args(["--format", "json"])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).expect("json");
+    assert_eq!(json["status"], "ok");
+    assert_eq!(
+        json["changes"],
+        serde_json::json!([{"kind": "fact", "name": "admin", "change": "changed"}])
+    );
+    assert_eq!(
+        json["affected_obligations"],
+        serde_json::json!(["assert::admins-allowed", "assert::alice-known"])
+    );
+}
+
+#[test]
+fn cli_refs_lists_uses_of_a_relation() {
+    let file = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/examples/complex_policy_import_entry.dtl"
+    );
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .args(["refs", "ユーザー"])
+        .arg(file)
+        .args(["--format", "json"])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).expect("json");
+    assert_eq!(json["status"], "ok");
+    assert_eq!(json["symbol"], "ユーザー");
+    let references = json["references"].as_array().expect("references");
+    assert!(!references.is_empty());
+    for reference in references {
+        assert!(reference["source"].as_str().is_some());
+        assert!(reference["span"]["line"].as_u64().is_some());
+    }
+}
+
+#[test]
+fn cli_tags_writes_a_ctags_file_across_imports() {
+    let dir = tempdir().expect("tempdir");
+    let out = dir.path().join("tags");
+    let file = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/examples/complex_policy_import_entry.dtl"
+    );
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("tags")
+        .arg(file)
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+
+    let tags = fs::read_to_string(&out).expect("tags file");
+    assert!(tags.starts_with("!_TAG_FILE_FORMAT"));
+    let relation = tags
+        .lines()
+        .find(|line| line.starts_with("有効ユーザー\t"))
+        .expect("relation tag");
+    assert!(relation.contains("complex_policy_schema.dtl"));
+    assert!(relation.contains(";\"\tr\tline:"));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("tags")
+        .arg(file)
+        .args(["--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).expect("json");
+    assert_eq!(json["status"], "ok");
+    assert!(
+        json["tags"]
+            .as_array()
+            .expect("tags")
+            .iter()
+            .any(|tag| { tag["name"] == "有効ユーザー存在" && tag["kind"] == "defn" })
+    );
+}
+
+#[test]
+fn cli_cache_reuses_and_invalidates_derived_facts() {
+    let dir = tempdir().expect("tempdir");
+    let src = r#"(sort Node)
+(relation edge (Node Node))
+(relation reach (Node Node))
+(fact edge a b)
+(fact edge b c)
+(rule (reach ?x ?y) (edge ?x ?y))
+(rule (reach ?x ?z) (and (edge ?x ?y) (reach ?y ?z)))
+(universe Node (a b c))
+(assert a-reaches-c () (reach a c))
+"#;
+    fs::write(dir.path().join("graph.dtl"), src).expect("write source");
+    let entries = || {
+        let facts = dir.path().join(".dtl-cache/facts");
+        let mut names = fs::read_dir(&facts)
+            .map(|items| {
+                items
+                    .map(|item| item.expect("entry").path())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    };
+    let prove = |cache: bool| {
+        let mut cmd = cargo_bin_cmd!("dtl");
+        cmd.current_dir(dir.path())
+            .args(["prove", "graph.dtl", "--format", "json"]);
+        if cache {
+            cmd.arg("--cache");
+        }
+        cmd.output().expect("run prove")
+    };
+
+    let uncached = prove(false);
+    assert!(entries().is_empty());
+    let first = prove(true);
+    assert_eq!(first.status.code(), Some(0));
+    assert_eq!(first.stdout, uncached.stdout);
+    let cached = entries();
+    assert_eq!(cached.len(), 1, "{cached:?}");
+    assert!(cached[0].to_string_lossy().ends_with(".json.zst"));
+
+    assert_eq!(prove(true).stdout, uncached.stdout);
+    fs::write(&cached[0], "not a snapshot").expect("corrupt entry");
+    assert_eq!(prove(true).stdout, uncached.stdout);
+    assert_eq!(entries(), cached);
+
+    fs::write(
+        dir.path().join("graph.dtl"),
+        src.replace("(fact edge b c)", "(fact edge c b)"),
+    )
+    .expect("rewrite source");
+    let changed = prove(true);
+    assert_eq!(changed.status.code(), Some(3));
+    assert_eq!(entries().len(), 2);
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.current_dir(dir.path())
+        .args(["check", "graph.dtl", "--cache"])
+        .assert()
+        .success();
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.current_dir(dir.path())
+        .args(["lint", "graph.dtl", "--semantic-dup", "--cache"])
+        .assert()
+        .success();
+    assert_eq!(entries().len(), 2);
+}
+
+#[test]
+fn cli_expand_traces_macro_steps_and_prints_the_expanded_program() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("macros.dtl");
+    fs::write(
+        &src,
+        r#"; syntax: core
+(defmacro grant (rel who ...)
+  (fact rel who) ...)
+(sort Subject)
+(relation admin (Subject))
+(grant admin alice bob)
+"#,
+    )
+    .expect("write");
+
+    let output = cargo_bin_cmd!("dtl")
+        .arg("expand")
+        .arg(&src)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).expect("utf8");
+    assert_eq!(
+        text,
+        "; syntax: core\n\
+         ; 1. grant at 6:1\n\
+         ;   (grant admin alice bob)\n\
+         ;   => (fact admin alice)\n\
+         ;   => (fact admin bob)\n\
+         (sort Subject)\n\
+         (relation admin (Subject))\n\
+         (fact admin alice)\n\
+         (fact admin bob)\n"
+    );
+    let expanded = dir.path().join("expanded.dtl");
+    fs::write(&expanded, &text).expect("write expanded");
+    cargo_bin_cmd!("dtl")
+        .arg("check")
+        .arg(&expanded)
+        .assert()
+        .success();
+
+    let output = cargo_bin_cmd!("dtl")
+        .arg("expand")
+        .arg(&src)
+        .args(["--format", "json"])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let json: Value = serde_json::from_slice(&output).expect("json");
+    assert_eq!(json["status"], "ok");
+    assert_eq!(json["steps"][0]["macro"], "grant");
+    assert_eq!(json["steps"][0]["span"]["line"], 6);
+    assert_eq!(
+        json["steps"][0]["expansion"],
+        json!(["(fact admin alice)", "(fact admin bob)"])
+    );
+    assert_eq!(json["forms"].as_array().expect("forms").len(), 4);
+
+    fs::write(&src, "(defmacro pair (a b) (fact r a b))\n(pair x)\n").expect("write");
+    let output = cargo_bin_cmd!("dtl")
+        .arg("expand")
+        .arg(&src)
+        .args(["--format", "json"])
+        .assert()
+        .failure()
+        .get_output()
+        .stdout
+        .clone();
+    let json: Value = serde_json::from_slice(&output).expect("json");
+    let diag = &json["diagnostics"][0];
+    assert_eq!(diag["code"], "E-PARSE");
+    assert_eq!(diag["message"], "macro pair expects 2 arguments, got 1");
+    assert_eq!(diag["span"]["line"], 2);
+}
@@ -51,6 +51,58 @@ fn cli_lint_reports_duplicate_fact_in_json() {
     );
 }
 
+#[test]
+fn cli_lint_attributes_cross_file_duplicates_and_can_ignore_them() {
+    let dir = tempdir().expect("tempdir");
+    let base = dir.path().join("base.dtl");
+    let extra = dir.path().join("extra.dtl");
+    fs::write(
+        &base,
+        "(sort Subject)\n(relation allowed (Subject))\n(fact allowed alice)\n",
+    )
+    .expect("write");
+    fs::write(
+        &extra,
+        "(fact allowed alice)\n(fact allowed bob)\n(fact allowed bob)\n",
+    )
+    .expect("write");
+
+    let duplicates = |extra_args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("dtl");
+        let output = cmd
+            .arg("lint")
+            .arg(&base)
+            .arg(&extra)
+            .args(["--format", "json"])
+            .args(extra_args)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let value: Value = serde_json::from_slice(&output).expect("json");
+        value["diagnostics"]
+            .as_array()
+            .expect("array")
+            .iter()
+            .filter(|d| d["lint_code"] == "L-DUP-EXACT")
+            .map(|d| d["message"].as_str().expect("message").to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let all = duplicates(&[]);
+    assert_eq!(all.len(), 2, "{all:?}");
+    assert!(all.iter().any(|m| m.contains("base.dtl:3:2")), "{all:?}");
+    assert!(
+        all.iter().any(|m| m.ends_with("（最初の定義: 2:2）")),
+        "{all:?}"
+    );
+
+    let intra = duplicates(&["--intra-file-only"]);
+    assert_eq!(intra.len(), 1, "{intra:?}");
+    assert!(intra[0].contains("（最初の定義: 2:2）"), "{intra:?}");
+}
+
 #[test]
 fn cli_lint_deny_warnings_returns_failure() {
     let dir = tempdir().expect("tempdir");
@@ -385,6 +437,78 @@ fn cli_lint_semantic_dup_reports_depth_limit_warning() {
     assert!(!diags.iter().any(|d| d["lint_code"] == "L-DUP-MAYBE"));
 }
 
+#[test]
+fn cli_lint_semantic_dup_limits_are_configurable() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("semantic_dup_limits.dtl");
+    let nat = nested_nat(20);
+    fs::write(
+        &src,
+        format!(
+            r#"
+        (data Nat (zero) (succ Nat))
+        (defn loop_a ((n Nat)) Bool (match n ((zero) true) ((succ m) (loop_a m))))
+        (defn loop_b ((n Nat)) Bool (match n ((zero) true) ((succ m) (loop_b m))))
+        (defn passthrough_a ((f (-> (Symbol) Bool))) (-> (Symbol) Bool) f)
+        (defn passthrough_b ((g (-> (Symbol) Bool))) (-> (Symbol) Bool) (if true g g))
+
+        (universe Nat ((zero) {nat}))
+        (universe Symbol (alice bob))
+        (universe Bool (true false))
+        "#
+        ),
+    )
+    .expect("write");
+
+    let lint = |flags: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("dtl");
+        let output = cmd
+            .arg("lint")
+            .arg(&src)
+            .args(["--format", "json", "--semantic-dup"])
+            .args(flags)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let value: Value = serde_json::from_slice(&output).expect("json");
+        value["diagnostics"]
+            .as_array()
+            .expect("array")
+            .iter()
+            .map(|d| format!("{} {}", d["lint_code"], d["message"]))
+            .collect::<Vec<_>>()
+    };
+
+    let defaults = lint(&[]);
+    assert!(
+        defaults
+            .iter()
+            .any(|d| d.contains("L-DUP-MAYBE") && d.contains("loop_a と loop_b")),
+        "{defaults:?}"
+    );
+    assert!(defaults.iter().any(|d| d.contains("passthrough_a")));
+
+    let shallow = lint(&["--dup-depth-limit", "8"]);
+    assert!(
+        shallow.iter().any(|d| d.contains("L-DUP-SKIP-EVAL-DEPTH")
+            && d.contains("depth_limit=8")
+            && d.contains("checked=1")),
+        "{shallow:?}"
+    );
+
+    let small_functions = lint(&["--dup-max-function-values", "2"]);
+    assert!(!small_functions.iter().any(|d| d.contains("passthrough_a")));
+    assert!(small_functions.iter().any(|d| d.contains("loop_a")));
+
+    let few_points = lint(&["--dup-max-model-points", "1"]);
+    assert!(
+        !few_points.iter().any(|d| d.contains("L-DUP-MAYBE")),
+        "{few_points:?}"
+    );
+}
+
 #[test]
 fn cli_fmt_check_and_write() {
     let dir = tempdir().expect("tempdir");
@@ -423,6 +547,97 @@ fn cli_fmt_check_and_write() {
     assert!(body.contains("(型 Subject)"));
 }
 
+#[test]
+fn cli_lint_reads_complexity_thresholds_from_dtl_toml() {
+    let dir = tempdir().expect("tempdir");
+    fs::write(
+        dir.path().join("dtl.toml"),
+        "[lint]\nmax-rule-body-atoms = 1\nmax-assert-params = 1\n",
+    )
+    .expect("write config");
+    let src = dir.path().join("complex.dtl");
+    fs::write(
+        &src,
+        r#"
+        (sort Subject)
+        (relation admin (Subject))
+        (relation staff (Subject))
+        (relation manages (Subject Subject))
+        (fact admin alice)
+        (fact staff alice)
+        (rule (manages ?a ?b) (and (admin ?a) (staff ?b)))
+        (universe Subject (alice bob))
+        (assert managers-are-admins ((a Subject) (b Subject)) (not (and (manages a b) (not (admin a)))))
+        "#,
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("lint")
+        .arg(&src)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let value: Value = serde_json::from_slice(&output).expect("json");
+    let codes = value["diagnostics"]
+        .as_array()
+        .expect("array")
+        .iter()
+        .filter(|d| d["category"] == "complexity")
+        .map(|d| d["lint_code"].as_str().expect("code").to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        vec!["L-COMPLEX-RULE-BODY", "L-COMPLEX-ASSERT-PARAMS"],
+        "{value}"
+    );
+}
+
+#[test]
+fn cli_check_and_fmt_use_project_surface_keywords() {
+    let dir = tempdir().expect("tempdir");
+    fs::write(
+        dir.path().join("dtl.toml"),
+        "[syntax.keywords]\n\"取引関係\" = \"関係\"\n\":取引項\" = \":項\"\n\"取引事実\" = \"事実\"\n",
+    )
+    .expect("write config");
+    let src = dir.path().join("custom_keywords.dtl");
+    fs::write(
+        &src,
+        "(型 主体)\n(取引関係 締結済み :引数 (主体))\n(取引事実 締結済み :取引項 (山田))\n",
+    )
+    .expect("write");
+
+    let mut check_cmd = cargo_bin_cmd!("dtl");
+    check_cmd.arg("check").arg(&src).assert().success();
+
+    let mut fmt_cmd = cargo_bin_cmd!("dtl");
+    fmt_cmd.arg("fmt").arg(&src).assert().success();
+    let body = fs::read_to_string(&src).expect("read");
+    assert!(
+        body.contains("(取引関係 締結済み :引数 (主体))\n"),
+        "{body}"
+    );
+    assert!(
+        body.contains("(取引事実 締結済み :取引項 (山田))\n"),
+        "{body}"
+    );
+
+    let mut check_after_cmd = cargo_bin_cmd!("dtl");
+    check_after_cmd
+        .arg("fmt")
+        .arg(&src)
+        .arg("--check")
+        .assert()
+        .success();
+}
+
 #[test]
 fn cli_fmt_preserves_multi_context_blocks_idempotently() {
     let dir = tempdir().expect("tempdir");
@@ -581,6 +796,55 @@ fn cli_fmt_incomplete_list_reports_parse_error_instead_of_selfdoc_error() {
         .stderr(predicate::str::contains("E-FMT-SELFDOC-UNSUPPORTED").not());
 }
 
+#[test]
+fn cli_fmt_warns_about_forms_left_as_written_for_block_comments() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("commented.dtl");
+    fs::write(
+        &src,
+        "; syntax: surface\n(型   主体)\n(関係 閲覧 :引数 (主体 #| 資源 |#))\n",
+    )
+    .expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("fmt")
+        .arg(&src)
+        .arg("--stdout")
+        .assert()
+        .success()
+        .stdout("; syntax: surface\n(型 主体)\n(関係 閲覧 :引数 (主体 #| 資源 |#))\n")
+        .stderr(
+            predicate::str::contains("warning: W-FMT-COMMENT")
+                .and(predicate::str::contains("at 3:1")),
+        );
+}
+
+#[test]
+fn cli_fmt_check_fails_for_core_files_left_unformatted_by_comments() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("core.dtl");
+    let original = "; syntax: core\n(sort   Subject)\n#| (sort Old) |#\n(sort Role)\n";
+    fs::write(&src, original).expect("write");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("fmt")
+        .arg(&src)
+        .arg("--check")
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains(
+            "warning: W-FMT-COMMENT: core syntax file contains a block or datum comment",
+        ));
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("fmt")
+        .arg(&src)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warning: W-FMT-COMMENT"));
+    assert_eq!(fs::read_to_string(&src).expect("read"), original);
+}
+
 #[test]
 fn cli_fmt_stdout_requires_single_input_file() {
     let dir = tempdir().expect("tempdir");
@@ -52,7 +52,7 @@ fn cli_prove_json_writes_trace_file() {
 
     let value: Value = serde_json::from_slice(&output).expect("json");
     assert_eq!(value["status"], "ok");
-    assert_eq!(value["proof"]["schema_version"], "2.2.0");
+    assert_eq!(value["proof"]["schema_version"], "2.3.0");
     assert_eq!(value["proof"]["engine"], "native");
     assert!(out_dir.join("proof-trace.json").exists());
 }
@@ -259,6 +259,164 @@ fn cli_prove_returns_error_when_obligation_fails() {
     );
 }
 
+#[test]
+fn cli_prove_selects_profile_gated_facts_and_asserts() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("profiles.dtl");
+    fs::write(
+        &src,
+        r#"
+        (data Subject (alice) (bob))
+        (relation allowed (Subject))
+        (fact allowed (alice))
+        (universe Subject ((alice) (bob)))
+        (when-profile production
+          (fact allowed (bob)))
+        (assert everyone-allowed ((u Subject)) (allowed u) :profile production)
+        (assert bob-allowed () (allowed (bob)) :profile staging)
+        "#,
+    )
+    .expect("write");
+
+    let prove = |profiles: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("dtl");
+        cmd.arg("prove").arg(&src).arg("--format").arg("json");
+        for profile in profiles {
+            cmd.arg("--profile").arg(profile);
+        }
+        let output = cmd.output().expect("run");
+        let value: Value = serde_json::from_slice(&output.stdout).expect("json");
+        value["proof"]["obligations"]
+            .as_array()
+            .expect("obligations")
+            .iter()
+            .map(|o| (o["id"].clone(), o["result"].clone()))
+            .collect::<Vec<_>>()
+    };
+
+    assert!(prove(&[]).is_empty());
+    assert_eq!(
+        prove(&["production"]),
+        vec![(json!("assert::everyone-allowed"), json!("proved"))]
+    );
+    assert_eq!(
+        prove(&["staging"]),
+        vec![(json!("assert::bob-allowed"), json!("failed"))]
+    );
+    assert_eq!(
+        prove(&["production", "staging"]),
+        vec![
+            (json!("assert::everyone-allowed"), json!("proved")),
+            (json!("assert::bob-allowed"), json!("proved"))
+        ]
+    );
+}
+
+#[test]
+fn cli_prove_jsonl_streams_one_event_per_obligation() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("stream.dtl");
+    fs::write(
+        &src,
+        r#"
+        (data Subject (alice) (bob))
+        (relation allowed (Subject))
+        (fact allowed (alice))
+        (universe Subject ((alice) (bob)))
+        (assert alice-allowed () (allowed (alice)))
+        (assert everyone-allowed ((u Subject)) (allowed u))
+        "#,
+    )
+    .expect("write");
+
+    for engine in ["native", "reference"] {
+        let mut cmd = cargo_bin_cmd!("dtl");
+        let output = cmd
+            .arg("prove")
+            .arg(&src)
+            .arg("--engine")
+            .arg(engine)
+            .arg("--format")
+            .arg("jsonl")
+            .assert()
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .get_output()
+            .stdout
+            .clone();
+
+        let events = String::from_utf8(output)
+            .expect("utf8")
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).expect("json line"))
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 3, "engine={engine} events={events:?}");
+        assert_eq!(events[0]["event"], "obligation");
+        assert_eq!(events[0]["id"], "assert::alice-allowed");
+        assert_eq!(events[0]["result"], "proved");
+        assert_eq!(events[1]["event"], "obligation");
+        assert_eq!(events[1]["id"], "assert::everyone-allowed");
+        assert_eq!(events[1]["result"], "failed");
+        assert_eq!(
+            events[2],
+            json!({
+                "event": "done",
+                "status": "error",
+                "proof": {"total": 2, "proved": 1, "failed": 1}
+            })
+        );
+    }
+}
+
+#[test]
+fn cli_prove_reports_assert_failure_messages() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("message.dtl");
+    fs::write(
+        &src,
+        r#"
+        (data Subject (alice) (bob))
+        (relation allowed (Subject))
+        (fact allowed (alice))
+        (universe Subject ((alice) (bob)))
+        (assert everyone-allowed ((u Subject)) (allowed u) :message "every subject must be allowed to read")
+        "#,
+    )
+    .expect("write");
+
+    let mut text_cmd = cargo_bin_cmd!("dtl");
+    text_cmd
+        .arg("prove")
+        .arg(&src)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "- assert::everyone-allowed: every subject must be allowed to read",
+        ));
+
+    for engine in ["native", "reference"] {
+        let mut json_cmd = cargo_bin_cmd!("dtl");
+        let output = json_cmd
+            .arg("prove")
+            .arg(&src)
+            .arg("--engine")
+            .arg(engine)
+            .arg("--format")
+            .arg("json")
+            .assert()
+            .failure()
+            .stderr(predicate::str::is_empty())
+            .get_output()
+            .stdout
+            .clone();
+        let value: Value = serde_json::from_slice(&output).expect("json");
+        assert_eq!(
+            value["proof"]["obligations"][0]["message"], "every subject must be allowed to read",
+            "engine={engine}"
+        );
+    }
+}
+
 #[test]
 fn cli_doc_generates_bundle_only_when_proved() {
     let dir = tempdir().expect("tempdir");
@@ -292,7 +450,26 @@ fn cli_doc_generates_bundle_only_when_proved() {
         &fs::read(ok_out.join("doc-index.json")).expect("read markdown doc index"),
     )
     .expect("valid markdown doc index");
-    assert_eq!(index["files"], json!(["spec.md", "proof-trace.json"]));
+    assert_eq!(
+        index["files"],
+        json!(["spec.md", "proof-trace.json", "badge.json"])
+    );
+    let spec = fs::read_to_string(ok_out.join("spec.md")).expect("read spec.md");
+    assert!(spec.contains(
+        "## 検証サマリー\n| 項目 | 値 |\n|---|---|\n| 証明義務 | 1 |\n| proved | 1 |\n| failed | 0 |\n| timeout | 0 |\n| claim coverage | 1/1（100.0%） |\n| engine | `native` |\n| 所要時間 | "
+    ));
+    let badge: Value =
+        serde_json::from_slice(&fs::read(ok_out.join("badge.json")).expect("read badge"))
+            .expect("valid badge json");
+    assert_eq!(
+        badge,
+        json!({
+            "schemaVersion": 1,
+            "label": "dtl proof",
+            "message": "1/1 proved",
+            "color": "brightgreen"
+        })
+    );
     assert_eq!(index["status"], "ok");
     assert_eq!(index["schema_version"], "2.0.0");
     assert_eq!(index["profile"], "standard");
@@ -325,6 +502,49 @@ fn cli_doc_generates_bundle_only_when_proved() {
     assert!(!ng_out.join("spec.json").exists());
 }
 
+#[test]
+fn cli_doc_allow_failed_renders_failed_obligations() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("doc_ng.dtl");
+    let out = dir.path().join("out");
+    fs::write(
+        &src,
+        r#"(data Subject (alice) (bob))
+(relation allowed (Subject))
+(fact allowed (alice))
+(universe Subject ((alice) (bob)))
+(assert everyone-allowed ((u Subject)) (allowed u) :message "everyone must be allowed")
+"#,
+    )
+    .expect("write ng");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&out)
+        .arg("--allow-failed")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("assert::everyone-allowed"));
+
+    let spec = fs::read_to_string(out.join("spec.md")).expect("read spec.md");
+    let expected = format!(
+        "## 失敗した証明義務\n\n### `assert::everyone-allowed`\n- 結果: `failed`\n- 位置: `{}:5:2`\n- 式: `(allowed u)`\n- メッセージ: everyone must be allowed\n- 反例の代入: `u = (bob)`\n- 成立しない goal:\n  - `allowed((bob))`\n",
+        src.display()
+    );
+    assert!(spec.contains(&expected), "{spec}");
+    let index: Value =
+        serde_json::from_slice(&fs::read(out.join("doc-index.json")).expect("read doc index"))
+            .expect("valid doc index");
+    assert_eq!(index["status"], "failed");
+    let badge: Value =
+        serde_json::from_slice(&fs::read(out.join("badge.json")).expect("read badge"))
+            .expect("valid badge json");
+    assert_eq!(badge["message"], "0/1 proved");
+    assert_eq!(badge["color"], "red");
+}
+
 #[test]
 fn cli_doc_json_generates_json_bundle() {
     let dir = tempdir().expect("tempdir");
@@ -397,6 +617,7 @@ fn cli_doc_json_generates_json_bundle() {
         "assertions": [
             {"name": "consistency"}
         ],
+        "functions": [],
         "proof_status": [
             {"id": "assert::consistency", "kind": "assert", "result": "proved"}
         ]
@@ -406,13 +627,464 @@ fn cli_doc_json_generates_json_bundle() {
     let index: Value =
         serde_json::from_slice(&fs::read(out.join("doc-index.json")).expect("read json index"))
             .expect("valid doc index json");
-    assert_eq!(index["files"], json!(["spec.json", "proof-trace.json"]));
+    assert_eq!(
+        index["files"],
+        json!(["spec.json", "proof-trace.json", "badge.json"])
+    );
     assert_eq!(index["status"], "ok");
     assert_eq!(index["schema_version"], "2.0.0");
     assert_eq!(index["profile"], "standard");
     assert_eq!(index["intermediate"]["dsl"], Value::Null);
 }
 
+#[test]
+fn cli_doc_cross_links_asserts_and_relations() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("doc_links.dtl");
+    fs::write(
+        &src,
+        r#"
+        (data Subject (alice) (bob))
+        (relation member (Subject))
+        (relation allowed (Subject))
+        (relation audited (Subject))
+        (fact member (alice))
+        (rule (allowed ?u) (member ?u))
+        (universe Subject ((alice) (bob)))
+        (assert consistency ((u Subject)) (not (and (allowed u) (not (allowed u)))))
+        "#,
+    )
+    .expect("write links case");
+
+    let out = dir.path().join("out");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .success();
+    let spec = fs::read_to_string(out.join("spec.md")).expect("read spec.md");
+    assert!(spec.contains(
+        "<a id=\"assert-consistency\"></a>\n### assert `consistency`\n- 依存 relation: [`allowed`](#relation-allowed), [`member`](#relation-member)\n"
+    ));
+    assert!(spec.contains(
+        "<a id=\"relation-allowed\"></a>\n### relation `allowed`\n- rule: 1 件\n  - `(allowed u) <- (member u)`\n- 依存 relation: [`member`](#relation-member)\n- 制約する assert: [`consistency`](#assert-consistency)\n"
+    ));
+    assert!(spec.contains(
+        "### relation `member`\n- fact: 1 件\n  - `(member (alice))`\n- 制約する assert: [`consistency`](#assert-consistency)\n"
+    ));
+    assert!(spec.contains("### relation `audited`\n- 制約する assert なし\n"));
+}
+
+#[test]
+fn cli_doc_lists_changes_since_previous_trace() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("changes.dtl");
+    let program = |facts: &str, assert_name: &str| {
+        format!(
+            r#"
+            (data Subject (alice) (bob))
+            (relation allowed (Subject))
+            {facts}
+            (universe Subject ((alice) (bob)))
+            (assert everyone ((u Subject)) (allowed u))
+            (assert {assert_name} ((u Subject)) (not (and (allowed u) (not (allowed u)))))
+            "#
+        )
+    };
+    fs::write(
+        &src,
+        program("(fact allowed (alice)) (fact allowed (bob))", "stable"),
+    )
+    .expect("write first version");
+    let out = dir.path().join("out");
+    let mut first = cargo_bin_cmd!("dtl");
+    first
+        .arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .success();
+
+    fs::write(&src, program("(fact allowed (alice))", "fresh")).expect("write second version");
+    let mut second = cargo_bin_cmd!("dtl");
+    second
+        .arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&out)
+        .arg("--previous-trace")
+        .arg(out.join("proof-trace.json"))
+        .arg("--allow-failed")
+        .assert()
+        .code(3);
+
+    let spec = fs::read_to_string(out.join("spec.md")).expect("read spec.md");
+    assert!(
+        spec.contains(concat!(
+            "## 前回検証からの変更\n",
+            "- 3 件の証明義務が変わりました（うち状態遷移 1 件）\n\n",
+            "| 証明義務 | 変更 | 前回 | 今回 |\n",
+            "| --- | --- | --- | --- |\n",
+            "| `assert::everyone` | 状態遷移 | `proved` | `failed` |\n",
+            "| `assert::fresh` | 追加 | - | `proved` |\n",
+            "| `assert::stable` | 削除 | `proved` | - |\n",
+        )),
+        "{spec}"
+    );
+
+    let changes: Value = serde_json::from_str(
+        &fs::read_to_string(out.join("proof-changes.json")).expect("read proof-changes.json"),
+    )
+    .expect("parse proof-changes.json");
+    assert_eq!(
+        changes,
+        json!([
+            {"change": "changed", "id": "assert::everyone", "before": "proved", "after": "failed"},
+            {"change": "added", "id": "assert::fresh", "after": "proved"},
+            {"change": "removed", "id": "assert::stable", "before": "proved"}
+        ])
+    );
+    let index: Value = serde_json::from_str(
+        &fs::read_to_string(out.join("doc-index.json")).expect("read doc-index.json"),
+    )
+    .expect("parse doc-index.json");
+    assert_eq!(
+        index["files"],
+        json!([
+            "spec.md",
+            "proof-trace.json",
+            "badge.json",
+            "proof-changes.json"
+        ])
+    );
+}
+
+#[test]
+fn cli_doc_rejects_unreadable_previous_trace() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("ok.dtl");
+    fs::write(&src, "(sort Subject)\n").expect("write source");
+    let previous = dir.path().join("broken.json");
+    fs::write(&previous, "{not json").expect("write broken trace");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(dir.path().join("out"))
+        .arg("--previous-trace")
+        .arg(&previous)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failed to parse proof trace"));
+}
+
+#[test]
+fn cli_prove_derivations_match_across_engines() {
+    let src = example_path("customer_contract_ja.dtl");
+    let derivations = |engine: &str| {
+        let mut cmd = cargo_bin_cmd!("dtl");
+        let output = cmd
+            .arg("prove")
+            .arg(&src)
+            .arg("--format")
+            .arg("json")
+            .arg("--engine")
+            .arg(engine)
+            .arg("--derivations")
+            .output()
+            .expect("run prove");
+        assert!(output.status.success(), "{output:?}");
+        let value: Value = serde_json::from_slice(&output.stdout).expect("prove json");
+        value["proof"]["derivations"].clone()
+    };
+
+    let native = derivations("native");
+    assert_eq!(native, derivations("reference"));
+    let steps = native.as_array().expect("derivations array");
+    assert!(!steps.is_empty());
+    assert_eq!(
+        steps[0],
+        json!({
+            "stratum": 0,
+            "iteration": 1,
+            "rule": 0,
+            "fact": "契約締結可能(佐藤,基本契約,(個人))",
+            "premises": ["契約登録(基本契約)", "担当顧客種別(佐藤,(個人))"]
+        })
+    );
+
+    let mut plain = cargo_bin_cmd!("dtl");
+    let output = plain
+        .arg("prove")
+        .arg(&src)
+        .arg("--format")
+        .arg("json")
+        .output()
+        .expect("run prove");
+    let value: Value = serde_json::from_slice(&output.stdout).expect("prove json");
+    assert!(value["proof"].get("derivations").is_none());
+}
+
+#[test]
+fn cli_prove_removes_the_trace_of_a_program_that_fails_to_check() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("broken.dtl");
+    fs::write(&src, "(relation admin (Subject))\n(fact admin alice)\n").expect("write");
+    let out = dir.path().join("out");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("prove")
+        .arg(&src)
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .failure();
+    assert!(!out.join("proof-trace.json").exists());
+}
+
+#[test]
+fn cli_prove_compress_trace_writes_zstd_trace() {
+    let dir = tempdir().expect("tempdir");
+    let src = example_path("customer_contract_ja.dtl");
+    let plain = dir.path().join("plain");
+    let compressed = dir.path().join("compressed");
+    let mut prove_plain = cargo_bin_cmd!("dtl");
+    prove_plain
+        .arg("prove")
+        .arg(&src)
+        .arg("--out")
+        .arg(&plain)
+        .assert()
+        .success();
+    let mut prove_compressed = cargo_bin_cmd!("dtl");
+    prove_compressed
+        .arg("prove")
+        .arg(&src)
+        .arg("--out")
+        .arg(&compressed)
+        .arg("--compress-trace")
+        .assert()
+        .success();
+
+    assert!(!compressed.join("proof-trace.json").exists());
+    let packed = fs::read(compressed.join("proof-trace.json.zst")).expect("read compressed trace");
+    let unpacked = zstd::decode_all(packed.as_slice()).expect("decompress trace");
+    let expected = fs::read(plain.join("proof-trace.json")).expect("read plain trace");
+    assert_eq!(unpacked, expected);
+    assert!(packed.len() < expected.len());
+
+    let out = dir.path().join("doc");
+    let mut doc = cargo_bin_cmd!("dtl");
+    doc.arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&out)
+        .arg("--compress-trace")
+        .arg("--previous-trace")
+        .arg(compressed.join("proof-trace.json.zst"))
+        .assert()
+        .success();
+    let index: Value = serde_json::from_str(
+        &fs::read_to_string(out.join("doc-index.json")).expect("read doc-index.json"),
+    )
+    .expect("parse doc-index.json");
+    assert_eq!(
+        index["files"],
+        json!([
+            "spec.md",
+            "proof-trace.json.zst",
+            "badge.json",
+            "proof-changes.json"
+        ])
+    );
+    let spec = fs::read_to_string(out.join("spec.md")).expect("read spec.md");
+    assert!(
+        spec.contains("## 前回検証からの変更\n- 変更はありません\n"),
+        "{spec}"
+    );
+}
+
+#[test]
+fn cli_doc_deterministic_bundles_are_byte_identical() {
+    let dir = tempdir().expect("tempdir");
+    let src = example_path("customer_contract_ja.dtl");
+    for format in ["markdown", "json"] {
+        let outs = ["first", "second"].map(|name| dir.path().join(format).join(name));
+        for out in &outs {
+            let mut cmd = cargo_bin_cmd!("dtl");
+            cmd.arg("doc")
+                .arg(&src)
+                .arg("--out")
+                .arg(out)
+                .arg("--format")
+                .arg(format)
+                .arg("--deterministic")
+                .assert()
+                .success();
+        }
+
+        let index: Value = serde_json::from_str(
+            &fs::read_to_string(outs[0].join("doc-index.json")).expect("read doc-index.json"),
+        )
+        .expect("parse doc-index.json");
+        let files = index["files"].as_array().expect("files array");
+        for file in files
+            .iter()
+            .filter_map(Value::as_str)
+            .chain(["doc-index.json"])
+        {
+            let first = fs::read(outs[0].join(file)).expect("read first bundle");
+            let second = fs::read(outs[1].join(file)).expect("read second bundle");
+            assert_eq!(first, second, "{format}: {file} differs");
+        }
+    }
+
+    let spec = fs::read_to_string(dir.path().join("markdown/first/spec.md")).expect("read spec.md");
+    assert!(spec.contains("| 所要時間 | - |\n"), "{spec}");
+}
+
+#[test]
+fn cli_doc_generates_glossary() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("glossary.dtl");
+    fs::write(
+        &src,
+        r#"
+        (sort Resource)
+        (data Subject (alice) (bob))
+        (relation allowed (Subject) :doc "subjects allowed to read")
+        (fact allowed (alice))
+        (universe Subject ((alice) (bob)))
+        (assert consistency ((u Subject)) (not (and (allowed u) (not (allowed u)))))
+        "#,
+    )
+    .expect("write glossary case");
+
+    let out = dir.path().join("out");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .success();
+    let spec = fs::read_to_string(out.join("spec.md")).expect("read spec.md");
+    assert!(spec.contains(concat!(
+        "## 用語集\n",
+        "- <a id=\"term-Resource\"></a>`Resource`（sort）: 使用 0 件\n",
+        "- <a id=\"term-Subject\"></a>`Subject`（data）: コンストラクタ `alice`, `bob` / 使用 3 件 / 引数に取る relation [`allowed`](#term-allowed)\n",
+        "- <a id=\"term-allowed\"></a>`allowed`（relation）: 引数 ([`Subject`](#term-Subject)) / 使用 3 件 / [依存と制約](#relation-allowed) / subjects allowed to read\n",
+    )), "{spec}");
+}
+
+#[test]
+fn cli_doc_embeds_formatted_source_excerpts() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("excerpts.dtl");
+    fs::write(
+        &src,
+        "; syntax: surface\n(データ Subject :コンストラクタ ((alice)   (bob)))\n(関係 allowed :引数 (Subject))\n(事実 allowed :項 ((alice)))\n(宇宙 Subject :値 ((alice) (bob)))\n(検証 consistency :引数 ((u Subject)) :式 (not (and (allowed u) (not (allowed u)))))\n",
+    )
+    .expect("write excerpts case");
+
+    let out = dir.path().join("out");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .success();
+    let spec = fs::read_to_string(out.join("spec.md")).expect("read spec.md");
+    let file = src.display();
+    assert!(spec.contains(&format!(
+        "  - `bob`\n  <details><summary>ソース（{file}:2）</summary>\n\n  ```dtl\n  (データ Subject :コンストラクタ ((alice) (bob)))\n  ```\n\n  </details>\n"
+    )));
+    assert!(spec.contains(&format!(
+        "- relation `allowed`(Subject)\n  <details><summary>ソース（{file}:3）</summary>\n"
+    )));
+    assert!(spec.contains(&format!(
+        "  - `consistency`\n    <details><summary>ソース（{file}:6）</summary>\n\n    ```dtl\n    (検証 consistency"
+    )));
+}
+
+#[test]
+fn cli_doc_renders_declaration_docstrings_and_attributes() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("doc_docstrings.dtl");
+    fs::write(
+        &src,
+        r#"
+        (data Subject (alice) (bob))
+        (relation allowed (Subject) :doc "subjects allowed to read" :owner "security" :ticket "SEC-12")
+        (fact allowed (alice))
+        (universe Subject ((alice) (bob)))
+        (defn is-alice ((u Subject)) Bool (allowed u) :doc "true for allowed subjects")
+        (assert consistency ((u Subject)) (not (and (allowed u) (not (allowed u)))) :doc "no contradiction" :message "allowed must be consistent")
+        "#,
+    )
+    .expect("write docstrings case");
+
+    let md_out = dir.path().join("md_out");
+    let mut md_cmd = cargo_bin_cmd!("dtl");
+    md_cmd
+        .arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&md_out)
+        .assert()
+        .success();
+    let spec = fs::read_to_string(md_out.join("spec.md")).expect("read spec.md");
+    assert!(spec.contains("- relation `allowed`(Subject)\n  - 説明: subjects allowed to read\n"));
+    assert!(
+        spec.contains(
+            "  - `is-alice`(u: Subject) -> Bool\n    - 説明: true for allowed subjects\n"
+        )
+    );
+    assert!(spec.contains(
+        "  - `consistency`\n    - 説明: no contradiction\n    - 失敗時メッセージ: allowed must be consistent\n"
+    ));
+
+    let json_out = dir.path().join("json_out");
+    let mut json_cmd = cargo_bin_cmd!("dtl");
+    json_cmd
+        .arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&json_out)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success();
+    let spec: Value =
+        serde_json::from_slice(&fs::read(json_out.join("spec.json")).expect("read json spec"))
+            .expect("valid spec json");
+    assert_eq!(spec["relations"][0]["doc"], "subjects allowed to read");
+    assert_eq!(
+        spec["relations"][0]["attributes"],
+        json!({"owner": "security", "ticket": "SEC-12"})
+    );
+    assert!(spec["data_declarations"][0].get("attributes").is_none());
+    assert_eq!(spec["assertions"][0]["doc"], "no contradiction");
+    assert_eq!(
+        spec["assertions"][0]["message"],
+        "allowed must be consistent"
+    );
+    assert_eq!(
+        spec["functions"],
+        json!([{
+            "name": "is-alice",
+            "params": ["Subject"],
+            "ret": "Bool",
+            "doc": "true for allowed subjects"
+        }])
+    );
+}
+
 #[test]
 fn cli_doc_generates_bundle_for_japanese_example() {
     let src = example_path("customer_contract_ja.dtl");
@@ -437,7 +1109,7 @@ fn cli_doc_generates_bundle_for_japanese_example() {
         &fs::read(out.join("proof-trace.json")).expect("read japanese proof trace"),
     )
     .expect("valid japanese proof trace");
-    assert_eq!(trace["schema_version"], "2.2.0");
+    assert_eq!(trace["schema_version"], "2.3.0");
     assert_eq!(trace["profile"], "standard");
     assert_eq!(trace["engine"], "native");
     assert!(
@@ -544,9 +1216,67 @@ fn cli_doc_reference_engine_supports_function_typed_quantifier() {
     let trace: Value =
         serde_json::from_slice(&fs::read(out.join("proof-trace.json")).expect("read proof trace"))
             .expect("valid proof trace");
-    assert_eq!(trace["schema_version"], "2.2.0");
+    assert_eq!(trace["schema_version"], "2.3.0");
     assert_eq!(trace["engine"], "reference");
     assert_eq!(trace["summary"]["failed"], 0);
     assert!(out.join("spec.json").exists());
     assert!(out.join("doc-index.json").exists());
 }
+
+#[test]
+fn cli_prove_and_doc_accept_partial_eval_with_the_same_results() {
+    let dir = tempdir().expect("tempdir");
+    let src = dir.path().join("tenant.dtl");
+    fs::write(
+        &src,
+        r#"
+        (defconst bonus 2)
+        (data Tenant (acme))
+        (data Subject (alice) (bob))
+        (relation member (Tenant Subject))
+        (relation admin (Subject))
+        (relation active (Tenant Subject))
+        (fact member (acme) (alice))
+        (fact member (acme) (bob))
+        (fact admin (alice))
+        (rule (active ?t ?s) (and (member ?t ?s) (admin ?s)))
+        (universe Tenant ((acme)))
+        (universe Subject ((alice) (bob)))
+        (defn quota ((n Int)) Int (let ((extra (* bonus 0))) (+ n extra)))
+        (assert admins-active ((s Subject)) (not (and (admin s) (not (active (acme) s)))))
+        "#,
+    )
+    .expect("write");
+
+    let prove = |partial_eval: bool| {
+        let mut cmd = cargo_bin_cmd!("dtl");
+        cmd.arg("prove").arg(&src).arg("--format").arg("json");
+        if partial_eval {
+            cmd.arg("--partial-eval");
+        }
+        let output = cmd.output().expect("run");
+        assert!(output.status.success());
+        serde_json::from_slice::<Value>(&output.stdout).expect("json")["proof"].clone()
+    };
+    assert_eq!(prove(true), prove(false));
+
+    let out_dir = dir.path().join("doc");
+    cargo_bin_cmd!("dtl")
+        .arg("doc")
+        .arg(&src)
+        .arg("--out")
+        .arg(&out_dir)
+        .arg("--partial-eval")
+        .assert()
+        .success();
+    assert!(out_dir.join("spec.md").exists());
+
+    fs::write(&src, "(defn pick () Int (if true 1 false))").expect("write");
+    cargo_bin_cmd!("dtl")
+        .arg("prove")
+        .arg(&src)
+        .arg("--partial-eval")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("E-TYPE"));
+}
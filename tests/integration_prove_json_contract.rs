@@ -79,7 +79,7 @@ fn prove_json_reference_engine_contract_is_stable() {
     let expected = json!({
         "status": "ok",
         "proof": {
-            "schema_version": "2.2.0",
+            "schema_version": "2.3.0",
             "profile": "standard",
             "engine": "reference",
             "summary": {
@@ -161,3 +161,48 @@ fn prove_json_out_trace_contract_is_stable() {
     assert_eq!(actual_trace, expected_trace);
     assert_eq!(actual_stdout["proof"], actual_trace);
 }
+
+#[test]
+fn prove_json_timings_report_each_phase_and_obligation() {
+    let src = fixture_path("ok.dtl");
+    let expected = read_fixture_json("ok.stdout.json");
+
+    let mut cmd = cargo_bin_cmd!("dtl");
+    let output = cmd
+        .arg("prove")
+        .arg(&src)
+        .arg("--format")
+        .arg("json")
+        .arg("--timings")
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+
+    assert!(
+        output.stderr.is_empty(),
+        "json mode should not write stderr with --timings"
+    );
+    let actual: Value = serde_json::from_slice(&output.stdout).expect("valid json output");
+    assert_eq!(actual["proof"], expected["proof"]);
+
+    let phases = actual["timings"]["phases"]
+        .as_array()
+        .expect("timings.phases array")
+        .iter()
+        .map(|p| p["phase"].as_str().expect("phase name").to_string())
+        .collect::<Vec<_>>();
+    for phase in ["parse", "resolve", "stratify", "typecheck", "fixpoint"] {
+        assert!(phases.iter().any(|p| p == phase), "missing phase {phase}");
+    }
+    let obligation_ids = expected["proof"]["obligations"]
+        .as_array()
+        .expect("obligations")
+        .iter()
+        .map(|o| format!("prove:{}", o["id"].as_str().expect("id")))
+        .collect::<Vec<_>>();
+    for id in obligation_ids {
+        assert!(phases.contains(&id), "missing obligation phase {id}");
+    }
+    assert!(actual["timings"]["total_micros"].is_u64());
+}
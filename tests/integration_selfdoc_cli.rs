@@ -23,6 +23,21 @@ sample repository
 | fmt | src/main.rs |
 | selfdoc | src/main.rs |
 | selfcheck | src/main.rs |
+| strata | src/main.rs |
+| codes | src/main.rs |
+| grammar | src/main.rs |
+| bench | src/main.rs |
+| slice | src/main.rs |
+| minimize | src/main.rs |
+| diff | src/main.rs |
+| expand | src/main.rs |
+| refs | src/main.rs |
+| plan | src/main.rs |
+| tags | src/main.rs |
+| codegen | src/main.rs |
+| eval | src/main.rs |
+| run | src/main.rs |
+| explore | src/main.rs |
 <!-- selfdoc:cli-contracts:end -->
 "#,
     )
@@ -87,7 +102,7 @@ fn selfdoc_exits_with_code_2_when_config_missing() {
         .arg("--out")
         .arg(&out)
         .assert()
-        .code(2)
+        .code(4)
         .get_output()
         .clone();
 
@@ -127,11 +142,11 @@ fn selfdoc_generates_bundle_and_intermediate_dsl() {
     let trace: Value =
         serde_json::from_slice(&fs::read(out.join("proof-trace.json")).expect("read proof trace"))
             .expect("valid proof trace");
-    assert_eq!(trace["schema_version"], "2.2.0");
+    assert_eq!(trace["schema_version"], "2.3.0");
     assert_eq!(trace["profile"], "selfdoc");
     assert_eq!(trace["engine"], "native");
-    assert_eq!(trace["claim_coverage"]["total_claims"], 7);
-    assert_eq!(trace["claim_coverage"]["proved_claims"], 7);
+    assert_eq!(trace["claim_coverage"]["total_claims"], 22);
+    assert_eq!(trace["claim_coverage"]["proved_claims"], 22);
 
     let index: Value =
         serde_json::from_slice(&fs::read(out.join("doc-index.json")).expect("read index"))
@@ -141,6 +156,39 @@ fn selfdoc_generates_bundle_and_intermediate_dsl() {
     assert_eq!(index["intermediate"]["dsl"], "selfdoc.generated.dtl");
 }
 
+#[test]
+fn selfdoc_deterministic_bundle_does_not_depend_on_output_directory() {
+    let dir = tempdir().expect("tempdir");
+    write_base_repo(dir.path());
+
+    let outs = ["out-a", "out-b"].map(|name| dir.path().join(name));
+    for out in &outs {
+        let mut cmd = cargo_bin_cmd!("dtl");
+        cmd.arg("selfdoc")
+            .arg("--repo")
+            .arg(dir.path())
+            .arg("--out")
+            .arg(out)
+            .arg("--deterministic")
+            .assert()
+            .success();
+    }
+
+    let spec = fs::read_to_string(outs[0].join("spec.md")).expect("read spec.md");
+    assert!(spec.contains("ソース（selfdoc.generated.dtl:"), "{spec}");
+    for file in [
+        "spec.md",
+        "proof-trace.json",
+        "badge.json",
+        "doc-index.json",
+        "selfdoc.generated.dtl",
+    ] {
+        let first = fs::read(outs[0].join(file)).expect("read first bundle");
+        let second = fs::read(outs[1].join(file)).expect("read second bundle");
+        assert_eq!(first, second, "{file} differs");
+    }
+}
+
 #[test]
 fn selfdoc_reference_engine_writes_reference_trace() {
     let dir = tempdir().expect("tempdir");
@@ -163,11 +211,11 @@ fn selfdoc_reference_engine_writes_reference_trace() {
     let trace: Value =
         serde_json::from_slice(&fs::read(out.join("proof-trace.json")).expect("read proof trace"))
             .expect("valid proof trace");
-    assert_eq!(trace["schema_version"], "2.2.0");
+    assert_eq!(trace["schema_version"], "2.3.0");
     assert_eq!(trace["profile"], "selfdoc");
     assert_eq!(trace["engine"], "reference");
-    assert_eq!(trace["claim_coverage"]["total_claims"], 7);
-    assert_eq!(trace["claim_coverage"]["proved_claims"], 7);
+    assert_eq!(trace["claim_coverage"]["total_claims"], 22);
+    assert_eq!(trace["claim_coverage"]["proved_claims"], 22);
 }
 
 #[test]
@@ -295,3 +343,56 @@ jobs:
 
     assert!(out.join("proof-trace.json").exists());
 }
+
+#[test]
+fn selfdoc_accepts_user_defined_categories() {
+    let dir = tempdir().expect("tempdir");
+    write_base_repo(dir.path());
+    fs::create_dir_all(dir.path().join("proto")).expect("mkdir proto");
+    fs::write(dir.path().join("proto/api.proto"), "syntax = \"proto3\";\n").expect("write proto");
+
+    let config_path = dir.path().join(".dtl-selfdoc.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    let config = config.replace(
+        r#"include = ["README.md", "src/**", ".github/workflows/**", ".dtl-selfdoc.toml"]"#,
+        r#"include = ["README.md", "src/**", "proto/**", ".github/workflows/**", ".dtl-selfdoc.toml"]"#,
+    ) + r#"
+[[category]]
+name = "proto"
+description = "gRPC のスキーマ定義"
+
+[[category]]
+name = "generated"
+
+[[classify]]
+category = "proto"
+patterns = ["proto/**"]
+"#;
+    fs::write(&config_path, config).expect("write config");
+
+    let out = dir.path().join("out");
+    let mut cmd = cargo_bin_cmd!("dtl");
+    cmd.arg("selfdoc")
+        .arg("--repo")
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&out)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success();
+
+    let dsl = fs::read_to_string(out.join("selfdoc.generated.dtl")).expect("read dsl");
+    assert!(dsl.contains(
+        r#"(モジュール :名前 "proto/api.proto" :パス "proto/api.proto" :カテゴリ proto)"#
+    ));
+    assert!(dsl.contains("(宇宙 Category :値 (ci config doc generated proto source))"));
+
+    let spec: Value = serde_json::from_slice(&fs::read(out.join("spec.json")).expect("read spec"))
+        .expect("valid spec");
+    let categories = &spec["self_description"]["categories"];
+    assert_eq!(categories[0]["name"], "proto");
+    assert_eq!(categories[0]["description"], "gRPC のスキーマ定義");
+    assert_eq!(categories[1]["name"], "generated");
+    assert!(categories[1].get("description").is_none());
+}
@@ -0,0 +1,120 @@
+use dtl::{check_program, parse_program, parse_program_lossy};
+use proptest::prelude::*;
+
+const VOCAB: &[&str] = &[
+    "(",
+    "(",
+    "(",
+    ")",
+    ")",
+    ")",
+    " ",
+    " ",
+    "\n",
+    "\"",
+    "\\",
+    ";",
+    "\"a b\"",
+    "\"\\q\"",
+    "sort",
+    "data",
+    "relation",
+    "fact",
+    "rule",
+    "assert",
+    "universe",
+    "defn",
+    "alias",
+    "import",
+    "match",
+    "let",
+    "letfn",
+    "if",
+    "not",
+    "and",
+    "Refine",
+    "Int",
+    "Bool",
+    "Symbol",
+    "->",
+    "?x",
+    "x",
+    "y",
+    "0",
+    "-1",
+    "true",
+    "false",
+    ":doc",
+    ":message",
+    ":tag",
+    "関数",
+    "事実",
+    "型",
+    "関係",
+    ":頭",
+    ":本体",
+    ":名前",
+    "; edition: 2025\n",
+    "; edition: 1999\n",
+    "; syntax: surface\n",
+    "; syntax: core\n",
+];
+
+fn token_soup() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(VOCAB), 0..80).prop_map(|parts| parts.concat())
+}
+
+fn form_soup() -> impl Strategy<Value = String> {
+    let atom = prop::sample::select(VOCAB)
+        .prop_filter("atoms only", |t| {
+            !matches!(*t, "(" | ")" | " " | "\n" | "\"" | "\\" | ";")
+        })
+        .prop_map(str::to_string);
+    let form = atom.prop_recursive(4, 32, 6, |inner| {
+        prop::collection::vec(inner, 0..6).prop_map(|items| format!("({})", items.join(" ")))
+    });
+    prop::collection::vec(form, 0..8).prop_map(|forms| forms.join("\n"))
+}
+
+fn assert_agrees_with_strict(src: &str) -> Result<(), TestCaseError> {
+    let (program, errors) = parse_program_lossy(src);
+    match parse_program(src) {
+        Ok(strict) => {
+            prop_assert!(errors.is_empty(), "errors={errors:?}");
+            prop_assert_eq!(program, strict);
+        }
+        Err(strict) => {
+            prop_assert!(!errors.is_empty());
+            prop_assert!(
+                strict.iter().all(|d| errors.contains(d)),
+                "strict={strict:?} lossy={errors:?}"
+            );
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn lossy_parse_never_panics_on_token_soup(src in token_soup()) {
+        assert_agrees_with_strict(&src)?;
+    }
+
+    #[test]
+    fn lossy_parse_never_panics_on_forms(src in form_soup()) {
+        assert_agrees_with_strict(&src)?;
+    }
+
+    #[test]
+    fn partially_parsed_programs_can_be_checked(src in prop_oneof![token_soup(), form_soup()]) {
+        let (program, _) = parse_program_lossy(&src);
+        let _ = check_program(&program);
+    }
+
+    #[test]
+    fn lossy_parse_never_panics_on_arbitrary_text(src in any::<String>()) {
+        assert_agrees_with_strict(&src)?;
+    }
+}
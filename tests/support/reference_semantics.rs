@@ -1,16 +1,14 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
 
-pub use dtl::{
-    ReferenceDerivedFacts, ReferenceEnv, ReferenceObligationResult, ReferenceValue,
-    reference_value_to_string,
+pub use dtl::reference::{
+    DerivedFacts as ReferenceDerivedFacts, Env as ReferenceEnv,
+    ObligationResult as ReferenceObligationResult, Value as ReferenceValue,
+    value_to_string as reference_value_to_string,
 };
 
 use dtl::ast::{AssertDecl, Defn};
-use dtl::{
-    Program, reference_prove_program as dtl_reference_prove_program,
-    reference_solve_facts as dtl_reference_solve_facts,
-};
+use dtl::{Program, reference};
 
 fn render_diagnostics(diags: Vec<dtl::Diagnostic>) -> String {
     diags
@@ -20,35 +18,26 @@ fn render_diagnostics(diags: Vec<dtl::Diagnostic>) -> String {
         .join("; ")
 }
 
-pub fn reference_solve_facts_wrapper(program: &Program) -> Result<ReferenceDerivedFacts, String> {
-    dtl_reference_solve_facts(program).map_err(render_diagnostics)
+pub fn reference_solve_facts(program: &Program) -> Result<ReferenceDerivedFacts, String> {
+    reference::solve_facts(program).map_err(render_diagnostics)
 }
 
-pub fn reference_prove_program_wrapper(
+pub fn reference_prove_program(
     program: &Program,
 ) -> Result<Vec<ReferenceObligationResult>, String> {
-    dtl_reference_prove_program(program).map_err(render_diagnostics)
+    reference::prove(program).map_err(render_diagnostics)
 }
 
 pub fn reference_check_assert(
     program: &Program,
     assertion: &AssertDecl,
 ) -> Result<ReferenceObligationResult, String> {
-    reference_prove_program_wrapper(program)?
-        .into_iter()
-        .find(|item| item.id == format!("assert::{}", assertion.name))
-        .ok_or_else(|| format!("missing obligation for assert::{}", assertion.name))
+    reference::check_assert(program, &assertion.name).map_err(render_diagnostics)
 }
 
 pub fn reference_check_refine(
     program: &Program,
     defn: &Defn,
 ) -> Result<ReferenceObligationResult, String> {
-    reference_prove_program_wrapper(program)?
-        .into_iter()
-        .find(|item| item.id == format!("defn::{}", defn.name))
-        .ok_or_else(|| format!("missing obligation for defn::{}", defn.name))
+    reference::check_refine(program, &defn.name).map_err(render_diagnostics)
 }
-
-pub use reference_prove_program_wrapper as reference_prove_program;
-pub use reference_solve_facts_wrapper as reference_solve_facts;
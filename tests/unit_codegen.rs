@@ -0,0 +1,326 @@
+use std::fs;
+use std::process::Command;
+
+use dtl::{generate_jsonschema, generate_rust, generate_sql, generate_typescript, parse_program};
+
+const SAMPLE: &str = r#"
+(sort Subject)
+(sort Resource)
+(data Tier (gold) (silver))
+(data Action (read) (write))
+(data Nat (z) (s Nat))
+(data Cart (empty) (item Int Cart))
+
+(relation has-role (Subject Symbol))
+(relation public (Resource))
+(relation can-access (Subject Resource Action))
+(relation premium (Tier))
+
+(fact has-role alice admin)
+(fact public doc1)
+(fact premium (gold))
+(rule (can-access ?u ?r (read)) (and (has-role ?u admin) (public ?r)))
+
+(defn discount ((tier Tier) (amount (Refine n Int (and (>= n 0) (<= n 1000000)))))
+  (Refine d Int (>= d 0))
+  (match tier
+    ((gold) (+ amount 1))
+    ((silver) (let ((same (+ amount 0))) same))))
+
+(defn total ((cart Cart)) Int
+  (match cart
+    ((empty) 0)
+    ((item price rest) (+ price (total rest)))))
+
+(defn depth ((n Nat)) Int
+  (match n
+    ((z) 0)
+    ((s (s m)) (+ 2 (depth m)))
+    ((s m) (+ 1 (depth m)))))
+
+(defn pair-tier ((a Tier) (b Tier)) Int
+  (match (item 1 (empty))
+    ((item 1 (empty)) (if (premium a) 10 (letfn ((bump ((x Int)) Int (* x -3))) (bump 1))))
+    (_ 0)))
+
+(defn can-read ((u Subject) (r Resource)) Bool
+  (can-access u r (read)))
+
+(defn type ((match (Refine n Int (and (>= n 0) (<= n 1000000))))) Int (discount (gold) match))
+
+(defn passthrough ((f (-> (Int) Int))) (-> (Int) Int) f)
+"#;
+
+const MAIN: &str = r#"
+fn main() {
+    assert_eq!(discount(Tier::Gold, DiscountAmount::new(120).unwrap()).unwrap().into_inner(), 121);
+    assert_eq!(discount(Tier::Silver, DiscountAmount::new(120).unwrap()).unwrap().into_inner(), 120);
+    assert!(DiscountAmount::new(-1).is_err());
+    assert_eq!(
+        DiscountAmount::new(-1).unwrap_err().to_string(),
+        "value does not satisfy the refinement of DiscountAmount"
+    );
+    let cart = Cart::Item(3, Box::new(Cart::Item(4, Box::new(Cart::Empty))));
+    assert_eq!(total(cart), Ok(7));
+    let three = Nat::S(Box::new(Nat::S(Box::new(Nat::S(Box::new(Nat::Z))))));
+    assert_eq!(depth(three), Ok(3));
+    assert_eq!(pair_tier(Tier::Gold, Tier::Silver), Ok(10));
+    assert_eq!(pair_tier(Tier::Silver, Tier::Gold), Ok(-3));
+    assert!(can_read(Subject::new("alice"), Resource::new("doc1")));
+    assert!(!can_read(Subject::new("bob"), Resource::new("doc1")));
+    assert_eq!(r#type(DiscountAmount::new(5).unwrap()), Ok(6));
+}
+"#;
+
+fn generated(src: &str) -> String {
+    generate_rust(&parse_program(src).expect("parse")).expect("codegen")
+}
+
+#[test]
+fn codegen_rust_emits_enums_newtypes_and_functions() {
+    let code = generated(SAMPLE);
+    assert!(code.starts_with("// @generated by `dtl codegen rust`"));
+    assert!(code.contains("pub enum Nat {\n    Z,\n    S(Box<Nat>),\n}"));
+    assert!(code.contains("pub enum Cart {\n    Empty,\n    Item(i64, Box<Cart>),\n}"));
+    assert!(code.contains("pub struct Subject(pub String);"));
+    assert!(code.contains("pub struct DiscountAmount(i64);"));
+    assert!(
+        code.contains("pub fn discount(tier: Tier, amount: DiscountAmount) -> Result<DiscountOutput, ArithmeticOverflow> {")
+    );
+    assert!(
+        code.contains(
+            "pub fn r#type(r#match: DiscountAmount) -> Result<i64, ArithmeticOverflow> {"
+        )
+    );
+    assert!(code.contains("pub fn can_read(u: Subject, r: Resource) -> bool {"));
+    assert!(code.contains("pub fn can_access(a0: &Subject, a1: &Resource, a2: &Action) -> bool {"));
+    assert!(code.contains("pub fn premium(a0: &Tier) -> bool {"));
+    assert!(!code.contains("pub fn has_role("));
+    assert!(!code.contains("pub fn passthrough("));
+    assert!(code.contains("// - passthrough: it takes or returns a function"));
+}
+
+#[test]
+fn codegen_rust_output_compiles_and_matches_the_program() {
+    let output = compile_and_run(&format!("{}\n{MAIN}", generated(SAMPLE)));
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn compile_and_run(code: &str) -> std::process::Output {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("generated.rs");
+    fs::write(&path, code).expect("write");
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let binary = dir.path().join("generated");
+    let output = Command::new(rustc)
+        .args(["--edition", "2021", "-D", "warnings", "-o"])
+        .arg(&binary)
+        .arg(&path)
+        .output()
+        .expect("run rustc");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Command::new(&binary).output().expect("run generated")
+}
+
+#[test]
+fn codegen_rust_reports_overflow_as_an_error() {
+    let src = r#"
+(defn scale ((n Int)) Int (* n 4))
+(defn offset ((n Int)) Int (letfn ((down ((x Int)) Int (- x 1))) (down (scale n))))
+"#;
+    let main = r#"
+fn main() {
+    assert_eq!(offset(10), Ok(39));
+    let err = offset(i64::MAX / 2).unwrap_err();
+    assert_eq!(err.op, "*");
+    assert_eq!(err.to_string(), format!("integer overflow in (* {} 4)", i64::MAX / 2));
+    assert_eq!(
+        scale(i64::MIN).unwrap_err(),
+        ArithmeticOverflow { op: "*", lhs: i64::MIN, rhs: 4 }
+    );
+    assert!(ArithmeticOverflow::checked_sub(i64::MIN, 1).is_err());
+}
+"#;
+    let output = compile_and_run(&format!("{}\n{main}", generated(src)));
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn codegen_rust_reports_names_that_collide_in_rust() {
+    let program =
+        parse_program("(data Mode (on))\n(defn to-mode () Mode (on))\n(defn to_mode () Mode (on))")
+            .expect("parse");
+    let errors = generate_rust(&program).expect_err("collision");
+    assert_eq!(errors[0].code, "E-CODEGEN");
+    assert_eq!(
+        errors[0].message,
+        "to-mode and to_mode both become the Rust name to_mode"
+    );
+
+    let program = parse_program("(sort String)").expect("parse");
+    let errors = generate_rust(&program).expect_err("reserved");
+    assert_eq!(
+        errors[0].message,
+        "String becomes the Rust name String, which the generated code uses"
+    );
+}
+
+#[test]
+fn codegen_typescript_emits_types_and_validators() {
+    let src = format!(
+        "{SAMPLE}{}",
+        r#"
+(universe Subject (alice bob))
+(relation vip (Subject))
+(fact vip alice)
+(defn greet ((u (Refine x Subject (and (vip x) (!= x bob))))) Bool true)
+"#
+    );
+    let code = generate_typescript(&parse_program(&src).expect("parse")).expect("codegen");
+    assert!(code.starts_with("// @generated by `dtl codegen typescript`"));
+    assert!(code.contains("export type Subject = string;"));
+    assert!(code.contains(
+        "export function isSubject(value: unknown): value is Subject {\n  \
+         return typeof value === \"string\" && [\"alice\", \"bob\"].includes(value);\n}"
+    ));
+    assert!(code.contains(
+        "export type Cart =\n  | { tag: \"empty\" }\n  | { tag: \"item\"; fields: [number, Cart] };"
+    ));
+    assert!(code.contains(
+        "      return Array.isArray(fields) && fields.length === 2 && isInt(fields[0]) && isCart(fields[1]);"
+    ));
+    assert!(code.contains(
+        "export type DiscountAmount = number & { readonly __refinement: \"DiscountAmount\" };"
+    ));
+    assert!(code.contains("return isInt(value) && (value >= 0);"));
+    assert!(
+        code.contains("return isSubject(value) && (vip(value) && !sameValue(value, \"bob\"));")
+    );
+    assert!(code.contains("export function vip(a0: Subject): boolean {"));
+    assert!(!code.contains("export function canAccess("));
+
+    let program = parse_program("(sort Mode)\n(relation is-mode (Mode))").expect("parse");
+    let errors = generate_typescript(&program).expect_err("collision");
+    assert_eq!(
+        errors[0].message,
+        "Mode and is-mode both become the TypeScript name isMode"
+    );
+}
+
+#[test]
+fn codegen_jsonschema_emits_tagged_unions_and_constrained_refinements() {
+    let src = format!(
+        "{SAMPLE}{}",
+        r#"
+(universe Subject (alice bob))
+(relation grade (Tier Int))
+(fact grade (gold) 3)
+(fact grade (silver) 1)
+(defn greet ((u (Refine x Subject (!= x bob))) (g (Refine k Int (and (grade (silver) k) (< k 10))))) Bool true)
+(defn bounded ((n (Refine v Int (and (< 0 v) (<= v 100))))) Int n)
+(defn premium-only ((t (Refine x Tier (premium x)))) Tier t)
+"#
+    );
+    let schema: serde_json::Value = serde_json::from_str(
+        &generate_jsonschema(&parse_program(&src).expect("parse")).expect("codegen"),
+    )
+    .expect("json");
+    let defs = &schema["$defs"];
+    assert_eq!(
+        schema["$schema"],
+        "https://json-schema.org/draft/2020-12/schema"
+    );
+    assert_eq!(defs["Subject"]["enum"], serde_json::json!(["alice", "bob"]));
+    assert_eq!(defs["Resource"]["type"], "string");
+    assert!(defs["Resource"].get("enum").is_none());
+    assert_eq!(
+        defs["Cart"]["oneOf"][1],
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "tag": { "const": "item" },
+                "fields": {
+                    "type": "array",
+                    "prefixItems": [{ "type": "integer" }, { "$ref": "#/$defs/Cart" }],
+                    "minItems": 2,
+                    "items": false,
+                },
+            },
+            "required": ["tag", "fields"],
+            "additionalProperties": false,
+        })
+    );
+    assert_eq!(defs["DiscountAmount"]["minimum"], 0);
+    assert_eq!(defs["GreetU"]["$ref"], "#/$defs/Subject");
+    assert_eq!(defs["GreetU"]["not"], serde_json::json!({ "const": "bob" }));
+    assert_eq!(defs["GreetG"]["enum"], serde_json::json!([1]));
+    assert_eq!(defs["GreetG"]["exclusiveMaximum"], 10);
+    assert_eq!(defs["BoundedN"]["exclusiveMinimum"], 0);
+    assert_eq!(defs["BoundedN"]["maximum"], 100);
+    assert_eq!(
+        defs["PremiumOnlyT"]["enum"],
+        serde_json::json!([{ "tag": "gold" }])
+    );
+    assert!(defs.get("TypeMatch").is_none());
+}
+
+#[test]
+fn codegen_sql_emits_tables_with_keys_and_checks() {
+    let src = r#"
+(sort Subject)
+(sort Node)
+(universe Subject (alice bob))
+(data Tier (gold) (silver))
+(data Cart (empty) (item Int Cart))
+(relation registered (Subject) :doc "登録済み")
+(relation edge (Node Node))
+(relation member (Subject Tier) :key (1))
+(relation age ((Refine p Subject (registered p)) (Refine n Int (and (>= n 0) (not (> n 150))))))
+(relation basket (Node Cart Bool))
+(relation maintenance ())
+(fact registered alice)
+(fact member alice gold)
+"#;
+    let sql = generate_sql(&parse_program(src).expect("parse")).expect("codegen");
+    assert!(sql.starts_with("-- @generated by `dtl codegen sql`"));
+    assert!(sql.contains(
+        "-- 登録済み\nCREATE TABLE \"registered\" (\n    \
+         \"arg_1\" TEXT NOT NULL CHECK (\"arg_1\" IN ('alice', 'bob')),\n    \
+         PRIMARY KEY (\"arg_1\")\n);"
+    ));
+    assert!(sql.contains(
+        "CREATE TABLE \"edge\" (\n    \
+         \"arg_1\" TEXT NOT NULL,\n    \
+         \"arg_2\" TEXT NOT NULL,\n    \
+         PRIMARY KEY (\"arg_1\", \"arg_2\")\n);"
+    ));
+    assert!(sql.contains(
+        "\"arg_2\" TEXT NOT NULL CHECK (\"arg_2\" IN ('(gold)', '(silver)')),\n    \
+         PRIMARY KEY (\"arg_1\")\n);"
+    ));
+    assert!(sql.contains("CHECK (\"arg_1\" IN ('alice', 'bob')) CHECK (\"arg_1\" IN ('alice')),"));
+    assert!(
+        sql.contains("\"arg_2\" BIGINT NOT NULL CHECK (\"arg_2\" >= 0 AND NOT (\"arg_2\" > 150)),")
+    );
+    assert!(sql.contains("    \"arg_2\" TEXT NOT NULL,\n    \"arg_3\" BOOLEAN NOT NULL,"));
+    assert!(
+        sql.ends_with("-- Not generated (a table needs at least one column):\n-- - maintenance\n")
+    );
+
+    let broken = format!("{src}(fact member alice silver)\n");
+    let errors = generate_sql(&parse_program(&broken).expect("parse")).expect_err("key");
+    assert!(errors.iter().any(|d| d.code == "E-KEY"
+        && d.message == "fact member breaks its :key, as (member alice gold) has the same key"));
+}
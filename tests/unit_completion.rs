@@ -0,0 +1,94 @@
+use dtl::{
+    CompletionKind, ParseOptions, completions_at, parse_program, parse_program_with_options,
+};
+
+const SRC: &str = r#"(sort Subject)
+(data Role (admin-role) (guest-role))
+(relation admin (Subject))
+(fact admin alice)
+(rule (allowed ?u) (admin ?u))
+(relation allowed (Subject))
+(assert admins-allowed ((u Subject)) (not (and (admin u) (not (allowed u)))))
+(defn role-of ((u Subject)) Role (let ((a (admin u))) (if a (admin-role) (guest-role))))
+(defn weight ((r Role)) Int (match r ((admin-role) 10) (_ 0)))
+"#;
+
+fn labels_at(needle: &str, kind: CompletionKind) -> Vec<String> {
+    let program = parse_program(SRC).expect("parse");
+    let offset = SRC.find(needle).expect("needle");
+    completions_at(&program, "policy.dtl", offset)
+        .into_iter()
+        .filter(|completion| completion.kind == kind)
+        .map(|completion| completion.label)
+        .collect()
+}
+
+#[test]
+fn completions_offer_types_in_parameter_and_signature_positions() {
+    assert_eq!(
+        labels_at("Subject)) Role", CompletionKind::Sort),
+        ["Role", "Subject"]
+    );
+    assert_eq!(
+        labels_at("Role (let", CompletionKind::Sort),
+        ["Role", "Subject"]
+    );
+    assert!(labels_at("Role (let", CompletionKind::Relation).is_empty());
+    assert!(labels_at("Subject))\n(fact", CompletionKind::Keyword).contains(&"Int".to_string()));
+}
+
+#[test]
+fn completions_offer_relations_in_rules_and_formulas() {
+    assert_eq!(
+        labels_at("(admin ?u)", CompletionKind::Relation),
+        ["admin", "allowed"]
+    );
+    assert_eq!(
+        labels_at("(admin ?u)", CompletionKind::Keyword),
+        ["and", "not"]
+    );
+    assert!(labels_at("alice)", CompletionKind::Keyword).is_empty());
+    assert_eq!(labels_at("(allowed u)", CompletionKind::Variable), ["u"]);
+    assert!(labels_at("(allowed u)", CompletionKind::Defn).is_empty());
+}
+
+#[test]
+fn completions_in_expressions_include_scope_and_functions() {
+    assert_eq!(
+        labels_at("a (admin-role)", CompletionKind::Variable),
+        ["a", "u"]
+    );
+    assert_eq!(labels_at("(admin u)))", CompletionKind::Variable), ["u"]);
+    assert_eq!(
+        labels_at("(admin-role) 10", CompletionKind::Variable),
+        ["r"]
+    );
+    assert_eq!(
+        labels_at("10)", CompletionKind::Defn),
+        ["role-of", "weight"]
+    );
+    assert_eq!(
+        labels_at("10)", CompletionKind::Constructor),
+        ["admin-role", "guest-role"]
+    );
+    assert!(labels_at("10)", CompletionKind::Keyword).contains(&"match".to_string()));
+}
+
+#[test]
+fn completions_offer_form_heads_at_top_level() {
+    let heads = labels_at("sort Subject", CompletionKind::Keyword);
+    assert!(heads.contains(&"defn".to_string()));
+    assert!(heads.contains(&"関数".to_string()));
+    let options = ParseOptions {
+        source: Some("policy.dtl".to_string()),
+        ..ParseOptions::default()
+    };
+    let program = parse_program_with_options(SRC, &options).expect("parse");
+    let offset = SRC.find("(admin ?u)").expect("rule body");
+    assert!(!completions_at(&program, "policy.dtl", offset).is_empty());
+    assert!(
+        completions_at(&program, "other.dtl", offset)
+            .iter()
+            .all(|completion| completion.kind == CompletionKind::Keyword)
+    );
+}
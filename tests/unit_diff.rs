@@ -0,0 +1,78 @@
+use dtl::{ChangeKind, DeclKind, diff_programs, parse_program};
+
+const BASE: &str = r#"
+(sort Subject)
+(relation admin (Subject))
+(relation staff (Subject))
+(relation allowed (Subject))
+(fact admin alice)
+(fact staff bob)
+(rule (allowed ?u) (admin ?u))
+(universe Subject (alice bob))
+(assert admins-allowed ((u Subject)) (not (and (admin u) (not (allowed u)))))
+(assert staff-known ((u Subject)) (not (and (staff u) (not (staff u)))))
+"#;
+
+fn changes(old: &str, new: &str) -> Vec<(DeclKind, String, ChangeKind)> {
+    let old = parse_program(old).expect("parse old");
+    let new = parse_program(new).expect("parse new");
+    diff_programs(&old, &new)
+        .expect("diff")
+        .changes
+        .into_iter()
+        .map(|change| (change.kind, change.name, change.change))
+        .collect()
+}
+
+#[test]
+fn diff_ignores_alpha_renaming_and_declaration_order() {
+    let renamed = r#"
+(sort Subject)
+(relation allowed (Subject))
+(relation staff (Subject))
+(relation admin (Subject))
+(fact staff bob)
+(fact admin alice)
+(rule (allowed ?x) (admin ?x))
+(universe Subject (bob alice))
+(assert staff-known ((s Subject)) (not (and (staff s) (not (staff s)))))
+(assert admins-allowed ((v Subject)) (not (and (admin v) (not (allowed v)))))
+"#;
+    assert!(changes(BASE, renamed).is_empty());
+}
+
+#[test]
+fn diff_reports_added_removed_and_changed_declarations() {
+    let edited = BASE
+        .replace("(fact staff bob)\n", "")
+        .replace(
+            "(rule (allowed ?u) (admin ?u))",
+            "(rule (allowed ?u) (staff ?u))",
+        )
+        .replace(
+            "(relation allowed (Subject))",
+            "(relation allowed (Subject))\n(relation auditor (Subject))",
+        );
+    assert_eq!(
+        changes(BASE, &edited),
+        vec![
+            (DeclKind::Relation, "auditor".to_string(), ChangeKind::Added),
+            (DeclKind::Fact, "staff".to_string(), ChangeKind::Removed),
+            (DeclKind::Rule, "allowed".to_string(), ChangeKind::Changed),
+        ]
+    );
+}
+
+#[test]
+fn diff_lists_obligations_that_depend_on_a_change() {
+    let old = parse_program(BASE).expect("parse old");
+    let rule_changed = BASE.replace("(admin ?u))", "(staff ?u))");
+    let new = parse_program(&rule_changed).expect("parse new");
+    let diff = diff_programs(&old, &new).expect("diff");
+    assert_eq!(diff.affected_obligations, vec!["assert::admins-allowed"]);
+
+    let facts_removed = BASE.replace("(fact staff bob)\n", "");
+    let new = parse_program(&facts_removed).expect("parse new");
+    let diff = diff_programs(&old, &new).expect("diff");
+    assert_eq!(diff.affected_obligations, vec!["assert::staff-known"]);
+}
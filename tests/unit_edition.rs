@@ -0,0 +1,115 @@
+use dtl::{
+    Edition, EditionFeature, FormatOptions, ParseOptions, SurfaceKeywordMap, format_source,
+    parse_program, parse_program_with_options,
+};
+
+fn custom_keywords() -> SurfaceKeywordMap {
+    let mut keywords = SurfaceKeywordMap::new();
+    keywords
+        .insert("取引関係", "関係")
+        .expect("keyword should be valid");
+    keywords
+}
+
+#[test]
+fn edition_feature_matrix_orders_editions() {
+    assert_eq!(Edition::LATEST, Edition::E2027);
+    assert_eq!(Edition::parse("2025"), Some(Edition::E2025));
+    assert_eq!(Edition::parse("2030"), None);
+    assert!(!Edition::E2025.supports(EditionFeature::ProjectSurfaceKeywords));
+    assert!(Edition::E2026.supports(EditionFeature::ProjectSurfaceKeywords));
+    assert!(!Edition::E2026.supports(EditionFeature::BlockComments));
+    assert!(Edition::E2027.supports(EditionFeature::Macros));
+}
+
+#[test]
+fn older_editions_lex_hash_atoms_as_before() {
+    let src = "; edition: 2026\n(sort P)\n(relation p (P))\n(fact p #|x)\n";
+    let program = parse_program(src).expect("2026 should read #|x as an atom");
+    assert_eq!(program.facts.len(), 1);
+    let formatted = format_source(src, FormatOptions::default()).expect("format");
+    assert!(formatted.contains("#|x"), "formatted={formatted}");
+    parse_program(&formatted).expect("formatted output should parse");
+
+    let errs = parse_program(&src.replace("edition: 2026", "edition: 2027"))
+        .expect_err("2027 reads #| as an unterminated block comment");
+    assert_eq!(errs[0].code, "E-PARSE");
+}
+
+#[test]
+fn parser_gates_later_forms_by_declared_edition() {
+    let src = "; edition: 2026\n(sort P)\n(defconst limit 3)\n";
+    let errs = parse_program(src).expect_err("should fail");
+    assert_eq!(errs[0].code, "E-EDITION");
+    assert!(
+        errs[0]
+            .message
+            .contains("defconst is not available in edition 2026"),
+        "errs={errs:?}"
+    );
+    parse_program(&src.replace("edition: 2026", "edition: 2027"))
+        .expect("2027 should accept defconst");
+}
+
+#[test]
+fn parser_rejects_unsupported_edition_pragma() {
+    let errs = parse_program("; edition: 2030\n(sort Subject)\n").expect_err("should fail");
+    assert_eq!(errs[0].code, "E-EDITION");
+    assert!(errs[0].message.contains("unsupported edition: 2030"));
+    let span = errs[0].span.as_ref().expect("span");
+    assert_eq!((span.line, span.column), (1, 1));
+}
+
+#[test]
+fn parser_gates_features_by_declared_edition() {
+    let src = "; edition: 2025\n(型 主体)\n(取引関係 締結済み :引数 (主体))\n";
+    let options = ParseOptions {
+        keywords: custom_keywords(),
+        ..ParseOptions::default()
+    };
+    let errs = parse_program_with_options(src, &options).expect_err("should fail");
+    assert_eq!(errs[0].code, "E-EDITION");
+    assert!(
+        errs[0]
+            .message
+            .contains("project surface keywords is not available in edition 2025"),
+        "errs={errs:?}"
+    );
+    let span = errs[0].span.as_ref().expect("span");
+    assert_eq!(&src[span.start..span.end], "取引関係");
+
+    let upgraded = src.replace("edition: 2025", "edition: 2026");
+    parse_program_with_options(&upgraded, &options).expect("2026 should accept the feature");
+    parse_program_with_options(&src.replace("; edition: 2025\n", ""), &options)
+        .expect("files without a pragma use the latest edition");
+}
+
+#[test]
+fn formatter_preserves_edition_pragma() {
+    let formatted = format_source(
+        "; edition: 2025\n(sort Subject)\n",
+        FormatOptions::default(),
+    )
+    .expect("format");
+    assert!(formatted.starts_with("; syntax: surface\n; edition: 2025\n"));
+    parse_program(&formatted).expect("formatted output should parse");
+}
+
+#[test]
+fn edition_pragma_is_read_after_a_shebang_line() {
+    let src = "#!/usr/bin/env -S dtl check\n; edition: 2030\n(sort Subject)\n";
+    let errs = parse_program(src).expect_err("should fail");
+    assert_eq!(errs[0].code, "E-EDITION");
+    let span = errs[0].span.as_ref().expect("span");
+    assert_eq!((span.line, span.column), (2, 1));
+
+    let src = "#!/usr/bin/env -S dtl check\n; edition: 2025\n(sort Subject)\n";
+    let formatted = format_source(src, FormatOptions::default()).expect("format");
+    assert!(
+        formatted.starts_with("#!/usr/bin/env -S dtl check\n; syntax: surface\n; edition: 2025\n")
+    );
+    assert_eq!(
+        format_source(&formatted, FormatOptions::default()).expect("reformat"),
+        formatted
+    );
+}
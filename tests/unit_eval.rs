@@ -0,0 +1,245 @@
+use dtl::{
+    EvalLimits, EvalTrace, EvalValue, evaluate, evaluate_traced, parse_program, run_entry_point,
+    with_eval_stack,
+};
+
+const SRC: &str = r#"
+(data Tier (gold) (silver))
+(data Cart (empty) (item Int Cart))
+(relation premium (Tier))
+(fact premium (gold))
+(defconst base-amount 120)
+
+(defn discount ((tier Tier) (amount (Refine n Int (and (>= n 0) (<= n 1000000)))))
+  (Refine d Int (>= d 0))
+  (match tier
+    ((gold) (+ amount 1))
+    ((silver) amount)))
+
+(defn total ((cart Cart)) Int
+  (match cart
+    ((empty) 0)
+    ((item price rest) (+ price (total rest)))))
+
+(defn twice ((x Int)) Int
+  (letfn ((bump ((y Int)) Int (+ y x))
+          (both ((y Int)) Int (bump (bump y))))
+    (let ((start 100)) (both start))))
+
+(defn perks ((tier Tier)) Bool (premium tier))
+"#;
+
+fn eval(application: &str) -> Result<String, String> {
+    let program = parse_program(SRC).expect("parse");
+    evaluate(&program, application, EvalLimits::default())
+        .map(|evaluation| evaluation.value.to_string())
+        .map_err(|diags| format!("{}: {}", diags[0].code, diags[0].message))
+}
+
+#[test]
+fn eval_applies_defns_to_literal_arguments() {
+    assert_eq!(eval("(discount gold 120)"), Ok("121".to_string()));
+    assert_eq!(eval("(discount (silver) 120)"), Ok("120".to_string()));
+    assert_eq!(eval("(discount gold base-amount)"), Ok("121".to_string()));
+    assert_eq!(
+        eval("(total (item 2 (item 3 (empty))))"),
+        Ok("5".to_string())
+    );
+    assert_eq!(eval("(perks gold)"), Ok("true".to_string()));
+    assert_eq!(eval("(perks silver)"), Ok("false".to_string()));
+    assert_eq!(eval("(twice 1)"), Ok("102".to_string()));
+}
+
+#[test]
+fn eval_checks_arguments_against_parameter_types() {
+    assert_eq!(
+        eval("(discount gold -1)"),
+        Err(
+            "E-EVAL: argument amount of discount: -1 does not satisfy (Refine n Int (and (>= n 0) (<= n 1000000)))"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        eval("(discount bronze 1)"),
+        Err("E-EVAL: argument tier of discount: bronze is not a constructor of Tier".to_string())
+    );
+    assert_eq!(
+        eval("(total (item 1))"),
+        Err("E-EVAL: argument cart of total: item expects 2 fields, got 1".to_string())
+    );
+    assert_eq!(
+        eval("(discount gold)"),
+        Err("E-EVAL: discount expects 2 arguments, got 1".to_string())
+    );
+    assert_eq!(
+        eval("(refund 1)"),
+        Err("E-RESOLVE: unknown defn: refund".to_string())
+    );
+}
+
+#[test]
+fn eval_reports_application_errors_against_the_application() {
+    let program = parse_program(SRC).expect("parse");
+    for (application, code, column) in [
+        ("(discount gold 1))", "E-PARSE", 18),
+        ("(refund 1)", "E-RESOLVE", 0),
+    ] {
+        let diags = evaluate(&program, application, EvalLimits::default()).expect_err(application);
+        assert_eq!(diags[0].code, code);
+        assert_eq!(diags[0].source(), Some("<application>"));
+        assert_eq!(diags[0].span.as_ref().map_or(0, |span| span.column), column);
+    }
+}
+
+#[test]
+fn eval_stops_at_resource_limits_and_overflow() {
+    let program = parse_program(SRC).expect("parse");
+    let cart = "(total (item 1 (item 2 (item 3 (empty)))))";
+    let evaluation = evaluate(&program, cart, EvalLimits::default()).expect("eval");
+    assert_eq!(evaluation.value, EvalValue::Int(6));
+
+    let steps = EvalLimits {
+        max_steps: evaluation.steps - 1,
+        ..EvalLimits::default()
+    };
+    let err = evaluate(&program, cart, steps).expect_err("step limit");
+    assert_eq!(
+        err[0].message,
+        format!(
+            "evaluation exceeded the step limit of {}",
+            evaluation.steps - 1
+        )
+    );
+    let depth = EvalLimits {
+        max_depth: 4,
+        ..EvalLimits::default()
+    };
+    let err = evaluate(&program, cart, depth).expect_err("depth limit");
+    assert_eq!(err[0].message, "evaluation exceeded the depth limit of 4");
+
+    assert_eq!(
+        eval("(total (item 9223372036854775807 (item 1 (empty))))"),
+        Err("E-EVAL: integer overflow in (+ 9223372036854775807 1)".to_string())
+    );
+}
+
+#[test]
+fn eval_session_runs_deep_calls_on_one_sized_stack() {
+    let program = parse_program(SRC).expect("parse");
+    let cart = format!("{}(empty){}", "(item 1 ".repeat(200), ")".repeat(200));
+    let application = format!("(total {cart})");
+    let limits = EvalLimits {
+        max_depth: 2048,
+        ..EvalLimits::default()
+    };
+    let values = with_eval_stack(limits.max_depth, || {
+        (0..3)
+            .map(|_| {
+                evaluate(&program, &application, limits)
+                    .expect("eval")
+                    .value
+            })
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(values, vec![EvalValue::Int(200); 3]);
+    let deeper = EvalLimits {
+        max_depth: 4096,
+        ..limits
+    };
+    let value = with_eval_stack(1024, || evaluate(&program, &application, deeper))
+        .expect("eval")
+        .value;
+    assert_eq!(value, EvalValue::Int(200));
+}
+
+fn run(src: &str) -> Result<(String, String), String> {
+    let program = parse_program(&format!("{SRC}{src}")).expect("parse");
+    run_entry_point(&program, EvalLimits::default())
+        .map(|(entry, evaluation)| (entry, evaluation.value.to_string()))
+        .map_err(|diags| format!("{}: {}", diags[0].code, diags[0].message))
+}
+
+#[test]
+fn run_evaluates_main_or_the_marked_entrypoint() {
+    assert_eq!(
+        run("(defn main () Int (discount (gold) 1))"),
+        Ok(("main".to_string(), "2".to_string()))
+    );
+    assert_eq!(
+        run("(defn main () Int 0)\n(defn checkout () Int (total (item 4 (empty))) :entry true)"),
+        Ok(("checkout".to_string(), "4".to_string()))
+    );
+    assert_eq!(
+        run(""),
+        Err(
+            "E-EVAL: no entrypoint: define (defn main () ...) or mark a defn with :entry true"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        run("(defn a () Int 1 :entry true)\n(defn b () Int 2 :entry true)"),
+        Err("E-EVAL: several defns are marked :entry true: a and b".to_string())
+    );
+    assert_eq!(
+        run("(defn main ((x Int)) Int x)"),
+        Err("E-EVAL: entrypoint main must take no parameters".to_string())
+    );
+}
+
+#[test]
+fn eval_traces_calls_arms_and_bindings() {
+    let program = parse_program(SRC).expect("parse");
+    let mut trace = EvalTrace::default();
+    let evaluation = evaluate_traced(
+        &program,
+        "(total (item 2 (empty)))",
+        EvalLimits::default(),
+        &mut trace,
+    )
+    .expect("eval");
+    assert_eq!(evaluation.value, EvalValue::Int(2));
+    let lines = trace
+        .steps
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "call total cart=(item 2 (empty))",
+            "  match (item 2 (empty)) -> arm 2 (item price rest) price=2 rest=(empty)",
+            "  call total cart=(empty)",
+            "    match (empty) -> arm 1 (empty)",
+            "  return total = 0",
+            "return total = 2",
+        ]
+    );
+
+    let mut trace = EvalTrace {
+        max_depth: Some(0),
+        ..EvalTrace::default()
+    };
+    evaluate_traced(&program, "(twice 1)", EvalLimits::default(), &mut trace).expect("eval");
+    assert_eq!(
+        trace
+            .steps
+            .iter()
+            .map(|step| (step.depth, step.event.kind()))
+            .collect::<Vec<_>>(),
+        [(0, "call"), (0, "return")]
+    );
+
+    let mut trace = EvalTrace::default();
+    let limits = EvalLimits {
+        max_steps: 3,
+        ..EvalLimits::default()
+    };
+    evaluate_traced(&program, "(perks gold)", limits, &mut trace).expect("eval");
+    assert_eq!(
+        trace.steps.last().map(ToString::to_string),
+        Some("return perks = true".to_string())
+    );
+    let mut trace = EvalTrace::default();
+    evaluate_traced(&program, "(twice 1)", limits, &mut trace).expect_err("step limit");
+    assert_eq!(trace.steps[0].to_string(), "call twice x=1");
+}
@@ -0,0 +1,93 @@
+use dtl::{Explorer, parse_program};
+
+const SRC: &str = r#"
+(sort Subject)
+(sort Resource)
+(data Action (read) (write))
+(relation member (Subject))
+(relation owner (Subject Resource))
+(relation doc (Resource))
+(relation can (Subject Resource Action))
+(universe Subject (alice bob))
+(universe Resource (doc1))
+(fact member alice)
+(fact doc doc1)
+(fact owner alice doc1)
+(rule (can ?u ?r (read)) (and (member ?u) (doc ?r)))
+(rule (can ?u ?r (write)) (owner ?u ?r))
+(assert members-read ((u Subject) (r Resource)) (can u r (read)))
+(assert owners-write ((r Resource)) (can alice r (write)))
+"#;
+
+fn explorer() -> Explorer {
+    Explorer::new(&parse_program(SRC).expect("parse")).expect("explorer")
+}
+
+#[test]
+fn explorer_lists_obligations_and_opens_a_counterexample() {
+    let mut explorer = explorer();
+    let screen = explorer.screen();
+    assert!(screen.starts_with("証明義務 2 件（成功 1 / 失敗 1）"));
+    assert!(screen.contains("  1. [failed] assert::members-read\n"));
+    assert!(screen.contains("  2. [proved] assert::owners-write\n"));
+
+    assert!(explorer.command("1"));
+    let screen = explorer.screen();
+    assert!(screen.contains("式: (can u r (read))\n"));
+    assert!(screen.contains("反例の代入: r = doc1, u = bob\n"));
+    assert!(screen.contains("成立しない goal:\n  1. can(bob,doc1,(read))\n"));
+    assert!(
+        screen.contains(
+            "関連する rule:\n  - (rule (can ?u ?r (read)) (and (member ?u) (doc ?r)))\n\n"
+        )
+    );
+
+    assert!(explorer.command("1"));
+    let screen = explorer.screen();
+    assert!(screen.starts_with("can(bob,doc1,(read))\n状態: 導出されていない\n"));
+    assert!(screen.contains("head が一致する rule:\n  - (rule (can ?u ?r (read))"));
+}
+
+#[test]
+fn explorer_follows_the_provenance_of_derived_facts() {
+    let mut explorer = explorer();
+    explorer.command("1");
+    let screen = explorer.screen();
+    assert!(screen.contains("  3. can(alice,doc1,(write))\n"));
+
+    explorer.command("3");
+    assert_eq!(
+        explorer.screen(),
+        "can(alice,doc1,(write))\n\
+         状態: rule で導出（stratum 0 / iteration 1）\n\
+         rule: (rule (can ?u ?r (write)) (owner ?u ?r))\n\
+         前提:\n  1. owner(alice,doc1)\n\
+         \n番号: 開く  b: 戻る  help: ヘルプ  q: 終了\n"
+    );
+    explorer.command("1");
+    assert!(
+        explorer
+            .screen()
+            .starts_with("owner(alice,doc1)\n状態: fact として宣言\n")
+    );
+
+    explorer.command("b");
+    explorer.command("b");
+    explorer.command("b");
+    explorer.command("b");
+    assert!(explorer.screen().starts_with("証明義務"));
+    explorer.command("7");
+    assert!(explorer.screen().contains("\n項目 7 はありません\n"));
+    explorer.command("x");
+    assert!(
+        explorer
+            .screen()
+            .contains("\n不明なコマンド: x（help でコマンド一覧）\n")
+    );
+    explorer.command("help");
+    let screen = explorer.screen();
+    assert!(screen.starts_with("証明義務"));
+    assert!(screen.contains("\nコマンド:\n  番号  その項目を開く\n"));
+    assert!(screen.contains("  q     終了する\n"));
+    assert!(!explorer.command("q"));
+}
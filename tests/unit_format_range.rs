@@ -0,0 +1,175 @@
+use dtl::diagnostics::make_span;
+use dtl::{
+    FormatOptions, SurfaceKeywordMap, format_range, format_source, format_warnings,
+    leaves_core_comments_unformatted,
+};
+
+const SURFACE: &str = r#"; syntax: surface
+; keywords: en
+
+(sort   Subject)
+
+; who may administer
+(relation admin :args   (Subject))
+(fact admin :terms (alice))
+"#;
+
+fn cursor(src: &str, needle: &str) -> dtl::Span {
+    let offset = src.find(needle).expect("needle");
+    make_span(src, offset, offset)
+}
+
+#[test]
+fn format_range_rewrites_only_the_form_under_the_cursor() {
+    let edit = format_range(
+        SURFACE,
+        &cursor(SURFACE, "admin :args"),
+        FormatOptions::default(),
+    )
+    .expect("format")
+    .expect("edit");
+
+    assert_eq!(
+        &SURFACE[edit.span.start..edit.span.end],
+        "(relation admin :args   (Subject))"
+    );
+    assert_eq!(edit.replacement, "(relation admin :args (Subject))");
+    assert_eq!(edit.span.line, 7);
+}
+
+#[test]
+fn format_range_keeps_block_comments_between_forms() {
+    let src = "; syntax: surface\n; keywords: en\n\n(sort   Subject)\n#| (sort Unused)\n   (sort Old) |#\n(sort  Role)\n";
+    let edit = format_range(src, &make_span(src, 0, src.len()), FormatOptions::default())
+        .expect("format")
+        .expect("edit");
+    assert_eq!(
+        edit.replacement,
+        "(sort Subject)\n#| (sort Unused)\n   (sort Old) |#\n(sort Role)"
+    );
+}
+
+#[test]
+fn format_formats_around_block_comments_and_keeps_the_forms_they_hold() {
+    let src = "; syntax: surface\n; keywords: en\n\n(sort   Subject)\n#| (sort Unused)\n   (sort Old) |#\n(sort  Role)\n";
+    assert_eq!(
+        format_source(src, FormatOptions::default()).expect("format"),
+        "; syntax: surface\n; keywords: en\n\n(sort Subject)\n#| (sort Unused)\n   (sort Old) |#\n(sort Role)\n"
+    );
+    assert!(format_warnings(src, &SurfaceKeywordMap::default()).is_empty());
+
+    let inner = "; syntax: surface\n; keywords: en\n\n(relation admin :args   (Subject #| Role |#))\n(sort   Role)\n";
+    assert_eq!(
+        format_range(inner, &cursor(inner, "admin"), FormatOptions::default()).expect("format"),
+        None
+    );
+    assert_eq!(
+        format_source(inner, FormatOptions::default()).expect("format"),
+        "; syntax: surface\n; keywords: en\n\n(relation admin :args   (Subject #| Role |#))\n(sort Role)\n"
+    );
+    let warnings = format_warnings(inner, &SurfaceKeywordMap::default());
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "W-FMT-COMMENT");
+    assert_eq!(warnings[0].span.as_ref().map(|span| span.line), Some(4));
+}
+
+#[test]
+fn format_warns_once_for_core_files_with_block_comments() {
+    let src = "; syntax: core\n(sort   Subject)\n#; (sort Old)\n(sort Role)\n";
+    assert_eq!(
+        format_source(src, FormatOptions::default()).expect("format"),
+        src
+    );
+    let warnings = format_warnings(src, &SurfaceKeywordMap::default());
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "W-FMT-COMMENT");
+    assert!(warnings[0].span.is_none());
+    assert!(leaves_core_comments_unformatted(
+        src,
+        &SurfaceKeywordMap::default()
+    ));
+    assert!(!leaves_core_comments_unformatted(
+        "; syntax: core\n(sort Subject)\n",
+        &SurfaceKeywordMap::default()
+    ));
+}
+
+#[test]
+fn format_keeps_datum_commented_forms_in_place() {
+    let src =
+        "; syntax: surface\n; keywords: en\n\n(sort   Subject)\n#; (sort Unused)\n(sort  Role)\n";
+    assert_eq!(
+        format_source(src, FormatOptions::default()).expect("format"),
+        "; syntax: surface\n; keywords: en\n\n(sort Subject)\n#; (sort Unused)\n(sort Role)\n"
+    );
+
+    let inner = "; syntax: surface\n; keywords: en\n\n(relation admin :args   (Subject #; Role))\n";
+    assert_eq!(
+        format_range(inner, &cursor(inner, "admin"), FormatOptions::default()).expect("format"),
+        None
+    );
+}
+
+#[test]
+fn format_range_keeps_datum_commented_forms_verbatim() {
+    let src = "; syntax: surface\n; keywords: en\n\n#; (sort   Unused)\n(sort  Role)\n";
+    let edit = format_range(src, &make_span(src, 0, src.len()), FormatOptions::default())
+        .expect("format")
+        .expect("edit");
+    assert_eq!(&src[edit.span.start..edit.span.end], "(sort  Role)");
+    assert_eq!(edit.replacement, "(sort Role)");
+}
+
+#[test]
+fn format_range_keeps_text_between_selected_forms() {
+    let start = SURFACE.find("(sort").expect("sort");
+    let end = SURFACE.find("(fact").expect("fact");
+    let edit = format_range(
+        SURFACE,
+        &make_span(SURFACE, start, end),
+        FormatOptions::default(),
+    )
+    .expect("format")
+    .expect("edit");
+
+    assert_eq!(edit.span.start, start);
+    assert_eq!(
+        edit.replacement,
+        "(sort Subject)\n\n; who may administer\n(relation admin :args (Subject))"
+    );
+
+    let mut patched = SURFACE.to_string();
+    patched.replace_range(edit.span.start..edit.span.end, &edit.replacement);
+    assert_eq!(
+        format_range(
+            &patched,
+            &make_span(&patched, 0, patched.len()),
+            FormatOptions::default()
+        )
+        .expect("format"),
+        None
+    );
+    assert_eq!(
+        format_range(
+            SURFACE,
+            &make_span(SURFACE, 0, 10),
+            FormatOptions::default()
+        )
+        .expect("format"),
+        None
+    );
+}
+
+#[test]
+fn format_range_formats_the_whole_file_for_core_syntax() {
+    let src = "(sort Subject)\n(relation admin (Subject))\n(fact admin alice)\n";
+    let edit = format_range(src, &cursor(src, "(fact"), FormatOptions::default())
+        .expect("format")
+        .expect("edit");
+
+    assert_eq!((edit.span.start, edit.span.end), (0, src.len()));
+    assert_eq!(
+        edit.replacement,
+        format_source(src, FormatOptions::default()).expect("format")
+    );
+}
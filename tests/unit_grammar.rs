@@ -0,0 +1,104 @@
+use std::collections::HashSet;
+
+use dtl::grammar::{self, Dialect, FORMS};
+use dtl::{SurfaceKeywordMap, parse_program};
+
+fn referenced_rules(rhs: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut chars = rhs.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' | '\'' | '?' => {
+                for next in chars.by_ref() {
+                    if next == ch {
+                        break;
+                    }
+                }
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut name = c.to_string();
+                while let Some(next) = chars.peek().copied() {
+                    if !next.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    name.push(next);
+                    chars.next();
+                }
+                out.push(name);
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[test]
+fn grammar_productions_reference_only_defined_rules() {
+    let productions = grammar::productions();
+    let defined = productions
+        .iter()
+        .map(|production| production.name.clone())
+        .collect::<HashSet<_>>();
+    assert_eq!(defined.len(), productions.len(), "duplicate production");
+
+    for production in &productions {
+        for name in referenced_rules(&production.rhs) {
+            assert!(
+                defined.contains(&name),
+                "{} references undefined rule {name}",
+                production.name
+            );
+        }
+    }
+
+    let core = productions
+        .iter()
+        .filter(|production| production.dialect == Dialect::Core)
+        .count();
+    let surface = productions
+        .iter()
+        .filter(|production| production.dialect == Dialect::Surface)
+        .count();
+    let core_forms = FORMS.iter().filter(|form| form.core.is_some()).count();
+    assert_eq!(core, core_forms + 1);
+    assert_eq!(surface, FORMS.len() + 1);
+}
+
+#[test]
+fn grammar_table_defines_parser_keywords() {
+    for form in FORMS {
+        assert_eq!(grammar::surface_form(form.japanese), Some(form));
+        let mut keywords = SurfaceKeywordMap::new();
+        assert!(keywords.insert(form.head, form.japanese).is_err());
+        keywords
+            .insert(&format!("独自-{}", form.head), form.japanese)
+            .expect("every table head is a surface head");
+        for field in form.surface_tags {
+            for spelling in field.spellings {
+                assert!(grammar::is_surface_tag(spelling));
+                assert_eq!(form.tag(spelling), field.spellings);
+                assert!(keywords.insert(":独自", spelling).is_ok());
+            }
+        }
+    }
+    assert!(grammar::core_form("project").is_none());
+    assert!(grammar::core_form("defn").is_some());
+}
+
+#[test]
+fn every_tag_spelling_in_the_table_is_accepted_by_the_parser() {
+    let relation = grammar::surface_form("relation").expect("relation");
+    let defn = grammar::surface_form("defn").expect("defn");
+    for args in relation.tag(":引数") {
+        for params in defn.tag(":引数") {
+            for ret in defn.tag(":戻り") {
+                let src = format!(
+                    "; syntax: surface\n(sort S)\n(relation r {args} (S))\n(defn f {params} ((s S)) {ret} Bool :body (r s))\n"
+                );
+                let program = parse_program(&src).expect("surface spelling should parse");
+                assert_eq!(program.relations.len(), 1);
+                assert_eq!(program.defns.len(), 1);
+            }
+        }
+    }
+}
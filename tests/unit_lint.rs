@@ -0,0 +1,218 @@
+use dtl::{
+    ComplexityLimits, DupSampling, LintDiagnostic, LintOptions, lint_program, parse_program,
+};
+
+fn lint(src: &str) -> Vec<LintDiagnostic> {
+    let program = parse_program(src).expect("parse");
+    lint_program(&program, LintOptions::default())
+}
+
+fn codes(diags: &[LintDiagnostic], code: &str) -> Vec<String> {
+    diags
+        .iter()
+        .filter(|d| d.lint_code == code)
+        .map(|d| d.message.clone())
+        .collect()
+}
+
+#[test]
+fn lint_reports_duplicate_universe_values() {
+    let diags = lint(
+        r#"(sort Subject)
+(relation admin (Subject))
+(fact admin alice)
+(universe Subject (alice bob alice))
+(assert some-admin ((u Subject)) (admin u))
+"#,
+    );
+
+    let dups = codes(&diags, "L-UNIVERSE-DUP");
+    assert_eq!(dups.len(), 1, "{diags:?}");
+    assert!(dups[0].contains("alice"), "{dups:?}");
+    assert!(codes(&diags, "L-UNIVERSE-TYPE").is_empty());
+}
+
+#[test]
+fn lint_reports_universe_values_with_ill_typed_constructor_fields() {
+    let diags = lint(
+        r#"(data Role (admin-role) (guest-role Int))
+(defn weight ((r Role)) Int (match r ((admin-role) 10) ((guest-role n) n)))
+(universe Role ((admin-role) (guest-role 3) (guest-role alice)))
+"#,
+    );
+
+    let mismatches = codes(&diags, "L-UNIVERSE-TYPE");
+    assert_eq!(mismatches.len(), 1, "{diags:?}");
+    assert!(
+        mismatches[0].contains("(guest-role alice)"),
+        "{mismatches:?}"
+    );
+    assert!(mismatches[0].contains("Int"), "{mismatches:?}");
+}
+
+#[test]
+fn lint_reports_fact_and_rule_constants_outside_the_universe() {
+    let diags = lint(
+        r#"(sort Subject)
+(relation admin (Subject))
+(relation staff (Subject))
+(fact admin alice)
+(fact admin carol)
+(rule (staff ?u) (and (admin ?u) (not (admin dave))))
+(universe Subject (alice bob))
+(assert admins-are-staff ((u Subject)) (not (and (admin u) (not (staff u)))))
+"#,
+    );
+
+    let missing = codes(&diags, "L-UNIVERSE-MISSING");
+    assert_eq!(missing.len(), 2, "{diags:?}");
+    assert!(missing[0].contains("carol"), "{missing:?}");
+    assert!(missing[1].contains("dave"), "{missing:?}");
+}
+
+#[test]
+fn lint_reports_asserts_that_hold_only_because_a_relation_is_empty() {
+    let diags = lint(
+        r#"(sort Subject)
+(relation admin (Subject))
+(relation suspended (Subject))
+(relation staff (Subject))
+(relation can-login (Subject))
+(fact admin alice)
+(rule (staff ?u) (and (admin ?u) (suspended ?u)))
+(rule (can-login ?u) (admin ?u))
+(universe Subject (alice bob))
+(assert suspended-cannot-login ((u Subject)) (not (and (suspended u) (can-login u))))
+(assert staff-can-login ((u Subject)) (not (and (staff u) (not (can-login u)))))
+(assert admins-can-login ((u Subject)) (not (and (admin u) (not (can-login u)))))
+"#,
+    );
+
+    let vacuous = diags
+        .iter()
+        .filter(|d| d.lint_code == "L-VACUOUS-ASSERT")
+        .map(|d| (d.message.as_str(), d.confidence))
+        .collect::<Vec<_>>();
+    assert_eq!(vacuous.len(), 2, "{diags:?}");
+    assert_eq!(
+        vacuous[0].0,
+        "assert suspended-cannot-login は前提の relation suspended が空のため常に真です（前提が成り立たない）"
+    );
+    assert_eq!(vacuous[0].1, Some(0.95));
+    assert!(vacuous[1].0.contains("staff-can-login"));
+    assert_eq!(vacuous[1].1, Some(0.6));
+}
+
+#[test]
+fn lint_names_the_negated_relation_of_a_vacuous_assert_without_a_premise() {
+    let diags = lint(
+        r#"(sort Subject)
+(relation admin (Subject))
+(relation banned (Subject))
+(relation blocked (Subject))
+(fact admin alice)
+(rule (blocked ?u) (and (admin ?u) (banned ?u)))
+(universe Subject (alice bob))
+(assert nobody-banned ((u Subject)) (not (banned u)))
+(assert nobody-blocked ((u Subject)) (and (not (blocked u)) (not (banned u))))
+"#,
+    );
+
+    let vacuous = diags
+        .iter()
+        .filter(|d| d.lint_code == "L-VACUOUS-ASSERT")
+        .map(|d| (d.message.as_str(), d.confidence))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vacuous,
+        vec![
+            (
+                "assert nobody-banned は否定している relation banned が空のため常に真です（どの値も否定している relation に当てはまらない）",
+                Some(0.95)
+            ),
+            (
+                "assert nobody-blocked は否定している relation banned, blocked が空のため常に真です（どの値も否定している relation に当てはまらない）",
+                Some(0.6)
+            ),
+        ],
+        "{diags:?}"
+    );
+}
+
+#[test]
+fn lint_complexity_limits_apply_to_defn_bodies() {
+    let program = parse_program(
+        r#"(defn score ((n Int)) Int (let ((a (if true n 0))) (if true (if true a 1) 2)))
+"#,
+    )
+    .expect("parse");
+
+    let defaults = lint_program(&program, LintOptions::default());
+    assert!(defaults.iter().all(|d| d.category != "complexity"));
+
+    let strict = lint_program(
+        &program,
+        LintOptions {
+            complexity: ComplexityLimits {
+                max_expr_depth: 3,
+                max_defn_nodes: 8,
+                ..ComplexityLimits::default()
+            },
+            ..LintOptions::default()
+        },
+    );
+    let messages = codes(&strict, "L-COMPLEX-EXPR-DEPTH");
+    assert_eq!(messages.len(), 1, "{strict:?}");
+    assert!(messages[0].contains(": 4（上限 3）"), "{messages:?}");
+    let messages = codes(&strict, "L-COMPLEX-DEFN-SIZE");
+    assert!(
+        messages[0].contains(": 12 ノード（上限 8）"),
+        "{messages:?}"
+    );
+}
+
+#[test]
+fn semantic_dup_sampling_checks_a_seeded_subset_with_lower_confidence() {
+    let subjects = (0..12)
+        .map(|i| format!("s{i}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let src = format!(
+        r#"(sort Subject)
+(relation admin (Subject))
+(relation staff (Subject))
+(fact admin s0)
+(fact staff s1)
+(universe Subject ({subjects}))
+(assert left ((a Subject) (b Subject) (c Subject)) (not (and (admin a) (staff b) (admin c))))
+(assert right ((a Subject) (b Subject) (c Subject)) (not (and (admin a) (not (not (staff b))) (admin c))))
+"#
+    );
+    let program = parse_program(&src).expect("parse");
+    let candidates = |options: LintOptions| {
+        lint_program(&program, options)
+            .into_iter()
+            .filter(|d| d.lint_code == "L-DUP-MAYBE")
+            .map(|d| d.confidence.expect("confidence"))
+            .collect::<Vec<_>>()
+    };
+
+    let exhaustive = candidates(LintOptions {
+        semantic_dup: true,
+        ..LintOptions::default()
+    });
+    let sampled_options = LintOptions {
+        semantic_dup: true,
+        sampling: Some(DupSampling {
+            samples: 50,
+            seed: 7,
+        }),
+        ..LintOptions::default()
+    };
+    let sampled = candidates(sampled_options);
+
+    assert_eq!(exhaustive.len(), 1);
+    assert_eq!(sampled.len(), 1);
+    assert!(sampled[0] < exhaustive[0], "{sampled:?} vs {exhaustive:?}");
+    assert_eq!(candidates(sampled_options), sampled);
+}
@@ -1,4 +1,14 @@
-use dtl::{KnowledgeBase, parse_program, solve_facts};
+use std::num::NonZeroUsize;
+
+use dtl::logic_engine::{SKOLEM_CTOR_PREFIX, Value};
+use dtl::symbol::Symbol;
+use dtl::{
+    Access, FactCache, FixpointOptions, GroundFact, KnowledgeBase, KnowledgeBaseSnapshot,
+    PhaseTimings, StepKind, check_program_with_timings, content_hash, kb_fingerprint,
+    parse_program, plan_program, read_kb_snapshot, solve_facts, solve_facts_cached,
+    solve_facts_incremental, solve_facts_iter, solve_facts_with_derivations,
+    solve_facts_with_snapshot, write_kb_snapshot,
+};
 
 #[test]
 fn logic_derives_expected_facts() {
@@ -68,3 +78,407 @@ fn logic_uses_closed_world_assumption() {
 
     assert!(!derived.contains("p", &["unknown"]));
 }
+
+#[test]
+fn logic_incremental_solving_matches_solving_from_scratch() {
+    let extra = vec![GroundFact {
+        pred: "edge".to_string(),
+        terms: vec![Value::Symbol("c".into()), Value::Symbol("d".into())],
+    }];
+    for src in [
+        r#"
+        (sort N)
+        (relation edge (N N))
+        (relation path (N N))
+        (fact edge a b)
+        (fact edge b c)
+        (rule (path ?x ?y) (edge ?x ?y))
+        (rule (path ?x ?z) (and (path ?x ?y) (edge ?y ?z)))
+        "#,
+        r#"
+        (sort N)
+        (relation edge (N N))
+        (relation sink (N))
+        (relation has-out (N))
+        (fact edge a b)
+        (fact edge b c)
+        (rule (has-out ?x) (edge ?x ?y))
+        (rule (sink ?y) (and (edge ?x ?y) (not (has-out ?y))))
+        "#,
+    ] {
+        let program = parse_program(src).expect("parse should succeed");
+        let kb = KnowledgeBase::from_program(&program).expect("kb should build");
+        let base = solve_facts(&kb).expect("solve should succeed");
+        let incremental =
+            solve_facts_incremental(&kb, &base, extra.clone()).expect("solve should succeed");
+        let scratch =
+            solve_facts(&kb.with_extra_facts(extra.clone())).expect("solve should succeed");
+        for pred in ["edge", "path", "sink", "has-out"] {
+            assert_eq!(
+                incremental.relation_facts(pred),
+                scratch.relation_facts(pred),
+                "{pred}"
+            );
+        }
+    }
+}
+
+#[test]
+fn logic_snapshot_round_trips_and_detects_changed_rules() {
+    let src = r#"
+        (sort X)
+        (relation edge (X X))
+        (relation reach (X X))
+        (fact edge a b)
+        (fact edge b c)
+        (rule (reach ?x ?y) (edge ?x ?y))
+        (rule (reach ?x ?z) (and (edge ?x ?y) (reach ?y ?z)))
+    "#;
+    let program = parse_program(src).expect("parse should succeed");
+    let kb = KnowledgeBase::from_program(&program).expect("kb should build");
+    let derived = solve_facts(&kb).expect("solve should succeed");
+    let snapshot = KnowledgeBaseSnapshot::new(&kb, &derived);
+    assert_eq!(snapshot.fingerprint, kb_fingerprint(&kb));
+    assert_eq!(content_hash(b""), "cbf29ce484222325");
+    assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    for name in ["kb.json", "kb.json.zst"] {
+        let path = dir.path().join(name);
+        write_kb_snapshot(&path, &snapshot).expect("write snapshot");
+        let loaded = read_kb_snapshot(&path).expect("read snapshot");
+        assert_eq!(loaded, snapshot, "{name}");
+        assert!(loaded.matches(&kb));
+        assert!(loaded.matches(&loaded.knowledge_base()));
+        assert_eq!(
+            loaded.derived_facts().relation_facts("reach"),
+            derived.relation_facts("reach")
+        );
+    }
+
+    let reordered = src.replace(
+        "(fact edge a b)\n        (fact edge b c)",
+        "(fact edge b c)\n        (fact edge a b)",
+    );
+    let reordered_kb = KnowledgeBase::from_program(&parse_program(&reordered).expect("parse"))
+        .expect("kb should build");
+    assert!(snapshot.matches(&reordered_kb));
+
+    let mut tampered = snapshot.clone();
+    tampered.rules.pop();
+    assert!(!tampered.matches(&kb));
+    let mut tampered = snapshot.clone();
+    tampered.facts.pop_first();
+    assert!(!tampered.matches(&kb));
+
+    let changed = src.replace("(fact edge b c)", "(fact edge b d)");
+    let changed_kb = KnowledgeBase::from_program(&parse_program(&changed).expect("parse"))
+        .expect("kb should build");
+    assert!(!snapshot.matches(&changed_kb));
+    let warm = solve_facts_with_snapshot(&changed_kb, Some(&snapshot)).expect("solve");
+    assert!(warm.contains("reach", &["a", "d"]));
+    assert!(!warm.contains("reach", &["a", "c"]));
+}
+
+#[test]
+fn logic_snapshot_rejects_unknown_schema_version() {
+    let program = parse_program("(sort X)\n(relation p (X))\n(fact p a)").expect("parse");
+    let kb = KnowledgeBase::from_program(&program).expect("kb should build");
+    let mut snapshot = KnowledgeBaseSnapshot::new(&kb, &solve_facts(&kb).expect("solve"));
+    snapshot.schema_version = "0.0.1".to_string();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("kb.json");
+    write_kb_snapshot(&path, &snapshot).expect("write snapshot");
+    let err = read_kb_snapshot(&path).expect_err("old schema should be rejected");
+    assert_eq!(err.code, "E-IO");
+    assert!(
+        err.message
+            .contains("unsupported knowledge base snapshot schema 0.0.1")
+    );
+}
+
+#[test]
+fn logic_fact_cache_is_used_only_when_passed() {
+    let program = parse_program(
+        "(sort X)\n(relation edge (X X))\n(relation reach (X X))\n(fact edge a b)\n(rule (reach ?x ?y) (edge ?x ?y))",
+    )
+    .expect("parse");
+    let kb = KnowledgeBase::from_program(&program).expect("kb should build");
+    let dir = tempfile::tempdir().expect("tempdir");
+    let cache = FactCache::new(dir.path());
+
+    check_program_with_timings(
+        &program,
+        FixpointOptions::default(),
+        &mut PhaseTimings::new(),
+    )
+    .expect("check");
+    assert!(!cache.entry_path(&kb).exists());
+
+    let fixpoint = FixpointOptions {
+        cache: Some(&cache),
+        ..FixpointOptions::default()
+    };
+    check_program_with_timings(&program, fixpoint, &mut PhaseTimings::new()).expect("check");
+    assert!(cache.entry_path(&kb).exists());
+    let cached = solve_facts_cached(&kb, fixpoint).expect("cached solve");
+    assert_eq!(cached.facts, solve_facts(&kb).expect("solve").facts);
+
+    let mut forged = read_kb_snapshot(&cache.entry_path(&kb)).expect("read entry");
+    forged.facts.clear();
+    forged.derived.clear();
+    write_kb_snapshot(&cache.entry_path(&kb), &forged).expect("write entry");
+    let cached = solve_facts_cached(&kb, fixpoint).expect("cached solve");
+    assert!(cached.contains("reach", &["a", "b"]));
+}
+
+#[test]
+fn logic_iterator_streams_derivations_and_stops_early() {
+    let src = r#"
+        (sort X)
+        (relation edge (X X))
+        (relation reach (X X))
+        (relation isolated (X))
+        (fact edge a b)
+        (fact edge b c)
+        (fact edge c d)
+        (fact edge d d)
+        (rule (reach ?x ?y) (edge ?x ?y))
+        (rule (reach ?x ?z) (and (edge ?x ?y) (reach ?y ?z)))
+        (rule (isolated ?x) (and (edge ?x ?x) (not (reach ?x a))))
+    "#;
+    let program = parse_program(src).expect("parse should succeed");
+    let kb = KnowledgeBase::from_program(&program).expect("kb should build");
+
+    let iter = solve_facts_iter(&kb).expect("iterator should start");
+    assert_eq!(iter.stratum_count(), 2);
+    let streamed = iter
+        .collect::<Result<Vec<_>, _>>()
+        .expect("solve should succeed");
+    let (derived, steps) = solve_facts_with_derivations(&kb).expect("solve should succeed");
+    let keys = streamed
+        .iter()
+        .map(|item| (item.stratum, item.iteration, item.rule))
+        .collect::<Vec<_>>();
+    assert!(keys.is_sorted(), "{keys:?}");
+    assert_eq!(streamed.len(), steps.len());
+    assert_eq!(
+        streamed.iter().map(|item| item.stratum).max(),
+        Some(1),
+        "the negated rule runs in a later stratum"
+    );
+    for item in &streamed {
+        assert!(derived.facts[&item.fact.pred].contains(&item.fact.terms));
+    }
+
+    let goal = GroundFact {
+        pred: "reach".to_string(),
+        terms: vec![Value::Symbol("a".into()), Value::Symbol("d".into())],
+    };
+    let mut iter = solve_facts_iter(&kb).expect("iterator should start");
+    let found = iter
+        .by_ref()
+        .map(|item| item.expect("solve should succeed"))
+        .find(|item| item.fact == goal)
+        .expect("goal is derivable");
+    assert_eq!((found.stratum, found.iteration), (0, 2));
+    let partial = iter.derived();
+    assert!(partial.contains("reach", &["a", "d"]));
+    assert!(partial.relation_facts("isolated").is_empty());
+    assert_eq!(
+        solve_facts(&kb).expect("solve").relation_facts("isolated"),
+        derived.relation_facts("isolated")
+    );
+}
+
+#[test]
+fn logic_skolem_rules_invent_witnesses_up_to_the_depth_bound() {
+    let src = r#"
+        (sort N)
+        (relation node (N))
+        (relation next (N N))
+        (fact node zero)
+        (rule (next ?x ?y) (node ?x) :skolem 3)
+        (rule (node ?y) (next ?x ?y))
+    "#;
+    let program = parse_program(src).expect("parse should succeed");
+    let kb = KnowledgeBase::from_program(&program).expect("kb should build");
+    let derived = solve_facts(&kb).expect("solve should terminate");
+
+    let witness = |of: Value| Value::Adt {
+        ctor: format!("{SKOLEM_CTOR_PREFIX}next:y").into(),
+        fields: vec![of],
+    };
+    let one = witness(Value::Symbol("zero".into()));
+    let two = witness(one.clone());
+    let three = witness(two.clone());
+    assert_eq!(
+        derived.relation_facts("next").len(),
+        3,
+        "terms deeper than 3 are never built"
+    );
+    assert!(derived.facts["next"].contains(&vec![two.clone(), three.clone()]));
+    assert!(!derived.facts["node"].contains(&vec![witness(three)]));
+
+    let reference = dtl::reference::solve_facts(&program).expect("reference solve");
+    for pred in ["node", "next"] {
+        assert_eq!(reference.facts[pred].len(), derived.facts[pred].len());
+    }
+    dtl::reference::check_derivation_parity(&program).expect("engines should agree");
+}
+
+#[test]
+fn logic_disequality_filters_bound_variables() {
+    let src = r#"
+        (sort Subject)
+        (sort Team)
+        (relation member (Subject Team))
+        (relation peer (Subject Subject))
+        (relation self (Subject))
+        (fact member alice red)
+        (fact member bob red)
+        (rule (peer ?x ?y) (and (member ?x ?t) (!= ?x ?y) (member ?y ?t)))
+        (rule (self ?x) (and (member ?x ?t) (member ?y ?t) (not (!= ?x ?y))))
+    "#;
+    let program = parse_program(src).expect("parse should succeed");
+    let kb = KnowledgeBase::from_program(&program).expect("kb should build");
+    let derived = solve_facts(&kb).expect("solve should succeed");
+    let pair = |a: &str, b: &str| vec![a.to_string(), b.to_string()];
+    assert_eq!(
+        derived.relation_facts("peer"),
+        [pair("alice", "bob"), pair("bob", "alice")].into()
+    );
+    assert_eq!(derived.relation_facts("self").len(), 2);
+    assert!(!derived.facts.contains_key("!="));
+    dtl::reference::check_derivation_parity(&program).expect("engines should agree");
+}
+
+#[test]
+fn logic_plan_follows_engine_order_and_propagates_estimates() {
+    let src = r#"
+        (sort Person)
+        (relation parent (Person Person))
+        (relation ancestor (Person Person))
+        (relation blocked (Person))
+        (relation visible (Person Person))
+        (fact parent alice bob)
+        (fact parent bob carol)
+        (fact parent carol dave)
+        (fact parent dave erin)
+        (fact blocked erin)
+        (rule (ancestor ?x ?y) (parent ?x ?y))
+        (rule (ancestor ?x ?z) (and (parent ?x ?y) (ancestor ?y ?z)))
+        (rule (visible ?x ?y) (and (not (blocked ?y)) (!= ?x ?y) (ancestor ?x ?y)))
+    "#;
+    let program = parse_program(src).expect("parse should succeed");
+    let plan = plan_program(&program, NonZeroUsize::MIN).expect("plan should build");
+    assert_eq!(plan.strata.len(), 2);
+
+    let base = &plan.strata[0];
+    assert!(base.recursive);
+    assert_eq!(base.rules[0].estimated_rows, 4);
+    let join = &base.rules[1];
+    assert_eq!(join.steps[1].relation, "ancestor");
+    assert_eq!(join.steps[1].access, Access::FullScan);
+    assert_eq!(join.steps[1].bound_columns, vec![0]);
+    assert_eq!(join.steps[1].join_vars, vec!["y".to_string()]);
+    assert_eq!(join.steps[1].binds, vec!["z".to_string()]);
+    assert_eq!(join.steps[1].output_rows, 8);
+    assert_eq!(join.estimated_cost, 4 + 4 * 4);
+
+    let top = &plan.strata[1];
+    assert!(!top.recursive);
+    let kinds = top.rules[0]
+        .steps
+        .iter()
+        .map(|step| step.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [StepKind::Scan, StepKind::Filter, StepKind::AntiJoin]
+    );
+    assert_eq!(top.rules[0].steps[0].relation_rows, 12);
+    assert_eq!(top.rules[0].steps[2].access, Access::TupleLookup);
+    assert_eq!(top.rules[0].rule, 2);
+}
+
+#[test]
+fn logic_plan_does_not_mark_rules_over_facts_recursive() {
+    let src = r#"
+        (sort Person)
+        (relation a (Person))
+        (relation c (Person))
+        (fact a alice)
+        (rule (c ?x) (a ?x))
+    "#;
+    let program = parse_program(src).expect("parse should succeed");
+    let plan = plan_program(&program, NonZeroUsize::MIN).expect("plan should build");
+    assert_eq!(plan.strata.len(), 1);
+    assert_eq!(plan.strata[0].relations, ["a", "c"]);
+    assert!(!plan.strata[0].recursive);
+}
+
+#[test]
+fn logic_parallel_fixpoint_matches_single_worker() {
+    let mut src = String::from(
+        r#"
+        (sort Node)
+        (relation edge (Node Node))
+        (relation hub (Node))
+        (relation two-hop (Node Node))
+        (relation leaf (Node))
+        (fact hub n0)
+        (fact hub n7)
+        (rule (two-hop ?x ?z) (and (edge ?x ?y) (edge ?y ?z) (!= ?x ?z)))
+        (rule (leaf ?x) (and (edge ?x ?y) (not (hub ?y))))
+    "#,
+    );
+    for i in 0..1200 {
+        src.push_str(&format!("(fact edge n{} n{})\n", i % 97, (i * 7) % 89));
+    }
+    let program = parse_program(&src).expect("parse should succeed");
+    let kb = KnowledgeBase::from_program(&program).expect("kb should build");
+
+    let (serial, serial_steps) = solve_facts_with_derivations(&kb).expect("serial solve");
+    let kb = kb.with_workers(NonZeroUsize::new(4).expect("non-zero"));
+    let (parallel, parallel_steps) = solve_facts_with_derivations(&kb).expect("parallel solve");
+
+    let four = NonZeroUsize::new(4).expect("non-zero");
+    let partitions = |workers| {
+        plan_program(&program, workers)
+            .expect("plan should build")
+            .strata[0]
+            .rules[0]
+            .partitions
+    };
+    assert_eq!(partitions(NonZeroUsize::MIN), 1);
+    assert_eq!(partitions(four), 4);
+
+    assert!(!serial.relation_facts("two-hop").is_empty());
+    assert_eq!(serial.facts, parallel.facts);
+    assert_eq!(serial_steps, parallel_steps);
+}
+
+#[test]
+fn logic_symbols_are_interned_but_ordered_and_serialized_by_name() {
+    let bob = Symbol::intern("bob");
+    assert_eq!(bob, Symbol::from(String::from("bob")));
+    assert!(std::ptr::eq(bob.as_str(), Symbol::intern("bob").as_str()));
+    assert!(Symbol::intern("alice") < bob);
+    assert_eq!(bob, *"bob");
+
+    let tuple = vec![Value::Symbol(bob), Value::Int(3)];
+    let json = serde_json::to_string(&tuple).expect("serialize");
+    assert_eq!(json, r#"[{"Symbol":"bob"},{"Int":3}]"#);
+    let back: Vec<Value> = serde_json::from_str(&json).expect("deserialize");
+    assert_eq!(back, tuple);
+
+    let leaf = Value::Adt {
+        ctor: Symbol::intern("leaf"),
+        fields: Vec::new(),
+    };
+    let json = serde_json::to_string(&leaf).expect("serialize");
+    assert_eq!(json, r#"{"Adt":{"ctor":"leaf","fields":[]}}"#);
+    let back: Value = serde_json::from_str(&json).expect("deserialize");
+    assert_eq!(back, leaf);
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
 
 use dtl::ast::Rule;
 use dtl::logic_engine::{GroundFact, KnowledgeBase, Value, solve_facts};
@@ -10,10 +11,11 @@ fn solve_facts_rejects_undefined_fact_relation() {
         relation_schemas: HashMap::new(),
         facts: vec![GroundFact {
             pred: "p".to_string(),
-            terms: vec![Value::Symbol("a".to_string())],
+            terms: vec![Value::Symbol("a".into())],
         }],
         rules: vec![],
         strata: HashMap::new(),
+        workers: NonZeroUsize::MIN,
     };
 
     let errs = solve_facts(&kb).expect_err("solve should fail");
@@ -34,6 +36,8 @@ fn solve_facts_rejects_unbound_head_variable() {
             terms: vec![LogicTerm::Var("x".to_string())],
         },
         body: Formula::True,
+        skolem: None,
+        profile: None,
         span: dtl::Span {
             start: 0,
             end: 0,
@@ -51,6 +55,7 @@ fn solve_facts_rejects_unbound_head_variable() {
         facts: vec![],
         rules: vec![rule],
         strata,
+        workers: NonZeroUsize::MIN,
     };
 
     let errs = solve_facts(&kb).expect_err("solve should fail");
@@ -0,0 +1,260 @@
+use dtl::{
+    FormatOptions, SurfaceKeywords, check_program, expand_macros, format_source, parse_program,
+    render_program,
+};
+
+fn rendered(src: &str) -> String {
+    render_program(
+        &parse_program(src).expect("parse"),
+        SurfaceKeywords::English,
+    )
+}
+
+fn parse_error(src: &str) -> (String, &'static str, usize, usize) {
+    let errors = parse_program(src).expect_err("parse should fail");
+    let diag = &errors[0];
+    let span = diag.span.as_ref().expect("span");
+    (diag.message.clone(), diag.code, span.line, span.column)
+}
+
+#[test]
+fn macros_expand_to_the_forms_they_stand_for() {
+    let with_macros = r#"
+(defmacro grant (rel who ...)
+  (fact rel who) ...)
+(defmacro implies (a b) (not (and a (not b))))
+(defmacro derive-all (head src) (rule (head ?x) (src ?x)))
+(data Subject (alice) (bob))
+(relation admin (Subject))
+(relation allowed (Subject))
+(grant admin (alice) (bob))
+(derive-all allowed admin)
+(universe Subject ((alice) (bob)))
+(when-profile production (grant allowed (alice)))
+(assert admins-allowed ((u Subject)) (implies (admin u) (allowed u)))
+"#;
+    let by_hand = r#"
+(data Subject (alice) (bob))
+(relation admin (Subject))
+(relation allowed (Subject))
+(fact admin (alice))
+(fact admin (bob))
+(rule (allowed ?x) (admin ?x))
+(universe Subject ((alice) (bob)))
+(when-profile production (fact allowed (alice)))
+(assert admins-allowed ((u Subject)) (not (and (admin u) (not (allowed u)))))
+"#;
+    assert_eq!(rendered(with_macros), rendered(by_hand));
+
+    let surface = r#"
+; syntax: surface
+(マクロ role (rel who) (事実 rel :項 (who)))
+(型 Subject)
+(関係 admin :引数 (Subject))
+(role admin alice)
+"#;
+    let program = parse_program(surface).expect("parse surface");
+    assert_eq!(program.facts.len(), 1);
+    assert_eq!(program.facts[0].name, "admin");
+}
+
+#[test]
+fn macro_expansion_keeps_use_site_spans_and_renames_captured_binders() {
+    let src = r#"(sort Node)
+(relation e (Node Node))
+(relation r1 (Node))
+(defmacro guarded (head cond) (rule (head ?x) (and cond (e ?x ?z))))
+(guarded r1 (e ?z a))
+"#;
+    let program = parse_program(src).expect("parse");
+    let rule = &program.rules[0];
+    assert_eq!((rule.span.line, rule.span.column), (5, 1));
+    assert_eq!(
+        rendered(src),
+        rendered(
+            r#"(sort Node)
+(relation e (Node Node))
+(relation r1 (Node))
+(rule (r1 ?x) (and (e ?z a) (e ?x ?z%1)))
+"#
+        )
+    );
+
+    let expansion = expand_macros(src).expect("expand");
+    assert_eq!(expansion.steps.len(), 1);
+    let step = &expansion.steps[0];
+    assert_eq!(step.macro_name, "guarded");
+    assert_eq!((step.span.line, step.span.column), (5, 1));
+    assert_eq!(step.call, "(guarded r1 (e ?z a))");
+    assert_eq!(
+        step.expansion,
+        vec!["(rule (r1 ?x) (and (e ?z a) (e ?x ?z%1)))".to_string()]
+    );
+    assert_eq!(expansion.forms.len(), 4);
+
+    let src = r#"(defmacro with-flag (f body) (let ((x f)) body))
+(defn keep ((x Bool)) Bool (with-flag true x))
+(defn flag ((y Bool)) Bool (with-flag y x))
+"#;
+    let expansion = expand_macros(src).expect("expand");
+    assert_eq!(
+        expansion.forms,
+        vec![
+            "(defn keep ((x Bool)) Bool (let ((x%1 true)) x))".to_string(),
+            "(defn flag ((y Bool)) Bool (let ((x%2 y)) x))".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn nested_macro_calls_are_traced_outermost_first() {
+    let src = r#"(defmacro implies (a b) (not (and a (not b))))
+(defmacro iff (a b) (and (implies a b) (implies b a)))
+(sort S)
+(relation p (S))
+(relation q (S))
+(assert same ((s S)) (iff (p s) (q s)))
+"#;
+    let expansion = expand_macros(src).expect("expand");
+    let names = expansion
+        .steps
+        .iter()
+        .map(|step| step.macro_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["iff", "implies", "implies"]);
+    assert!(
+        expansion
+            .steps
+            .iter()
+            .all(|step| (step.span.line, step.span.column) == (6, 22))
+    );
+    assert_eq!(
+        expansion.forms[3],
+        "(assert same ((s S)) (and (not (and (p s) (not (q s)))) (not (and (q s) (not (p s))))))"
+    );
+}
+
+#[test]
+fn macro_errors_point_at_the_definition_or_the_call() {
+    assert_eq!(
+        parse_error("(defmacro pair (a b) (fact r a b))\n(pair x)"),
+        (
+            "macro pair expects 2 arguments, got 1".to_string(),
+            "E-PARSE",
+            2,
+            1
+        )
+    );
+    assert_eq!(
+        parse_error("(defmacro facts (r x ...) (fact r x) ...)\n(facts)"),
+        (
+            "macro facts expects at least 1 arguments, got 0".to_string(),
+            "E-PARSE",
+            2,
+            1
+        )
+    );
+    assert_eq!(
+        parse_error("(defmacro two (r) (fact r a) (fact r b))\n(assert t () (two p))"),
+        (
+            "macro two expands to 2 forms where one is expected".to_string(),
+            "E-PARSE",
+            2,
+            14
+        )
+    );
+    assert_eq!(
+        parse_error("(defmacro loop (x) (loop x))\n(loop a)"),
+        (
+            "expansion of macro loop nests deeper than 64 levels".to_string(),
+            "E-PARSE",
+            2,
+            1
+        )
+    );
+    assert_eq!(
+        parse_error("(defmacro fact (x) x)"),
+        ("macro name is reserved: fact".to_string(), "E-PARSE", 1, 11)
+    );
+    assert_eq!(
+        parse_error("(defmacro m (x ... y) x)"),
+        (
+            "'...' must follow the last macro parameter".to_string(),
+            "E-PARSE",
+            1,
+            16
+        )
+    );
+    assert_eq!(
+        parse_error("(defmacro m (r x ...) (fact r x))\n(m p a)"),
+        (
+            "macro parameter x must be followed by '...'".to_string(),
+            "E-PARSE",
+            1,
+            31
+        )
+    );
+    assert_eq!(
+        parse_error("(defmacro m (r) (fact r a) ...)\n(m p)"),
+        (
+            "'...' in a macro template must follow a template using the rest parameter".to_string(),
+            "E-PARSE",
+            1,
+            28
+        )
+    );
+    assert_eq!(
+        parse_error("(defmacro m () (defmacro n () (sort S)))\n(m)"),
+        (
+            "a macro expansion cannot define a macro".to_string(),
+            "E-PARSE",
+            2,
+            1
+        )
+    );
+    assert_eq!(
+        parse_error("(defmacro s () (sort S))\n(when-profile p (defmacro m () (sort T)))"),
+        (
+            "defmacro is only allowed at the top level".to_string(),
+            "E-PARSE",
+            2,
+            17
+        )
+    );
+
+    let errors = parse_program("(defmacro m () (sort S))\n(defmacro m () (sort T))")
+        .expect_err("duplicate macro");
+    assert_eq!(errors[0].code, "E-RESOLVE");
+    assert_eq!(errors[0].message, "duplicate macro: m");
+    assert_eq!(errors[0].related[0].label, "first defined here");
+}
+
+#[test]
+fn an_argument_substituted_twice_is_reported_once() {
+    let errors =
+        parse_program("(defmacro both (e) (defn f () Int e) (defn g () Int e))\n(both (let x))")
+            .expect_err("malformed argument");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code, "E-PARSE");
+
+    let program =
+        parse_program("(defmacro twice (e) (defn f ((n Int)) Int (+ e e)))\n(twice (+ n true))")
+            .expect("parse");
+    let errors = check_program(&program).expect_err("ill-typed argument");
+    assert_eq!(
+        errors
+            .iter()
+            .map(|diag| (diag.code, diag.span.as_ref().map(|span| span.column)))
+            .collect::<Vec<_>>(),
+        vec![("E-TYPE", Some(11)), ("E-TYPE", Some(13))]
+    );
+}
+
+#[test]
+fn formatter_keeps_files_that_define_macros_as_written() {
+    let src = "(defmacro role (r who) (fact r who))\n(sort S)\n(relation admin (S))\n(role admin alice)\n";
+    assert_eq!(
+        format_source(src, FormatOptions::default()).expect("format"),
+        src
+    );
+}
@@ -0,0 +1,51 @@
+use dtl::{Symptom, minimize_program, parse_program};
+
+const SRC: &str = r#"
+(sort Subject)
+(sort Resource)
+(data Action (read) (write))
+(relation allowed (Subject Resource Action))
+(relation admin (Subject))
+(relation audited (Resource))
+(fact admin alice)
+(fact audited doc1)
+(rule (allowed ?u ?r (read)) (and (admin ?u) (audited ?r)))
+(universe Subject (alice bob))
+(universe Resource (doc1))
+(assert admins-read ((u Subject) (r Resource))
+  (not (and (admin u) (not (allowed u r (read))))))
+(assert everyone-audited ((u Subject)) (admin u))
+(defn can-read ((u Subject) (r Resource)) Bool (allowed u r (read)))
+"#;
+
+#[test]
+fn minimizer_keeps_a_failing_obligation_with_few_declarations() {
+    let program = parse_program(SRC).expect("parse");
+    let report = minimize_program(&program, &Symptom::ProofFailure).expect("minimize");
+    assert!(Symptom::ProofFailure.reproduces(&report.program));
+    assert_eq!(report.original_declarations, 14);
+    assert!(report.remaining_declarations < 6, "{report:?}");
+    assert_eq!(report.program.asserts.len(), 1);
+    assert_eq!(report.program.asserts[0].name, "everyone-audited");
+    assert!(report.program.defns.is_empty());
+    assert!(report.program.rules.is_empty());
+}
+
+#[test]
+fn minimizer_preserves_a_diagnostic_code() {
+    let src = format!("{SRC}\n(fact missing-relation alice)\n");
+    let program = parse_program(&src).expect("parse");
+    let symptom = Symptom::Diagnostic("E-RESOLVE".to_string());
+    let report = minimize_program(&program, &symptom).expect("minimize");
+    assert_eq!(report.original_declarations, 15);
+    assert_eq!(report.remaining_declarations, 1);
+    assert!(symptom.reproduces(&report.program));
+}
+
+#[test]
+fn minimizer_rejects_inputs_without_the_symptom() {
+    let program = parse_program(SRC).expect("parse");
+    let err = minimize_program(&program, &Symptom::Diagnostic("E-TOTAL".to_string()))
+        .expect_err("no E-TOTAL in input");
+    assert_eq!(err.code, "E-MINIMIZE");
+}
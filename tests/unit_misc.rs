@@ -1,6 +1,8 @@
 use dtl::ast::Program;
-use dtl::diagnostics::{Diagnostic, line_col, make_span};
+use dtl::diagnostics::{Diagnostic, LineIndex, closest_name, line_col, make_span};
+use dtl::exit_code::{FailureClass, failure_class, failure_exit_code};
 use dtl::parse_program_with_source;
+use dtl::timings::PhaseStats;
 use dtl::types::{Formula, LogicTerm, Type};
 
 #[test]
@@ -21,6 +23,20 @@ fn diagnostics_line_col_counts_unicode_scalars() {
     assert_eq!((line, col), (1, 3));
 }
 
+#[test]
+fn diagnostics_line_index_matches_line_col() {
+    let src = "(sort 主体)\n\n  (関係 p (主体))\r\nx\n";
+    let index = LineIndex::new(src);
+    for offset in 0..=src.len() + 2 {
+        assert_eq!(
+            index.line_col(src, offset),
+            line_col(src, offset),
+            "offset={offset}"
+        );
+    }
+    assert_eq!(index.span(src, 3, 5), make_span(src, 3, 5));
+}
+
 #[test]
 fn diagnostics_hint_is_attached_for_known_code() {
     let d = Diagnostic::new("E-TYPE", "msg", None);
@@ -28,6 +44,36 @@ fn diagnostics_hint_is_attached_for_known_code() {
     assert!(d.to_string().contains("hint:"));
 }
 
+#[test]
+fn diagnostics_closest_name_prefers_smallest_edit_distance() {
+    let candidates = ["allowed", "audited", "blocked", "契約可能"];
+    assert_eq!(
+        closest_name("alowed", candidates),
+        Some("allowed".to_string())
+    );
+    assert_eq!(
+        closest_name("契約可", candidates),
+        Some("契約可能".to_string())
+    );
+    assert_eq!(closest_name("zzz", candidates), None);
+    assert_eq!(closest_name("allowed", candidates), None);
+
+    let short = ["x", "y", "ab", "Q"];
+    assert_eq!(closest_name("z", short), None);
+    assert_eq!(closest_name("ac", short), None);
+    assert_eq!(closest_name("q", short), Some("Q".to_string()));
+    assert_eq!(closest_name("AB", short), Some("ab".to_string()));
+}
+
+#[test]
+fn diagnostics_suggestion_is_prepended_to_hint() {
+    let d = Diagnostic::new("E-RESOLVE", "msg", None).with_suggestion(Some("allowed".to_string()));
+    assert_eq!(d.suggestion(), Some("allowed"));
+    let hint = d.hint().expect("hint");
+    assert!(hint.starts_with("`allowed` のことですか？"));
+    assert!(d.to_string().contains("`allowed` のことですか？"));
+}
+
 #[test]
 fn diagnostics_can_hold_source_path() {
     let d = Diagnostic::new("E-IO", "msg", None).with_source("foo/bar.dtl");
@@ -35,6 +81,51 @@ fn diagnostics_can_hold_source_path() {
     assert!(d.to_string().contains("foo/bar.dtl: E-IO: msg"));
 }
 
+#[test]
+fn diagnostics_render_related_spans() {
+    let src = "(sort A)\n(sort A)\n";
+    let first = make_span(src, 1, 5);
+    let d = Diagnostic::new(
+        "E-RESOLVE",
+        "duplicate sort: A",
+        Some(make_span(src, 10, 14)),
+    )
+    .with_related(first.clone(), "first defined here");
+    assert_eq!(d.related().len(), 1);
+    assert_eq!(d.related()[0].span, first);
+    assert!(
+        d.to_string()
+            .contains("duplicate sort: A at 2:2 (first defined here at 1:2)")
+    );
+
+    let mut other_file = first;
+    other_file.file_id = Some("schema.dtl".to_string());
+    let d = Diagnostic::new("E-RESOLVE", "duplicate sort: A", None)
+        .with_source("policy.dtl")
+        .with_related(other_file, "first defined here");
+    assert!(
+        d.to_string()
+            .contains("(first defined here at schema.dtl:1:2)")
+    );
+}
+
+#[test]
+fn diagnostics_turn_suggestions_into_fixes() {
+    let src = "(alowed x)";
+    let name = make_span(src, 1, 7);
+    let d = Diagnostic::new("E-RESOLVE", "unknown relation", None)
+        .with_suggestion(Some("allowed".to_string()))
+        .with_suggestion_fix(&name);
+    assert_eq!(d.fixes().len(), 1);
+    assert_eq!(d.fixes()[0].span, name);
+    assert_eq!(d.fixes()[0].replacement, "allowed");
+
+    let d = Diagnostic::new("E-RESOLVE", "unknown relation", None)
+        .with_suggestion(None)
+        .with_suggestion_fix(&name);
+    assert!(d.fixes().is_empty());
+}
+
 #[test]
 fn ast_program_default_and_new() {
     let p = Program::new();
@@ -67,3 +158,44 @@ fn parse_program_with_source_sets_span_file_id() {
         Some("fixtures/schema.dtl")
     );
 }
+
+#[test]
+fn failure_class_uses_earliest_pipeline_stage() {
+    assert_eq!(FailureClass::of_code("E-PARSE").exit_code(), 1);
+    assert_eq!(FailureClass::of_code("E-TOTAL").exit_code(), 2);
+    assert_eq!(FailureClass::of_code("E-KEY").exit_code(), 2);
+    assert_eq!(FailureClass::of_code("E-PROVE").exit_code(), 3);
+    assert_eq!(FailureClass::of_code("E-SELFDOC-REF").exit_code(), 4);
+    assert_eq!(FailureClass::of_code("E-EVAL").exit_code(), 6);
+    assert_eq!(FailureClass::of_code("E-UNKNOWN"), FailureClass::Type);
+
+    let diags = vec![
+        Diagnostic::new("E-TYPE", "type mismatch", None),
+        Diagnostic::new("E-PARSE", "unexpected token", None),
+    ];
+    assert_eq!(failure_class(&diags), Some(FailureClass::Parse));
+    assert_eq!(failure_exit_code(&diags), 1);
+    assert_eq!(failure_class(&[]), None);
+
+    let exit_codes = FailureClass::ALL
+        .iter()
+        .map(|class| class.exit_code())
+        .collect::<Vec<_>>();
+    assert_eq!(exit_codes, vec![1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn phase_stats_report_mean_and_nearest_rank_p95() {
+    let samples = (1..=20)
+        .map(std::time::Duration::from_millis)
+        .collect::<Vec<_>>();
+    let stats = PhaseStats::from_samples("solve", &samples).expect("stats");
+    assert_eq!(stats.samples, 20);
+    assert_eq!(stats.mean_micros, 10_500);
+    assert_eq!(stats.p95_micros, 19_000);
+    assert_eq!((stats.min_micros, stats.max_micros), (1_000, 20_000));
+
+    let single = PhaseStats::from_samples("parse", &samples[..1]).expect("stats");
+    assert_eq!(single.p95_micros, 1_000);
+    assert!(PhaseStats::from_samples("prove", &[]).is_none());
+}
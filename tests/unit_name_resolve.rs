@@ -41,3 +41,39 @@ fn resolve_rejects_unknown_type_in_defn() {
     let errors = check_program(&program).expect_err("resolve should fail");
     assert!(errors.iter().any(|d| d.message.contains("unknown type")));
 }
+
+#[test]
+fn resolve_suggests_closest_names_for_typos() {
+    let src = r#"
+        (sort Subject)
+        (data Action (read) (write))
+        (relation allowed (Subject Action))
+        (fact alowed alice (read))
+        (defn can ((u Subjct)) Bool (allowd u (reed)))
+    "#;
+
+    let program = parse_program(src).expect("parse should succeed");
+    let errors = check_program(&program).expect_err("resolve should fail");
+    let suggestion_for = |needle: &str| {
+        errors
+            .iter()
+            .find(|d| d.code == "E-RESOLVE" && d.message.contains(needle))
+            .and_then(|d| d.suggestion().map(str::to_string))
+    };
+    assert_eq!(
+        suggestion_for("undefined relation in fact: alowed"),
+        Some("allowed".to_string())
+    );
+    assert_eq!(
+        suggestion_for("unknown type: Subjct"),
+        Some("Subject".to_string())
+    );
+    assert_eq!(
+        suggestion_for("unknown function/relation/constructor: allowd"),
+        Some("allowed".to_string())
+    );
+    assert_eq!(
+        suggestion_for("unknown function/relation/constructor: reed"),
+        Some("read".to_string())
+    );
+}
@@ -60,6 +60,14 @@ fn resolve_rejects_unsafe_negated_variable() {
     );
 }
 
+#[test]
+fn resolve_rejects_unbound_disequality_variable() {
+    expect_resolve_error(
+        "(sort A) (relation p (A)) (relation seed (A)) (fact seed a) (rule (p ?x) (and (seed ?x) (!= ?x ?y)))",
+        "unsafe rule: != variable ?y is not bound in positive body",
+    );
+}
+
 #[test]
 fn resolve_rejects_duplicate_function() {
     expect_resolve_error(
@@ -99,3 +107,27 @@ fn resolve_rejects_shadowed_let_binding() {
         "duplicate or shadowed let binding",
     );
 }
+
+#[test]
+fn resolve_rejects_skolem_rule_without_unbound_head_variable() {
+    expect_resolve_error(
+        "(sort A) (relation p (A)) (relation q (A)) (rule (p ?x) (q ?x) :skolem 1)",
+        "declares :skolem but binds every head variable",
+    );
+}
+
+#[test]
+fn resolve_reserves_builtin_operators_and_checks_comparison_operands() {
+    expect_resolve_error(
+        "(sort A) (relation < (A A))",
+        "< is a builtin operator and cannot be declared as a relation",
+    );
+    expect_resolve_error(
+        "(defn + ((x Int) (y Int)) Int x)",
+        "+ is a builtin operator and cannot be declared as a function",
+    );
+    expect_resolve_error(
+        "(defn f ((x Int)) (Refine n Int (>= n zero)) x)",
+        "comparison >= expects two Int literals or variables",
+    );
+}
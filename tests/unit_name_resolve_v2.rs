@@ -1,4 +1,6 @@
-use dtl::{check_program, parse_program};
+use dtl::name_resolve::normalize_program_aliases;
+use dtl::types::LogicTerm;
+use dtl::{check_program, parse_program, parse_project_config};
 
 fn expect_error(src: &str, code: &str, needle: &str) {
     let program = parse_program(src).expect("parse should succeed");
@@ -18,6 +20,40 @@ fn resolve_accepts_recursive_data() {
     assert_eq!(report.errors, 0);
 }
 
+#[test]
+fn resolve_accepts_mutually_recursive_data() {
+    let src = r#"
+        (data Stmt (skip) (eval Expr) (seq Stmt Stmt))
+        (data Expr (lit Int) (block Stmt))
+        (universe Stmt ((skip) (eval (block (seq (skip) (eval (lit 1)))))))
+    "#;
+    let program = parse_program(src).expect("parse should succeed");
+    let report = check_program(&program).expect("check should succeed");
+    assert_eq!(report.errors, 0);
+}
+
+#[test]
+fn resolve_rejects_mutually_recursive_data_without_finite_value() {
+    expect_error(
+        "(data Expr (block Stmt)) (data Stmt (eval Expr))",
+        "E-DATA",
+        "data Expr has no finite value",
+    );
+}
+
+#[test]
+fn resolve_rejects_universe_value_with_field_of_wrong_data_type() {
+    expect_error(
+        r#"
+        (data Stmt (skip) (eval Expr))
+        (data Expr (lit Int) (block Stmt))
+        (universe Stmt ((eval (skip))))
+        "#,
+        "E-DATA",
+        "constructor skip belongs to Stmt, expected Expr",
+    );
+}
+
 #[test]
 fn resolve_rejects_duplicate_constructor() {
     expect_error(
@@ -95,3 +131,142 @@ fn resolve_rejects_alias_conflict_with_constructor() {
         "alias conflicts with constructor",
     );
 }
+
+#[test]
+fn resolve_accepts_alias_for_relation_usage() {
+    let src = r#"
+        (sort Subject)
+        (relation can-access (Subject))
+        (alias アクセス可能 can-access)
+        (fact アクセス可能 alice)
+        (rule (アクセス可能 ?u) (can-access ?u))
+    "#;
+    let program = parse_program(src).expect("parse should succeed");
+    let report = check_program(&program).expect("check should succeed");
+    assert_eq!(report.errors, 0);
+}
+
+#[test]
+fn project_config_aliases_are_applied_like_inline_aliases() {
+    let config = parse_project_config("[aliases]\n\"アクセス可能\" = \"can-access\"\n", "dtl.toml")
+        .expect("config should parse");
+    assert_eq!(config.aliases.len(), 1);
+    assert_eq!(config.aliases[0].alias, "アクセス可能");
+    assert_eq!(config.aliases[0].canonical, "can-access");
+    assert_eq!(config.aliases[0].span.line, 2);
+
+    let mut program =
+        parse_program("(sort Subject) (relation can-access (Subject)) (fact アクセス可能 alice)")
+            .expect("parse should succeed");
+    config.apply(&mut program);
+    let report = check_program(&program).expect("check should succeed");
+    assert_eq!(report.errors, 0);
+}
+
+#[test]
+fn project_config_rejects_unknown_sections() {
+    let errs = parse_project_config("[alias]\nx = \"y\"\n", "dtl.toml")
+        .expect_err("unknown section should fail");
+    assert!(errs.iter().any(|d| d.code == "E-CONFIG"), "errs={errs:?}");
+    assert!(
+        errs.iter()
+            .any(|d| d.message.contains("invalid project config")),
+        "errs={errs:?}"
+    );
+}
+
+#[test]
+fn duplicate_declarations_point_at_first_definition() {
+    let program = parse_program(
+        "(sort Subject)\n(relation allowed (Subject))\n(relation allowed (Subject))\n",
+    )
+    .expect("parse should succeed");
+    let errs = check_program(&program).expect_err("check should fail");
+    let diag = errs
+        .iter()
+        .find(|d| d.message == "duplicate relation: allowed")
+        .expect("duplicate relation");
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(3));
+    assert_eq!(diag.related().len(), 1);
+    assert_eq!(diag.related()[0].label, "first defined here");
+    assert_eq!(diag.related()[0].span.line, 2);
+}
+
+#[test]
+fn unknown_call_fix_replaces_only_the_misspelled_name() {
+    let src = "(defn fib ((x Int)) Int x)\n(defn g ((x Int)) Int (fibb x))\n";
+    let program = parse_program(src).expect("parse should succeed");
+    let errs = check_program(&program).expect_err("check should fail");
+    let diag = errs
+        .iter()
+        .find(|d| d.message.contains("fibb"))
+        .expect("unknown call");
+    let fix = &diag.fixes()[0];
+    assert_eq!(&src[fix.span.start..fix.span.end], "fibb");
+    let mut fixed = src.to_string();
+    fixed.replace_range(fix.span.start..fix.span.end, &fix.replacement);
+    let program = parse_program(&fixed).expect("fixed source parses");
+    assert!(check_program(&program).is_ok(), "fixed={fixed}");
+}
+
+#[test]
+fn defconst_values_are_folded_into_terms_and_expressions() {
+    let program = parse_program(
+        r#"
+        (defconst base 3)
+        (defconst limit (* base 2))
+        (defconst role admin)
+        (data Role (admin) (guest))
+        (relation cap (Role Int))
+        (fact cap role limit)
+        (universe Int (base limit))
+        (defn f ((base Int)) Int (+ base limit))
+    "#,
+    )
+    .expect("parse should succeed");
+    check_program(&program).expect("check should succeed");
+    let normalized = normalize_program_aliases(&program).expect("normalize");
+    assert_eq!(
+        normalized.facts[0].terms,
+        vec![LogicTerm::Symbol("admin".to_string()), LogicTerm::Int(6)]
+    );
+    assert_eq!(
+        normalized.universes[0].values,
+        vec![LogicTerm::Int(3), LogicTerm::Int(6)]
+    );
+    let body = &normalized.exprs[normalized.defns[0].body];
+    let dtl::ast::Expr::Call { args, .. } = body else {
+        panic!("expected call, got {body:?}");
+    };
+    assert!(
+        matches!(&normalized.exprs[args[0]], dtl::ast::Expr::Var { name, .. } if name == "base")
+    );
+    assert!(matches!(
+        &normalized.exprs[args[1]],
+        dtl::ast::Expr::Int { value: 6, .. }
+    ));
+}
+
+#[test]
+fn defconst_rejects_cycles_conflicts_and_ill_typed_arithmetic() {
+    expect_error(
+        "(defconst a b)\n(defconst b a)",
+        "E-RESOLVE",
+        "cyclic constant definition: a -> b -> a",
+    );
+    expect_error(
+        "(relation r (Int))\n(defconst r 1)",
+        "E-RESOLVE",
+        "constant conflicts with relation: r",
+    );
+    expect_error(
+        "(defconst a 1)\n(defconst a 2)",
+        "E-RESOLVE",
+        "duplicate constant declaration: a",
+    );
+    expect_error(
+        "(defconst a (+ 1 true))",
+        "E-TYPE",
+        "constant arithmetic expects 2 Int operands",
+    );
+}
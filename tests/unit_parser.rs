@@ -113,6 +113,227 @@ fn parser_accepts_whitespace_inside_quoted_atom() {
     assert_eq!(program.sorts[0].name, "\"A B\"");
 }
 
+#[test]
+fn parser_reads_raw_and_multi_line_strings_as_quoted_atoms() {
+    let src = "(import #\"C:\\dtl\\\"a\".dtl\"#)\n(import ##\"x\"#y\"##)\n(import \"\"\"\n1 \"2\"\n3\\t\"\"\"\")";
+    let program = parse_program(src).expect("parse should succeed");
+    let paths = program
+        .imports
+        .iter()
+        .map(|import| import.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, ["C:\\dtl\\\"a\".dtl", "x\"#y", "1 \"2\"\n3\t\""]);
+
+    let program = parse_program("(relation p (Symbol)) (fact p #\"a (b) ; c\"#)")
+        .expect("parse should succeed");
+    assert_eq!(
+        program.facts[0].terms,
+        vec![LogicTerm::Symbol("\"a (b) ; c\"".to_string())]
+    );
+
+    let errors = parse_program("(import #\"open.dtl\")").expect_err("parse should fail");
+    assert!(
+        errors
+            .iter()
+            .any(|d| d.code == "E-PARSE" && d.message.contains("unterminated raw string"))
+    );
+}
+
+#[test]
+fn parser_skips_nested_block_comments() {
+    let src = r#"
+        (sort Subject)
+        #| (relation unused (Subject))
+           #| (fact unused alice) |# (sort Unused) |#
+        (relation p #| inline |# (Subject))
+        (fact p alice#|x|#)
+    "#;
+    let program = parse_program(src).expect("parse should succeed");
+    assert_eq!(program.relations.len(), 1);
+    assert_eq!(program.relations[0].name, "p");
+    assert_eq!(
+        program.facts[0].terms,
+        vec![LogicTerm::Symbol("alice#|x|#".to_string())]
+    );
+
+    let errors = parse_program("(sort A)\n#| #| |# (sort B)").expect_err("parse should fail");
+    assert!(errors.iter().any(|d| d.code == "E-PARSE"
+        && d.message == "unterminated block comment"
+        && d.span.as_ref().is_some_and(|span| span.line == 2)));
+}
+
+#[test]
+fn parser_drops_the_expression_after_a_datum_comment() {
+    let src = "(sort Subject)\n#; (relation unused (Subject))\n(relation p #;(Int) (Subject))\n(fact p #; #; bob carol alice)\n#;\n(fact p dave)";
+    let program = parse_program(src).expect("parse should succeed");
+    assert_eq!(program.relations.len(), 1);
+    assert_eq!(program.relations[0].name, "p");
+    assert_eq!(program.relations[0].span.line, 3);
+    assert_eq!(program.facts.len(), 1);
+    assert_eq!(
+        program.facts[0].terms,
+        vec![LogicTerm::Symbol("alice".to_string())]
+    );
+    assert_eq!(program.facts[0].span.line, 4);
+
+    for (src, column) in [("(sort A #;)", 9), ("(sort A)\n  #;", 3)] {
+        let errors = parse_program(src).expect_err("parse should fail");
+        assert!(
+            errors.iter().any(|d| d.code == "E-PARSE"
+                && d.message.contains("datum comment")
+                && d.span.as_ref().is_some_and(|span| span.column == column)),
+            "{errors:?}"
+        );
+    }
+}
+
+#[test]
+fn parser_skips_a_leading_shebang_line() {
+    let src = "#!/usr/bin/env -S dtl check\n; syntax: core\n(sort Subject)\n";
+    let program = parse_program(src).expect("parse should succeed");
+    assert_eq!(program.sorts[0].name, "Subject");
+    assert_eq!(program.sorts[0].span.line, 3);
+
+    let errors =
+        parse_program("(sort Subject)\n#!/usr/bin/env dtl\n").expect_err("parse should fail");
+    assert!(errors.iter().any(|d| d.code == "E-PARSE"));
+}
+
+#[test]
+fn parser_gates_facts_rules_and_asserts_on_profiles() {
+    let core = r#"
+        (sort Subject)
+        (relation admin (Subject))
+        (fact admin alice)
+        (when-profile production
+          (fact admin ops)
+          (rule (admin ?x) (admin ops)))
+        (assert has-ops ((u Subject)) (admin ops) :profile production)
+        (assert has-alice ((u Subject)) (admin alice) :profile staging)
+    "#;
+    let surface = r#"
+        ; syntax: surface
+        (型 Subject)
+        (関係 admin :引数 (Subject))
+        (事実 admin :項 (alice))
+        (プロファイル限定 production
+          (事実 admin :項 (ops))
+          (規則 :頭 (admin ?x) :本体 (admin ops)))
+        (検証 has-ops :引数 ((u Subject)) :式 (admin ops) :プロファイル production)
+        (when-profile staging
+          (assert has-alice :params ((u Subject)) :formula (admin alice)))
+    "#;
+    for src in [core, surface] {
+        let mut program = parse_program(src).expect("parse should succeed");
+        let profiles = |program: &dtl::Program| {
+            (
+                program
+                    .facts
+                    .iter()
+                    .map(|fact| fact.profile.clone())
+                    .collect::<Vec<_>>(),
+                program
+                    .rules
+                    .iter()
+                    .map(|rule| rule.profile.clone())
+                    .collect::<Vec<_>>(),
+                program
+                    .asserts
+                    .iter()
+                    .map(|assertion| assertion.profile.clone())
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let production = Some("production".to_string());
+        let staging = Some("staging".to_string());
+        assert_eq!(
+            profiles(&program),
+            (
+                vec![None, production.clone()],
+                vec![production.clone()],
+                vec![production.clone(), staging]
+            )
+        );
+        assert!(program.asserts.iter().all(|a| a.attributes.is_empty()));
+
+        program.select_profiles(&["production".to_string()]);
+        assert_eq!(
+            profiles(&program),
+            (
+                vec![None, production.clone()],
+                vec![production.clone()],
+                vec![production]
+            )
+        );
+        program.select_profiles(&[]);
+        assert_eq!(profiles(&program), (vec![None], vec![], vec![]));
+    }
+
+    for (src, message) in [
+        (
+            "(when-profile p (sort S))",
+            "when-profile accepts only fact, rule and assert forms",
+        ),
+        (
+            "(when-profile p (when-profile q (fact r a)))",
+            "when-profile accepts only fact, rule and assert forms",
+        ),
+        (
+            "(sort S) (when-profile p (assert a ((x S)) true :profile q))",
+            "conflicting profiles: q inside when-profile p",
+        ),
+        (
+            "(when-profile \"p q\" (fact r a))",
+            "profile name must be a bare name",
+        ),
+        ("(sort S :profile p)", "sort does not accept :profile"),
+        (
+            "; syntax: surface\n(when-profile p (型 S))",
+            "when-profile accepts only fact, rule and assert forms",
+        ),
+    ] {
+        let errors = parse_program(src).expect_err("parse should fail");
+        assert!(
+            errors
+                .iter()
+                .any(|d| d.code == "E-PARSE" && d.message.contains(message)),
+            "{src}: {errors:?}"
+        );
+    }
+}
+
+#[test]
+fn fmt_keeps_a_mixed_when_profile_block_together() {
+    let src = r#"
+        (sort Subject)
+        (relation admin (Subject))
+        (fact admin alice)
+        (when-profile production
+          (fact admin bob)
+          (assert all-admin ((u Subject)) (admin u)))
+    "#;
+    let expected = "; syntax: surface
+; @context: default
+
+(型 Subject)
+
+(関係 admin :引数 (Subject))
+
+(事実 admin :項 (alice))
+
+(プロファイル限定 production
+  (事実 admin :項 (bob))
+  (検証 all-admin :引数 ((u Subject)) :式 (admin u)))
+";
+    let formatted = dtl::format_source(src, Default::default()).expect("format");
+    assert_eq!(formatted, expected);
+    let again = dtl::format_source(&formatted, Default::default()).expect("format again");
+    assert_eq!(again, expected);
+    let program = parse_program(&formatted).expect("formatted source should parse");
+    assert_eq!(program.facts[1].profile.as_deref(), Some("production"));
+    assert_eq!(program.asserts[0].profile.as_deref(), Some("production"));
+}
+
 #[test]
 fn parser_rejects_unknown_escape_sequence_in_quoted_atom() {
     let src = r#"(import "bad\q.dtl")"#;
@@ -1,4 +1,4 @@
-use dtl::parse_program;
+use dtl::{parse_program, parse_program_lossy};
 
 fn expect_parse_error(src: &str, needle: &str) {
     let errs = parse_program(src).expect_err("parse should fail");
@@ -109,3 +109,54 @@ fn parser_rejects_empty_expr_list() {
         "expression list cannot be empty",
     );
 }
+
+#[test]
+fn lossy_parser_keeps_forms_around_a_broken_one() {
+    let src =
+        "(sort Subject)\n)\n(relation allowed (Subject)\n(relation blocked (Subject))\n(fact)\n";
+    let (program, errors) = parse_program_lossy(src);
+    assert_eq!(program.sorts.len(), 1);
+    assert_eq!(
+        program
+            .relations
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["blocked"]
+    );
+    let messages = errors
+        .iter()
+        .map(|d| d.message.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(messages.len(), 3, "errors={errors:?}");
+    assert!(messages[0].contains("unexpected ')'"));
+    assert!(messages[1].contains("unbalanced parentheses"));
+    assert!(messages[2].contains("fact"));
+}
+
+#[test]
+fn lossy_parser_reports_lex_errors_and_continues() {
+    let (program, errors) = parse_program_lossy("(sort \"bad\\q\")\n(sort Subject)\n");
+    assert_eq!(program.sorts.len(), 1);
+    assert!(
+        errors
+            .iter()
+            .any(|d| d.message.contains("unsupported escape sequence"))
+    );
+}
+
+#[test]
+fn parser_rejects_excessive_nesting() {
+    let src = format!("(defn f () Int {}0{})", "(".repeat(3000), ")".repeat(3000));
+    expect_parse_error(&src, "maximum depth");
+    let (_, errors) = parse_program_lossy(&src);
+    assert!(errors.iter().any(|d| d.message.contains("maximum depth")));
+}
+
+#[test]
+fn parser_rejects_non_positive_skolem_bound() {
+    expect_parse_error(
+        "(rule (p ?x ?y) (q ?x) :skolem 0)",
+        "rule :skolem expects a positive depth bound",
+    );
+}
@@ -35,3 +35,32 @@ fn parser_rejects_selfdoc_form_without_required_tags() {
             .any(|d| d.message.contains("contract requires :パス"))
     );
 }
+
+#[test]
+fn parser_names_the_tags_of_the_selected_keyword_set_in_selfdoc_errors() {
+    let src = r#"
+    ; syntax: surface
+    ; keywords: en
+    (project "domain-typed-lisp")
+    (module :name "README" :path "README.md")
+    (when-profile ci (fact sd-project a b))
+    "#;
+
+    let messages = parse_program(src)
+        .expect_err("parse should fail")
+        .into_iter()
+        .map(|d| d.message)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            "project expects tags :name/:summary",
+            "module requires :category",
+            "fact expects tagged terms: :terms",
+        ]
+    );
+
+    let errors = parse_program("; syntax: surface\n(プロジェクト \"domain-typed-lisp\")")
+        .expect_err("parse should fail");
+    assert_eq!(errors[0].message, "project expects tags :名前/:概要");
+}
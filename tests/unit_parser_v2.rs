@@ -1,4 +1,7 @@
-use dtl::parse_program;
+use dtl::{
+    ParseOptions, SurfaceKeywordMap, SurfaceKeywords, check_program, parse_program,
+    parse_program_with_options, parse_project_config, surface_keywords,
+};
 
 #[test]
 fn parser_accepts_data_assert_universe_and_match() {
@@ -145,3 +148,452 @@ fn parser_accepts_surface_alias_declaration() {
     assert_eq!(program.aliases[0].alias, "閲覧");
     assert_eq!(program.aliases[0].canonical, "read");
 }
+
+#[test]
+fn surface_spans_point_at_user_written_positions() {
+    let src = "; syntax: surface\n(型 顧客)\n(関数 f\n  :引数 ((x 顧客))\n  :戻り Bool\n  :本体 (可否 x))\n";
+
+    let program = parse_program(src).expect("surface parse should succeed");
+    let defn = &program.defns[0];
+    assert_eq!((defn.span.line, defn.span.column), (3, 2));
+    assert_eq!(&src[defn.span.start..defn.span.end], "関数");
+    assert_eq!(
+        (defn.params[0].span.line, defn.params[0].span.column),
+        (4, 8)
+    );
+    assert_eq!(
+        &src[defn.params[0].span.start..defn.params[0].span.end],
+        "(x 顧客)"
+    );
+
+    let errs = check_program(&program).expect_err("unknown function");
+    let diag = errs
+        .iter()
+        .find(|d| d.code == "E-RESOLVE")
+        .expect("resolve error");
+    let span = diag.span.as_ref().expect("span");
+    assert_eq!((span.line, span.column), (6, 7));
+    assert_eq!(&src[span.start..span.end], "(可否 x)");
+}
+
+#[test]
+fn parser_accepts_english_surface_keywords_and_detects_vocabulary() {
+    let src = r#"
+        ; syntax: surface
+        (sort Subject)
+        (data Action :constructors ((read) (write)))
+        (relation allowed :args (Subject Action))
+        (fact allowed :terms (alice (read)))
+        (rule :head (allowed ?u (write)) :body (allowed ?u (read)))
+        (assert readable :params ((u Subject)) :formula (allowed u (read)))
+        (universe Subject :values (alice))
+        (defn can :params ((u Subject)) :ret Bool :body (allowed u (read)))
+    "#;
+    let program = parse_program(src).expect("english surface parse should succeed");
+    assert_eq!(program.rules.len(), 1);
+    assert_eq!(program.defns.len(), 1);
+    assert_eq!(surface_keywords(src), SurfaceKeywords::English);
+
+    assert_eq!(
+        surface_keywords("(型 Subject)\n(relation allowed :args (Subject))"),
+        SurfaceKeywords::Japanese
+    );
+    assert_eq!(
+        surface_keywords("; keywords: en\n(型 Subject)"),
+        SurfaceKeywords::English
+    );
+}
+
+#[test]
+fn parser_applies_project_surface_keywords() {
+    let config = parse_project_config(
+        "[syntax.keywords]\n\"取引関係\" = \"関係\"\n\":取引項\" = \":項\"\n\"取引事実\" = \"fact\"\n",
+        "dtl.toml",
+    )
+    .expect("config should parse");
+    assert_eq!(config.keywords.canonical("取引関係"), Some("関係"));
+
+    let src = r#"
+        (型 主体)
+        (取引関係 締結済み :引数 (主体))
+        (取引事実 締結済み :取引項 (山田))
+    "#;
+    assert!(parse_program(src).is_err());
+    let program = parse_program_with_options(
+        src,
+        &ParseOptions {
+            keywords: config.keywords,
+            ..ParseOptions::default()
+        },
+    )
+    .expect("custom keywords should parse");
+    assert_eq!(program.relations[0].name, "締結済み");
+    assert_eq!(program.facts.len(), 1);
+}
+
+#[test]
+fn surface_keyword_map_rejects_invalid_entries() {
+    let mut keywords = SurfaceKeywordMap::new();
+    assert!(keywords.insert("関係", "relation").is_err());
+    assert!(keywords.insert("取引", "unknown-head").is_err());
+    assert!(keywords.insert("取引", ":項").is_err());
+    assert!(keywords.insert(":取引", "関係").is_err());
+    assert!(keywords.insert(":取引", ":未知").is_err());
+    assert!(keywords.is_empty());
+
+    let errs = parse_project_config("[syntax.keywords]\n\"関係\" = \"fact\"\n", "dtl.toml")
+        .expect_err("shadowing a built-in keyword should fail");
+    assert!(errs.iter().any(|d| d.code == "E-CONFIG"), "errs={errs:?}");
+    assert!(
+        errs.iter()
+            .any(|d| d.message.contains("shadows a built-in keyword")),
+        "errs={errs:?}"
+    );
+}
+
+#[test]
+fn parser_reads_docstrings_on_relation_assert_and_defn() {
+    let surface = r#"
+        ; syntax: surface
+        (データ 主体 :コンストラクタ ((山田)))
+        (関係 許可 :引数 (主体) :説明 "閲覧を許可された主体")
+        (検証 整合性 :引数 ((u 主体)) :式 (許可 u) :説明 "全員が許可される")
+        (関数 可否
+          :引数 ((u 主体))
+          :戻り Bool
+          :本体 (許可 u)
+          :説明 "許可の有無を \"真偽\" で返す")
+    "#;
+    let program = parse_program(surface).expect("surface docstrings should parse");
+    assert_eq!(
+        program.relations[0].doc.as_deref(),
+        Some("閲覧を許可された主体")
+    );
+    assert_eq!(program.asserts[0].doc.as_deref(), Some("全員が許可される"));
+    assert_eq!(
+        program.defns[0].doc.as_deref(),
+        Some("許可の有無を \"真偽\" で返す")
+    );
+
+    let core = r#"
+        (sort Subject)
+        (relation allowed (Subject) :doc "who may read")
+        (defn id ((x Int)) Int x :doc "identity")
+        (assert trivial ((u Subject)) (allowed u))
+    "#;
+    let program = parse_program(core).expect("core docstrings should parse");
+    assert_eq!(program.relations[0].doc.as_deref(), Some("who may read"));
+    assert_eq!(program.defns[0].doc.as_deref(), Some("identity"));
+    assert_eq!(program.asserts[0].doc, None);
+
+    let errs = parse_program("(relation allowed (Subject) :doc \"a\" :doc \"b\")")
+        .expect_err("duplicate docstring should fail");
+    assert!(
+        errs.iter()
+            .any(|d| d.code == "E-PARSE" && d.message.contains("duplicate relation tag: :doc")),
+        "errs={errs:?}"
+    );
+}
+
+#[test]
+fn parser_keeps_declaration_metadata_attributes() {
+    let core = r#"
+        (sort Subject :owner "identity-team")
+        (data Action (read) (write) :since "0.3")
+        (relation allowed (Subject Action) :owner "security" :ticket "SEC-12")
+        (assert readable ((u Subject)) (allowed u (read)) :since "0.4")
+        (defn can ((u Subject)) Bool (allowed u (read)) :doc "can read" :owner "security")
+    "#;
+    let program = parse_program(core).expect("core attributes should parse");
+    assert_eq!(program.sorts[0].attributes["owner"], "identity-team");
+    assert_eq!(program.data_decls[0].constructors.len(), 2);
+    assert_eq!(program.data_decls[0].attributes["since"], "0.3");
+    assert_eq!(program.relations[0].attributes.len(), 2);
+    assert_eq!(program.relations[0].attributes["ticket"], "SEC-12");
+    assert_eq!(program.asserts[0].attributes["since"], "0.4");
+    assert_eq!(program.defns[0].doc.as_deref(), Some("can read"));
+    assert_eq!(program.defns[0].attributes["owner"], "security");
+
+    let surface = r#"
+        ; syntax: surface
+        (型 主体 :owner "identity-team")
+        (関係 許可 :引数 (主体) :説明 "許可" :ticket "SEC-12")
+    "#;
+    let program = parse_program(surface).expect("surface attributes should parse");
+    assert_eq!(program.sorts[0].attributes["owner"], "identity-team");
+    assert_eq!(program.relations[0].doc.as_deref(), Some("許可"));
+    assert_eq!(program.relations[0].attributes["ticket"], "SEC-12");
+
+    let errs = parse_program("(sort Subject :doc \"subjects\")")
+        .expect_err("sort docstrings are not supported");
+    assert!(
+        errs.iter()
+            .any(|d| d.message.contains("sort does not accept :doc")),
+        "errs={errs:?}"
+    );
+}
+
+#[test]
+fn parser_reads_assert_failure_messages() {
+    let src = r#"
+        ; syntax: surface
+        (型 主体)
+        (関係 許可 :引数 (主体))
+        (検証 全員許可 :引数 ((u 主体)) :式 (許可 u) :メッセージ "全員に閲覧を許可すること")
+    "#;
+    let program = parse_program(src).expect("surface message should parse");
+    assert_eq!(
+        program.asserts[0].message.as_deref(),
+        Some("全員に閲覧を許可すること")
+    );
+    assert!(program.asserts[0].attributes.is_empty());
+
+    let errs = parse_program("(relation allowed (Subject) :message \"x\")")
+        .expect_err("relations do not take failure messages");
+    assert!(
+        errs.iter()
+            .any(|d| d.message.contains("relation does not accept :message")),
+        "errs={errs:?}"
+    );
+}
+
+#[test]
+fn parser_reads_skolem_bound_in_core_and_surface_rules() {
+    let core = parse_program("(rule (p ?x ?y) (q ?x) :skolem 2)").expect("core parse");
+    assert_eq!(core.rules[0].skolem, Some(2));
+
+    let surface = parse_program(
+        r#"
+        ; syntax: surface
+        (規則 :頭 (p ?x ?y) :本体 (q ?x) :スコーレム 2)
+        (規則 :頭 (q ?x) :本体 (p ?x ?x))
+    "#,
+    )
+    .expect("surface parse");
+    assert_eq!(surface.rules[0].skolem, Some(2));
+    assert_eq!(surface.rules[1].skolem, None);
+
+    let formatted = dtl::format_source("(rule (p ?x ?y) (q ?x) :skolem 2)", Default::default())
+        .expect("format");
+    assert!(formatted.contains(":スコーレム 2"), "{formatted}");
+    assert_eq!(
+        parse_program(&formatted).expect("reparse").rules[0].skolem,
+        Some(2)
+    );
+}
+
+#[test]
+fn parser_reads_relation_keys_in_core_and_surface() {
+    let core = parse_program("(sort S) (relation owner (S S S) :key (2 1))").expect("core parse");
+    assert_eq!(core.relations[0].key, Some(vec![2, 1]));
+
+    let surface = parse_program(
+        r#"
+        ; syntax: surface
+        (型 S)
+        (関係 owner :引数 (S S) :キー (1))
+        (関係 edge :引数 (S S))
+    "#,
+    )
+    .expect("surface parse");
+    assert_eq!(surface.relations[0].key, Some(vec![1]));
+    assert_eq!(surface.relations[1].key, None);
+
+    let formatted = dtl::format_source(
+        "(sort S) (relation owner (S S) :key (1))",
+        Default::default(),
+    )
+    .expect("format");
+    assert!(
+        formatted.contains("(関係 owner :引数 (S S) :キー (1))"),
+        "{formatted}"
+    );
+    assert_eq!(
+        parse_program(&formatted).expect("reparse").relations[0].key,
+        Some(vec![1])
+    );
+
+    for src in [
+        "(sort S) (relation owner (S S) :key ())",
+        "(sort S) (relation owner (S S) :key (3))",
+        "(sort S) (relation owner (S S) :key (1 1))",
+        "(sort S) (relation owner (S S) :key 1)",
+    ] {
+        let errs = parse_program(src).expect_err("key should be rejected");
+        assert!(
+            errs.iter().any(|d| d.code == "E-PARSE"
+                && d.message == "relation :key expects distinct argument positions from 1 to 2"),
+            "{src}: {errs:?}"
+        );
+    }
+}
+
+#[test]
+fn parser_reads_termination_measures_in_core_and_surface_defn() {
+    let core = parse_program(
+        r#"
+        (relation zero (Int))
+        (defn down ((n Int)) Bool (if (zero n) true (down (- n 1))) :decreases n)
+        (defn twice ((n Int)) Bool (if (zero n) true (twice (- n 1))) :measure (* 2 n))
+    "#,
+    )
+    .expect("core parse");
+    assert!(matches!(
+        core.defns[0].measure.map(|measure| &core.exprs[measure]),
+        Some(dtl::ast::Expr::Var { name, .. }) if name == "n"
+    ));
+    assert!(matches!(
+        core.defns[1].measure.map(|measure| &core.exprs[measure]),
+        Some(dtl::ast::Expr::Call { name, .. }) if name == "*"
+    ));
+
+    let surface = parse_program(
+        r#"
+        ; syntax: surface
+        (関係 zero :引数 (Int))
+        (関数 down :引数 ((n Int)) :戻り Bool :本体 (if (zero n) true (down (- n 1))) :減少 n)
+    "#,
+    )
+    .expect("surface parse");
+    assert!(surface.defns[0].measure.is_some());
+
+    let errs =
+        parse_program("(defn f ((n Int)) Bool true :decreases m)").expect_err("unknown parameter");
+    assert!(
+        errs.iter().any(|d| d
+            .message
+            .contains("defn :decreases expects a parameter name")),
+        "errs={errs:?}"
+    );
+    let errs = parse_program("(defn f ((n Int)) Bool true :decreases n :measure n)")
+        .expect_err("two measures");
+    assert!(
+        errs.iter().any(|d| d
+            .message
+            .contains("defn accepts a single termination measure")),
+        "errs={errs:?}"
+    );
+
+    let formatted = dtl::format_source(
+        "(relation zero (Int)) (defn down ((n Int)) Bool (if (zero n) true (down (- n 1))) :decreases n)",
+        Default::default(),
+    )
+    .expect("format");
+    assert!(formatted.contains(":尺度 n"), "{formatted}");
+    assert!(
+        parse_program(&formatted).expect("reparse").defns[0]
+            .measure
+            .is_some()
+    );
+}
+
+#[test]
+fn parser_reads_letfn_local_functions() {
+    let program = parse_program(
+        r#"
+        (defn f ((x Int)) Int
+          (letfn ((inc ((n Int)) Int (+ n 1))
+                  (twice ((n Int)) Int (inc (inc n))))
+            (twice x)))
+    "#,
+    )
+    .expect("parse");
+    let body = &program.exprs[program.defns[0].body];
+    let dtl::ast::Expr::LetFn { fns, body, .. } = body else {
+        panic!("expected letfn, got {body:?}");
+    };
+    assert_eq!(
+        fns.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(),
+        ["inc", "twice"]
+    );
+    assert_eq!(fns[0].params[0].name, "n");
+    assert_eq!(fns[0].ret_type, dtl::types::Type::Int);
+    assert!(matches!(&program.exprs[*body], dtl::ast::Expr::Call { name, .. } if name == "twice"));
+
+    let errs = parse_program("(defn f ((x Int)) Int (letfn ((g Int x)) x))")
+        .expect_err("malformed local function");
+    assert!(errs.iter().any(|d| d.code == "E-PARSE"), "errs={errs:?}");
+}
+
+#[test]
+fn parser_reads_multi_clause_defns_in_core_and_surface() {
+    let core = parse_program(
+        r#"
+        (data Nat (z) (s Nat))
+        (defn add ((n Nat) (m Nat)) Nat
+          :clauses ((((z) m) m)
+                    (((s k) m) (s (add k m)))))
+    "#,
+    )
+    .expect("core parse");
+    let defn = &core.defns[0];
+    assert_eq!(defn.clauses.len(), 2);
+    assert_eq!(defn.clauses[1].patterns.len(), 2);
+    assert!(matches!(
+        &core.exprs[defn.body],
+        dtl::ast::Expr::Match { scrutinee, .. }
+            if matches!(&core.exprs[*scrutinee], dtl::ast::Expr::Var { name, .. } if name == "n")
+    ));
+
+    let surface = parse_program(
+        "; syntax: surface\n(データ Nat :コンストラクタ ((z) (s Nat)))\n(関数 pred :引数 ((n Nat)) :戻り Nat :節 ((((z)) (z)) (((s k)) k)))",
+    )
+    .expect("surface parse");
+    assert_eq!(surface.defns[0].clauses.len(), 2);
+
+    for src in [
+        "(defn f ((x Int) (y Int)) Int :clauses (((x) x)))",
+        "(defn f ((x Int) (y Int)) Int :clauses (((a a) a)))",
+        "(defn f ((x Int)) Int :clauses ())",
+    ] {
+        let errs = parse_program(src).expect_err("malformed clauses");
+        assert!(
+            errs.iter().any(|d| d.code == "E-PARSE"),
+            "src={src} errs={errs:?}"
+        );
+    }
+}
+
+#[test]
+fn parser_reads_refined_relation_arguments() {
+    let program = parse_program(
+        "(sort Person) (relation registered (Person)) (relation age ((Refine p Person (registered p)) Int))",
+    )
+    .expect("parse");
+    let age = &program.relations[1];
+    assert_eq!(age.arg_sorts, ["Person", "Int"]);
+    let refinement = age.arg_refinements[0]
+        .as_ref()
+        .expect("refined first argument");
+    assert_eq!(refinement.var, "p");
+    assert!(age.arg_refinements[1].is_none());
+
+    let errs =
+        parse_program("(relation age ((Refine p Person)))").expect_err("malformed refinement");
+    assert!(errs.iter().any(|d| d.code == "E-PARSE"), "errs={errs:?}");
+}
+
+#[test]
+fn parser_reads_defconst_in_core_and_surface() {
+    let core = parse_program("(defconst limit (* 2 3))").expect("core parse");
+    assert_eq!(core.consts[0].name, "limit");
+    assert!(matches!(
+        &core.exprs[core.consts[0].value],
+        dtl::ast::Expr::Call { name, .. } if name == "*"
+    ));
+
+    let surface = parse_program("; syntax: surface\n(定数 上限 :値 3)").expect("surface parse");
+    assert_eq!(surface.consts[0].name, "上限");
+    assert!(matches!(
+        surface.exprs[surface.consts[0].value],
+        dtl::ast::Expr::Int { value: 3, .. }
+    ));
+
+    let errs = parse_program("(defconst a (if true 1 2))").expect_err("non-constant value");
+    assert!(
+        errs.iter().any(|d| d
+            .message
+            .contains("defconst value must be built from literals")),
+        "errs={errs:?}"
+    );
+}
@@ -0,0 +1,132 @@
+use dtl::ast::Expr;
+use dtl::types::LogicTerm;
+use dtl::{Program, parse_program, partially_evaluate, prove_program};
+
+fn partially_evaluated(src: &str) -> (Program, Program) {
+    let program = parse_program(src).expect("parse");
+    let evaluated = partially_evaluate(&program).expect("partial evaluation");
+    (program, evaluated)
+}
+
+fn results(program: &Program) -> Vec<(String, String)> {
+    prove_program(program)
+        .expect("prove")
+        .obligations
+        .into_iter()
+        .map(|o| (o.id, o.result))
+        .collect()
+}
+
+fn ctor(name: &str) -> LogicTerm {
+    LogicTerm::Ctor {
+        name: name.to_string(),
+        args: Vec::new(),
+    }
+}
+
+#[test]
+fn partial_evaluation_folds_constant_expressions_in_defn_bodies() {
+    let src = r#"
+(defconst limit 10)
+(data Shape (square Int) (dot))
+(defn quota ((n Int)) Int
+  (let ((base (* limit 2)) (extra 0) (on true))
+    (if on (+ n extra) (* n base))))
+(defn side () Int
+  (match (square (+ 1 2)) ((square s) (* s s)) ((dot) 0)))
+(defn scaled ((n Int)) Int
+  (let ((m (* n 2)) (k (- 5 3))) (* m k)))
+(defn area ((shape Shape)) Int
+  :clauses ((((square s)) (* s (* 1 1)))
+            (((dot)) (+ 0 0))))
+"#;
+    let (program, evaluated) = partially_evaluated(src);
+    let body = |name: &str| {
+        let defn = evaluated
+            .defns
+            .iter()
+            .find(|defn| defn.name == name)
+            .expect("defn");
+        &evaluated.exprs[defn.body]
+    };
+
+    assert!(matches!(body("quota"), Expr::Var { name, .. } if name == "n"));
+    assert!(matches!(body("side"), Expr::Int { value: 9, .. }));
+    let Expr::Let {
+        bindings,
+        body: let_body,
+        ..
+    } = body("scaled")
+    else {
+        panic!("scaled keeps the binding that depends on n");
+    };
+    assert_eq!(bindings.len(), 1);
+    assert_eq!(bindings[0].0, "m");
+    let Expr::Call { name, args, .. } = &evaluated.exprs[*let_body] else {
+        panic!("scaled body is a call");
+    };
+    assert_eq!(name, "*");
+    assert!(matches!(
+        evaluated.exprs[args[1]],
+        Expr::Int { value: 2, .. }
+    ));
+
+    let area = evaluated
+        .defns
+        .iter()
+        .find(|defn| defn.name == "area")
+        .expect("area");
+    let clause_bodies = area
+        .clauses
+        .iter()
+        .map(|clause| evaluated.exprs[clause.body].clone())
+        .collect::<Vec<_>>();
+    assert!(matches!(&clause_bodies[0], Expr::Var { name, .. } if name == "s"));
+    assert!(matches!(clause_bodies[1], Expr::Int { value: 0, .. }));
+
+    assert_eq!(results(&program), results(&evaluated));
+}
+
+#[test]
+fn partial_evaluation_instantiates_rules_over_singleton_universes() {
+    let src = r#"
+(data Tenant (acme))
+(data Subject (alice) (bob))
+(relation member (Tenant Subject))
+(relation admin (Subject))
+(relation active (Tenant Subject))
+(relation idle (Subject))
+(fact member (acme) (alice))
+(fact member (acme) (bob))
+(fact admin (alice))
+(rule (active ?t ?s) (and (member ?t ?s) (admin ?s)))
+(rule (idle ?s) (and (member ?t ?s) (not (admin ?s))))
+(universe Tenant ((acme)))
+(universe Subject ((alice) (bob)))
+(assert admins-active ((s Subject)) (not (and (admin s) (not (active (acme) s)))))
+"#;
+    let (program, evaluated) = partially_evaluated(src);
+    assert_eq!(
+        evaluated.rules[0].head.terms,
+        vec![ctor("acme"), LogicTerm::Var("s".to_string())]
+    );
+    assert_eq!(evaluated.rules[1].head.terms, program.rules[1].head.terms);
+    assert!(!format!("{:?}", evaluated.rules[1].body).contains("Var(\"t\")"));
+    assert_eq!(results(&program), results(&evaluated));
+
+    let src = src
+        .replace("(data Tenant (acme))", "(data Tenant (acme) (other))")
+        .replace(
+            "(fact admin (alice))",
+            "(fact admin (alice))\n(fact member (other) (alice))",
+        );
+    let (program, evaluated) = partially_evaluated(&src);
+    assert_eq!(evaluated.rules, program.rules);
+}
+
+#[test]
+fn partial_evaluation_reports_type_errors_in_branches_it_would_drop() {
+    let program = parse_program("(defn pick () Int (if true 1 false))").expect("parse");
+    let errors = partially_evaluate(&program).expect_err("type error");
+    assert_eq!(errors[0].code, "E-TYPE");
+}
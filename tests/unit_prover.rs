@@ -1,4 +1,7 @@
-use dtl::{has_failed_obligation, parse_program, prove_program};
+use dtl::{
+    ProofTraceWriter, has_failed_obligation, parse_program, prove_program, read_proof_trace,
+};
+use tempfile::tempdir;
 
 #[test]
 fn prove_program_succeeds_and_emits_schema_version() {
@@ -18,7 +21,7 @@ fn prove_program_succeeds_and_emits_schema_version() {
 
     let program = parse_program(src).expect("parse");
     let trace = prove_program(&program).expect("prove should succeed");
-    assert_eq!(trace.schema_version, "2.2.0");
+    assert_eq!(trace.schema_version, "2.3.0");
     assert_eq!(trace.profile, "standard");
     assert_eq!(trace.engine, "native");
     assert_eq!(trace.summary.total, trace.obligations.len());
@@ -59,6 +62,35 @@ fn prove_program_reports_counterexample() {
     );
 }
 
+#[test]
+fn prove_program_quantifies_over_skolem_witnesses() {
+    let src = r#"
+        (sort Order)
+        (sort Invoice)
+        (relation order (Order))
+        (relation invoice-of (Order Invoice))
+        (universe Order (o1))
+        (universe Invoice (i1))
+        (fact order o1)
+        (rule (invoice-of ?o ?i) (order ?o) :skolem 1)
+        (assert no-invoice ((o Order) (i Invoice)) (not (invoice-of o i)))
+    "#;
+
+    let program = parse_program(src).expect("parse");
+    let trace = prove_program(&program).expect("prove should run");
+    let failed = &trace.obligations[0];
+    assert_eq!(failed.result, "failed");
+    let witness = failed
+        .valuation
+        .iter()
+        .find(|binding| binding.name == "i")
+        .expect("valuation of i");
+    assert_eq!(witness.value, "(sk:invoice-of:i o1)");
+
+    let result = dtl::reference::check_assert(&program, "no-invoice").expect("reference");
+    assert_eq!(result.result, "failed");
+}
+
 #[test]
 fn prove_program_requires_universe() {
     let src = r#"
@@ -147,3 +179,78 @@ fn prove_program_reports_missing_universe_with_span() {
         "missing universe diagnostic should have span"
     );
 }
+
+#[test]
+fn prove_program_checks_termination_measures() {
+    let src = |universe: &str| {
+        format!(
+            r#"
+            (relation zero (Int))
+            (fact zero 0)
+            (universe Int ({universe}))
+            (defn down ((n Int)) Bool
+              (if (zero n) true (down (- n 1)))
+              :decreases n)
+        "#
+        )
+    };
+
+    let program = parse_program(&src("0 1 3")).expect("parse");
+    let trace = prove_program(&program).expect("prove should succeed");
+    assert_eq!(trace.obligations.len(), 1);
+    assert_eq!(trace.obligations[0].id, "measure::down");
+    assert_eq!(trace.obligations[0].result, "proved");
+
+    let program = parse_program(&src("-2 0")).expect("parse");
+    let trace = prove_program(&program).expect("prove should run");
+    let failed = &trace.obligations[0];
+    assert_eq!(failed.result, "failed");
+    assert_eq!(
+        failed
+            .counterexample
+            .as_ref()
+            .expect("counterexample")
+            .missing_goals,
+        vec!["<=(0,-3)".to_string()]
+    );
+}
+
+#[test]
+fn proof_trace_writer_appends_obligations_before_the_summary() {
+    let src = r#"
+        (data Subject (alice) (bob))
+        (relation allowed (Subject))
+        (fact allowed (alice))
+        (universe Subject ((alice) (bob)))
+        (assert everyone ((u Subject)) (allowed u))
+        (assert alice-allowed () (allowed (alice)))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let trace = prove_program(&program).expect("prove should run");
+    let dir = tempdir().expect("tempdir");
+
+    for name in ["proof-trace.json", "proof-trace.json.zst"] {
+        let path = dir.path().join(name);
+        let mut writer = ProofTraceWriter::create(&path).expect("create trace");
+        for obligation in &trace.obligations {
+            writer
+                .write_obligation(obligation)
+                .expect("write obligation");
+        }
+        writer.finish(&trace).expect("finish trace");
+
+        let read = read_proof_trace(&path).expect("read trace");
+        assert_eq!(read.schema_version, trace.schema_version);
+        assert_eq!(read.summary.failed, 1);
+        assert_eq!(
+            read.obligations
+                .iter()
+                .map(|o| (o.id.as_str(), o.result.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("assert::everyone", "failed"),
+                ("assert::alice-allowed", "proved")
+            ]
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use dtl::{find_references, parse_program};
+
+const SRC: &str = r#"(sort Subject)
+(data Role (admin-role) (guest-role))
+(relation admin (Subject))
+(relation allowed (Subject))
+(fact admin alice)
+(rule (allowed ?u) (admin ?u))
+(universe Subject (alice bob))
+(assert admins-allowed ((u Subject)) (not (and (admin u) (not (allowed u)))))
+(defn role-of ((u Subject)) Role (if (admin u) (admin-role) (guest-role)))
+(defn is-guest ((r Role)) Bool (match r ((admin-role) false) ((guest-role) true)))
+"#;
+
+fn lines(src: &str, symbol: &str) -> Vec<(usize, usize)> {
+    let program = parse_program(src).expect("parse");
+    find_references(&program, symbol)
+        .into_iter()
+        .map(|span| {
+            assert_eq!(&src[span.start..span.end], symbol);
+            (span.line, span.column)
+        })
+        .collect()
+}
+
+#[test]
+fn references_cover_facts_rules_formulas_and_expressions() {
+    assert_eq!(lines(SRC, "admin"), vec![(5, 7), (6, 21), (8, 49), (9, 39)]);
+}
+
+#[test]
+fn references_point_at_the_name_in_signatures_and_universes() {
+    assert_eq!(
+        lines(SRC, "Subject"),
+        vec![(3, 18), (4, 20), (7, 11), (8, 28), (9, 19)]
+    );
+    assert_eq!(lines(SRC, "alice"), vec![(5, 13), (7, 20)]);
+}
+
+#[test]
+fn references_in_example_rule_and_refinement_are_exact() {
+    let src = include_str!("../examples/access_control_ok.dtl");
+    assert_eq!(lines(src, "can-access"), vec![(13, 8), (18, 19), (19, 4)]);
+    assert_eq!(lines(src, "read"), vec![(13, 26), (18, 35), (19, 20)]);
+}
+
+#[test]
+fn references_point_at_constructor_calls_and_patterns() {
+    assert_eq!(lines(SRC, "admin-role"), vec![(9, 49), (10, 43)]);
+    assert_eq!(lines(SRC, "Role"), vec![(9, 29), (10, 20)]);
+    assert!(lines(SRC, "unknown").is_empty());
+}
+
+#[test]
+fn references_resolve_aliases_in_both_directions() {
+    let src = format!("{SRC}(alias 管理者 admin)\n(fact 管理者 bob)\n");
+    let program = parse_program(&src).expect("parse");
+    let canonical = find_references(&program, "admin");
+    let names = canonical
+        .iter()
+        .map(|span| &src[span.start..span.end])
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["admin", "admin", "admin", "admin", "管理者"]);
+    assert_eq!(find_references(&program, "管理者"), canonical);
+}
@@ -0,0 +1,81 @@
+use dtl::{SliceTarget, check_program, parse_program, prove_program, slice_program};
+
+const SRC: &str = r#"
+(sort Subject)
+(sort Resource)
+(data Action (read) (write))
+(data Level (low) (high))
+(relation allowed (Subject Resource Action))
+(relation admin (Subject))
+(relation audited (Resource))
+(relation clearance (Subject Level))
+(alias 許可 allowed)
+(fact admin alice)
+(fact audited doc1)
+(fact clearance alice (high))
+(rule (allowed ?u ?r (read)) (and (admin ?u) (audited ?r)))
+(universe Subject (alice bob))
+(universe Resource (doc1))
+(universe Level ((low) (high)))
+(universe Action ((read) (write)))
+(assert admins-read ((u Subject) (r Resource))
+  (not (and (admin u) (not (許可 u r (read))))))
+(assert audited-docs ((r Resource)) (audited r))
+(defn high? ((l Level)) Bool
+  (match l ((high) true) (_ false)))
+"#;
+
+#[test]
+fn assert_slice_keeps_only_transitive_dependencies() {
+    let program = parse_program(SRC).expect("parse");
+    let slice =
+        slice_program(&program, &SliceTarget::Assert("admins-read".to_string())).expect("slice");
+
+    let relations = slice
+        .relations
+        .iter()
+        .map(|relation| relation.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(relations, vec!["allowed", "admin", "audited"]);
+    assert_eq!(slice.asserts.len(), 1);
+    assert_eq!(slice.rules.len(), 1);
+    assert_eq!(slice.facts.len(), 2);
+    assert!(slice.aliases.is_empty());
+    assert!(slice.defns.is_empty());
+    let data = slice
+        .data_decls
+        .iter()
+        .map(|data| data.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(data, vec!["Action"]);
+    let universes = slice
+        .universes
+        .iter()
+        .map(|universe| universe.ty_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(universes, vec!["Subject", "Resource", "Action"]);
+
+    check_program(&slice).expect("slice should check");
+    let trace = prove_program(&slice).expect("slice should prove");
+    assert_eq!(trace.summary.failed, 0);
+}
+
+#[test]
+fn defn_slice_follows_pattern_constructors() {
+    let program = parse_program(SRC).expect("parse");
+    let slice = slice_program(&program, &SliceTarget::Defn("high?".to_string())).expect("slice");
+    assert_eq!(slice.defns.len(), 1);
+    assert!(slice.relations.is_empty());
+    assert_eq!(slice.data_decls.len(), 1);
+    assert_eq!(slice.data_decls[0].name, "Level");
+    check_program(&slice).expect("slice should check");
+}
+
+#[test]
+fn unknown_slice_target_suggests_closest_name() {
+    let program = parse_program(SRC).expect("parse");
+    let errors = slice_program(&program, &SliceTarget::Assert("admin-read".to_string()))
+        .expect_err("unknown assert");
+    assert_eq!(errors[0].code, "E-RESOLVE");
+    assert_eq!(errors[0].suggestion(), Some("admins-read"));
+}
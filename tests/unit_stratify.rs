@@ -1,4 +1,4 @@
-use dtl::{check_program, parse_program};
+use dtl::{check_program, compute_strata, parse_program};
 
 #[test]
 fn stratify_accepts_positive_cycle() {
@@ -46,3 +46,126 @@ fn stratify_rejects_self_negation() {
     let errors = check_program(&program).expect_err("stratify should fail");
     assert!(errors.iter().any(|d| d.code == "E-STRATIFY"));
 }
+
+#[test]
+fn stratify_negative_cycle_reports_minimal_witness() {
+    let src = r#"
+        (sort Subject)
+        (relation seed (Subject))
+        (relation p (Subject))
+        (relation q (Subject))
+        (relation r (Subject))
+        (fact seed a)
+        (rule (p ?x) (and (seed ?x) (not (q ?x))))
+        (rule (q ?x) (and (seed ?x) (r ?x)))
+        (rule (r ?x) (and (seed ?x) (p ?x)))
+        (rule (q ?x) (and (seed ?x) (p ?x)))
+    "#;
+
+    let program = parse_program(src).expect("parse should succeed");
+    let errors = check_program(&program).expect_err("stratify should fail");
+    let diag = errors
+        .iter()
+        .find(|d| d.code == "E-STRATIFY")
+        .expect("stratify diagnostic");
+    assert_eq!(diag.reason(), Some("negative_cycle"));
+    assert!(
+        diag.message.contains("p -(not)-> q -> p"),
+        "message: {}",
+        diag.message
+    );
+    assert!(diag.message.contains("rules at 8:10, 11:10"));
+    assert!(
+        diag.message
+            .contains("removing `(not (q ...))` from the rule for p")
+    );
+    let span = diag.span.as_ref().expect("span of negated rule");
+    assert_eq!(span.line, 8);
+}
+
+#[test]
+fn compute_strata_exposes_layers_and_forcing_edges() {
+    let src = r#"
+        (sort Subject)
+        (relation seed (Subject))
+        (relation blocked (Subject))
+        (relation allowed (Subject))
+        (relation audited (Subject))
+        (fact seed a)
+        (rule (blocked ?x) (seed ?x))
+        (rule (allowed ?x) (and (seed ?x) (not (blocked ?x))))
+        (rule (audited ?x) (allowed ?x))
+    "#;
+
+    let program = parse_program(src).expect("parse should succeed");
+    let strata = compute_strata(&program).expect("stratify should succeed");
+    assert_eq!(
+        strata.layers(),
+        vec![
+            (0, vec!["blocked".to_string(), "seed".to_string()]),
+            (1, vec!["allowed".to_string(), "audited".to_string()]),
+        ]
+    );
+    assert_eq!(strata.level("audited"), Some(1));
+
+    let forcing = strata
+        .forcing_edges()
+        .into_iter()
+        .map(|edge| (edge.head.as_str(), edge.dep.as_str(), edge.negated))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        forcing,
+        vec![("allowed", "blocked", true), ("audited", "allowed", false)]
+    );
+}
+
+#[test]
+fn compute_strata_returns_negative_cycle_witness() {
+    let src = r#"
+        (sort Subject)
+        (relation seed (Subject))
+        (relation p (Subject))
+        (relation q (Subject))
+        (relation r (Subject))
+        (fact seed a)
+        (rule (p ?x) (and (seed ?x) (not (q ?x))))
+        (rule (q ?x) (and (seed ?x) (r ?x)))
+        (rule (r ?x) (and (seed ?x) (p ?x)))
+    "#;
+
+    let program = parse_program(src).expect("parse should succeed");
+    let err = compute_strata(&program).expect_err("stratify should fail");
+    let cycle = err.cycle.expect("negative cycle witness");
+    assert_eq!(cycle.relations(), vec!["p", "q", "r", "p"]);
+    let negated = cycle.negated_edges().collect::<Vec<_>>();
+    assert_eq!(negated.len(), 1);
+    assert_eq!((negated[0].head.as_str(), negated[0].span.line), ("p", 8));
+    assert_eq!(
+        cycle
+            .edges
+            .iter()
+            .map(|edge| edge.span.line)
+            .collect::<Vec<_>>(),
+        vec![8, 9, 10]
+    );
+
+    let diag = &err.diagnostics[0];
+    assert_eq!(diag.reason(), Some("negative_cycle"));
+    assert_eq!(
+        diag.related()
+            .iter()
+            .map(|related| (related.span.line, related.label.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (9, "rule for q depends on r here"),
+            (10, "rule for r depends on p here")
+        ]
+    );
+
+    let self_negation = parse_program(
+        "(sort S) (relation seed (S)) (relation p (S)) (rule (p ?x) (and (seed ?x) (not (p ?x))))",
+    )
+    .expect("parse should succeed");
+    let err = compute_strata(&self_negation).expect_err("stratify should fail");
+    assert_eq!(err.cycle.expect("witness").relations(), vec!["p", "p"]);
+}
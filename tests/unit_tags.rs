@@ -0,0 +1,53 @@
+use dtl::{TagKind, collect_tags, parse_program, render_ctags};
+
+const SRC: &str = r#"(sort Subject)
+(data Role (admin-role) (guest-role))
+(relation admin (Subject))
+(alias 管理者 admin)
+(assert admin-known ((u Subject)) (admin u))
+(defn is-admin ((u Subject)) Bool (admin u))
+"#;
+
+#[test]
+fn tags_cover_every_named_declaration_in_name_order() {
+    let program = parse_program(SRC).expect("parse");
+    let tags = collect_tags(&program);
+    let summary = tags
+        .iter()
+        .map(|tag| (tag.name.as_str(), tag.kind, tag.span.line))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            ("Role", TagKind::Data, 2),
+            ("Subject", TagKind::Sort, 1),
+            ("admin", TagKind::Relation, 3),
+            ("admin-known", TagKind::Assert, 5),
+            ("admin-role", TagKind::Constructor, 2),
+            ("guest-role", TagKind::Constructor, 2),
+            ("is-admin", TagKind::Defn, 6),
+            ("管理者", TagKind::Alias, 4),
+        ]
+    );
+    assert_eq!(tags[4].scope.as_deref(), Some("Role"));
+}
+
+#[test]
+fn ctags_output_is_sorted_extended_format() {
+    let program = parse_program(SRC).expect("parse");
+    let rendered = render_ctags(&collect_tags(&program), "policy.dtl");
+    let lines = rendered.lines().collect::<Vec<_>>();
+    assert!(lines[0].starts_with("!_TAG_FILE_FORMAT\t2\t"));
+    assert!(lines[1].starts_with("!_TAG_FILE_SORTED\t1\t"));
+    assert!(lines.contains(&"admin-role\tpolicy.dtl\t2;\"\tc\tline:2\tdata:Role"));
+    assert!(lines.contains(&"is-admin\tpolicy.dtl\t6;\"\tf\tline:6"));
+
+    let names = lines
+        .iter()
+        .filter(|line| !line.starts_with("!_"))
+        .map(|line| line.split('\t').next().expect("name"))
+        .collect::<Vec<_>>();
+    let mut sorted = names.clone();
+    sorted.sort_unstable();
+    assert_eq!(names, sorted);
+}
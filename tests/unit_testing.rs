@@ -0,0 +1,63 @@
+use dtl::testing::{check_source_snapshot, proof_snapshot, prove_source_snapshot};
+use dtl::{parse_program, prove_program_reference};
+
+const POLICY: &str = r#"
+(data Subject (alice) (bob))
+(relation allowed (Subject))
+(fact allowed (alice))
+(universe Subject ((alice) (bob)))
+(assert alice-allowed () (allowed (alice)))
+(assert everyone-allowed ((u Subject)) (allowed u) :message "every subject must be allowed")
+"#;
+
+#[test]
+fn check_snapshot_reports_ok_summary() {
+    assert_eq!(
+        check_source_snapshot("(defn id ((x Int)) Int x)\n"),
+        "status: ok\nfunctions_checked: 1\n"
+    );
+}
+
+#[test]
+fn check_snapshot_orders_diagnostics_and_includes_fixes() {
+    let src =
+        "(defn fib ((x Int)) Int x)\n(defn g ((x Int)) Int (fibb x))\n(defn fib ((y Int)) Int y)\n";
+    assert_eq!(
+        check_source_snapshot(src),
+        "status: error\n\
+         E-RESOLVE 2:23: unknown function/relation/constructor: fibb\n\
+         \x20 suggestion: fib\n\
+         \x20 fix: 2:24 replace 4 bytes with \"fib\"\n\
+         E-RESOLVE 3:2: duplicate function: fib\n\
+         \x20 related: first defined here at 1:2\n"
+    );
+}
+
+#[test]
+fn prove_snapshot_lists_obligations_and_counterexamples() {
+    assert_eq!(
+        prove_source_snapshot(POLICY),
+        "status: failed\n\
+         obligations: 1 proved, 1 failed, 2 total\n\
+         assert::alice-allowed: proved\n\
+         assert::everyone-allowed: failed\n\
+         \x20 message: every subject must be allowed\n\
+         \x20 let u = (bob)\n\
+         \x20 missing: allowed((bob))\n"
+    );
+}
+
+#[test]
+fn proof_snapshot_redacts_engine_differences() {
+    let program = parse_program(POLICY).expect("parse");
+    let reference = prove_program_reference(&program).expect("reference prove");
+    assert_eq!(proof_snapshot(&reference), prove_source_snapshot(POLICY));
+}
+
+#[test]
+fn prove_snapshot_reports_parse_errors() {
+    assert_eq!(
+        prove_source_snapshot("(relation allowed (Subject)"),
+        "status: error\nE-PARSE 1:1: unbalanced parentheses\n"
+    );
+}
@@ -0,0 +1,64 @@
+use dtl::types::Type;
+use dtl::{ParseOptions, parse_program, parse_program_with_options, type_at};
+
+const SRC: &str = r#"(sort Subject)
+(data Role (admin-role) (guest-role Int))
+(relation admin (Subject))
+(defn role-of ((u Subject)) Role (if (admin u) (admin-role) (guest-role 7)))
+(defn weight ((r Role)) Int (match r ((admin-role) 10) ((guest-role n) (let ((m n)) m))))
+"#;
+
+fn type_at_text(src: &str, needle: &str, nth: usize) -> Option<Type> {
+    let program = parse_program(src).expect("parse");
+    let offset = src
+        .match_indices(needle)
+        .nth(nth)
+        .map(|(idx, _)| idx)
+        .expect("needle");
+    type_at(&program, "policy.dtl", offset)
+}
+
+#[test]
+fn type_at_reports_parameters_and_innermost_expressions() {
+    assert_eq!(
+        type_at_text(SRC, "u Subject", 0),
+        Some(Type::Domain("Subject".to_string()))
+    );
+    assert_eq!(type_at_text(SRC, "7", 0), Some(Type::Int));
+    assert_eq!(
+        type_at_text(SRC, "(guest-role 7)", 0),
+        Some(Type::Adt("Role".to_string()))
+    );
+    assert!(matches!(
+        type_at_text(SRC, "(admin u)", 0),
+        Some(Type::Refine { base, .. }) if *base == Type::Bool
+    ));
+    assert_eq!(type_at_text(SRC, "(sort", 0), None);
+}
+
+#[test]
+fn type_at_sees_pattern_and_let_bindings() {
+    assert_eq!(type_at_text(SRC, "n)", 0), Some(Type::Int));
+    assert_eq!(type_at_text(SRC, "(m n)", 0), Some(Type::Int));
+    assert_eq!(type_at_text(SRC, "m))))", 0), Some(Type::Int));
+    assert_eq!(
+        type_at_text(SRC, "(admin-role) 10", 0),
+        Some(Type::Adt("Role".to_string()))
+    );
+}
+
+#[test]
+fn type_at_only_matches_the_requested_file() {
+    let options = ParseOptions {
+        source: Some("policy.dtl".to_string()),
+        ..ParseOptions::default()
+    };
+    let program = parse_program_with_options(SRC, &options).expect("parse");
+    let offset = SRC.find('7').expect("literal");
+    assert_eq!(type_at(&program, "policy.dtl", offset), Some(Type::Int));
+    assert_eq!(type_at(&program, "other.dtl", offset), None);
+
+    let unresolved = format!("{SRC}(fact missing alice)\n");
+    let program = parse_program(&unresolved).expect("parse");
+    assert_eq!(type_at(&program, "policy.dtl", offset), None);
+}
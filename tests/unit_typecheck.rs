@@ -53,3 +53,29 @@ fn typecheck_rejects_unprovable_entailment() {
     let errors = check_program(&program).expect_err("typecheck should fail");
     assert!(errors.iter().any(|d| d.code == "E-ENTAIL"));
 }
+
+#[test]
+fn typecheck_reports_entailment_failure_of_each_defn_at_its_span() {
+    let src = r#"
+(sort Subject)
+(relation allowed (Subject))
+(relation admin (Subject))
+
+(defn f ((u Subject))
+  (Refine b Bool (allowed u))
+  (admin u))
+
+(defn g ((u Subject))
+  (Refine b Bool (admin u))
+  (allowed u))
+"#;
+
+    let program = parse_program(src).expect("parse should succeed");
+    let errors = check_program(&program).expect_err("typecheck should fail");
+    let lines = errors
+        .iter()
+        .filter(|d| d.code == "E-ENTAIL")
+        .map(|d| d.span.as_ref().map(|s| s.line))
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [Some(6), Some(10)], "errors={errors:?}");
+}
@@ -58,3 +58,153 @@ fn typecheck_handles_refinement_not_formula() {
     let report = check_program(&program).expect("should pass under CWA");
     assert_eq!(report.errors, 0);
 }
+
+#[test]
+fn typecheck_decides_int_refinements_on_intervals() {
+    let src = r#"
+        (sort Slot)
+        (defn digit ((x (Refine k Int (and (>= k 0) (<= k 4)))))
+          (Refine n Int (and (>= n 0) (< n 10)))
+          (let ((twice (* x 2))) (+ twice 1)))
+        (defn positive ((x (Refine k Int (and (> k 0) (<= k 100)))))
+          (Refine n Int (and (>= n 1) (<= n 100)))
+          x)
+        (defn caller ((s Slot)) (Refine n Int (>= n 0)) (+ (digit 2) (positive 3)))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let report = check_program(&program).expect("interval refinements should pass");
+    assert_eq!(report.errors, 0);
+
+    for (src, code) in [
+        (
+            "(sort Slot) (defn f ((s Slot)) (Refine n Int (< n 10)) (+ 9 1))",
+            "E-ENTAIL",
+        ),
+        (
+            "(defn f ((x (Refine k Int (>= k 1)))) Int x) (defn g ((y Int)) Int (f 0))",
+            "E-TYPE",
+        ),
+        (
+            "(defn f ((x Int)) (Refine n Int (>= n 0)) (- x 1))",
+            "E-ENTAIL",
+        ),
+        (
+            "(defn f ((n (Refine k Int (>= k 9223372036854775806)))) (Refine r Int (> r 0)) (+ n 1))",
+            "E-ENTAIL",
+        ),
+        (
+            "(defn f ((n (Refine k Int (>= k 0)))) (Refine r Int (> r 0)) (+ n 1))",
+            "E-ENTAIL",
+        ),
+    ] {
+        let program = parse_program(src).expect("parse");
+        let errors = check_program(&program).expect_err("interval check should fail");
+        assert!(errors.iter().any(|d| d.code == code), "{src}: {errors:?}");
+    }
+}
+
+#[test]
+fn typecheck_narrows_variables_by_relation_condition_in_then_branch() {
+    let src = r#"
+        (sort User)
+        (relation active (User))
+        (relation verified (User))
+        (defn grant ((u (Refine x User (and (active x) (verified x))))) Bool true)
+        (defn maybe-grant ((u (Refine a User (active a)))) Bool
+          (if (verified u) (grant u) false))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let report = check_program(&program).expect("narrowed call should typecheck");
+    assert_eq!(report.errors, 0);
+
+    let src = r#"
+        (sort User)
+        (relation verified (User))
+        (defn grant ((u (Refine x User (verified x)))) Bool true)
+        (defn maybe-grant ((u User)) Bool (if (verified u) false (grant u)))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let errors = check_program(&program).expect_err("else branch is not narrowed");
+    assert!(errors.iter().any(|d| d.code == "E-TYPE"), "{errors:?}");
+
+    let src = r#"
+        (sort User)
+        (data Box (box User))
+        (relation verified (User))
+        (defn f ((v User) (b Box)) Bool
+          (if (verified v) (match b ((box v) true)) false))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let report = check_program(&program).expect("shadowing pattern should typecheck");
+    assert_eq!(report.errors, 0);
+}
+
+#[test]
+fn typecheck_refines_match_arms_by_the_matched_constructor() {
+    let src = r#"
+        (sort User)
+        (data Slot (Empty) (Held User))
+        (relation reserved (Slot))
+        (relation owns (User Slot))
+        (relation holder (User))
+        (relation co-owner (User))
+        (rule (holder ?u) (reserved (Held ?u)))
+        (relation occupied (Slot))
+        (rule (co-owner ?x) (owns ?x (Held ?y)))
+        (rule (occupied (Held ?u)) (holder ?u))
+        (defn notify ((u (Refine x User (holder x)))) Bool true)
+        (defn share ((u (Refine x User (co-owner x)))) Bool true)
+        (defn release ((s (Refine x Slot (occupied x)))) Bool true)
+        (defn on-reserved ((s (Refine r Slot (reserved r)))) Bool
+          (match s
+            ((Held u) (notify u))
+            (Empty false)))
+        (defn release-reserved ((s (Refine r Slot (reserved r)))) Bool
+          (match s
+            ((Held _) (release s))
+            (Empty false)))
+        (defn on-owned ((u User) (s Slot)) Bool
+          (if (owns u s)
+            (match s
+              ((Held _) (share u))
+              (Empty false))
+            false))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let report = check_program(&program).expect("arm refinements should typecheck");
+    assert_eq!(report.errors, 0);
+
+    let src = r#"
+        (sort User)
+        (data Slot (Empty) (Held User))
+        (relation reserved (Slot))
+        (relation holder (User))
+        (rule (holder ?u) (reserved (Held ?u)))
+        (defn notify ((u (Refine x User (holder x)))) Bool true)
+        (defn on-any ((s Slot)) Bool
+          (match s
+            ((Held u) (notify u))
+            (Empty false)))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let errors = check_program(&program).expect_err("unrefined scrutinee proves nothing");
+    assert!(errors.iter().any(|d| d.code == "E-TYPE"), "{errors:?}");
+
+    let src = r#"
+        (sort User)
+        (data Slot (Empty) (Held User))
+        (relation reserved (Slot))
+        (relation holder (User))
+        (relation occupied (Slot))
+        (rule (holder ?u) (reserved (Held ?u)))
+        (rule (occupied (Held ?u)) (holder ?u))
+        (defn release ((s (Refine x Slot (occupied x)))) Bool true)
+        (defn release-empty ((s (Refine r Slot (reserved r)))) Bool
+          (match s
+            ((Held _) true)
+            (Empty (release s))))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let errors = check_program(&program).expect_err("the Empty arm does not make s occupied");
+    assert!(errors.iter().any(|d| d.code == "E-TYPE"), "{errors:?}");
+}
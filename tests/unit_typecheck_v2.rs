@@ -34,21 +34,41 @@ fn typecheck_accepts_structural_tail_recursion() {
 }
 
 #[test]
-fn typecheck_rejects_non_tail_recursive_call() {
+fn typecheck_accepts_non_tail_structural_recursion() {
+    let src = r#"
+        (data Nat (z) (s Nat))
+        (data NatList (nil) (cons Nat NatList))
+        (defn ok ((n Nat)) Bool
+          (match n
+            ((z) true)
+            ((s m) (if (ok m) true false))))
+        (defn succs ((xs NatList)) NatList
+          (match xs
+            ((nil) (nil))
+            ((cons x rest) (cons (s x) (succs rest)))))
+    "#;
+
+    let program = parse_program(src).expect("parse");
+    let report = check_program(&program).expect("should pass");
+    assert_eq!(report.errors, 0);
+}
+
+#[test]
+fn typecheck_rejects_non_tail_recursive_call_without_descent() {
     let src = r#"
         (data Nat (z) (s Nat))
         (defn bad ((n Nat)) Bool
           (match n
             ((z) true)
-            ((s m) (if (bad m) true false))))
+            ((s m) (if (bad n) true false))))
     "#;
 
     let program = parse_program(src).expect("parse");
     let errs = check_program(&program).expect_err("should fail");
     let diag = first_totality_error(&errs);
-    assert!(diag.message.contains("tail position"));
-    assert_eq!(diag.reason(), Some("non_tail_recursive_call"));
-    assert_eq!(diag.arg_indices(), None);
+    assert!(diag.message.contains("not structurally decreasing"));
+    assert_eq!(diag.reason(), Some("non_decreasing_argument"));
+    assert_eq!(diag.arg_indices(), Some(&[1][..]));
 }
 
 #[test]
@@ -105,7 +125,7 @@ fn typecheck_accepts_mutual_recursion_with_structural_decrease() {
 }
 
 #[test]
-fn typecheck_rejects_mutual_recursion_when_edge_is_non_tail() {
+fn typecheck_accepts_mutual_recursion_through_non_tail_edge() {
     let src = r#"
         (data Nat (z) (s Nat))
         (defn f ((n Nat)) Bool
@@ -119,10 +139,8 @@ fn typecheck_rejects_mutual_recursion_when_edge_is_non_tail() {
     "#;
 
     let program = parse_program(src).expect("parse");
-    let errs = check_program(&program).expect_err("should fail");
-    let diag = first_totality_error(&errs);
-    assert_eq!(diag.reason(), Some("non_tail_recursive_call"));
-    assert!(diag.message.contains("f -> g"));
+    let report = check_program(&program).expect("should pass");
+    assert_eq!(report.errors, 0);
 }
 
 #[test]
@@ -238,6 +256,53 @@ fn typecheck_rejects_unreachable_match_arm() {
     assert!(errs.iter().any(|d| d.message.contains("unreachable")));
 }
 
+#[test]
+fn typecheck_checks_nested_patterns_over_mutually_recursive_data() {
+    let src = |arms: &str| {
+        format!(
+            r#"
+            (data Stmt (skip) (eval Expr) (seq Stmt Stmt))
+            (data Expr (lit Int) (block Stmt))
+            (defn expr-ok ((e Expr)) Bool
+              (match e
+                ((lit n) true)
+                ((block s) (stmt-ok s))))
+            (defn stmt-ok ((s Stmt)) Bool
+              (match s
+                ((skip) true)
+                ((eval e) (expr-ok e))
+                ((seq a b) (if (stmt-ok a) (stmt-ok b) false))))
+            (defn shallow ((s Stmt)) Bool
+              (match s
+                {arms}))
+        "#
+        )
+    };
+
+    let exhaustive = src("((eval (lit n)) true) ((eval (block t)) false) (_ true)");
+    let program = parse_program(&exhaustive).expect("parse");
+    let report = check_program(&program).expect("should pass");
+    assert_eq!(report.errors, 0);
+
+    let missing = src("((skip) true) ((eval (lit n)) true) ((seq a b) false)");
+    let program = parse_program(&missing).expect("parse");
+    let errs = check_program(&program).expect_err("should fail");
+    assert!(
+        errs.iter()
+            .any(|d| d.code == "E-MATCH" && d.message == "non-exhaustive match"),
+        "errs={errs:?}"
+    );
+
+    let duplicate = src("((eval _) true) ((eval (lit n)) false) (_ true)");
+    let program = parse_program(&duplicate).expect("parse");
+    let errs = check_program(&program).expect_err("should fail");
+    assert!(
+        errs.iter()
+            .any(|d| d.message == "unreachable duplicate constructor pattern: eval"),
+        "errs={errs:?}"
+    );
+}
+
 #[test]
 fn typecheck_accepts_recursive_refine_with_universe_fallback() {
     let src = r#"
@@ -312,3 +377,409 @@ fn typecheck_treats_nfc_equivalent_sort_names_as_same() {
             .any(|d| d.message.contains("duplicate sort: ガ"))
     );
 }
+
+#[test]
+fn function_arity_mismatch_points_at_signature() {
+    let program = parse_program("(defn f ((x Int)) Int x)\n(defn g ((x Int)) Int (f x x))\n")
+        .expect("parse should succeed");
+    let errs = check_program(&program).expect_err("check should fail");
+    let diag = errs
+        .iter()
+        .find(|d| d.message.contains("function f arity mismatch"))
+        .expect("arity mismatch");
+    assert_eq!(diag.related()[0].label, "function signature");
+    assert_eq!(
+        (diag.related()[0].span.line, diag.related()[0].span.column),
+        (1, 2)
+    );
+}
+
+#[test]
+fn arity_mismatch_fix_removes_surplus_arguments() {
+    let src = "(defn f ((x Int)) Int x)\n(defn g ((x Int)) Int (f x 1 2))\n";
+    let program = parse_program(src).expect("parse should succeed");
+    let errs = check_program(&program).expect_err("check should fail");
+    let diag = errs
+        .iter()
+        .find(|d| d.message.contains("function f arity mismatch"))
+        .expect("arity mismatch");
+    let fix = &diag.fixes()[0];
+    assert_eq!(&src[fix.span.start..fix.span.end], "1 2");
+    assert_eq!(fix.replacement, "");
+
+    let program = parse_program("(defn f ((x Int) (y Int)) Int x)\n(defn g ((x Int)) Int (f x))\n")
+        .expect("parse should succeed");
+    let errs = check_program(&program).expect_err("check should fail");
+    assert!(errs.iter().all(|d| d.fixes().is_empty()), "errs={errs:?}");
+}
+
+#[test]
+fn typecheck_accepts_int_recursion_with_termination_measure() {
+    let measured = r#"
+        (relation zero (Int))
+        (defn down ((n Int)) Bool
+          (if (zero n) true (down (- n 1)))
+          :decreases n)
+    "#;
+    let program = parse_program(measured).expect("parse");
+    let report = check_program(&program).expect("should pass");
+    assert_eq!(report.errors, 0);
+
+    let unmeasured = r#"
+        (relation zero (Int))
+        (defn down ((n Int)) Bool
+          (if (zero n) true (down (- n 1))))
+    "#;
+    let program = parse_program(unmeasured).expect("parse");
+    let errs = check_program(&program).expect_err("should fail");
+    assert_eq!(
+        first_totality_error(&errs).reason(),
+        Some("no_adt_parameter")
+    );
+}
+
+#[test]
+fn typecheck_rejects_termination_measure_that_recurses() {
+    let src = r#"
+        (relation zero (Int))
+        (defn down ((n Int)) Bool
+          (if (zero n) true (down (- n 1)))
+          :measure (size n))
+        (defn size ((n Int)) Int
+          (if (down n) n n))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let errs = check_program(&program).expect_err("should fail");
+    let diag = first_totality_error(&errs);
+    assert!(
+        diag.message
+            .contains("termination measure of down calls size of its own recursive group"),
+        "{diag:?}"
+    );
+    assert_eq!(diag.reason(), Some("recursive_measure"));
+
+    let errs = parse_program(
+        "(relation zero (Int)) (defn down ((n Int)) Bool (if (zero n) true (down (- n 1))) :measure (zero n))",
+    )
+    .and_then(|program| check_program(&program))
+    .expect_err("Bool measure");
+    assert!(
+        errs.iter()
+            .any(|d| d.message.contains("termination measure must be Int")),
+        "errs={errs:?}"
+    );
+}
+
+#[test]
+fn typecheck_accepts_lexicographic_descent_across_parameters_and_functions() {
+    let src = r#"
+        (data Nat (z) (s Nat))
+        (defn walk ((m Nat) (n Nat)) Bool
+          (match m
+            ((z) true)
+            ((s m1)
+              (match n
+                ((z) (walk m1 (s (z))))
+                ((s n1) (walk m n1))))))
+        (defn enter ((x Nat)) Bool
+          (leave x))
+        (defn leave ((x Nat)) Bool
+          (match x
+            ((z) true)
+            ((s y) (enter y))))
+    "#;
+
+    let program = parse_program(src).expect("parse");
+    let report = check_program(&program).expect("should pass");
+    assert_eq!(report.errors, 0);
+}
+
+#[test]
+fn typecheck_rejects_calls_without_a_common_lexicographic_order() {
+    let src = r#"
+        (data Nat (z) (s Nat))
+        (defn bounce ((a Nat) (b Nat)) Bool
+          (match a
+            ((z)
+              (match b
+                ((z) true)
+                ((s b1) (bounce (s a) b1))))
+            ((s a1) (bounce a1 (s b)))))
+    "#;
+
+    let program = parse_program(src).expect("parse");
+    let errs = check_program(&program).expect_err("should fail");
+    let totality = errs
+        .iter()
+        .filter(|d| d.code == "E-TOTAL")
+        .collect::<Vec<_>>();
+    assert_eq!(totality.len(), 2, "{errs:?}");
+    assert!(totality.iter().all(|d| {
+        d.message
+            .contains("recursive call is not lexicographically decreasing in bounce -> bounce")
+            && d.reason() == Some("non_decreasing_argument")
+            && d.arg_indices() == Some(&[1, 2][..])
+    }));
+}
+
+#[test]
+fn typecheck_reports_expected_type_and_candidates_for_typed_holes() {
+    let src = r#"
+        (data Subject (alice) (bob))
+        (relation allowed (Subject))
+        (defn pick ((u Subject)) Subject u)
+        (defn can ((u Subject) (n Int)) Bool
+          (let ((v (pick u)))
+            (if (allowed _?) _? (allowed (pick _?)))))
+    "#;
+
+    let program = parse_program(src).expect("parse");
+    let errs = check_program(&program).expect_err("holes never type check");
+    let holes = errs
+        .iter()
+        .filter(|d| d.code == "E-HOLE")
+        .map(|d| d.message.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        holes,
+        vec![
+            "typed hole: expected Subject; in scope: n: Int, u: Subject, v: Subject; candidates: u, v, alice, bob, pick",
+            "typed hole: expected Bool; in scope: n: Int, u: Subject, v: Subject; candidates: allowed, can",
+            "typed hole: expected Subject; in scope: n: Int, u: Subject, v: Subject; candidates: u, v, alice, bob, pick",
+        ]
+    );
+    assert_eq!(dtl::failure_class(&errs), Some(dtl::FailureClass::Type));
+
+    let formatted = dtl::format_source(src, Default::default()).expect("format");
+    assert!(formatted.contains("(allowed _?)"), "{formatted}");
+}
+
+#[test]
+fn typecheck_reports_every_type_error_in_a_defn_body() {
+    let src = r#"
+        (sort User)
+        (data Shape (circle Int) (square Int))
+        (relation admin (User))
+        (defn f ((u User) (n Int) (s Shape)) Int
+          (let ((a (+ n true))
+                (b (admin n)))
+            (if n
+                (+ a 1)
+                (match s
+                  ((circle r) (+ r 'x))
+                  ((square w) w)))))
+    "#;
+
+    let program = parse_program(src).expect("parse");
+    let errs = check_program(&program).expect_err("must fail");
+    let messages = errs
+        .iter()
+        .map(|d| (d.code, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                "E-TYPE",
+                "operator argument type mismatch: got Bool, expected Int"
+            ),
+            (
+                "E-TYPE",
+                "relation argument type mismatch: got Int, expected Domain(\"User\")"
+            ),
+            (
+                "E-TYPE",
+                "if condition must be Bool: got Int, expected Bool"
+            ),
+            (
+                "E-TYPE",
+                "operator argument type mismatch: got Symbol, expected Int"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn typecheck_checks_clauses_for_combined_exhaustiveness() {
+    let ok = parse_program(
+        r#"
+        (data Nat (z) (s Nat))
+        (defn both ((a Bool) (b Nat)) Bool
+          :clauses (((true (s _)) true)
+                    ((_ _) false)))
+        (defn add ((n Nat) (m Nat)) Nat
+          :clauses ((((z) m) m)
+                    (((s k) m) (s (add k m)))))
+    "#,
+    )
+    .expect("parse");
+    check_program(&ok).expect("covering clauses should typecheck");
+
+    let cases = [
+        (
+            "(data Nat (z) (s Nat)) (defn f ((a Bool) (n Nat)) Nat :clauses (((true (z)) (z)) ((false _) n)))",
+            "E-MATCH",
+            "non-exhaustive match",
+        ),
+        (
+            "(data Nat (z) (s Nat)) (defn f ((n Nat)) Nat :clauses ((((z)) n) (((s k)) k) ((_) n)))",
+            "E-MATCH",
+            "unreachable clause in f",
+        ),
+        (
+            "(data Nat (z) (s Nat)) (defn f ((n Nat)) Nat :clauses ((((s)) n) (((s k)) k) (((z)) n)))",
+            "E-MATCH",
+            "constructor pattern arity differs between clauses: s",
+        ),
+        (
+            "(defn f ((x Int)) Bool :clauses (((0) true) ((_) 1)))",
+            "E-MATCH",
+            "match arms have incompatible result types",
+        ),
+    ];
+    for (src, code, needle) in cases {
+        let program = parse_program(src).expect("parse");
+        let errs = check_program(&program).expect_err("check should fail");
+        assert!(
+            errs.iter()
+                .any(|d| d.code == code && d.message.contains(needle)),
+            "src={src} errs={errs:?}"
+        );
+    }
+}
+
+#[test]
+fn typecheck_checks_facts_and_rule_heads_against_relation_refinements() {
+    let src = r#"
+        (sort Person)
+        (relation registered (Person))
+        (relation person (Person))
+        (relation age ((Refine p Person (registered p)) (Refine n Int (>= n 0))))
+        (relation member ((Refine p Person (registered p))))
+        (fact registered alice)
+        (fact person alice)
+        (fact person bob)
+        (fact age alice 30)
+        (fact age bob 20)
+        (fact age alice -1)
+        (rule (member ?p) (person ?p))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let errs = check_program(&program).expect_err("refinements should be violated");
+    let messages = errs
+        .iter()
+        .filter(|d| d.code == "E-ENTAIL")
+        .map(|d| (d.message.as_str(), d.span.as_ref().map(|s| s.line)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            (
+                "fact age violates argument 1 refinement (Refine p Person (registered p))",
+                Some(11)
+            ),
+            (
+                "fact age violates argument 2 refinement (Refine n Int (>= n 0))",
+                Some(12)
+            ),
+            (
+                "rule for member derives (member bob), which violates argument 1 refinement (Refine p Person (registered p))",
+                Some(13)
+            ),
+        ],
+        "errs={errs:?}"
+    );
+
+    let ok = src
+        .replace("(fact age bob 20)", "")
+        .replace("(fact age alice -1)", "")
+        .replace("(person ?p)", "(and (person ?p) (registered ?p))");
+    check_program(&parse_program(&ok).expect("parse")).expect("refinements should hold");
+}
+
+#[test]
+fn typecheck_checks_facts_and_rule_heads_against_relation_keys() {
+    let src = r#"
+        (sort Person)
+        (data Tier (gold) (silver))
+        (relation member (Person Tier) :key (1))
+        (relation vip (Person))
+        (fact member alice (gold))
+        (fact member alice (silver))
+        (fact vip bob)
+        (fact member bob (gold))
+        (rule (member ?p (silver)) (vip ?p))
+    "#;
+    let program = parse_program(src).expect("parse");
+    let errs = check_program(&program).expect_err("keys should be broken");
+    let messages = errs
+        .iter()
+        .filter(|d| d.code == "E-KEY")
+        .map(|d| (d.message.as_str(), d.span.as_ref().map(|s| s.line)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            (
+                "fact member breaks its :key, as (member alice (gold)) has the same key",
+                Some(7)
+            ),
+            (
+                "rule for member derives (member bob (silver)), which breaks its :key, as (member bob (gold)) has the same key",
+                Some(10)
+            ),
+        ],
+        "errs={errs:?}"
+    );
+
+    let ok = src
+        .replace("(fact member alice (silver))", "")
+        .replace("(fact vip bob)", "");
+    check_program(&parse_program(&ok).expect("parse")).expect("keys should hold");
+}
+
+#[test]
+fn typecheck_checks_letfn_local_functions() {
+    let ok = parse_program(
+        r#"
+        (defn f ((x Int)) Int
+          (letfn ((add ((n Int)) Int (+ n x))
+                  (twice ((n Int)) Int (add (add n))))
+            (twice 1)))
+    "#,
+    )
+    .expect("parse");
+    check_program(&ok).expect("captured helpers should typecheck");
+
+    let cases = [
+        (
+            "(defn f ((x Int)) Int (letfn ((g ((n Int)) Int n)) (g true)))",
+            "E-TYPE",
+            "argument type mismatch",
+        ),
+        (
+            "(defn f ((x Int)) Int (letfn ((g ((n Int)) Int true)) (g x)))",
+            "E-TYPE",
+            "local function return type mismatch",
+        ),
+        (
+            "(defn f ((x Int)) Int (letfn ((g ((n Int)) Int (g n))) (g x)))",
+            "E-RESOLVE",
+            "unknown function/relation/constructor: g",
+        ),
+        (
+            "(defn f ((x Int)) Int (letfn ((x ((n Int)) Int n)) 0))",
+            "E-RESOLVE",
+            "local function shadows an existing name: x",
+        ),
+    ];
+    for (src, code, needle) in cases {
+        let program = parse_program(src).expect("parse");
+        let errs = check_program(&program).expect_err("check should fail");
+        assert!(
+            errs.iter()
+                .any(|d| d.code == code && d.message.contains(needle)),
+            "src={src} errs={errs:?}"
+        );
+    }
+}
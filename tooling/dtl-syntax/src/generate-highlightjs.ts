@@ -1,10 +1,52 @@
-import { type DtlSyntaxSpec, uniqueTokens } from "./syntax-spec";
+import { type DtlSyntaxSpec, escapeRegexLiteral, uniqueTokens } from "./syntax-spec";
+
+// Regex sources for comments and strings, compiled by the runtime.
+interface HighlightPatterns {
+  readonly lineComment: string;
+  readonly blockCommentBegin: string;
+  readonly blockCommentEnd: string;
+  readonly datumCommentList: string;
+  readonly datumCommentAtom: string;
+  readonly quotedAtom: string;
+  readonly multiLineBegin: string;
+  readonly multiLineEnd: string;
+  readonly rawBegin: string;
+  readonly rawEnd: string;
+  readonly quote: string;
+  readonly escape: string;
+}
 
 interface HighlightPayload {
   readonly keywordTokens: readonly string[];
   readonly typeKeywords: readonly string[];
   readonly booleanLiterals: readonly string[];
+  readonly operators: readonly string[];
   readonly surfaceTags: readonly string[];
+  readonly patterns: HighlightPatterns;
+}
+
+function buildPatterns(spec: DtlSyntaxSpec): HighlightPatterns {
+  const { comments, strings } = spec;
+  const quote = escapeRegexLiteral(strings.quote);
+  const multiLineQuote = escapeRegexLiteral(strings.multiLineQuote);
+  const rawHash = escapeRegexLiteral(strings.rawHash);
+  const datum = escapeRegexLiteral(comments.datum);
+  const quotedAtom = `${quote}(?:[^${quote}\\\\]|\\\\.)*${quote}`;
+
+  return {
+    lineComment: escapeRegexLiteral(comments.line),
+    blockCommentBegin: escapeRegexLiteral(comments.blockBegin),
+    blockCommentEnd: escapeRegexLiteral(comments.blockEnd),
+    datumCommentList: `${datum}\\s*\\(`,
+    datumCommentAtom: `${datum}\\s*(?:${quotedAtom}|[^\\s()]+)?`,
+    quotedAtom,
+    multiLineBegin: multiLineQuote,
+    multiLineEnd: `${multiLineQuote}(?!${quote})`,
+    rawBegin: `((?:${rawHash})+)${quote}`,
+    rawEnd: `${quote}((?:${rawHash})+)`,
+    quote,
+    escape: `\\\\(?:${strings.escapes.map(escapeRegexLiteral).join("|")})`
+  };
 }
 
 function buildPayload(spec: DtlSyntaxSpec): HighlightPayload {
@@ -16,7 +58,9 @@ function buildPayload(spec: DtlSyntaxSpec): HighlightPayload {
     ),
     typeKeywords: spec.typeKeywords,
     booleanLiterals: spec.booleanLiterals,
-    surfaceTags: spec.surfaceTags
+    operators: spec.operators,
+    surfaceTags: spec.surfaceTags,
+    patterns: buildPatterns(spec)
   };
 }
 
@@ -53,30 +97,72 @@ export function generateHighlightJsScript(spec: DtlSyntaxSpec): string {
     const typePattern = tokenPattern(payload.typeKeywords);
     const literalPattern = tokenPattern(payload.booleanLiterals);
     const surfaceTagPattern = tokenPattern(payload.surfaceTags);
+    const dtlOperatorPattern = tokenPattern(payload.operators);
+    const patterns = payload.patterns;
+    const pattern = (source) => new RegExp(source, "u");
     const dtlNumberPattern = supportsLookbehind
       ? /(?<![^\\s()])[-+]?\\d+(?![^\\s()])/u
       : /[-+]?\\d+/u;
     const dtlVariablePattern = supportsLookbehind
       ? /(?<![^\\s()])\\?[^\\s()";]+(?![^\\s()])/u
       : /\\?[^\\s()";]+/u;
-    const dtlOperatorPattern = supportsLookbehind
-      ? /(?<![^\\s()])->(?![^\\s()])/u
-      : /->/u;
     const dtlWildcardPattern = supportsLookbehind
       ? /(?<![^\\s()])_(?![^\\s()])/u
       : /_/u;
 
+    const escape = { begin: pattern(patterns.escape), relevance: 0 };
+    const datumCommentBody = {
+      begin: /\\(/u,
+      end: /\\)/u,
+      contains: [{ begin: pattern(patterns.quotedAtom) }, "self"]
+    };
+
     return {
       name: "DTL",
       aliases: ["dtl"],
       unicodeRegex: true,
       contains: [
-        hljs.COMMENT(";", "$"),
+        {
+          className: "comment",
+          begin: pattern(patterns.blockCommentBegin),
+          end: pattern(patterns.blockCommentEnd),
+          contains: ["self"]
+        },
+        {
+          className: "comment",
+          begin: pattern(patterns.datumCommentList),
+          end: /\\)/u,
+          contains: [{ begin: pattern(patterns.quotedAtom) }, datumCommentBody]
+        },
+        {
+          className: "comment",
+          begin: pattern(patterns.datumCommentAtom)
+        },
+        hljs.COMMENT(patterns.lineComment, "$"),
+        {
+          className: "string",
+          begin: pattern(patterns.multiLineBegin),
+          end: pattern(patterns.multiLineEnd),
+          contains: [escape]
+        },
+        {
+          className: "string",
+          begin: pattern(patterns.rawBegin),
+          end: pattern(patterns.rawEnd),
+          "on:begin": (match, response) => {
+            response.data.rawHashes = match[1];
+          },
+          "on:end": (match, response) => {
+            if (response.data.rawHashes !== match[1]) {
+              response.ignoreMatch();
+            }
+          }
+        },
         {
           className: "string",
-          begin: /"/u,
-          end: /"/u,
-          illegal: /\\n/u
+          begin: pattern(patterns.quote),
+          end: pattern(patterns.quote),
+          contains: [escape]
         },
         {
           className: "number",
@@ -1,17 +1,105 @@
-import { type DtlSyntaxSpec, uniqueTokens } from "./syntax-spec";
+import {
+  type DtlCommentSyntax,
+  type DtlStringSyntax,
+  type DtlSyntaxSpec,
+  escapeRegexLiteral,
+  uniqueTokens
+} from "./syntax-spec";
 
 const TEXTMATE_SCHEMA_URL =
   "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json";
 
-function escapeRegexLiteral(value: string): string {
-  return value.replace(/[.*+?^${}()|[\\]\\]/g, "\\$&");
-}
-
 function delimitedTokenPattern(tokens: readonly string[]): string {
   const body = tokens.map(escapeRegexLiteral).join("|");
   return `(?<![^\\s()])(?:${body})(?![^\\s()])`;
 }
 
+// A quoted atom, skipped whole so parentheses inside it do not count.
+function quotedAtomPattern(strings: DtlStringSyntax): string {
+  const quote = escapeRegexLiteral(strings.quote);
+  return `${quote}(?:[^${quote}\\\\]|\\\\.)*${quote}`;
+}
+
+function commentRepository(
+  comments: DtlCommentSyntax,
+  strings: DtlStringSyntax
+): Record<string, unknown> {
+  const datum = escapeRegexLiteral(comments.datum);
+  const quotedAtom = quotedAtomPattern(strings);
+
+  return {
+    comment: {
+      patterns: [
+        { include: "#blockComment" },
+        { include: "#datumComment" },
+        {
+          name: "comment.line.semicolon.dtl",
+          match: `${escapeRegexLiteral(comments.line)}.*$`
+        }
+      ]
+    },
+    blockComment: {
+      name: "comment.block.dtl",
+      begin: escapeRegexLiteral(comments.blockBegin),
+      end: escapeRegexLiteral(comments.blockEnd),
+      patterns: [{ include: "#blockComment" }]
+    },
+    datumComment: {
+      patterns: [
+        {
+          name: "comment.block.datum.dtl",
+          begin: `${datum}\\s*\\(`,
+          end: "\\)",
+          patterns: [{ include: "#datumCommentBody" }]
+        },
+        {
+          name: "comment.block.datum.dtl",
+          match: `${datum}\\s*(?:${quotedAtom}|[^\\s()]+)?`
+        }
+      ]
+    },
+    datumCommentBody: {
+      patterns: [
+        { match: quotedAtom },
+        { begin: "\\(", end: "\\)", patterns: [{ include: "#datumCommentBody" }] }
+      ]
+    }
+  };
+}
+
+function stringRepository(strings: DtlStringSyntax): Record<string, unknown> {
+  const quote = escapeRegexLiteral(strings.quote);
+  const multiLineQuote = escapeRegexLiteral(strings.multiLineQuote);
+  const escapes = strings.escapes.map(escapeRegexLiteral).join("|");
+
+  return {
+    string: {
+      patterns: [
+        {
+          name: "string.quoted.triple.dtl",
+          begin: multiLineQuote,
+          end: `${multiLineQuote}(?!${quote})`,
+          patterns: [{ include: "#stringEscape" }]
+        },
+        {
+          name: "string.quoted.other.raw.dtl",
+          begin: `(?<![^\\s()])((?:${escapeRegexLiteral(strings.rawHash)})+)${quote}`,
+          end: `${quote}\\1`
+        },
+        {
+          name: "string.quoted.double.dtl",
+          begin: quote,
+          end: quote,
+          patterns: [{ include: "#stringEscape" }]
+        }
+      ]
+    },
+    stringEscape: {
+      patterns: [{ name: "constant.character.escape.dtl", match: `\\\\(?:${escapes})` }]
+    }
+  };
+}
+
 export function generateTextMateGrammar(spec: DtlSyntaxSpec): Record<string, unknown> {
   const keywordTokens = uniqueTokens(
     spec.coreTopLevelKeywords,
@@ -36,12 +124,8 @@ export function generateTextMateGrammar(spec: DtlSyntaxSpec): Record<string, unk
       { include: "#keyword" }
     ],
     repository: {
-      comment: {
-        patterns: [{ name: "comment.line.semicolon.dtl", match: ";.*$" }]
-      },
-      string: {
-        patterns: [{ name: "string.quoted.double.dtl", match: '"[^"\\n]*"' }]
-      },
+      ...commentRepository(spec.comments, spec.strings),
+      ...stringRepository(spec.strings),
       number: {
         patterns: [
           {
@@ -85,8 +169,8 @@ export function generateTextMateGrammar(spec: DtlSyntaxSpec): Record<string, unk
       operator: {
         patterns: [
           {
-            name: "keyword.operator.function-type.dtl",
-            match: "(?<![^\\s()])->(?![^\\s()])"
+            name: "keyword.operator.dtl",
+            match: delimitedTokenPattern(spec.operators)
           }
         ]
       },
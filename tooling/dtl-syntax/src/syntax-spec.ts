@@ -1,15 +1,35 @@
+export interface DtlCommentSyntax {
+  readonly line: string;
+  readonly blockBegin: string;
+  readonly blockEnd: string;
+  readonly datum: string;
+}
+
+export interface DtlStringSyntax {
+  readonly quote: string;
+  readonly multiLineQuote: string;
+  readonly rawHash: string;
+  readonly escapes: readonly string[];
+}
+
 export interface DtlSyntaxSpec {
   readonly coreTopLevelKeywords: readonly string[];
   readonly surfaceTopLevelKeywords: readonly string[];
   readonly specialFormKeywords: readonly string[];
   readonly typeKeywords: readonly string[];
   readonly booleanLiterals: readonly string[];
+  readonly operators: readonly string[];
   readonly surfaceTags: readonly string[];
+  readonly comments: DtlCommentSyntax;
+  readonly strings: DtlStringSyntax;
 }
 
 export const DTL_SYNTAX_SPEC: DtlSyntaxSpec = {
   coreTopLevelKeywords: [
     "import",
+    "alias",
+    "defconst",
+    "defmacro",
     "sort",
     "data",
     "relation",
@@ -17,10 +37,19 @@ export const DTL_SYNTAX_SPEC: DtlSyntaxSpec = {
     "rule",
     "assert",
     "universe",
-    "defn"
+    "defn",
+    "when-profile",
+    "project",
+    "module",
+    "reference",
+    "contract",
+    "quality-gate"
   ],
   surfaceTopLevelKeywords: [
     "インポート",
+    "同義語",
+    "定数",
+    "マクロ",
     "型",
     "データ",
     "関係",
@@ -28,33 +57,95 @@ export const DTL_SYNTAX_SPEC: DtlSyntaxSpec = {
     "規則",
     "検証",
     "宇宙",
-    "関数"
+    "関数",
+    "プロファイル限定",
+    "プロジェクト",
+    "モジュール",
+    "参照",
+    "契約",
+    "品質ゲート"
   ],
-  specialFormKeywords: ["and", "not", "let", "if", "match"],
+  specialFormKeywords: ["and", "not", "let", "letfn", "if", "match"],
   typeKeywords: ["Bool", "Int", "Symbol", "Refine", "Adt"],
   booleanLiterals: ["true", "false"],
+  operators: ["->", "!="],
   surfaceTags: [
+    ":別名",
+    ":alias",
+    ":正規",
+    ":canonical",
     ":コンストラクタ",
     ":constructors",
     ":ctors",
     ":引数",
     ":args",
+    ":キー",
+    ":key",
     ":項",
     ":terms",
     ":頭",
     ":head",
     ":本体",
     ":body",
+    ":スコーレム",
+    ":skolem",
     ":params",
     ":式",
     ":formula",
     ":値",
+    ":value",
     ":values",
     ":戻り",
-    ":ret"
-  ]
+    ":ret",
+    ":節",
+    ":clauses",
+    ":尺度",
+    ":measure",
+    ":減少",
+    ":decreases",
+    ":説明",
+    ":doc",
+    ":メッセージ",
+    ":message",
+    ":プロファイル",
+    ":profile",
+    ":名前",
+    ":name",
+    ":概要",
+    ":summary",
+    ":パス",
+    ":path",
+    ":カテゴリ",
+    ":category",
+    ":元",
+    ":from",
+    ":先",
+    ":to",
+    ":出典",
+    ":source",
+    ":コマンド",
+    ":command",
+    ":必須",
+    ":required"
+  ],
+  comments: {
+    line: ";",
+    blockBegin: "#|",
+    blockEnd: "|#",
+    datum: "#;"
+  },
+  strings: {
+    quote: "\"",
+    multiLineQuote: "\"\"\"",
+    rawHash: "#",
+    escapes: ["\\", "\"", "n", "t", "r"]
+  }
 };
 
 export function uniqueTokens(...groups: ReadonlyArray<readonly string[]>): string[] {
   return [...new Set(groups.flat())];
 }
+
+export function escapeRegexLiteral(value: string): string {
+  return value.replace(/[.*+?^${}()|[\]\\]/g, "\\$&");
+}
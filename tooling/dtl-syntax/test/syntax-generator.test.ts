@@ -17,6 +17,59 @@ describe("dtl syntax generator", () => {
     expect(once).toContain(":引数");
   });
 
+  it("covers every top-level form, local function and tag in both vocabularies", () => {
+    const grammar = generateTextMateGrammar(DTL_SYNTAX_SPEC) as {
+      repository: Record<string, { patterns: { match: string }[] }>;
+    };
+    const keywords = grammar.repository.keyword.patterns[0].match;
+    const tags = grammar.repository.surfaceTag.patterns[0].match;
+
+    for (const keyword of [
+      "defconst",
+      "defmacro",
+      "when-profile",
+      "quality-gate",
+      "定数",
+      "マクロ",
+      "プロファイル限定",
+      "品質ゲート",
+      "letfn"
+    ]) {
+      expect(keywords).toContain(keyword);
+    }
+    for (const tag of [":key", ":キー", ":doc", ":説明", ":profile", ":プロファイル", ":summary"]) {
+      expect(tags).toContain(tag);
+    }
+    expect(grammar.repository.operator.patterns[0].match).toContain("!=");
+  });
+
+  it("generates nestable block comments, datum comments and every string form", () => {
+    const grammar = generateTextMateGrammar(DTL_SYNTAX_SPEC) as {
+      repository: Record<string, Record<string, unknown>>;
+    };
+    const repository = grammar.repository;
+
+    expect(repository.blockComment).toMatchObject({
+      name: "comment.block.dtl",
+      begin: "#\\|",
+      end: "\\|#",
+      patterns: [{ include: "#blockComment" }]
+    });
+    expect(JSON.stringify(repository.datumComment)).toContain("#;");
+
+    const strings = JSON.stringify(repository.string);
+    expect(strings).toContain("string.quoted.triple.dtl");
+    expect(strings).toContain("string.quoted.other.raw.dtl");
+    expect(strings).toContain("string.quoted.double.dtl");
+
+    const script = generateHighlightJsScript(DTL_SYNTAX_SPEC);
+    expect(script).toContain("\"blockCommentBegin\": \"#\\\\|\"");
+    expect(script).toContain("\"datumCommentList\"");
+    expect(script).toContain("\"multiLineBegin\"");
+    expect(script).toContain("\"rawBegin\"");
+    expect(script).toContain("\"on:end\"");
+  });
+
   it("generates highlight.js runtime with dtl+lisp+terminal compatibility", () => {
     const script = generateHighlightJsScript(DTL_SYNTAX_SPEC);
 